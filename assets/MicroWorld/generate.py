@@ -0,0 +1,160 @@
+# Writes r.0.0.mca, the micro world of the tests in src/model/micro_world_tests.rs.
+#
+# Four chunks of region 0, 0 in the 1.18+ format, each on the same ground of stone from y 56
+# to 62 covered by grass at y 63, with nothing below:
+#   chunk 4, 4 - only the ground
+#   chunk 5, 4 - a stone wall 1 block thick along z at x 8, z 2..13, y 64..66
+#   chunk 4, 5 - a tree, an oak log at x 8, z 8, y 64..68 with leaves around its top
+#   chunk 5, 5 - a hut of oak planks, walls around x 5..9, z 5..9 at y 64..66 with a door
+#                at x 7, z 5, y 64..65 and a roof at y 67
+# Coordinates are within the chunk. Run with python3 from this folder.
+import io
+import struct
+import zlib
+
+DATA_VERSION = 3120  # 1.19.2
+SECTIONS = range(-4, 5)
+
+
+def tag_string(value):
+    data = value.encode("utf-8")
+    return struct.pack(">H", len(data)) + data
+
+
+def named(tag_type, name, payload):
+    return bytes([tag_type]) + tag_string(name) + payload
+
+
+def compound(entries):
+    return b"".join(entries) + b"\x00"
+
+
+def int_tag(name, value):
+    return named(3, name, struct.pack(">i", value))
+
+
+def byte_tag(name, value):
+    return named(1, name, struct.pack(">b", value))
+
+
+def string_tag(name, value):
+    return named(8, name, tag_string(value))
+
+
+def compound_list(name, payloads):
+    return named(9, name, bytes([10]) + struct.pack(">i", len(payloads)) + b"".join(payloads))
+
+
+def string_list(name, values):
+    return named(9, name, bytes([8]) + struct.pack(">i", len(values)) + b"".join(map(tag_string, values)))
+
+
+def long_array(name, values):
+    signed = [value - (1 << 64) if value >= 1 << 63 else value for value in values]
+    return named(12, name, struct.pack(">i", len(signed)) + struct.pack(f">{len(signed)}q", *signed))
+
+
+def ground(blocks):
+    for x in range(16):
+        for z in range(16):
+            for y in range(56, 63):
+                blocks[(x, y, z)] = "minecraft:stone"
+            blocks[(x, 63, z)] = "minecraft:grass_block"
+
+
+def wall(blocks):
+    for z in range(2, 14):
+        for y in range(64, 67):
+            blocks[(8, y, z)] = "minecraft:stone"
+
+
+def tree(blocks):
+    for y in range(67, 69):
+        for x in range(6, 11):
+            for z in range(6, 11):
+                blocks[(x, y, z)] = "minecraft:oak_leaves"
+    for x in range(7, 10):
+        for z in range(7, 10):
+            blocks[(x, 69, z)] = "minecraft:oak_leaves"
+    for y in range(64, 69):
+        blocks[(8, y, 8)] = "minecraft:oak_log"
+
+
+def hut(blocks):
+    for x in range(5, 10):
+        for z in range(5, 10):
+            if x in (5, 9) or z in (5, 9):
+                for y in range(64, 67):
+                    blocks[(x, y, z)] = "minecraft:oak_planks"
+            blocks[(x, 67, z)] = "minecraft:oak_planks"
+    for y in range(64, 66):
+        del blocks[(7, y, 5)]
+
+
+CHUNKS = {
+    (4, 4): [ground],
+    (5, 4): [ground, wall],
+    (4, 5): [ground, tree],
+    (5, 5): [ground, hut],
+}
+
+
+def section(section_y, blocks):
+    names = [
+        blocks.get((i & 15, section_y * 16 + (i >> 8), (i >> 4) & 15), "minecraft:air")
+        for i in range(4096)
+    ]
+    palette = sorted(set(names), key=names.index)
+
+    block_states = [compound_list("palette", [compound([string_tag("Name", name)]) for name in palette])]
+    if len(palette) > 1:
+        bits = max(4, (len(palette) - 1).bit_length())
+        per_long = 64 // bits
+        longs = [0] * ((4096 + per_long - 1) // per_long)
+        for i, name in enumerate(names):
+            longs[i // per_long] |= palette.index(name) << (i % per_long * bits)
+        block_states.append(long_array("data", longs))
+
+    return compound([
+        byte_tag("Y", section_y),
+        named(10, "block_states", compound(block_states)),
+        named(10, "biomes", compound([string_list("palette", ["minecraft:plains"])])),
+    ])
+
+
+def chunk_nbt(chunk_x, chunk_z, features):
+    blocks = {}
+    for feature in features:
+        feature(blocks)
+
+    return named(10, "", compound([
+        int_tag("DataVersion", DATA_VERSION),
+        int_tag("xPos", chunk_x),
+        int_tag("zPos", chunk_z),
+        int_tag("yPos", SECTIONS.start),
+        string_tag("Status", "full"),
+        compound_list("sections", [section(y, blocks) for y in SECTIONS]),
+    ]))
+
+
+def region():
+    header = bytearray(8192)
+    body = io.BytesIO()
+    sector = 2
+    for (chunk_x, chunk_z), features in sorted(CHUNKS.items()):
+        compressed = zlib.compress(chunk_nbt(chunk_x, chunk_z, features))
+        data = struct.pack(">IB", len(compressed) + 1, 2) + compressed
+        data += bytes(-len(data) % 4096)
+
+        sectors = len(data) // 4096
+        entry = 4 * (chunk_x + chunk_z * 32)
+        header[entry:entry + 4] = struct.pack(">I", sector << 8 | sectors)
+        header[4096 + entry:4096 + entry + 4] = struct.pack(">I", 1)
+        body.write(data)
+        sector += sectors
+
+    return bytes(header) + body.getvalue()
+
+
+with open("r.0.0.mca", "wb") as file:
+    file.write(region())
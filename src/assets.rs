@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config;
+
+// Assets the application can't start without are compiled in, so the binary runs from any folder.
+// A file with the same name in one of the asset folders replaces the compiled in copy.
+// The shaders are compiled in where they are used.
+const EMBEDDED: [(&str, &[u8]); 1] = [(
+    "block-palette.png",
+    include_bytes!("../assets/block-palette.png"),
+)];
+
+// Folder given with --assets-dir, searched before the others
+static ASSETS_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn set_assets_dir(folder: PathBuf) {
+    *ASSETS_DIR.lock().unwrap() = Some(folder);
+}
+
+// Folders searched for assets, in order: the --assets-dir folder,
+// then config::ASSETS_PATH in the working directory and next to the executable
+pub fn search_path() -> Vec<PathBuf> {
+    let mut folders = Vec::new();
+    folders.extend(ASSETS_DIR.lock().unwrap().clone());
+    folders.push(PathBuf::from(config::ASSETS_PATH));
+
+    let executable_folder = std::env::current_exe()
+        .ok()
+        .and_then(|executable| executable.parent().map(Path::to_path_buf));
+    if let Some(folder) = executable_folder {
+        folders.push(folder.join(config::ASSETS_PATH));
+    }
+
+    folders
+}
+
+// First file at *relative* in the asset folders
+pub fn find(relative: impl AsRef<Path>) -> Option<PathBuf> {
+    search_path()
+        .into_iter()
+        .map(|folder| folder.join(relative.as_ref()))
+        .find(|path| path.is_file())
+}
+
+// Contents of the asset *name*, the compiled in copy when none of the asset folders has it
+pub fn read(name: &str) -> io::Result<Cow<'static, [u8]>> {
+    if let Some(path) = find(name) {
+        return fs::read(path).map(Cow::Owned);
+    }
+
+    EMBEDDED
+        .iter()
+        .find(|(embedded_name, _)| *embedded_name == name)
+        .map(|(_, data)| Cow::Borrowed(*data))
+        .ok_or_else(|| {
+            let message = format!("{name} is not in any of {:?}", search_path());
+            io::Error::new(io::ErrorKind::NotFound, message)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_assets_decode() {
+        for (name, data) in EMBEDDED {
+            assert!(
+                image::load_from_memory(data).is_ok(),
+                "{name} doesn't decode"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_asset_is_an_error() {
+        let error = read("no-such-asset.png").unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use crate::infrastructure::event_bus::{AppEvent, EventSubscriber, FrameContext};
+use crate::minecraft::get_minecraft_chunk_position;
+use crate::model::chunk::ChunkPosition;
+use crate::model::common::BLOCK_TYPES;
+
+pub type MaterialCounts = [usize; BLOCK_TYPES];
+
+// Number of blocks of each material in the loaded chunks, shown in the composition window.
+//
+// The blocks of a chunk are counted once when it is loaded and again when blocks in it are edited,
+// the totals are summed from the per chunk counts.
+pub struct BlockComposition {
+    chunk_counts: HashMap<ChunkPosition, MaterialCounts>,
+    totals: MaterialCounts,
+
+    // Chunk whose breakdown is shown next to the totals
+    pub selected_chunk: Option<ChunkPosition>,
+}
+
+impl BlockComposition {
+    pub fn new() -> Self {
+        BlockComposition {
+            chunk_counts: HashMap::new(),
+            totals: [0; BLOCK_TYPES],
+            selected_chunk: None,
+        }
+    }
+
+    pub fn totals(&self) -> &MaterialCounts {
+        &self.totals
+    }
+
+    pub fn chunk_counts(&self, chunk_position: ChunkPosition) -> Option<&MaterialCounts> {
+        self.chunk_counts.get(&chunk_position)
+    }
+
+    // Loaded chunks ordered by their position in the world
+    pub fn chunks(&self) -> Vec<ChunkPosition> {
+        let mut chunks = self.chunk_counts.keys().copied().collect::<Vec<_>>();
+        chunks.sort_by_key(|chunk_position| {
+            let (x, z) = chunk_position.get_global_position_in_chunks();
+            (z, x)
+        });
+
+        chunks
+    }
+}
+
+impl EventSubscriber for BlockComposition {
+    fn handle_events(&mut self, events: &[AppEvent], context: &FrameContext) {
+        let world = context.world;
+        let mut changed = false;
+        for event in events {
+            let chunk_position = match event {
+                AppEvent::ChunkLoaded(chunk_position) => *chunk_position,
+                AppEvent::BlockEdited(position, _) => get_minecraft_chunk_position(*position),
+                _ => continue,
+            };
+
+            if let Some(counts) = world.count_chunk_materials(chunk_position) {
+                self.chunk_counts.insert(chunk_position, counts);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return;
+        }
+
+        // Chunks that were unloaded to make room for the new ones
+        let loaded = world.get_loaded_chunk_positions();
+        self.chunk_counts
+            .retain(|chunk_position, _| loaded.contains(chunk_position));
+        if let Some(selected) = self.selected_chunk {
+            if !self.chunk_counts.contains_key(&selected) {
+                self.selected_chunk = None;
+            }
+        }
+
+        self.totals = [0; BLOCK_TYPES];
+        for counts in self.chunk_counts.values() {
+            for (total, count) in self.totals.iter_mut().zip(counts) {
+                *total += count;
+            }
+        }
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     config,
     input::Direction,
     model::{Coord, Position, Real},
-    InputAction, InputConsumer, RenderState,
+    settings, InputAction, InputConsumer, RenderState,
 };
 use cgmath::{
     perspective, Angle, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector2, Vector3,
@@ -17,7 +17,11 @@ pub struct Camera {
     pub view_to_world: Matrix4<Real>,
     pub projection: Matrix4<Real>,
     translation: Vector3<Real>,
+    // Mouse motion since the last update
     rotation: Option<Vector2<Real>>,
+    // Speeds the camera actually moves and turns at, they trail the requested ones when smoothing
+    velocity: Vector3<Real>,
+    angular_velocity: Vector2<Real>,
     fovy: Rad<Real>,
     aspect_ratio: Real,
     near_clipping_plane: Real,
@@ -44,6 +48,8 @@ impl Camera {
             projection,
             translation: Vector3::new(0., 0., 0.),
             rotation: None,
+            velocity: Vector3::new(0., 0., 0.),
+            angular_velocity: Vector2::new(0., 0.),
             fovy,
             aspect_ratio,
             near_clipping_plane,
@@ -51,24 +57,40 @@ impl Camera {
         }
     }
 
-    pub fn update(&mut self, delta_time: Real) {
+    // With *smooth_motion* the camera eases into and out of movement and rotation
+    pub fn update(&mut self, delta_time: Real, smooth_motion: bool) {
+        let delta_time = delta_time.min(config::MAX_CAMERA_DELTA_TIME);
+
+        // Only the direction of the mouse motion is used, the camera turns at a constant speed
+        let target_angular_velocity = match self.rotation.take() {
+            Some(rotation) if rotation.magnitude2() > 0.0 => {
+                rotation.normalize_to(settings::current().sensitivity)
+            }
+            _ => Vector2::new(0.0, 0.0),
+        };
+
+        let follow = match smooth_motion {
+            true => 1.0 - (-delta_time / config::CAMERA_SMOOTHING_TIME).exp(),
+            false => 1.0,
+        };
+        self.velocity += (self.translation - self.velocity) * follow;
+        self.angular_velocity += (target_angular_velocity - self.angular_velocity) * follow;
+
         let direction = self.view_to_world.z;
 
         let mut yaw: Rad<Real> = Angle::atan2(direction.z, direction.x);
         let mut pitch: Rad<Real> = Angle::asin(direction.y);
 
-        if let Some(rotation) = self.rotation.take() {
-            yaw += Rad(rotation.x * config::SENSITIVITY);
-
-            pitch += Rad(rotation.y * config::SENSITIVITY);
-            pitch = if pitch > MAX_PITCH {
-                MAX_PITCH
-            } else if pitch < MIN_PITCH {
-                MIN_PITCH
-            } else {
-                pitch
-            };
-        }
+        yaw += Rad(self.angular_velocity.x * delta_time);
+
+        pitch += Rad(self.angular_velocity.y * delta_time);
+        pitch = if pitch > MAX_PITCH {
+            MAX_PITCH
+        } else if pitch < MIN_PITCH {
+            MIN_PITCH
+        } else {
+            pitch
+        };
 
         let new_direction = Vector3::new(
             pitch.cos() * yaw.cos(),
@@ -104,13 +126,22 @@ impl Camera {
     ) -> Position {
         let position = Point3::from_homogeneous(self.view_to_world.w);
         let mut new_position = position;
-        new_position += aside * self.translation.x * delta_time;
-        new_position += up * self.translation.y * delta_time;
-        new_position += dir * self.translation.z * delta_time;
+        new_position += aside * self.velocity.x * delta_time;
+        new_position += up * self.velocity.y * delta_time;
+        new_position += dir * self.velocity.z * delta_time;
 
         new_position
     }
 
+    // Places the camera at *position* looking in *direction*, used when the camera
+    // is not driven by the user
+    pub fn look_from(&mut self, position: Position, direction: Vector3<Real>) {
+        let view = Matrix4::<Real>::look_to_rh(position, direction, Vector3::unit_y());
+
+        self.world_to_view = view;
+        self.view_to_world = view.invert().unwrap();
+    }
+
     pub fn get_position(&self) -> Position {
         return Point3::from_homogeneous(self.view_to_world.w);
     }
@@ -120,9 +151,30 @@ impl Camera {
         return -self.view_to_world.z.truncate();
     }
 
+    pub fn fovy(&self) -> Rad<Real> {
+        self.fovy
+    }
+
+    pub fn aspect_ratio(&self) -> Real {
+        self.aspect_ratio
+    }
+
     fn update_aspect(&mut self, aspect_ratio: Real) {
         self.aspect_ratio = aspect_ratio;
+        self.update_projection();
+    }
+
+    // The far plane follows the size of the loaded world, see far_clipping_plane in main.rs
+    pub fn set_far_clipping_plane(&mut self, far_clipping_plane: Real) {
+        if self.far_clipping_plane == far_clipping_plane {
+            return;
+        }
+
+        self.far_clipping_plane = far_clipping_plane;
+        self.update_projection();
+    }
 
+    fn update_projection(&mut self) {
         self.projection = perspective(
             self.fovy,
             self.aspect_ratio,
@@ -135,14 +187,17 @@ impl Camera {
 impl InputConsumer for Camera {
     fn consume(&mut self, action: &InputAction, state: &RenderState) {
         match action {
-            InputAction::BeginMove { dir } => match dir {
-                Direction::Forward => self.translation.z = -config::CAMERA_MOVE_SPEED,
-                Direction::Back => self.translation.z = config::CAMERA_MOVE_SPEED,
-                Direction::Left => self.translation.x = -config::CAMERA_MOVE_SPEED,
-                Direction::Right => self.translation.x = config::CAMERA_MOVE_SPEED,
-                Direction::Up => self.translation.y = config::CAMERA_MOVE_SPEED,
-                Direction::Down => self.translation.y = -config::CAMERA_MOVE_SPEED,
-            },
+            InputAction::BeginMove { dir } => {
+                let speed = settings::current().camera_move_speed;
+                match dir {
+                    Direction::Forward => self.translation.z = -speed,
+                    Direction::Back => self.translation.z = speed,
+                    Direction::Left => self.translation.x = -speed,
+                    Direction::Right => self.translation.x = speed,
+                    Direction::Up => self.translation.y = speed,
+                    Direction::Down => self.translation.y = -speed,
+                }
+            }
             InputAction::EndMove { dir } => match dir {
                 Direction::Forward => self.translation.z = 0.0,
                 Direction::Back => self.translation.z = 0.0,
@@ -161,11 +216,11 @@ impl InputConsumer for Camera {
             return;
         }
 
+        // All motion events of a frame are summed up, the camera turns once per update
         if let InputAction::CursorMoved { x, y } = action {
-            let rotation_direction =
-                Vector2::new(*x as Real, *y as Real).normalize_to(config::SPHERE_RADIUS);
+            let motion = Vector2::new(*x as Real, *y as Real);
 
-            self.rotation = Some(rotation_direction);
+            self.rotation = Some(self.rotation.unwrap_or(Vector2::new(0.0, 0.0)) + motion);
         }
     }
 }
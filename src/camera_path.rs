@@ -0,0 +1,78 @@
+use cgmath::{EuclideanSpace, InnerSpace, Vector3};
+
+use crate::model::{Position, Real};
+
+// Smooth path through a list of waypoints, each segment is a Catmull-Rom spline
+// so the path passes through every waypoint without sharp turns.
+pub struct CameraPath {
+    waypoints: Vec<Position>,
+    // Approximated by the distance between the segment endpoints
+    segment_lengths: Vec<Real>,
+}
+
+impl CameraPath {
+    // A path needs at least two waypoints
+    pub fn new(waypoints: Vec<Position>) -> Option<Self> {
+        if waypoints.len() < 2 {
+            return None;
+        }
+
+        let segment_lengths = waypoints
+            .windows(2)
+            .map(|segment| (segment[1] - segment[0]).magnitude())
+            .collect();
+
+        Some(CameraPath {
+            waypoints,
+            segment_lengths,
+        })
+    }
+
+    pub fn length(&self) -> Real {
+        self.segment_lengths.iter().sum()
+    }
+
+    // Position and unnormalized tangent at the given distance along the path,
+    // the distance is clamped to the ends of the path
+    pub fn sample(&self, distance: Real) -> (Position, Vector3<Real>) {
+        let mut remaining = distance.max(0.0);
+        let last_segment = self.segment_lengths.len() - 1;
+
+        let mut segment = 0;
+        while segment < last_segment && remaining > self.segment_lengths[segment] {
+            remaining -= self.segment_lengths[segment];
+            segment += 1;
+        }
+
+        let segment_length = self.segment_lengths[segment];
+        let t = match segment_length > 0.0 {
+            true => (remaining / segment_length).min(1.0),
+            false => 0.0,
+        };
+
+        // The end waypoints are repeated so the spline reaches them
+        let waypoint = |index: isize| {
+            let clamped = index.clamp(0, self.waypoints.len() as isize - 1) as usize;
+            self.waypoints[clamped].to_vec()
+        };
+        let segment = segment as isize;
+        let p0 = waypoint(segment - 1);
+        let p1 = waypoint(segment);
+        let p2 = waypoint(segment + 1);
+        let p3 = waypoint(segment + 2);
+
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let position = 0.5
+            * (2.0 * p1
+                + (p2 - p0) * t
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3);
+        let tangent = 0.5
+            * ((p2 - p0)
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * 2.0 * t
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * 3.0 * t2);
+
+        (Position::from_vec(position), tangent)
+    }
+}
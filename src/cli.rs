@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use crate::config;
+use crate::model::Position;
+
+const USAGE: &str =
+    "usage: dd-terrain [--world <path>] [--spawn <x>,<y>,<z>] [--assets-dir <path>]";
+
+// Options given on the command line, they take the place of the world dialog
+#[derive(Debug, Default)]
+pub struct CliArguments {
+    // Save folder, folder of region files or an archive of a save
+    pub world: Option<String>,
+    // Start location in block coordinates, skips picking one in the dialog
+    pub spawn: Option<Position>,
+    // Folder searched for assets before the default ones, see assets.rs
+    pub assets_dir: Option<PathBuf>,
+}
+
+impl CliArguments {
+    pub fn parse() -> Result<Self, String> {
+        let mut parsed = CliArguments::default();
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value of {arg}, {USAGE}"))
+            };
+            match arg.as_str() {
+                "--world" => parsed.world = Some(value()?),
+                "--spawn" => parsed.spawn = Some(parse_position(&value()?)?),
+                "--assets-dir" => parsed.assets_dir = Some(parse_folder(&value()?)?),
+                _ => return Err(format!("Unknown argument {arg}, {USAGE}")),
+            }
+        }
+
+        // A start location alone is taken to be in the demo world
+        if parsed.spawn.is_some() && parsed.world.is_none() {
+            parsed.world = Some(config::WORLD_FOLDER.to_owned());
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn parse_folder(value: &str) -> Result<PathBuf, String> {
+    let folder = PathBuf::from(value);
+    match folder.is_dir() {
+        true => Ok(folder),
+        false => Err(format!("Invalid --assets-dir {value} - not a folder")),
+    }
+}
+
+fn parse_position(value: &str) -> Result<Position, String> {
+    let coordinates = value
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| format!("Invalid --spawn {value} - {error}"))?;
+
+    match coordinates[..] {
+        [x, y, z] => Ok(Position::new(x, y, z)),
+        _ => Err(format!("Invalid --spawn {value} - expected x,y,z")),
+    }
+}
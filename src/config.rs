@@ -1,30 +1,208 @@
+use crate::imgui_wrapper::UiTheme;
+use crate::model::common::BlockType;
 use crate::model::{Coord, Position, Real};
 use cgmath::{Point3, Rad, Vector3};
+use std::time::Duration;
 
 pub const TITLE: &str = "dd-terrain";
 pub const ASSETS_PATH: &str = r#"assets"#;
+// Textures of single blocks inside the assets folder, see infrastructure/texture/block_textures.rs
+pub const BLOCK_TEXTURE_FOLDER: &str = r#"blocks"#;
+pub const BLOCK_TEXTURE_RESOLUTION: u32 = 16;
 pub const DYNAMIC_WORLD: bool = true;
+// Reports of panics are written into this folder, see crash_report.rs
+pub const CRASH_REPORT_FOLDER: &str = r#"crash_reports"#;
+// Background work is waited for at most this long when the application closes
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub const FOVY: Rad<Real> = Rad(std::f64::consts::FRAC_PI_2);
 pub const Z_NEAR: Real = 0.1;
+// Closest the far plane gets, it grows with the view distance, see far_clipping_plane in main.rs
 pub const Z_FAR: Real = 100.;
 
 // Village
 //pub const SPAWN_POINT: Position = Point3::new(314.09, 76.47, 288.93);
 //pub const SPAWN_DIR: Vector3<Coord> = Vector3::new(0.84, -0.41, 0.36);
 
-// Ravine
+// Ravine, default of the settings file
 pub const SPAWN_POINT: Position = Point3::new(219.0, 71.5, 208.4);
 pub const SPAWN_DIR: Vector3<Coord> = Vector3::new(-0.71, -0.41, -0.57);
 
-pub const WORLD_SIZE: usize = 10;
+// Chunks loaded in every direction from the chunk of the camera, the loaded window is twice
+// as wide. Its outer ring only provides density data to the meshed chunks.
+// This is the default of the view distance in the controls, which can be set within the limits.
+pub const CHUNK_LOAD_RADIUS: usize = 5;
+pub const MIN_CHUNK_LOAD_RADIUS: usize = 2;
+pub const MAX_CHUNK_LOAD_RADIUS: usize = 16;
+// Width of the windows the map export loads in the background, independent of the view distance
+pub const WORLD_SIZE: usize = 2 * CHUNK_LOAD_RADIUS;
 
+// Settings that can be changed while the app runs, see settings.rs.
+// The defaults of the settings are the constants marked as such in this file
+pub const SETTINGS_FILE: &str = r#"settings.toml"#;
 pub const WORLD_FOLDER: &str = r#"assets/RavineDemo"#;
+// Region files kept open between chunk loads, the loaded chunks span at most 4 regions
+pub const REGION_CACHE_SIZE: usize = 8;
+// Worlds opened from the world dialog are remembered in this file
+pub const RECENT_WORLDS_FILE: &str = r#"recent_worlds.txt"#;
+pub const RECENT_WORLDS_COUNT: usize = 8;
+// The extension of the exported smooth mesh is added by its format
+pub const SMOOTH_MESH_EXPORT_PATH: &str = r#"smooth_mesh"#;
+pub const BOUNDARY_EXPORT_PATH: &str = r#"chunk_boundaries.obj"#;
+// Every chunk gets a mesh and a splat map texture with the same name in this folder
+pub const SPLAT_MAP_EXPORT_FOLDER: &str = r#"splat_maps"#;
+pub const NAVMESH_EXPORT_PATH: &str = r#"navmesh.obj"#;
+pub const PROFILE_EXPORT_PATH: &str = r#"elevation_profile.csv"#;
+pub const WORLD_REPORT_PATH: &str = r#"world_report.json"#;
+// Units done by a cancelled world scan or map export, removed once it finishes
+pub const WORLD_SCAN_CHECKPOINT: &str = r#"world_scan.checkpoint"#;
+pub const MAP_EXPORT_CHECKPOINT: &str = r#"map_export.checkpoint"#;
+// Built chunk meshes are kept in this folder between runs, see model/mesh_cache.rs.
+// None to build every mesh again
+pub const MESH_CACHE_FOLDER: Option<&str> = Some(r#"mesh_cache"#);
 
+// Look of the UI, a TrueType font replaces the built in imgui font when set
+pub const UI_THEME: UiTheme = UiTheme::Dark;
+pub const UI_FONT_PATH: Option<&str> = None;
+pub const UI_FONT_SIZE: f32 = 15.0;
+// Positions and sizes of the UI windows are kept in this file between runs, None to not keep them
+pub const UI_LAYOUT_FILE: Option<&str> = Some(r#"ui_layout.ini"#);
+
+// Material of modded blocks whose name doesn't match any minecraft block,
+// namespaces without an entry use MODDED_BLOCK_FALLBACK.
+// These are the defaults of the material registry, they can be changed in the material legend.
+pub const NAMESPACE_FALLBACKS: [(&str, BlockType); 2] = [
+    ("biomesoplenty", BlockType::Dirt),
+    ("create", BlockType::Cobblestone),
+];
+pub const MODDED_BLOCK_FALLBACK: BlockType = BlockType::Stone;
+
+// Signs further away from the camera are not labeled
+pub const SIGN_LABEL_DISTANCE: Real = 48.0;
+
+// Points of the elevation profile are marked on the terrain at most this far from the camera
+pub const PROFILE_PICK_DISTANCE: Real = 256.0;
+// Sculpting edits the terrain where the camera looks within this distance,
+// the material added where the smooth surface has no block under it
+pub const SCULPT_REACH: Real = 64.0;
+pub const SCULPT_FALLBACK_BLOCK: BlockType = BlockType::Dirt;
+// The stats window shows the block and smooth surface point looked at within this distance,
+// marching the density is too slow every frame for the profile distance
+pub const STATS_PICK_DISTANCE: Real = 64.0;
+
+// The sun direction gizmo floats this far in front of the camera
+pub const SUN_GIZMO_DISTANCE: Real = 3.0;
+pub const SUN_GIZMO_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+
+// Outline of the cell in the cell inspector and the wireframe of its triangles
+pub const CELL_EDGE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const CELL_TRIANGLE_COLOR: [f32; 4] = [0.2, 0.9, 0.9, 1.0];
+
+// Corners of the chunk picked with a right click on the chunk grid
+pub const CHUNK_HIGHLIGHT_COLOR: [f32; 4] = [1.0, 0.3, 0.8, 1.0];
+
+// Gradient probe at the point the camera looks at, sizes in blocks
+pub const GRADIENT_PROBE_LENGTH: f32 = 1.5;
+pub const GRADIENT_PROBE_DISC_RADIUS: f32 = 0.75;
+pub const GRADIENT_PROBE_ARROW_COLOR: [f32; 4] = [0.9, 0.2, 0.2, 1.0];
+pub const GRADIENT_PROBE_DISC_COLOR: [f32; 4] = [0.2, 0.6, 1.0, 0.5];
+
+// Steepest walkable step of the path finder, in blocks of height per block travelled
+pub const PATH_MAX_SLOPE: Real = 1.0;
+// The path finder gives up after looking at this many columns
+pub const PATH_MAX_EXPANDED_COLUMNS: usize = 200_000;
+
+// Columns with at least this many columns draining through them are drawn as streams
+pub const STREAM_ACCUMULATION_THRESHOLD: u32 = 100;
+
+// Default of the settings file, as is the sensitivity
 pub const CAMERA_MOVE_SPEED: Real = 5.0;
-pub const SENSITIVITY: Real = 0.009;
-pub const SPHERE_RADIUS: Real = 5.0; // TODO: is this needed?
+// Radians per second the camera turns while the mouse moves
+pub const SENSITIVITY: Real = 2.7;
+// Longer frames, such as hitches while meshes are built, move the camera as if they took this long
+pub const MAX_CAMERA_DELTA_TIME: Real = 0.1;
+// Time the smoothed camera motion takes to get most of the way to the requested speed
+pub const CAMERA_SMOOTHING_TIME: Real = 0.08;
+
+// Triangles of the exported navigation mesh are at most this steep,
+// vertices closer than the cell size are welded into one
+pub const NAVMESH_MAX_SLOPE_DEGREES: f32 = 45.0;
+pub const NAVMESH_CELL_SIZE: f32 = 1.0;
+
+// Path traced image of the smooth mesh, see offline_render.rs
+pub const OFFLINE_RENDER_PATH: &str = "offline_render.png";
+// Folder the map export writes its image or tiles into
+pub const MAP_EXPORT_FOLDER: &str = "map_export";
+pub const OFFLINE_RENDER_WIDTH: u32 = 1920;
+pub const OFFLINE_RENDER_HEIGHT: u32 = 1080;
+pub const OFFLINE_RENDER_SAMPLES: u32 = 64;
+pub const OFFLINE_RENDER_BOUNCES: u32 = 3;
+
+// Baked lighting, see model/lightmap.rs. Every triangle gets a square of texels in the atlas,
+// the triangles of a chunk are laid out in a page and pages in rows of the atlas
+pub const LIGHTMAP_CHART_TEXELS: usize = 4;
+pub const LIGHTMAP_PAGE_WIDTH: usize = 256;
+pub const LIGHTMAP_ATLAS_WIDTH: usize = 4096;
+pub const LIGHTMAP_SKY_SAMPLES: usize = 24;
+pub const LIGHTMAP_OCCLUSION_DISTANCE: f32 = 12.0;
+// The lightmap and its material are written next to the mesh with the same name
+pub const LIGHTMAP_EXPORT_PATH: &str = "lightmapped.obj";
+
+// Texels of a triangle and width of the splat map of a chunk, laid out like the lightmaps
+pub const SPLAT_MAP_CHART_TEXELS: usize = 4;
+pub const SPLAT_MAP_PAGE_WIDTH: usize = 256;
+
+// Material dithering picks the same materials on every run with the same seed
+pub const MATERIAL_DITHER_SEED: u32 = 0x5eed;
+
+// Opacity of rigid glass blocks, see infrastructure/translucency.rs
+pub const TRANSLUCENT_BLOCK_OPACITY: f32 = 0.4;
+
+// Opacity of rigid water looked at from above, it grows towards grazing angles, see shaders/water_fs.glsl
+pub const WATER_OPACITY: f32 = 0.6;
+
+// Distances from the camera the shadow cascades reach, the last one covers the view up to Z_FAR,
+// terrain beyond it is drawn without shadows.
+// See infrastructure/shadow_map.rs
+pub const SHADOW_CASCADE_ENDS: [f32; 3] = [12.0, 36.0, 100.0];
+pub const SHADOW_MAP_RESOLUTION: u32 = 2048;
+// How far towards the sun terrain outside a cascade still casts shadows into it
+pub const SHADOW_CASTER_DISTANCE: Real = 256.0;
+
+// Default fog densities per block of distance. The surface fog fades to the background,
+// the cave fog fades to darkness where neither the sky nor block light reach
+pub const SURFACE_FOG_DENSITY: f32 = 0.004;
+pub const CAVE_FOG_DENSITY: f32 = 0.12;
+
+// Chunks rebuilt with new options fade from the old mesh to the new one for this long
+pub const MESH_CROSSFADE_SECONDS: f32 = 0.3;
+
+// The chunk mesh buffers are allocated this many times larger than the meshes,
+// so rebuilt chunks can be written into the same buffers, see infrastructure/mesh_buffers.rs
+pub const MESH_BUFFER_HEADROOM: f64 = 1.5;
+
+// Heights are exaggerated around this y close to the sea level, see infrastructure/vertical_scale.rs
+pub const VERTICAL_SCALE_PIVOT: Real = 64.0;
+
+// Scripts are stopped after this many operations, so a runaway loop can't freeze the viewer
+pub const SCRIPT_MAX_OPERATIONS: u64 = 50_000_000;
+
+// Spectator tour
+pub const TOUR_SPEED: Real = 8.0;
+pub const TOUR_ALTITUDE: Real = 12.0;
+pub const TOUR_WAYPOINTS: usize = 6;
 
+// Defaults of the settings file
 pub const MULTIPASS: bool = true;
 pub const LOCK_LEAVES: bool = true;
 pub const FILTER_RIGID: bool = true;
+pub const BUILD_OCTREE: bool = true;
+// Caps the chunks meshed in every direction from the chunk of the camera, None meshes all chunks
+// within the view distance. The outer ring of the loaded window is never meshed
+// as the density of a mesh needs the chunks around it.
+pub const MESH_RADIUS: Option<usize> = None;
+// Threads of the world scan and the map export, as many as there are cores for 0
+pub const SCAN_WORKERS: usize = 0;
+// Threads running background jobs, see infrastructure/jobs.rs. The jobs spread their work over
+// the rayon pool, the workers only decide which job runs next.
+pub const JOB_WORKERS: usize = 4;
@@ -0,0 +1,143 @@
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cgmath::Vector3;
+
+use crate::config;
+use crate::infrastructure::RenderingMode;
+use crate::minecraft;
+use crate::model::chunk::ChunkPosition;
+use crate::model::polygonize::PolygonizationOptions;
+use crate::model::{Coord, Position};
+use crate::settings;
+
+// What the application was doing, updated every frame for the crash report
+#[derive(Clone)]
+pub struct CrashContext {
+    pub options: PolygonizationOptions,
+    pub render_mode: RenderingMode,
+    // In world space
+    pub camera_position: Position,
+    pub camera_direction: Vector3<Coord>,
+    pub loaded_chunks: Vec<ChunkPosition>,
+}
+
+static CONTEXT: Mutex<Option<CrashContext>> = Mutex::new(None);
+
+pub fn update_context(context: CrashContext) {
+    *CONTEXT.lock().unwrap() = Some(context);
+}
+
+// Writes a report of every panic into config::CRASH_REPORT_FOLDER after the default message is printed.
+// A panic of the main thread ends the application, it is also shown in a message box.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let report = crash_report(info);
+        let path = match write_report(&report) {
+            Ok(path) => {
+                eprintln!("[ERROR] Crash report written to {}", path.display());
+                path.display().to_string()
+            }
+            Err(error) => {
+                eprintln!("[ERROR] Failed to write the crash report - {error}");
+                String::from("nowhere, it failed to be written")
+            }
+        };
+
+        if thread::current().name() == Some("main") {
+            rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Error)
+                .set_title(config::TITLE)
+                .set_description(&format!(
+                    "The application crashed - {}\n\nThe crash report was written to {path}, \
+                     please attach it to the bug report.",
+                    panic_message(info)
+                ))
+                .set_buttons(rfd::MessageButtons::Ok)
+                .show();
+        }
+    }));
+}
+
+fn crash_report(info: &PanicHookInfo) -> String {
+    let mut lines = vec![
+        format!("{} crash report", config::TITLE),
+        format!("Thread: {}", thread::current().name().unwrap_or("unnamed")),
+        format!("Panic: {}", panic_message(info)),
+    ];
+    if let Some(location) = info.location() {
+        lines.push(format!("Location: {location}"));
+    }
+
+    // A panic while the world, the settings or the context were being updated leaves them locked,
+    // the report would wait for them forever
+    match minecraft::try_world_folder() {
+        Some(folder) => lines.push(format!("World: {}", folder.display())),
+        None => lines.push(String::from("World: unknown, it was locked")),
+    }
+    match settings::try_current() {
+        Some(settings) => lines.push(format!("Settings: {settings:?}")),
+        None => lines.push(String::from("Settings: unknown, they were locked")),
+    }
+
+    let context = CONTEXT.try_lock().ok().and_then(|context| context.clone());
+    match context {
+        Some(context) => {
+            let position = context.camera_position;
+            let direction = context.camera_direction;
+            lines.push(format!("Options: {:?}", context.options));
+            lines.push(format!("Render mode: {:?}", context.render_mode));
+            lines.push(format!(
+                "Camera position: {:.2} {:.2} {:.2}",
+                position.x, position.y, position.z
+            ));
+            lines.push(format!(
+                "Camera direction: {:.2} {:.2} {:.2}",
+                direction.x, direction.y, direction.z
+            ));
+            lines.push(String::from("Loaded chunks:"));
+            lines.extend(context.loaded_chunks.iter().map(|chunk| {
+                let (chunk_x, chunk_z) = chunk.get_global_position_in_chunks();
+                format!("    {chunk_x} {chunk_z}")
+            }));
+        }
+        None => lines.push(String::from("The world wasn't shown yet")),
+    }
+
+    lines.push(format!("\nBacktrace:\n{}", Backtrace::force_capture()));
+    lines.join("\n")
+}
+
+// The payload of a panic is the formatted message, or the string literal it was given
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    match payload.downcast_ref::<String>() {
+        Some(message) => message.clone(),
+        None => payload
+            .downcast_ref::<&str>()
+            .map_or("unknown panic", |message| message)
+            .to_owned(),
+    }
+}
+
+// Reports are named by the seconds since the unix epoch, so they sort by time
+fn write_report(report: &str) -> std::io::Result<PathBuf> {
+    let folder = PathBuf::from(config::CRASH_REPORT_FOLDER);
+    fs::create_dir_all(&folder)?;
+
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = folder.join(format!("crash_{seconds}.txt"));
+    fs::write(&path, report)?;
+
+    Ok(path)
+}
@@ -1,9 +1,7 @@
-use crate::infrastructure::vertex::{TexturedVertex, Vertex};
-use crate::model::common::BLOCK_TEXTURE_FRACTION;
+use crate::infrastructure::vertex::{TexturedVertex, Vertex, Vertex2D};
 use glium::{index::PrimitiveType, Display, IndexBuffer, VertexBuffer};
 
 // colorful unit cube, each face has exclusive vertexes
-#[allow(dead_code)]
 pub fn cube_color_exclusive_vertex(display: &Display) -> (VertexBuffer<Vertex>, IndexBuffer<u32>) {
     // front face
     let color_red = [1.0, 0.0, 0.0];
@@ -190,7 +188,8 @@ pub fn cube_color_exclusive_vertex(display: &Display) -> (VertexBuffer<Vertex>,
     (vertex_buffer, index_buffer)
 }
 
-const TEXTURE_END: f32 = BLOCK_TEXTURE_FRACTION;
+// Every face covers the whole texture of the block
+const TEXTURE_END: f32 = 1.0;
 pub fn cube_textured_exclusive_vertex(
     display: &Display,
 ) -> (VertexBuffer<TexturedVertex>, IndexBuffer<u32>) {
@@ -370,3 +369,169 @@ pub fn cube_textured_exclusive_vertex(
 
     (vertex_buffer, index_buffer)
 }
+
+// Two triangles covering the whole screen in normalized device coordinates
+pub fn fullscreen_quad(display: &Display) -> (VertexBuffer<Vertex2D>, IndexBuffer<u32>) {
+    let shape = [
+        Vertex2D {
+            position: [-1.0, -1.0],
+        },
+        Vertex2D {
+            position: [1.0, -1.0],
+        },
+        Vertex2D {
+            position: [-1.0, 1.0],
+        },
+        Vertex2D {
+            position: [1.0, 1.0],
+        },
+    ];
+    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+
+    let indices = vec![0, 1, 2, 2, 1, 3];
+    let index_buffer =
+        glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices).unwrap();
+
+    (vertex_buffer, index_buffer)
+}
+
+// Unit sphere around the origin, made of *rings* stacked from the bottom pole to the top one
+pub fn sphere(
+    display: &Display,
+    rings: u32,
+    segments: u32,
+) -> (VertexBuffer<Vertex>, IndexBuffer<u32>) {
+    let mut shape = Vec::new();
+    for ring in 0..=rings {
+        let polar = std::f32::consts::PI * ring as f32 / rings as f32;
+        for segment in 0..=segments {
+            let azimuth = std::f32::consts::TAU * segment as f32 / segments as f32;
+            let position = [
+                polar.sin() * azimuth.cos(),
+                -polar.cos(),
+                polar.sin() * azimuth.sin(),
+            ];
+            shape.push(Vertex {
+                position,
+                color: [1.0, 1.0, 1.0],
+                normal: position,
+            });
+        }
+    }
+    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+
+    let indices = grid_indices(rings, segments);
+    let index_buffer =
+        glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices).unwrap();
+
+    (vertex_buffer, index_buffer)
+}
+
+// Arrow of unit length pointing from the origin up the y axis,
+// a thin shaft with a cone on top
+pub fn arrow(display: &Display, segments: u32) -> (VertexBuffer<Vertex>, IndexBuffer<u32>) {
+    const SHAFT_RADIUS: f32 = 0.03;
+    const HEAD_RADIUS: f32 = 0.08;
+    const HEAD_START: f32 = 0.75;
+
+    // Rings of the shaft and the cone, the cone has an own ring at its base for its normals
+    let cone_slope = HEAD_RADIUS / (1.0 - HEAD_START);
+    let rings = [
+        (SHAFT_RADIUS, 0.0, 0.0),
+        (SHAFT_RADIUS, HEAD_START, 0.0),
+        (HEAD_RADIUS, HEAD_START, cone_slope),
+        (0.0, 1.0, cone_slope),
+    ];
+
+    let mut shape = Vec::new();
+    for (radius, height, slope) in rings {
+        for segment in 0..=segments {
+            let azimuth = std::f32::consts::TAU * segment as f32 / segments as f32;
+            let (sin, cos) = azimuth.sin_cos();
+            let normal_length = (1.0 + slope * slope).sqrt();
+            shape.push(Vertex {
+                position: [radius * cos, height, radius * sin],
+                color: [1.0, 1.0, 1.0],
+                normal: [
+                    cos / normal_length,
+                    slope / normal_length,
+                    sin / normal_length,
+                ],
+            });
+        }
+    }
+    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+
+    // The rings between the shaft and the cone are not connected,
+    // the base of the cone is left open
+    let ring_size = segments + 1;
+    let mut indices = grid_indices(1, segments);
+    indices.extend(
+        grid_indices(1, segments)
+            .into_iter()
+            .map(|index| index + 2 * ring_size),
+    );
+    let index_buffer =
+        glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices).unwrap();
+
+    (vertex_buffer, index_buffer)
+}
+
+// Disc of unit radius around the origin facing up the y axis
+pub fn disc(display: &Display, segments: u32) -> (VertexBuffer<Vertex>, IndexBuffer<u32>) {
+    let center = Vertex {
+        position: [0.0, 0.0, 0.0],
+        color: [1.0, 1.0, 1.0],
+        normal: [0.0, 1.0, 0.0],
+    };
+    let rim = (0..segments).map(|segment| {
+        let azimuth = std::f32::consts::TAU * segment as f32 / segments as f32;
+        Vertex {
+            position: [azimuth.cos(), 0.0, azimuth.sin()],
+            ..center
+        }
+    });
+    let shape = std::iter::once(center).chain(rim).collect::<Vec<_>>();
+    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+
+    let indices = (0..segments)
+        .flat_map(|segment| [0, segment + 1, (segment + 1) % segments + 1])
+        .collect::<Vec<_>>();
+    let index_buffer =
+        glium::IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices).unwrap();
+
+    (vertex_buffer, index_buffer)
+}
+
+// Line of unit length from the origin up the y axis
+pub fn line_segment(display: &Display) -> (VertexBuffer<Vertex>, IndexBuffer<u32>) {
+    let shape = [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0]].map(|position| Vertex {
+        position,
+        color: [1.0, 1.0, 1.0],
+        // Lines are not shaded
+        normal: [0.0, 0.0, 0.0],
+    });
+    let vertex_buffer = glium::VertexBuffer::new(display, &shape).unwrap();
+
+    let index_buffer = glium::IndexBuffer::new(display, PrimitiveType::LinesList, &[0, 1]).unwrap();
+
+    (vertex_buffer, index_buffer)
+}
+
+// Triangles between consecutive rings of *segments* + 1 vertices each
+fn grid_indices(rings: u32, segments: u32) -> Vec<u32> {
+    let ring_size = segments + 1;
+    (0..rings)
+        .flat_map(|ring| (0..segments).map(move |segment| ring * ring_size + segment))
+        .flat_map(|corner| {
+            [
+                corner,
+                corner + ring_size,
+                corner + 1,
+                corner + 1,
+                corner + ring_size,
+                corner + ring_size + 1,
+            ]
+        })
+        .collect()
+}
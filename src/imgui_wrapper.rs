@@ -1,26 +1,349 @@
-use glium::glutin::event::Event;
+use glium::glutin::event::{Event, WindowEvent};
 use glium::glutin::window::Window;
+use glium::texture::Texture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior};
 use glium::Frame;
 use imgui_glium_renderer::RendererError;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::time::Duration;
 
+use crate::assets;
+use crate::config;
+use crate::infrastructure::color_ramp::ColorRamp;
+use crate::infrastructure::RenderCommand;
+use crate::model::chunk::ChunkPosition;
+use crate::model::common::BLOCK_TYPES;
+use crate::model::export::MeshFormat;
+use crate::model::polygonize::{cell_size_for_level, kernel_size_for_level, PolygonizationMethod};
+
 #[derive(Clone, Copy)]
 pub struct SmoothMeshOptions {
+    pub polygonization_method: PolygonizationMethod,
+    // Presets of the kernel and cell size, changing a level sets the size to its preset
     pub smoothness_level: u8,
     pub mesh_resolution_level: u8,
+    // The sizes the meshes are built with, they can also be entered exactly
+    pub kernel_size: f32,
+    pub cell_size: f32,
     pub y_low_limit: isize,
     pub y_size: usize,
+    pub surface_level: f32,
+    pub density_bias: f32,
+    pub density_gain: f32,
+    pub distance_lod: bool,
+    pub skirts: bool,
+    pub caps: bool,
+    pub octree_density: bool,
+    pub density_cache: bool,
+    pub ambient_occlusion: bool,
     pub apply: bool,
+    // Apply the options on their own once they stop changing
+    pub auto_apply: bool,
+    // Comparison view, applied immediately
+    pub comparison_swipe: f32,
+    pub comparison_difference: bool,
+    // Navigation overlays, applied immediately
+    pub show_grid: bool,
+    pub grid_on_terrain: bool,
+    pub grid_plane_y: f32,
+    pub show_compass: bool,
+    pub show_signs: bool,
+    pub show_build_cost: bool,
+    // Walls between the generated chunks and the ones that were never generated
+    pub show_generated_boundary: bool,
+    // Marching cubes cell picked with V, taken apart in the cell inspector window
+    pub show_cell_inspector: bool,
+    // Move the inspected cell by this many cells along x, y and z
+    pub cell_step: Option<[i32; 3]>,
+    // Arrow towards the sun in front of the camera
+    pub show_sun_gizmo: bool,
+    // Density gradient and isosurface tangent where the camera looks at the terrain
+    pub show_gradient_probe: bool,
+    // Colors of the heatmaps
+    pub color_ramp: ColorRamp,
+    pub show_material_legend: bool,
+    // Rigid blocks of the unchecked materials are not drawn
+    pub rigid_materials_shown: [bool; BLOCK_TYPES],
+    pub show_block_composition: bool,
+    pub show_elevation_profile: bool,
+    pub show_path_finder: bool,
+    pub path_max_slope: f32,
+    pub show_streams: bool,
+    pub show_watersheds: bool,
+    pub stream_threshold: u32,
+    // Set when the pass of a material was edited in the legend, the meshes are rebuilt right away
+    pub materials_changed: bool,
+    // Set when only activation thresholds were edited, the meshes are recolored without a rebuild
+    pub materials_recolored: bool,
+    // Pick a single material per texel instead of mixing the textures of the blend
+    pub material_dithering: bool,
+    // Weighted blended transparency instead of sorting the translucent blocks
+    pub weighted_oit: bool,
+    // Shadows of the terrain from the sun, see infrastructure/shadow_map.rs
+    pub shadows: bool,
+    // Fog on the surface and in places the sky doesn't reach, see the fog in the shaders
+    pub surface_fog_density: f32,
+    pub cave_fog_density: f32,
+    // Fade rebuilt chunks in over their old meshes
+    pub mesh_crossfade: bool,
+    // Heights of the drawn world are multiplied by this, applied immediately
+    pub vertical_scale: f32,
+    // Chunks loaded in every direction from the camera, the loaded window is resized to match
+    pub load_radius: usize,
+    pub smooth_camera: bool,
+    pub reset_frame_stats: bool,
+    // Render setting switched in the stats window, handed to the render state at the next frame
+    pub render_command: Option<RenderCommand>,
+    pub quality_governor: bool,
+    // Size of the UI relative to the scale factor of the monitor
+    pub ui_scale: f32,
+    // Show the world dialog to switch to another world
+    pub open_world_dialog: bool,
+    // Path trace the smooth mesh from the current view into an image
+    pub offline_render_requested: bool,
+    // Bake the lighting of the smooth mesh into lightmaps
+    pub lightmap_bake_requested: bool,
+    // Use the baked lightmaps in the implicit shader once there are any
+    pub baked_lighting: bool,
+    // Chunk picked with a right click on the chunk grid and what was requested for it
+    pub inspected_chunk: Option<ChunkPosition>,
+    pub chunk_command: Option<ChunkCommand>,
+    // Write the smooth mesh into a file of the format
+    pub mesh_export_requested: Option<MeshFormat>,
+    // Left click adds material around the point looked at, right click carves it
+    pub sculpt: bool,
+    pub sculpt_radius: f32,
+    // Top-down map of the smooth terrain, of the loaded chunks or tiled over the whole world
+    pub map_export_requested: bool,
+    pub map_meters_per_pixel: f32,
+    pub map_contours: bool,
+    pub map_contour_interval: f32,
+    pub map_whole_world: bool,
+    pub map_web_tiles: bool,
+    pub map_export_command: Option<ScanCommand>,
+    // Scan every region of the world and write a report
+    pub world_scan_requested: bool,
+    pub world_scan_command: Option<ScanCommand>,
+    pub show_world_report: bool,
 }
 
 impl Default for SmoothMeshOptions {
     fn default() -> Self {
         SmoothMeshOptions {
+            polygonization_method: PolygonizationMethod::MarchingCubes,
             smoothness_level: 2,
             mesh_resolution_level: 1,
+            kernel_size: kernel_size_for_level(2) as f32,
+            cell_size: cell_size_for_level(1) as f32,
             y_low_limit: 40,
             y_size: 40,
+            surface_level: 0.0,
+            density_bias: 0.5,
+            density_gain: 0.5,
+            distance_lod: false,
+            skirts: false,
+            caps: false,
+            octree_density: false,
+            density_cache: false,
+            ambient_occlusion: false,
             apply: false,
+            auto_apply: false,
+            comparison_swipe: 0.5,
+            comparison_difference: false,
+            show_grid: false,
+            grid_on_terrain: true,
+            grid_plane_y: 64.0,
+            show_compass: true,
+            show_signs: true,
+            show_build_cost: false,
+            show_generated_boundary: true,
+            show_cell_inspector: false,
+            cell_step: None,
+            show_sun_gizmo: false,
+            show_gradient_probe: false,
+            color_ramp: ColorRamp::Heat,
+            show_material_legend: false,
+            rigid_materials_shown: [true; BLOCK_TYPES],
+            show_block_composition: false,
+            show_elevation_profile: false,
+            show_path_finder: false,
+            path_max_slope: config::PATH_MAX_SLOPE as f32,
+            show_streams: false,
+            show_watersheds: false,
+            stream_threshold: config::STREAM_ACCUMULATION_THRESHOLD,
+            materials_changed: false,
+            materials_recolored: false,
+            material_dithering: false,
+            weighted_oit: false,
+            shadows: true,
+            surface_fog_density: config::SURFACE_FOG_DENSITY,
+            cave_fog_density: config::CAVE_FOG_DENSITY,
+            mesh_crossfade: true,
+            vertical_scale: 1.0,
+            load_radius: config::CHUNK_LOAD_RADIUS,
+            smooth_camera: false,
+            reset_frame_stats: false,
+            render_command: None,
+            quality_governor: false,
+            ui_scale: 1.0,
+            open_world_dialog: false,
+            offline_render_requested: false,
+            lightmap_bake_requested: false,
+            baked_lighting: true,
+            inspected_chunk: None,
+            chunk_command: None,
+            mesh_export_requested: None,
+            sculpt: false,
+            sculpt_radius: 3.0,
+            map_export_requested: false,
+            map_meters_per_pixel: 0.5,
+            map_contours: true,
+            map_contour_interval: 4.0,
+            map_whole_world: false,
+            map_web_tiles: false,
+            map_export_command: None,
+            world_scan_requested: false,
+            world_scan_command: None,
+            show_world_report: false,
+        }
+    }
+}
+
+impl SmoothMeshOptions {
+    pub fn set_smoothness_level(&mut self, level: u8) {
+        self.smoothness_level = level;
+        self.kernel_size = kernel_size_for_level(level) as f32;
+    }
+
+    pub fn set_mesh_detail_level(&mut self, level: u8) {
+        self.mesh_resolution_level = level;
+        self.cell_size = cell_size_for_level(level) as f32;
+    }
+
+    // Height of the chunk grid plane, None when the grid follows the terrain
+    pub fn grid_plane(&self) -> Option<f64> {
+        match self.grid_on_terrain {
+            true => None,
+            false => Some(self.grid_plane_y as f64),
+        }
+    }
+}
+
+// Debugging actions on a single chunk, requested from the chunk window
+#[derive(Clone, Copy, Debug)]
+pub enum ChunkCommand {
+    ReloadFromDisk(ChunkPosition),
+    RebuildMesh(ChunkPosition),
+}
+
+// Control of a world scan or map export running in the background, requested from its window
+#[derive(Clone, Copy, Debug)]
+pub enum ScanCommand {
+    Pause,
+    Resume,
+    Cancel,
+}
+
+// Size of the default imgui font at a scale factor of 1
+const BASE_FONT_SIZE: f32 = 13.0;
+
+#[derive(Clone, Copy, Debug)]
+pub enum UiTheme {
+    Dark,
+    Light,
+    Classic,
+    // White text on black with yellow highlights and borders around every widget
+    HighContrast,
+    // The dark theme with its colors replaced, as RGBA in [0, 1]
+    Custom {
+        window: [f32; 4],
+        text: [f32; 4],
+        accent: [f32; 4],
+    },
+}
+
+impl UiTheme {
+    fn apply(&self, style: &mut imgui::Style) {
+        match *self {
+            UiTheme::Dark => {
+                style.use_dark_colors();
+            }
+            UiTheme::Light => {
+                style.use_light_colors();
+            }
+            UiTheme::Classic => {
+                style.use_classic_colors();
+            }
+            UiTheme::HighContrast => {
+                const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+                const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+                const YELLOW: [f32; 4] = [1.0, 0.85, 0.0, 1.0];
+                const DARK_YELLOW: [f32; 4] = [0.45, 0.38, 0.0, 1.0];
+
+                style.use_dark_colors();
+                style.window_border_size = 2.0;
+                style.frame_border_size = 1.0;
+                style.popup_border_size = 1.0;
+                for color in [
+                    imgui::StyleColor::WindowBg,
+                    imgui::StyleColor::ChildBg,
+                    imgui::StyleColor::PopupBg,
+                    imgui::StyleColor::FrameBg,
+                    imgui::StyleColor::TitleBg,
+                    imgui::StyleColor::MenuBarBg,
+                ] {
+                    style[color] = BLACK;
+                }
+                for color in [
+                    imgui::StyleColor::Text,
+                    imgui::StyleColor::Border,
+                    imgui::StyleColor::Separator,
+                ] {
+                    style[color] = WHITE;
+                }
+                for color in [
+                    imgui::StyleColor::TitleBgActive,
+                    imgui::StyleColor::Button,
+                    imgui::StyleColor::Header,
+                    imgui::StyleColor::FrameBgHovered,
+                ] {
+                    style[color] = DARK_YELLOW;
+                }
+                for color in [
+                    imgui::StyleColor::ButtonHovered,
+                    imgui::StyleColor::HeaderHovered,
+                    imgui::StyleColor::SliderGrab,
+                    imgui::StyleColor::CheckMark,
+                    imgui::StyleColor::PlotHistogram,
+                    imgui::StyleColor::PlotLines,
+                ] {
+                    style[color] = YELLOW;
+                }
+                style[imgui::StyleColor::TextDisabled] = [0.75, 0.75, 0.75, 1.0];
+            }
+            UiTheme::Custom {
+                window,
+                text,
+                accent,
+            } => {
+                style.use_dark_colors();
+                style[imgui::StyleColor::WindowBg] = window;
+                style[imgui::StyleColor::ChildBg] = window;
+                style[imgui::StyleColor::PopupBg] = window;
+                style[imgui::StyleColor::Text] = text;
+                for color in [
+                    imgui::StyleColor::TitleBgActive,
+                    imgui::StyleColor::Button,
+                    imgui::StyleColor::Header,
+                    imgui::StyleColor::SliderGrab,
+                    imgui::StyleColor::CheckMark,
+                    imgui::StyleColor::PlotHistogram,
+                ] {
+                    style[color] = accent;
+                }
+            }
         }
     }
 }
@@ -32,12 +355,20 @@ pub struct ImguiWrapper {
     platform: imgui_winit_support::WinitPlatform,
     renderer: imgui_glium_renderer::Renderer,
     window_builders: Vec<UIWindowBuilder>,
+    // Style before scaling, sizes are scaled from it whenever the UI scale changes
+    base_style: imgui::Style,
+    // Scale factor of the monitor and UI scale the font atlas was built for
+    font_scale: (f64, f32),
+    // Contents of config::UI_FONT_PATH, None for the built in font
+    font_data: Option<Vec<u8>>,
+    ui_scale: f32,
 }
 
 impl ImguiWrapper {
     pub fn new(display: &glium::Display) -> Self {
         let mut imgui_context = imgui::Context::create();
-        imgui_context.set_ini_filename(None);
+        imgui_context.set_ini_filename(config::UI_LAYOUT_FILE.map(PathBuf::from));
+        config::UI_THEME.apply(imgui_context.style_mut());
 
         let mut winit_platform = imgui_winit_support::WinitPlatform::init(&mut imgui_context);
 
@@ -48,9 +379,21 @@ impl ImguiWrapper {
 
         winit_platform.attach_window(imgui_context.io_mut(), window, dpi_mode);
 
-        imgui_context
-            .fonts()
-            .add_font(&[imgui::FontSource::DefaultFontData { config: None }]);
+        // Relative font paths are looked up in the asset folders first, see assets.rs
+        let font_data = config::UI_FONT_PATH.and_then(|path| {
+            let font_path = assets::find(path).unwrap_or_else(|| PathBuf::from(path));
+            match fs::read(font_path) {
+                Ok(data) => Some(data),
+                Err(error) => {
+                    eprintln!("[ERROR] Failed to load the UI font {path} - {error}");
+                    None
+                }
+            }
+        });
+
+        let hidpi_factor = winit_platform.hidpi_factor();
+        add_font(&mut imgui_context, font_data.as_deref(), hidpi_factor, 1.0);
+        let base_style = *imgui_context.style();
 
         let imgui_renderer = imgui_glium_renderer::Renderer::init(&mut imgui_context, display)
             .expect("failed to init imgui renderer");
@@ -60,10 +403,61 @@ impl ImguiWrapper {
             platform: winit_platform,
             renderer: imgui_renderer,
             window_builders: Vec::new(),
+            base_style,
+            font_scale: (hidpi_factor, 1.0),
+            font_data,
+            ui_scale: 1.0,
         }
     }
 
+    // Makes *texture* available to imgui::Image, sampled without filtering like the block textures
+    pub fn register_texture(&mut self, texture: Texture2d) -> imgui::TextureId {
+        let texture = imgui_glium_renderer::Texture {
+            texture: Rc::new(texture),
+            sampler: SamplerBehavior {
+                magnify_filter: MagnifySamplerFilter::Nearest,
+                minify_filter: MinifySamplerFilter::Nearest,
+                ..Default::default()
+            },
+        };
+
+        self.renderer.textures().insert(texture)
+    }
+
+    // Scale of the UI on top of the scale factor of the monitor, applied from the next frame
+    pub fn set_ui_scale(&mut self, ui_scale: f32) {
+        self.ui_scale = ui_scale;
+    }
+
+    // The font atlas is rasterized for the scale factor of the monitor, so the text stays sharp
+    // on high DPI displays, and has to be rebuilt when the window moves to another monitor
+    fn update_scale(&mut self) {
+        let scale = (self.platform.hidpi_factor(), self.ui_scale);
+        if scale == self.font_scale {
+            return;
+        }
+        let (hidpi_factor, ui_scale) = scale;
+
+        self.context.fonts().clear();
+        add_font(
+            &mut self.context,
+            self.font_data.as_deref(),
+            hidpi_factor,
+            ui_scale,
+        );
+        if let Err(error) = self.renderer.reload_font_texture(&mut self.context) {
+            eprintln!("[ERROR] Failed to rebuild the UI font - {error:?}");
+        }
+
+        let mut style = self.base_style;
+        style.scale_all_sizes(ui_scale);
+        *self.context.style_mut() = style;
+
+        self.font_scale = scale;
+    }
+
     pub fn prepare(&mut self, window: &Window, delta_time: Duration) {
+        self.update_scale();
         self.context.io_mut().update_delta_time(delta_time);
 
         self.platform
@@ -93,8 +487,70 @@ impl ImguiWrapper {
         self.renderer.render(target, draw_data)
     }
 
+    // Writes the window layout now, so it is kept even when the process is ended before the context is dropped
+    pub fn save_layout(&mut self) {
+        let Some(path) = config::UI_LAYOUT_FILE else {
+            return;
+        };
+
+        let mut layout = String::new();
+        self.context.save_ini_settings(&mut layout);
+        if let Err(error) = fs::write(path, layout) {
+            eprintln!("[ERROR] Failed to save the UI layout into {path} - {error}");
+        }
+    }
+
+    // True while the mouse is over a window, mouse input then belongs to the UI
+    pub fn wants_mouse(&self) -> bool {
+        self.context.io().want_capture_mouse
+    }
+
+    // True while a text field or other widget takes the keyboard input
+    pub fn wants_keyboard(&self) -> bool {
+        self.context.io().want_capture_keyboard
+    }
+
     pub fn handle_event<T>(&mut self, window: &Window, event: &Event<T>) {
         self.platform
             .handle_event(self.context.io_mut(), window, event);
+
+        // Moving to a monitor with another scale factor changes the logical size of the window,
+        // which the UI is laid out in, even when no resize follows. The font atlas is rebuilt
+        // for the new factor with the next frame, see update_scale.
+        if let Event::WindowEvent {
+            event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+            ..
+        } = event
+        {
+            let logical_size = new_inner_size.to_logical::<f32>(self.platform.hidpi_factor());
+            self.context.io_mut().display_size = [logical_size.width, logical_size.height];
+        }
     }
 }
+
+// Imgui works in logical pixels, the font is rasterized at the physical size
+// and scaled back down so the glyphs map to the pixels of the screen
+fn add_font(
+    context: &mut imgui::Context,
+    font_data: Option<&[u8]>,
+    hidpi_factor: f64,
+    ui_scale: f32,
+) {
+    let scale = hidpi_factor as f32 * ui_scale;
+
+    let font = match font_data {
+        Some(data) => imgui::FontSource::TtfData {
+            data,
+            size_pixels: config::UI_FONT_SIZE * scale,
+            config: None,
+        },
+        None => imgui::FontSource::DefaultFontData {
+            config: Some(imgui::FontConfig {
+                size_pixels: BASE_FONT_SIZE * scale,
+                ..imgui::FontConfig::default()
+            }),
+        },
+    };
+    context.fonts().add_font(&[font]);
+    context.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
+}
@@ -0,0 +1,49 @@
+// Top level state of the application, decides what is updated and shown each frame
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppState {
+    // No world was given on the command line, the user picks one in the world dialog
+    ChoosingWorld,
+    // The world was opened and the user picks where the camera starts
+    ChoosingStart,
+    // The chunks are loaded and the initial meshes are being built, a loading screen is shown instead of the world
+    Loading,
+    Running,
+    // The world is shown but the camera, the tour and chunk loading are stopped
+    Paused,
+    // The world could not be opened, only the message is shown
+    Error(String),
+}
+
+impl AppState {
+    pub fn toggle_pause(&self) -> Self {
+        match self {
+            AppState::Running => AppState::Paused,
+            AppState::Paused => AppState::Running,
+            other => other.clone(),
+        }
+    }
+
+    // Whether the camera and the loaded part of the world are updated
+    pub fn is_active(&self) -> bool {
+        matches!(self, AppState::Running)
+    }
+
+    pub fn shows_world(&self) -> bool {
+        matches!(self, AppState::Running | AppState::Paused)
+    }
+}
+
+// How far the startup got, shown on the loading screen
+#[derive(Debug, Clone, Copy)]
+pub struct LoadingProgress {
+    pub chunks_loaded: usize,
+    pub chunks_total: usize,
+    pub meshes_built: usize,
+    pub meshes_total: usize,
+}
+
+impl LoadingProgress {
+    pub fn is_done(&self) -> bool {
+        self.chunks_loaded == self.chunks_total && self.meshes_built == self.meshes_total
+    }
+}
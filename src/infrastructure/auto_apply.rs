@@ -0,0 +1,40 @@
+use std::time::{Duration, Instant};
+
+// How long the value has to stay the same before it is applied,
+// dragging a slider keeps restarting the wait
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// Applies a value from the controls window, like the polygonization options, without pressing APPLY
+pub struct AutoApply<T> {
+    // Value that differs from the applied one and since when it is requested
+    pending: Option<(T, Instant)>,
+}
+
+impl<T: Copy + PartialEq> AutoApply<T> {
+    pub fn new() -> Self {
+        AutoApply { pending: None }
+    }
+
+    // True once *requested* stayed unchanged for long enough and differs from *applied*
+    pub fn update(&mut self, enabled: bool, requested: T, applied: T) -> bool {
+        if !enabled || requested == applied {
+            self.pending = None;
+            return false;
+        }
+
+        match self.pending {
+            Some((pending, since)) if pending == requested => {
+                if since.elapsed() < DEBOUNCE {
+                    return false;
+                }
+
+                self.pending = None;
+                true
+            }
+            _ => {
+                self.pending = Some((requested, Instant::now()));
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use glium::program::Program;
+use glium::uniforms::Uniforms;
+use glium::{Display, DrawParameters, Surface};
+
+use crate::model::chunk::ChunkPosition;
+use crate::model::discrete::World;
+use crate::model::material_registry;
+use crate::model::polygonize::Mesh;
+use crate::scene::WorldScene;
+
+use super::event_bus::{AppEvent, EventSubscriber, FrameContext};
+use super::mesh_buffers::MeshBuffers;
+use super::render_fragment::create_program;
+
+// The smooth terrain with the mesh of every chunk in buffers of its own.
+//
+// When a chunk mesh is built only that chunk is uploaded, the rest stay on the gpu as they are.
+// A chunk keeps drawing its old mesh while it is rebuilt, the rebuilt mesh is then written
+// into the same buffers. The buffers of chunks that are no longer polygonized are kept aside
+// and reused for the chunks loaded after them.
+pub struct ChunkMeshScene {
+    program: Program,
+    chunks: HashMap<ChunkPosition, MeshBuffers>,
+    // Buffers of dropped chunks, waiting for a chunk to take them over
+    spare: Vec<MeshBuffers>,
+    // Lightmapped mesh of all the chunks, drawn instead of them until a chunk mesh is rebuilt
+    baked: Option<MeshBuffers>,
+}
+
+impl ChunkMeshScene {
+    pub fn new(display: &Display, vertex_shader: &str, fragment_shader: &str) -> Self {
+        ChunkMeshScene {
+            program: create_program(display, vertex_shader, None, fragment_shader),
+            chunks: HashMap::new(),
+            spare: Vec::new(),
+            baked: None,
+        }
+    }
+
+    // Uploads every chunk mesh the world has built so far
+    pub fn upload_world(&mut self, display: &Display, world: &World) {
+        for position in world.polygonized_chunk_positions() {
+            if let Some(mesh) = world.smooth_chunk_mesh(position) {
+                self.place(display, position, mesh);
+            }
+        }
+        self.drop_unloaded(world);
+        self.baked = None;
+    }
+
+    // Writes the mesh of a chunk into its buffers, or into spare ones for a chunk without any yet
+    fn place(&mut self, display: &Display, position: ChunkPosition, mesh: &Mesh) {
+        let buffers = match self.chunks.remove(&position) {
+            Some(buffers) => Some(buffers),
+            None => self.spare.pop(),
+        };
+
+        match buffers {
+            Some(mut buffers) => {
+                // Chunks without any surface have nothing to draw
+                if buffers.rewrite(display, mesh) {
+                    self.chunks.insert(position, buffers);
+                } else {
+                    self.spare.push(buffers);
+                }
+            }
+            None => {
+                if let Some(buffers) = MeshBuffers::upload(display, mesh) {
+                    self.chunks.insert(position, buffers);
+                }
+            }
+        }
+    }
+
+    fn drop_unloaded(&mut self, world: &World) {
+        let polygonized = world.polygonized_chunk_positions();
+        let unloaded = self
+            .chunks
+            .keys()
+            .filter(|position| !polygonized.contains(position))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for position in unloaded {
+            let buffers = self.chunks.remove(&position).unwrap();
+            self.spare.push(buffers);
+        }
+    }
+
+    pub fn show_baked(&mut self, display: &Display, mesh: &Mesh) {
+        self.baked = MeshBuffers::upload(display, mesh);
+    }
+
+    pub fn view<'a>(&'a self, buffers: &'a MeshBuffers) -> MeshView<'a> {
+        MeshView {
+            program: &self.program,
+            buffers,
+        }
+    }
+
+    // The baked mesh replaces the chunk meshes while it is shown
+    pub fn shown_buffers(&self) -> Vec<&MeshBuffers> {
+        match &self.baked {
+            Some(baked) => vec![baked],
+            None => self.chunks.values().collect(),
+        }
+    }
+
+    // Uploads the material weights of every mesh again after the activation thresholds changed,
+    // the geometry stays as it is
+    pub fn recolor(&self) {
+        let materials = material_registry::snapshot();
+        for buffers in self.chunks.values().chain(&self.baked) {
+            buffers.recolor(&materials);
+        }
+    }
+}
+
+impl EventSubscriber for ChunkMeshScene {
+    // Uploads the meshes built this frame and drops the chunks that were unloaded
+    fn handle_events(&mut self, events: &[AppEvent], context: &FrameContext) {
+        let (display, world) = (context.display, context.world);
+        for event in events {
+            let AppEvent::MeshBuilt(position) = event else {
+                continue;
+            };
+            let Some(mesh) = world.smooth_chunk_mesh(*position) else {
+                continue;
+            };
+
+            self.place(display, *position, mesh);
+            self.baked = None;
+        }
+
+        self.drop_unloaded(world);
+    }
+}
+
+impl WorldScene for &ChunkMeshScene {
+    fn draw<U, S>(&self, target: &mut S, uniforms: &U, draw_parameters: DrawParameters)
+    where
+        U: Uniforms,
+        S: Surface,
+    {
+        for buffers in self.shown_buffers() {
+            buffers.draw(target, &self.program, uniforms, &draw_parameters);
+        }
+    }
+}
+
+// A single mesh drawn with the program of the chunk meshes, such as a replaced mesh fading out
+pub struct MeshView<'a> {
+    program: &'a Program,
+    buffers: &'a MeshBuffers,
+}
+
+impl WorldScene for MeshView<'_> {
+    fn draw<U, S>(&self, target: &mut S, uniforms: &U, draw_parameters: DrawParameters)
+    where
+        U: Uniforms,
+        S: Surface,
+    {
+        self.buffers
+            .draw(target, self.program, uniforms, &draw_parameters);
+    }
+}
@@ -0,0 +1,85 @@
+use glium::texture::{RawImage2d, Texture2d};
+use glium::Display;
+
+// Colors of the visualizations that map a value in [0, 1] to a color.
+// Viridis and cividis stay readable with color vision deficiencies,
+// cividis also for people who can't tell red from green at all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorRamp {
+    // Green through yellow to red
+    Heat,
+    Viridis,
+    Cividis,
+}
+
+pub const ALL_COLOR_RAMPS: [ColorRamp; 3] =
+    [ColorRamp::Heat, ColorRamp::Viridis, ColorRamp::Cividis];
+
+// Evenly spaced stops of the ramps, the colors in between are interpolated
+const HEAT_STOPS: [[f32; 3]; 3] = [[0.0, 0.8, 0.1], [1.0, 0.8, 0.1], [1.0, 0.0, 0.1]];
+const VIRIDIS_STOPS: [u32; 9] = [
+    0x440154, 0x472d7b, 0x3b528b, 0x2c728e, 0x21918c, 0x28ae80, 0x5ec962, 0xaddc30, 0xfde725,
+];
+const CIVIDIS_STOPS: [u32; 9] = [
+    0x00224e, 0x123570, 0x3b496c, 0x575d6d, 0x707173, 0x8a8678, 0xa59c74, 0xc3b369, 0xfee838,
+];
+
+// Texels of the ramp texture the shaders sample
+const TEXTURE_WIDTH: usize = 256;
+
+impl ColorRamp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorRamp::Heat => "Heat",
+            ColorRamp::Viridis => "Viridis",
+            ColorRamp::Cividis => "Cividis",
+        }
+    }
+
+    pub fn sample(&self, value: f32) -> [f32; 3] {
+        let stops = match self {
+            ColorRamp::Heat => HEAT_STOPS.to_vec(),
+            ColorRamp::Viridis => VIRIDIS_STOPS.map(hex_color).to_vec(),
+            ColorRamp::Cividis => CIVIDIS_STOPS.map(hex_color).to_vec(),
+        };
+
+        let position = value.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let low = (position.floor() as usize).min(stops.len() - 2);
+        let t = position - low as f32;
+
+        let [a, b] = [stops[low], stops[low + 1]];
+        [0, 1, 2].map(|channel| a[channel] + (b[channel] - a[channel]) * t)
+    }
+}
+
+fn hex_color(rgb: u32) -> [f32; 3] {
+    [16, 8, 0].map(|shift| ((rgb >> shift) & 0xFF) as f32 / 255.0)
+}
+
+// The selected ramp as a texture one texel high,
+// shaders look colors up with texture(color_ramp, vec2(value, 0.5))
+pub struct ColorRampTexture {
+    pub texture: Texture2d,
+    ramp: ColorRamp,
+}
+
+impl ColorRampTexture {
+    pub fn new(display: &Display, ramp: ColorRamp) -> Self {
+        let texels = (0..TEXTURE_WIDTH)
+            .flat_map(|texel| ramp.sample(texel as f32 / (TEXTURE_WIDTH - 1) as f32))
+            .collect::<Vec<f32>>();
+        let image = RawImage2d::from_raw_rgb(texels, (TEXTURE_WIDTH as u32, 1));
+
+        ColorRampTexture {
+            texture: Texture2d::new(display, image).expect("failed to create color ramp texture"),
+            ramp,
+        }
+    }
+
+    // Rebuilds the texture when another ramp gets selected
+    pub fn fit(&mut self, display: &Display, ramp: ColorRamp) {
+        if ramp != self.ramp {
+            *self = ColorRampTexture::new(display, ramp);
+        }
+    }
+}
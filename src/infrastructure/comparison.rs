@@ -0,0 +1,110 @@
+use std::cell::Cell;
+
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, MipmapsOption, SrgbFormat, SrgbTexture2d};
+use glium::Display;
+
+// Pixels whose color channels differ by more than this are counted as changed
+const CHANGED_PIXEL_THRESHOLD: u8 = 8;
+// The changed pixels are counted again after this many frames, reading the images back stalls the GPU
+const CHANGED_PIXELS_INTERVAL: usize = 30;
+
+// Offscreen targets the discrete and implicit scenes are rendered into,
+// so they can be shown side by side or subtracted from each other
+pub struct ComparisonTargets {
+    pub discrete: SrgbTexture2d,
+    pub implicit: SrgbTexture2d,
+    discrete_depth: DepthRenderBuffer,
+    implicit_depth: DepthRenderBuffer,
+    dimensions: (u32, u32),
+    // Last count of changed_pixels and the frames since
+    changed_pixels: Cell<Option<f32>>,
+    frames_since_count: Cell<usize>,
+}
+
+impl ComparisonTargets {
+    pub fn new(display: &Display, dimensions: (u32, u32)) -> Self {
+        let (width, height) = dimensions;
+
+        let color_texture = || {
+            SrgbTexture2d::empty_with_format(
+                display,
+                SrgbFormat::U8U8U8U8,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .expect("failed to create comparison texture")
+        };
+        let depth_buffer = || {
+            DepthRenderBuffer::new(display, DepthFormat::I24, width, height)
+                .expect("failed to create comparison depth buffer")
+        };
+
+        ComparisonTargets {
+            discrete: color_texture(),
+            implicit: color_texture(),
+            discrete_depth: depth_buffer(),
+            implicit_depth: depth_buffer(),
+            dimensions,
+            changed_pixels: Cell::new(None),
+            frames_since_count: Cell::new(0),
+        }
+    }
+
+    // The targets have to match the window, recreate them after a resize
+    pub fn fit(&mut self, display: &Display) {
+        let dimensions = display.get_framebuffer_dimensions();
+        if dimensions != self.dimensions {
+            *self = ComparisonTargets::new(display, dimensions);
+        }
+    }
+
+    pub fn discrete_surface(&self, display: &Display) -> SimpleFrameBuffer<'_> {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.discrete, &self.discrete_depth)
+            .expect("failed to create discrete comparison framebuffer")
+    }
+
+    pub fn implicit_surface(&self, display: &Display) -> SimpleFrameBuffer<'_> {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.implicit, &self.implicit_depth)
+            .expect("failed to create implicit comparison framebuffer")
+    }
+
+    // Fraction of pixels that differ between the two scenes, called once per frame.
+    // It is counted every CHANGED_PIXELS_INTERVAL frames, in between the last count is returned.
+    pub fn changed_pixels(&self) -> f32 {
+        let frames = self.frames_since_count.get();
+        self.frames_since_count.set(frames + 1);
+
+        match self.changed_pixels.get() {
+            Some(changed) if frames < CHANGED_PIXELS_INTERVAL => changed,
+            _ => {
+                let changed = self.count_changed_pixels();
+                self.changed_pixels.set(Some(changed));
+                self.frames_since_count.set(0);
+                changed
+            }
+        }
+    }
+
+    // Reads both images back from the GPU, so it stalls the pipeline
+    fn count_changed_pixels(&self) -> f32 {
+        let discrete: Vec<Vec<(u8, u8, u8, u8)>> = self.discrete.read();
+        let implicit: Vec<Vec<(u8, u8, u8, u8)>> = self.implicit.read();
+
+        let differs = |a: u8, b: u8| a.abs_diff(b) > CHANGED_PIXEL_THRESHOLD;
+        let (changed, total) = discrete
+            .iter()
+            .flatten()
+            .zip(implicit.iter().flatten())
+            .fold((0usize, 0usize), |(changed, total), (a, b)| {
+                let is_changed = differs(a.0, b.0) || differs(a.1, b.1) || differs(a.2, b.2);
+                (changed + is_changed as usize, total + 1)
+            });
+
+        match total {
+            0 => 0.0,
+            _ => changed as f32 / total as f32,
+        }
+    }
+}
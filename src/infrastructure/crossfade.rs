@@ -0,0 +1,62 @@
+use std::time::{Duration, Instant};
+
+use glium::{BlendingFunction, LinearBlendingFactor};
+
+use super::mesh_buffers::MeshBuffers;
+
+// Chunk meshes replaced by a rebuild, faded out over their rebuilt versions.
+//
+// The replaced meshes of a batch are kept in buffers of their own and drawn over the new meshes
+// with their opacity dropping to zero, then they are dropped. A batch starts fading when its
+// rebuilt chunks are uploaded, so chunks rebuilt by later batches fade later.
+pub struct Crossfade {
+    duration: Duration,
+    fades: Vec<(MeshBuffers, Instant)>,
+}
+
+impl Crossfade {
+    pub fn new(duration: Duration) -> Self {
+        Crossfade {
+            duration,
+            fades: Vec::new(),
+        }
+    }
+
+    pub fn start(&mut self, replaced: MeshBuffers) {
+        self.fades.push((replaced, Instant::now()));
+    }
+
+    pub fn clear(&mut self) {
+        self.fades.clear();
+    }
+
+    // Drops the meshes that faded out completely
+    pub fn update(&mut self) {
+        let duration = self.duration;
+        self.fades
+            .retain(|(_, started)| started.elapsed() < duration);
+    }
+
+    // Calls *draw* with every replaced mesh and its current opacity
+    pub fn render(&self, mut draw: impl FnMut(&MeshBuffers, f32)) {
+        for (scene, started) in &self.fades {
+            let progress = started.elapsed().as_secs_f32() / self.duration.as_secs_f32();
+            draw(scene, (1.0 - progress).clamp(0.0, 1.0));
+        }
+    }
+}
+
+// Mixes the faded mesh into the scene by *opacity*, the alpha of the scene is kept
+pub fn fade_blending(opacity: f32) -> glium::Blend {
+    glium::Blend {
+        color: BlendingFunction::Addition {
+            source: LinearBlendingFactor::ConstantAlpha,
+            destination: LinearBlendingFactor::OneMinusConstantAlpha,
+        },
+        alpha: BlendingFunction::Addition {
+            source: LinearBlendingFactor::Zero,
+            destination: LinearBlendingFactor::One,
+        },
+        constant_value: (0.0, 0.0, 0.0, opacity),
+    }
+}
@@ -0,0 +1,181 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Quaternion, Vector3};
+use glium::program::Program;
+use glium::uniforms::Uniforms;
+use glium::{implement_vertex, uniform, Display, IndexBuffer, Surface, VertexBuffer};
+
+use crate::camera::Camera;
+use crate::geometry;
+use crate::to_uniform_matrix;
+
+use super::render_fragment::create_program;
+use super::vertex::Vertex;
+use super::vertical_scale::VerticalScale;
+
+const DEBUG_VS: &str = include_str!("../shaders/debug_vs.glsl");
+const DEBUG_FS: &str = include_str!("../shaders/debug_fs.glsl");
+
+const SPHERE_RINGS: u32 = 8;
+const ROUND_SEGMENTS: u32 = 12;
+
+#[derive(Clone, Copy)]
+struct DebugInstance {
+    transform: [[f32; 4]; 4],
+    tint: [f32; 4],
+}
+implement_vertex!(DebugInstance, transform, tint);
+
+#[derive(Clone, Copy)]
+enum Primitive {
+    Sphere,
+    Cuboid,
+    Arrow,
+    Line,
+    Disc,
+}
+const PRIMITIVE_COUNT: usize = 5;
+
+// Debug shapes queued during a frame and drawn all at once,
+// one instanced draw call per kind of shape.
+//
+// Shapes are given in world space, the queue is emptied every time it is rendered,
+// so whatever should stay visible has to be queued again the next frame.
+pub struct DebugDraw {
+    program: Program,
+    meshes: [(VertexBuffer<Vertex>, IndexBuffer<u32>); PRIMITIVE_COUNT],
+    instances: [Vec<DebugInstance>; PRIMITIVE_COUNT],
+}
+
+impl DebugDraw {
+    pub fn new(display: &Display) -> Self {
+        DebugDraw {
+            program: create_program(display, DEBUG_VS, None, DEBUG_FS),
+            meshes: [
+                geometry::sphere(display, SPHERE_RINGS, ROUND_SEGMENTS),
+                geometry::cube_color_exclusive_vertex(display),
+                geometry::arrow(display, ROUND_SEGMENTS),
+                geometry::line_segment(display),
+                geometry::disc(display, ROUND_SEGMENTS),
+            ],
+            instances: Default::default(),
+        }
+    }
+
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: [f32; 4]) {
+        let transform = Matrix4::from_translation(center.to_vec()) * Matrix4::from_scale(radius);
+        self.push(Primitive::Sphere, transform, color);
+    }
+
+    // Axis aligned box between two of its corners
+    pub fn cuboid(&mut self, min: Point3<f32>, max: Point3<f32>, color: [f32; 4]) {
+        let size = max - min;
+        let transform = Matrix4::from_translation(min.midpoint(max).to_vec())
+            * Matrix4::from_nonuniform_scale(size.x, size.y, size.z);
+        self.push(Primitive::Cuboid, transform, color);
+    }
+
+    // The head of the arrow grows with its length
+    pub fn arrow(&mut self, from: Point3<f32>, to: Point3<f32>, color: [f32; 4]) {
+        if let Some(transform) = segment_transform(from, to) {
+            self.push(Primitive::Arrow, transform, color);
+        }
+    }
+
+    pub fn line(&mut self, from: Point3<f32>, to: Point3<f32>, color: [f32; 4]) {
+        if let Some(transform) = segment_transform(from, to) {
+            self.push(Primitive::Line, transform, color);
+        }
+    }
+
+    // Flat disc facing along *normal*
+    pub fn disc(
+        &mut self,
+        center: Point3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+        color: [f32; 4],
+    ) {
+        // The unit disc is scaled like a unit segment, its radius is the length of the segment
+        if let Some(transform) = segment_transform(center, center + normal.normalize() * radius) {
+            self.push(Primitive::Disc, transform, color);
+        }
+    }
+
+    fn push(&mut self, primitive: Primitive, transform: Matrix4<f32>, tint: [f32; 4]) {
+        self.instances[primitive as usize].push(DebugInstance {
+            transform: transform.into(),
+            tint,
+        });
+    }
+
+    // Draws the queued shapes over the scene and empties the queue.
+    // The shapes are hidden by the terrain in front of them, but don't hide each other
+    // when they are translucent.
+    pub fn render<S: Surface>(
+        &mut self,
+        display: &Display,
+        target: &mut S,
+        camera: &Camera,
+        vertical_scale: VerticalScale,
+    ) {
+        let uni = uniform! {
+            projection: to_uniform_matrix(&camera.projection),
+            view: to_uniform_matrix(&camera.world_to_view),
+            model: to_uniform_matrix(&vertical_scale.model_matrix()),
+        };
+
+        for (mesh, instances) in self.meshes.iter().zip(self.instances.iter_mut()) {
+            if instances.is_empty() {
+                continue;
+            }
+
+            let instance_buffer = VertexBuffer::dynamic(display, instances).unwrap();
+            draw_instanced(target, &self.program, mesh, &instance_buffer, &uni);
+            instances.clear();
+        }
+    }
+}
+
+fn draw_instanced<S: Surface, U: Uniforms>(
+    target: &mut S,
+    program: &Program,
+    (vertices, indices): &(VertexBuffer<Vertex>, IndexBuffer<u32>),
+    instance_buffer: &VertexBuffer<DebugInstance>,
+    uniforms: &U,
+) {
+    let draw_parameters = glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::DepthTest::IfLess,
+            write: false,
+            ..Default::default()
+        },
+        blend: glium::Blend::alpha_blending(),
+        ..Default::default()
+    };
+
+    target
+        .draw(
+            (vertices, instance_buffer.per_instance().unwrap()),
+            indices,
+            program,
+            uniforms,
+            &draw_parameters,
+        )
+        .unwrap();
+}
+
+// Maps the unit segment up the y axis onto the segment between the points,
+// None when they are the same point
+fn segment_transform(from: Point3<f32>, to: Point3<f32>) -> Option<Matrix4<f32>> {
+    let direction = to - from;
+    let length = direction.magnitude();
+    if length <= f32::EPSILON {
+        return None;
+    }
+
+    let rotation = Quaternion::from_arc(Vector3::unit_y(), direction / length, None);
+    Some(
+        Matrix4::from_translation(from.to_vec())
+            * Matrix4::from(rotation)
+            * Matrix4::from_scale(length),
+    )
+}
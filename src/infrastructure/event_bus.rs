@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glium::Display;
+
+use crate::camera::Camera;
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::model::chunk::ChunkPosition;
+use crate::model::common::BlockType;
+use crate::model::discrete::World;
+use crate::model::polygonize::PolygonizationOptions;
+use crate::model::Position;
+
+use super::{RenderSettings, RenderState};
+
+// Things that happened in the application during a frame
+#[derive(Clone, Copy, Debug)]
+pub enum AppEvent {
+    // A chunk was loaded from the save after the world was recentered
+    ChunkLoaded(ChunkPosition),
+    // The smooth mesh of a chunk was built and integrated into the world
+    MeshBuilt(ChunkPosition),
+    // New polygonization options were applied, all meshes are being rebuilt
+    OptionsChanged(PolygonizationOptions),
+    CameraMoved(Position),
+    BlockEdited(Position, BlockType),
+    // A render setting was switched, from the keyboard or the UI
+    RenderSettingsChanged(RenderSettings),
+}
+
+// What a subscriber can look at when the events of a frame are delivered to it
+pub struct FrameContext<'a> {
+    pub display: &'a Display,
+    pub world: &'a World,
+    pub camera: &'a Camera,
+    pub state: &'a RenderState,
+    pub controls: &'a SmoothMeshOptions,
+    pub options: PolygonizationOptions,
+}
+
+pub trait EventSubscriber {
+    // Gets the events published since the previous dispatch, in the order they were published.
+    // Called every frame, also without any events, so the subscriber can follow the options
+    // and the camera as well.
+    fn handle_events(&mut self, events: &[AppEvent], context: &FrameContext);
+}
+
+// Events are queued as they are published and delivered to the subscribers
+// all at once, so a subscriber never runs in the middle of another subsystem's update.
+// The subscribers get the events in the order they subscribed.
+pub struct EventBus {
+    queue: Vec<AppEvent>,
+    subscribers: Vec<Rc<RefCell<dyn EventSubscriber>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus {
+            queue: Vec::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn subscribe(&mut self, subscriber: Rc<RefCell<dyn EventSubscriber>>) {
+        self.subscribers.push(subscriber);
+    }
+
+    pub fn publish(&mut self, event: AppEvent) {
+        self.queue.push(event);
+    }
+
+    pub fn publish_all(&mut self, events: impl IntoIterator<Item = AppEvent>) {
+        self.queue.extend(events);
+    }
+
+    // Delivers the queued events to every subscriber
+    pub fn dispatch(&mut self, context: &FrameContext) {
+        let events = std::mem::take(&mut self.queue);
+        for subscriber in &self.subscribers {
+            subscriber.borrow_mut().handle_events(&events, context);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Percentiles and the worst frame are computed over this window
+const WINDOW: Duration = Duration::from_secs(10);
+
+// Frames taking longer than this are counted as slow, 30 fps
+const SLOW_FRAME: Duration = Duration::from_millis(33);
+
+// Frame times recorded over the last few seconds, used to spot stutter
+// that the instantaneous fps hides
+pub struct FrameStats {
+    frames: VecDeque<(Instant, Duration)>,
+    slow_frames: usize,
+    total_frames: usize,
+}
+
+// Snapshot of the statistics shown in the ui, times in milliseconds
+#[derive(Debug, Copy, Clone)]
+pub struct FrameSummary {
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub worst: f32,
+    pub slow_frames: usize,
+    pub total_frames: usize,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        FrameStats {
+            frames: VecDeque::new(),
+            slow_frames: 0,
+            total_frames: 0,
+        }
+    }
+
+    pub fn record(&mut self, frame_time: Duration) {
+        let now = Instant::now();
+        self.frames.push_back((now, frame_time));
+        while let Some((recorded_at, _)) = self.frames.front() {
+            if now.duration_since(*recorded_at) <= WINDOW {
+                break;
+            }
+            self.frames.pop_front();
+        }
+
+        self.total_frames += 1;
+        if frame_time > SLOW_FRAME {
+            self.slow_frames += 1;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = FrameStats::new();
+    }
+
+    pub fn summary(&self) -> FrameSummary {
+        let mut frame_times = self
+            .frames
+            .iter()
+            .map(|(_, frame_time)| frame_time.as_secs_f32() * 1000.0)
+            .collect::<Vec<_>>();
+        frame_times.sort_by(|a, b| a.total_cmp(b));
+
+        // Nearest rank percentile
+        let percentile = |fraction: f32| {
+            if frame_times.is_empty() {
+                return 0.0;
+            }
+            let rank = (fraction * frame_times.len() as f32).ceil() as usize;
+            frame_times[rank.clamp(1, frame_times.len()) - 1]
+        };
+
+        FrameSummary {
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            worst: frame_times.last().copied().unwrap_or(0.0),
+            slow_frames: self.slow_frames,
+            total_frames: self.total_frames,
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use glium::glutin::event::{
-    DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent,
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
 };
 
 use crate::RenderState;
@@ -14,8 +15,10 @@ pub enum Direction {
     Down,
 }
 
+// Touchpads scroll by pixels, this many of them count as one line of a mouse wheel
+const PIXELS_PER_SCROLL_LINE: f64 = 40.0;
+
 type Key = VirtualKeyCode;
-//type MouseButton = glium::glutin::event::MouseButton;
 
 #[derive(Debug)]
 pub enum InputAction {
@@ -23,8 +26,10 @@ pub enum InputAction {
     BeginMove { dir: Direction },
     EndMove { dir: Direction },
     CursorMoved { x: f64, y: f64 },
-    //Scroll(f64, f64),
-    //MousePressed { button: MouseButton },
+    // Lines scrolled, positive away from the user
+    Scroll(f64),
+    ModifiersChanged { shift: bool },
+    MousePressed { button: MouseButton },
     KeyPressed { key: Key },
     Char { c: char },
     Capture,
@@ -45,6 +50,32 @@ pub fn translate_event(event: Event<()>) -> Option<InputAction> {
     }
 }
 
+// Whether the UI uses the keyboard or the mouse, for example while typing into a text field
+// or with the cursor over a window
+#[derive(Clone, Copy, Debug)]
+pub struct UiFocus {
+    pub keyboard: bool,
+    pub mouse: bool,
+}
+
+// Input the UI uses doesn't reach the rest of the application.
+// Releasing a key always does, so the camera doesn't keep moving when a text field is focused mid-move.
+pub fn reaches_scene(action: &InputAction, focus: UiFocus) -> bool {
+    match action {
+        InputAction::Quit
+        | InputAction::BeginMove { .. }
+        | InputAction::KeyPressed { .. }
+        | InputAction::Char { .. }
+        | InputAction::Capture => !focus.keyboard,
+        InputAction::CursorMoved { .. }
+        | InputAction::Scroll(_)
+        | InputAction::MousePressed { .. } => !focus.mouse,
+        InputAction::EndMove { .. }
+        | InputAction::ModifiersChanged { .. }
+        | InputAction::Resized(..) => true,
+    }
+}
+
 fn translate_window_event(event: WindowEvent) -> Option<InputAction> {
     match event {
         WindowEvent::CloseRequested => Some(InputAction::Quit),
@@ -55,6 +86,20 @@ fn translate_window_event(event: WindowEvent) -> Option<InputAction> {
         } => handle_keypress(&input),
         WindowEvent::ReceivedCharacter(c) => Some(InputAction::Char { c }),
         WindowEvent::Resized(size) => Some(InputAction::Resized(size.width, size.height)),
+        WindowEvent::MouseWheel { delta, .. } => match delta {
+            MouseScrollDelta::LineDelta(_, y) => Some(InputAction::Scroll(y as f64)),
+            MouseScrollDelta::PixelDelta(position) => {
+                Some(InputAction::Scroll(position.y / PIXELS_PER_SCROLL_LINE))
+            }
+        },
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button,
+            ..
+        } => Some(InputAction::MousePressed { button }),
+        WindowEvent::ModifiersChanged(modifiers) => Some(InputAction::ModifiersChanged {
+            shift: modifiers.shift(),
+        }),
         _ => None,
     }
 }
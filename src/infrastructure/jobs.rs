@@ -0,0 +1,401 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once};
+use std::thread;
+
+use crate::config;
+
+// Jobs of a higher priority are started first once a worker is free,
+// jobs of the same priority start in the order they were spawned
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    // Long work the user waits for anyway, like baking and offline renders
+    Low,
+    Normal,
+    // Work the next frames depend on
+    High,
+}
+
+// Shared between the owner of a job and the job itself, the job looks at it between steps of its work
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// What a running job is given, it can be shared with the rayon tasks of the job
+pub struct JobContext {
+    token: CancelToken,
+    done: Arc<AtomicUsize>,
+}
+
+impl JobContext {
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    // Counts *steps* of the total the job was spawned with as done
+    pub fn advance(&self, steps: usize) {
+        self.done.fetch_add(steps, Ordering::Relaxed);
+    }
+}
+
+type JobResult<T> = thread::Result<T>;
+
+struct ResultSlot<T> {
+    result: Mutex<Option<JobResult<T>>>,
+    finished: Condvar,
+}
+
+// Owner's side of a spawned job, like a JoinHandle of a thread.
+// Dropping the handle detaches the job, it still runs and its result is dropped.
+pub struct JobHandle<T> {
+    name: &'static str,
+    token: CancelToken,
+    done: Arc<AtomicUsize>,
+    total: usize,
+    slot: Arc<ResultSlot<T>>,
+}
+
+impl<T> JobHandle<T> {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+
+    // Share of the total steps done, see JobContext::advance
+    pub fn progress(&self) -> f32 {
+        self.done.load(Ordering::Relaxed) as f32 / self.total.max(1) as f32
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.slot.result.lock().unwrap().is_some()
+    }
+
+    // Waits for the job, Err holds the panic of the job
+    pub fn join(self) -> JobResult<T> {
+        let mut result = self.slot.result.lock().unwrap();
+        while result.is_none() {
+            result = self.slot.finished.wait(result).unwrap();
+        }
+
+        result.take().unwrap()
+    }
+}
+
+struct QueuedJob {
+    priority: Priority,
+    sequence: usize,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+struct JobQueue {
+    jobs: Mutex<Vec<QueuedJob>>,
+    available: Condvar,
+    sequence: AtomicUsize,
+}
+
+static QUEUE: JobQueue = JobQueue {
+    jobs: Mutex::new(Vec::new()),
+    available: Condvar::new(),
+    sequence: AtomicUsize::new(0),
+};
+static START_WORKERS: Once = Once::new();
+
+// The workers are started with the first job and live as long as the application.
+//
+// They only run the jobs one after another, the parallel work of a job goes to the rayon pool,
+// so a few of them are enough.
+fn start_workers() {
+    for index in 0..config::JOB_WORKERS.max(1) {
+        thread::Builder::new()
+            .name(format!("job worker {index}"))
+            .spawn(|| loop {
+                let job = next_job();
+                (job.run)();
+            })
+            .expect("Failed to start a job worker");
+    }
+}
+
+// Waits for a job, see next_index
+fn next_job() -> QueuedJob {
+    let mut jobs = QUEUE.jobs.lock().unwrap();
+    loop {
+        match next_index(&jobs) {
+            Some(index) => return jobs.swap_remove(index),
+            None => jobs = QUEUE.available.wait(jobs).unwrap(),
+        }
+    }
+}
+
+// The queued job with the highest priority that was spawned first
+fn next_index(jobs: &[QueuedJob]) -> Option<usize> {
+    jobs.iter()
+        .enumerate()
+        .max_by_key(|(_, job)| (job.priority, Reverse(job.sequence)))
+        .map(|(index, _)| index)
+}
+
+// Runs *work* on a job worker. *total* is the number of steps the job reports with JobContext::advance,
+// 0 for jobs without progress.
+pub fn spawn<T: Send + 'static>(
+    name: &'static str,
+    priority: Priority,
+    total: usize,
+    work: impl FnOnce(&JobContext) -> T + Send + 'static,
+) -> JobHandle<T> {
+    let token = CancelToken::new();
+    let done = Arc::new(AtomicUsize::new(0));
+    let slot = Arc::new(ResultSlot {
+        result: Mutex::new(None),
+        finished: Condvar::new(),
+    });
+
+    let context = JobContext {
+        token: token.clone(),
+        done: done.clone(),
+    };
+    let job_slot = slot.clone();
+    let run = move || {
+        // A panic is kept for the owner like the panic of a joined thread, the worker goes on
+        let result = panic::catch_unwind(AssertUnwindSafe(|| work(&context)));
+        *job_slot.result.lock().unwrap() = Some(result);
+        job_slot.finished.notify_all();
+    };
+
+    START_WORKERS.call_once(start_workers);
+    let job = QueuedJob {
+        priority,
+        sequence: QUEUE.sequence.fetch_add(1, Ordering::Relaxed),
+        run: Box::new(run),
+    };
+    QUEUE.jobs.lock().unwrap().push(job);
+    QUEUE.available.notify_one();
+
+    JobHandle {
+        name,
+        token,
+        done,
+        total,
+        slot,
+    }
+}
+
+// Waiting for their job to finish, polled by run_completions
+type Completion = Box<dyn FnMut() -> bool>;
+
+thread_local! {
+    static COMPLETIONS: RefCell<Vec<Completion>> = RefCell::new(Vec::new());
+}
+
+// Like spawn, *on_complete* is called with the result on the thread that spawned the job
+// by run_completions once the job finished. A job that panicked is reported instead.
+pub fn spawn_then<T: Send + 'static>(
+    name: &'static str,
+    priority: Priority,
+    total: usize,
+    work: impl FnOnce(&JobContext) -> T + Send + 'static,
+    on_complete: impl FnOnce(T) + 'static,
+) -> CancelToken {
+    let handle = spawn(name, priority, total, work);
+    let token = handle.token();
+
+    let mut pending = Some((handle, on_complete));
+    let completion = move || {
+        let finished = pending
+            .as_ref()
+            .is_none_or(|(handle, _)| handle.is_finished());
+        if !finished {
+            return false;
+        }
+
+        if let Some((handle, on_complete)) = pending.take() {
+            match handle.join() {
+                Ok(result) => on_complete(result),
+                Err(panic_message) => {
+                    eprintln!("[ERROR] The {name} job panicked - {panic_message:?}")
+                }
+            }
+        }
+        true
+    };
+    COMPLETIONS.with(|completions| completions.borrow_mut().push(Box::new(completion)));
+
+    token
+}
+
+// Calls the completion callbacks of the finished jobs spawned with spawn_then, once per frame.
+// The callbacks may spawn more jobs. Returns the number of jobs still waited for.
+pub fn run_completions() -> usize {
+    let mut pending = COMPLETIONS.with(|completions| completions.take());
+    pending.retain_mut(|completion| !completion());
+
+    COMPLETIONS.with(|completions| {
+        let mut completions = completions.borrow_mut();
+        pending.append(&mut completions);
+        *completions = pending;
+        completions.len()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    fn queued(priority: Priority, sequence: usize) -> QueuedJob {
+        QueuedJob {
+            priority,
+            sequence,
+            run: Box::new(|| {}),
+        }
+    }
+
+    // Calls run_completions until no job is waited for
+    fn run_all_completions() {
+        while run_completions() > 0 {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn higher_priority_jobs_start_first_then_the_earlier_ones() {
+        let mut jobs = vec![
+            queued(Priority::Normal, 0),
+            queued(Priority::Low, 1),
+            queued(Priority::High, 2),
+            queued(Priority::Normal, 3),
+            queued(Priority::High, 4),
+        ];
+
+        let mut started = Vec::new();
+        while let Some(index) = next_index(&jobs) {
+            started.push(jobs.swap_remove(index).sequence);
+        }
+
+        assert_eq!(started, [2, 4, 0, 3, 1]);
+    }
+
+    #[test]
+    fn cancelled_job_sees_the_cancellation() {
+        let (started_sender, started) = mpsc::channel();
+        let job = spawn("cancelled", Priority::High, 0, move |context| {
+            started_sender.send(()).unwrap();
+
+            let mut steps = 0;
+            while !context.is_cancelled() {
+                steps += 1;
+                thread::yield_now();
+            }
+            steps
+        });
+
+        started.recv().unwrap();
+        job.cancel();
+        assert!(job.join().is_ok());
+    }
+
+    #[test]
+    fn progress_counts_the_steps_done() {
+        let job = spawn("progress", Priority::High, 4, |context| {
+            context.advance(1);
+            context.advance(2);
+        });
+
+        while !job.is_finished() {
+            thread::yield_now();
+        }
+        assert_eq!(job.progress(), 0.75);
+    }
+
+    #[test]
+    fn panic_of_a_job_is_returned_by_join() {
+        let job = spawn("panicking", Priority::High, 0, |_| -> usize {
+            panic!("the job failed");
+        });
+
+        let panic_message = job.join().unwrap_err();
+        assert_eq!(
+            panic_message.downcast_ref::<&str>(),
+            Some(&"the job failed")
+        );
+
+        // The worker that ran it goes on with other jobs
+        let job = spawn("after the panic", Priority::High, 0, |_| 7);
+        assert_eq!(job.join().unwrap(), 7);
+    }
+
+    #[test]
+    fn completion_runs_on_the_spawning_thread_only_from_run_completions() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+
+        let recorded = calls.clone();
+        let (finished_sender, finished) = mpsc::channel();
+        spawn_then(
+            "completed",
+            Priority::High,
+            0,
+            move |_| {
+                finished_sender.send(()).unwrap();
+                5
+            },
+            move |result| recorded.borrow_mut().push((result, thread::current().id())),
+        );
+
+        finished.recv().unwrap();
+        assert!(calls.borrow().is_empty());
+
+        run_all_completions();
+        assert_eq!(*calls.borrow(), [(5, thread::current().id())]);
+    }
+
+    #[test]
+    fn jobs_spawned_by_a_completion_complete_after_it() {
+        let order = Rc::new(RefCell::new(Vec::new()));
+
+        let first = order.clone();
+        spawn_then(
+            "first",
+            Priority::High,
+            0,
+            |_| "first",
+            move |name| {
+                first.borrow_mut().push(name);
+
+                let second = first.clone();
+                spawn_then(
+                    "second",
+                    Priority::High,
+                    0,
+                    |_| "second",
+                    move |name| second.borrow_mut().push(name),
+                );
+            },
+        );
+
+        run_all_completions();
+        assert_eq!(*order.borrow(), ["first", "second"]);
+    }
+}
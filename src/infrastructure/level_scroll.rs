@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::infrastructure::input::{InputAction, InputConsumer};
+use crate::infrastructure::RenderState;
+
+// Same ranges as the sliders in the controls window
+const SMOOTHNESS_RANGE: (u8, u8) = (1, 6);
+const MESH_DETAIL_RANGE: (u8, u8) = (1, 4);
+
+// The meshes are rebuilt once the wheel has been still for this long,
+// so sweeping through several levels rebuilds them only once
+const REBUILD_DELAY: Duration = Duration::from_millis(350);
+
+// Steps the smoothness level with the mouse wheel, or the mesh detail level while shift is held.
+// The controls window shows the new level right away, the meshes follow after a short pause.
+pub struct LevelScroll {
+    shift_held: bool,
+    // Scrolled lines not yet turned into whole steps, touchpads scroll by fractions of a line
+    smoothness_lines: f64,
+    detail_lines: f64,
+    rebuild_at: Option<Instant>,
+}
+
+impl LevelScroll {
+    pub fn new() -> Self {
+        LevelScroll {
+            shift_held: false,
+            smoothness_lines: 0.0,
+            detail_lines: 0.0,
+            rebuild_at: None,
+        }
+    }
+
+    // Applies the scrolled steps to *controls*, returns true when the meshes should be rebuilt
+    pub fn update(&mut self, controls: &mut SmoothMeshOptions) -> bool {
+        let smoothness_steps = take_steps(&mut self.smoothness_lines);
+        let detail_steps = take_steps(&mut self.detail_lines);
+
+        let smoothness = step_level(
+            controls.smoothness_level,
+            smoothness_steps,
+            SMOOTHNESS_RANGE,
+        );
+        let detail = step_level(
+            controls.mesh_resolution_level,
+            detail_steps,
+            MESH_DETAIL_RANGE,
+        );
+        if smoothness != controls.smoothness_level || detail != controls.mesh_resolution_level {
+            controls.set_smoothness_level(smoothness);
+            controls.set_mesh_detail_level(detail);
+            self.rebuild_at = Some(Instant::now() + REBUILD_DELAY);
+        }
+
+        match self.rebuild_at {
+            Some(rebuild_at) if Instant::now() >= rebuild_at => {
+                self.rebuild_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Scrolling while the world is not shown does nothing
+    pub fn discard_scroll(&mut self) {
+        self.smoothness_lines = 0.0;
+        self.detail_lines = 0.0;
+    }
+}
+
+impl InputConsumer for LevelScroll {
+    fn consume(&mut self, action: &InputAction, _state: &RenderState) {
+        match action {
+            InputAction::ModifiersChanged { shift } => self.shift_held = *shift,
+            InputAction::Scroll(lines) if self.shift_held => self.detail_lines += lines,
+            InputAction::Scroll(lines) => self.smoothness_lines += lines,
+            _ => (),
+        }
+    }
+}
+
+// Whole lines scrolled, the remainder is kept for later
+fn take_steps(lines: &mut f64) -> i32 {
+    let steps = lines.trunc();
+    *lines -= steps;
+
+    steps as i32
+}
+
+fn step_level(level: u8, steps: i32, (min, max): (u8, u8)) -> u8 {
+    (level as i32 + steps).clamp(min as i32, max as i32) as u8
+}
@@ -0,0 +1,212 @@
+use glium::index::{IndexBuffer, IndexBufferSlice, PrimitiveType};
+use glium::program::Program;
+use glium::uniforms::Uniforms;
+use glium::{implement_vertex, Display, DrawParameters, Surface, VertexBuffer};
+
+use crate::config;
+use crate::model::material_registry::{self, MaterialRegistry};
+use crate::model::polygonize::{Mesh, VertexIndex};
+
+use super::texture::{MaterialBlend, MaterialSample};
+
+// Attributes of the mesh vertex that only change when the mesh is built again
+#[derive(Clone, Copy)]
+struct GeometryVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    light: [f32; 2],
+    lightmap_uv: [f32; 2],
+    biome_tint: [f32; 3],
+    ambient_occlusion: f32,
+}
+implement_vertex!(
+    GeometryVertex,
+    position,
+    normal,
+    light,
+    lightmap_uv,
+    biome_tint,
+    ambient_occlusion
+);
+
+// Attributes that follow the material registry, see MaterialBlend::activate_materials
+#[derive(Clone, Copy)]
+struct WeightsVertex {
+    vertex_material_weights: [[f32; 4]; 4],
+}
+implement_vertex!(WeightsVertex, vertex_material_weights);
+
+// A smooth mesh on the gpu, its vertex attributes are split into streams bound together at draw time.
+//
+// The geometry and the material weights are uploaded separately, so recoloring a mesh writes only
+// the weights, a pass that needs only the surface can bind the geometry alone,
+// and the weights can change their format without touching the rest.
+//
+// The buffers are allocated with config::MESH_BUFFER_HEADROOM to spare and persistently mapped
+// where the driver supports it. A rebuilt mesh is written into the same buffers,
+// new ones are only allocated once the mesh outgrows them.
+pub struct MeshBuffers {
+    geometry: VertexBuffer<GeometryVertex>,
+    weights: VertexBuffer<WeightsVertex>,
+    indices: IndexBuffer<VertexIndex>,
+    // Only the start of the index buffer holds the mesh, the rest is never drawn
+    index_count: usize,
+    // The weights are computed from these with the current activation thresholds
+    samples: Vec<MaterialSample>,
+}
+
+impl MeshBuffers {
+    // None for meshes without any triangles, they have nothing to draw
+    pub fn upload(display: &Display, mesh: &Mesh) -> Option<Self> {
+        if mesh.indices.is_empty() {
+            return None;
+        }
+
+        let mut buffers = MeshBuffers::allocate(display, mesh);
+        buffers.write(mesh);
+        Some(buffers)
+    }
+
+    // Writes *mesh* over the current one, reallocating only when it doesn't fit.
+    // False for meshes without any triangles, the buffers are left as they are.
+    pub fn rewrite(&mut self, display: &Display, mesh: &Mesh) -> bool {
+        if mesh.indices.is_empty() {
+            return false;
+        }
+
+        if mesh.vertices.len() > self.geometry.len() || mesh.indices.len() > self.indices.len() {
+            *self = MeshBuffers::allocate(display, mesh);
+        }
+        self.write(mesh);
+        true
+    }
+
+    fn allocate(display: &Display, mesh: &Mesh) -> Self {
+        let vertices = with_headroom(mesh.vertices.len());
+        let indices = with_headroom(mesh.indices.len());
+
+        MeshBuffers {
+            geometry: VertexBuffer::empty_persistent(display, vertices)
+                .or_else(|_| VertexBuffer::empty_dynamic(display, vertices))
+                .expect("failed to create the mesh geometry buffer"),
+            weights: VertexBuffer::empty_persistent(display, vertices)
+                .or_else(|_| VertexBuffer::empty_dynamic(display, vertices))
+                .expect("failed to create the mesh weights buffer"),
+            indices: IndexBuffer::empty_persistent(display, PrimitiveType::TrianglesList, indices)
+                .or_else(|_| {
+                    IndexBuffer::empty_dynamic(display, PrimitiveType::TrianglesList, indices)
+                })
+                .expect("failed to create the mesh index buffer"),
+            index_count: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, mesh: &Mesh) {
+        let vertex_count = mesh.vertices.len();
+        let index_count = mesh.indices.len();
+
+        {
+            let mut geometry = self
+                .geometry
+                .slice_mut(0..vertex_count)
+                .unwrap()
+                .map_write();
+            for (position, vertex) in mesh.vertices.iter().enumerate() {
+                geometry.set(
+                    position,
+                    GeometryVertex {
+                        position: vertex.position,
+                        normal: vertex.normal,
+                        light: vertex.light,
+                        lightmap_uv: vertex.lightmap_uv,
+                        biome_tint: vertex.biome_tint,
+                        ambient_occlusion: vertex.ambient_occlusion,
+                    },
+                );
+            }
+        }
+
+        {
+            let mut indices = self.indices.slice_mut(0..index_count).unwrap().map_write();
+            for (position, index) in mesh.indices.iter().enumerate() {
+                indices.set(position, *index);
+            }
+        }
+        self.index_count = index_count;
+
+        self.samples.clear();
+        self.samples
+            .extend(mesh.vertices.iter().map(|vertex| vertex.material_sample));
+        self.recolor(&material_registry::snapshot());
+    }
+
+    // Uploads the material weights again after the activation thresholds changed
+    pub fn recolor(&self, materials: &MaterialRegistry) {
+        self.weights
+            .slice(0..self.samples.len())
+            .unwrap()
+            .write(&weights_of(&self.samples, materials));
+    }
+
+    pub fn draw<U, S>(
+        &self,
+        target: &mut S,
+        program: &Program,
+        uniforms: &U,
+        draw_parameters: &DrawParameters,
+    ) where
+        U: Uniforms,
+        S: Surface,
+    {
+        let vertices = (&self.geometry, &self.weights);
+        target
+            .draw(
+                vertices,
+                &self.drawn_indices(),
+                program,
+                uniforms,
+                draw_parameters,
+            )
+            .unwrap();
+    }
+
+    // Binds only the geometry, for passes that don't look at the materials like the shadow maps
+    pub fn draw_geometry<U, S>(
+        &self,
+        target: &mut S,
+        program: &Program,
+        uniforms: &U,
+        draw_parameters: &DrawParameters,
+    ) where
+        U: Uniforms,
+        S: Surface,
+    {
+        target
+            .draw(
+                &self.geometry,
+                &self.drawn_indices(),
+                program,
+                uniforms,
+                draw_parameters,
+            )
+            .unwrap();
+    }
+
+    fn drawn_indices(&self) -> IndexBufferSlice<VertexIndex> {
+        self.indices.slice(0..self.index_count).unwrap()
+    }
+}
+
+fn with_headroom(count: usize) -> usize {
+    ((count as f64 * config::MESH_BUFFER_HEADROOM).ceil() as usize).max(1)
+}
+
+fn weights_of(samples: &[MaterialSample], materials: &MaterialRegistry) -> Vec<WeightsVertex> {
+    samples
+        .iter()
+        .map(|sample| WeightsVertex {
+            vertex_material_weights: MaterialBlend::activate_materials(sample, materials),
+        })
+        .collect()
+}
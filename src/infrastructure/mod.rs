@@ -1,9 +1,33 @@
+pub mod app_state;
+pub mod auto_apply;
+pub mod chunk_meshes;
+pub mod color_ramp;
+pub mod comparison;
+pub mod crossfade;
+pub mod debug_draw;
+pub mod event_bus;
+pub mod frame_stats;
 pub mod input;
+pub mod jobs;
+pub mod level_scroll;
+pub mod mesh_buffers;
+pub mod quality_governor;
 pub mod render_fragment;
+pub mod render_plugin;
+pub mod scene_target;
+pub mod shadow_map;
 pub mod texture;
+pub mod translucency;
 pub mod vertex;
+pub mod vertical_scale;
+pub mod water;
 
 mod render_state;
+pub use render_state::LightVisualization;
+pub use render_state::RenderCommand;
+pub use render_state::RenderSettings;
 pub use render_state::RenderState;
+pub use render_state::RenderStateManager;
 pub use render_state::RenderingMode;
 pub use render_state::Timing;
+pub use render_state::RENDERING_MODES;
@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+// Frame time the governor aims for, 60 fps
+const TARGET_FRAME_TIME: f32 = 1.0 / 60.0;
+
+// The scale is lowered when frames are slower than the target by this factor
+// and raised when they are faster than the target by this factor.
+// The gap between them keeps the scale from oscillating.
+const LOWER_THRESHOLD: f32 = 1.15;
+const RAISE_THRESHOLD: f32 = 0.7;
+
+// How long the frame time has to stay over or under the threshold before the scale changes,
+// raising is slower so a single fast moment doesn't bring the stutter back
+const LOWER_AFTER: Duration = Duration::from_millis(1000);
+const RAISE_AFTER: Duration = Duration::from_millis(3000);
+
+const SCALE_STEP: f32 = 0.1;
+const MIN_RENDER_SCALE: f32 = 0.5;
+
+// Weight of the newest frame in the smoothed frame time
+const SMOOTHING: f32 = 0.1;
+
+// Lowers the render resolution when the frame time stays above the target
+// and raises it back once there is headroom
+pub struct QualityGovernor {
+    render_scale: f32,
+    smoothed_frame_time: f32,
+    over_target_since: Option<Instant>,
+    under_target_since: Option<Instant>,
+}
+
+impl QualityGovernor {
+    pub fn new() -> Self {
+        QualityGovernor {
+            render_scale: 1.0,
+            smoothed_frame_time: TARGET_FRAME_TIME,
+            over_target_since: None,
+            under_target_since: None,
+        }
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    pub fn update(&mut self, frame_time: Duration, enabled: bool) {
+        if !enabled {
+            *self = QualityGovernor::new();
+            return;
+        }
+
+        let frame_time = frame_time.as_secs_f32();
+        self.smoothed_frame_time += SMOOTHING * (frame_time - self.smoothed_frame_time);
+
+        let now = Instant::now();
+        let over_target = self.smoothed_frame_time > TARGET_FRAME_TIME * LOWER_THRESHOLD;
+        let under_target = self.smoothed_frame_time < TARGET_FRAME_TIME * RAISE_THRESHOLD;
+
+        self.over_target_since = match over_target {
+            true => self.over_target_since.or(Some(now)),
+            false => None,
+        };
+        self.under_target_since = match under_target {
+            true => self.under_target_since.or(Some(now)),
+            false => None,
+        };
+
+        let sustained = |since: Option<Instant>, duration: Duration| {
+            since.is_some_and(|since| now.duration_since(since) >= duration)
+        };
+
+        if sustained(self.over_target_since, LOWER_AFTER) {
+            self.render_scale = (self.render_scale - SCALE_STEP).max(MIN_RENDER_SCALE);
+            self.over_target_since = None;
+        } else if sustained(self.under_target_since, RAISE_AFTER) {
+            self.render_scale = (self.render_scale + SCALE_STEP).min(1.0);
+            self.under_target_since = None;
+        }
+    }
+}
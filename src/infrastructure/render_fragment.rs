@@ -4,6 +4,7 @@ use glium::index::IndicesSource;
 use glium::program::Program;
 use glium::program::ProgramCreationInput;
 use glium::uniforms::Uniforms;
+use glium::vertex::PerInstance;
 use glium::Surface;
 use glium::VertexBuffer;
 
@@ -36,13 +37,14 @@ where
     IndicesSource<'a>: From<&'a I>,
 {
     // TODO: check compatibility of uniforms and print warnings in debug mode
-    pub fn render<U>(
+    pub fn render<U, S>(
         &'a self,
-        target: &mut glium::Frame,
+        target: &mut S,
         uniforms: &U,
         draw_parameters: Option<glium::DrawParameters>,
     ) where
         U: Uniforms,
+        S: Surface,
     {
         let params = draw_parameters.unwrap_or_else(|| Self::default_draw_parameters());
 
@@ -57,21 +59,21 @@ where
             .unwrap();
     }
 
-    pub fn render_instanced<U, D>(
+    pub fn render_instanced<U, S>(
         &'a self,
-        target: &mut glium::Frame,
+        target: &mut S,
         uniforms: &U,
-        instance_data: &VertexBuffer<D>,
+        instances: PerInstance,
         draw_parameters: Option<glium::DrawParameters>,
     ) where
         U: Uniforms,
-        D: Copy,
+        S: Surface,
     {
         let params = draw_parameters.unwrap_or_else(|| Self::default_draw_parameters());
 
         target
             .draw(
-                (&self.vertex_buffer, instance_data.per_instance().unwrap()),
+                (&self.vertex_buffer, instances),
                 &self.indices,
                 &self.program,
                 uniforms,
@@ -177,18 +179,12 @@ where
             .fragment_shader_source
             .ok_or(FragmentCreationError::NoGeometry)?;
 
-        let program_input = ProgramCreationInput::SourceCode {
-            vertex_shader: vertex_shader_source,
-            tessellation_control_shader: None,
-            tessellation_evaluation_shader: None,
-            geometry_shader: self.geometry_shader_source,
-            fragment_shader: fragment_shader_source,
-            transform_feedback_varyings: None,
-            outputs_srgb: false,
-            uses_point_size: false,
-        };
-
-        let program = Program::new(display, program_input).unwrap();
+        let program = create_program(
+            display,
+            vertex_shader_source,
+            self.geometry_shader_source,
+            fragment_shader_source,
+        );
 
         Ok(RenderFragment {
             vertex_buffer,
@@ -198,3 +194,49 @@ where
         })
     }
 }
+
+// Shader snippets shared between programs, a line `#include "<name>"` is replaced by the snippet
+const SHADER_INCLUDES: [(&str, &str); 1] = [(
+    "light_visualization.glsl",
+    include_str!("../shaders/light_visualization.glsl"),
+)];
+
+fn expand_includes(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| {
+            let Some(name) = line.trim().strip_prefix("#include ") else {
+                return line;
+            };
+            let name = name.trim_matches('"');
+            SHADER_INCLUDES
+                .iter()
+                .find(|(include, _)| *include == name)
+                .map(|(_, snippet)| *snippet)
+                .unwrap_or_else(|| panic!("Unknown shader include {name}"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Programs drawing many buffers, like the chunk meshes, are created without a fragment
+pub fn create_program(
+    display: &glium::Display,
+    vertex_shader: &str,
+    geometry_shader: Option<&str>,
+    fragment_shader: &str,
+) -> Program {
+    let fragment_shader = expand_includes(fragment_shader);
+    let program_input = ProgramCreationInput::SourceCode {
+        vertex_shader,
+        tessellation_control_shader: None,
+        tessellation_evaluation_shader: None,
+        geometry_shader,
+        fragment_shader: &fragment_shader,
+        transform_feedback_varyings: None,
+        outputs_srgb: false,
+        uses_point_size: false,
+    };
+
+    Program::new(display, program_input).unwrap()
+}
@@ -0,0 +1,91 @@
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::Display;
+
+use crate::camera::Camera;
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::model::discrete::World;
+
+use super::event_bus::{AppEvent, EventSubscriber, FrameContext};
+use super::RenderState;
+
+// What a plugin can look at when it updates its data
+pub struct PluginContext<'a> {
+    pub world: &'a World,
+    pub camera: &'a Camera,
+    pub state: &'a RenderState,
+    pub controls: &'a SmoothMeshOptions,
+
+    // Events dispatched this frame
+    pub events: &'a [AppEvent],
+}
+
+// A visualization drawn on top of the scene.
+//
+// Plugins are updated once per frame before rendering, the render hook draws
+// into the scene target after the terrain and the translucent blocks,
+// so the depth buffer of the terrain is available.
+pub trait RenderPlugin {
+    fn name(&self) -> &'static str;
+
+    // Called once when the plugin is registered
+    fn setup(&mut self, _display: &Display) {}
+
+    // Rebuild the gpu data that depends on the world or the options
+    fn update(&mut self, _display: &Display, _context: &PluginContext) {}
+
+    fn render(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera: &Camera,
+        state: &RenderState,
+        controls: &SmoothMeshOptions,
+    );
+}
+
+// Plugins are updated and rendered in the order they were registered
+pub struct PluginRegistry {
+    plugins: Vec<Box<dyn RenderPlugin>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        PluginRegistry {
+            plugins: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, display: &Display, mut plugin: Box<dyn RenderPlugin>) {
+        plugin.setup(display);
+        println!("[INFO] Registered render plugin {}", plugin.name());
+
+        self.plugins.push(plugin);
+    }
+
+    pub fn render(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera: &Camera,
+        state: &RenderState,
+        controls: &SmoothMeshOptions,
+    ) {
+        for plugin in &self.plugins {
+            plugin.render(target, camera, state, controls);
+        }
+    }
+}
+
+impl EventSubscriber for PluginRegistry {
+    fn handle_events(&mut self, events: &[AppEvent], context: &FrameContext) {
+        let plugin_context = PluginContext {
+            world: context.world,
+            camera: context.camera,
+            state: context.state,
+            controls: context.controls,
+            events,
+        };
+
+        for plugin in &mut self.plugins {
+            plugin.update(context.display, &plugin_context);
+        }
+    }
+}
@@ -1,26 +1,159 @@
+use glium::glutin::event::VirtualKeyCode as Key;
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Copy, Clone)]
+use super::input::InputAction;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum RenderingMode {
     Discrete,
     Implicit,
+    // Raymarch the density baked for each chunk along with its mesh
+    Raymarched,
+    // Discrete and implicit scenes split by a swipe line or subtracted from each other
+    Comparison,
+}
+
+pub const RENDERING_MODES: [RenderingMode; 4] = [
+    RenderingMode::Discrete,
+    RenderingMode::Implicit,
+    RenderingMode::Raymarched,
+    RenderingMode::Comparison,
+];
+
+// How the light levels stored in the save are shown on the terrain.
+// The integer values are passed to the shaders.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LightVisualization {
+    Off = 0,
+    // Darken the terrain based on the brightest of block and sky light
+    Tint = 1,
+    // Color the terrain by block light, dark areas are where mobs can spawn
+    Heatmap = 2,
+}
+
+impl LightVisualization {
+    pub fn next(self) -> Self {
+        match self {
+            LightVisualization::Off => LightVisualization::Tint,
+            LightVisualization::Tint => LightVisualization::Heatmap,
+            LightVisualization::Heatmap => LightVisualization::Off,
+        }
+    }
+}
+
+// How the scene is drawn, switched at runtime from the keyboard or the UI
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderSettings {
+    pub render_wireframe: bool,
+    pub render_mode: RenderingMode,
+    pub light_visualization: LightVisualization,
+}
+
+impl RenderSettings {
+    fn apply(&mut self, command: RenderCommand) {
+        match command {
+            RenderCommand::ToggleWireframe => self.render_wireframe = !self.render_wireframe,
+            RenderCommand::SetMode(mode) => self.render_mode = mode,
+            RenderCommand::NextLightVisualization => {
+                self.light_visualization = self.light_visualization.next()
+            }
+        }
+    }
 }
 
+// A change to the render settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderCommand {
+    ToggleWireframe,
+    SetMode(RenderingMode),
+    NextLightVisualization,
+}
+
+pub const RENDER_KEY_BINDINGS: [(Key, RenderCommand); 6] = [
+    (Key::B, RenderCommand::ToggleWireframe),
+    (Key::U, RenderCommand::SetMode(RenderingMode::Discrete)),
+    (Key::I, RenderCommand::SetMode(RenderingMode::Implicit)),
+    (Key::R, RenderCommand::SetMode(RenderingMode::Raymarched)),
+    (Key::C, RenderCommand::SetMode(RenderingMode::Comparison)),
+    (Key::L, RenderCommand::NextLightVisualization),
+];
+
+// State of the current frame, a copy that doesn't change while the frame is built
 #[derive(Debug, Copy, Clone)]
 pub struct RenderState {
     pub timing: Timing,
     pub cursor_captured: bool,
-    pub render_wireframe: bool,
-    pub render_mode: RenderingMode,
+    pub settings: RenderSettings,
 }
 
-impl RenderState {
+// Owns the render state, the input and the UI only request changes to it.
+//
+// The requests are applied all at once at the start of a frame,
+// so the UI, the input handling and the renderer see the same state for the whole frame.
+pub struct RenderStateManager {
+    state: RenderState,
+    // Made by the UI, which is built after the frame started
+    requested: Vec<RenderCommand>,
+}
+
+impl RenderStateManager {
     pub fn new() -> Self {
-        RenderState {
-            timing: Timing::new(),
-            cursor_captured: false,
-            render_wireframe: false,
-            render_mode: RenderingMode::Discrete,
+        RenderStateManager {
+            state: RenderState {
+                timing: Timing::new(),
+                cursor_captured: false,
+                settings: RenderSettings {
+                    render_wireframe: false,
+                    render_mode: RenderingMode::Discrete,
+                    light_visualization: LightVisualization::Off,
+                },
+            },
+            requested: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> RenderState {
+        self.state
+    }
+
+    pub fn record_frame(&mut self) {
+        self.state.timing.record_frame();
+    }
+
+    // Applied at the start of the next frame
+    pub fn request(&mut self, command: RenderCommand) {
+        self.requested.push(command);
+    }
+
+    // Applies the requests of the last frame, then the key bindings and cursor captures
+    // pressed since. Returns the new settings if they changed
+    pub fn begin_frame(&mut self, actions: &[InputAction]) -> Option<RenderSettings> {
+        let previous = self.state.settings;
+
+        let pressed = actions.iter().filter_map(|action| match action {
+            InputAction::KeyPressed { key } => RENDER_KEY_BINDINGS
+                .iter()
+                .find(|(bound_key, _)| bound_key == key)
+                .map(|(_, command)| *command),
+            _ => None,
+        });
+        let commands = std::mem::take(&mut self.requested)
+            .into_iter()
+            .chain(pressed)
+            .collect::<Vec<_>>();
+        for command in commands {
+            self.state.settings.apply(command);
+        }
+
+        for action in actions {
+            if let InputAction::Capture = action {
+                self.state.cursor_captured = !self.state.cursor_captured;
+            }
+        }
+
+        match self.state.settings != previous {
+            true => Some(self.state.settings),
+            false => None,
         }
     }
 }
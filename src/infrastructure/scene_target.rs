@@ -0,0 +1,78 @@
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, MipmapsOption, SrgbFormat, SrgbTexture2d};
+use glium::uniforms::MagnifySamplerFilter;
+use glium::{BlitTarget, Display, Surface};
+
+// Offscreen target the scene is rendered into before it is scaled up to the window,
+// rendering at a fraction of the window resolution is the cheapest way to save GPU time
+pub struct SceneTarget {
+    color: SrgbTexture2d,
+    depth: DepthRenderBuffer,
+    dimensions: (u32, u32),
+}
+
+impl SceneTarget {
+    pub fn new(display: &Display, dimensions: (u32, u32)) -> Self {
+        let (width, height) = dimensions;
+        let color = SrgbTexture2d::empty_with_format(
+            display,
+            SrgbFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+        )
+        .expect("failed to create scene texture");
+        let depth = DepthRenderBuffer::new(display, DepthFormat::I24, width, height)
+            .expect("failed to create scene depth buffer");
+
+        SceneTarget {
+            color,
+            depth,
+            dimensions,
+        }
+    }
+
+    // Recreates the target when the window size or the render scale change
+    pub fn fit(&mut self, display: &Display, render_scale: f32) {
+        let (width, height) = display.get_framebuffer_dimensions();
+        let dimensions = (
+            ((width as f32 * render_scale) as u32).max(1),
+            ((height as f32 * render_scale) as u32).max(1),
+        );
+
+        if dimensions != self.dimensions {
+            *self = SceneTarget::new(display, dimensions);
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    // Shared with the order independent transparency targets, see translucency.rs
+    pub fn depth(&self) -> &DepthRenderBuffer {
+        &self.depth
+    }
+
+    pub fn surface(&self, display: &Display) -> SimpleFrameBuffer<'_> {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.color, &self.depth)
+            .expect("failed to create scene framebuffer")
+    }
+
+    // Stretches the rendered scene over the whole *target*
+    pub fn present<S: Surface>(&self, display: &Display, target: &S) {
+        let (width, height) = target.get_dimensions();
+        let whole_target = BlitTarget {
+            left: 0,
+            bottom: 0,
+            width: width as i32,
+            height: height as i32,
+        };
+
+        self.surface(display).blit_whole_color_to(
+            target,
+            &whole_target,
+            MagnifySamplerFilter::Linear,
+        );
+    }
+}
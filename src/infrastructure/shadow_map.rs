@@ -0,0 +1,258 @@
+use std::ops::Range;
+
+use cgmath::{
+    ortho, EuclideanSpace, InnerSpace, Matrix4, MetricSpace, Point3, SquareMatrix, Transform,
+    Vector3,
+};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::IndexBuffer;
+use glium::program::Program;
+use glium::texture::DepthTexture2d;
+use glium::uniforms::{
+    DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior,
+    SamplerWrapFunction, UniformValue, Uniforms,
+};
+use glium::{uniform, Display, Surface, VertexBuffer};
+
+use crate::camera::Camera;
+use crate::config;
+use crate::geometry;
+use crate::model::chunk::BlockData;
+use crate::model::Real;
+use crate::to_uniform_matrix;
+
+use super::chunk_meshes::ChunkMeshScene;
+use super::render_fragment::create_program;
+use super::vertex::TexturedVertex;
+
+const SHADOW_BLOCKS_VS: &str = include_str!("../shaders/shadow_blocks_vs.glsl");
+const SHADOW_MESH_VS: &str = include_str!("../shaders/shadow_mesh_vs.glsl");
+const SHADOW_FS: &str = include_str!("../shaders/shadow_fs.glsl");
+
+pub const CASCADES: usize = config::SHADOW_CASCADE_ENDS.len();
+
+// The names the cascades are bound by in the fragment shaders
+const MAP_NAMES: [&str; CASCADES] = ["shadow_map_0", "shadow_map_1", "shadow_map_2"];
+const MATRIX_NAMES: [&str; CASCADES] = ["shadow_matrix_0", "shadow_matrix_1", "shadow_matrix_2"];
+
+// What is drawn into the shadow maps
+pub enum ShadowCaster<'a> {
+    // Instanced blocks, only the instances in the ranges when they are given
+    Blocks(&'a VertexBuffer<BlockData>, Option<&'a [Range<usize>]>),
+    // The shown chunk meshes, only their geometry is bound
+    Meshes(&'a ChunkMeshScene),
+}
+
+// Depth of the terrain seen from the sun, in a few cascades of growing size.
+//
+// Cascade i covers everything closer to the camera than config::SHADOW_CASCADE_ENDS[i],
+// the near cascades are sharp and the far ones cover the rest of the view. Each one is fit
+// around the bounding sphere of its part of the view frustum, so it keeps its size as the camera
+// turns, and it moves in whole texels, so the edges of the shadows don't shimmer as it moves.
+pub struct ShadowCascades {
+    maps: [DepthTexture2d; CASCADES],
+    // Scene space to the clip space of the sun, per cascade
+    light_matrices: [[[f32; 4]; 4]; CASCADES],
+    block_program: Program,
+    mesh_program: Program,
+    cube_vertices: VertexBuffer<TexturedVertex>,
+    cube_indices: IndexBuffer<u32>,
+}
+
+impl ShadowCascades {
+    pub fn new(display: &Display) -> Self {
+        let resolution = config::SHADOW_MAP_RESOLUTION;
+        let (cube_vertices, cube_indices) = geometry::cube_textured_exclusive_vertex(display);
+
+        ShadowCascades {
+            maps: array_init::array_init(|_| {
+                DepthTexture2d::empty(display, resolution, resolution)
+                    .expect("failed to create a shadow map")
+            }),
+            light_matrices: [Matrix4::<f32>::identity().into(); CASCADES],
+            block_program: create_program(display, SHADOW_BLOCKS_VS, None, SHADOW_FS),
+            mesh_program: create_program(display, SHADOW_MESH_VS, None, SHADOW_FS),
+            cube_vertices,
+            cube_indices,
+        }
+    }
+
+    // Fits the cascades around the view of *camera*, lit from *sun_position*
+    pub fn update(&mut self, camera: &Camera, sun_position: [f32; 3]) {
+        let [x, y, z] = sun_position.map(Real::from);
+        let sun_direction = (Point3::new(x, y, z) - camera.get_position()).normalize();
+
+        let mut near = config::Z_NEAR;
+        for (index, far) in config::SHADOW_CASCADE_ENDS.into_iter().enumerate() {
+            let far = Real::from(far);
+            let light_matrix = fit_cascade(camera, near, far, sun_direction);
+            self.light_matrices[index] = to_uniform_matrix(&light_matrix);
+            near = far;
+        }
+    }
+
+    pub fn render(&self, display: &Display, model: [[f32; 4]; 4], casters: &[ShadowCaster]) {
+        // The slope scaled offset keeps the lit surfaces from shadowing themselves
+        let draw_parameters = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            polygon_offset: glium::draw_parameters::PolygonOffset {
+                factor: 2.0,
+                units: 4.0,
+                fill: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for (map, light_matrix) in self.maps.iter().zip(self.light_matrices) {
+            let mut target = SimpleFrameBuffer::depth_only(display, map)
+                .expect("failed to create the shadow map framebuffer");
+            target.clear_depth(1.0);
+
+            let uniforms = uniform! {
+                light_matrix: light_matrix,
+                model: model,
+            };
+
+            for caster in casters {
+                match caster {
+                    ShadowCaster::Blocks(instances, ranges) => {
+                        let all = [0..instances.len()];
+                        for range in ranges.unwrap_or(&all) {
+                            let Some(slice) = instances.slice(range.clone()) else {
+                                continue;
+                            };
+                            let vertices = (&self.cube_vertices, slice.per_instance().unwrap());
+                            target
+                                .draw(
+                                    vertices,
+                                    &self.cube_indices,
+                                    &self.block_program,
+                                    &uniforms,
+                                    &draw_parameters,
+                                )
+                                .unwrap();
+                        }
+                    }
+                    ShadowCaster::Meshes(scene) => {
+                        for buffers in scene.shown_buffers() {
+                            buffers.draw_geometry(
+                                &mut target,
+                                &self.mesh_program,
+                                &uniforms,
+                                &draw_parameters,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // *uniforms* of a pass drawing the world together with the cascades,
+    // the shaders skip the lookup when *enabled* is false
+    pub fn with_uniforms<'a, U: Uniforms>(
+        &'a self,
+        uniforms: &'a U,
+        enabled: bool,
+    ) -> ShadowUniforms<'a, U> {
+        ShadowUniforms {
+            uniforms,
+            cascades: self,
+            enabled,
+        }
+    }
+}
+
+pub struct ShadowUniforms<'a, U> {
+    uniforms: &'a U,
+    cascades: &'a ShadowCascades,
+    enabled: bool,
+}
+
+impl<U: Uniforms> Uniforms for ShadowUniforms<'_, U> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut visit: F) {
+        self.uniforms.visit_values(&mut visit);
+
+        // Compared with the depth of the fragment, the maps are sampled as sampler2DShadow
+        let sampler = SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            depth_texture_comparison: Some(DepthTextureComparison::LessOrEqual),
+            ..Default::default()
+        };
+
+        visit("shadows", UniformValue::Bool(self.enabled));
+        visit(
+            "cascade_ends",
+            UniformValue::Vec3(config::SHADOW_CASCADE_ENDS),
+        );
+        for (index, map) in self.cascades.maps.iter().enumerate() {
+            let matrix = self.cascades.light_matrices[index];
+            visit(
+                MAP_NAMES[index],
+                UniformValue::DepthTexture2d(map, Some(sampler)),
+            );
+            visit(MATRIX_NAMES[index], UniformValue::Mat4(matrix));
+        }
+    }
+}
+
+// Orthographic view of the sun around the part of the view frustum between *near* and *far*
+fn fit_cascade(
+    camera: &Camera,
+    near: Real,
+    far: Real,
+    sun_direction: Vector3<Real>,
+) -> Matrix4<Real> {
+    let tan_y = (camera.fovy().0 / 2.0).tan();
+    let tan_x = tan_y * camera.aspect_ratio();
+
+    // The view looks down -z
+    let corners = [near, far]
+        .into_iter()
+        .flat_map(|depth| {
+            [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+                .map(|(x, y)| Point3::new(x * tan_x * depth, y * tan_y * depth, -depth))
+        })
+        .map(|corner| camera.view_to_world.transform_point(corner))
+        .collect::<Vec<_>>();
+    let center = Point3::centroid(&corners);
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, Real::max)
+        .ceil();
+
+    let light_view = Matrix4::look_to_rh(Point3::origin(), -sun_direction, Vector3::unit_y());
+
+    // Whole texels of the map, in the units of the scene
+    let texel = 2.0 * radius / config::SHADOW_MAP_RESOLUTION as Real;
+    let center = light_view.transform_point(center);
+    let (x, y) = (
+        (center.x / texel).floor() * texel,
+        (center.y / texel).floor() * texel,
+    );
+
+    // Terrain between the sun and the cascade casts shadows into it,
+    // so the depth range reaches further towards the sun
+    let projection = ortho(
+        x - radius,
+        x + radius,
+        y - radius,
+        y + radius,
+        -center.z - radius - config::SHADOW_CASTER_DISTANCE,
+        -center.z + radius,
+    );
+
+    projection * light_view
+}
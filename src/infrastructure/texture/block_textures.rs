@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use image::imageops::{self, FilterType};
+use image::{ImageResult, RgbaImage};
+
+use crate::assets;
+use crate::config;
+use crate::model::common::{BlockType, ALL_BLOCK_TYPES};
+
+// Tiles of the fallback pallette, counted from the bottom left
+const PALLETTE_FILE: &str = "block-palette.png";
+const PALLETTE_SIZE: u32 = 4;
+fn pallette_tile(block_type: BlockType) -> (u32, u32) {
+    match block_type {
+        BlockType::Grass => (0, 3),
+        BlockType::Dirt => (0, 2),
+        BlockType::Stone => (1, 3),
+        BlockType::Sand => (2, 3),
+        BlockType::Ore => (3, 3),
+        BlockType::Wood => (1, 2),
+        BlockType::Leaves => (2, 2),
+        BlockType::Water => (3, 2),
+        BlockType::Lava => (0, 1),
+        BlockType::Planks => (1, 1),
+        BlockType::DarkStone => (2, 1),
+        BlockType::RedSand => (3, 1),
+        BlockType::Cobblestone => (0, 0),
+        BlockType::Glass => (1, 0),
+        BlockType::Cactus => (2, 0),
+        _ => (3, 0),
+    }
+}
+
+// Texture of every block type, indexed by its integer value like the layers of the texture array.
+// A block takes its texture from config::BLOCK_TEXTURE_FOLDER/<name>.png in the asset folders,
+// e.g. assets/blocks/darkstone.png, blocks without one take their tile from the pallette.
+// The pallette is compiled in, see assets.rs.
+// The images are ordered top-down, all of them are config::BLOCK_TEXTURE_RESOLUTION pixels wide and high.
+pub struct BlockTextures {
+    images: Vec<RgbaImage>,
+}
+
+impl BlockTextures {
+    pub fn load() -> ImageResult<Self> {
+        let pallette = image::load_from_memory(&assets::read(PALLETTE_FILE)?)?.to_rgba8();
+        let tile_size = pallette.width() / PALLETTE_SIZE;
+
+        let mut images = Vec::with_capacity(ALL_BLOCK_TYPES.len());
+        for block_type in ALL_BLOCK_TYPES {
+            let name = format!("{block_type:?}").to_lowercase();
+            let file_path = Path::new(config::BLOCK_TEXTURE_FOLDER).join(format!("{name}.png"));
+
+            let image = if let Some(file_path) = assets::find(file_path) {
+                image::open(file_path)?.to_rgba8()
+            } else {
+                let (tile_x, tile_y) = pallette_tile(block_type);
+                let top = (PALLETTE_SIZE - 1 - tile_y) * tile_size;
+                imageops::crop_imm(&pallette, tile_x * tile_size, top, tile_size, tile_size)
+                    .to_image()
+            };
+
+            // Layers of a texture array share their size
+            let resolution = config::BLOCK_TEXTURE_RESOLUTION;
+            let image = if image.dimensions() != (resolution, resolution) {
+                println!(
+                    "[INFO] Texture of {name} is {}x{}, it is scaled to {resolution}x{resolution}",
+                    image.width(),
+                    image.height()
+                );
+                imageops::resize(&image, resolution, resolution, FilterType::Nearest)
+            } else {
+                image
+            };
+
+            images.push(image);
+        }
+
+        Ok(BlockTextures { images })
+    }
+
+    pub fn images(&self) -> &[RgbaImage] {
+        &self.images
+    }
+}
@@ -0,0 +1,90 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use glium::texture::{ClientFormat, MipmapsOption, RawImage3d, Texture3d, UncompressedFloatFormat};
+
+use crate::model::implicit::density_volume::DensityVolume;
+
+// Sampled terrain density uploaded to the GPU together with the area it covers.
+// Densities are stored as R16F, the precision is plenty for a value that is interpolated anyway.
+pub struct DensityTexture {
+    pub texture: Texture3d,
+    pub origin: [f32; 3],
+    pub extent: [f32; 3],
+    pub resolution: [f32; 3],
+}
+
+impl DensityTexture {
+    pub fn new(volume: &DensityVolume, facade: &glium::Display) -> Self {
+        let [width, height, depth] = volume.resolution;
+        let data_source = RawImage3d {
+            data: Cow::Borrowed(volume.values.as_slice()),
+            width: width as u32,
+            height: height as u32,
+            depth: depth as u32,
+            format: ClientFormat::F32,
+        };
+
+        let texture = match Texture3d::with_format(
+            facade,
+            data_source,
+            UncompressedFloatFormat::F16,
+            MipmapsOption::NoMipmap,
+        ) {
+            Ok(tex) => tex,
+            Err(texture_creation_error) => {
+                panic!("failed to create density texture - {texture_creation_error}!")
+            }
+        };
+
+        DensityTexture {
+            texture,
+            origin: [
+                volume.origin.x as f32,
+                volume.origin.y as f32,
+                volume.origin.z as f32,
+            ],
+            extent: volume.extent(),
+            resolution: volume.resolution.map(|samples| samples as f32),
+        }
+    }
+}
+
+// Density textures of the chunks with a baked density
+pub struct ChunkDensityTextures {
+    textures: Vec<(Arc<DensityVolume>, DensityTexture)>,
+}
+
+impl ChunkDensityTextures {
+    pub fn new() -> Self {
+        ChunkDensityTextures {
+            textures: Vec::new(),
+        }
+    }
+
+    // Uploads the volumes that don't have a texture yet and drops textures of volumes
+    // that are gone, a volume is replaced whenever its chunk is baked again
+    pub fn update(&mut self, volumes: Vec<Arc<DensityVolume>>, facade: &glium::Display) {
+        let mut previous = std::mem::take(&mut self.textures);
+
+        self.textures = volumes
+            .into_iter()
+            .map(|volume| {
+                let uploaded = previous
+                    .iter()
+                    .position(|(uploaded, _)| Arc::ptr_eq(uploaded, &volume));
+                match uploaded {
+                    Some(index) => previous.swap_remove(index),
+                    None => {
+                        let texture = DensityTexture::new(&volume, facade);
+                        (volume, texture)
+                    }
+                }
+            })
+            .collect();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DensityTexture> {
+        self.textures.iter().map(|(_, texture)| texture)
+    }
+}
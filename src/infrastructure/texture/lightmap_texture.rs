@@ -0,0 +1,50 @@
+use std::borrow::Cow;
+
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+
+use crate::model::lightmap::Lightmap;
+
+// Baked light of the smooth mesh uploaded to the GPU, stored as RGB16F since the light
+// of sunlit texels goes above 1.0. Until something is baked a single black texel stands in.
+pub struct LightmapTexture {
+    pub texture: Texture2d,
+    pub baked: bool,
+}
+
+impl LightmapTexture {
+    pub fn new(lightmap: &Lightmap, facade: &glium::Display) -> Self {
+        let texels = lightmap.texels.concat();
+        LightmapTexture {
+            texture: upload(&texels, lightmap.width, lightmap.height, facade),
+            baked: true,
+        }
+    }
+
+    pub fn empty(facade: &glium::Display) -> Self {
+        LightmapTexture {
+            texture: upload(&[0.0; 3], 1, 1, facade),
+            baked: false,
+        }
+    }
+}
+
+fn upload(texels: &[f32], width: usize, height: usize, facade: &glium::Display) -> Texture2d {
+    let data_source = RawImage2d {
+        data: Cow::Borrowed(texels),
+        width: width as u32,
+        height: height as u32,
+        format: ClientFormat::F32F32F32,
+    };
+
+    match Texture2d::with_format(
+        facade,
+        data_source,
+        UncompressedFloatFormat::F16F16F16,
+        MipmapsOption::NoMipmap,
+    ) {
+        Ok(texture) => texture,
+        Err(texture_creation_error) => {
+            panic!("failed to create lightmap texture - {texture_creation_error}!")
+        }
+    }
+}
@@ -1,13 +1,24 @@
 use crate::model::{
-    common::{activation_treshold, BlockType, BLOCK_TYPES},
+    biome::Biome,
+    common::{BlockType, BLOCK_TYPES},
+    material_registry::MaterialRegistry,
     Real,
 };
 use array_init::array_init;
 
 type MaterialWeights = [Real; BLOCK_TYPES];
+
+// Share of every material in the blend before the activation thresholds are applied,
+// meshes keep it so their weights can be computed again when the thresholds change
+pub type MaterialSample = [[f32; 4]; 4];
+
 pub struct MaterialBlend {
     material_contributions: MaterialWeights,
     contributed: Real,
+
+    // Biome tints of the grass and leaves in the blend, weighted by their amount
+    biome_tint: [Real; 3],
+    tinted: Real,
 }
 
 impl MaterialBlend {
@@ -15,6 +26,8 @@ impl MaterialBlend {
         MaterialBlend {
             material_contributions: [0.0; BLOCK_TYPES],
             contributed: 0.0,
+            biome_tint: [0.0; 3],
+            tinted: 0.0,
         }
     }
 
@@ -25,6 +38,13 @@ impl MaterialBlend {
         self.contributed += amount;
     }
 
+    pub fn mix_biome(&mut self, biome: Biome, amount: Real) {
+        for (channel, tint) in self.biome_tint.iter_mut().zip(biome.tint()) {
+            *channel += tint as Real * amount;
+        }
+        self.tinted += amount;
+    }
+
     pub fn merge(&mut self, other: MaterialBlend) {
         for (base, other_val) in self
             .material_contributions
@@ -35,17 +55,31 @@ impl MaterialBlend {
         }
 
         self.contributed += other.contributed;
-    }
 
-    pub fn into_material_weights(self) -> [[f32; 4]; 4] {
-        let mut weights_flat = self.normalized_weights();
+        for (channel, other_channel) in self.biome_tint.iter_mut().zip(other.biome_tint) {
+            *channel += other_channel;
+        }
+        self.tinted += other.tinted;
+    }
 
-        let redistribute = Self::has_active_materials(&weights_flat);
-        if redistribute {
-            Self::redistribute_inactive_weights(&mut weights_flat);
+    // Average tint of the grass and leaves, blends without them are not tinted
+    pub fn biome_tint(&self) -> [f32; 3] {
+        if self.tinted <= 0.0 {
+            return Biome::Temperate.tint();
         }
 
-        let weights = array_init(|col| {
+        self.biome_tint
+            .map(|channel| (channel / self.tinted) as f32)
+    }
+
+    pub fn into_material_weights(self, materials: &MaterialRegistry) -> [[f32; 4]; 4] {
+        Self::activate_materials(&self.into_material_sample(), materials)
+    }
+
+    pub fn into_material_sample(self) -> MaterialSample {
+        let weights_flat = self.normalized_weights();
+
+        array_init(|col| {
             array_init(|row| {
                 let contribution_index = col * 4 + row;
 
@@ -56,9 +90,25 @@ impl MaterialBlend {
                     0.0
                 }
             })
+        })
+    }
+
+    // Weights of the materials shown on the surface, with the activation thresholds of *materials*
+    pub fn activate_materials(
+        sample: &MaterialSample,
+        materials: &MaterialRegistry,
+    ) -> [[f32; 4]; 4] {
+        let mut weights_flat: MaterialWeights = array_init(|i| match sample.get(i / 4) {
+            Some(column) => column[i % 4] as Real,
+            None => 0.0,
         });
 
-        return weights;
+        let redistribute = Self::has_active_materials(&weights_flat, materials);
+        if redistribute {
+            Self::redistribute_inactive_weights(&mut weights_flat, materials);
+        }
+
+        array_init(|col| array_init(|row| weights_flat[col * 4 + row] as f32))
     }
 
     fn normalized_weights(self) -> MaterialWeights {
@@ -70,10 +120,10 @@ impl MaterialBlend {
         })
     }
 
-    fn has_active_materials(weights: &[Real; BLOCK_TYPES]) -> bool {
+    fn has_active_materials(weights: &[Real; BLOCK_TYPES], materials: &MaterialRegistry) -> bool {
         let any_active_materials = weights.iter().enumerate().any(|(i, w)| {
             if let Ok(block_type) = i.try_into() {
-                let treshold = activation_treshold(block_type);
+                let treshold = materials.activation_threshold(block_type);
                 *w > treshold
             } else {
                 false
@@ -83,7 +133,10 @@ impl MaterialBlend {
         any_active_materials
     }
 
-    fn redistribute_inactive_weights(weights_flat: &mut MaterialWeights) {
+    fn redistribute_inactive_weights(
+        weights_flat: &mut MaterialWeights,
+        materials: &MaterialRegistry,
+    ) {
         let mut redistributed_weight = 0.0;
         let mut dominant_material_index = 0;
 
@@ -93,7 +146,7 @@ impl MaterialBlend {
             };
 
             let w = weights_flat[material_index];
-            let treshold = activation_treshold(block_type);
+            let treshold = materials.activation_threshold(block_type);
 
             let material_active = w > treshold;
             if material_active {
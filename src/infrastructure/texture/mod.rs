@@ -1,4 +1,8 @@
+mod block_textures;
+pub mod density_texture;
+pub mod lightmap_texture;
 mod material_blend;
 pub mod texture_loader;
 
-pub use material_blend::MaterialBlend;
+pub use block_textures::BlockTextures;
+pub use material_blend::{MaterialBlend, MaterialSample};
@@ -0,0 +1,87 @@
+use glium::framebuffer::{DepthRenderBuffer, MultiOutputFrameBuffer};
+use glium::texture::{MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::{BlendingFunction, Display, LinearBlendingFactor};
+
+// How the translucent blocks are drawn over the opaque scene
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranslucencyMode {
+    // Blended straight into the scene, the chunks are sorted back to front
+    Sorted = 1,
+    // Accumulated into the OIT targets and composited afterwards, no sorting needed
+    WeightedBlended = 2,
+}
+
+// Targets of weighted blended order independent transparency (McGuire and Bavoil 2013).
+//
+// The accumulation target sums the weighted premultiplied colors in rgb and multiplies
+// the transparency of all surfaces in alpha, the weight target sums the weights.
+// Both use the depth buffer of the scene so translucent blocks behind the terrain are hidden.
+pub struct OitTargets {
+    pub accumulation: Texture2d,
+    pub weights: Texture2d,
+    dimensions: (u32, u32),
+}
+
+impl OitTargets {
+    pub fn new(display: &Display, dimensions: (u32, u32)) -> Self {
+        let (width, height) = dimensions;
+        let float_texture = |format| {
+            Texture2d::empty_with_format(display, format, MipmapsOption::NoMipmap, width, height)
+                .expect("failed to create transparency texture")
+        };
+
+        OitTargets {
+            accumulation: float_texture(UncompressedFloatFormat::F16F16F16F16),
+            weights: float_texture(UncompressedFloatFormat::F16),
+            dimensions,
+        }
+    }
+
+    // The targets have to match the scene target they share the depth buffer with
+    pub fn fit(&mut self, display: &Display, dimensions: (u32, u32)) {
+        if dimensions != self.dimensions {
+            *self = OitTargets::new(display, dimensions);
+        }
+    }
+
+    // The outputs are bound by the names they have in discrete_fs.glsl
+    pub fn surface<'a>(
+        &'a self,
+        display: &Display,
+        depth: &'a DepthRenderBuffer,
+    ) -> MultiOutputFrameBuffer<'a> {
+        let outputs = [("color", &self.accumulation), ("oit_weight", &self.weights)];
+        MultiOutputFrameBuffer::with_depth_buffer(display, outputs, depth)
+            .expect("failed to create transparency framebuffer")
+    }
+}
+
+// Colors and weights add up, alpha keeps the product of the transparencies
+pub fn accumulation_blending() -> glium::Blend {
+    glium::Blend {
+        color: BlendingFunction::Addition {
+            source: LinearBlendingFactor::One,
+            destination: LinearBlendingFactor::One,
+        },
+        alpha: BlendingFunction::Addition {
+            source: LinearBlendingFactor::Zero,
+            destination: LinearBlendingFactor::OneMinusSourceAlpha,
+        },
+        constant_value: (0.0, 0.0, 0.0, 0.0),
+    }
+}
+
+// The composite shader outputs the average color and the remaining transparency in alpha,
+// the scene shows through by that transparency
+pub fn composite_blending() -> glium::Blend {
+    let over = BlendingFunction::Addition {
+        source: LinearBlendingFactor::OneMinusSourceAlpha,
+        destination: LinearBlendingFactor::SourceAlpha,
+    };
+
+    glium::Blend {
+        color: over,
+        alpha: over,
+        constant_value: (0.0, 0.0, 0.0, 0.0),
+    }
+}
@@ -0,0 +1,62 @@
+use cgmath::{InnerSpace, Matrix4, Vector3};
+
+use crate::config;
+use crate::model::{Position, Real};
+
+// Exaggeration of the heights of the drawn world.
+//
+// The world is scaled along y around config::VERTICAL_SCALE_PIVOT by the model matrix of every
+// pass drawing world geometry. The camera moves through the scaled scene, so whatever relates
+// the camera to the world, like picking, goes through to_world and to_scene.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VerticalScale {
+    factor: Real,
+}
+
+impl VerticalScale {
+    pub fn new(factor: f32) -> Self {
+        VerticalScale {
+            factor: factor as Real,
+        }
+    }
+
+    pub fn model_matrix(&self) -> Matrix4<Real> {
+        let pivot = Vector3::new(0.0, config::VERTICAL_SCALE_PIVOT, 0.0);
+
+        Matrix4::from_translation(pivot)
+            * Matrix4::from_nonuniform_scale(1.0, self.factor, 1.0)
+            * Matrix4::from_translation(-pivot)
+    }
+
+    pub fn to_scene(&self, position: Position) -> Position {
+        let pivot = config::VERTICAL_SCALE_PIVOT;
+        Position::new(
+            position.x,
+            pivot + (position.y - pivot) * self.factor,
+            position.z,
+        )
+    }
+
+    pub fn to_world(&self, position: Position) -> Position {
+        let pivot = config::VERTICAL_SCALE_PIVOT;
+        Position::new(
+            position.x,
+            pivot + (position.y - pivot) / self.factor,
+            position.z,
+        )
+    }
+
+    // Directions are only scaled, they are not normalized
+    pub fn direction_to_scene(&self, direction: Vector3<Real>) -> Vector3<Real> {
+        Vector3::new(direction.x, direction.y * self.factor, direction.z)
+    }
+
+    pub fn direction_to_world(&self, direction: Vector3<Real>) -> Vector3<Real> {
+        Vector3::new(direction.x, direction.y / self.factor, direction.z)
+    }
+
+    // Normals are scaled by the inverse transpose of the model matrix
+    pub fn normal_to_scene(&self, normal: Vector3<Real>) -> Vector3<Real> {
+        self.direction_to_world(normal).normalize()
+    }
+}
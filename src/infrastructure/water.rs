@@ -0,0 +1,66 @@
+use std::ops::Range;
+
+use glium::index::IndexBuffer;
+use glium::program::Program;
+use glium::uniforms::Uniforms;
+use glium::{Display, DrawParameters, Surface, VertexBuffer};
+
+use crate::geometry;
+use crate::model::chunk::BlockData;
+
+use super::render_fragment::create_program;
+use super::vertex::TexturedVertex;
+
+// Rigid water blocks, drawn in a pass of their own after everything opaque.
+//
+// The blocks are instances in the buffer of the rigid blocks, the pass only brings the program
+// with the water surface: fresnel reflection of the sky and ripples scrolling over the normal.
+// Water smoothed together with the terrain is part of the opaque chunk meshes and isn't drawn here.
+pub struct WaterScene {
+    program: Program,
+    vertices: VertexBuffer<TexturedVertex>,
+    indices: IndexBuffer<u32>,
+    // Clusters of the water blocks in the instance buffer, back to front
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl WaterScene {
+    pub fn new(display: &Display, vertex_shader: &str, fragment_shader: &str) -> Self {
+        let (vertices, indices) = geometry::cube_textured_exclusive_vertex(display);
+
+        WaterScene {
+            program: create_program(display, vertex_shader, None, fragment_shader),
+            vertices,
+            indices,
+            ranges: Vec::new(),
+        }
+    }
+
+    // Draws the water blocks of *instances* one range after another, in their order
+    pub fn draw<U, S>(
+        &self,
+        target: &mut S,
+        uniforms: &U,
+        draw_parameters: &DrawParameters,
+        instances: &VertexBuffer<BlockData>,
+    ) where
+        U: Uniforms,
+        S: Surface,
+    {
+        for range in &self.ranges {
+            let Some(slice) = instances.slice(range.clone()) else {
+                continue;
+            };
+            let vertices = (&self.vertices, slice.per_instance().unwrap());
+            target
+                .draw(
+                    vertices,
+                    &self.indices,
+                    &self.program,
+                    uniforms,
+                    draw_parameters,
+                )
+                .unwrap();
+        }
+    }
+}
@@ -1,269 +1,3056 @@
 use glium::glutin::event::{Event, WindowEvent};
 use glium::glutin::event_loop::{ControlFlow, EventLoop};
-use glium::index::IndicesSource;
-use glium::texture::SrgbTexture2d;
-use glium::{uniform, Display, Frame, IndexBuffer, Surface};
+use glium::texture::{SrgbTexture2dArray, Texture2d};
+use glium::{uniform, Display, IndexBuffer, Surface};
 
-use glium::glutin::event::VirtualKeyCode;
+use glium::glutin::event::{MouseButton, VirtualKeyCode};
 use glium::glutin::window::CursorGrabMode;
 use glium::glutin::window::Window;
 
 use array_init::array_init;
-use cgmath::{Matrix4, Vector3};
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix4, Point2, Point3, SquareMatrix, Transform, Vector3,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
 
 mod imgui_wrapper;
-use imgui_wrapper::{ImguiWrapper, SmoothMeshOptions, UIWindowBuilder};
+use imgui_wrapper::{ChunkCommand, ImguiWrapper, ScanCommand, SmoothMeshOptions, UIWindowBuilder};
 
 mod minecraft;
 
 mod camera;
 use camera::Camera;
 
+mod block_composition;
+use block_composition::{BlockComposition, MaterialCounts};
+
+mod profile_tool;
+use profile_tool::ProfileTool;
+
+mod sculpt_tool;
+use sculpt_tool::BrushMode;
+
+mod path_tool;
+use path_tool::PathTool;
+
+mod offline_render;
+use offline_render::{OfflineRender, OfflineRenderSettings, RenderView};
+
+mod map_export;
+use map_export::{MapArea, MapSettings};
+
+mod world_report;
+mod world_scanner;
+use world_report::WorldReport;
+use world_scanner::{ScanEnd, WorldScanner};
+
+mod shutdown;
+use shutdown::Shutdown;
+
+mod crash_report;
+use crash_report::CrashContext;
+
+mod camera_path;
+mod tour;
+use tour::Tour;
+
+mod scripting;
+use scripting::{ScriptConsole, ScriptHost};
+
+mod world_dialog;
+use world_dialog::WorldDialog;
+
+mod world_source;
+use world_source::WorldSource;
+
+mod assets;
+mod cli;
+use cli::CliArguments;
+
+mod settings;
+use settings::SettingsFile;
+
 mod geometry;
 
 mod infrastructure;
-use infrastructure::input::{self, InputAction, InputConsumer};
+use infrastructure::app_state::{AppState, LoadingProgress};
+use infrastructure::auto_apply::AutoApply;
+use infrastructure::chunk_meshes::ChunkMeshScene;
+use infrastructure::color_ramp::{ColorRampTexture, ALL_COLOR_RAMPS};
+use infrastructure::comparison::ComparisonTargets;
+use infrastructure::crossfade::{self, Crossfade};
+use infrastructure::debug_draw::DebugDraw;
+use infrastructure::event_bus::{AppEvent, EventBus, FrameContext};
+use infrastructure::frame_stats::{FrameStats, FrameSummary};
+use infrastructure::input::{self, InputAction, InputConsumer, UiFocus};
+use infrastructure::jobs::{self, Priority};
+use infrastructure::level_scroll::LevelScroll;
+use infrastructure::mesh_buffers::MeshBuffers;
+use infrastructure::quality_governor::QualityGovernor;
 use infrastructure::render_fragment::RenderFragmentBuilder;
-use infrastructure::texture::texture_loader::texture_from_file;
-use infrastructure::{RenderState, RenderingMode};
+use infrastructure::render_plugin::PluginRegistry;
+use infrastructure::scene_target::SceneTarget;
+use infrastructure::shadow_map::{ShadowCascades, ShadowCaster};
+use infrastructure::texture::density_texture::{ChunkDensityTextures, DensityTexture};
+use infrastructure::texture::lightmap_texture::LightmapTexture;
+use infrastructure::texture::texture_loader::{texture_array_from_images, ui_texture_from_image};
+use infrastructure::texture::BlockTextures;
+use infrastructure::translucency::{self, OitTargets, TranslucencyMode};
+use infrastructure::vertex::{TexturedVertex, Vertex2D};
+use infrastructure::vertical_scale::VerticalScale;
+use infrastructure::water::WaterScene;
+use infrastructure::{
+    RenderCommand, RenderState, RenderStateManager, RenderingMode, RENDERING_MODES,
+};
 use minecraft::get_minecraft_chunk_position;
+use minecraft::level::{self, StartLocation};
 
 mod model;
-use model::discrete::World;
+use model::bvh::WorldBvh;
+use model::chunk::ChunkPosition;
+use model::common::{is_translucent_block, BlockType, ALL_BLOCK_TYPES, BLOCK_TYPES};
+use model::discrete::{MaterialRanges, World};
+use model::export::{self, MeshFormat};
 use model::implicit::smooth::{get_density, get_smooth_normal};
-use model::polygonize::{MeshVertex, PolygonizationOptions};
-use model::{discrete, Real};
+use model::lightmap::{LightmapBake, LightmapSettings};
+use model::material_registry::{self, SmoothingPass, SMOOTHING_PASSES};
+use model::polygonize::{
+    grid_pool_stats, mc_tables, CellInspection, Mesh, OptionCorrection, PolygonizationOptions,
+    ALL_POLYGONIZATION_METHODS, WORLD_BOTTOM, WORLD_TOP,
+};
+use model::{discrete, Position, Real};
 
 mod config;
 mod scene;
-use scene::{NoInstance, RenderPass};
+use scene::{NoInstance, RenderPass, WorldScene};
 
 mod macros;
 
+mod plugins;
+use plugins::build_cost::BuildCostPlugin;
+use plugins::chunk_grid::ChunkGridPlugin;
+use plugins::generated_boundary::GeneratedBoundaryPlugin;
+use plugins::walkable_path::WalkablePathPlugin;
+use plugins::water_flow::WaterFlowPlugin;
+
 const DISCRETE_VS: &str = include_str!("shaders/discrete_vs.glsl");
 const DISCRETE_FS: &str = include_str!("shaders/discrete_fs.glsl");
 const IMPLICIT_VS: &str = include_str!("shaders/implicit_vs.glsl");
 const IMPLICIT_FS: &str = include_str!("shaders/implicit_fs.glsl");
+const RAYMARCH_VS: &str = include_str!("shaders/raymarch_vs.glsl");
+const RAYMARCH_FS: &str = include_str!("shaders/raymarch_fs.glsl");
+const COMPARISON_VS: &str = include_str!("shaders/comparison_vs.glsl");
+const COMPARISON_FS: &str = include_str!("shaders/comparison_fs.glsl");
+const OIT_COMPOSITE_FS: &str = include_str!("shaders/oit_composite_fs.glsl");
+const WATER_VS: &str = include_str!("shaders/water_vs.glsl");
+const WATER_FS: &str = include_str!("shaders/water_fs.glsl");
+
+fn main() {
+    crash_report::install_panic_hook();
+    // Parsed before anything is loaded, the assets folder given there takes precedence
+    let cli_arguments = CliArguments::parse();
+    if let Ok(CliArguments {
+        assets_dir: Some(folder),
+        ..
+    }) = &cli_arguments
+    {
+        assets::set_assets_dir(folder.clone());
+    }
+    let (event_loop, display) = create_window();
+
+    let block_textures = match BlockTextures::load() {
+        Ok(textures) => textures,
+        Err(error) => panic!("failed to load the block textures - {error}"),
+    };
+    let block_texture_array = texture_array_from_images(block_textures.images(), &display);
+
+    // Loaded before anything uses the spawn point, changes are picked up in the event loop
+    let mut settings_file = SettingsFile::new(config::SETTINGS_FILE);
+    if let Some(Err(message)) = settings_file.poll() {
+        eprintln!("[ERROR] {message}");
+    }
+
+    let mut controls = SmoothMeshOptions::default();
+    let mut polygonization_options = controls.into();
+    // Options the last APPLY had to repair, shown in the controls window
+    let mut option_corrections = Rc::new(Vec::new());
+
+    let mut imgui_data = ImguiWrapper::new(&display);
+    let ui_block_textures: [imgui::TextureId; BLOCK_TYPES] = array_init(|material| {
+        let image = &block_textures.images()[material];
+        imgui_data.register_texture(ui_texture_from_image(image, &display))
+    });
+
+    // The world dialog is skipped when the world is given on the command line,
+    // and the start location dialog as well when the spawn is given too
+    let world_dialog = Rc::new(RefCell::new(WorldDialog::new()));
+    let mut app_state = match cli_arguments {
+        Ok(CliArguments {
+            world: Some(world_path),
+            spawn,
+            ..
+        }) => {
+            let mut dialog = world_dialog.borrow_mut();
+            match open_world(&world_path, &mut dialog, None) {
+                Ok(()) => {
+                    dialog.chosen_start = spawn.or(dialog.chosen_start);
+                    AppState::ChoosingStart
+                }
+                Err(message) => {
+                    eprintln!("[ERROR] {message}");
+                    AppState::Error(message)
+                }
+            }
+        }
+        Ok(_) => AppState::ChoosingWorld,
+        Err(message) => {
+            eprintln!("[ERROR] {message}");
+            AppState::Error(message)
+        }
+    };
+    let mut loading_progress = LoadingProgress {
+        chunks_loaded: 0,
+        chunks_total: (2 * controls.load_radius).pow(2),
+        meshes_built: 0,
+        meshes_total: 0,
+    };
+
+    // The chunks are loaded once the user picks where to start
+    let mut world = discrete::World::empty(settings::current().spawn_point(), controls.load_radius);
+    let mut world_loaded = false;
+
+    let mut camera = create_camera(display.get_framebuffer_dimensions());
+
+    let (mut rigid_scene, mut rigid_ranges) = create_rigid_scene(&world, &display);
+    let mut discrete_scene = create_discrete_scene(&world, &display);
+    let implicit_scene = Rc::new(RefCell::new(create_implicit_scene(&world, &display)));
+    let mut water_scene = WaterScene::new(&display, WATER_VS, WATER_FS);
+    let mut shadow_cascades = ShadowCascades::new(&display);
+    let mut crossfade = Crossfade::new(Duration::from_secs_f32(config::MESH_CROSSFADE_SECONDS));
+    let raymarch_scene = create_raymarch_scene(&display);
+    let mut density_textures = ChunkDensityTextures::new();
+    let comparison_scene = create_comparison_scene(&display);
+    let oit_composite_scene = create_oit_composite_scene(&display);
+    let mut comparison_targets =
+        ComparisonTargets::new(&display, display.get_framebuffer_dimensions());
+    let mut changed_pixels: Option<f32> = None;
+    let mut tour: Option<Tour> = None;
+    let mut offline_render: Option<OfflineRender> = None;
+    let mut lightmap_bake: Option<LightmapBake> = None;
+    // The smooth mesh changed while the bake was running, the result is thrown away
+    let mut lightmap_bake_outdated = false;
+    let mut lightmap = LightmapTexture::empty(&display);
+    let mut world_scan: Option<WorldScanner<WorldReport>> = None;
+    let mut map_export: Option<WorldScanner<()>> = None;
+    let mut world_report: Option<Rc<WorldReport>> = None;
+    let world_bvh = Rc::new(RefCell::new(WorldBvh::new()));
+
+    let mut last_camera_position = camera.get_position();
+    let mut vertical_scale = VerticalScale::new(controls.vertical_scale);
+
+    let path_tool = Rc::new(RefCell::new(PathTool::new()));
+
+    let mut plugins = PluginRegistry::new();
+    plugins.register(&display, Box::new(ChunkGridPlugin::new()));
+    plugins.register(&display, Box::new(BuildCostPlugin::new()));
+    plugins.register(&display, Box::new(GeneratedBoundaryPlugin::new()));
+    plugins.register(&display, Box::new(WaterFlowPlugin::new()));
+    plugins.register(
+        &display,
+        Box::new(WalkablePathPlugin::new(path_tool.clone())),
+    );
+    let plugins = Rc::new(RefCell::new(plugins));
+    let mut debug_draw = DebugDraw::new(&display);
+    let mut inspected_cell: Option<Position> = None;
+
+    let mut render_states = RenderStateManager::new();
+    let mut render_state = render_states.state();
+    let mut frame_stats = FrameStats::new();
+    let mut quality_governor = QualityGovernor::new();
+    let mut level_scroll = LevelScroll::new();
+    let mut auto_apply = AutoApply::new();
+    let mut view_distance_apply = AutoApply::new();
+    let mut scene_target = SceneTarget::new(&display, display.get_framebuffer_dimensions());
+    let mut oit_targets = OitTargets::new(&display, scene_target.dimensions());
+    let mut color_ramp = ColorRampTexture::new(&display, controls.color_ramp);
+    let mut actions: Vec<InputAction> = Vec::new();
+
+    let script_console = Rc::new(RefCell::new(ScriptConsole::new()));
+    let block_composition = Rc::new(RefCell::new(BlockComposition::new()));
+    let profile_tool = Rc::new(RefCell::new(ProfileTool::new()));
+    let mut script_host = ScriptHost::new();
+
+    // The subscribers get the events in this order,
+    // the path tool searches its route before the path plugin draws it
+    let mut event_bus = EventBus::new();
+    event_bus.subscribe(block_composition.clone());
+    event_bus.subscribe(world_bvh.clone());
+    event_bus.subscribe(implicit_scene.clone());
+    event_bus.subscribe(path_tool.clone());
+    event_bus.subscribe(plugins.clone());
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::NewEvents(_) => {
+            actions.clear();
+            render_states.record_frame();
+            render_state = render_states.state();
+            frame_stats.record(render_state.timing.delta_time);
+            quality_governor.update(render_state.timing.delta_time, controls.quality_governor);
+        }
+        Event::MainEventsCleared => {
+            let gl_window = display.gl_window();
+            let quit = actions
+                .iter()
+                .any(|action| matches!(action, InputAction::Quit));
+            if quit {
+                println!("[INFO] Shutting down");
+                // The scans keep their checkpoints, files being written are finished
+                let shutdown = Shutdown::new(config::SHUTDOWN_TIMEOUT);
+                stop_scan(&shutdown, "the world scan", world_scan.take());
+                stop_scan(&shutdown, "the map export", map_export.take());
+                if let Some(render) = &offline_render {
+                    render.cancel();
+                    shutdown.wait("the offline render", || render.is_finished());
+                }
+                if let Some(bake) = &lightmap_bake {
+                    bake.cancel();
+                    shutdown.wait("the lightmap bake", || bake.is_finished());
+                }
+                world.shut_down(&shutdown);
+                shutdown.wait("the background jobs", || jobs::run_completions() == 0);
+                imgui_data.save_layout();
+
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+
+            // The state stays the same until the next frame, whatever the UI requests meanwhile
+            if let Some(command) = controls.render_command.take() {
+                render_states.request(command);
+            }
+            let settings_changed = render_states.begin_frame(&actions);
+            let was_captured = render_state.cursor_captured;
+            render_state = render_states.state();
+            if render_state.cursor_captured != was_captured {
+                capture_cursor(gl_window.window(), render_state.cursor_captured);
+            }
+            if let Some(settings) = settings_changed {
+                event_bus.publish(AppEvent::RenderSettingsChanged(settings));
+            }
+            let render_mode = render_state.settings.render_mode;
+
+            // The camera keeps its place in the world when the heights are scaled differently
+            let scale = VerticalScale::new(controls.vertical_scale);
+            if scale != vertical_scale {
+                let position = vertical_scale.to_world(camera.get_position());
+                let direction = vertical_scale.direction_to_world(camera.get_direction());
+                camera.look_from(
+                    scale.to_scene(position),
+                    scale.direction_to_scene(direction),
+                );
+                vertical_scale = scale;
+            }
+
+            let toggle_pause = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::KeyPressed {
+                        key: VirtualKeyCode::P
+                    }
+                )
+            });
+            if toggle_pause {
+                app_state = app_state.toggle_pause();
+            }
+
+            if controls.open_world_dialog {
+                world_dialog.borrow_mut().error = None;
+                app_state = AppState::ChoosingWorld;
+                controls.open_world_dialog = false;
+            }
+            if std::mem::take(&mut world_dialog.borrow_mut().cancel_requested) {
+                app_state = AppState::Running;
+            }
+
+            let browsed_world = {
+                let mut dialog = world_dialog.borrow_mut();
+                let browse_folder = std::mem::take(&mut dialog.browse_folder_requested);
+                let browse_backup = std::mem::take(&mut dialog.browse_backup_requested);
+
+                if browse_folder {
+                    rfd::FileDialog::new().pick_folder()
+                } else if browse_backup {
+                    rfd::FileDialog::new()
+                        .add_filter("World backup", &["zip", "tar", "gz", "tgz"])
+                        .pick_file()
+                } else {
+                    None
+                }
+            };
+            if let Some(path) = browsed_world {
+                world_dialog.borrow_mut().chosen_world = Some(path.to_string_lossy().into_owned());
+            }
+
+            let chosen_world = world_dialog.borrow_mut().chosen_world.take();
+            if let Some(world_path) = chosen_world {
+                let mut dialog = world_dialog.borrow_mut();
+                match open_world(&world_path, &mut dialog, Some(&mut world)) {
+                    Ok(()) => {
+                        dialog.error = None;
+                        app_state = AppState::ChoosingStart;
+                    }
+                    Err(message) => {
+                        eprintln!("[ERROR] {message}");
+                        dialog.error = Some(message);
+                        app_state = AppState::ChoosingWorld;
+                    }
+                }
+            }
+
+            // The jobs of the previous world were cancelled when the world was opened,
+            // the rest of it is dropped when the new one takes its place
+            let chosen_start = world_dialog.borrow_mut().chosen_start.take();
+            if let Some(position) = chosen_start {
+                tour = None;
+                density_textures = ChunkDensityTextures::new();
+                changed_pixels = None;
+
+                // The chunks are put into the world and polygonized by update_chunk_data
+                // as their jobs finish, the loading screen shows how far they got
+                world = World::load(position, controls.load_radius);
+                loading_progress = LoadingProgress {
+                    chunks_loaded: 0,
+                    chunks_total: world.chunks_being_loaded(),
+                    meshes_built: 0,
+                    meshes_total: 0,
+                };
+                world_loaded = true;
+
+                camera.look_from(
+                    vertical_scale.to_scene(position),
+                    vertical_scale.direction_to_scene(settings::current().spawn_dir()),
+                );
+                (rigid_scene, rigid_ranges) = create_rigid_scene(&world, &display);
+                discrete_scene = create_discrete_scene(&world, &display);
+                *implicit_scene.borrow_mut() = create_implicit_scene(&world, &display);
+                crossfade.clear();
+                lightmap = LightmapTexture::empty(&display);
+                lightmap_bake_outdated = true;
+                world_report = None;
+                app_state = AppState::Loading;
+            }
+
+            let run_script = std::mem::take(&mut script_console.borrow_mut().run_requested);
+            if run_script {
+                let mut console = script_console.borrow_mut();
+                let source = console.source.clone();
+                let commands = script_host.run(
+                    &source,
+                    &world,
+                    &camera,
+                    vertical_scale,
+                    polygonization_options,
+                    &mut console,
+                );
+                scripting::apply_commands(
+                    commands,
+                    &mut world,
+                    &mut camera,
+                    vertical_scale,
+                    &mut controls,
+                    polygonization_options,
+                    &mut world_dialog.borrow_mut(),
+                    &mut console,
+                );
+            }
+
+            for action in &actions {
+                level_scroll.consume(action, &render_state);
+            }
+            if !app_state.is_active() {
+                level_scroll.discard_scroll();
+            }
+            controls.apply |= level_scroll.update(&mut controls);
+            let mut requested_options: PolygonizationOptions = controls.into();
+            requested_options.validate();
+            // Edits of the material legend and the settings file are applied below, not by APPLY
+            requested_options.materials = polygonization_options.materials;
+            requested_options.settings = polygonization_options.settings;
+            controls.apply |= auto_apply.update(
+                controls.auto_apply,
+                requested_options,
+                polygonization_options,
+            );
+
+            match settings_file.poll() {
+                Some(Ok((previous, settings))) => {
+                    println!("[INFO] Settings reloaded from {}", config::SETTINGS_FILE);
+                    // Also sorts the blocks into rigid and smooth again
+                    controls.materials_changed |= settings.changes_meshes(&previous);
+                    polygonization_options.settings = settings;
+                }
+                Some(Err(message)) => eprintln!("[ERROR] {message}"),
+                None => (),
+            }
+
+            if controls.apply {
+                polygonization_options = controls.into();
+                option_corrections = Rc::new(polygonization_options.validate());
+                // The sliders show the repaired Y range
+                controls.y_low_limit = polygonization_options.y_low_limit as isize;
+                controls.y_size = polygonization_options.y_size as usize;
+                world.rebuild_all_meshes(polygonization_options);
+                event_bus.publish(AppEvent::OptionsChanged(polygonization_options));
+
+                controls.apply = false;
+            }
+
+            if controls.materials_changed {
+                polygonization_options.materials = material_registry::snapshot();
+                world.refresh_materials(polygonization_options);
+                controls.materials_changed = false;
+                controls.materials_recolored = false;
+            }
+
+            // The loaded window follows the view distance of the controls once the slider rests,
+            // every step of a drag would cancel the mesh builds and load chunks that are dropped again
+            let resize =
+                view_distance_apply.update(world_loaded, controls.load_radius, world.load_radius());
+            if resize {
+                world.resize(controls.load_radius, polygonization_options);
+            }
+            camera.set_far_clipping_plane(far_clipping_plane(world.load_radius()));
+
+            if controls.materials_recolored {
+                polygonization_options.materials = material_registry::snapshot();
+                world.recolor_meshes();
+                implicit_scene.borrow().recolor();
+                controls.materials_recolored = false;
+            }
+
+            if controls.reset_frame_stats {
+                frame_stats.reset();
+                controls.reset_frame_stats = false;
+            }
+
+            // The profile points are marked where the center of the screen looks at the terrain,
+            // see pick_terrain
+            let mark_profile_point = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::KeyPressed {
+                        key: VirtualKeyCode::E
+                    }
+                )
+            });
+            if mark_profile_point && app_state.is_active() {
+                match pick_terrain(
+                    &world,
+                    &world_bvh.borrow(),
+                    &camera,
+                    vertical_scale,
+                    render_mode,
+                ) {
+                    Some(position) => {
+                        let point = Point2::new(position.x, position.z);
+                        profile_tool.borrow_mut().mark(&world, point);
+                        controls.show_elevation_profile = true;
+                    }
+                    None => println!("[INFO] No terrain to mark the profile point on"),
+                }
+            }
+
+            // The route end points are picked the same way
+            let mark_path_point = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::KeyPressed {
+                        key: VirtualKeyCode::F
+                    }
+                )
+            });
+            if mark_path_point && app_state.is_active() {
+                match pick_terrain(
+                    &world,
+                    &world_bvh.borrow(),
+                    &camera,
+                    vertical_scale,
+                    render_mode,
+                ) {
+                    Some(position) => {
+                        let point = Point2::new(position.x, position.z);
+                        path_tool.borrow_mut().mark(point);
+                        controls.show_path_finder = true;
+                    }
+                    None => println!("[INFO] No terrain to mark the route point on"),
+                }
+            }
+
+            // The inspected marching cubes cell is picked the same way as the profile points
+            let pick_cell = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::KeyPressed {
+                        key: VirtualKeyCode::V
+                    }
+                )
+            });
+            if pick_cell && controls.show_cell_inspector && app_state.is_active() {
+                match pick_terrain(
+                    &world,
+                    &world_bvh.borrow(),
+                    &camera,
+                    vertical_scale,
+                    render_mode,
+                ) {
+                    Some(position) => {
+                        let origin = CellInspection::cell_origin(position, polygonization_options);
+                        inspected_cell = Some(origin);
+                    }
+                    None => println!("[INFO] No terrain to pick the cell from"),
+                }
+            }
+            if let (Some(origin), Some(step)) = (inspected_cell, controls.cell_step.take()) {
+                let cell_size = polygonization_options.marching_cubes_cell_size;
+                let step = Vector3::from(step.map(|cells| cells as Real * cell_size));
+                inspected_cell = Some(origin + step);
+            }
+            let cell_density = |point| get_density(&world, point, polygonization_options);
+            let cell_inspection = inspected_cell
+                .filter(|_| controls.show_cell_inspector)
+                .map(|origin| CellInspection::new(origin, cell_density, polygonization_options));
+
+            // A right click on the chunk grid opens the chunk window for the picked chunk
+            let inspect_chunk = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::MousePressed {
+                        button: MouseButton::Right
+                    }
+                )
+            });
+            if inspect_chunk && controls.show_grid && !controls.sculpt && app_state.is_active() {
+                match pick_terrain(
+                    &world,
+                    &world_bvh.borrow(),
+                    &camera,
+                    vertical_scale,
+                    render_mode,
+                ) {
+                    Some(position) => {
+                        controls.inspected_chunk = Some(get_minecraft_chunk_position(position))
+                    }
+                    None => println!("[INFO] No terrain to pick the chunk from"),
+                }
+            }
+
+            // While sculpting, a left click adds material around the smooth surface point
+            // under the center of the screen and a right click carves it
+            let brush_mode = actions.iter().find_map(|action| match action {
+                InputAction::MousePressed {
+                    button: MouseButton::Left,
+                } => Some(BrushMode::Add),
+                InputAction::MousePressed {
+                    button: MouseButton::Right,
+                } => Some(BrushMode::Carve),
+                _ => None,
+            });
+            if let Some(mode) = brush_mode.filter(|_| controls.sculpt && app_state.is_active()) {
+                let origin = vertical_scale.to_world(camera.get_position());
+                let direction = vertical_scale.direction_to_world(camera.get_direction());
+                let hit = world.raycast_smooth(
+                    origin,
+                    direction,
+                    config::SCULPT_REACH,
+                    polygonization_options,
+                );
+                match hit {
+                    Some(hit) => {
+                        let radius = controls.sculpt_radius as Real;
+                        let blocks = sculpt_tool::brush_stroke(&world, &hit, radius, mode);
+                        world.set_blocks(&blocks, polygonization_options);
+                    }
+                    None => println!("[INFO] No terrain to sculpt"),
+                }
+            }
+
+            match controls.chunk_command.take() {
+                Some(ChunkCommand::ReloadFromDisk(position)) => {
+                    match world.reload_chunk(position) {
+                        true => println!("[INFO] Reloading chunk {:?} from disk", position),
+                        false => println!("[INFO] Chunk {:?} is no longer loaded", position),
+                    }
+                }
+                Some(ChunkCommand::RebuildMesh(position)) => {
+                    match world.rebuild_chunk_mesh(position, polygonization_options) {
+                        true => println!("[INFO] Rebuilding the mesh of chunk {:?}", position),
+                        false => println!("[INFO] Chunk {:?} has no smooth mesh", position),
+                    }
+                }
+                None => (),
+            }
+
+            if std::mem::take(&mut profile_tool.borrow_mut().export_requested) {
+                if let Some(profile) = &profile_tool.borrow().profile {
+                    match profile.export_csv(config::PROFILE_EXPORT_PATH) {
+                        Ok(()) => println!(
+                            "[INFO] Elevation profile exported to {}",
+                            config::PROFILE_EXPORT_PATH
+                        ),
+                        Err(error) => {
+                            eprintln!("[ERROR] Failed to export the elevation profile - {error}")
+                        }
+                    }
+                }
+            }
+
+            if std::mem::take(&mut controls.offline_render_requested) {
+                match offline_render {
+                    Some(_) => println!("[INFO] An offline render is already in progress"),
+                    None => {
+                        offline_render = Some(OfflineRender::start(
+                            world.get_smooth_mesh(),
+                            RenderView::from_camera(&camera, vertical_scale),
+                            OfflineRenderSettings::from_config(),
+                            config::OFFLINE_RENDER_PATH,
+                        ))
+                    }
+                }
+            }
+            let render_finished = offline_render
+                .as_ref()
+                .is_some_and(OfflineRender::is_finished);
+            if render_finished {
+                match offline_render.take().unwrap().join() {
+                    Ok(()) => println!(
+                        "[INFO] Offline render saved to {}",
+                        config::OFFLINE_RENDER_PATH
+                    ),
+                    Err(error) => eprintln!("[ERROR] Offline render failed - {error}"),
+                }
+            }
+
+            if std::mem::take(&mut controls.lightmap_bake_requested) {
+                match lightmap_bake {
+                    Some(_) => println!("[INFO] Lightmaps are already being baked"),
+                    None => {
+                        lightmap_bake_outdated = false;
+                        lightmap_bake = Some(LightmapBake::start(
+                            world.get_smooth_chunk_meshes(),
+                            world.get_chunk_densities(),
+                            LightmapSettings::new(polygonization_options),
+                        ))
+                    }
+                }
+            }
+            let bake_finished = lightmap_bake
+                .as_ref()
+                .is_some_and(LightmapBake::is_finished);
+            if bake_finished {
+                let baked = lightmap_bake.take().unwrap().join();
+                if lightmap_bake_outdated {
+                    println!("[INFO] Lightmaps discarded, the smooth mesh changed while baking");
+                } else {
+                    match baked.export(config::LIGHTMAP_EXPORT_PATH) {
+                        Ok(()) => println!(
+                            "[INFO] Lightmapped mesh exported to {}",
+                            config::LIGHTMAP_EXPORT_PATH
+                        ),
+                        Err(error) => eprintln!("[ERROR] Failed to export the lightmaps - {error}"),
+                    }
+
+                    lightmap = LightmapTexture::new(&baked.lightmap, &display);
+                    implicit_scene
+                        .borrow_mut()
+                        .show_baked(&display, &baked.mesh);
+                }
+            }
+
+            if std::mem::take(&mut controls.world_scan_requested) {
+                match world_scan {
+                    Some(_) => println!("[INFO] The world is already being scanned"),
+                    None => {
+                        let workers = settings::current().scan_workers;
+                        let scan = world_report::start_world_scan(polygonization_options, workers);
+                        world_scan = Some(scan);
+                        controls.show_world_report = true;
+                    }
+                }
+            }
+            if let (Some(scan), Some(command)) = (&world_scan, controls.world_scan_command.take()) {
+                apply_scan_command(scan, command);
+            }
+            let scan_finished = world_scan.as_ref().is_some_and(WorldScanner::is_finished);
+            if scan_finished {
+                match world_scan.take().unwrap().join() {
+                    ScanEnd::Finished(report) => {
+                        let written = serde_json::to_string_pretty(&report.to_json())
+                            .map_err(std::io::Error::from)
+                            .and_then(|json| std::fs::write(config::WORLD_REPORT_PATH, json));
+                        match written {
+                            Ok(()) => println!(
+                                "[INFO] World report written to {}",
+                                config::WORLD_REPORT_PATH
+                            ),
+                            Err(error) => {
+                                eprintln!("[ERROR] Failed to write the world report - {error}")
+                            }
+                        }
+
+                        world_report = Some(Rc::new(report));
+                        controls.show_world_report = true;
+                    }
+                    ScanEnd::Cancelled { done, total } => println!(
+                        "[INFO] World scan cancelled after {done} of {total} regions, \
+                         scanning again resumes it"
+                    ),
+                    ScanEnd::Failed(error) => eprintln!("[ERROR] World scan failed - {error}"),
+                }
+            }
+
+            if std::mem::take(&mut controls.map_export_requested) {
+                match map_export {
+                    Some(_) => println!("[INFO] A map is already being exported"),
+                    None => {
+                        let area = match controls.map_whole_world {
+                            true => MapArea::WholeWorld,
+                            false => MapArea::Loaded(world.get_chunks()),
+                        };
+                        let settings = MapSettings {
+                            meters_per_pixel: controls.map_meters_per_pixel as Real,
+                            contour_interval: controls
+                                .map_contours
+                                .then_some(controls.map_contour_interval as Real),
+                            color_ramp: controls.color_ramp,
+                            web_tiles: controls.map_web_tiles,
+                        };
+                        map_export = Some(map_export::start_map_export(
+                            area,
+                            polygonization_options,
+                            settings,
+                            config::MAP_EXPORT_FOLDER,
+                            settings::current().scan_workers,
+                        ));
+                    }
+                }
+            }
+            if let (Some(export), Some(command)) = (&map_export, controls.map_export_command.take())
+            {
+                apply_scan_command(export, command);
+            }
+            let map_finished = map_export.as_ref().is_some_and(WorldScanner::is_finished);
+            if map_finished {
+                match map_export.take().unwrap().join() {
+                    ScanEnd::Finished(()) => {
+                        println!("[INFO] Map exported to {}", config::MAP_EXPORT_FOLDER)
+                    }
+                    ScanEnd::Cancelled { done, total } => println!(
+                        "[INFO] Map export cancelled after {done} of {total} tiles, \
+                         exporting again resumes it"
+                    ),
+                    ScanEnd::Failed(error) => eprintln!("[ERROR] Map export failed - {error}"),
+                }
+            }
+
+            if let Some(format) = controls.mesh_export_requested.take() {
+                let path = format!("{}.{}", config::SMOOTH_MESH_EXPORT_PATH, format.extension());
+                let mesh = world.get_smooth_mesh();
+                jobs::spawn_then(
+                    "mesh export",
+                    Priority::Low,
+                    0,
+                    {
+                        let path = path.clone();
+                        move |_| export::save_mesh(&mesh, &path, format)
+                    },
+                    move |result| match result {
+                        Ok(()) => println!("[INFO] Smooth mesh exported to {}", path),
+                        Err(error) => println!("[ERROR] Failed to export smooth mesh: {}", error),
+                    },
+                );
+            }
+            jobs::run_completions();
+
+            let export_boundaries = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::KeyPressed {
+                        key: VirtualKeyCode::O
+                    }
+                )
+            });
+            if export_boundaries {
+                match world.export_mesh_boundaries(config::BOUNDARY_EXPORT_PATH) {
+                    Ok(()) => println!(
+                        "[INFO] Mesh boundaries exported to {}",
+                        config::BOUNDARY_EXPORT_PATH
+                    ),
+                    Err(error) => println!("[ERROR] Failed to export mesh boundaries: {}", error),
+                }
+            }
+
+            let export_navmesh = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::KeyPressed {
+                        key: VirtualKeyCode::N
+                    }
+                )
+            });
+            if export_navmesh {
+                match world.export_navmesh(config::NAVMESH_EXPORT_PATH) {
+                    Ok(triangles) => println!(
+                        "[INFO] Navigation mesh with {} triangles exported to {}",
+                        triangles,
+                        config::NAVMESH_EXPORT_PATH
+                    ),
+                    Err(error) => println!("[ERROR] Failed to export navigation mesh: {}", error),
+                }
+            }
+
+            let export_splat_maps = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::KeyPressed {
+                        key: VirtualKeyCode::M
+                    }
+                )
+            });
+            if export_splat_maps {
+                match world.export_splat_maps(config::SPLAT_MAP_EXPORT_FOLDER) {
+                    Ok(chunks) => println!(
+                        "[INFO] Splat maps of {} chunks exported to {}",
+                        chunks,
+                        config::SPLAT_MAP_EXPORT_FOLDER
+                    ),
+                    Err(error) => println!("[ERROR] Failed to export splat maps: {}", error),
+                }
+            }
+
+            imgui_data.set_ui_scale(controls.ui_scale);
+            imgui_data.prepare(gl_window.window(), render_state.timing.delta_time);
+
+            if app_state.is_active() {
+                for action in &actions {
+                    camera.consume(action, &render_state);
+                }
+
+                camera.update(
+                    render_state.timing.delta_time.as_secs_f64(),
+                    controls.smooth_camera,
+                );
+            }
+
+            let toggle_tour = actions.iter().any(|action| {
+                matches!(
+                    action,
+                    InputAction::KeyPressed {
+                        key: VirtualKeyCode::T
+                    }
+                )
+            });
+            if toggle_tour && app_state.is_active() {
+                tour = match tour {
+                    Some(_) => None,
+                    None => Tour::plan(&world, vertical_scale.to_world(camera.get_position())),
+                };
+            }
+
+            if let Some(active_tour) = tour.as_mut().filter(|_| app_state.is_active()) {
+                match active_tour.advance(render_state.timing.delta_time.as_secs_f64()) {
+                    Some((position, direction)) => camera.look_from(
+                        vertical_scale.to_scene(position),
+                        vertical_scale.direction_to_scene(direction),
+                    ),
+                    // Continue with the chunks loaded around the end of the path
+                    None => {
+                        tour = Tour::plan(&world, vertical_scale.to_world(camera.get_position()))
+                    }
+                }
+            }
+
+            let camera_position = vertical_scale.to_world(camera.get_position());
+            if camera_position != last_camera_position {
+                event_bus.publish(AppEvent::CameraMoved(camera_position));
+                last_camera_position = camera_position;
+            }
+
+            let loading = app_state == AppState::Loading;
+            if loading || config::DYNAMIC_WORLD && app_state.is_active() {
+                world.update_chunk_data(camera_position, polygonization_options);
+            }
+            crash_report::update_context(CrashContext {
+                options: polygonization_options,
+                render_mode,
+                camera_position,
+                camera_direction: vertical_scale.direction_to_world(camera.get_direction()),
+                loaded_chunks: world.get_loaded_chunk_positions(),
+            });
+
+            if let Some(instance_data) = world.take_instance_data() {
+                let instance_positions = {
+                    let blocks = instance_data.surface_blocks;
+                    glium::vertex::VertexBuffer::new(&display, &blocks).unwrap()
+                };
+                discrete_scene.update_instance_data(instance_positions);
+
+                let rigid_positions = {
+                    let rigid_blocks = instance_data.rigid_blocks;
+                    glium::vertex::VertexBuffer::new(&display, &rigid_blocks).unwrap()
+                };
+                rigid_scene.update_instance_data(rigid_positions);
+                rigid_ranges = instance_data.rigid_ranges;
+            }
+            // Hidden materials are skipped when drawing, the buffer is left as it is.
+            // Translucent materials are drawn separately after everything opaque,
+            // water in a pass of its own.
+            let shown = controls.rigid_materials_shown;
+            let opaque_shown =
+                |material: BlockType| shown[material as usize] && !is_translucent_block(material);
+            rigid_scene.instance_ranges = Some(rigid_ranges.visible(opaque_shown));
+            let translucent_ranges = rigid_ranges.back_to_front(camera_position, |material| {
+                shown[material as usize] && material != BlockType::Water
+            });
+            water_scene.ranges = rigid_ranges.back_to_front(camera_position, |material| {
+                shown[material as usize] && material == BlockType::Water
+            });
+
+            let update_implicit_scene = world.update_smooth_mesh();
+            if update_implicit_scene {
+                let replaced = world.take_replaced_meshes();
+                if controls.mesh_crossfade && !replaced.is_empty() {
+                    let merged = Mesh::copy_merge(replaced.iter());
+                    if let Some(buffers) = MeshBuffers::upload(&display, &merged) {
+                        crossfade.start(buffers);
+                    }
+                }
+                lightmap = LightmapTexture::empty(&display);
+                lightmap_bake_outdated = true;
+            }
+            if world.update_densities() {
+                density_textures.update(world.get_chunk_densities(), &display);
+            }
+            crossfade.update();
+
+            if loading {
+                loading_progress.chunks_loaded =
+                    loading_progress.chunks_total - world.chunks_being_loaded();
+                let (meshes_built, meshes_total) = world.mesh_build_progress();
+                loading_progress.meshes_built = meshes_built;
+                loading_progress.meshes_total = meshes_total;
+
+                if loading_progress.is_done() {
+                    app_state = AppState::Running;
+                }
+            }
+
+            event_bus.publish_all(world.take_events());
+            event_bus.dispatch(&FrameContext {
+                display: &display,
+                world: &world,
+                camera: &camera,
+                state: &render_state,
+                controls: &controls,
+                options: polygonization_options,
+            });
+
+            gl_window.window().request_redraw();
+        }
+        Event::RedrawRequested(_) => {
+            // Setup for drawing
+            let gl_window = display.gl_window();
+            let mut frame = display.draw();
+
+            // Until the world is ready only the loading or the error screen is shown
+            if !app_state.shows_world() {
+                frame.clear_color_srgb(1.0, 1.0, 1.0, 1.0);
+
+                let screen = match &app_state {
+                    AppState::Error(message) => get_error_screen_builder(message.clone()),
+                    AppState::ChoosingWorld | AppState::ChoosingStart => {
+                        let can_cancel = world_loaded && app_state == AppState::ChoosingWorld;
+                        get_world_dialog_builder(
+                            world_dialog.clone(),
+                            app_state.clone(),
+                            can_cancel,
+                        )
+                    }
+                    _ => get_loading_screen_builder(loading_progress),
+                };
+                imgui_data.add_window(screen);
+                imgui_data
+                    .render_frame(gl_window.window(), &mut frame, &mut controls)
+                    .expect("Failed to render imgui ui!");
+
+                frame.finish().expect("Failed to swap buffers");
+                return;
+            }
+            let implicit_scene = implicit_scene.borrow();
+            let world_bvh = world_bvh.borrow();
+
+            // The scene is rendered at the resolution picked by the quality governor
+            scene_target.fit(&display, quality_governor.render_scale());
+            let mut target = scene_target.surface(&display);
+
+            // Clear window
+            target.clear_color_srgb(1.0, 1.0, 1.0, 1.0);
+            target.clear_depth(1.0);
+
+            color_ramp.fit(&display, controls.color_ramp);
+
+            // The shadow maps are drawn before the scene, from the terrain the mode shows
+            let shows_terrain = matches!(
+                render_mode,
+                RenderingMode::Discrete | RenderingMode::Implicit | RenderingMode::Comparison
+            );
+            if controls.shadows && shows_terrain {
+                shadow_cascades.update(&camera, sun_position(&camera));
+
+                let mut casters = Vec::new();
+                if render_mode != RenderingMode::Implicit {
+                    if let Some(instances) = &discrete_scene.instance_data {
+                        casters.push(ShadowCaster::Blocks(instances, None));
+                    }
+                }
+                if render_mode != RenderingMode::Discrete {
+                    casters.push(ShadowCaster::Meshes(&implicit_scene));
+                    let rigid_instances = match settings::current().filter_rigid {
+                        true => rigid_scene.instance_data.as_ref(),
+                        false => None,
+                    };
+                    if let Some(instances) = rigid_instances {
+                        let ranges = rigid_scene.instance_ranges.as_deref();
+                        casters.push(ShadowCaster::Blocks(instances, ranges));
+                    }
+                }
+
+                let model = VerticalScale::new(controls.vertical_scale).model_matrix();
+                shadow_cascades.render(&display, to_uniform_matrix(&model), &casters);
+            }
+
+            let world_textures = WorldTextures {
+                block_textures: &block_texture_array,
+                lightmap: &lightmap,
+                color_ramp: &color_ramp.texture,
+                shadows: &shadow_cascades,
+            };
+
+            // Draw Scene
+            match render_mode {
+                RenderingMode::Discrete => render_world(
+                    &discrete_scene,
+                    &mut target,
+                    &camera,
+                    &render_state,
+                    &world_textures,
+                    &controls,
+                ),
+                RenderingMode::Implicit => {
+                    render_implicit_world(
+                        &rigid_scene,
+                        &implicit_scene,
+                        &mut target,
+                        &camera,
+                        &render_state,
+                        &world_textures,
+                        &controls,
+                    );
+                    crossfade.render(|replaced, opacity| {
+                        render_world_faded(
+                            implicit_scene.view(replaced),
+                            &mut target,
+                            &camera,
+                            &render_state,
+                            &world_textures,
+                            &controls,
+                            opacity,
+                        )
+                    });
+
+                    // Translucent blocks go after the opaque layer and before the plugins
+                    match controls.weighted_oit {
+                        false => {
+                            render_translucent(
+                                &rigid_scene,
+                                &mut target,
+                                &camera,
+                                &render_state,
+                                &world_textures,
+                                &controls,
+                                &translucent_ranges,
+                            );
+                            render_water(
+                                &water_scene,
+                                &rigid_scene,
+                                &mut target,
+                                &camera,
+                                &render_state,
+                                &world_textures,
+                                &controls,
+                            );
+                        }
+                        true => {
+                            oit_targets.fit(&display, scene_target.dimensions());
+                            let mut accumulation =
+                                oit_targets.surface(&display, scene_target.depth());
+                            accumulation.clear_color(0.0, 0.0, 0.0, 1.0);
+                            render_translucent(
+                                &rigid_scene,
+                                &mut accumulation,
+                                &camera,
+                                &render_state,
+                                &world_textures,
+                                &controls,
+                                &translucent_ranges,
+                            );
+                            render_water(
+                                &water_scene,
+                                &rigid_scene,
+                                &mut accumulation,
+                                &camera,
+                                &render_state,
+                                &world_textures,
+                                &controls,
+                            );
+                            drop(accumulation);
+
+                            render_oit_composite(&oit_composite_scene, &mut target, &oit_targets);
+                        }
+                    }
+                }
+                RenderingMode::Raymarched => {
+                    for density_texture in density_textures.iter() {
+                        render_raymarched(
+                            &raymarch_scene,
+                            &mut target,
+                            &camera,
+                            vertical_scale,
+                            density_texture,
+                            polygonization_options,
+                        );
+                    }
+                }
+                RenderingMode::Comparison => {
+                    comparison_targets.fit(&display);
+
+                    let mut discrete_target = comparison_targets.discrete_surface(&display);
+                    discrete_target.clear_color_srgb_and_depth((1.0, 1.0, 1.0, 1.0), 1.0);
+                    render_world(
+                        &discrete_scene,
+                        &mut discrete_target,
+                        &camera,
+                        &render_state,
+                        &world_textures,
+                        &controls,
+                    );
+
+                    let mut implicit_target = comparison_targets.implicit_surface(&display);
+                    implicit_target.clear_color_srgb_and_depth((1.0, 1.0, 1.0, 1.0), 1.0);
+                    render_implicit_world(
+                        &rigid_scene,
+                        &implicit_scene,
+                        &mut implicit_target,
+                        &camera,
+                        &render_state,
+                        &world_textures,
+                        &controls,
+                    );
+                    // The comparison targets have no transparency targets of their own
+                    let sorted_controls = SmoothMeshOptions {
+                        weighted_oit: false,
+                        ..controls
+                    };
+                    render_translucent(
+                        &rigid_scene,
+                        &mut implicit_target,
+                        &camera,
+                        &render_state,
+                        &world_textures,
+                        &sorted_controls,
+                        &translucent_ranges,
+                    );
+                    render_water(
+                        &water_scene,
+                        &rigid_scene,
+                        &mut implicit_target,
+                        &camera,
+                        &render_state,
+                        &world_textures,
+                        &sorted_controls,
+                    );
+
+                    changed_pixels = match controls.comparison_difference {
+                        true => Some(comparison_targets.changed_pixels()),
+                        false => None,
+                    };
+
+                    render_comparison(
+                        &comparison_scene,
+                        &mut target,
+                        &comparison_targets,
+                        controls,
+                    );
+                }
+            }
+
+            plugins
+                .borrow()
+                .render(&mut target, &camera, &render_state, &controls);
+
+            if controls.show_gradient_probe && app_state.is_active() {
+                queue_gradient_probe(
+                    &mut debug_draw,
+                    &world,
+                    &world_bvh,
+                    &camera,
+                    vertical_scale,
+                    render_mode,
+                    polygonization_options,
+                );
+            }
+            if let Some(inspection) = &cell_inspection {
+                queue_cell_outline(&mut debug_draw, inspection);
+            }
+            if let Some(chunk) = controls.inspected_chunk.filter(|_| controls.show_grid) {
+                queue_chunk_highlight(&mut debug_draw, chunk);
+            }
+            if controls.show_sun_gizmo {
+                queue_sun_gizmo(&mut debug_draw, &camera, vertical_scale);
+            }
+            debug_draw.render(&display, &mut target, &camera, vertical_scale);
+
+            drop(target);
+            scene_target.present(&display, &frame);
+
+            // Draw ui last so it shows on top of everything
+            let statistics_menu_builder = get_statistics_menu_builder(
+                &render_state,
+                &camera,
+                vertical_scale,
+                &world,
+                polygonization_options,
+                changed_pixels,
+                frame_stats.summary(),
+                quality_governor.render_scale(),
+            );
+            let controls_menu = get_controls_menu_builder(option_corrections.clone());
+            let compass = get_compass_builder(&camera);
+            let sign_labels = get_sign_labels_builder(&camera, vertical_scale, &world);
+            let script_console_window = get_script_console_builder(script_console.clone());
+            let material_legend = get_material_legend_builder(ui_block_textures);
+            let profile_window = get_elevation_profile_builder(profile_tool.clone());
+            let path_window = get_path_finder_builder(path_tool.clone());
+            let chunk_window = get_chunk_builder(&world);
+            let cell_window = get_cell_inspector_builder(cell_inspection, &camera);
+            let composition_window = get_block_composition_builder(
+                block_composition.clone(),
+                get_minecraft_chunk_position(camera_position),
+            );
+
+            imgui_data.add_window(statistics_menu_builder);
+            imgui_data.add_window(controls_menu);
+            imgui_data.add_window(compass);
+            imgui_data.add_window(sign_labels);
+            imgui_data.add_window(script_console_window);
+            imgui_data.add_window(material_legend);
+            imgui_data.add_window(composition_window);
+            imgui_data.add_window(chunk_window);
+            imgui_data.add_window(cell_window);
+            imgui_data.add_window(profile_window);
+            imgui_data.add_window(path_window);
+            if let Some(render) = &offline_render {
+                imgui_data.add_window(get_offline_render_builder(render.progress()));
+            }
+            if let Some(bake) = &lightmap_bake {
+                imgui_data.add_window(get_lightmap_bake_builder(bake.progress()));
+            }
+            if let Some(export) = &map_export {
+                imgui_data.add_window(get_map_export_builder(
+                    export.progress(),
+                    export.is_paused(),
+                ));
+            }
+            imgui_data.add_window(get_world_report_builder(
+                world_report.clone(),
+                world_scan
+                    .as_ref()
+                    .map(|scan| (scan.progress(), scan.is_paused())),
+            ));
+            if app_state == AppState::Paused {
+                imgui_data.add_window(get_paused_builder());
+            }
+            imgui_data
+                .render_frame(gl_window.window(), &mut frame, &mut controls)
+                .expect("Failed to render imgui ui!");
+
+            // Finish building the frame and swap buffers
+            frame.finish().expect("Failed to swap buffers");
+        }
+        // Closing the window shuts down with the next frame, even while the UI has the keyboard
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => actions.push(InputAction::Quit),
+
+        event => {
+            let gl_window = display.gl_window();
+            imgui_data.handle_event(gl_window.window(), &event);
+
+            // The captured cursor drives the camera even when it rests over a window
+            let focus = UiFocus {
+                keyboard: imgui_data.wants_keyboard(),
+                mouse: imgui_data.wants_mouse() && !render_state.cursor_captured,
+            };
+            if let Some(action) = input::translate_event(event) {
+                if input::reaches_scene(&action, focus) {
+                    actions.push(action);
+                }
+            }
+        }
+    });
+}
+
+fn to_uniform_matrix(matrix: &Matrix4<Real>) -> [[f32; 4]; 4] {
+    array_init(|i| array_init(|j| matrix[i][j] as f32))
+}
+
+// The sun follows the camera so the lighting of the terrain doesn't change as it moves
+fn sun_position(camera: &Camera) -> [f32; 3] {
+    let camera_position = camera.get_position();
+    [
+        (camera_position.x + 200.0) as f32,
+        (camera_position.y + 300.0) as f32,
+        (camera_position.z + 200.0) as f32,
+    ]
+}
+
+// Textures sampled by the shaders of the discrete and the implicit world
+struct WorldTextures<'a> {
+    block_textures: &'a SrgbTexture2dArray,
+    lightmap: &'a LightmapTexture,
+    color_ramp: &'a Texture2d,
+    shadows: &'a ShadowCascades,
+}
+
+fn render_world<W: WorldScene, S: Surface>(
+    scene: W,
+    target: &mut S,
+    camera: &Camera,
+    state: &RenderState,
+    textures: &WorldTextures,
+    controls: &SmoothMeshOptions,
+) {
+    render_world_faded(scene, target, camera, state, textures, controls, 1.0);
+}
+
+// Below full opacity the pass is blended over the scene and doesn't write depth,
+// so it doesn't hide the meshes it fades into
+fn render_world_faded<W: WorldScene, S: Surface>(
+    scene: W,
+    target: &mut S,
+    camera: &Camera,
+    state: &RenderState,
+    textures: &WorldTextures,
+    controls: &SmoothMeshOptions,
+    opacity: f32,
+) {
+    let sun_position = sun_position(camera);
+    let camera_position = camera.get_position();
+
+    let model = to_uniform_matrix(&VerticalScale::new(controls.vertical_scale).model_matrix());
+    let projection: [[f32; 4]; 4] = to_uniform_matrix(&camera.projection);
+    let view: [[f32; 4]; 4] = to_uniform_matrix(&camera.world_to_view);
+
+    let uni = uniform! {
+        projection: projection,
+        view: view,
+        model: model,
+        block_textures: textures.block_textures.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::BorderClamp),
+        sun_position: sun_position,
+        light_visualization: state.settings.light_visualization as u32,
+        color_ramp: textures.color_ramp.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+        material_dithering: controls.material_dithering,
+        dither_seed: config::MATERIAL_DITHER_SEED,
+        baked_lighting: textures.lightmap.baked && controls.baked_lighting,
+        camera_position: [
+            camera_position.x as f32,
+            camera_position.y as f32,
+            camera_position.z as f32,
+        ],
+        surface_fog_density: controls.surface_fog_density,
+        cave_fog_density: controls.cave_fog_density,
+        lightmap: textures.lightmap.texture.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+    };
+
+    let polygon_mode = match state.settings.render_wireframe {
+        true => glium::PolygonMode::Line,
+        false => glium::PolygonMode::Fill,
+    };
+    let faded = opacity < 1.0;
+    let draw_parameters = glium::DrawParameters {
+        backface_culling: glium::BackfaceCullingMode::CullClockwise,
+        polygon_mode,
+        depth: glium::Depth {
+            test: glium::DepthTest::IfLess,
+            write: !faded,
+            ..Default::default()
+        },
+        blend: match faded {
+            true => crossfade::fade_blending(opacity),
+            false => Default::default(),
+        },
+        ..Default::default()
+    };
+
+    let uni = textures.shadows.with_uniforms(&uni, controls.shadows);
+    scene.draw(target, &uni, draw_parameters);
+}
+
+// Smooth terrain along with the blocks that are excluded from smoothing
+fn render_implicit_world<'a, S: Surface>(
+    rigid_scene: &'a RenderPass<'a, model::chunk::BlockData, TexturedVertex, IndexBuffer<u32>>,
+    implicit_scene: &ChunkMeshScene,
+    target: &mut S,
+    camera: &Camera,
+    state: &RenderState,
+    textures: &WorldTextures,
+    controls: &SmoothMeshOptions,
+) {
+    if settings::current().filter_rigid {
+        // render rigid blocks
+        render_world(rigid_scene, target, camera, state, textures, controls);
+    }
+    // render smooth terrain
+    render_world(implicit_scene, target, camera, state, textures, controls);
+}
+
+// Rigid blocks of the translucent materials in *ranges*, tested against the depth of the opaque
+// scene without writing to it. Water has a pass of its own, see render_water
+fn render_translucent<'a, S: Surface>(
+    rigid_scene: &'a RenderPass<'a, model::chunk::BlockData, TexturedVertex, IndexBuffer<u32>>,
+    target: &mut S,
+    camera: &Camera,
+    state: &RenderState,
+    textures: &WorldTextures,
+    controls: &SmoothMeshOptions,
+    ranges: &[std::ops::Range<usize>],
+) {
+    if !settings::current().filter_rigid || ranges.is_empty() {
+        return;
+    }
+
+    let mode = match controls.weighted_oit {
+        true => TranslucencyMode::WeightedBlended,
+        false => TranslucencyMode::Sorted,
+    };
+
+    let sun_position = sun_position(camera);
+    let camera_position = camera.get_position();
+
+    let model = to_uniform_matrix(&VerticalScale::new(controls.vertical_scale).model_matrix());
+    let uni = uniform! {
+        projection: to_uniform_matrix(&camera.projection),
+        view: to_uniform_matrix(&camera.world_to_view),
+        model: model,
+        block_textures: textures.block_textures.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::BorderClamp),
+        sun_position: sun_position,
+        light_visualization: state.settings.light_visualization as u32,
+        color_ramp: textures.color_ramp.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+        translucency: mode as u32,
+        opacity: config::TRANSLUCENT_BLOCK_OPACITY,
+        camera_position: [
+            camera_position.x as f32,
+            camera_position.y as f32,
+            camera_position.z as f32,
+        ],
+        surface_fog_density: controls.surface_fog_density,
+        cave_fog_density: controls.cave_fog_density,
+    };
+
+    let blend = match mode {
+        TranslucencyMode::Sorted => glium::Blend::alpha_blending(),
+        TranslucencyMode::WeightedBlended => translucency::accumulation_blending(),
+    };
+    let draw_parameters = glium::DrawParameters {
+        backface_culling: glium::BackfaceCullingMode::CullClockwise,
+        depth: glium::Depth {
+            test: glium::DepthTest::IfLess,
+            write: false,
+            ..Default::default()
+        },
+        blend,
+        ..Default::default()
+    };
+
+    let uni = textures.shadows.with_uniforms(&uni, controls.shadows);
+    rigid_scene.execute_ranges(target, &uni, Some(draw_parameters), ranges);
+}
+
+// Rigid water blocks, drawn like the translucent blocks with the program of the water surface
+fn render_water<'a, S: Surface>(
+    water_scene: &WaterScene,
+    rigid_scene: &'a RenderPass<'a, model::chunk::BlockData, TexturedVertex, IndexBuffer<u32>>,
+    target: &mut S,
+    camera: &Camera,
+    state: &RenderState,
+    textures: &WorldTextures,
+    controls: &SmoothMeshOptions,
+) {
+    let Some(instances) = &rigid_scene.instance_data else {
+        return;
+    };
+    if !settings::current().filter_rigid || water_scene.ranges.is_empty() {
+        return;
+    }
+
+    let mode = match controls.weighted_oit {
+        true => TranslucencyMode::WeightedBlended,
+        false => TranslucencyMode::Sorted,
+    };
+
+    let sun_position = sun_position(camera);
+    let camera_position = camera.get_position();
+
+    let model = to_uniform_matrix(&VerticalScale::new(controls.vertical_scale).model_matrix());
+    let uni = uniform! {
+        projection: to_uniform_matrix(&camera.projection),
+        view: to_uniform_matrix(&camera.world_to_view),
+        model: model,
+        block_textures: textures.block_textures.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::BorderClamp),
+        sun_position: sun_position,
+        light_visualization: state.settings.light_visualization as u32,
+        color_ramp: textures.color_ramp.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+        translucency: mode as u32,
+        opacity: config::WATER_OPACITY,
+        camera_position: [
+            camera_position.x as f32,
+            camera_position.y as f32,
+            camera_position.z as f32,
+        ],
+        surface_fog_density: controls.surface_fog_density,
+        cave_fog_density: controls.cave_fog_density,
+        time: state.timing.running_time.as_secs_f32(),
+    };
+
+    let blend = match mode {
+        TranslucencyMode::Sorted => glium::Blend::alpha_blending(),
+        TranslucencyMode::WeightedBlended => translucency::accumulation_blending(),
+    };
+    let draw_parameters = glium::DrawParameters {
+        backface_culling: glium::BackfaceCullingMode::CullClockwise,
+        depth: glium::Depth {
+            test: glium::DepthTest::IfLess,
+            write: false,
+            ..Default::default()
+        },
+        blend,
+        ..Default::default()
+    };
+
+    water_scene.draw(target, &uni, &draw_parameters, instances);
+}
+
+// Blends the accumulated weighted transparency over the scene
+fn render_oit_composite<'a, S: Surface>(
+    render_pass: &'a RenderPass<'a, NoInstance, Vertex2D, IndexBuffer<u32>>,
+    target: &mut S,
+    oit_targets: &OitTargets,
+) {
+    let uni = uniform! {
+        accumulation: oit_targets.accumulation.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+        weights: oit_targets.weights.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+    };
+
+    let draw_parameters = glium::DrawParameters {
+        backface_culling: glium::BackfaceCullingMode::CullClockwise,
+        blend: translucency::composite_blending(),
+        ..Default::default()
+    };
+
+    render_pass.execute(target, &uni, Some(draw_parameters));
+}
+
+fn render_raymarched<'a, S: Surface>(
+    render_pass: &'a RenderPass<'a, NoInstance, Vertex2D, IndexBuffer<u32>>,
+    target: &mut S,
+    camera: &Camera,
+    vertical_scale: VerticalScale,
+    density_texture: &DensityTexture,
+    options: PolygonizationOptions,
+) {
+    let sun_position = sun_position(camera);
+
+    // The rays are marched through the unscaled volume
+    let camera_position = vertical_scale.to_world(camera.get_position());
+    let model = vertical_scale.model_matrix();
+    let world_to_view = camera.world_to_view * model;
+    let view_to_world = model.invert().unwrap() * camera.view_to_world;
+    let inverse_projection = camera.projection.invert().unwrap();
+
+    let uni = uniform! {
+        projection: to_uniform_matrix(&camera.projection),
+        inverse_projection: to_uniform_matrix(&inverse_projection),
+        world_to_view: to_uniform_matrix(&world_to_view),
+        view_to_world: to_uniform_matrix(&view_to_world),
+        camera_position: [
+            camera_position.x as f32,
+            camera_position.y as f32,
+            camera_position.z as f32,
+        ],
+        sun_position: sun_position,
+        density: density_texture.texture.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
+            .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp),
+        volume_origin: density_texture.origin,
+        volume_extent: density_texture.extent,
+        volume_resolution: density_texture.resolution,
+        surface_level: options.surface_level as f32,
+    };
+
+    // Every chunk is marched in its own pass, the written depth sorts out which chunk is in front
+    let draw_parameters = glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::draw_parameters::DepthTest::IfLess,
+            write: true,
+            ..Default::default()
+        },
+        backface_culling: glium::BackfaceCullingMode::CullClockwise,
+        ..Default::default()
+    };
+
+    render_pass.execute(target, &uni, Some(draw_parameters));
+}
+
+fn render_comparison<'a, S: Surface>(
+    render_pass: &'a RenderPass<'a, NoInstance, Vertex2D, IndexBuffer<u32>>,
+    target: &mut S,
+    comparison_targets: &ComparisonTargets,
+    controls: SmoothMeshOptions,
+) {
+    let uni = uniform! {
+        discrete_image: comparison_targets.discrete.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+        implicit_image: comparison_targets.implicit.sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+        swipe: controls.comparison_swipe,
+        show_difference: controls.comparison_difference,
+    };
+
+    // The quad covers the whole screen and doesn't need depth testing
+    let draw_parameters = glium::DrawParameters {
+        backface_culling: glium::BackfaceCullingMode::CullClockwise,
+        ..Default::default()
+    };
+
+    render_pass.execute(target, &uni, Some(draw_parameters));
+}
+
+fn get_controls_menu_builder(corrections: Rc<Vec<OptionCorrection>>) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        ui.window("controls")
+            .size([300.0, 250.0], imgui::Condition::FirstUseEver)
+            .position([60.0, 300.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                let warn = |text: &str| ui.text_colored([1.0, 0.7, 0.2, 1.0], text);
+
+                let method_names = ALL_POLYGONIZATION_METHODS.map(|method| method.name());
+                let mut method_index = ALL_POLYGONIZATION_METHODS
+                    .iter()
+                    .position(|method| *method == controls.polygonization_method)
+                    .unwrap_or(0);
+                if ui.combo_simple_string("Polygonization", &mut method_index, &method_names) {
+                    controls.polygonization_method = ALL_POLYGONIZATION_METHODS[method_index];
+                }
+
+                // The levels are presets of the sizes below them,
+                // the sizes can be dragged or entered exactly with ctrl+click
+                let mut detail = controls.mesh_resolution_level;
+                if ui.slider_config("Mesh detail", 1, 4).build(&mut detail) {
+                    controls.set_mesh_detail_level(detail);
+                }
+                imgui::Drag::new("Cell size")
+                    .range(0.125, 1.0)
+                    .speed(0.005)
+                    .display_format("%.3f")
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .build(ui, &mut controls.cell_size);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Edge of the marching cubes cells in blocks. Smaller cells follow \
+                         the blocks closer and take longer to build. Ctrl+click to type a value.",
+                    );
+                }
+                let cells_per_block = 1.0 / controls.cell_size;
+                if (cells_per_block - cells_per_block.round()).abs() > 0.001 {
+                    warn("Cells that don't divide a block leave artefacts");
+                }
+
+                let mut smoothness = controls.smoothness_level;
+                if ui.slider_config("Smoothness", 1, 6).build(&mut smoothness) {
+                    controls.set_smoothness_level(smoothness);
+                }
+                imgui::Drag::new("Kernel size")
+                    .range(0.5, 4.0)
+                    .speed(0.01)
+                    .display_format("%.3f")
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .build(ui, &mut controls.kernel_size);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Radius of the cube the density is averaged over, in blocks. Larger \
+                         kernels round the terrain more. Ctrl+click to type a value.",
+                    );
+                }
+                if controls.kernel_size > 1.0 {
+                    warn("Kernels above 1.0 make 1 block thick walls disappear");
+                }
+                ui.checkbox("Distance LOD", &mut controls.distance_lod);
+                ui.checkbox("Chunk skirts", &mut controls.skirts);
+                ui.checkbox("Watertight caps", &mut controls.caps);
+                ui.checkbox("Octree density", &mut controls.octree_density);
+                ui.checkbox("Density cache", &mut controls.density_cache);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Sample the density once per chunk and interpolate it for the normals \
+                         and the stitching. Faster builds, slightly flatter shading.",
+                    );
+                }
+                ui.checkbox("Ambient occlusion", &mut controls.ambient_occlusion);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Darken creases and cavities by sampling the density around every \
+                         vertex. Slower builds.",
+                    );
+                }
+
+                // PolygonizationOptions::validate repairs what the sliders can't prevent
+                let (bottom, top) = (WORLD_BOTTOM as isize, WORLD_TOP as isize);
+                ui.slider_config("Limit Y", bottom, top - 1)
+                    .build(&mut controls.y_low_limit);
+                let y_range_max = (top - controls.y_low_limit).max(2) as usize;
+                ui.slider_config("Y Range", 1, y_range_max)
+                    .build(&mut controls.y_size);
+                ui.separator();
+                ui.slider_config("Surface level", -0.9, 0.9)
+                    .build(&mut controls.surface_level);
+                ui.slider_config("Density bias", 0.05, 0.95)
+                    .build(&mut controls.density_bias);
+                ui.slider_config("Density gain", 0.05, 0.95)
+                    .build(&mut controls.density_gain);
+                ui.separator();
+                controls.apply |= ui.button_with_size("APPLY", [0.0, 0.0]);
+                ui.same_line();
+                ui.checkbox("Auto apply", &mut controls.auto_apply);
+                ui.same_line();
+                controls.open_world_dialog |= ui.button("Open world...");
+                for correction in corrections.iter() {
+                    ui.text_colored(
+                        [1.0, 0.7, 0.2, 1.0],
+                        format!(
+                            "{} {} changed to {}",
+                            correction.option, correction.requested, correction.corrected
+                        ),
+                    );
+                }
+                ui.separator();
+                ui.slider_config("Comparison swipe", 0.0, 1.0)
+                    .build(&mut controls.comparison_swipe);
+                ui.checkbox("Comparison difference", &mut controls.comparison_difference);
+                ui.separator();
+                ui.checkbox("Chunk grid", &mut controls.show_grid);
+                ui.checkbox("Grid on terrain", &mut controls.grid_on_terrain);
+                if !controls.grid_on_terrain {
+                    ui.slider_config("Grid height", -64.0, 320.0)
+                        .build(&mut controls.grid_plane_y);
+                }
+                ui.checkbox("Compass", &mut controls.show_compass);
+                ui.checkbox("Sign labels", &mut controls.show_signs);
+                ui.checkbox("Build cost heatmap", &mut controls.show_build_cost);
+                let ramp_names = ALL_COLOR_RAMPS.map(|ramp| ramp.name());
+                let mut ramp_index = ALL_COLOR_RAMPS
+                    .iter()
+                    .position(|ramp| *ramp == controls.color_ramp)
+                    .unwrap_or(0);
+                if ui.combo_simple_string("Color ramp", &mut ramp_index, &ramp_names) {
+                    controls.color_ramp = ALL_COLOR_RAMPS[ramp_index];
+                }
+                ui.checkbox("Generated boundary", &mut controls.show_generated_boundary);
+                ui.checkbox("Sun direction", &mut controls.show_sun_gizmo);
+                ui.checkbox("Gradient probe", &mut controls.show_gradient_probe);
+                ui.checkbox("Cell inspector", &mut controls.show_cell_inspector);
+                ui.checkbox("Material legend", &mut controls.show_material_legend);
+                ui.checkbox("Block composition", &mut controls.show_block_composition);
+                ui.checkbox("Elevation profile", &mut controls.show_elevation_profile);
+                ui.checkbox("Path finder", &mut controls.show_path_finder);
+                ui.checkbox("Streams", &mut controls.show_streams);
+                ui.same_line();
+                ui.checkbox("Watersheds", &mut controls.show_watersheds);
+                if controls.show_streams {
+                    ui.slider_config("Stream threshold", 8, 4096)
+                        .flags(imgui::SliderFlags::LOGARITHMIC)
+                        .build(&mut controls.stream_threshold);
+                }
+                ui.checkbox("Sculpt", &mut controls.sculpt);
+                if controls.sculpt {
+                    ui.slider_config("Brush radius", 1.0, 8.0)
+                        .display_format("%.1f")
+                        .build(&mut controls.sculpt_radius);
+                }
+                ui.checkbox("Dithered materials", &mut controls.material_dithering);
+                ui.checkbox("Order independent transparency", &mut controls.weighted_oit);
+                ui.checkbox("Shadows", &mut controls.shadows);
+                ui.slider_config("Surface fog", 0.0, 0.05)
+                    .display_format("%.3f")
+                    .build(&mut controls.surface_fog_density);
+                ui.slider_config("Cave fog", 0.0, 0.5)
+                    .display_format("%.3f")
+                    .build(&mut controls.cave_fog_density);
+                ui.slider_config("Vertical scale", 0.5, 5.0)
+                    .display_format("%.2fx")
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .build(&mut controls.vertical_scale);
+                ui.slider_config(
+                    "View distance",
+                    config::MIN_CHUNK_LOAD_RADIUS,
+                    config::MAX_CHUNK_LOAD_RADIUS,
+                )
+                .display_format("%d chunks")
+                .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                .build(&mut controls.load_radius);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        "Chunks loaded in every direction from the camera. Chunks further away \
+                         take more memory and longer to mesh.",
+                    );
+                }
+                ui.checkbox("Crossfade rebuilt meshes", &mut controls.mesh_crossfade);
+                ui.checkbox("Smooth camera motion", &mut controls.smooth_camera);
+                ui.separator();
+                ui.slider_config("UI scale", 0.75, 2.5)
+                    .display_format("%.2f")
+                    .build(&mut controls.ui_scale);
+                ui.separator();
+                controls.offline_render_requested |= ui.button("Render offline image");
+                controls.lightmap_bake_requested |= ui.button("Bake lightmaps");
+                ui.same_line();
+                ui.checkbox("Baked lighting", &mut controls.baked_lighting);
+                if ui.button("Export OBJ") {
+                    controls.mesh_export_requested = Some(MeshFormat::Obj);
+                }
+                ui.same_line();
+                if ui.button("Export glTF") {
+                    controls.mesh_export_requested = Some(MeshFormat::Glb);
+                }
+                controls.map_export_requested |= ui.button("Export map");
+                ui.same_line();
+                ui.checkbox("Whole world", &mut controls.map_whole_world);
+                if controls.map_whole_world {
+                    ui.same_line();
+                    ui.checkbox("Web tiles", &mut controls.map_web_tiles);
+                }
+                ui.slider_config("Meters per pixel", 0.125, 8.0)
+                    .flags(imgui::SliderFlags::LOGARITHMIC)
+                    .display_format("%.3f")
+                    .build(&mut controls.map_meters_per_pixel);
+                ui.checkbox("Contours", &mut controls.map_contours);
+                if controls.map_contours {
+                    ui.slider_config("Contour interval", 1.0, 32.0)
+                        .display_format("%.0f")
+                        .build(&mut controls.map_contour_interval);
+                }
+                controls.world_scan_requested |= ui.button("Scan world");
+            });
+    };
+
+    Box::new(builder)
+}
+
+// Every material with its texture and the properties from the material registry,
+// which can be edited here. Edits are applied to the loaded world right away.
+fn get_material_legend_builder(block_textures: [imgui::TextureId; BLOCK_TYPES]) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        if !controls.show_material_legend {
+            return;
+        }
+
+        const SWATCH_SIZE: f32 = 16.0;
+        let pass_names = SMOOTHING_PASSES.map(|pass| format!("{pass:?}"));
+        let material_names = ALL_BLOCK_TYPES.map(|material| format!("{material:?}"));
+
+        ui.window("materials")
+            .size([420.0, 400.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 300.0], imgui::Condition::FirstUseEver)
+            .opened(&mut controls.show_material_legend)
+            .build(|| {
+                ui.text_disabled("Smoothing pass and activation threshold of each material.");
+                ui.text_disabled("Rigid materials are drawn as blocks, Leaves are smoothed apart.");
+                ui.separator();
+
+                for material in ALL_BLOCK_TYPES {
+                    let texture = block_textures[material as usize];
+                    imgui::Image::new(texture, [SWATCH_SIZE, SWATCH_SIZE]).build(ui);
+                    ui.same_line();
+                    ui.text(format!("{material:?}"));
+
+                    // Air is never part of a surface
+                    if material == BlockType::Air {
+                        ui.same_line_with_pos(140.0);
+                        ui.text_disabled("empty space");
+                        continue;
+                    }
+
+                    let properties = material_registry::get(material);
+                    let mut edited = properties;
+
+                    let mut pass_index = SMOOTHING_PASSES
+                        .iter()
+                        .position(|pass| *pass == properties.pass)
+                        .unwrap_or(0);
+                    ui.same_line_with_pos(140.0);
+                    ui.set_next_item_width(90.0);
+                    if ui.combo_simple_string(
+                        format!("##pass{material:?}"),
+                        &mut pass_index,
+                        &pass_names,
+                    ) {
+                        edited.pass = SMOOTHING_PASSES[pass_index];
+                    }
+
+                    let mut threshold = properties.activation_threshold as f32;
+                    ui.same_line();
+                    ui.set_next_item_width(-1.0);
+                    if ui
+                        .slider_config(format!("##threshold{material:?}"), 0.0, 1.0)
+                        .display_format("%.2f")
+                        .build(&mut threshold)
+                    {
+                        edited.activation_threshold = threshold as Real;
+                    }
+
+                    // The pass decides the shape of the surface,
+                    // the threshold only how the materials are blended on it
+                    if edited != properties {
+                        material_registry::set(material, edited);
+                        if edited.pass != properties.pass {
+                            controls.materials_changed = true;
+                        } else {
+                            controls.materials_recolored = true;
+                        }
+                    }
+                }
+
+                // Modded blocks are mapped to a material when their chunk is loaded
+                ui.separator();
+                ui.text_disabled("Material of modded blocks, applies to the chunks loaded next.");
+                let material_index = |material: BlockType| {
+                    ALL_BLOCK_TYPES
+                        .iter()
+                        .position(|block| *block == material)
+                        .unwrap_or(0)
+                };
+                for (namespace, material) in material_registry::namespace_fallbacks() {
+                    let mut index = material_index(material);
+                    ui.text(&namespace);
+                    ui.same_line_with_pos(140.0);
+                    ui.set_next_item_width(-1.0);
+                    if ui.combo_simple_string(
+                        format!("##namespace{namespace}"),
+                        &mut index,
+                        &material_names,
+                    ) {
+                        material_registry::set_namespace_fallback(
+                            &namespace,
+                            ALL_BLOCK_TYPES[index],
+                        );
+                    }
+                }
+                let mut index = material_index(material_registry::modded_block_fallback());
+                ui.text("other namespaces");
+                ui.same_line_with_pos(140.0);
+                ui.set_next_item_width(-1.0);
+                if ui.combo_simple_string("##modded", &mut index, &material_names) {
+                    material_registry::set_modded_block_fallback(ALL_BLOCK_TYPES[index]);
+                }
+
+                ui.separator();
+                if ui.button("Reset to defaults") {
+                    material_registry::reset();
+                    controls.materials_changed = true;
+                }
+
+                // Hiding rigid blocks only skips their range of the instance buffer
+                ui.separator();
+                ui.text_disabled("Rigid blocks shown");
+                for material in material_registry::materials_in(SmoothingPass::Rigid) {
+                    let index = material as usize;
+                    ui.checkbox(
+                        format!("{material:?}"),
+                        &mut controls.rigid_materials_shown[index],
+                    );
+                    ui.same_line_with_pos(140.0);
+                    if ui.small_button(format!("only##{material:?}")) {
+                        controls.rigid_materials_shown = [false; BLOCK_TYPES];
+                        controls.rigid_materials_shown[index] = true;
+                    }
+                }
+                if ui.button("Show all") {
+                    controls.rigid_materials_shown = [true; BLOCK_TYPES];
+                }
+            });
+    };
+
+    Box::new(builder)
+}
+
+// Histogram of the materials in the loaded chunks, and in a single chunk picked from the list
+fn get_block_composition_builder(
+    composition: Rc<RefCell<BlockComposition>>,
+    camera_chunk: ChunkPosition,
+) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        if !controls.show_block_composition {
+            return;
+        }
+        let mut composition = composition.borrow_mut();
+
+        ui.window("block composition")
+            .size([520.0, 420.0], imgui::Condition::FirstUseEver)
+            .position([720.0, 60.0], imgui::Condition::FirstUseEver)
+            .opened(&mut controls.show_block_composition)
+            .build(|| {
+                let chunks = composition.chunks();
+
+                ui.child_window("chunks")
+                    .size([140.0, 0.0])
+                    .border(true)
+                    .build(|| {
+                        if ui.button("Under camera") {
+                            composition.selected_chunk = Some(camera_chunk);
+                        }
+                        ui.separator();
+
+                        for chunk_position in &chunks {
+                            let (x, z) = chunk_position.get_global_position_in_chunks();
+                            let selected = composition.selected_chunk == Some(*chunk_position);
+                            let label = match *chunk_position == camera_chunk {
+                                true => format!("{x}, {z} (camera)"),
+                                false => format!("{x}, {z}"),
+                            };
+                            if ui.selectable_config(label).selected(selected).build() {
+                                composition.selected_chunk = Some(*chunk_position);
+                            }
+                        }
+                    });
+                ui.same_line();
+
+                ui.group(|| {
+                    ui.text(format!("All {} loaded chunks", chunks.len()));
+                    material_histogram(ui, composition.totals());
+
+                    ui.separator();
+                    let selected_counts = composition
+                        .selected_chunk
+                        .and_then(|chunk_position| composition.chunk_counts(chunk_position));
+                    match (composition.selected_chunk, selected_counts) {
+                        (Some(chunk_position), Some(counts)) => {
+                            let (x, z) = chunk_position.get_global_position_in_chunks();
+                            ui.text(format!("Chunk {x}, {z}"));
+                            material_histogram(ui, counts);
+                        }
+                        _ => ui.text_disabled("Pick a chunk to see its breakdown"),
+                    }
+                });
+            });
+    };
+
+    Box::new(builder)
+}
+
+// Surface height along the line marked with E, see ProfileTool
+fn get_elevation_profile_builder(tool: Rc<RefCell<ProfileTool>>) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        if !controls.show_elevation_profile {
+            return;
+        }
+        let mut tool = tool.borrow_mut();
+
+        ui.window("elevation profile")
+            .size([480.0, 280.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 480.0], imgui::Condition::FirstUseEver)
+            .opened(&mut controls.show_elevation_profile)
+            .build(|| {
+                if let Some(start) = tool.start {
+                    ui.text(format!("Start at x: {:.1} z: {:.1}", start.x, start.y));
+                    ui.text_disabled("Press E while looking at the end of the line");
+                } else if tool.profile.is_none() {
+                    ui.text_disabled("Press E while looking at the terrain to start a line");
+                }
+
+                let Some(profile) = &tool.profile else {
+                    return;
+                };
+
+                ui.text(format!(
+                    "x: {:.1} z: {:.1} -> x: {:.1} z: {:.1}, {:.1} blocks",
+                    profile.start.x,
+                    profile.start.y,
+                    profile.end.x,
+                    profile.end.y,
+                    profile.length()
+                ));
+
+                match profile.height_range() {
+                    Some((min_height, max_height)) => {
+                        // Samples without terrain are drawn at the bottom of the plot
+                        let heights = profile
+                            .samples
+                            .iter()
+                            .map(|sample| sample.height.unwrap_or(min_height) as f32)
+                            .collect::<Vec<_>>();
+
+                        ui.text(format!("height: {min_height} to {max_height}"));
+                        ui.plot_lines("##profile", &heights)
+                            .graph_size([-1.0, 160.0])
+                            .scale_min(min_height as f32 - 1.0)
+                            .scale_max(max_height as f32 + 1.0)
+                            .build();
+                    }
+                    None => ui.text_disabled("The line doesn't cross any loaded terrain"),
+                }
+
+                let export = ui.button("Export CSV");
+                ui.same_line();
+                if ui.button("Clear") {
+                    tool.clear();
+                    return;
+                }
+                tool.export_requested |= export;
+            });
+    };
+
+    Box::new(builder)
+}
+
+// Walkable route between the points marked with F, see PathTool
+fn get_path_finder_builder(tool: Rc<RefCell<PathTool>>) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        if !controls.show_path_finder {
+            return;
+        }
+        let mut tool = tool.borrow_mut();
+
+        ui.window("path finder")
+            .size([360.0, 180.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 280.0], imgui::Condition::FirstUseEver)
+            .opened(&mut controls.show_path_finder)
+            .build(|| {
+                ui.slider_config("Max slope", 0.25, 4.0)
+                    .display_format("%.2f")
+                    .build(&mut controls.path_max_slope);
+                ui.text_disabled(format!(
+                    "blocks of height per block, {:.0} degrees",
+                    controls.path_max_slope.atan().to_degrees()
+                ));
+                ui.separator();
+
+                match (tool.start, tool.goal) {
+                    (None, _) => {
+                        ui.text_disabled("Press F while looking at the terrain to mark the start")
+                    }
+                    (Some(start), None) => {
+                        ui.text(format!("Start at x: {:.1} z: {:.1}", start.x, start.y));
+                        ui.text_disabled("Press F while looking at the goal");
+                    }
+                    (Some(start), Some(goal)) => ui.text(format!(
+                        "x: {:.1} z: {:.1} -> x: {:.1} z: {:.1}",
+                        start.x, start.y, goal.x, goal.y
+                    )),
+                }
+
+                match &tool.path {
+                    Some(Ok(path)) => ui.text(format!(
+                        "{:.1} blocks long, {} columns searched",
+                        path.length, path.expanded
+                    )),
+                    Some(Err(error)) => ui.text_colored([1.0, 0.4, 0.4, 1.0], error.to_string()),
+                    None => {}
+                }
+
+                if ui.button("Clear") {
+                    tool.clear();
+                }
+            });
+    };
+
+    Box::new(builder)
+}
+
+// A bar per material present in *counts*, the most common material first
+fn material_histogram(ui: &imgui::Ui, counts: &MaterialCounts) {
+    let total = counts.iter().sum::<usize>();
+    if total == 0 {
+        ui.text_disabled("No blocks");
+        return;
+    }
+
+    let most_common = counts.iter().copied().max().unwrap_or(1);
+    let mut present = ALL_BLOCK_TYPES
+        .into_iter()
+        .filter(|material| counts[*material as usize] > 0)
+        .collect::<Vec<_>>();
+    present.sort_by_key(|material| std::cmp::Reverse(counts[*material as usize]));
+
+    for material in present {
+        let count = counts[material as usize];
+        let share = count as f32 / total as f32;
+
+        imgui::ProgressBar::new(count as f32 / most_common as f32)
+            .size([-1.0, 0.0])
+            .overlay_text(format!("{material:?}: {count} ({:.1}%)", share * 100.0))
+            .build(ui);
+    }
+}
+
+// Debugging actions for the chunk picked with a right click on the chunk grid
+fn get_chunk_builder(world: &World) -> UIWindowBuilder {
+    let polygonized = world.polygonized_chunk_positions();
+    let chunks_being_loaded = world.chunks_being_loaded();
+
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        let Some(chunk) = controls.inspected_chunk else {
+            return;
+        };
+
+        let mut opened = true;
+        ui.window("chunk")
+            .opened(&mut opened)
+            .size([260.0, 140.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 200.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                ui.text(format!(
+                    "region: [{}, {}] chunk: [{}, {}]",
+                    chunk.region_x, chunk.region_z, chunk.chunk_x, chunk.chunk_z
+                ));
+                ui.text(format!("chunks loading: {chunks_being_loaded}"));
+                if ui.button("Reload from disk") {
+                    controls.chunk_command = Some(ChunkCommand::ReloadFromDisk(chunk));
+                }
+                // Only the chunks with all their neighbours loaded have a smooth mesh
+                if polygonized.contains(&chunk) && ui.button("Rebuild mesh") {
+                    controls.chunk_command = Some(ChunkCommand::RebuildMesh(chunk));
+                }
+            });
+
+        if !opened {
+            controls.inspected_chunk = None;
+        }
+    };
+
+    Box::new(builder)
+}
+
+// Corner densities, case and triangles of the inspected marching cubes cell.
+// The cell is drawn magnified as the camera sees it, corners inside the surface are filled
+fn get_cell_inspector_builder(
+    inspection: Option<CellInspection>,
+    camera: &Camera,
+) -> UIWindowBuilder {
+    let world_to_view = camera.world_to_view;
+
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        if !controls.show_cell_inspector {
+            return;
+        }
+
+        ui.window("cell inspector")
+            .opened(&mut controls.show_cell_inspector)
+            .size([320.0, 560.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 200.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                let Some(inspection) = &inspection else {
+                    ui.text_wrapped("Press V to inspect the cell in the center of the screen");
+                    return;
+                };
+
+                let origin = inspection.corners[0];
+                ui.text(format!(
+                    "corner 0: {:.2} {:.2} {:.2}",
+                    origin.x, origin.y, origin.z
+                ));
+                ui.text(format!(
+                    "case: {} ({:08b}), {} triangles",
+                    inspection.case,
+                    inspection.case,
+                    inspection.triangles.len()
+                ));
+                for (corner, density) in inspection.densities.iter().enumerate() {
+                    let side = match inspection.is_inside(corner) {
+                        true => "inside",
+                        false => "outside",
+                    };
+                    ui.text(format!("corner {corner}: {density:>8.3} {side}"));
+                }
+
+                ui.separator();
+                let steps = [
+                    ("-x", [-1, 0, 0]),
+                    ("+x", [1, 0, 0]),
+                    ("-y", [0, -1, 0]),
+                    ("+y", [0, 1, 0]),
+                    ("-z", [0, 0, -1]),
+                    ("+z", [0, 0, 1]),
+                ];
+                for (index, (label, step)) in steps.into_iter().enumerate() {
+                    if index > 0 {
+                        ui.same_line();
+                    }
+                    if ui.button(label) {
+                        controls.cell_step = Some(step);
+                    }
+                }
+
+                draw_cell(ui, inspection, &world_to_view);
+            });
+    };
+
+    Box::new(builder)
+}
+
+// Orthographic view of the cell rotated like the camera, filling the width of the window
+fn draw_cell(ui: &imgui::Ui, inspection: &CellInspection, world_to_view: &Matrix4<Real>) {
+    let [width, _] = ui.content_region_avail();
+    let size = width.max(100.0);
+    let [left, top] = ui.cursor_screen_pos();
+    let center = [left + size / 2.0, top + size / 2.0];
+    ui.dummy([size, size]);
+
+    // The cell spans about half of the canvas whichever way it is turned
+    let cell_center = inspection.corners[0].midpoint(inspection.corners[6]);
+    let cell_size = inspection.corners[6].x - inspection.corners[0].x;
+    let scale = size as Real * 0.35 / cell_size;
+    let to_canvas = |point: Position| {
+        let view = world_to_view.transform_vector(point - cell_center) * scale;
+        [center[0] + view.x as f32, center[1] - view.y as f32]
+    };
+
+    let draw_list = ui.get_window_draw_list();
+    for [start, end] in mc_tables::EDGE_CORNERS {
+        let (start, end) = (inspection.corners[start], inspection.corners[end]);
+        draw_list
+            .add_line(to_canvas(start), to_canvas(end), [0.6, 0.6, 0.6, 1.0])
+            .build();
+    }
+    for triangle in &inspection.triangles {
+        let [a, b, c] = triangle.map(to_canvas);
+        draw_list
+            .add_triangle(a, b, c, [0.2, 0.7, 0.7, 0.35])
+            .filled(true)
+            .build();
+        draw_list
+            .add_triangle(a, b, c, [0.2, 0.9, 0.9, 1.0])
+            .thickness(1.5)
+            .build();
+    }
+    for (corner, position) in inspection.corners.iter().enumerate() {
+        let point = to_canvas(*position);
+        draw_list
+            .add_circle(point, 5.0, [1.0, 1.0, 1.0, 1.0])
+            .filled(inspection.is_inside(corner))
+            .build();
+        draw_list.add_text(
+            [point[0] + 7.0, point[1] - 7.0],
+            [1.0, 1.0, 1.0, 1.0],
+            format!("{corner}"),
+        );
+    }
+}
+
+// Compass rose in the top right corner of the screen, north points towards -z
+fn get_compass_builder(camera: &Camera) -> UIWindowBuilder {
+    let direction = camera.get_direction();
+    let heading = Vector3::new(direction.x, 0.0, direction.z);
+    let heading = match heading.magnitude2() > 0.0 {
+        true => heading.normalize(),
+        false => -Vector3::unit_z(),
+    };
+
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        if !controls.show_compass {
+            return;
+        }
+
+        const RADIUS: f32 = 40.0;
+        const MARGIN: f32 = 20.0;
+        let [width, _] = ui.io().display_size;
+        let center = [width - RADIUS - MARGIN, RADIUS + MARGIN];
+
+        // Screen up is the heading of the camera, screen right is the heading rotated clockwise
+        let to_screen = |world_x: f64, world_z: f64, scale: f32| {
+            let right = -world_x * heading.z + world_z * heading.x;
+            let up = world_x * heading.x + world_z * heading.z;
+            [
+                center[0] + right as f32 * RADIUS * scale,
+                center[1] - up as f32 * RADIUS * scale,
+            ]
+        };
+
+        let draw_list = ui.get_foreground_draw_list();
+        draw_list
+            .add_circle(center, RADIUS, [0.1, 0.1, 0.1, 0.6])
+            .filled(true)
+            .build();
+
+        let cardinals = [
+            ("N", 0.0, -1.0, [1.0, 0.2, 0.2, 1.0]),
+            ("E", 1.0, 0.0, [1.0, 1.0, 1.0, 1.0]),
+            ("S", 0.0, 1.0, [1.0, 1.0, 1.0, 1.0]),
+            ("W", -1.0, 0.0, [1.0, 1.0, 1.0, 1.0]),
+        ];
+        for (label, world_x, world_z, color) in cardinals {
+            draw_list
+                .add_line(center, to_screen(world_x, world_z, 0.6), color)
+                .thickness(2.0)
+                .build();
+
+            let [label_x, label_y] = to_screen(world_x, world_z, 0.85);
+            let [label_width, label_height] = ui.calc_text_size(label);
+            let label_position = [label_x - label_width / 2.0, label_y - label_height / 2.0];
+            draw_list.add_text(label_position, color, label);
+        }
+    };
+
+    Box::new(builder)
+}
+
+// Text of the nearby signs drawn at their positions in the world
+fn get_sign_labels_builder(
+    camera: &Camera,
+    vertical_scale: VerticalScale,
+    world: &World,
+) -> UIWindowBuilder {
+    let camera_position = vertical_scale.to_world(camera.get_position());
+    let signs = world.get_signs_near(camera_position, config::SIGN_LABEL_DISTANCE);
+    let world_to_clip = camera.projection * camera.world_to_view * vertical_scale.model_matrix();
+
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        if !controls.show_signs {
+            return;
+        }
+
+        let [width, height] = ui.io().display_size;
+        let draw_list = ui.get_foreground_draw_list();
+
+        for sign in signs.iter() {
+            let clip = world_to_clip * sign.position.to_homogeneous();
+            // Behind the camera
+            if clip.w <= 0.0 {
+                continue;
+            }
+
+            let (ndc_x, ndc_y) = (clip.x / clip.w, clip.y / clip.w);
+            if ndc_x.abs() > 1.0 || ndc_y.abs() > 1.0 {
+                continue;
+            }
+
+            let [text_width, text_height] = ui.calc_text_size(&sign.text);
+            let anchor = [
+                (ndc_x as f32 + 1.0) / 2.0 * width,
+                (1.0 - ndc_y as f32) / 2.0 * height,
+            ];
+            let text_position = [anchor[0] - text_width / 2.0, anchor[1] - text_height];
+
+            const PADDING: f32 = 3.0;
+            draw_list
+                .add_rect(
+                    [text_position[0] - PADDING, text_position[1] - PADDING],
+                    [
+                        text_position[0] + text_width + PADDING,
+                        text_position[1] + text_height + PADDING,
+                    ],
+                    [0.3, 0.2, 0.1, 0.75],
+                )
+                .filled(true)
+                .build();
+            draw_list.add_text(text_position, [1.0, 1.0, 1.0, 1.0], &sign.text);
+        }
+    };
+
+    Box::new(builder)
+}
+
+// Window in the middle of the screen without decorations
+fn centered_window<'ui>(ui: &'ui imgui::Ui, name: &'ui str) -> imgui::Window<'ui, 'ui, &'ui str> {
+    let [width, height] = ui.io().display_size;
+
+    ui.window(name)
+        .position([width / 2.0, height / 2.0], imgui::Condition::Always)
+        .position_pivot([0.5, 0.5])
+        .always_auto_resize(true)
+        .title_bar(false)
+        .movable(false)
+}
+
+fn get_loading_screen_builder(progress: LoadingProgress) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, _controls: &mut SmoothMeshOptions| {
+        let fraction = |done: usize, total: usize| match total {
+            0 => 0.0,
+            _ => done as f32 / total as f32,
+        };
+
+        centered_window(ui, "loading").build(|| {
+            ui.text("Loading chunks");
+            imgui::ProgressBar::new(fraction(progress.chunks_loaded, progress.chunks_total))
+                .overlay_text(format!(
+                    "{}/{}",
+                    progress.chunks_loaded, progress.chunks_total
+                ))
+                .size([320.0, 0.0])
+                .build(ui);
+
+            ui.text("Building meshes");
+            imgui::ProgressBar::new(fraction(progress.meshes_built, progress.meshes_total))
+                .overlay_text(format!(
+                    "{}/{}",
+                    progress.meshes_built, progress.meshes_total
+                ))
+                .size([320.0, 0.0])
+                .build(ui);
+        });
+    };
+
+    Box::new(builder)
+}
+
+fn get_error_screen_builder(message: String) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, _controls: &mut SmoothMeshOptions| {
+        centered_window(ui, "error").build(|| {
+            ui.text_colored([0.8, 0.1, 0.1, 1.0], "The world could not be opened");
+            ui.text(&message);
+            ui.separator();
+            ui.text("Press Q to quit");
+        });
+    };
+
+    Box::new(builder)
+}
+
+// Full screen panel for picking the world and then the start location
+fn get_world_dialog_builder(
+    dialog: Rc<RefCell<WorldDialog>>,
+    state: AppState,
+    can_cancel: bool,
+) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, _controls: &mut SmoothMeshOptions| {
+        let mut dialog = dialog.borrow_mut();
+        let dialog = &mut *dialog;
+
+        ui.window("world dialog")
+            .position([0.0, 0.0], imgui::Condition::Always)
+            .size(ui.io().display_size, imgui::Condition::Always)
+            .title_bar(false)
+            .movable(false)
+            .resizable(false)
+            .build(|| {
+                if state == AppState::ChoosingStart {
+                    ui.text("Where should the camera start?");
+                    ui.separator();
+
+                    for (index, location) in dialog.start_locations.iter().enumerate() {
+                        let StartLocation { name, position } = location;
+                        let label = format!(
+                            "{name} [{:.0}, {:.0}, {:.0}]##{index}",
+                            position.x, position.y, position.z
+                        );
+                        if ui.button(label) {
+                            dialog.chosen_start = Some(*position);
+                        }
+                    }
+                    return;
+                }
+
+                ui.text("Open a world");
+                ui.separator();
+
+                if dialog.recent_worlds.is_empty() {
+                    ui.text_disabled("No recently opened worlds");
+                }
+                for world_path in &dialog.recent_worlds {
+                    if ui.selectable(world_path) {
+                        dialog.chosen_world = Some(world_path.clone());
+                    }
+                }
+                if ui.selectable(format!("Demo world ({})", config::WORLD_FOLDER)) {
+                    dialog.chosen_world = Some(config::WORLD_FOLDER.to_owned());
+                }
+
+                ui.separator();
+                dialog.browse_folder_requested |= ui.button("Browse save folder...");
+                ui.same_line();
+                dialog.browse_backup_requested |= ui.button("Browse backup...");
+                if can_cancel {
+                    ui.same_line();
+                    dialog.cancel_requested |= ui.button("Cancel");
+                }
+
+                if let Some(error) = &dialog.error {
+                    ui.separator();
+                    ui.text_colored([0.8, 0.1, 0.1, 1.0], error);
+                }
+            });
+    };
+
+    Box::new(builder)
+}
+
+// Edges of the inspected cell and the wireframe of its triangles
+fn queue_cell_outline(debug_draw: &mut DebugDraw, inspection: &CellInspection) {
+    let corner = |index: usize| inspection.corners[index].cast::<f32>().unwrap();
+
+    for [start, end] in mc_tables::EDGE_CORNERS {
+        debug_draw.line(corner(start), corner(end), config::CELL_EDGE_COLOR);
+    }
+    for triangle in &inspection.triangles {
+        let [a, b, c] = triangle.map(|vertex| vertex.cast::<f32>().unwrap());
+        for (start, end) in [(a, b), (b, c), (c, a)] {
+            debug_draw.line(start, end, config::CELL_TRIANGLE_COLOR);
+        }
+    }
+}
 
-fn main() {
-    let (event_loop, display) = create_window();
+// Corners of the inspected chunk over the whole height of the world
+fn queue_chunk_highlight(debug_draw: &mut DebugDraw, chunk: ChunkPosition) {
+    let bounds = chunk.coords().bounds();
+    let corners = [
+        (bounds.left(), bounds.bottom()),
+        (bounds.right(), bounds.bottom()),
+        (bounds.left(), bounds.top()),
+        (bounds.right(), bounds.top()),
+    ];
 
-    let block_pallette = texture_from_file("block-palette.png", &display);
+    for (x, z) in corners {
+        let (x, z) = (x as f32, z as f32);
+        debug_draw.line(
+            Point3::new(x, minecraft::MIN_BLOCK_Y as f32, z),
+            Point3::new(x, minecraft::MAX_BLOCK_Y as f32, z),
+            config::CHUNK_HIGHLIGHT_COLOR,
+        );
+    }
+}
 
-    let mut controls = SmoothMeshOptions::default();
-    let mut polygonization_options = controls.into();
+// Arrow towards the sun a few blocks in front of the camera.
+// The debug shapes are drawn scaled like the world, so they are placed in the world.
+fn queue_sun_gizmo(debug_draw: &mut DebugDraw, camera: &Camera, vertical_scale: VerticalScale) {
+    let camera_position = camera.get_position();
+    let origin = camera_position + camera.get_direction().normalize() * config::SUN_GIZMO_DISTANCE;
+    let origin = vertical_scale.to_world(origin).cast::<f32>().unwrap();
+    let sun = vertical_scale.to_world(Point3::from(sun_position(camera)).cast().unwrap());
 
-    let mut world = discrete::World::new(config::SPAWN_POINT);
-    world.dispatch_mesh_builder(polygonization_options);
+    let sun_direction = (sun.cast::<f32>().unwrap() - origin).normalize();
+    debug_draw.arrow(origin, origin + sun_direction, config::SUN_GIZMO_COLOR);
+    debug_draw.sphere(origin, 0.05, config::SUN_GIZMO_COLOR);
+}
 
-    let mut camera = create_camera(display.get_framebuffer_dimensions());
+// Density gradient and the tangent plane of the isosurface where the center of the screen
+// looks at the terrain, see pick_terrain
+fn queue_gradient_probe(
+    debug_draw: &mut DebugDraw,
+    world: &World,
+    world_bvh: &WorldBvh,
+    camera: &Camera,
+    vertical_scale: VerticalScale,
+    render_mode: RenderingMode,
+    options: PolygonizationOptions,
+) {
+    let Some(position) = pick_terrain(world, world_bvh, camera, vertical_scale, render_mode) else {
+        return;
+    };
+    let gradient = get_smooth_normal(world, position, options);
+    // No gradient where the density is flat
+    if gradient.x.is_nan() {
+        return;
+    }
 
-    let mut rigid_scene = create_rigid_scene(&world, &display);
-    let mut discrete_scene = create_discrete_scene(&world, &display);
-    let mut implicit_scene = create_implicit_scene(&world, &display);
+    let point = position.cast::<f32>().unwrap();
+    let gradient = gradient.cast::<f32>().unwrap();
+    debug_draw.arrow(
+        point,
+        point + gradient * config::GRADIENT_PROBE_LENGTH,
+        config::GRADIENT_PROBE_ARROW_COLOR,
+    );
+    debug_draw.disc(
+        point,
+        gradient,
+        config::GRADIENT_PROBE_DISC_RADIUS,
+        config::GRADIENT_PROBE_DISC_COLOR,
+    );
+}
 
-    let mut imgui_data = ImguiWrapper::new(&display);
+// Point on the terrain in the center of the screen. The smooth surface is picked where its mesh
+// is built and shown, the blocks otherwise. The ray of the camera is cast in the unscaled world.
+fn pick_terrain(
+    world: &World,
+    world_bvh: &WorldBvh,
+    camera: &Camera,
+    vertical_scale: VerticalScale,
+    render_mode: RenderingMode,
+) -> Option<Position> {
+    let origin = vertical_scale.to_world(camera.get_position());
+    let direction = vertical_scale
+        .direction_to_world(camera.get_direction())
+        .normalize();
+
+    let smooth_hit = match render_mode {
+        RenderingMode::Implicit => world_bvh.closest_hit(
+            origin.to_vec().cast().unwrap(),
+            direction.cast().unwrap(),
+            config::PROFILE_PICK_DISTANCE as f32,
+        ),
+        _ => None,
+    };
 
-    let mut render_state = RenderState::new();
-    let mut actions: Vec<InputAction> = Vec::new();
+    match smooth_hit {
+        Some(hit) => Some(origin + direction * hit.hit.distance as Real),
+        None => world
+            .raycast(origin, direction, config::PROFILE_PICK_DISTANCE)
+            .map(|hit| hit.position),
+    }
+}
 
-    event_loop.run(move |event, _, control_flow| match event {
-        Event::NewEvents(_) => {
-            actions.clear();
-            render_state.timing.record_frame();
-        }
-        Event::MainEventsCleared => {
-            let gl_window = display.gl_window();
-            let Some(new_state) = create_state(&actions, render_state, gl_window.window()) else {
-                *control_flow = ControlFlow::Exit;
-                return;
-            };
-            render_state = new_state;
+// Progress of the path traced image, shown while it renders in the background
+fn get_offline_render_builder(progress: f32) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, _controls: &mut SmoothMeshOptions| {
+        ui.window("offline render")
+            .size([300.0, 80.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 20.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                ui.text(format!("Rendering {}", config::OFFLINE_RENDER_PATH));
+                imgui::ProgressBar::new(progress)
+                    .size([-1.0, 0.0])
+                    .build(ui);
+            });
+    };
 
-            if controls.apply {
-                polygonization_options = controls.into();
-                world.rebuild_all_meshes(polygonization_options);
+    Box::new(builder)
+}
 
-                controls.apply = false;
-            }
+// Progress of the map export, shown while it runs in the background
+fn get_map_export_builder(progress: f32, paused: bool) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        ui.window("map export")
+            .size([300.0, 100.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 200.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                ui.text(format!("Exporting {}", config::MAP_EXPORT_FOLDER));
+                imgui::ProgressBar::new(progress)
+                    .size([-1.0, 0.0])
+                    .build(ui);
+                controls.map_export_command = scan_buttons(ui, paused);
+            });
+    };
 
-            imgui_data.prepare(gl_window.window(), render_state.timing.delta_time);
+    Box::new(builder)
+}
 
-            for action in &actions {
-                camera.consume(action, &render_state);
-            }
+// Pause or resume and cancel of a scan running in the background
+fn scan_buttons(ui: &imgui::Ui, paused: bool) -> Option<ScanCommand> {
+    let (label, toggle) = match paused {
+        true => ("Resume", ScanCommand::Resume),
+        false => ("Pause", ScanCommand::Pause),
+    };
 
-            camera.update(render_state.timing.delta_time.as_secs_f64());
+    let mut command = None;
+    if ui.button(label) {
+        command = Some(toggle);
+    }
+    ui.same_line();
+    if ui.button("Cancel") {
+        command = Some(ScanCommand::Cancel);
+    }
 
-            let update_geometry = config::DYNAMIC_WORLD
-                && world.update_chunk_data(camera.get_position(), polygonization_options);
+    command
+}
 
-            if update_geometry {
-                let instance_positions = {
-                    let blocks = world.get_surface_block_data();
-                    glium::vertex::VertexBuffer::new(&display, &blocks).unwrap()
-                };
-                discrete_scene.update_instance_data(instance_positions);
+// Cancels the scan and waits for the units being visited, so they are written into its checkpoint
+fn stop_scan<R: Send + 'static>(shutdown: &Shutdown, name: &str, scanner: Option<WorldScanner<R>>) {
+    let Some(scanner) = scanner else {
+        return;
+    };
 
-                let rigid_positions = {
-                    let rigid_blocks = world.get_rigid_blocks_data();
-                    glium::vertex::VertexBuffer::new(&display, &rigid_blocks).unwrap()
-                };
-                rigid_scene.update_instance_data(rigid_positions);
-            }
+    scanner.cancel();
+    if shutdown.wait(name, || scanner.is_finished()) {
+        if let ScanEnd::Failed(error) = scanner.join() {
+            eprintln!("[ERROR] {error}");
+        }
+    }
+}
 
-            let update_implicit_scene = world.update_smooth_mesh();
-            if update_implicit_scene {
-                implicit_scene = create_implicit_scene(&world, &display);
-            }
+fn apply_scan_command<R: Send + 'static>(scanner: &WorldScanner<R>, command: ScanCommand) {
+    match command {
+        ScanCommand::Pause => scanner.set_paused(true),
+        ScanCommand::Resume => scanner.set_paused(false),
+        ScanCommand::Cancel => scanner.cancel(),
+    }
+}
 
-            gl_window.window().request_redraw();
-        }
-        Event::RedrawRequested(_) => {
-            // Setup for drawing
-            let gl_window = display.gl_window();
-            let mut target = display.draw();
+// Progress of the lightmap bake, shown while it runs in the background
+fn get_lightmap_bake_builder(progress: f32) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, _controls: &mut SmoothMeshOptions| {
+        ui.window("lightmap bake")
+            .size([300.0, 80.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 110.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                ui.text("Baking lightmaps");
+                imgui::ProgressBar::new(progress)
+                    .size([-1.0, 0.0])
+                    .build(ui);
+            });
+    };
 
-            // Clear window
-            target.clear_color_srgb(1.0, 1.0, 1.0, 1.0);
-            target.clear_depth(1.0);
+    Box::new(builder)
+}
 
-            // Draw Scene
-            match render_state.render_mode {
-                RenderingMode::Discrete => render_world(
-                    &discrete_scene,
-                    &mut target,
-                    &camera,
-                    &render_state,
-                    &block_pallette,
-                ),
-                RenderingMode::Implicit => {
-                    if config::FILTER_RIGID {
-                        // render rigid blocks
-                        render_world(
-                            &rigid_scene,
-                            &mut target,
-                            &camera,
-                            &render_state,
-                            &block_pallette,
-                        );
-                    }
-                    // render smooth terrain
-                    render_world(
-                        &implicit_scene,
-                        &mut target,
-                        &camera,
-                        &render_state,
-                        &block_pallette,
-                    );
-                }
-            }
+// Statistics of the whole world, *scan* is the progress and whether it is paused
+// while a scan runs in the background
+fn get_world_report_builder(
+    report: Option<Rc<WorldReport>>,
+    scan: Option<(f32, bool)>,
+) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
+        if !controls.show_world_report {
+            return;
+        }
 
-            // Draw ui last so it shows on top of everything
-            let statistics_menu_builder =
-                get_statistics_menu_builder(&render_state, &camera, &world, polygonization_options);
-            let controls_menu = get_controls_menu_builder();
+        ui.window("world report")
+            .size([320.0, 420.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 200.0], imgui::Condition::FirstUseEver)
+            .opened(&mut controls.show_world_report)
+            .build(|| {
+                if let Some((progress, paused)) = scan {
+                    ui.text("Scanning regions");
+                    imgui::ProgressBar::new(progress)
+                        .size([-1.0, 0.0])
+                        .build(ui);
+                    controls.world_scan_command = scan_buttons(ui, paused);
+                    ui.separator();
+                }
 
-            imgui_data.add_window(statistics_menu_builder);
-            imgui_data.add_window(controls_menu);
-            imgui_data
-                .render_frame(gl_window.window(), &mut target, &mut controls)
-                .expect("Failed to render imgui ui!");
+                let Some(report) = &report else {
+                    ui.text_disabled("The world has not been scanned yet");
+                    return;
+                };
 
-            // Finish building the frame and swap buffers
-            target.finish().expect("Failed to swap buffers");
-        }
-        Event::WindowEvent {
-            event: WindowEvent::CloseRequested,
-            ..
-        } => *control_flow = ControlFlow::Exit,
+                ui.text(format!("Regions: {}", report.regions));
+                ui.text(format!("Chunks: {}", report.chunks));
+                match (report.lowest_surface, report.highest_surface) {
+                    (Some(lowest), Some(highest)) => {
+                        ui.text(format!("Surface height: {lowest} to {highest}"))
+                    }
+                    _ => ui.text_disabled("No surface blocks"),
+                }
+                ui.text(format!("Rigid structures: {}", report.rigid_structures));
+                ui.text(format!(
+                    "Smooth mesh triangles: ~{} (cell size {})",
+                    report.estimated_triangles, report.cell_size
+                ));
 
-        event => {
-            let gl_window = display.gl_window();
-            imgui_data.handle_event(gl_window.window(), &event);
+                ui.separator();
+                material_histogram(ui, &report.material_counts);
+            });
+    };
 
-            if let Some(action) = input::translate_event(event) {
-                actions.push(action);
-            }
-        }
-    });
+    Box::new(builder)
 }
 
-fn to_uniform_matrix(matrix: &Matrix4<Real>) -> [[f32; 4]; 4] {
-    array_init(|i| array_init(|j| matrix[i][j] as f32))
-}
+fn get_paused_builder() -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, _controls: &mut SmoothMeshOptions| {
+        centered_window(ui, "paused").build(|| {
+            ui.text("Paused, press P to continue");
+        });
+    };
 
-fn render_world<'a, D, T, I>(
-    render_pass: &'a RenderPass<'a, D, T, I>,
-    target: &mut Frame,
-    camera: &Camera,
-    state: &RenderState,
-    texture: &SrgbTexture2d,
-) -> ()
-where
-    D: Copy,
-    T: Copy,
-    I: 'a,
-    IndicesSource<'a>: From<&'a I>,
-{
-    let camera_position = camera.get_position();
-    let sun_position = [
-        (camera_position.x + 200.0) as f32,
-        (camera_position.y + 300.0) as f32,
-        (camera_position.z + 200.0) as f32,
-    ];
+    Box::new(builder)
+}
 
-    let model: [[f32; 4]; 4] = cgmath::Matrix4::from_scale(1.0).into();
-    let projection: [[f32; 4]; 4] = to_uniform_matrix(&camera.projection);
-    let view: [[f32; 4]; 4] = to_uniform_matrix(&camera.world_to_view);
+// Points the chunk loader at the world at *world_path* and offers its start locations in the dialog.
+// Worlds without level.dat start at the spawn point from the config right away.
+// The jobs of *previous_world* are cancelled before the world folder is switched,
+// its queued chunk loads would read the new world otherwise
+fn open_world(
+    world_path: &str,
+    dialog: &mut WorldDialog,
+    previous_world: Option<&mut discrete::World>,
+) -> Result<(), String> {
+    let region_folder = WorldSource::from_path(world_path)
+        .open()
+        .map_err(|error| format!("Failed to open world {world_path} - {error}"))?;
+    if !world_source::has_region_files(&region_folder) {
+        return Err(format!(
+            "No region files found in {}",
+            region_folder.display()
+        ));
+    }
 
-    let uni = uniform! {
-        projection: projection,
-        view: view,
-        model: model,
-        block_pallette: texture.sampled()
-            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
-            .wrap_function(glium::uniforms::SamplerWrapFunction::BorderClamp),
-        sun_position: sun_position,
-    };
+    if let Some(world) = previous_world {
+        world.cancel_jobs();
+    }
+    minecraft::set_world_folder(region_folder.clone());
+    dialog.remember_world(world_path);
 
-    let polygon_mode = match state.render_wireframe {
-        true => glium::PolygonMode::Line,
-        false => glium::PolygonMode::Fill,
-    };
-    let draw_parameters = glium::DrawParameters {
-        backface_culling: glium::BackfaceCullingMode::CullClockwise,
-        polygon_mode,
-        depth: glium::Depth {
-            test: glium::DepthTest::IfLess,
-            write: true,
-            ..Default::default()
-        },
-        ..Default::default()
-    };
+    dialog.start_locations = level::start_locations(&level::save_folder(&region_folder));
+    if dialog.start_locations.is_empty() {
+        dialog.chosen_start = Some(settings::current().spawn_point());
+    }
 
-    render_pass.execute(target, &uni, Some(draw_parameters));
+    Ok(())
 }
 
-fn get_controls_menu_builder() -> UIWindowBuilder {
-    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
-        ui.window("controls")
-            .size([300.0, 150.0], imgui::Condition::FirstUseEver)
-            .position([60.0, 300.0], imgui::Condition::FirstUseEver)
+fn get_script_console_builder(console: Rc<RefCell<ScriptConsole>>) -> UIWindowBuilder {
+    let builder = move |ui: &imgui::Ui, _controls: &mut SmoothMeshOptions| {
+        let mut console = console.borrow_mut();
+
+        ui.window("script console")
+            .size([480.0, 420.0], imgui::Condition::FirstUseEver)
+            .position([380.0, 60.0], imgui::Condition::FirstUseEver)
+            .collapsed(true, imgui::Condition::FirstUseEver)
             .build(|| {
-                ui.slider_config("Mesh detail", 1, 4)
-                    .build(&mut controls.mesh_resolution_level);
-                ui.slider_config("Smoothness", 1, 6)
-                    .build(&mut controls.smoothness_level);
-
-                let y_low = controls.y_low_limit;
-                let y_range_max = (383 - y_low as isize).max(2) as usize;
-                ui.slider_config("Limit Y", -64, 383)
-                    .build(&mut controls.y_low_limit);
-                ui.slider_config("Y Range", 1, y_range_max)
-                    .build(&mut controls.y_size);
+                ui.input_text_multiline("##script", &mut console.source, [-1.0, 220.0])
+                    .build();
+                console.run_requested |= ui.button("Run");
+                ui.same_line();
+                if ui.button("Clear output") {
+                    console.output.clear();
+                }
                 ui.separator();
-                controls.apply |= ui.button_with_size("APPLY", [0.0, 0.0]);
+
+                ui.child_window("script output").build(|| {
+                    for line in &console.output {
+                        ui.text_wrapped(line);
+                    }
+                });
             });
     };
 
@@ -273,28 +3060,73 @@ fn get_controls_menu_builder() -> UIWindowBuilder {
 fn get_statistics_menu_builder(
     state: &RenderState,
     camera: &Camera,
+    vertical_scale: VerticalScale,
     world: &discrete::World,
     poly_options: PolygonizationOptions,
+    changed_pixels: Option<f32>,
+    frame_summary: FrameSummary,
+    render_scale: f32,
 ) -> UIWindowBuilder {
-    let position = camera.get_position();
-    let direction = camera.get_direction();
+    let position = vertical_scale.to_world(camera.get_position());
+    let direction = vertical_scale.direction_to_world(camera.get_direction());
     let fps = state.timing.fps();
     let is_cursor_captured = state.cursor_captured;
     let chunk_position = get_minecraft_chunk_position(position);
     let block_at_position = world.get_block(position);
-    let render_mode = state.render_mode;
-
-    let density = get_density(world, position, poly_options.kernel_size);
-    let gradient = get_smooth_normal(world, position, poly_options.kernel_size);
+    let settings = state.settings;
+    let light = world.get_light(position);
+    let chunk_hash = world.get_chunk_hash(position);
+    let build_cost = world.get_chunk_build_cost(position);
+    let chunks_being_loaded = world.chunks_being_loaded();
+    let generated_chunks = world.count_generated_chunks();
+    let loaded_chunks = world.get_loaded_chunk_positions().len();
+
+    let density = get_density(world, position, poly_options);
+    let gradient = get_smooth_normal(world, position, poly_options);
+    let pick = world.pick(
+        position,
+        direction,
+        config::STATS_PICK_DISTANCE,
+        poly_options,
+    );
 
-    let builder = move |ui: &imgui::Ui, _: &mut SmoothMeshOptions| {
+    let builder = move |ui: &imgui::Ui, controls: &mut SmoothMeshOptions| {
         ui.window("stats")
             .position([60.0, 60.0], imgui::Condition::FirstUseEver)
             //.size([270.0, 120.0], imgui::Condition::FirstUseEver)
             .build(|| {
                 ui.text(format!("fps: {:.2}", fps));
+                ui.text(format!(
+                    "frame ms p50: {:.1} p95: {:.1} p99: {:.1}",
+                    frame_summary.p50, frame_summary.p95, frame_summary.p99
+                ));
+                ui.text(format!("worst frame (10 s): {:.1} ms", frame_summary.worst));
+                ui.text(format!(
+                    "frames over 33 ms: {} / {}",
+                    frame_summary.slow_frames, frame_summary.total_frames
+                ));
+                controls.reset_frame_stats |= ui.button("Reset frame stats");
+                ui.checkbox("Quality governor", &mut controls.quality_governor);
+                ui.text(format!("render scale: {:.0}%", render_scale * 100.0));
+                ui.separator();
                 ui.text(format!("cursor captured: {}", is_cursor_captured));
-                ui.text(format!("rendering mode: {render_mode:?}"));
+                let mut mode_index = RENDERING_MODES
+                    .iter()
+                    .position(|mode| *mode == settings.render_mode)
+                    .unwrap_or(0);
+                let mode_names = RENDERING_MODES.map(|mode| format!("{mode:?}"));
+                if ui.combo_simple_string("Rendering mode", &mut mode_index, &mode_names) {
+                    let mode = RENDERING_MODES[mode_index];
+                    controls.render_command = Some(RenderCommand::SetMode(mode));
+                }
+                let mut wireframe = settings.render_wireframe;
+                if ui.checkbox("Wireframe", &mut wireframe) {
+                    controls.render_command = Some(RenderCommand::ToggleWireframe);
+                }
+                ui.text(format!(
+                    "light visualization: {:?}",
+                    settings.light_visualization
+                ));
                 ui.separator();
                 ui.text(format!(
                     "position: x: {:.2} y: {:.2} z: {:.2}",
@@ -314,7 +3146,39 @@ fn get_statistics_menu_builder(
                     "chunk: [{}, {}]",
                     chunk_position.chunk_x, chunk_position.chunk_z
                 ));
+                ui.text(format!("chunks loading: {chunks_being_loaded}"));
+                ui.text(format!(
+                    "generated chunks: {} / {} ({:.0}%)",
+                    generated_chunks,
+                    loaded_chunks,
+                    generated_chunks as f32 / loaded_chunks as f32 * 100.0
+                ));
+                match chunk_hash {
+                    Some(hash) => ui.text(format!("chunk hash: {:016x}", hash)),
+                    None => ui.text("chunk hash: not loaded"),
+                }
+                if let Some(cost) = build_cost {
+                    ui.text(format!(
+                        "chunk build: {:.1} ms, {} density evaluations",
+                        cost.duration.as_secs_f64() * 1000.0,
+                        cost.density_evaluations
+                    ));
+                    ui.text(format!(
+                        "kernel scans: {}, {:.0}% shared between the passes",
+                        cost.density_memo.misses,
+                        cost.density_memo.reuse_rate() * 100.0
+                    ));
+                }
+                let grid_pool = grid_pool_stats();
+                ui.text(format!(
+                    "grid buffers: {} allocated, {} reused ({:.0}%), {:.1} MiB pooled",
+                    grid_pool.allocations,
+                    grid_pool.reuses,
+                    grid_pool.reuse_rate() * 100.0,
+                    grid_pool.pooled_bytes as f64 / (1024.0 * 1024.0)
+                ));
                 ui.text(format!("block: {:?}", block_at_position));
+                ui.text(format!("light: block {} sky {}", light.block, light.sky));
 
                 ui.separator();
                 ui.text(format!("density: {}", density));
@@ -322,54 +3186,37 @@ fn get_statistics_menu_builder(
                     "gradient: {:.2} {:.2} {:.2}",
                     gradient.x, gradient.y, gradient.z
                 ));
+
+                ui.separator();
+                match pick.block {
+                    Some(hit) => ui.text(format!(
+                        "looking at: {:?} [{}, {}, {}]",
+                        hit.block, hit.block_position.x, hit.block_position.y, hit.block_position.z
+                    )),
+                    None => ui.text("looking at: nothing"),
+                }
+                match pick.surface {
+                    Some(hit) => ui.text(format!(
+                        "smooth surface: x: {:.2} y: {:.2} z: {:.2} ({:?}, {:.1} away)",
+                        hit.position.x,
+                        hit.position.y,
+                        hit.position.z,
+                        hit.block,
+                        (hit.position - position).magnitude()
+                    )),
+                    None => ui.text("smooth surface: nothing"),
+                }
+
+                if let Some(changed_pixels) = changed_pixels {
+                    ui.separator();
+                    ui.text(format!("changed pixels: {:.2}%", changed_pixels * 100.0));
+                }
             });
     };
 
     Box::new(builder)
 }
 
-fn create_state(
-    events: &Vec<InputAction>,
-    old_state: RenderState,
-    window: &Window,
-) -> Option<RenderState> {
-    let mut cursor_captured = old_state.cursor_captured;
-    let mut should_render = true;
-    let mut render_wireframe = old_state.render_wireframe;
-    let mut render_mode = old_state.render_mode;
-
-    for action in events {
-        match action {
-            InputAction::Quit => should_render = false,
-            InputAction::Capture => {
-                cursor_captured = !cursor_captured;
-                capture_cursor(window, cursor_captured);
-            }
-            InputAction::KeyPressed {
-                key: VirtualKeyCode::B,
-            } => render_wireframe = !render_wireframe,
-            InputAction::KeyPressed {
-                key: VirtualKeyCode::U,
-            } => render_mode = RenderingMode::Discrete,
-            InputAction::KeyPressed {
-                key: VirtualKeyCode::I,
-            } => render_mode = RenderingMode::Implicit,
-            _ => (),
-        };
-    }
-
-    if !should_render {
-        return None;
-    }
-
-    Some(RenderState {
-        timing: old_state.timing,
-        cursor_captured,
-        render_wireframe,
-        render_mode,
-    })
-}
-
 fn capture_cursor(window: &Window, capture: bool) {
     let grab_mode = match capture {
         true => CursorGrabMode::Confined,
@@ -397,16 +3244,17 @@ fn create_window() -> (EventLoop<()>, glium::Display) {
     (event_loop, display)
 }
 
+// The rigid blocks are sorted by material, the ranges tell where each material is in the buffer
 fn create_rigid_scene<'a>(
     world: &World,
     display: &Display,
-) -> RenderPass<'a, model::chunk::BlockData, infrastructure::vertex::TexturedVertex, IndexBuffer<u32>>
-{
+) -> (
+    RenderPass<'a, model::chunk::BlockData, TexturedVertex, IndexBuffer<u32>>,
+    MaterialRanges,
+) {
     let (vertex_buffer, indices) = geometry::cube_textured_exclusive_vertex(display);
-    let instance_positions = {
-        let blocks = world.get_rigid_blocks_data();
-        glium::vertex::VertexBuffer::new(display, &blocks).unwrap()
-    };
+    let (blocks, ranges) = world.get_rigid_blocks_data();
+    let instance_positions = glium::vertex::VertexBuffer::new(display, &blocks).unwrap();
 
     let cube_fragment = RenderFragmentBuilder::new()
         .set_geometry(vertex_buffer, indices)
@@ -415,7 +3263,10 @@ fn create_rigid_scene<'a>(
         .build(display)
         .unwrap();
 
-    RenderPass::new_instanced(cube_fragment, instance_positions)
+    (
+        RenderPass::new_instanced(cube_fragment, instance_positions),
+        ranges,
+    )
 }
 
 fn create_discrete_scene<'a>(
@@ -439,36 +3290,74 @@ fn create_discrete_scene<'a>(
     RenderPass::new_instanced(cube_fragment, instance_positions)
 }
 
-fn create_implicit_scene<'a>(
-    world: &World,
+fn create_implicit_scene(world: &World, display: &Display) -> ChunkMeshScene {
+    let mut scene = ChunkMeshScene::new(display, IMPLICIT_VS, IMPLICIT_FS);
+    scene.upload_world(display, world);
+
+    scene
+}
+
+fn create_raymarch_scene<'a>(
     display: &Display,
-) -> RenderPass<'a, NoInstance, MeshVertex, IndexBuffer<u32>> {
-    let smooth_mesh = world.get_smooth_mesh();
-
-    let vertex_buffer = glium::VertexBuffer::new(display, &smooth_mesh.vertices).unwrap();
-    let index_buffer = glium::IndexBuffer::new(
-        display,
-        glium::index::PrimitiveType::TrianglesList,
-        &smooth_mesh.indices,
-    )
-    .unwrap();
+) -> RenderPass<'a, NoInstance, Vertex2D, IndexBuffer<u32>> {
+    let (vertex_buffer, index_buffer) = geometry::fullscreen_quad(display);
+
+    let fragment = RenderFragmentBuilder::new()
+        .set_geometry(vertex_buffer, index_buffer)
+        .set_vertex_shader(RAYMARCH_VS)
+        .set_fragment_shader(RAYMARCH_FS)
+        .build(display)
+        .unwrap();
+
+    RenderPass::new(fragment)
+}
+
+fn create_comparison_scene<'a>(
+    display: &Display,
+) -> RenderPass<'a, NoInstance, Vertex2D, IndexBuffer<u32>> {
+    let (vertex_buffer, index_buffer) = geometry::fullscreen_quad(display);
+
+    let fragment = RenderFragmentBuilder::new()
+        .set_geometry(vertex_buffer, index_buffer)
+        .set_vertex_shader(COMPARISON_VS)
+        .set_fragment_shader(COMPARISON_FS)
+        .build(display)
+        .unwrap();
+
+    RenderPass::new(fragment)
+}
+
+// The composite reads the transparency targets pixel by pixel over the whole screen
+fn create_oit_composite_scene<'a>(
+    display: &Display,
+) -> RenderPass<'a, NoInstance, Vertex2D, IndexBuffer<u32>> {
+    let (vertex_buffer, index_buffer) = geometry::fullscreen_quad(display);
 
     let fragment = RenderFragmentBuilder::new()
         .set_geometry(vertex_buffer, index_buffer)
-        .set_vertex_shader(IMPLICIT_VS)
-        .set_fragment_shader(IMPLICIT_FS)
+        .set_vertex_shader(COMPARISON_VS)
+        .set_fragment_shader(OIT_COMPOSITE_FS)
         .build(display)
         .unwrap();
 
     RenderPass::new(fragment)
 }
 
+// Far enough to see the farthest corner of a window of *load_radius* from anywhere in its center chunk,
+// never closer than config::Z_FAR
+fn far_clipping_plane(load_radius: usize) -> Real {
+    let reach = ((load_radius + 1) * minecraft::BLOCKS_IN_CHUNK) as Real;
+
+    (reach * std::f64::consts::SQRT_2).max(config::Z_FAR)
+}
+
 fn create_camera(window_dimensions: (u32, u32)) -> Camera {
     let aspect_ratio = window_dimensions.0 as Real / window_dimensions.1 as Real;
 
+    let settings = settings::current();
     Camera::new(
-        config::SPAWN_POINT,
-        config::SPAWN_DIR,
+        settings.spawn_point(),
+        settings.spawn_dir(),
         Vector3::unit_y(),
         config::FOVY,
         aspect_ratio,
@@ -0,0 +1,426 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+use cgmath::{InnerSpace, Vector3};
+use image::imageops::{self, FilterType};
+use image::{ImageError, ImageResult, Rgba, RgbaImage};
+use itertools::iproduct;
+use rayon::prelude::*;
+
+use crate::config::{self, WORLD_SIZE};
+use crate::infrastructure::color_ramp::ColorRamp;
+use crate::minecraft::{self, BLOCKS_IN_CHUNK, CHUNKS_IN_REGION};
+use crate::model::chunk::ChunkPosition;
+use crate::model::discrete::{load_window, WorldChunks};
+use crate::model::implicit::smooth::terrain_density;
+use crate::model::polygonize::PolygonizationOptions;
+use crate::model::{Position, Real};
+use crate::world_scanner::{log_progress, ScanSettings, WorldScanner};
+
+// A tile covers the inner chunks of a window, the ones that would be polygonized
+const TILE_CHUNKS: usize = WORLD_SIZE - 2;
+// Bisection steps refining the height of the surface once it was stepped over
+const HEIGHT_REFINEMENT_STEPS: usize = 8;
+// Hillshading is lit from the north west, 45 degrees above the horizon
+const LIGHT_DIRECTION: [Real; 3] = [-1.0, std::f64::consts::SQRT_2, -1.0];
+// Brightness of the slopes facing away from the light
+const AMBIENT: f32 = 0.35;
+const CONTOUR_DARKENING: f32 = 0.45;
+// Side of the web map tiles in pixels, as Leaflet expects them
+const WEB_TILE_SIZE: u32 = 256;
+const LEAFLET_VIEWER: &str = include_str!("map_viewer.html");
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapSettings {
+    pub meters_per_pixel: Real,
+    // Height between two contour lines, no lines without it
+    pub contour_interval: Option<Real>,
+    pub color_ramp: ColorRamp,
+    // Cut the tiles of the whole world into a pyramid of web map tiles
+    pub web_tiles: bool,
+}
+
+pub enum MapArea {
+    // The inner chunks of the loaded window in a single image
+    Loaded(WorldChunks),
+    // Every part of the world with generated chunks, in tiles loaded one after another
+    WholeWorld,
+}
+
+// Orthographic top-down map of the smooth terrain, exported in the background.
+//
+// Every pixel looks straight down at the density of the terrain pass. The surface is found by
+// stepping down through the Y range by the cell size and refining the crossing by bisection.
+// The map is colored by height with the color ramp and hillshaded by the slope between the
+// heights of neighboring pixels. Pixels without terrain are transparent.
+//
+// The tiles of the whole world are exported by a world scanner, a cancelled export resumes
+// from its checkpoint when started again with the same settings.
+pub fn start_map_export(
+    area: MapArea,
+    options: PolygonizationOptions,
+    settings: MapSettings,
+    folder: &str,
+    workers: usize,
+) -> WorldScanner<()> {
+    let folder = PathBuf::from(folder);
+    let chunks = match area {
+        MapArea::Loaded(chunks) => chunks,
+        MapArea::WholeWorld => return start_world_export(options, settings, folder, workers),
+    };
+
+    let scan = ScanSettings {
+        workers,
+        checkpoint: None,
+        signature: String::new(),
+        on_progress: None,
+    };
+    let visit = move |_: &()| {
+        fs::create_dir_all(&folder).map_err(|error| error.to_string())?;
+        map_tile(&chunks, options, settings)
+            .save(folder.join("map.png"))
+            .map_err(|error| error.to_string())
+    };
+
+    WorldScanner::start(vec![()], scan, visit, |_| Ok(()))
+}
+
+// Exports the tiles in parallel, the web tiles are cut from each of them once it is done
+fn start_world_export(
+    options: PolygonizationOptions,
+    settings: MapSettings,
+    folder: PathBuf,
+    workers: usize,
+) -> WorldScanner<()> {
+    // The web tiles need the map tiles to split into whole web tiles,
+    // so a pixel covers a power of two fraction of a block
+    let settings = match settings.web_tiles {
+        true => MapSettings {
+            meters_per_pixel: web_meters_per_pixel(settings.meters_per_pixel),
+            ..settings
+        },
+        false => settings,
+    };
+
+    let tiles = world_tiles();
+    println!("[INFO] Map export of {} tiles started", tiles.len());
+
+    let world = minecraft::world_folder();
+    let scan = ScanSettings {
+        workers,
+        checkpoint: Some(PathBuf::from(config::MAP_EXPORT_CHECKPOINT)),
+        signature: format!("{} {options:?} {settings:?}", world.display()),
+        on_progress: Some(log_progress("map tiles")),
+    };
+
+    let web_tiles = WebTiles {
+        folder: folder.clone(),
+    };
+    let pyramid = web_tiles.clone();
+    let visit = move |&(tile_x, tile_z): &(i32, i32)| {
+        // The window reaches a chunk past the tile so the kernels have their data
+        let base = ChunkPosition::from_global_position_in_chunks(
+            tile_x * TILE_CHUNKS as i32 - 1,
+            tile_z * TILE_CHUNKS as i32 - 1,
+        );
+        let image = map_tile(&load_window(base, WORLD_SIZE), options, settings);
+
+        let written = match settings.web_tiles {
+            true => web_tiles.cut(&image, tile_x, tile_z),
+            false => fs::create_dir_all(&folder)
+                .map_err(ImageError::from)
+                .and_then(|()| image.save(folder.join(format!("tile_{tile_x}_{tile_z}.png"))))
+                .map(|()| Vec::new()),
+        };
+        written.map_err(|error| error.to_string())
+    };
+    let finish = move |outputs: Vec<((i32, i32), Vec<(i32, i32)>)>| {
+        if !settings.web_tiles {
+            return Ok(());
+        }
+
+        let native_tiles = outputs.into_iter().flat_map(|(_, pieces)| pieces).collect();
+        pyramid
+            .finish(&native_tiles, settings.meters_per_pixel)
+            .map_err(|error| error.to_string())
+    };
+
+    WorldScanner::start(tiles, scan, visit, finish)
+}
+
+// Tiles containing a generated chunk, numbered by their position on the grid of tiles
+fn world_tiles() -> Vec<(i32, i32)> {
+    let tile_of = |global: i32| global.div_euclid(TILE_CHUNKS as i32);
+
+    let mut tiles = BTreeSet::new();
+    for (region_x, region_z) in minecraft::region_positions() {
+        for chunk_z in 0..CHUNKS_IN_REGION {
+            for chunk_x in 0..CHUNKS_IN_REGION {
+                let chunk_position = ChunkPosition {
+                    region_x,
+                    region_z,
+                    chunk_x,
+                    chunk_z,
+                };
+                if !minecraft::is_chunk_generated(chunk_position) {
+                    continue;
+                }
+
+                let (global_x, global_z) = chunk_position.get_global_position_in_chunks();
+                tiles.insert((tile_of(global_x), tile_of(global_z)));
+            }
+        }
+    }
+
+    tiles.into_iter().collect()
+}
+
+// Map of the inner chunks of *chunks*, north (-z) is up
+fn map_tile(
+    chunks: &WorldChunks,
+    options: PolygonizationOptions,
+    settings: MapSettings,
+) -> RgbaImage {
+    let (base_x, base_z) = chunks[0].position.get_global_position_in_chunks();
+    let chunk_size = BLOCKS_IN_CHUNK as Real;
+    let origin_x = (base_x + 1) as Real * chunk_size;
+    let origin_z = (base_z + 1) as Real * chunk_size;
+    let extent = ((chunks.size() - 2) * BLOCKS_IN_CHUNK) as Real;
+    let size = (extent / settings.meters_per_pixel).ceil() as usize;
+
+    // Heights at the centers of the pixels
+    let density = terrain_density(chunks, options);
+    let heights: Vec<Option<Real>> = (0..size * size)
+        .into_par_iter()
+        .map(|index| {
+            let x = origin_x + ((index % size) as Real + 0.5) * settings.meters_per_pixel;
+            let z = origin_z + ((index / size) as Real + 0.5) * settings.meters_per_pixel;
+            surface_height(&density, x, z, options)
+        })
+        .collect();
+
+    let height_at = |x: usize, z: usize| heights[z * size + x];
+    let light = Vector3::from(LIGHT_DIRECTION).normalize();
+
+    RgbaImage::from_fn(size as u32, size as u32, |x, z| {
+        let (x, z) = (x as usize, z as usize);
+        let Some(height) = height_at(x, z) else {
+            return Rgba([0, 0, 0, 0]);
+        };
+
+        // Central differences, one sided on the border and next to pixels without terrain
+        let (left, right) = (x.saturating_sub(1), (x + 1).min(size - 1));
+        let (up, down) = (z.saturating_sub(1), (z + 1).min(size - 1));
+        let neighbor = |x, z| height_at(x, z).unwrap_or(height);
+        let slope = |low: Real, high: Real, span: usize| match span {
+            0 => 0.0,
+            _ => (high - low) / (span as Real * settings.meters_per_pixel),
+        };
+        let slope_x = slope(neighbor(left, z), neighbor(right, z), right - left);
+        let slope_z = slope(neighbor(x, up), neighbor(x, down), down - up);
+        let normal = Vector3::new(-slope_x, 1.0, -slope_z).normalize();
+        let lit = normal.dot(light).max(0.0) as f32;
+        let mut shade = AMBIENT + (1.0 - AMBIENT) * lit;
+
+        // Lines where the contour band changes towards the next pixel
+        if let Some(interval) = settings.contour_interval {
+            let band = |height: Real| (height / interval).floor();
+            let crosses = [neighbor(right, z), neighbor(x, down)]
+                .into_iter()
+                .any(|other| band(other) != band(height));
+            if crosses {
+                shade *= CONTOUR_DARKENING;
+            }
+        }
+
+        let elevation = ((height - options.y_low_limit) / options.y_size) as f32;
+        let color = settings.color_ramp.sample(elevation);
+        let [r, g, b] = color.map(|channel| (channel * shade * 255.0).round() as u8);
+        Rgba([r, g, b, 255])
+    })
+}
+
+// Height of the highest surface above *x*, *z* within the Y range, None where there is none
+fn surface_height(
+    density: &impl Fn(Position) -> Real,
+    x: Real,
+    z: Real,
+    options: PolygonizationOptions,
+) -> Option<Real> {
+    let is_inside = |y: Real| density(Position::new(x, y, z)) < options.surface_level;
+
+    let top = options.y_low_limit + options.y_size;
+    if is_inside(top) {
+        return Some(top);
+    }
+
+    let mut above = top;
+    let mut y = top - options.marching_cubes_cell_size;
+    while y >= options.y_low_limit {
+        if is_inside(y) {
+            let (mut outside, mut inside) = (above, y);
+            for _ in 0..HEIGHT_REFINEMENT_STEPS {
+                let middle = (outside + inside) / 2.0;
+                match is_inside(middle) {
+                    true => inside = middle,
+                    false => outside = middle,
+                }
+            }
+
+            return Some((outside + inside) / 2.0);
+        }
+
+        above = y;
+        y -= options.marching_cubes_cell_size;
+    }
+
+    None
+}
+
+// The largest power of two up to half a block, a map tile is then a whole number of web tiles
+fn web_meters_per_pixel(meters_per_pixel: Real) -> Real {
+    let exponent = meters_per_pixel.log2().floor().min(-1.0);
+    (2.0 as Real).powf(exponent)
+}
+
+// Pyramid of web map tiles in tiles/z/x/y.png with a Leaflet viewer in index.html.
+//
+// The map tiles are cut into the tiles of the most detailed zoom level while they are exported.
+// Its number is only known once all of them are, until then the tiles are kept in tiles/native.
+// Every less detailed level is then downsampled from the four tiles under each of its tiles,
+// until the whole world fits into a couple of tiles at zoom 0.
+#[derive(Clone)]
+struct WebTiles {
+    folder: PathBuf,
+}
+
+impl WebTiles {
+    fn tile_path(&self, zoom: &str, (x, y): (i32, i32)) -> PathBuf {
+        self.folder
+            .join("tiles")
+            .join(zoom)
+            .join(x.to_string())
+            .join(format!("{y}.png"))
+    }
+
+    fn save(&self, image: &RgbaImage, zoom: &str, tile: (i32, i32)) -> ImageResult<()> {
+        let path = self.tile_path(zoom, tile);
+        fs::create_dir_all(path.parent().unwrap())?;
+        image.save(path)
+    }
+
+    // Writes the native tiles of a map tile, returns where they are
+    fn cut(&self, image: &RgbaImage, tile_x: i32, tile_z: i32) -> ImageResult<Vec<(i32, i32)>> {
+        let pieces = image.width() / WEB_TILE_SIZE;
+        let mut written = Vec::new();
+        for (piece_y, piece_x) in iproduct!(0..pieces, 0..pieces) {
+            let tile = (
+                tile_x * pieces as i32 + piece_x as i32,
+                tile_z * pieces as i32 + piece_y as i32,
+            );
+            let (left, top) = (piece_x * WEB_TILE_SIZE, piece_y * WEB_TILE_SIZE);
+            let piece = imageops::crop_imm(image, left, top, WEB_TILE_SIZE, WEB_TILE_SIZE);
+
+            self.save(&piece.to_image(), "native", tile)?;
+            written.push(tile);
+        }
+
+        Ok(written)
+    }
+
+    fn finish(
+        &self,
+        native_tiles: &BTreeSet<(i32, i32)>,
+        meters_per_pixel: Real,
+    ) -> ImageResult<()> {
+        let Some(bounds) = TileBounds::of(native_tiles) else {
+            return Ok(());
+        };
+        let max_zoom = bounds.span().next_power_of_two().trailing_zeros();
+
+        let tiles = self.folder.join("tiles");
+        let native_level = tiles.join(max_zoom.to_string());
+        if native_level.exists() {
+            fs::remove_dir_all(&native_level)?;
+        }
+        fs::rename(tiles.join("native"), native_level)?;
+
+        let mut level = native_tiles.clone();
+        for zoom in (0..max_zoom).rev() {
+            let parents: BTreeSet<(i32, i32)> = level
+                .iter()
+                .map(|(x, y)| (x.div_euclid(2), y.div_euclid(2)))
+                .collect();
+
+            for &(x, y) in &parents {
+                let mut combined = RgbaImage::new(2 * WEB_TILE_SIZE, 2 * WEB_TILE_SIZE);
+                for (offset_y, offset_x) in iproduct!(0..2, 0..2) {
+                    let child = (2 * x + offset_x, 2 * y + offset_y);
+                    if !level.contains(&child) {
+                        continue;
+                    }
+
+                    let path = self.tile_path(&(zoom + 1).to_string(), child);
+                    let image = image::open(path)?.to_rgba8();
+                    let (left, top) = (offset_x as i64, offset_y as i64);
+                    imageops::replace(
+                        &mut combined,
+                        &image,
+                        left * WEB_TILE_SIZE as i64,
+                        top * WEB_TILE_SIZE as i64,
+                    );
+                }
+
+                let tile = imageops::resize(
+                    &combined,
+                    WEB_TILE_SIZE,
+                    WEB_TILE_SIZE,
+                    FilterType::Triangle,
+                );
+                self.save(&tile, &zoom.to_string(), (x, y))?;
+            }
+
+            level = parents;
+        }
+
+        let min_tile = format!("[{}, {}]", bounds.min.0, bounds.min.1);
+        let max_tile = format!("[{}, {}]", bounds.max.0, bounds.max.1);
+        let viewer = LEAFLET_VIEWER
+            .replace("%MAX_ZOOM%", &max_zoom.to_string())
+            .replace("%METERS_PER_PIXEL%", &meters_per_pixel.to_string())
+            .replace("%TILE_SIZE%", &WEB_TILE_SIZE.to_string())
+            .replace("%MIN_TILE%", &min_tile)
+            .replace("%MAX_TILE%", &max_tile);
+        fs::write(self.folder.join("index.html"), viewer)?;
+
+        Ok(())
+    }
+}
+
+// Smallest and largest tile coordinates of a set of tiles
+struct TileBounds {
+    min: (i32, i32),
+    max: (i32, i32),
+}
+
+impl TileBounds {
+    fn of(tiles: &BTreeSet<(i32, i32)>) -> Option<Self> {
+        let first = *tiles.iter().next()?;
+        let bounds = tiles.iter().fold((first, first), |(min, max), &(x, y)| {
+            ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+        });
+
+        Some(TileBounds {
+            min: bounds.0,
+            max: bounds.1,
+        })
+    }
+
+    // Tiles along the longer side
+    fn span(&self) -> u32 {
+        let width = self.max.0 - self.min.0 + 1;
+        let height = self.max.1 - self.min.1 + 1;
+        width.max(height) as u32
+    }
+}
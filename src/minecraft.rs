@@ -1,12 +1,23 @@
-use fastanvil::{CurrentJavaChunk, Region};
-use fastnbt::from_bytes;
-use std::path::Path;
+use fastanvil::{Chunk, CurrentJavaChunk, JavaChunk, Region};
+use fastnbt::{from_bytes, ByteArray};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use crate::config;
+use crate::model::biome::Biome;
 use crate::model::chunk::ChunkPosition;
 use crate::model::common::BlockType;
+use crate::model::material_registry;
 use crate::model::Position;
 
+mod block_entities;
+mod legacy;
+pub mod level;
+
 pub const MIN_BLOCK_Y: isize = -64; // TODO: Real value is -64
 pub const MAX_BLOCK_Y: isize = 320;
 
@@ -59,34 +70,384 @@ const BLOCK_BLACKLIST: [&str; 11] = [
     "oak_door",
 ];
 
+// Folder the region files are read from, config::WORLD_FOLDER until a world is opened
+static WORLD_FOLDER: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn set_world_folder(folder: PathBuf) {
+    *WORLD_FOLDER.lock().unwrap() = Some(folder);
+    REGION_LOADER.clear();
+}
+
+pub fn world_folder() -> PathBuf {
+    WORLD_FOLDER
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(config::WORLD_FOLDER))
+}
+
+// Doesn't wait for the folder, None while it is locked, see crash_report
+pub fn try_world_folder() -> Option<PathBuf> {
+    let folder = WORLD_FOLDER.try_lock().ok()?;
+
+    Some(
+        folder
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(config::WORLD_FOLDER)),
+    )
+}
+
 // Alias type definition to avoid ambiguity with fastanvil::Chunk
 type DDChunk = crate::model::chunk::Chunk;
 
-pub fn get_chunk(/*region_loader: &RegionFileLoader,*/ chunk_position: ChunkPosition,) -> DDChunk {
-    let mut dd_chunk = DDChunk::new(chunk_position);
+// fastanvil does not expose the light data of a section,
+// so we deserialize it separately from the same chunk data
+#[derive(Deserialize)]
+struct ChunkLight {
+    #[serde(default)]
+    sections: Vec<SectionLight>,
+}
+
+// Before 1.18 the sections are nested in the Level tag
+#[derive(Deserialize)]
+struct LegacyChunkLight {
+    #[serde(rename = "Level")]
+    level: LegacyLevelLight,
+}
+
+#[derive(Deserialize)]
+struct LegacyLevelLight {
+    #[serde(rename = "Sections", default)]
+    sections: Vec<SectionLight>,
+}
+
+#[derive(Deserialize)]
+struct SectionLight {
+    #[serde(rename = "Y")]
+    y: i8,
+    #[serde(rename = "BlockLight")]
+    block_light: Option<ByteArray>,
+    #[serde(rename = "SkyLight")]
+    sky_light: Option<ByteArray>,
+}
+
+const SECTION_SIZE: usize = 16;
+const BLOCKS_IN_SECTION: usize = SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+
+// DataVersion of the first snapshots that changed the chunk format
+// 17w47a - numeric block ids replaced by block states (the flattening)
+const DATA_VERSION_FLATTENING: i32 = 1451;
+// 21w43a - sections moved out of the Level tag, world height extended to -64..320
+const DATA_VERSION_EXTENDED_HEIGHT: i32 = 2844;
+
+#[derive(Deserialize)]
+struct ChunkVersion {
+    // Missing in chunks saved before 1.9
+    #[serde(rename = "DataVersion")]
+    data_version: Option<i32>,
+}
+
+// Layout of the chunk data, each needs a different decoding path
+#[derive(Debug, Clone, Copy)]
+enum ChunkFormat {
+    // Before 1.13, numeric block ids
+    Legacy,
+    // 1.13 to 1.17, block states under the Level tag
+    Flattened,
+    // 1.18 and later
+    Current,
+}
+
+impl ChunkFormat {
+    fn detect(data: &[u8]) -> Self {
+        let data_version = from_bytes::<ChunkVersion>(data)
+            .ok()
+            .and_then(|version| version.data_version)
+            .unwrap_or(0);
+
+        if data_version >= DATA_VERSION_EXTENDED_HEIGHT {
+            ChunkFormat::Current
+        } else if data_version >= DATA_VERSION_FLATTENING {
+            ChunkFormat::Flattened
+        } else {
+            ChunkFormat::Legacy
+        }
+    }
+}
+
+pub fn get_chunk(chunk_position: ChunkPosition) -> DDChunk {
+    REGION_LOADER.load_chunk(chunk_position)
+}
+
+// Whether the save has data for the chunk, read from the header of its region file
+pub fn is_chunk_generated(chunk_position: ChunkPosition) -> bool {
+    REGION_LOADER.is_chunk_generated(chunk_position)
+}
+
+// The next load of a chunk in the region of *chunk_position* reads the region file as it is on disk now
+pub fn forget_region(chunk_position: ChunkPosition) {
+    REGION_LOADER.forget((chunk_position.region_x, chunk_position.region_z));
+}
+
+// Region handles opened by earlier chunk loads, shared by every thread that loads chunks
+static REGION_LOADER: RegionLoader = RegionLoader::new();
+
+type RegionHandle = Arc<Mutex<Region<File>>>;
+
+// Which chunks of a region have data, indexed by chunk_z * CHUNKS_IN_REGION + chunk_x
+type GeneratedChunks = [bool; CHUNKS_IN_REGION * CHUNKS_IN_REGION];
+
+// The header of a region file starts with a 4 byte location for every chunk,
+// chunks that were never generated have a location of zero
+const REGION_LOCATION_BYTES: usize = 4;
+
+// Keeps the last config::REGION_CACHE_SIZE opened regions so loading a chunk
+// does not open and parse the region file again.
+//
+// The cache lock is held only while looking up the handle,
+// chunks of different regions are read in parallel.
+pub struct RegionLoader {
+    // Least recently used first
+    regions: Mutex<Vec<((i32, i32), RegionHandle)>>,
+    // Headers are tiny and asked for often, so they are kept for every region that was looked at
+    generated_chunks: Mutex<BTreeMap<(i32, i32), GeneratedChunks>>,
+}
+
+impl RegionLoader {
+    const fn new() -> Self {
+        RegionLoader {
+            regions: Mutex::new(Vec::new()),
+            generated_chunks: Mutex::new(BTreeMap::new()),
+        }
+    }
 
-    let region_file_path = build_region_filepath(chunk_position.region_x, chunk_position.region_z);
-    let file = std::fs::File::open(region_file_path).unwrap();
-    let mut region = Region::from_stream(file).unwrap();
-
-    let data = match region.read_chunk(chunk_position.chunk_x, chunk_position.chunk_z) {
-        Ok(opt_data) => match opt_data {
-            Some(chunk_data) => chunk_data,
-            None => {
-                println!(
-                    "INFO: chunk at position {:?} was not yet generated",
-                    chunk_position
-                );
+    pub fn is_chunk_generated(&self, chunk_position: ChunkPosition) -> bool {
+        let region_position = (chunk_position.region_x, chunk_position.region_z);
+        let chunk_index = chunk_position.chunk_z * CHUNKS_IN_REGION + chunk_position.chunk_x;
+
+        let mut generated_chunks = self.generated_chunks.lock().unwrap();
+        let region = generated_chunks
+            .entry(region_position)
+            .or_insert_with(|| read_generated_chunks(region_position.0, region_position.1));
+
+        region[chunk_index]
+    }
+
+    pub fn load_chunk(&self, chunk_position: ChunkPosition) -> DDChunk {
+        let dd_chunk = DDChunk::new(chunk_position);
+
+        // Regions nobody visited have no file, their chunks are empty
+        let Some(region) = self.region(chunk_position.region_x, chunk_position.region_z) else {
+            return dd_chunk;
+        };
+
+        let data = region
+            .lock()
+            .unwrap()
+            .read_chunk(chunk_position.chunk_x, chunk_position.chunk_z);
+        let data = match data {
+            Ok(opt_data) => match opt_data {
+                Some(chunk_data) => chunk_data,
+                None => {
+                    println!(
+                        "INFO: chunk at position {:?} was not yet generated",
+                        chunk_position
+                    );
+                    return dd_chunk;
+                }
+            },
+            Err(e) => {
+                eprintln!("Failed to load chunk data from region - {}", e);
                 return dd_chunk;
             }
-        },
+        };
+
+        decode_chunk(chunk_position, &data)
+    }
+
+    // Drops the open regions, they belong to the previous world after the world folder changes
+    pub fn clear(&self) {
+        self.regions.lock().unwrap().clear();
+        self.generated_chunks.lock().unwrap().clear();
+    }
+
+    pub fn forget(&self, region_position: (i32, i32)) {
+        let mut regions = self.regions.lock().unwrap();
+        regions.retain(|(position, _)| *position != region_position);
+        self.generated_chunks
+            .lock()
+            .unwrap()
+            .remove(&region_position);
+    }
+
+    fn region(&self, region_x: i32, region_z: i32) -> Option<RegionHandle> {
+        let mut regions = self.regions.lock().unwrap();
+        let cached = regions
+            .iter()
+            .position(|(position, _)| *position == (region_x, region_z));
+        if let Some(index) = cached {
+            let entry = regions.remove(index);
+            let region = entry.1.clone();
+            regions.push(entry);
+
+            return Some(region);
+        }
+
+        let region = Arc::new(Mutex::new(open_region(region_x, region_z)?));
+        if regions.len() >= config::REGION_CACHE_SIZE {
+            regions.remove(0);
+        }
+        regions.push(((region_x, region_z), region.clone()));
+
+        Some(region)
+    }
+}
+
+// Regions without a file, or with a broken header, have no generated chunks
+fn read_generated_chunks(region_x: i32, region_z: i32) -> GeneratedChunks {
+    let mut generated = [false; CHUNKS_IN_REGION * CHUNKS_IN_REGION];
+    let mut header = [0; CHUNKS_IN_REGION * CHUNKS_IN_REGION * REGION_LOCATION_BYTES];
+
+    let region_file_path = build_region_filepath(region_x, region_z);
+    let read = File::open(region_file_path).and_then(|mut file| file.read_exact(&mut header));
+    if read.is_err() {
+        return generated;
+    }
+
+    for (chunk, location) in generated
+        .iter_mut()
+        .zip(header.chunks_exact(REGION_LOCATION_BYTES))
+    {
+        *chunk = location.iter().any(|byte| *byte != 0);
+    }
+
+    generated
+}
+
+fn open_region(region_x: i32, region_z: i32) -> Option<Region<File>> {
+    let region_file_path = build_region_filepath(region_x, region_z);
+    let file = match File::open(&region_file_path) {
+        Ok(file) => file,
         Err(e) => {
-            eprintln!("Failed to load chunk data from region - {}", e);
-            return dd_chunk;
+            eprintln!("Failed to open region file {} - {}", region_file_path, e);
+            return None;
         }
     };
 
-    let chunk: CurrentJavaChunk = from_bytes(data.as_slice()).unwrap();
+    match Region::from_stream(file) {
+        Ok(region) => Some(region),
+        Err(e) => {
+            eprintln!("Failed to read region file {} - {}", region_file_path, e);
+            None
+        }
+    }
+}
+
+// Positions of the regions with a file in the world folder
+pub fn region_positions() -> Vec<(i32, i32)> {
+    let Ok(entries) = std::fs::read_dir(world_folder()) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let coordinates = file_name.strip_prefix("r.")?.strip_suffix(".mca")?;
+            let (region_x, region_z) = coordinates.split_once('.')?;
+
+            Some((region_x.parse().ok()?, region_z.parse().ok()?))
+        })
+        .collect()
+}
+
+// Loads every generated chunk of the region one at a time, the region file is read only once
+pub fn visit_region_chunks(region_x: i32, region_z: i32, mut visit: impl FnMut(DDChunk)) {
+    // Scans read every region once, caching them would evict the regions of the loaded chunks
+    let Some(mut region) = open_region(region_x, region_z) else {
+        return;
+    };
+
+    for chunk_z in 0..CHUNKS_IN_REGION {
+        for chunk_x in 0..CHUNKS_IN_REGION {
+            let chunk_position = ChunkPosition {
+                region_x,
+                region_z,
+                chunk_x,
+                chunk_z,
+            };
+
+            match region.read_chunk(chunk_x, chunk_z) {
+                Ok(Some(data)) => visit(decode_chunk(chunk_position, &data)),
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to load chunk data from region - {}", e),
+            }
+        }
+    }
+}
+
+// Reads the blocks, light and signs of a chunk from its NBT data
+fn decode_chunk(chunk_position: ChunkPosition, data: &[u8]) -> DDChunk {
+    let mut dd_chunk = DDChunk::new(chunk_position);
+
+    let format = ChunkFormat::detect(data);
+    let mut push_block = |x: usize, y: isize, z: usize, block_name: &str| {
+        let Some(block_type) = get_block_type_namespaced(block_name) else {
+            return;
+        };
+
+        if block_type == BlockType::Air {
+            return;
+        }
+
+        dd_chunk.push_block(x, z, y, block_type);
+    };
+
+    let blocks_result = match format {
+        ChunkFormat::Current => visit_current_blocks(data, &mut push_block),
+        ChunkFormat::Flattened => visit_flattened_blocks(data, &mut push_block),
+        ChunkFormat::Legacy => legacy::visit_blocks(data, &mut push_block),
+    };
+    if let Err(e) = blocks_result {
+        eprintln!("Failed to load blocks of {format:?} chunk {chunk_position:?} - {e}");
+    }
+
+    // Biomes are looked up at the surface, so the blocks are loaded first
+    let biomes_result = match format {
+        ChunkFormat::Current | ChunkFormat::Flattened => load_biomes(&mut dd_chunk, data),
+        ChunkFormat::Legacy => Ok(()),
+    };
+    if let Err(e) = biomes_result {
+        eprintln!("Failed to load biomes of chunk {chunk_position:?} - {e}");
+    }
+
+    let light_sections = match format {
+        ChunkFormat::Current => from_bytes::<ChunkLight>(data).map(|light| light.sections),
+        ChunkFormat::Flattened | ChunkFormat::Legacy => {
+            from_bytes::<LegacyChunkLight>(data).map(|light| light.level.sections)
+        }
+    };
+    match light_sections {
+        Ok(sections) => load_light(&mut dd_chunk, sections),
+        Err(e) => eprintln!("Failed to load light data of chunk {chunk_position:?} - {e}"),
+    }
+
+    let legacy_layout = !matches!(format, ChunkFormat::Current);
+    match block_entities::load_signs(data, legacy_layout) {
+        Ok(signs) => dd_chunk.set_signs(signs),
+        Err(e) => eprintln!("Failed to load signs of chunk {chunk_position:?} - {e}"),
+    }
+
+    dd_chunk
+}
+
+// Since 1.18 the sections are stored directly in the chunk as block states
+// with a per section palette
+fn visit_current_blocks(
+    data: &[u8],
+    visit: &mut impl FnMut(usize, isize, usize, &str),
+) -> fastnbt::error::Result<()> {
+    let chunk: CurrentJavaChunk = from_bytes(data)?;
 
     if let Some(tower) = chunk.sections {
         for section in tower.sections() {
@@ -103,44 +464,174 @@ pub fn get_chunk(/*region_loader: &RegionFileLoader,*/ chunk_position: ChunkPosi
             let palette = block_states.palette();
             for (i, palette_index) in blocks_iterator.enumerate() {
                 let block = &palette[palette_index];
-                let Some(block_id) = block.name().strip_prefix("minecraft:") else {
-                    continue;
-                };
-
-                let block_type = get_block_type_ng(block_id);
-
-                match block_type {
-                    BlockType::Unknown => {
-                        // TODO: this is bad
-                        if BLOCK_BLACKLIST.contains(&block_id) {
-                            continue;
-                        }
-                    }
-                    BlockType::Air => continue,
-                    _ => (),
-                };
 
                 let x = i & 0x000F;
                 let y = (i & 0x0F00) >> 8;
                 let z = (i & 0x00F0) >> 4;
 
-                dd_chunk.push_block(x, z, section_base_y + y as isize, block_type);
+                visit(x, section_base_y + y as isize, z, block.name());
             }
         }
     }
 
-    dd_chunk
+    Ok(())
+}
+
+// Between 1.13 and 1.18 block states are stored under the Level tag and packed differently,
+// fastanvil knows how to read them
+fn visit_flattened_blocks(
+    data: &[u8],
+    visit: &mut impl FnMut(usize, isize, usize, &str),
+) -> fastnbt::error::Result<()> {
+    let chunk = JavaChunk::from_bytes(data)?;
+
+    for y in chunk.y_range() {
+        for z in 0..BLOCKS_IN_CHUNK {
+            for x in 0..BLOCKS_IN_CHUNK {
+                let Some(block) = chunk.block(x, y, z) else {
+                    continue;
+                };
+
+                visit(x, y, z, block.name());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Biome of every column at its surface, where the grass and leaves are.
+// Chunks saved before the flattening keep the default biome.
+fn load_biomes(dd_chunk: &mut DDChunk, data: &[u8]) -> fastnbt::error::Result<()> {
+    let chunk = JavaChunk::from_bytes(data)?;
+    let bottom = chunk.y_range().start;
+
+    for z in 0..BLOCKS_IN_CHUNK {
+        for x in 0..BLOCKS_IN_CHUNK {
+            let y = dd_chunk
+                .get_surface_height(x, z)
+                .unwrap_or(bottom)
+                .max(bottom);
+            if let Some(biome) = chunk.biome(x, y, z) {
+                dd_chunk.set_biome(x, z, Biome::from_name(&format!("{biome:?}")));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Each byte of a nibble array holds the values of 2 blocks, the lower half comes first
+fn nibble(array: &ByteArray, i: usize) -> u8 {
+    let byte = array[i / 2] as u8;
+    if i % 2 == 0 {
+        byte & 0x0F
+    } else {
+        byte >> 4
+    }
+}
+
+// Light arrays are 2048 bytes long, each byte holds the light level of 2 blocks.
+// Blocks are ordered the same way as the block states - YZX
+fn load_light(dd_chunk: &mut DDChunk, sections: Vec<SectionLight>) {
+    for section in sections {
+        let section_base_y = section.y as isize * SECTION_SIZE as isize;
+
+        let section_out_of_range = section_base_y < MIN_BLOCK_Y || section_base_y >= MAX_BLOCK_Y;
+        if section_out_of_range {
+            continue;
+        }
+
+        for i in 0..BLOCKS_IN_SECTION {
+            let x = i & 0x000F;
+            let y = section_base_y + ((i & 0x0F00) >> 8) as isize;
+            let z = (i & 0x00F0) >> 4;
+
+            if let Some(block_light) = &section.block_light {
+                dd_chunk.set_block_light(x, z, y, nibble(block_light, i));
+            }
+
+            if let Some(sky_light) = &section.sky_light {
+                dd_chunk.set_sky_light(x, z, y, nibble(sky_light, i));
+            }
+        }
+    }
 }
 
 fn build_region_filepath(region_x: i32, region_z: i32) -> String {
     let region_file_name = format!("r.{}.{}.mca", region_x, region_z);
-    let region_file_path = Path::new(config::WORLD_FOLDER)
+    let region_file_path = world_folder()
         //.join("region")
         .join(region_file_name);
 
     region_file_path.to_str().unwrap().to_owned()
 }
 
+// Maps a namespaced block name to a block type, None for blocks that should be skipped.
+// Names without a namespace belong to minecraft.
+//
+// Blocks of other namespaces are matched by name the same way as minecraft blocks,
+// if that fails the fallback material of their namespace is used.
+fn get_block_type_namespaced(block_name: &str) -> Option<BlockType> {
+    let (namespace, block_id) = block_name
+        .split_once(':')
+        .unwrap_or(("minecraft", block_name));
+
+    let block_type = get_block_type_ng(block_id);
+    if namespace == "minecraft" {
+        if block_type == BlockType::Unknown {
+            // TODO: this is bad
+            if BLOCK_BLACKLIST.contains(&block_id) {
+                return None;
+            }
+
+            record_unmapped_block(block_name);
+        }
+
+        return Some(block_type);
+    }
+
+    if block_type != BlockType::Unknown {
+        return Some(block_type);
+    }
+
+    record_unmapped_block(block_name);
+    Some(material_registry::namespace_fallback(namespace))
+}
+
+// Names of the blocks that had no mapping, with the number of occurences.
+// Chunks are loaded from multiple threads.
+static UNMAPPED_BLOCKS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+fn record_unmapped_block(block_name: &str) {
+    let mut unmapped = UNMAPPED_BLOCKS.lock().unwrap();
+    match unmapped.get_mut(block_name) {
+        Some(count) => *count += 1,
+        None => {
+            unmapped.insert(block_name.to_owned(), 1);
+        }
+    }
+}
+
+// Prints the blocks without a mapping seen since the last call, most frequent first
+pub fn log_unmapped_blocks() {
+    let unmapped = std::mem::take(&mut *UNMAPPED_BLOCKS.lock().unwrap());
+    if unmapped.is_empty() {
+        return;
+    }
+
+    let mut counts = unmapped.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    println!("[INFO] Blocks without a mapping, extend the block maps in minecraft.rs");
+    println!(
+        "       or the modded block fallbacks of the material legend to change how they are shown:"
+    );
+    for (block_name, count) in counts {
+        println!("    {block_name}: {count}");
+    }
+}
+
 fn get_block_type_ng(block_id: &str) -> BlockType {
     let exact_match = BLOCK_MAP_EXACT.iter().find(|(key, _)| block_id == *key);
     if let Some((_, block_type)) = exact_match {
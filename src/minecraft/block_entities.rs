@@ -0,0 +1,129 @@
+use fastnbt::from_bytes;
+use serde::Deserialize;
+
+use crate::model::chunk::Sign;
+use crate::model::{Coord, Position};
+
+// Since 1.18 the block entities are stored directly in the chunk
+#[derive(Deserialize)]
+struct ChunkBlockEntities {
+    #[serde(default)]
+    block_entities: Vec<BlockEntity>,
+}
+
+// Before 1.18 they are nested in the Level tag and called tile entities
+#[derive(Deserialize)]
+struct LegacyChunkBlockEntities {
+    #[serde(rename = "Level")]
+    level: LegacyLevelBlockEntities,
+}
+
+#[derive(Deserialize)]
+struct LegacyLevelBlockEntities {
+    #[serde(rename = "TileEntities", default)]
+    block_entities: Vec<BlockEntity>,
+}
+
+// Only the fields of signs are read, everything else is ignored
+#[derive(Deserialize)]
+struct BlockEntity {
+    id: String,
+    x: i32,
+    y: i32,
+    z: i32,
+
+    // Before 1.20 each line is stored separately
+    #[serde(rename = "Text1")]
+    text1: Option<String>,
+    #[serde(rename = "Text2")]
+    text2: Option<String>,
+    #[serde(rename = "Text3")]
+    text3: Option<String>,
+    #[serde(rename = "Text4")]
+    text4: Option<String>,
+
+    // Since 1.20 signs can have text on both sides
+    front_text: Option<SignText>,
+    back_text: Option<SignText>,
+}
+
+#[derive(Deserialize)]
+struct SignText {
+    #[serde(default)]
+    messages: Vec<String>,
+}
+
+pub fn load_signs(data: &[u8], legacy_layout: bool) -> fastnbt::error::Result<Vec<Sign>> {
+    let block_entities = match legacy_layout {
+        true => {
+            from_bytes::<LegacyChunkBlockEntities>(data)?
+                .level
+                .block_entities
+        }
+        false => from_bytes::<ChunkBlockEntities>(data)?.block_entities,
+    };
+
+    let signs = block_entities
+        .into_iter()
+        .filter(|entity| entity.id.to_lowercase().ends_with("sign"))
+        .filter_map(|entity| {
+            let old_lines = [&entity.text1, &entity.text2, &entity.text3, &entity.text4]
+                .into_iter()
+                .flatten()
+                .cloned();
+            let sides = [&entity.front_text, &entity.back_text];
+            let new_lines = sides
+                .into_iter()
+                .flatten()
+                .flat_map(|side| side.messages.clone());
+
+            let lines = old_lines
+                .chain(new_lines)
+                .map(|line| plain_text(&line))
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>();
+            if lines.is_empty() {
+                return None;
+            }
+
+            // Labels are anchored in the middle of the sign block
+            let position = Position::new(
+                entity.x as Coord + 0.5,
+                entity.y as Coord + 0.5,
+                entity.z as Coord + 0.5,
+            );
+
+            Some(Sign {
+                position,
+                text: lines.join("\n"),
+            })
+        })
+        .collect();
+
+    Ok(signs)
+}
+
+// Sign lines are JSON text components since 1.8, older saves store the text directly
+fn plain_text(line: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(line) {
+        Ok(component) => component_text(&component),
+        Err(_) => line.to_owned(),
+    }
+}
+
+fn component_text(component: &serde_json::Value) -> String {
+    match component {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(parts) => parts.iter().map(component_text).collect(),
+        serde_json::Value::Object(fields) => {
+            let text = fields
+                .get("text")
+                .and_then(|text| text.as_str())
+                .unwrap_or_default();
+            let extra = fields.get("extra").map(component_text).unwrap_or_default();
+
+            format!("{text}{extra}")
+        }
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,117 @@
+use fastnbt::{from_bytes, ByteArray};
+use serde::Deserialize;
+
+use super::{nibble, BLOCKS_IN_SECTION, SECTION_SIZE};
+
+// Chunks saved before 1.13 store numeric block ids instead of block states,
+// the sections live under the Level tag
+#[derive(Deserialize)]
+struct LegacyChunk {
+    #[serde(rename = "Level")]
+    level: LegacyLevel,
+}
+
+#[derive(Deserialize)]
+struct LegacyLevel {
+    #[serde(rename = "Sections", default)]
+    sections: Vec<LegacySection>,
+}
+
+#[derive(Deserialize)]
+struct LegacySection {
+    #[serde(rename = "Y")]
+    y: i8,
+    // Low 8 bits of the block ids, YZX order
+    #[serde(rename = "Blocks")]
+    blocks: Option<ByteArray>,
+    // High 4 bits of the block ids, only present when some id exceeds 255
+    #[serde(rename = "Add")]
+    add: Option<ByteArray>,
+    // 4 bits of block metadata, used to tell apart variants sharing an id
+    #[serde(rename = "Data")]
+    data: Option<ByteArray>,
+}
+
+// Calls *visit* with the local position and the modern name of every block of the chunk
+pub fn visit_blocks(
+    data: &[u8],
+    mut visit: impl FnMut(usize, isize, usize, &str),
+) -> fastnbt::error::Result<()> {
+    let chunk: LegacyChunk = from_bytes(data)?;
+
+    for section in chunk.level.sections {
+        let Some(blocks) = &section.blocks else {
+            continue;
+        };
+        let section_base_y = section.y as isize * SECTION_SIZE as isize;
+
+        for i in 0..BLOCKS_IN_SECTION {
+            let high_bits = section.add.as_ref().map_or(0, |add| nibble(add, i)) as u16;
+            let id = (blocks[i] as u8) as u16 | (high_bits << 8);
+            let metadata = section.data.as_ref().map_or(0, |data| nibble(data, i));
+
+            let x = i & 0x000F;
+            let y = section_base_y + ((i & 0x0F00) >> 8) as isize;
+            let z = (i & 0x00F0) >> 4;
+
+            visit(x, y, z, legacy_block_name(id, metadata));
+        }
+    }
+
+    Ok(())
+}
+
+// Names of the blocks from before the flattening, as they are called since 1.13.
+// Only blocks that are distinguished by the block mapping or the blacklist are listed,
+// the remaining ids are reported as unknown.
+fn legacy_block_name(id: u16, metadata: u8) -> &'static str {
+    match (id, metadata) {
+        (0, _) => "air",
+        (1, 1) | (1, 2) => "granite",
+        (1, 3) | (1, 4) => "diorite",
+        (1, 5) | (1, 6) => "andesite",
+        (1, _) => "stone",
+        (2, _) => "grass_block",
+        (3, _) => "dirt",
+        (4, _) => "cobblestone",
+        (5, _) => "oak_planks",
+        (6, _) => "oak_sapling",
+        (7, _) => "bedrock",
+        (8, _) | (9, _) => "water",
+        (10, _) | (11, _) => "lava",
+        (12, 1) => "red_sand",
+        (12, _) => "sand",
+        (13, _) => "gravel",
+        (14, _) => "gold_ore",
+        (15, _) => "iron_ore",
+        (16, _) => "coal_ore",
+        (17, _) => "oak_log",
+        (18, _) => "oak_leaves",
+        (20, _) => "glass",
+        (21, _) => "lapis_ore",
+        (24, _) => "sandstone",
+        (31, _) => "grass",
+        (32, _) => "dead_bush",
+        (37, _) => "dandelion",
+        (38, _) => "poppy",
+        (48, _) => "mossy_cobblestone",
+        (50, _) => "torch",
+        (53, _) => "oak_stairs",
+        (56, _) => "diamond_ore",
+        (64, _) => "oak_door",
+        (73, _) | (74, _) => "redstone_ore",
+        (95, _) => "white_stained_glass",
+        (98, _) => "stone_bricks",
+        (102, _) => "glass_pane",
+        (106, _) => "vine",
+        (127, _) => "cocoa",
+        (129, _) => "emerald_ore",
+        (159, 0) => "white_terracotta",
+        (159, _) | (172, _) => "terracotta",
+        (161, _) => "acacia_leaves",
+        (162, _) => "acacia_log",
+        (175, _) => "tall_grass",
+        (179, _) => "red_sandstone",
+        _ => "unknown",
+    }
+}
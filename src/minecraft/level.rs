@@ -0,0 +1,133 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use cgmath::Point3;
+use fastnbt::from_bytes;
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+
+use crate::model::Position;
+
+// The camera is placed roughly at the eye height of a player
+const SPAWN_CAMERA_HEIGHT: f64 = 1.62;
+
+#[derive(Deserialize)]
+struct LevelDat {
+    #[serde(rename = "Data")]
+    data: LevelData,
+}
+
+#[derive(Deserialize)]
+struct LevelData {
+    #[serde(rename = "SpawnX")]
+    spawn_x: i32,
+    #[serde(rename = "SpawnY")]
+    spawn_y: i32,
+    #[serde(rename = "SpawnZ")]
+    spawn_z: i32,
+
+    // Singleplayer worlds keep the player in level.dat instead of playerdata
+    #[serde(rename = "Player")]
+    player: Option<PlayerData>,
+}
+
+#[derive(Deserialize)]
+struct PlayerData {
+    #[serde(rename = "Pos")]
+    position: Vec<f64>,
+}
+
+// A place in the world the camera can start at
+#[derive(Clone, Debug)]
+pub struct StartLocation {
+    pub name: String,
+    pub position: Position,
+}
+
+// The save folder holds level.dat and playerdata next to the region folder,
+// a bare folder of region files is its own save folder
+pub fn save_folder(region_folder: &Path) -> PathBuf {
+    match region_folder.file_name() {
+        Some(name) if name == "region" => region_folder
+            .parent()
+            .map_or_else(|| region_folder.to_path_buf(), Path::to_path_buf),
+        _ => region_folder.to_path_buf(),
+    }
+}
+
+// The world spawn and the last positions of the players, in that order.
+// Files that are missing or can't be read are skipped.
+pub fn start_locations(save_folder: &Path) -> Vec<StartLocation> {
+    let mut locations = Vec::new();
+
+    match read_nbt::<LevelDat>(&save_folder.join("level.dat")) {
+        Ok(level) => {
+            let level = level.data;
+            locations.push(StartLocation {
+                name: "World spawn".to_owned(),
+                position: Point3::new(
+                    level.spawn_x as f64 + 0.5,
+                    level.spawn_y as f64 + SPAWN_CAMERA_HEIGHT,
+                    level.spawn_z as f64 + 0.5,
+                ),
+            });
+
+            if let Some(position) = level.player.and_then(player_position) {
+                locations.push(StartLocation {
+                    name: "Player".to_owned(),
+                    position,
+                });
+            }
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+        Err(error) => eprintln!("[ERROR] Failed to read level.dat - {}", error),
+    }
+
+    let player_files = fs::read_dir(save_folder.join("playerdata"))
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "dat"));
+
+    for path in player_files {
+        let player = match read_nbt::<PlayerData>(&path) {
+            Ok(player) => player,
+            Err(error) => {
+                eprintln!("[ERROR] Failed to read {} - {}", path.display(), error);
+                continue;
+            }
+        };
+
+        // Player files are named after the uuid of the player
+        let uuid = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(position) = player_position(player) {
+            locations.push(StartLocation {
+                name: format!("Player {}", uuid),
+                position,
+            });
+        }
+    }
+
+    locations
+}
+
+// Position of the eyes, the stored position is at the feet of the player
+fn player_position(player: PlayerData) -> Option<Position> {
+    match player.position.as_slice() {
+        [x, y, z] => Some(Point3::new(*x, *y + SPAWN_CAMERA_HEIGHT, *z)),
+        _ => None,
+    }
+}
+
+// level.dat and the player files are gzipped NBT
+fn read_nbt<T: for<'de> Deserialize<'de>>(path: &Path) -> io::Result<T> {
+    let mut data = Vec::new();
+    GzDecoder::new(File::open(path)?).read_to_end(&mut data)?;
+
+    from_bytes(&data).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+}
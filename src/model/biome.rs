@@ -0,0 +1,82 @@
+use super::common::BlockType;
+
+// Groups of minecraft biomes that differ in the color of grass and leaves
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Biome {
+    // Plains, forests and every biome we don't know
+    #[default]
+    Temperate,
+    Jungle,
+    Swamp,
+    DarkForest,
+    Birch,
+    Taiga,
+    Snowy,
+    Savanna,
+    Badlands,
+    Desert,
+}
+
+// The name of the biome has to contain the key to match, underscores are ignored.
+// Note that order matters as some biomes may match multiple keys.
+const BIOME_MAP: [(&str, Biome); 15] = [
+    ("badlands", Biome::Badlands),
+    ("mesa", Biome::Badlands),
+    ("desert", Biome::Desert),
+    ("savanna", Biome::Savanna),
+    ("jungle", Biome::Jungle),
+    ("swamp", Biome::Swamp),
+    ("darkforest", Biome::DarkForest),
+    ("roofedforest", Biome::DarkForest),
+    ("birch", Biome::Birch),
+    ("snow", Biome::Snowy),
+    ("frozen", Biome::Snowy),
+    ("ice", Biome::Snowy),
+    ("grove", Biome::Snowy),
+    ("taiga", Biome::Taiga),
+    ("windswept", Biome::Taiga),
+];
+
+impl Biome {
+    // Maps a biome name to its group, both namespaced ids like minecraft:dark_forest
+    // and names like DarkForest are accepted
+    pub fn from_name(name: &str) -> Self {
+        let name = name.rsplit(':').next().unwrap_or(name);
+        let name = name.replace('_', "").to_lowercase();
+
+        BIOME_MAP
+            .iter()
+            .find(|(key, _)| name.contains(key))
+            .map_or(Biome::Temperate, |(_, biome)| *biome)
+    }
+
+    // Multiplies the textures of grass and leaves, which are drawn in the temperate color
+    pub fn tint(self) -> [f32; 3] {
+        match self {
+            Biome::Temperate => [1.0, 1.0, 1.0],
+            Biome::Jungle => [0.75, 1.0, 0.55],
+            Biome::Swamp => [0.6, 0.65, 0.45],
+            Biome::DarkForest => [0.7, 0.8, 0.6],
+            Biome::Birch => [0.9, 1.0, 0.8],
+            Biome::Taiga => [0.75, 0.9, 0.85],
+            Biome::Snowy => [0.85, 0.95, 0.95],
+            Biome::Savanna => [1.0, 0.9, 0.55],
+            Biome::Badlands => [0.95, 0.75, 0.5],
+            Biome::Desert => [1.0, 0.85, 0.5],
+        }
+    }
+}
+
+// Materials whose color follows the biome, the rest keep their texture
+pub fn is_biome_tinted(material: BlockType) -> bool {
+    matches!(material, BlockType::Grass | BlockType::Leaves)
+}
+
+// Tint of a block of *material* in *biome*
+pub fn block_tint(material: BlockType, biome: Biome) -> [f32; 3] {
+    if is_biome_tinted(material) {
+        biome.tint()
+    } else {
+        Biome::Temperate.tint()
+    }
+}
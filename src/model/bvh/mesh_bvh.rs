@@ -0,0 +1,137 @@
+use cgmath::InnerSpace;
+
+use crate::model::polygonize::{Mesh, VertexIndex};
+
+use super::{Aabb, Hierarchy, Vec3, Visit};
+
+// Rays closer than this to the plane of a triangle don't hit it
+const PARALLEL_EPSILON: f32 = 1e-8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct TriangleHit {
+    pub distance: f32,
+    // Index of the triangle in the mesh, the vertices are mesh.indices[3 * triangle..]
+    pub triangle: usize,
+    // Weights of the three vertices of the triangle at the hit point
+    pub barycentric: Vec3,
+}
+
+// Bounding volume hierarchy over the triangles of a single mesh
+pub struct MeshBvh {
+    hierarchy: Hierarchy,
+    corners: Vec<[Vec3; 3]>,
+}
+
+impl MeshBvh {
+    pub fn build(mesh: &Mesh) -> Self {
+        let corners = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| triangle_corners(mesh, triangle))
+            .collect::<Vec<_>>();
+
+        MeshBvh {
+            hierarchy: Hierarchy::build(&triangle_bounds(&corners)),
+            corners,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.corners.len()
+    }
+
+    // Bounds of the whole mesh, None for a mesh without triangles
+    pub(super) fn bounds(&self) -> Option<Aabb> {
+        let root = self.hierarchy.nodes.last()?;
+        Some(*root.bounds())
+    }
+
+    // Closest triangle hit by the ray within *max_distance*
+    pub fn closest_hit(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<TriangleHit> {
+        let mut closest: Option<TriangleHit> = None;
+        self.hierarchy
+            .traverse(origin, direction, max_distance, |triangle| {
+                let reach = closest.map_or(max_distance, |hit| hit.distance);
+                match intersect_triangle(&self.corners[triangle], origin, direction) {
+                    Some((distance, barycentric)) if distance < reach => {
+                        closest = Some(TriangleHit {
+                            distance,
+                            triangle,
+                            barycentric,
+                        });
+                        Visit::Shorten(distance)
+                    }
+                    _ => Visit::Continue,
+                }
+            });
+
+        closest
+    }
+
+    // Whether any triangle blocks the ray before *max_distance*, stops at the first one found
+    pub fn any_hit(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> bool {
+        let mut hit = false;
+        self.hierarchy.traverse(
+            origin,
+            direction,
+            max_distance,
+            |triangle| match intersect_triangle(&self.corners[triangle], origin, direction) {
+                Some((distance, _)) if distance < max_distance => {
+                    hit = true;
+                    Visit::Stop
+                }
+                _ => Visit::Continue,
+            },
+        );
+
+        hit
+    }
+}
+
+fn triangle_corners(mesh: &Mesh, triangle: &[VertexIndex]) -> [Vec3; 3] {
+    [0, 1, 2].map(|corner| Vec3::from(mesh.vertices[triangle[corner] as usize].position))
+}
+
+fn triangle_bounds(corners: &[[Vec3; 3]]) -> Vec<Aabb> {
+    corners
+        .iter()
+        .map(|&corners| Aabb::from_points(corners))
+        .collect()
+}
+
+// Möller–Trumbore, returns the distance and the barycentric coordinates of the hit
+fn intersect_triangle(corners: &[Vec3; 3], origin: Vec3, direction: Vec3) -> Option<(f32, Vec3)> {
+    let [a, b, c] = *corners;
+    let edge1 = b - a;
+    let edge2 = c - a;
+
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = to_origin.cross(edge1);
+    let v = direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) * inverse_determinant;
+    match distance > 0.0 {
+        true => Some((distance, Vec3::new(1.0 - u - v, u, v))),
+        false => None,
+    }
+}
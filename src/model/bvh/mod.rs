@@ -0,0 +1,224 @@
+use cgmath::{ElementWise, Vector3};
+
+pub use self::mesh_bvh::{MeshBvh, TriangleHit};
+pub use self::world_bvh::{WorldBvh, WorldHit};
+
+mod mesh_bvh;
+mod world_bvh;
+
+// Primitives stored in a leaf, larger leaves build faster but trace slower
+const MAX_LEAF_PRIMITIVES: usize = 4;
+
+type Vec3 = Vector3<f32>;
+
+#[derive(Clone, Copy, Debug)]
+struct Aabb {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Aabb {
+    fn empty() -> Self {
+        Aabb {
+            min: Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut bounds = Aabb::empty();
+        points.into_iter().for_each(|point| bounds.grow(point));
+        bounds
+    }
+
+    fn grow(&mut self, point: Vec3) {
+        self.min = Vec3::new(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Vec3::new(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::from_points([self.min, self.max, other.min, other.max])
+    }
+
+    fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    fn longest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        match (
+            extent.x >= extent.y,
+            extent.x >= extent.z,
+            extent.y >= extent.z,
+        ) {
+            (true, true, _) => 0,
+            (false, _, true) => 1,
+            _ => 2,
+        }
+    }
+
+    // Whether the ray enters the box before *max_distance*
+    fn is_hit(&self, origin: Vec3, inverse_direction: Vec3, max_distance: f32) -> bool {
+        let t1 = (self.min - origin).mul_element_wise(inverse_direction);
+        let t2 = (self.max - origin).mul_element_wise(inverse_direction);
+
+        let near = t1.x.min(t2.x).max(t1.y.min(t2.y)).max(t1.z.min(t2.z));
+        let far = t1.x.max(t2.x).min(t1.y.max(t2.y)).min(t1.z.max(t2.z));
+
+        near <= far && far >= 0.0 && near <= max_distance
+    }
+}
+
+enum BvhNode {
+    Inner {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Inner { bounds, .. } | BvhNode::Leaf { bounds, .. } => bounds,
+        }
+    }
+}
+
+// What the traversal does after a primitive was tested
+enum Visit {
+    Continue,
+    // The primitive was hit, only closer primitives are of interest now
+    Shorten(f32),
+    Stop,
+}
+
+// Hierarchy of primitives given only by their bounds, shared by the mesh and the world bvh.
+// The nodes split the primitives at the median of their centers along the longest axis.
+// Children are pushed before their parent, so the root is the last node.
+struct Hierarchy {
+    nodes: Vec<BvhNode>,
+    // Primitive indices, every leaf owns a contiguous range
+    primitives: Vec<usize>,
+}
+
+impl Hierarchy {
+    fn build(bounds: &[Aabb]) -> Self {
+        let mut hierarchy = Hierarchy {
+            nodes: Vec::new(),
+            primitives: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            hierarchy.build_node(bounds, 0, bounds.len());
+        }
+
+        hierarchy
+    }
+
+    fn build_node(&mut self, bounds: &[Aabb], first: usize, count: usize) -> usize {
+        let range = first..first + count;
+        let primitives = &self.primitives[range.clone()];
+        let node_bounds = primitives.iter().fold(Aabb::empty(), |node, &primitive| {
+            node.union(&bounds[primitive])
+        });
+
+        if count <= MAX_LEAF_PRIMITIVES {
+            self.nodes.push(BvhNode::Leaf {
+                bounds: node_bounds,
+                first,
+                count,
+            });
+            return self.nodes.len() - 1;
+        }
+
+        let centers = Aabb::from_points(
+            primitives
+                .iter()
+                .map(|&primitive| bounds[primitive].center()),
+        );
+        let axis = centers.longest_axis();
+        let half = count / 2;
+        self.primitives[range].select_nth_unstable_by(half, |&a, &b| {
+            bounds[a].center()[axis].total_cmp(&bounds[b].center()[axis])
+        });
+
+        let left = self.build_node(bounds, first, half);
+        let right = self.build_node(bounds, first + half, count - half);
+        self.nodes.push(BvhNode::Inner {
+            bounds: node_bounds,
+            left,
+            right,
+        });
+        self.nodes.len() - 1
+    }
+
+    // Recomputes the bounds of every node after the primitives moved, the tree stays the same.
+    // Cheaper than a rebuild, but the tree gets less efficient the more the primitives move.
+    fn refit(&mut self, bounds: &[Aabb]) {
+        for index in 0..self.nodes.len() {
+            let refitted = match self.nodes[index] {
+                BvhNode::Inner { left, right, .. } => {
+                    self.nodes[left].bounds().union(self.nodes[right].bounds())
+                }
+                BvhNode::Leaf { first, count, .. } => self.primitives[first..first + count]
+                    .iter()
+                    .fold(Aabb::empty(), |node, &primitive| {
+                        node.union(&bounds[primitive])
+                    }),
+            };
+
+            match &mut self.nodes[index] {
+                BvhNode::Inner { bounds, .. } | BvhNode::Leaf { bounds, .. } => *bounds = refitted,
+            }
+        }
+    }
+
+    // Calls *visit* for the primitives of every leaf the ray enters before the current reach
+    fn traverse(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        mut visit: impl FnMut(usize) -> Visit,
+    ) {
+        let Some(root) = self.nodes.len().checked_sub(1) else {
+            return;
+        };
+        let inverse_direction = Vec3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+
+        let mut reach = max_distance;
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !node.bounds().is_hit(origin, inverse_direction, reach) {
+                continue;
+            }
+
+            match *node {
+                BvhNode::Inner { left, right, .. } => stack.extend([left, right]),
+                BvhNode::Leaf { first, count, .. } => {
+                    for &primitive in &self.primitives[first..first + count] {
+                        match visit(primitive) {
+                            Visit::Continue => {}
+                            Visit::Shorten(distance) => reach = reach.min(distance),
+                            Visit::Stop => return,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::infrastructure::event_bus::{AppEvent, EventSubscriber, FrameContext};
+use crate::model::chunk::ChunkPosition;
+use crate::model::polygonize::Mesh;
+
+use super::{Aabb, Hierarchy, MeshBvh, TriangleHit, Vec3, Visit};
+
+#[derive(Clone, Copy, Debug)]
+pub struct WorldHit {
+    // Chunk whose mesh was hit, the triangle indexes into that mesh
+    pub chunk: ChunkPosition,
+    pub hit: TriangleHit,
+}
+
+struct ChunkEntry {
+    position: ChunkPosition,
+    bvh: MeshBvh,
+}
+
+// Two level bounding volume hierarchy over the smooth meshes of all chunks.
+//
+// Every chunk mesh has its own MeshBvh, the top level hierarchy sorts the chunks by their bounds.
+// When a chunk is rebuilt only its own bvh is built again and the top level is refitted,
+// the top level is rebuilt from scratch only when chunks are added or removed.
+pub struct WorldBvh {
+    chunks: Vec<ChunkEntry>,
+    top: Hierarchy,
+}
+
+impl WorldBvh {
+    pub fn new() -> Self {
+        WorldBvh {
+            chunks: Vec::new(),
+            top: Hierarchy::build(&[]),
+        }
+    }
+
+    // Adds the mesh of a chunk or replaces the mesh it had before
+    pub fn set_chunk(&mut self, position: ChunkPosition, mesh: &Mesh) {
+        let bvh = MeshBvh::build(mesh);
+        let existing = self
+            .chunks
+            .iter_mut()
+            .find(|entry| entry.position == position);
+
+        match (existing, bvh.bounds().is_some()) {
+            (Some(entry), true) => {
+                entry.bvh = bvh;
+                self.top.refit(&self.chunk_bounds());
+            }
+            (Some(_), false) => self.retain_chunks(|chunk_position| chunk_position != position),
+            (None, true) => {
+                self.chunks.push(ChunkEntry { position, bvh });
+                self.top = Hierarchy::build(&self.chunk_bounds());
+            }
+            // Meshes without triangles are left out, nothing can hit them
+            (None, false) => {}
+        }
+    }
+
+    pub fn retain_chunks(&mut self, mut keep: impl FnMut(ChunkPosition) -> bool) {
+        let count = self.chunks.len();
+        self.chunks.retain(|entry| keep(entry.position));
+
+        if self.chunks.len() != count {
+            self.top = Hierarchy::build(&self.chunk_bounds());
+        }
+    }
+
+    // Closest triangle of any chunk mesh hit by the ray within *max_distance*
+    pub fn closest_hit(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<WorldHit> {
+        let mut closest: Option<WorldHit> = None;
+        self.top.traverse(origin, direction, max_distance, |chunk| {
+            let reach = closest.map_or(max_distance, |closest| closest.hit.distance);
+            let entry = &self.chunks[chunk];
+
+            match entry.bvh.closest_hit(origin, direction, reach) {
+                Some(hit) => {
+                    closest = Some(WorldHit {
+                        chunk: entry.position,
+                        hit,
+                    });
+                    Visit::Shorten(hit.distance)
+                }
+                None => Visit::Continue,
+            }
+        });
+
+        closest
+    }
+
+    // Whether any chunk mesh blocks the ray before *max_distance*
+    pub fn any_hit(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> bool {
+        let mut hit = false;
+        self.top.traverse(origin, direction, max_distance, |chunk| {
+            match self.chunks[chunk]
+                .bvh
+                .any_hit(origin, direction, max_distance)
+            {
+                true => {
+                    hit = true;
+                    Visit::Stop
+                }
+                false => Visit::Continue,
+            }
+        });
+
+        hit
+    }
+
+    // Every chunk in the list has a mesh with at least one triangle
+    fn chunk_bounds(&self) -> Vec<Aabb> {
+        self.chunks
+            .iter()
+            .map(|entry| entry.bvh.bounds().unwrap())
+            .collect()
+    }
+}
+
+impl EventSubscriber for WorldBvh {
+    // Keeps the hierarchy in sync with the meshes of the world
+    fn handle_events(&mut self, events: &[AppEvent], context: &FrameContext) {
+        let world = context.world;
+        for event in events {
+            match event {
+                AppEvent::MeshBuilt(chunk_position) => {
+                    if let Some(mesh) = world.get_chunk_mesh(*chunk_position) {
+                        self.set_chunk(*chunk_position, mesh);
+                    }
+                }
+                AppEvent::ChunkLoaded(_) => {
+                    let loaded = world.get_loaded_chunk_positions();
+                    self.retain_chunks(|chunk_position| loaded.contains(&chunk_position));
+                }
+                _ => {}
+            }
+        }
+    }
+}
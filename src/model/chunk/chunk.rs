@@ -1,13 +1,14 @@
 use std::cmp::min;
 
+use super::light_tower::{LightLevel, LightStack};
 use super::material_tower::MaterialStack;
-use super::ChunkPosition;
-use crate::config;
-use crate::infrastructure::texture::MaterialBlend;
+use super::octree::{ChunkOctree, LocalBox};
+use super::{ChunkCoords, ChunkPosition, Sign};
 use crate::minecraft::{self, BLOCKS_IN_CHUNK, MAX_BLOCK_Y, MIN_BLOCK_Y};
-use crate::model::common::{
-    get_pallette_texture_coords, is_rigid_block, is_visible_block, BlockType, MaterialSetup,
-};
+use crate::model::biome::{self, Biome};
+use crate::model::common::{is_visible_block, BlockType, MaterialSetup, BLOCK_TYPES};
+use crate::model::implicit::density_memo::KernelScan;
+use crate::model::material_registry;
 use crate::model::rectangle::Rectangle;
 use crate::model::{Coord, Position, Real};
 
@@ -22,15 +23,24 @@ const EPSILON: Coord = 0.0001;
 #[derive(Clone, Copy)]
 pub struct BlockData {
     pub offset: [f32; 3],
-    pub pallette_offset: [f32; 2],
+    // Layer of the block texture array, the integer value of the material
+    pub texture_layer: u32,
+
+    // Normalized block and sky light reaching the block
+    pub light: [f32; 2],
+
+    // Multiplies the texture, see model/biome.rs
+    pub biome_tint: [f32; 3],
 }
-implement_vertex!(BlockData, offset, pallette_offset);
+implement_vertex!(BlockData, offset, texture_layer, light, biome_tint);
 
 impl BlockData {
-    pub fn create(offset: Position, material: BlockType) -> Self {
+    pub fn create(offset: Position, material: BlockType, light: LightLevel, biome: Biome) -> Self {
         BlockData {
             offset: [offset.x as f32, offset.y as f32, offset.z as f32],
-            pallette_offset: get_pallette_texture_coords(material),
+            texture_layer: material as u32,
+            light: light.normalized(),
+            biome_tint: biome::block_tint(material, biome),
         }
     }
 }
@@ -44,12 +54,27 @@ pub struct RigidBlockRecord {
 }
 
 // A chunks is a 16*y*16 region of blocks
+#[derive(Clone)]
 pub struct Chunk {
     data: [MaterialStack; CHUNK_SIZE * CHUNK_SIZE],
+    light: [LightStack; CHUNK_SIZE * CHUNK_SIZE],
+    // Biome at the surface of every column
+    biomes: [Biome; CHUNK_SIZE * CHUNK_SIZE],
     rigid_blocks: Vec<RigidBlockRecord>,
 
+    // Aggregated block data used to approximate density evaluation for large kernels,
+    // None until build_octree is called
+    octree: Option<ChunkOctree>,
+
+    // Signs with some text on them, in world coordinates
+    signs: Vec<Sign>,
+
     pub surface_blocks: Vec<BlockData>,
 
+    // Hash of the blocks in the chunk, changes whenever the blocks do.
+    // Updated together with the surface, zero until build_surface is called
+    content_hash: u64,
+
     // This is the position of the bottom left corner of the chunk from a top down view
     pub position: ChunkPosition,
 }
@@ -98,12 +123,21 @@ fn get_block_portion_in_range(block_start: usize, range_start: Coord, range_end:
 
 const MAX_BLOCK_INDEX: usize = BLOCKS_IN_CHUNK - 1;
 
+// Parameters of the FNV-1a hash of the chunk content, see Chunk::content_hash
+pub const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+pub const FNV_PRIME: u64 = 0x100000001b3;
+
 impl Chunk {
     pub fn new(chunk_position: ChunkPosition) -> Self {
         Chunk {
             data: array_init(|_inx| MaterialStack::new()),
+            light: array_init(|_inx| LightStack::new()),
+            biomes: [Biome::default(); CHUNK_SIZE * CHUNK_SIZE],
             rigid_blocks: Vec::new(),
+            octree: None,
+            signs: Vec::new(),
             surface_blocks: Vec::new(),
+            content_hash: 0,
             position: chunk_position,
         }
     }
@@ -116,51 +150,118 @@ impl Chunk {
         &mut self.data[z * CHUNK_SIZE + x]
     }
 
-    // Push block on top of the material tower at x, z
+    fn get_light_tower(&self, x: usize, z: usize) -> &LightStack {
+        &self.light[z * CHUNK_SIZE + x]
+    }
+
+    fn get_light_tower_mut(&mut self, x: usize, z: usize) -> &mut LightStack {
+        &mut self.light[z * CHUNK_SIZE + x]
+    }
+
+    pub fn set_biome(&mut self, x: usize, z: usize, biome: Biome) {
+        self.biomes[z * CHUNK_SIZE + x] = biome;
+    }
+
+    pub fn get_biome(&self, x: usize, z: usize) -> Biome {
+        self.biomes[z * CHUNK_SIZE + x]
+    }
+
+    pub fn set_signs(&mut self, signs: Vec<Sign>) {
+        self.signs = signs;
+    }
+
+    pub fn get_signs(&self) -> &[Sign] {
+        &self.signs
+    }
+
+    pub fn set_block_light(&mut self, x: usize, z: usize, y: isize, level: u8) {
+        self.get_light_tower_mut(x, z).set_block_light(y, level);
+    }
+
+    pub fn set_sky_light(&mut self, x: usize, z: usize, y: isize, level: u8) {
+        self.get_light_tower_mut(x, z).set_sky_light(y, level);
+    }
+
+    pub fn get_light(&self, x: usize, y: isize, z: usize) -> LightLevel {
+        self.get_light_tower(x, z).get_light_at_y(y)
+    }
+
+    // Solid blocks store no light of their own, the light that reaches their faces
+    // is the brightest light of their neighbors within the chunk
+    fn get_exposed_light(&self, x: usize, y: isize, z: usize) -> LightLevel {
+        let mut light = self.get_light(x, y + 1, z).max(self.get_light(x, y - 1, z));
+
+        if x > 0 {
+            light = light.max(self.get_light(x - 1, y, z));
+        }
+        if x < MAX_BLOCK_INDEX {
+            light = light.max(self.get_light(x + 1, y, z));
+        }
+        if z > 0 {
+            light = light.max(self.get_light(x, y, z - 1));
+        }
+        if z < MAX_BLOCK_INDEX {
+            light = light.max(self.get_light(x, y, z + 1));
+        }
+
+        light
+    }
+
+    // Push block on top of the material tower at x, z.
+    // The rigid blocks are recorded when the surface is built, see build_surface
     pub fn push_block(&mut self, x: usize, z: usize, base_height: isize, block: BlockType) {
         let stack = self.get_tower_mut(x, z);
         stack.insert(block, base_height);
+    }
 
-        // update rigid block records
-        if is_rigid_block(block) {
-            let local_position = Position::new(x as f64, base_height as f64, z as f64);
-            let position = self.to_global_position(local_position);
-
-            // offset the position to the center of the block
-            // TODO: make a constant for block size
-            // TODO: does this make sense? why not have the blocks centered by default?
-            // TODO: this offset is reversed in the shader, this is weird and probably shouldn't be
-            // done at all.
-            //let offset_position = position.map(|x| x + 0.5);
-
-            let rigid_record = RigidBlockRecord {
-                position, //offset_position,
-                material: block,
-            };
+    // Rebuilds the data derived from the blocks once edits were pushed with push_block,
+    // see World::set_blocks. Light is left as it was loaded.
+    pub fn rebuild_after_edit(&mut self) {
+        self.surface_blocks.clear();
+        self.build_surface();
+        if self.octree.is_some() {
+            self.build_octree();
+        }
+    }
 
-            self.rigid_blocks.push(rigid_record);
+    // Records the blocks of the rigid materials, the records have to be gathered again
+    // when the set of rigid materials changes.
+    // The registry is read once, not for every block of the chunk.
+    pub fn refresh_rigid_blocks(&mut self) {
+        let coords = self.coords();
+        let materials = material_registry::snapshot();
+
+        let mut rigid_blocks = Vec::new();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let stack = self.get_tower(x, z);
+                let rigid_in_stack = stack
+                    .iter_visible_blocks()
+                    .filter(|(_, material)| materials.is_rigid(*material))
+                    .map(|(y, material)| RigidBlockRecord {
+                        position: coords.block_to_global(x, y, z),
+                        material,
+                    });
+                rigid_blocks.extend(rigid_in_stack);
+            }
         }
+
+        self.rigid_blocks = rigid_blocks;
     }
 
     pub fn get_block_data(&self) -> Vec<BlockData> {
         let mut blocks = Vec::<BlockData>::new();
-        let (chunk_global_x, chunk_global_z) = self.position.get_global_position_in_chunks();
-        let global_offset_blocks_x = chunk_global_x * (minecraft::BLOCKS_IN_CHUNK as i32);
-        let global_offset_blocks_z = chunk_global_z * (minecraft::BLOCKS_IN_CHUNK as i32);
+        let coords = self.coords();
 
         for x in 0..CHUNK_SIZE {
             for z in 0..CHUNK_SIZE {
                 let stack = self.get_tower(x, z);
                 for (y, material) in stack.iter_visible_blocks() {
-                    let x_offset_blocks = global_offset_blocks_x + x as i32;
-                    let z_offset_blocks = global_offset_blocks_z + z as i32;
+                    let block_offset = coords.block_to_global(x, y, z);
+                    let light = self.get_exposed_light(x, y, z);
 
-                    let block_data = BlockData {
-                        offset: [x_offset_blocks as f32, y as f32, z_offset_blocks as f32],
-                        pallette_offset: get_pallette_texture_coords(material),
-                    };
-
-                    blocks.push(block_data);
+                    let biome = self.get_biome(x, z);
+                    blocks.push(BlockData::create(block_offset, material, light, biome));
                 }
             }
         }
@@ -169,17 +270,35 @@ impl Chunk {
     }
 
     pub fn get_rigid_block_data(&self) -> Vec<BlockData> {
-        self.rigid_blocks
-            .iter()
-            .map(|rigid_record| BlockData {
+        self.get_rigid_blocks().map(|(_, block)| block).collect()
+    }
+
+    // Instance data of the rigid blocks along with their material
+    pub fn get_rigid_blocks(&self) -> impl Iterator<Item = (BlockType, BlockData)> + '_ {
+        let coords = self.coords();
+
+        self.rigid_blocks.iter().map(move |rigid_record| {
+            let local_position = coords.to_local(rigid_record.position);
+            let local_x = local_position.x as usize;
+            let local_z = local_position.z as usize;
+            let y = local_position.y as isize;
+
+            let block = BlockData {
                 offset: [
                     rigid_record.position.x as f32,
                     rigid_record.position.y as f32,
                     rigid_record.position.z as f32,
                 ],
-                pallette_offset: get_pallette_texture_coords(rigid_record.material),
-            })
-            .collect()
+                texture_layer: rigid_record.material as u32,
+                light: self.get_exposed_light(local_x, y, local_z).normalized(),
+                biome_tint: biome::block_tint(
+                    rigid_record.material,
+                    self.get_biome(local_x, local_z),
+                ),
+            };
+
+            (rigid_record.material, block)
+        })
     }
 
     pub fn get_block_coords(x: Coord, z: Coord) -> (usize, usize) {
@@ -189,6 +308,20 @@ impl Chunk {
         (block_x, block_z)
     }
 
+    // Height of the surface in every column, in the same order as the columns are stored.
+    // Empty columns report the bottom of the world.
+    pub fn get_height_map(&self) -> Vec<isize> {
+        self.data
+            .iter()
+            .map(|tower| tower.get_top_visible_height().unwrap_or(MIN_BLOCK_Y))
+            .collect()
+    }
+
+    // Height of the topmost visible block in the column, None for columns made only of air
+    pub fn get_surface_height(&self, x: usize, z: usize) -> Option<isize> {
+        self.get_tower(x, z).get_top_visible_height()
+    }
+
     pub fn get_block(&self, x: usize, y: isize, z: usize) -> BlockType {
         let tower = self.get_tower(x, z);
 
@@ -206,8 +339,30 @@ impl Chunk {
         Some(tower.get_block_at_y(y))
     }
 
+    // Number of blocks of each material, air is not counted
+    pub fn count_materials(&self) -> [usize; BLOCK_TYPES] {
+        let mut counts = [0; BLOCK_TYPES];
+        for stack in &self.data {
+            for (_, material) in stack.iter_visible_blocks() {
+                counts[material as usize] += 1;
+            }
+        }
+
+        counts
+    }
+
+    pub fn content_hash(&self) -> u64 {
+        self.content_hash
+    }
+
     pub fn build_surface(&mut self) {
-        let chunk_base = self.position.get_global_position();
+        self.refresh_rigid_blocks();
+        self.content_hash = self
+            .data
+            .iter()
+            .fold(FNV_OFFSET_BASIS, |hash, stack| stack.hash_into(hash));
+
+        let coords = self.coords();
 
         // Include all inner blocks that have at least 1 invisible neighbor
         for ((_, lower_row), (row_index, center_row), (_, upper_row)) in self
@@ -223,9 +378,6 @@ impl Chunk {
                 let upper_tower = &upper_row[column_index];
                 let lower_tower = &lower_row[column_index];
 
-                let x_offset = chunk_base.x + column_index as Coord;
-                let z_offset = chunk_base.y + row_index as Coord;
-
                 for depth in MIN_BLOCK_Y + 1..MAX_BLOCK_Y - 1 {
                     let center_block = center_tower.get_block_at_y(depth);
                     if !is_visible_block(center_block) {
@@ -251,9 +403,12 @@ impl Chunk {
                         .into_iter()
                         .any(|block| !is_visible_block(block))
                     {
-                        let block_offset = Position::new(x_offset, depth as Coord, z_offset);
+                        let block_offset = coords.block_to_global(column_index, depth, row_index);
+                        let light = self.get_exposed_light(column_index, depth, row_index);
 
-                        let block_data = BlockData::create(block_offset, center_block);
+                        let biome = self.get_biome(column_index, row_index);
+                        let block_data =
+                            BlockData::create(block_offset, center_block, light, biome);
                         self.surface_blocks.push(block_data);
                     }
                 }
@@ -269,13 +424,16 @@ impl Chunk {
                 column == 0 || row == 0 || column == MAX_BLOCK_INDEX || row == MAX_BLOCK_INDEX;
             if is_edge {
                 let tower = &self.data[i];
-                let tower_blocks = tower.iter_visible_blocks().map(|(depth, material)| {
-                    let x_offset = chunk_base.x + column as Coord;
-                    let z_offset = chunk_base.y + row as Coord;
-                    let block_offset = Position::new(x_offset, depth as Coord, z_offset);
+                let tower_blocks = tower
+                    .iter_visible_blocks()
+                    .map(|(depth, material)| {
+                        let block_offset = coords.block_to_global(column, depth, row);
+                        let light = self.get_exposed_light(column, depth, row);
+                        let biome = self.get_biome(column, row);
 
-                    BlockData::create(block_offset, material)
-                });
+                        BlockData::create(block_offset, material, light, biome)
+                    })
+                    .collect_vec();
 
                 self.surface_blocks.extend(tower_blocks);
             }
@@ -319,9 +477,9 @@ impl Chunk {
                         return None;
                     }
 
-                    let local_block_position = Position::new(x as Coord, y as Coord, z as Coord);
                     let block_position = self
-                        .to_global_position(local_block_position)
+                        .coords()
+                        .block_to_global(x, y, z)
                         .map(|coord| coord + 0.5); // This offset is evil and should be abolished.
 
                     Some((block_position, material, block_position.distance2(position)))
@@ -339,7 +497,7 @@ impl Chunk {
         z: usize,
         material_setup: &MaterialSetup,
     ) -> bool {
-        if config::FILTER_RIGID == false {
+        if !material_setup.filters_rigid() {
             return true;
         }
 
@@ -362,34 +520,6 @@ impl Chunk {
         is_block_critical
     }
 
-    fn to_global_position(&self, relative_position: Position) -> Position {
-        let (chunk_global_x, chunk_global_z) = self.position.get_global_position_in_chunks();
-        let global_offset_blocks_x =
-            (chunk_global_x * (minecraft::BLOCKS_IN_CHUNK as i32)) as Coord;
-        let global_offset_blocks_z =
-            (chunk_global_z * (minecraft::BLOCKS_IN_CHUNK as i32)) as Coord;
-
-        Position::new(
-            global_offset_blocks_x + relative_position.x,
-            relative_position.y,
-            global_offset_blocks_z + relative_position.z,
-        )
-    }
-
-    fn to_local_position(&self, global_position: Position) -> Position {
-        let (chunk_global_x, chunk_global_z) = self.position.get_global_position_in_chunks();
-        let global_offset_blocks_x =
-            (chunk_global_x * (minecraft::BLOCKS_IN_CHUNK as i32)) as Coord;
-        let global_offset_blocks_z =
-            (chunk_global_z * (minecraft::BLOCKS_IN_CHUNK as i32)) as Coord;
-
-        Position::new(
-            global_offset_blocks_x - global_position.x,
-            global_position.y,
-            global_offset_blocks_z - global_position.z,
-        )
-    }
-
     // Intersection is a rectangle local to the chunk - its origin is in chunk local coordinates
     // and the whole rectangle fits inside the chunk
     pub fn get_chunk_intersection_volume(
@@ -432,13 +562,46 @@ impl Chunk {
         volume
     }
 
-    pub fn get_material_blend(
+    // Same as get_chunk_intersection_volume, but partially intersected octree nodes not larger
+    // than *approximation_size* blocks are not visited block by block.
+    // Returns None if the octree was not built for this chunk.
+    pub fn get_chunk_intersection_volume_approx(
         &self,
         intersection_xz: Rectangle,
         y_low: Coord,
         y_high: Coord,
+        approximation_size: usize,
         material_setup: &MaterialSetup,
-    ) -> MaterialBlend {
+    ) -> Option<Real> {
+        let octree = self.octree.as_ref()?;
+
+        // The octree only covers the world, kernels reaching past its top or bottom
+        // are left to the exact evaluation which handles the boundary
+        if y_low < MIN_BLOCK_Y as Coord || y_high > MAX_BLOCK_Y as Coord {
+            return None;
+        }
+
+        let query = LocalBox {
+            min: [intersection_xz.left(), y_low, intersection_xz.bottom()],
+            max: [intersection_xz.right(), y_high, intersection_xz.top()],
+        };
+
+        Some(octree.smoothable_volume(query, approximation_size, material_setup))
+    }
+
+    pub fn build_octree(&mut self) {
+        self.octree = Some(ChunkOctree::build(&self.data));
+    }
+
+    // Adds the blocks of the kernel part within the chunk to *scan*, by the portion of them
+    // inside the kernel. Intersection is local to the chunk, see get_chunk_intersection_volume.
+    pub fn scan_kernel(
+        &self,
+        intersection_xz: Rectangle,
+        y_low: Coord,
+        y_high: Coord,
+        scan: &mut KernelScan,
+    ) {
         let intersection_start_index_x = get_block_coord(intersection_xz.left());
         let intersection_start_index_z = get_block_coord(intersection_xz.bottom());
 
@@ -455,29 +618,21 @@ impl Chunk {
         let intersection_range = (intersection_start_index_x..intersection_end_index_x)
             .cartesian_product(intersection_start_index_z..intersection_end_index_z);
 
-        let volume = intersection_range.fold(MaterialBlend::new(), move |mut blend, (x, z)| {
+        for (x, z) in intersection_range {
             let x_scale =
                 get_block_portion_in_range(x, intersection_xz.left(), intersection_xz.right());
             let z_scale =
                 get_block_portion_in_range(z, intersection_xz.bottom(), intersection_xz.top());
+            let biome = self.get_biome(x, z);
 
             let tower = self.get_tower(x, z);
-            for (y_scale, material) in tower
-                .iter_intersecting_blocks(y_low, y_high)
-                .filter(|(_, material)| material_setup.contributes_color(*material))
-            {
-                let block_intersection_size = x_scale * y_scale * z_scale;
-                blend.mix(material, block_intersection_size);
+            for (y_scale, material) in tower.iter_intersecting_blocks(y_low, y_high) {
+                scan.add(material, biome, x_scale * y_scale * z_scale);
             }
-
-            blend
-        });
-
-        volume
+        }
     }
 
-    pub fn get_bounding_rectangle(&self) -> Rectangle {
-        let position_in_world = self.position.get_global_position();
-        Rectangle::square(position_in_world, CHUNK_SIZE as Coord)
+    pub fn coords(&self) -> ChunkCoords {
+        self.position.coords()
     }
 }
@@ -0,0 +1,128 @@
+use crate::{
+    minecraft,
+    model::{rectangle::Rectangle, Coord, PlanarPosition, Position},
+};
+
+use super::ChunkPosition;
+
+// Transform between world coordinates and coordinates local to a chunk.
+//
+// Local coordinates have their origin in the corner of the chunk with the smallest x and z,
+// the y axis is shared by both spaces.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChunkCoords {
+    origin: PlanarPosition,
+}
+
+impl ChunkCoords {
+    pub fn new(position: ChunkPosition) -> Self {
+        let (chunk_x, chunk_z) = position.get_global_position_in_chunks();
+        let blocks_in_chunk = minecraft::BLOCKS_IN_CHUNK as i32;
+
+        ChunkCoords {
+            origin: PlanarPosition::new(
+                (chunk_x * blocks_in_chunk) as Coord,
+                (chunk_z * blocks_in_chunk) as Coord,
+            ),
+        }
+    }
+
+    // Position of the local origin in the world, x and z
+    pub fn origin(&self) -> PlanarPosition {
+        self.origin
+    }
+
+    // The area of the world covered by the chunk
+    pub fn bounds(&self) -> Rectangle {
+        Rectangle::square(self.origin, minecraft::BLOCKS_IN_CHUNK as Coord)
+    }
+
+    pub fn to_global(&self, local: Position) -> Position {
+        Position::new(local.x + self.origin.x, local.y, local.z + self.origin.y)
+    }
+
+    pub fn to_local(&self, global: Position) -> Position {
+        Position::new(global.x - self.origin.x, global.y, global.z - self.origin.y)
+    }
+
+    // Position of the block at the given local indices in the world
+    pub fn block_to_global(&self, x: usize, y: isize, z: usize) -> Position {
+        self.to_global(Position::new(x as Coord, y as Coord, z as Coord))
+    }
+
+    pub fn rectangle_to_local(&self, global: Rectangle) -> Rectangle {
+        global.offset_origin(self.origin.map(|coord| -coord))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chunk x -1, z -33: the last chunk of region (-1, -2)
+    fn negative_chunk() -> ChunkCoords {
+        ChunkPosition::from_global_position_in_chunks(-1, -33).coords()
+    }
+
+    #[test]
+    fn origin_is_the_chunk_corner() {
+        let position = ChunkPosition {
+            region_x: 1,
+            region_z: 0,
+            chunk_x: 2,
+            chunk_z: 3,
+        };
+
+        assert_eq!(position.coords().origin(), PlanarPosition::new(544.0, 48.0));
+        assert_eq!(
+            negative_chunk().origin(),
+            PlanarPosition::new(-16.0, -528.0)
+        );
+    }
+
+    #[test]
+    fn local_and_global_round_trip() {
+        let coords = negative_chunk();
+        let local = Position::new(3.5, -20.0, 15.25);
+
+        let global = coords.to_global(local);
+        assert_eq!(global, Position::new(-12.5, -20.0, -512.75));
+        assert_eq!(coords.to_local(global), local);
+    }
+
+    // Chunk::to_local_position used to subtract the global position from the chunk origin,
+    // negating the local coordinates of every position except the chunk corner
+    #[test]
+    fn global_positions_in_a_negative_chunk_are_local_to_it() {
+        let coords = negative_chunk();
+
+        let local = coords.to_local(Position::new(-1.0, 64.0, -513.0));
+        assert_eq!(local, Position::new(15.0, 64.0, 15.0));
+
+        let local = coords.to_local(Position::new(-16.0, 64.0, -528.0));
+        assert_eq!(local, Position::new(0.0, 64.0, 0.0));
+    }
+
+    #[test]
+    fn blocks_map_to_their_world_position() {
+        let coords = negative_chunk();
+
+        assert_eq!(
+            coords.block_to_global(0, -64, 15),
+            Position::new(-16.0, -64.0, -513.0)
+        );
+    }
+
+    #[test]
+    fn bounds_cover_the_chunk_and_convert_back_to_local() {
+        let coords = negative_chunk();
+        let bounds = coords.bounds();
+
+        assert_eq!((bounds.left(), bounds.right()), (-16.0, 0.0));
+        assert_eq!(bounds.origin.y, -528.0);
+
+        let local = coords.rectangle_to_local(bounds);
+        assert_eq!(local.origin, PlanarPosition::new(0.0, 0.0));
+        assert_eq!((local.width, local.height), (16.0, 16.0));
+    }
+}
@@ -1,7 +1,6 @@
-use crate::{
-    minecraft,
-    model::{Coord, PlanarPosition},
-};
+use crate::minecraft;
+
+use super::ChunkCoords;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ChunkPosition {
@@ -21,12 +20,20 @@ impl ChunkPosition {
         (global_x, global_z)
     }
 
-    pub fn get_global_position(&self) -> PlanarPosition {
-        let (chunk_x, chunk_z) = self.get_global_position_in_chunks();
-        PlanarPosition::new(
-            (chunk_x * minecraft::BLOCKS_IN_CHUNK as i32) as Coord,
-            (chunk_z * minecraft::BLOCKS_IN_CHUNK as i32) as Coord,
-        )
+    // Inverse of get_global_position_in_chunks
+    pub fn from_global_position_in_chunks(global_x: i32, global_z: i32) -> Self {
+        let chunks_in_region = minecraft::CHUNKS_IN_REGION as i32;
+
+        ChunkPosition {
+            region_x: global_x.div_euclid(chunks_in_region),
+            region_z: global_z.div_euclid(chunks_in_region),
+            chunk_x: global_x.rem_euclid(chunks_in_region) as usize,
+            chunk_z: global_z.rem_euclid(chunks_in_region) as usize,
+        }
+    }
+
+    pub fn coords(&self) -> ChunkCoords {
+        ChunkCoords::new(*self)
     }
 
     pub fn offset(&self, offset_x: i32, offset_z: i32) -> Self {
@@ -0,0 +1,103 @@
+use crate::minecraft::{MAX_BLOCK_Y, MIN_BLOCK_Y};
+
+const STACK_HEIGHT: usize = (MAX_BLOCK_Y - MIN_BLOCK_Y) as usize;
+
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+// Light stored in the save file for a single block
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LightLevel {
+    // Light emitted by nearby blocks (torches, lava, ...)
+    pub block: u8,
+
+    // Light coming from the sky
+    pub sky: u8,
+}
+
+impl LightLevel {
+    pub fn combined(&self) -> u8 {
+        self.block.max(self.sky)
+    }
+
+    pub fn max(self, other: LightLevel) -> LightLevel {
+        LightLevel {
+            block: self.block.max(other.block),
+            sky: self.sky.max(other.sky),
+        }
+    }
+
+    // Normalized [block, sky] levels as they are passed to the shaders
+    pub fn normalized(&self) -> [f32; 2] {
+        [
+            self.block as f32 / MAX_LIGHT_LEVEL as f32,
+            self.sky as f32 / MAX_LIGHT_LEVEL as f32,
+        ]
+    }
+
+    fn pack(&self) -> u8 {
+        (self.sky << 4) | (self.block & 0x0F)
+    }
+
+    fn unpack(packed: u8) -> Self {
+        LightLevel {
+            block: packed & 0x0F,
+            sky: packed >> 4,
+        }
+    }
+}
+
+// Contains light levels from y = -64 to y = 320 in ascending order
+// Both block and sky light are packed into a single byte.
+#[derive(Clone)]
+pub struct LightStack {
+    levels: Vec<u8>,
+}
+
+fn height_to_index(height: isize) -> usize {
+    (height - MIN_BLOCK_Y) as usize
+}
+
+impl LightStack {
+    pub fn new() -> Self {
+        // Sections without light data are almost always the empty sections above the terrain,
+        // so default to full sky light.
+        let default_level = LightLevel {
+            block: 0,
+            sky: MAX_LIGHT_LEVEL,
+        };
+
+        LightStack {
+            levels: vec![default_level.pack(); STACK_HEIGHT],
+        }
+    }
+
+    pub fn set_block_light(&mut self, y: isize, level: u8) {
+        let index = height_to_index(y);
+        let mut light = LightLevel::unpack(self.levels[index]);
+        light.block = level.min(MAX_LIGHT_LEVEL);
+        self.levels[index] = light.pack();
+    }
+
+    pub fn set_sky_light(&mut self, y: isize, level: u8) {
+        let index = height_to_index(y);
+        let mut light = LightLevel::unpack(self.levels[index]);
+        light.sky = level.min(MAX_LIGHT_LEVEL);
+        self.levels[index] = light.pack();
+    }
+
+    // Above the build limit there is only sky, below the world there is no light at all
+    pub fn get_light_at_y(&self, y: isize) -> LightLevel {
+        if y >= MAX_BLOCK_Y {
+            return LightLevel {
+                block: 0,
+                sky: MAX_LIGHT_LEVEL,
+            };
+        }
+
+        if y < MIN_BLOCK_Y {
+            return LightLevel::default();
+        }
+
+        LightLevel::unpack(self.levels[height_to_index(y)])
+    }
+}
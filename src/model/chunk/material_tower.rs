@@ -3,11 +3,19 @@ use crate::model::{
     Coord, Real,
 };
 
+use super::chunk::FNV_PRIME;
+
 const BLOCK_SIZE: Real = 1.0;
 const STACK_HEIGHT: usize = 384;
 const NEGATIVE_HEIGHT_PART: isize = 64;
 
+// Kernels reaching past the ends of the stack see solid ground below the bottom of the world
+// and air above the build limit, so the bottom of the world doesn't turn into a surface
+const BELOW_WORLD_BLOCK: BlockType = BlockType::Stone;
+const ABOVE_WORLD_BLOCK: BlockType = BlockType::Air;
+
 // Contains blocks from y = -64 to y = 320 in ascending order
+#[derive(Clone)]
 pub struct MaterialStack {
     blocks: Vec<BlockType>,
 }
@@ -34,6 +42,16 @@ impl MaterialStack {
         self.blocks[stack_index] = material;
     }
 
+    // Block at *height* as seen by the density evaluation,
+    // heights outside of the stack follow the boundary conditions
+    fn block_at(&self, height: isize) -> BlockType {
+        match height {
+            height if height < index_to_height(0) => BELOW_WORLD_BLOCK,
+            height if height >= index_to_height(STACK_HEIGHT) => ABOVE_WORLD_BLOCK,
+            height => self.blocks[height_to_index(height)],
+        }
+    }
+
     pub fn get_intersection_size(
         &self,
         y_low: Coord,
@@ -42,11 +60,11 @@ impl MaterialStack {
     ) -> Real {
         let low_floor = y_low.floor();
         let high_ceil = y_high.ceil();
-        let low_index = height_to_index(low_floor as isize);
-        let high_index = height_to_index(high_ceil as isize);
+        let low_height = low_floor as isize;
+        let high_height = high_ceil as isize;
 
-        let blocks_in_range = (low_index..high_index)
-            .map(|i| self.blocks[i])
+        let blocks_in_range = (low_height..high_height)
+            .map(|height| self.block_at(height))
             .filter(|material| material_setup.is_material_smoothable(*material))
             .count();
 
@@ -55,7 +73,7 @@ impl MaterialStack {
         }
 
         let excess_low = {
-            let cutoff = material_setup.is_material_smoothable(self.blocks[low_index]);
+            let cutoff = material_setup.is_material_smoothable(self.block_at(low_height));
             //let cutoff = !rigid_set.contains(&self.blocks[low_index]); //is_smoothable_block(self.blocks[low_index]);
             match cutoff {
                 true => (y_low - low_floor) as Real,
@@ -63,7 +81,7 @@ impl MaterialStack {
             }
         };
         let excess_high = {
-            let cutoff = material_setup.is_material_smoothable(self.blocks[high_index - 1]);
+            let cutoff = material_setup.is_material_smoothable(self.block_at(high_height - 1));
             //let cutoff = !rigid_set.contains(&self.blocks[high_index - 1]);
             match cutoff {
                 true => (high_ceil - y_high) as Real,
@@ -84,27 +102,18 @@ impl MaterialStack {
     ) -> impl Iterator<Item = (Real, BlockType)> + '_ {
         let low_floor = y_low.floor();
         let high_ceil = y_high.ceil();
-        let low_index = height_to_index(low_floor as isize);
-        let high_index = height_to_index(high_ceil as isize);
+        let low_height = low_floor as isize;
+        let high_height = high_ceil as isize;
 
-        let intersecting_blocks = (low_index..high_index)
-            .map(|i| (index_to_height(i), self.blocks[i].clone()))
+        let intersecting_blocks = (low_height..high_height)
+            .map(|height| (height, self.block_at(height)))
             .filter(|(_, material)| is_visible_block(*material));
 
         let blocks_with_intersection_size =
             intersecting_blocks.map(move |(base_height, material)| {
                 let base_height = base_height as Coord;
-                let lower_cutoff = if base_height < y_low {
-                    y_low - base_height
-                } else {
-                    0.0
-                };
-
-                let upper_cutoff = if y_high - base_height < BLOCK_SIZE {
-                    y_high - base_height
-                } else {
-                    0.0
-                };
+                let lower_cutoff = (y_low - base_height).max(0.0);
+                let upper_cutoff = (base_height + BLOCK_SIZE - y_high).max(0.0);
 
                 let intersection_size = BLOCK_SIZE - lower_cutoff - upper_cutoff;
 
@@ -114,6 +123,13 @@ impl MaterialStack {
         blocks_with_intersection_size
     }
 
+    // Continues an FNV-1a hash over the blocks of the stack
+    pub fn hash_into(&self, hash: u64) -> u64 {
+        self.blocks.iter().fold(hash, |hash, block| {
+            (hash ^ *block as u64).wrapping_mul(FNV_PRIME)
+        })
+    }
+
     pub fn iter_visible_blocks(&self) -> impl Iterator<Item = (isize, BlockType)> + '_ {
         self.blocks
             .iter()
@@ -129,8 +145,9 @@ impl MaterialStack {
     ) -> impl Iterator<Item = (isize, BlockType)> + '_ {
         let low_floor = y_low.floor();
         let high_ceil = y_high.ceil();
-        let low_index = height_to_index(low_floor as isize);
-        let high_index = height_to_index(high_ceil as isize);
+        // Only blocks that exist in the world, nothing rigid lies beyond its bounds
+        let low_index = height_to_index((low_floor as isize).max(index_to_height(0)));
+        let high_index = height_to_index((high_ceil as isize).min(index_to_height(STACK_HEIGHT)));
 
         let intersecting_blocks =
             (low_index..high_index).map(|i| (index_to_height(i), self.blocks[i].clone()));
@@ -138,8 +155,128 @@ impl MaterialStack {
         intersecting_blocks
     }
 
+    // Height of the topmost visible block, None for columns made only of air
+    pub fn get_top_visible_height(&self) -> Option<isize> {
+        self.blocks
+            .iter()
+            .rposition(|material| is_visible_block(*material))
+            .map(index_to_height)
+    }
+
+    // There are no blocks outside of the world, heights above or below it are Air
     pub fn get_block_at_y(&self, y: isize) -> BlockType {
-        let block_index = height_to_index(y);
-        self.blocks[block_index]
+        match y {
+            y if y < index_to_height(0) || y >= index_to_height(STACK_HEIGHT) => BlockType::Air,
+            y => self.blocks[height_to_index(y)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOTTOM: isize = -64;
+    const TOP: isize = 319;
+
+    fn smooth_everything() -> MaterialSetup {
+        MaterialSetup::all_smooth([])
+    }
+
+    // Stone at the lowest height of the stack and dirt at the highest, air in between
+    fn stack_with_ends() -> MaterialStack {
+        let mut stack = MaterialStack::new();
+        stack.insert(BlockType::Stone, BOTTOM);
+        stack.insert(BlockType::Dirt, TOP);
+        stack
+    }
+
+    fn assert_close(actual: Real, expected: Real) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn kernel_below_the_world_intersects_solid_ground() {
+        let stack = MaterialStack::new();
+
+        // -66.5 to -64 lies below the world, the rest of the kernel is air
+        let size = stack.get_intersection_size(-66.5, -62.5, &smooth_everything());
+        assert_close(size, 2.5);
+
+        let blocks: Vec<_> = stack.iter_intersecting_blocks(-65.5, -63.5).collect();
+        assert_eq!(blocks, [(0.5, BELOW_WORLD_BLOCK), (1.0, BELOW_WORLD_BLOCK)]);
+    }
+
+    #[test]
+    fn kernel_straddling_the_bottom_row_joins_it_with_the_ground_below() {
+        let stack = stack_with_ends();
+
+        let size = stack.get_intersection_size(-65.25, -63.5, &smooth_everything());
+        assert_close(size, 1.75);
+
+        let blocks: Vec<_> = stack.iter_intersecting_blocks(-64.5, -62.0).collect();
+        assert_eq!(blocks, [(0.5, BELOW_WORLD_BLOCK), (1.0, BlockType::Stone)]);
+    }
+
+    #[test]
+    fn kernel_above_the_build_limit_intersects_air() {
+        let stack = stack_with_ends();
+
+        // Only the top row, from 319.5 to 320, is solid
+        let size = stack.get_intersection_size(319.5, 323.0, &smooth_everything());
+        assert_close(size, 0.5);
+
+        let blocks: Vec<_> = stack.iter_intersecting_blocks(318.75, 322.0).collect();
+        assert_eq!(blocks, [(1.0, BlockType::Dirt)]);
+
+        assert_eq!(
+            stack.get_intersection_size(320.0, 324.0, &smooth_everything()),
+            0.0
+        );
+        assert_eq!(stack.iter_intersecting_blocks(320.0, 324.0).count(), 0);
+    }
+
+    #[test]
+    fn ground_below_the_world_follows_the_material_setup() {
+        let stack = MaterialStack::new();
+        let rigid_ground = MaterialSetup::all_smooth([BELOW_WORLD_BLOCK]);
+
+        assert_eq!(
+            stack.get_intersection_size(-67.0, -63.0, &rigid_ground),
+            0.0
+        );
+    }
+
+    #[test]
+    fn boundary_rows_hold_their_blocks() {
+        let stack = stack_with_ends();
+
+        assert_eq!(stack.get_block_at_y(BOTTOM), BlockType::Stone);
+        assert_eq!(stack.get_block_at_y(BOTTOM + 1), BlockType::Air);
+        assert_eq!(stack.get_block_at_y(TOP - 1), BlockType::Air);
+        assert_eq!(stack.get_block_at_y(TOP), BlockType::Dirt);
+    }
+
+    #[test]
+    fn heights_above_the_build_limit_are_air() {
+        let stack = stack_with_ends();
+
+        for y in [TOP + 1, TOP + 2, 1000, isize::MAX] {
+            assert_eq!(stack.get_block_at_y(y), BlockType::Air, "y = {y}");
+        }
+    }
+
+    // Unlike the density evaluation, which sees solid ground there
+    #[test]
+    fn heights_below_the_world_are_air() {
+        let stack = stack_with_ends();
+
+        for y in [BOTTOM - 1, BOTTOM - 2, -1000, isize::MIN] {
+            assert_eq!(stack.get_block_at_y(y), BlockType::Air, "y = {y}");
+        }
+        assert_eq!(stack.block_at(BOTTOM - 1), BELOW_WORLD_BLOCK);
     }
 }
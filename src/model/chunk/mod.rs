@@ -1,7 +1,14 @@
 pub mod chunk;
+mod chunk_coords;
 pub mod chunk_position;
+mod light_tower;
 mod material_tower;
+mod octree;
+mod sign;
 
 pub use chunk::BlockData;
 pub use chunk::Chunk;
+pub use chunk_coords::ChunkCoords;
 pub use chunk_position::ChunkPosition;
+pub use light_tower::LightLevel;
+pub use sign::Sign;
@@ -0,0 +1,261 @@
+use array_init::array_init;
+
+use crate::minecraft::{BLOCKS_IN_CHUNK, MAX_BLOCK_Y, MIN_BLOCK_Y};
+use crate::model::common::{BlockType, MaterialSetup, BLOCK_TYPES};
+use crate::model::{Coord, Real};
+
+use super::material_tower::MaterialStack;
+
+// Octrees are built for each cubic section of the chunk, same as minecraft does
+const SECTION_SIZE: usize = BLOCKS_IN_CHUNK;
+const SECTION_COUNT: usize = (MAX_BLOCK_Y - MIN_BLOCK_Y) as usize / SECTION_SIZE;
+
+const EPSILON: Real = 0.0001;
+
+// Number of blocks of each material within an octree node.
+// A section has 4096 blocks so the counts fit into 16 bits.
+#[derive(Clone)]
+struct MaterialHistogram {
+    counts: [u16; BLOCK_TYPES],
+}
+
+impl MaterialHistogram {
+    fn new() -> Self {
+        MaterialHistogram {
+            counts: [0; BLOCK_TYPES],
+        }
+    }
+
+    fn add(&mut self, material: BlockType, count: u16) {
+        self.counts[material as usize] += count;
+    }
+
+    fn merge(&mut self, other: &MaterialHistogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+    }
+
+    fn smoothable_count(&self, material_setup: &MaterialSetup) -> u32 {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .filter(|(index, _)| material_setup.is_material_smoothable(material_at(*index)))
+            .map(|(_, count)| *count as u32)
+            .sum()
+    }
+}
+
+// Air and Unknown can't be converted from an index, see BlockType::try_from
+fn material_at(index: usize) -> BlockType {
+    match index {
+        x if x == BlockType::Air as usize => BlockType::Air,
+        x => BlockType::try_from(x).unwrap_or(BlockType::Unknown),
+    }
+}
+
+// Nodes made of a single material are not subdivided any further,
+// so large areas of air or stone are represented by a single node.
+#[derive(Clone)]
+enum OctreeNode {
+    Uniform(BlockType),
+    Mixed {
+        histogram: Box<MaterialHistogram>,
+        // Child i covers the octant with offset (i & 1, (i >> 1) & 1, (i >> 2) & 1)
+        children: Box<[OctreeNode; 8]>,
+    },
+}
+
+// Axis aligned box in chunk local coordinates
+#[derive(Clone, Copy)]
+pub struct LocalBox {
+    pub min: [Coord; 3],
+    pub max: [Coord; 3],
+}
+
+impl LocalBox {
+    fn overlap_volume(&self, origin: [usize; 3], size: usize) -> Real {
+        (0..3)
+            .map(|axis| {
+                let node_min = origin[axis] as Coord;
+                let node_max = node_min + size as Coord;
+                let overlap = self.max[axis].min(node_max) - self.min[axis].max(node_min);
+
+                overlap.max(0.0) as Real
+            })
+            .product()
+    }
+}
+
+impl OctreeNode {
+    // Origin is in section local block coordinates
+    fn build(
+        stacks: &[MaterialStack],
+        section_base: isize,
+        origin: [usize; 3],
+        size: usize,
+    ) -> Self {
+        if size == 1 {
+            let [x, y, z] = origin;
+            let material =
+                stacks[z * BLOCKS_IN_CHUNK + x].get_block_at_y(section_base + y as isize);
+            return OctreeNode::Uniform(material);
+        }
+
+        let half = size / 2;
+        let children: [OctreeNode; 8] = array_init(|i| {
+            let child_origin = [
+                origin[0] + (i & 1) * half,
+                origin[1] + ((i >> 1) & 1) * half,
+                origin[2] + ((i >> 2) & 1) * half,
+            ];
+            OctreeNode::build(stacks, section_base, child_origin, half)
+        });
+
+        if let OctreeNode::Uniform(first) = children[0] {
+            let all_same = children
+                .iter()
+                .all(|child| matches!(child, OctreeNode::Uniform(material) if *material == first));
+            if all_same {
+                return OctreeNode::Uniform(first);
+            }
+        }
+
+        let child_volume = (half * half * half) as u16;
+        let mut histogram = MaterialHistogram::new();
+        for child in children.iter() {
+            match child {
+                OctreeNode::Uniform(material) => histogram.add(*material, child_volume),
+                OctreeNode::Mixed {
+                    histogram: child_histogram,
+                    ..
+                } => histogram.merge(child_histogram),
+            }
+        }
+
+        OctreeNode::Mixed {
+            histogram: Box::new(histogram),
+            children: Box::new(children),
+        }
+    }
+
+    // Volume of smoothable blocks inside the query box.
+    //
+    // Nodes that are cut by the box and are not larger than *approximation_size*
+    // are not descended into, the smoothable blocks are assumed to be spread evenly
+    // across the node instead.
+    fn smoothable_volume(
+        &self,
+        origin: [usize; 3],
+        size: usize,
+        query: &LocalBox,
+        approximation_size: usize,
+        material_setup: &MaterialSetup,
+    ) -> Real {
+        let overlap = query.overlap_volume(origin, size);
+        if overlap < EPSILON {
+            return 0.0;
+        }
+
+        match self {
+            OctreeNode::Uniform(material) => match material_setup.is_material_smoothable(*material)
+            {
+                true => overlap,
+                false => 0.0,
+            },
+            OctreeNode::Mixed {
+                histogram,
+                children,
+            } => {
+                let node_volume = (size * size * size) as Real;
+                let fully_inside = overlap > node_volume - EPSILON;
+                if fully_inside || size <= approximation_size {
+                    let smoothable = histogram.smoothable_count(material_setup) as Real;
+                    return smoothable * overlap / node_volume;
+                }
+
+                let half = size / 2;
+                children
+                    .iter()
+                    .enumerate()
+                    .map(|(i, child)| {
+                        let child_origin = [
+                            origin[0] + (i & 1) * half,
+                            origin[1] + ((i >> 1) & 1) * half,
+                            origin[2] + ((i >> 2) & 1) * half,
+                        ];
+                        child.smoothable_volume(
+                            child_origin,
+                            half,
+                            query,
+                            approximation_size,
+                            material_setup,
+                        )
+                    })
+                    .sum()
+            }
+        }
+    }
+}
+
+// Sparse voxel octree of a chunk with a root node for every section
+#[derive(Clone)]
+pub struct ChunkOctree {
+    sections: Vec<OctreeNode>,
+}
+
+impl ChunkOctree {
+    pub fn build(stacks: &[MaterialStack]) -> Self {
+        let sections = (0..SECTION_COUNT)
+            .map(|section| {
+                let section_base = MIN_BLOCK_Y + (section * SECTION_SIZE) as isize;
+                OctreeNode::build(stacks, section_base, [0, 0, 0], SECTION_SIZE)
+            })
+            .collect();
+
+        ChunkOctree { sections }
+    }
+
+    // Query box uses chunk local x and z, y is the world height
+    pub fn smoothable_volume(
+        &self,
+        query: LocalBox,
+        approximation_size: usize,
+        material_setup: &MaterialSetup,
+    ) -> Real {
+        let first_section = section_index(query.min[1]);
+        let last_section = section_index(query.max[1] - EPSILON);
+
+        (first_section..=last_section)
+            .map(|section| {
+                let section_base = MIN_BLOCK_Y + (section * SECTION_SIZE) as isize;
+                let section_query = LocalBox {
+                    min: [
+                        query.min[0],
+                        query.min[1] - section_base as Coord,
+                        query.min[2],
+                    ],
+                    max: [
+                        query.max[0],
+                        query.max[1] - section_base as Coord,
+                        query.max[2],
+                    ],
+                };
+
+                self.sections[section].smoothable_volume(
+                    [0, 0, 0],
+                    SECTION_SIZE,
+                    &section_query,
+                    approximation_size,
+                    material_setup,
+                )
+            })
+            .sum()
+    }
+}
+
+fn section_index(y: Coord) -> usize {
+    let section = (y - MIN_BLOCK_Y as Coord).floor() as isize / SECTION_SIZE as isize;
+    section.clamp(0, SECTION_COUNT as isize - 1) as usize
+}
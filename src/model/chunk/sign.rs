@@ -0,0 +1,9 @@
+use crate::model::Position;
+
+// Text of a sign placed in the world, lines are separated by newlines
+#[derive(Clone, Debug)]
+pub struct Sign {
+    // Center of the sign block
+    pub position: Position,
+    pub text: String,
+}
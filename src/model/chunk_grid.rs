@@ -0,0 +1,100 @@
+use cgmath::Vector2;
+
+use crate::minecraft::BLOCKS_IN_CHUNK;
+
+use super::chunk::Chunk;
+use super::{Coord, PlanarPosition, Position, Real};
+
+const LAST_COLUMN: usize = BLOCKS_IN_CHUNK - 1;
+
+// Lines following the terrain are raised a bit so they don't z-fight with the top faces
+const SURFACE_OFFSET: Real = 0.05;
+
+// Line segments, as pairs of points, outlining the borders of the chunk.
+//
+// The lines follow the top of the border columns, or lie in the plane y = *plane_height*
+// when it is given.
+pub fn chunk_grid_lines(chunk: &Chunk, plane_height: Option<Real>) -> Vec<Position> {
+    let heights = chunk.get_height_map();
+    let column_top = |x: usize, z: usize| match plane_height {
+        Some(height) => height,
+        None => (heights[z * BLOCKS_IN_CHUNK + x] + 1) as Real + SURFACE_OFFSET,
+    };
+
+    let origin = chunk.coords().origin();
+    let size = BLOCKS_IN_CHUNK as Coord;
+
+    // Column at the i-th step along the side, where the side starts and the direction along it
+    let sides: [(fn(usize) -> (usize, usize), PlanarPosition, Vector2<Coord>); 4] = [
+        (|i| (0, i), origin, Vector2::unit_y()),
+        (
+            |i| (LAST_COLUMN, i),
+            origin + Vector2::new(size, 0.0),
+            Vector2::unit_y(),
+        ),
+        (|i| (i, 0), origin, Vector2::unit_x()),
+        (
+            |i| (i, LAST_COLUMN),
+            origin + Vector2::new(0.0, size),
+            Vector2::unit_x(),
+        ),
+    ];
+
+    let mut lines = Vec::new();
+    for (column, start, step) in sides {
+        let mut previous_height = None;
+        for i in 0..BLOCKS_IN_CHUNK {
+            let (x, z) = column(i);
+            let height = column_top(x, z);
+            let from = start + step * i as Coord;
+            let to = from + step;
+
+            // Connect steps in the terrain with a vertical line
+            if let Some(previous_height) = previous_height.filter(|previous| *previous != height) {
+                lines.push(Position::new(from.x, previous_height, from.y));
+                lines.push(Position::new(from.x, height, from.y));
+            }
+
+            lines.push(Position::new(from.x, height, from.y));
+            lines.push(Position::new(to.x, height, to.y));
+            previous_height = Some(height);
+        }
+    }
+
+    lines
+}
+
+// How far the boundary walls reach above the highest column of the generated chunk
+const BOUNDARY_WALL_HEADROOM: Real = 8.0;
+
+// Corners of a wall on the border between two neighbouring chunks, where *generated* has data
+// in the save and *missing* was never generated. The wall covers the columns of the generated chunk
+pub fn generated_boundary_wall(generated: &Chunk, missing: &Chunk) -> [Position; 4] {
+    let heights = generated.get_height_map();
+    let bottom = *heights.iter().min().unwrap() as Real;
+    let top = (*heights.iter().max().unwrap() + 1) as Real + BOUNDARY_WALL_HEADROOM;
+
+    let generated_origin = generated.coords().origin();
+    let missing_origin = missing.coords().origin();
+    let size = BLOCKS_IN_CHUNK as Coord;
+
+    // The chunks share the side at the larger origin of the axis they are neighbours along
+    let (start, step) = match generated_origin.x != missing_origin.x {
+        true => (
+            PlanarPosition::new(generated_origin.x.max(missing_origin.x), generated_origin.y),
+            Vector2::unit_y(),
+        ),
+        false => (
+            PlanarPosition::new(generated_origin.x, generated_origin.y.max(missing_origin.y)),
+            Vector2::unit_x(),
+        ),
+    };
+    let end = start + step * size;
+
+    [
+        Position::new(start.x, bottom, start.y),
+        Position::new(end.x, bottom, end.y),
+        Position::new(end.x, top, end.y),
+        Position::new(start.x, top, start.y),
+    ]
+}
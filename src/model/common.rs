@@ -1,7 +1,5 @@
 use std::collections::HashSet;
 
-use super::Real;
-
 // Note: Unknown must always be the last variant,
 // or at least the variant with the largest value.
 //
@@ -55,41 +53,25 @@ impl TryFrom<usize> for BlockType {
 
 pub const BLOCK_TYPES: usize = (BlockType::Unknown as usize) + 1;
 
-const PALLETTE_SIZE: usize = 4;
-pub const BLOCK_TEXTURE_FRACTION: f32 = 1.0 / (PALLETTE_SIZE as f32);
-pub fn get_pallette_texture_coords(block_type: BlockType) -> [f32; 2] {
-    let (x_offset, y_offset) = match block_type {
-        BlockType::Grass => (0, 3),
-        BlockType::Dirt => (0, 2),
-        BlockType::Stone => (1, 3),
-        BlockType::Sand => (2, 3),
-        BlockType::Ore => (3, 3),
-        BlockType::Wood => (1, 2),
-        BlockType::Leaves => (2, 2),
-        BlockType::Water => (3, 2),
-        BlockType::Lava => (0, 1),
-        BlockType::Planks => (1, 1),
-        BlockType::DarkStone => (2, 1),
-        BlockType::RedSand => (3, 1),
-        BlockType::Cobblestone => (0, 0),
-        BlockType::Glass => (1, 0),
-        BlockType::Cactus => (2, 0),
-        _ => (3, 0),
-    };
-
-    [
-        (x_offset as f32) * BLOCK_TEXTURE_FRACTION,
-        (y_offset as f32) * BLOCK_TEXTURE_FRACTION,
-    ]
-}
-
-pub fn activation_treshold(block_type: BlockType) -> Real {
-    match block_type {
-        BlockType::Dirt => 0.45,
-        BlockType::Leaves => 0.9,
-        _ => 0.0, // always activate
-    }
-}
+pub const ALL_BLOCK_TYPES: [BlockType; BLOCK_TYPES] = [
+    BlockType::Air,
+    BlockType::Dirt,
+    BlockType::Grass,
+    BlockType::Stone,
+    BlockType::Wood,
+    BlockType::Leaves,
+    BlockType::Sand,
+    BlockType::Ore,
+    BlockType::Water,
+    BlockType::Lava,
+    BlockType::Planks,
+    BlockType::DarkStone,
+    BlockType::RedSand,
+    BlockType::Cobblestone,
+    BlockType::Glass,
+    BlockType::Cactus,
+    BlockType::Unknown,
+];
 
 pub fn is_visible_block(material: BlockType) -> bool {
     !matches!(material, BlockType::Air)
@@ -105,39 +87,56 @@ pub struct MaterialSetup {
     smoothable_materials: HashSet<BlockType>,
     rigid_materials: HashSet<BlockType>,
     op: MaterialOperation,
+    // Rigid blocks only count next to a smoothed block, see Chunk::is_rigid_block_allowed
+    filter_rigid: bool,
 }
 
 impl MaterialSetup {
-    pub fn include<const S: usize, const R: usize>(
-        included: [BlockType; S],
-        rigid: [BlockType; R],
+    pub fn include(
+        included: impl IntoIterator<Item = BlockType>,
+        rigid: impl IntoIterator<Item = BlockType>,
     ) -> Self {
         MaterialSetup {
-            smoothable_materials: HashSet::from(included),
-            rigid_materials: HashSet::from(rigid),
+            smoothable_materials: HashSet::from_iter(included),
+            rigid_materials: HashSet::from_iter(rigid),
             op: MaterialOperation::Include,
+            filter_rigid: true,
         }
     }
 
-    pub fn exclude<const S: usize, const R: usize>(
-        excluded: [BlockType; S],
-        rigid: [BlockType; R],
+    pub fn exclude(
+        excluded: impl IntoIterator<Item = BlockType>,
+        rigid: impl IntoIterator<Item = BlockType>,
     ) -> Self {
         MaterialSetup {
-            smoothable_materials: HashSet::from(excluded),
-            rigid_materials: HashSet::from(rigid),
+            smoothable_materials: HashSet::from_iter(excluded),
+            rigid_materials: HashSet::from_iter(rigid),
             op: MaterialOperation::Exclude,
+            filter_rigid: true,
         }
     }
 
-    pub fn all_smooth<const R: usize>(rigid: [BlockType; R]) -> Self {
+    pub fn all_smooth(rigid: impl IntoIterator<Item = BlockType>) -> Self {
         MaterialSetup {
             smoothable_materials: HashSet::new(),
-            rigid_materials: HashSet::from(rigid),
+            rigid_materials: HashSet::from_iter(rigid),
             op: MaterialOperation::Exclude,
+            filter_rigid: true,
         }
     }
 
+    // Taken from the settings snapshot of the build, see PolygonizationOptions::settings
+    pub fn filtering_rigid(self, filter_rigid: bool) -> Self {
+        MaterialSetup {
+            filter_rigid,
+            ..self
+        }
+    }
+
+    pub fn filters_rigid(&self) -> bool {
+        self.filter_rigid
+    }
+
     pub fn is_material_smoothable(&self, material: BlockType) -> bool {
         let possibly_smoothable =
             !matches!(material, BlockType::Air) && !self.rigid_materials.contains(&material);
@@ -172,6 +171,7 @@ impl MaterialSetup {
     }
 }
 
+// Rigid by default, the material registry decides which materials are rigid at runtime
 pub const RIGID_MATERIALS: [BlockType; 6] = [
     BlockType::Wood,
     BlockType::Cobblestone,
@@ -181,12 +181,9 @@ pub const RIGID_MATERIALS: [BlockType; 6] = [
     BlockType::Unknown,
 ];
 
-//const RIGID_MATERIALS: u16 = 0b1110010000010000;
-pub fn is_rigid_block(material: BlockType) -> bool {
-    //let material_index = material as u16;
+// Drawn see-through when they are rigid, after everything opaque
+pub const TRANSLUCENT_MATERIALS: [BlockType; 2] = [BlockType::Glass, BlockType::Water];
 
-    //let bit_value = 1 << material_index;
-    //(RIGID_MATERIALS & bit_value) != 0
-    //matches!(material, BlockType::Wood)
-    RIGID_MATERIALS.contains(&material)
+pub fn is_translucent_block(material: BlockType) -> bool {
+    TRANSLUCENT_MATERIALS.contains(&material)
 }
@@ -1,49 +1,233 @@
-use array_init::array_init;
+use cgmath::{EuclideanSpace, MetricSpace, Point3, Vector3};
+use image::ImageResult;
 use itertools;
 use itertools::Itertools;
 use lazy_init::Lazy;
 use rayon::prelude::*;
 use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::{Deref, DerefMut, Range};
+use std::path::Path;
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::SendError;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
-use std::thread;
-use std::thread::JoinHandle;
+use std::time::Instant;
 
 use crate::config;
-use crate::config::WORLD_SIZE;
 use crate::get_minecraft_chunk_position;
-use crate::minecraft;
-use crate::model::implicit::smooth::polygonize_chunk;
+use crate::infrastructure::event_bus::AppEvent;
+use crate::infrastructure::jobs::{self, JobHandle, Priority};
+use crate::minecraft::{self, MAX_BLOCK_Y, MIN_BLOCK_Y};
+use crate::model::implicit::density_memo::MemoStats;
+use crate::model::implicit::density_volume::DensityVolume;
+use crate::model::implicit::smooth::{polygonize_chunk_with_density, BuildCost};
+use crate::settings;
+use crate::shutdown::Shutdown;
 use crate::time_it;
 
-use super::chunk::{BlockData, Chunk, ChunkPosition};
-use super::common::BlockType;
-use super::polygonize::Mesh;
+use super::chunk::chunk::FNV_OFFSET_BASIS;
+use super::chunk::{BlockData, Chunk, ChunkPosition, LightLevel, Sign};
+use super::chunk_grid::{chunk_grid_lines, generated_boundary_wall};
+use super::common::{BlockType, ALL_BLOCK_TYPES, BLOCK_TYPES, TRANSLUCENT_MATERIALS};
+use super::export::{self, MeshFormat};
+use super::material_registry;
+use super::mesh_cache::{self, MeshCache};
+use super::navmesh::NavMesh;
 use super::polygonize::PolygonizationOptions;
-use super::Position;
+use super::polygonize::{ChunkLod, ChunkSide, Mesh};
+use super::rectangle::Rectangle;
+use super::splat_map::SplatMap;
+use super::terrain_metrics::ChunkHeightMetrics;
+use super::{Coord, PlanarPosition, Position, Real};
+
+// Square window of chunks stored row by row, rows are parallel to the world x axis.
+// Its width is picked at runtime, see World::resize, so it is kept along with the chunks.
+#[derive(Clone)]
+pub struct WorldChunks {
+    chunks: Vec<Arc<Chunk>>,
+    size: usize,
+}
+
+impl WorldChunks {
+    // Window *size* chunks wide, *create_chunk* gets the x and z of each chunk in the window
+    pub fn from_fn(size: usize, mut create_chunk: impl FnMut(usize, usize) -> Arc<Chunk>) -> Self {
+        let chunks = (0..size * size)
+            .map(|index| create_chunk(index % size, index / size))
+            .collect();
 
-const CHUNKS_IN_WORLD: usize = WORLD_SIZE * WORLD_SIZE;
+        WorldChunks { chunks, size }
+    }
 
-pub type WorldChunks = [Arc<Chunk>; CHUNKS_IN_WORLD];
+    // Number of chunks along a side of the window
+    pub fn size(&self) -> usize {
+        self.size
+    }
 
-fn clone_world(chunks: &WorldChunks) -> WorldChunks {
-    let clone = array_init(|index| chunks[index].clone());
-    clone
+    pub fn chunk_index(&self, x: usize, z: usize) -> usize {
+        z * self.size + x
+    }
 }
 
-// A mesh of a chunk located at *ChunkPosition*
-struct BoundMesh(Mesh, ChunkPosition);
+impl Deref for WorldChunks {
+    type Target = [Arc<Chunk>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.chunks
+    }
+}
+
+impl DerefMut for WorldChunks {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.chunks
+    }
+}
+
+// A mesh of a chunk located at *ChunkPosition* built with *ChunkLod*
+// along with the cost of building the mesh and the build generation it belongs to
+struct BoundMesh(Mesh, ChunkPosition, ChunkLod, BuildCost, usize);
+
+// Density of the chunk at *ChunkPosition* and the build generation it was baked in
+struct BakedDensity(DensityVolume, ChunkPosition, usize);
 
 enum ChunkSource {
-    Direct(Chunk),
+    // The chunk at the position gets loaded by a chunk loading job
+    Pending(ChunkPosition),
     Reference(usize),
 }
 
 struct ChunkChange(usize, ChunkSource);
-struct WorldChange(ChunkPosition, JoinHandle<Vec<ChunkChange>>);
+
+// Instance data of all the blocks in the loaded world
+pub struct InstanceData {
+    pub surface_blocks: Vec<BlockData>,
+    pub rigid_blocks: Vec<BlockData>,
+    pub rigid_ranges: MaterialRanges,
+}
+
+// Where the blocks of each material are in an instance buffer sorted by material
+#[derive(Clone)]
+pub struct MaterialRanges {
+    // The blocks of material m are at offsets[m]..offsets[m + 1]
+    offsets: [usize; BLOCK_TYPES + 1],
+    clusters: Vec<InstanceCluster>,
+}
+
+// Blocks of a translucent material in a single chunk, the clusters are sorted by their distance
+// to the camera so translucent blocks can be drawn back to front without touching the buffer
+#[derive(Clone)]
+struct InstanceCluster {
+    material: BlockType,
+    range: Range<usize>,
+    center: Position,
+}
+
+impl MaterialRanges {
+    // Sorts *blocks* by material and records where each material starts,
+    // the blocks come with the index of their chunk and stay in chunk order within a material
+    fn sort(mut blocks: Vec<(BlockType, usize, BlockData)>) -> (Vec<BlockData>, Self) {
+        blocks.par_sort_by_key(|(material, _, _)| *material as usize);
+
+        let mut offsets = [0; BLOCK_TYPES + 1];
+        for (material, _, _) in &blocks {
+            offsets[*material as usize + 1] += 1;
+        }
+        for material in 0..BLOCK_TYPES {
+            offsets[material + 1] += offsets[material];
+        }
+
+        let mut clusters = Vec::new();
+        for material in TRANSLUCENT_MATERIALS {
+            let range = offsets[material as usize]..offsets[material as usize + 1];
+            let mut start = range.start;
+            for end in range.start + 1..=range.end {
+                if end < range.end && blocks[end].1 == blocks[start].1 {
+                    continue;
+                }
+
+                let center = blocks[start..end]
+                    .iter()
+                    .map(|(_, _, block)| Vector3::from(block.offset).cast::<Coord>().unwrap())
+                    .sum::<Vector3<Coord>>()
+                    / (end - start) as Coord;
+                clusters.push(InstanceCluster {
+                    material,
+                    range: start..end,
+                    center: Position::from_vec(center.map(|coordinate| coordinate + 0.5)),
+                });
+                start = end;
+            }
+        }
+
+        let blocks = blocks.into_iter().map(|(_, _, block)| block).collect();
+        (blocks, MaterialRanges { offsets, clusters })
+    }
+
+    pub fn range(&self, material: BlockType) -> Range<usize> {
+        let material = material as usize;
+        self.offsets[material]..self.offsets[material + 1]
+    }
+
+    // Ranges of the materials for which *shown* is true,
+    // neighbouring ranges are merged so they can be drawn at once
+    pub fn visible(&self, shown: impl Fn(BlockType) -> bool) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for material in ALL_BLOCK_TYPES {
+            let range = self.range(material);
+            if range.is_empty() || !shown(material) {
+                continue;
+            }
+
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+
+        ranges
+    }
+
+    // Chunk clusters of the translucent materials for which *shown* is true,
+    // the cluster furthest from *eye* comes first
+    pub fn back_to_front(
+        &self,
+        eye: Position,
+        shown: impl Fn(BlockType) -> bool,
+    ) -> Vec<Range<usize>> {
+        let mut clusters = self
+            .clusters
+            .iter()
+            .filter(|cluster| shown(cluster.material))
+            .map(|cluster| (eye.distance2(cluster.center), cluster.range.clone()))
+            .collect::<Vec<_>>();
+        clusters.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        clusters.into_iter().map(|(_, range)| range).collect()
+    }
+}
+
+fn collect_surface_block_data(chunks: &WorldChunks) -> Vec<BlockData> {
+    chunks
+        .par_iter()
+        .flat_map_iter(|chunk| chunk.surface_blocks.iter().copied())
+        .collect()
+}
+
+fn collect_rigid_block_data(chunks: &WorldChunks) -> (Vec<BlockData>, MaterialRanges) {
+    let blocks = chunks
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(index, chunk)| {
+            chunk
+                .get_rigid_blocks()
+                .map(move |(material, block)| (material, index, block))
+        })
+        .collect();
+
+    MaterialRanges::sort(blocks)
+}
 
 // Represents a 2D grid of chunks
 // Rows are parallel to the world x axis
@@ -51,7 +235,7 @@ struct WorldChange(ChunkPosition, JoinHandle<Vec<ChunkChange>>);
 //
 // Bigger indices correspond to bigger coordinates
 //
-// At any time only a part of the world is loaded, see config::WORLD_SIZE
+// At any time only a part of the world is loaded, see config::CHUNK_LOAD_RADIUS
 // This strusts represents a "window" into the the world
 // and can be used as a sliding window centered around the player
 pub struct World {
@@ -67,15 +251,47 @@ pub struct World {
     // NO!
     // When moving diagonal to the chunk grid, we need to load meshes for chunks is rapid
     // succession. That is why multiple we need support for multiple concurrent updates.
-    chunk_meshes: [Lazy<Mesh>; CHUNKS_IN_WORLD],
+    chunk_meshes: Vec<Lazy<Mesh>>,
+    // The resolution each built mesh was stitched against,
+    // meshes whose neighborhood resolution changed need to be rebuilt
+    chunk_mesh_lods: Vec<Option<ChunkLod>>,
+    // Density of the chunks sampled on a coarse grid. It is baked by jobs of its own,
+    // so the raymarched preview doesn't wait for the meshes, or by the mesh builds
+    // with the density cache, which sample it anyway.
+    chunk_densities: Vec<Option<Arc<DensityVolume>>>,
+    // How long building each mesh took, for finding the terrain that is expensive to polygonize
+    chunk_build_costs: Vec<Option<BuildCost>>,
+    // Meshes dropped for a rebuild with new options, kept until the rebuilt mesh arrives
+    // so the viewer can fade from one to the other
+    retired_meshes: Vec<Option<Mesh>>,
+    // Retired meshes whose replacement was integrated, waiting for take_replaced_meshes
+    replaced_meshes: Vec<Mesh>,
     mesh_sender: Sender<BoundMesh>,
     mesh_receiver: Receiver<BoundMesh>,
-    mesh_builders: Vec<JoinHandle<Vec<SendError<BoundMesh>>>>,
+    mesh_builders: Vec<JobHandle<Vec<SendError<BoundMesh>>>>,
     meshes_being_built: HashSet<ChunkPosition>,
-
-    // Handle to the worker thread that loads chunks from minecraft save file.
-    // None if no chunks are being loaded at the moment
-    world_change: Option<WorldChange>,
+    // Bumped when all meshes are rebuilt, the running builders are cancelled
+    // and the meshes they already sent are dropped
+    mesh_generation: usize,
+    density_sender: Sender<BakedDensity>,
+    density_receiver: Receiver<BakedDensity>,
+    density_bakers: Vec<JobHandle<()>>,
+    densities_being_baked: HashSet<ChunkPosition>,
+
+    // Chunks are loaded from the minecraft save file by jobs of their own,
+    // each loaded chunk is sent back on its own and integrated in the update loop.
+    // Until then the chunk is empty and the meshes around it are not built.
+    chunk_sender: Sender<Chunk>,
+    chunk_receiver: Receiver<Chunk>,
+    chunk_loaders: Vec<JobHandle<()>>,
+    chunks_being_loaded: HashSet<ChunkPosition>,
+
+    // Job gathering the instance data after the world was recentered,
+    // so the main thread doesn't have to copy all the blocks around.
+    instance_data_builder: Option<JobHandle<InstanceData>>,
+
+    // Changes of the world since the last call to take_events
+    events: Vec<AppEvent>,
 }
 
 fn get_difference_1d(region: i32, chunk: usize, new_region: i32, new_chunk: usize) -> i32 {
@@ -115,53 +331,197 @@ fn get_difference(original: &ChunkPosition, different: &ChunkPosition) -> (i32,
     (diff_x, diff_z)
 }
 
-const OFFSET_FROM_CENTER: usize = config::WORLD_SIZE / 2;
+// Chunk from the save, ready to be polygonized
+fn load_chunk(chunk_position: ChunkPosition) -> Chunk {
+    let mut chunk = minecraft::get_chunk(chunk_position);
+    chunk.build_surface();
+    if settings::current().build_octree {
+        chunk.build_octree();
+    }
+
+    chunk
+}
+
+// Chunks of a window *size* chunks wide that isn't shown, with *base* being the chunk with the
+// smallest coordinates. The chunks are loaded on the calling thread, for the work that goes over
+// the whole world.
+pub fn load_window(base: ChunkPosition, size: usize) -> WorldChunks {
+    WorldChunks::from_fn(size, |x, z| {
+        Arc::new(load_chunk(base.offset(x as i32, z as i32)))
+    })
+}
+
+// Block at *position* within *chunks*, Air outside of them
+pub fn get_block_in(chunks: &WorldChunks, position: Position) -> BlockType {
+    let chunk_position = get_minecraft_chunk_position(position);
+    let chunk = chunks.iter().find(|chunk| chunk.position == chunk_position);
+
+    let Some(chunk) = chunk else {
+        return BlockType::Air;
+    };
+
+    let (block_x, block_z) = Chunk::get_block_coords(position.x, position.z);
+    chunk.get_block(block_x, position.y.floor() as isize, block_z)
+}
+
+// Reads the mesh of the chunk from the mesh cache, polygonizes and stores it on a miss
+fn build_or_load_mesh(
+    chunks: &WorldChunks,
+    chunk_index: usize,
+    options: PolygonizationOptions,
+    lod: ChunkLod,
+    cache_key: (u64, u64),
+    baked_density: Option<&DensityVolume>,
+    mesh_cache: Option<&MeshCache>,
+) -> (Mesh, BuildCost, Option<DensityVolume>) {
+    let chunk = &chunks[chunk_index];
+    let Some(mesh_cache) = mesh_cache else {
+        return polygonize_chunk_with_density(chunks, chunk_index, options, lod, baked_density);
+    };
+
+    let (fingerprint, blocks_hash) = cache_key;
+    let start = Instant::now();
+    if let Some(mesh) = mesh_cache.load(chunk, fingerprint, blocks_hash, &options.materials) {
+        let cost = BuildCost {
+            duration: start.elapsed(),
+            density_evaluations: 0,
+            density_memo: MemoStats::default(),
+        };
+        return (mesh, cost, None);
+    }
+
+    let (mesh, cost, density) =
+        polygonize_chunk_with_density(chunks, chunk_index, options, lod, baked_density);
+    mesh_cache.store(chunk, fingerprint, blocks_hash, &mesh);
+
+    (mesh, cost, density)
+}
+
+// Index of the center chunk along both axes of a window *size* chunks wide
+fn offset_from_center(size: usize) -> usize {
+    size / 2
+}
 
 impl World {
-    pub fn new(position: Position) -> Self {
+    // Starts the jobs loading the chunks up to *radius* chunks around *position*.
+    // The chunks stay empty until update_chunk_data puts the loaded ones into the world,
+    // chunks_being_loaded tells how many are still missing.
+    pub fn load(position: Position, radius: usize) -> Self {
+        let mut world = World::empty(position, radius);
+        // The chunks announce themselves once they are loaded
+        world.events.clear();
+        for chunk_position in world.get_loaded_chunk_positions() {
+            world.dispatch_chunk_load(chunk_position);
+        }
+
+        world
+    }
+
+    // World without any blocks, used when there is no save to load the chunks from
+    pub fn empty(position: Position, radius: usize) -> Self {
         let center_chunk_position = get_minecraft_chunk_position(position);
+        let size = 2 * radius;
 
         // Get position of chunk that corresponds to 0,0 in the world grid
-        let base_chunk_position = center_chunk_position
-            .offset(-(OFFSET_FROM_CENTER as i32), -(OFFSET_FROM_CENTER as i32));
+        let offset = offset_from_center(size) as i32;
+        let base_chunk_position = center_chunk_position.offset(-offset, -offset);
 
         let (tx, rx) = mpsc::channel();
+        let (chunk_sender, chunk_receiver) = mpsc::channel();
+        let (density_sender, density_receiver) = mpsc::channel();
 
-        World {
-            chunks: array_init(|index| {
-                let x = index % config::WORLD_SIZE;
-                let z = index / config::WORLD_SIZE;
+        let chunk_count = size * size;
+        let mut world = World {
+            chunks: WorldChunks::from_fn(size, |x, z| {
                 let chunk_position = base_chunk_position.offset(x as i32, z as i32);
 
-                let mut chunk = minecraft::get_chunk(chunk_position);
-                chunk.build_surface();
-
-                Arc::new(chunk)
+                Arc::new(Chunk::new(chunk_position))
             }),
-            chunk_meshes: array_init(|_| Lazy::new()),
+            chunk_meshes: (0..chunk_count).map(|_| Lazy::new()).collect(),
+            chunk_mesh_lods: vec![None; chunk_count],
+            chunk_densities: vec![None; chunk_count],
+            chunk_build_costs: vec![None; chunk_count],
+            retired_meshes: (0..chunk_count).map(|_| None).collect(),
+            replaced_meshes: Vec::new(),
             center: center_chunk_position,
             mesh_sender: tx,
             mesh_receiver: rx,
             mesh_builders: Vec::new(),
             meshes_being_built: HashSet::new(),
-            world_change: None,
+            mesh_generation: 0,
+            density_sender,
+            density_receiver,
+            density_bakers: Vec::new(),
+            densities_being_baked: HashSet::new(),
+            chunk_sender,
+            chunk_receiver,
+            chunk_loaders: Vec::new(),
+            chunks_being_loaded: HashSet::new(),
+            instance_data_builder: None,
+            events: Vec::new(),
+        };
+
+        for chunk in &world.chunks {
+            world.events.push(AppEvent::ChunkLoaded(chunk.position));
         }
+
+        world
+    }
+
+    pub fn take_events(&mut self) -> Vec<AppEvent> {
+        std::mem::take(&mut self.events)
     }
 
     // Note: this allocates a bunch of *unnecessary* vectors
     // but I'm not sure if there is another way
     pub fn get_surface_block_data(&self) -> Vec<BlockData> {
-        self.chunks
-            .iter()
-            .flat_map(|chunk| chunk.surface_blocks.clone())
-            .collect_vec()
+        collect_surface_block_data(&self.chunks)
     }
 
-    pub fn get_rigid_blocks_data(&self) -> Vec<BlockData> {
-        self.chunks
-            .iter()
-            .flat_map(|chunk| chunk.get_rigid_block_data().into_iter())
-            .collect()
+    // Rigid blocks sorted by material, see MaterialRanges
+    pub fn get_rigid_blocks_data(&self) -> (Vec<BlockData>, MaterialRanges) {
+        collect_rigid_block_data(&self.chunks)
+    }
+
+    fn dispatch_instance_data_builder(&mut self) {
+        let chunks = self.get_chunks();
+        let job = jobs::spawn("instance data", Priority::High, 0, move |_| {
+            time_it!(
+                "Gather instance data",
+                let (rigid_blocks, rigid_ranges) = collect_rigid_block_data(&chunks);
+                let instance_data = InstanceData {
+                    surface_blocks: collect_surface_block_data(&chunks),
+                    rigid_blocks,
+                    rigid_ranges,
+                };
+            );
+
+            instance_data
+        });
+
+        // A builder that is still running works with outdated chunks,
+        // dropping its handle detaches it and its result is never used
+        self.instance_data_builder = Some(job);
+    }
+
+    // Returns the instance data of the world once it has been gathered after a recenter
+    pub fn take_instance_data(&mut self) -> Option<InstanceData> {
+        let finished = self
+            .instance_data_builder
+            .as_ref()
+            .is_some_and(|builder| builder.is_finished());
+        if !finished {
+            return None;
+        }
+
+        let builder = self.instance_data_builder.take()?;
+        match builder.join() {
+            Ok(instance_data) => Some(instance_data),
+            Err(panic_message) => {
+                println!("Instance data builder job panicked! --\n{panic_message:?}");
+                None
+            }
+        }
     }
 
     // Do not filter the blocks in any way - for debug purposes
@@ -181,85 +541,190 @@ impl World {
         let chunk_index = change.0;
 
         match change.1 {
-            ChunkSource::Direct(chunk) => {
-                self.chunks[chunk_index] = Arc::new(chunk);
-                self.chunk_meshes[chunk_index] = Lazy::new();
+            ChunkSource::Pending(chunk_position) => {
+                self.replace_chunk(chunk_index, Chunk::new(chunk_position));
+                self.dispatch_chunk_load(chunk_position);
             }
             ChunkSource::Reference(new_chunk_index) => {
                 self.chunks.swap(chunk_index, new_chunk_index);
                 self.chunk_meshes.swap(chunk_index, new_chunk_index);
+                self.chunk_mesh_lods.swap(chunk_index, new_chunk_index);
+                self.chunk_densities.swap(chunk_index, new_chunk_index);
+                self.chunk_build_costs.swap(chunk_index, new_chunk_index);
+                self.retired_meshes.swap(chunk_index, new_chunk_index);
             }
         }
     }
 
-    fn integrate_world_change(&mut self) -> bool {
-        let Some(world_change) = self.world_change.take() else {
-            return false;
-        };
-
-        let new_center = world_change.0;
-        let builder = world_change.1;
+    fn replace_chunk(&mut self, chunk_index: usize, chunk: Chunk) {
+        self.chunks[chunk_index] = Arc::new(chunk);
+        self.chunk_meshes[chunk_index] = Lazy::new();
+        self.chunk_mesh_lods[chunk_index] = None;
+        self.chunk_densities[chunk_index] = None;
+        self.chunk_build_costs[chunk_index] = None;
+        self.retired_meshes[chunk_index] = None;
+    }
 
-        match builder.join() {
-            Ok(chunk_changes) => {
-                // The changes have to be applied in a specific order
-                for change in chunk_changes {
-                    self.apply_chunk_change(change);
-                }
-                self.center = new_center;
+    fn dispatch_chunk_load(&mut self, chunk_position: ChunkPosition) {
+        // Still loading from an earlier visit, the chunk is integrated when that job finishes
+        if !self.chunks_being_loaded.insert(chunk_position) {
+            return;
+        }
 
-                return true;
+        let sender = self.chunk_sender.clone();
+        let job = jobs::spawn("chunk load", Priority::High, 0, move |context| {
+            if context.is_cancelled() {
+                return;
             }
-            Err(panic_message) => {
-                println!("Chunk builder thread panicked! Recentering to {new_center:?} was aborted. --\n{panic_message:?}");
+            let chunk = load_chunk(chunk_position);
 
-                return false;
-            }
-        }
+            // The world was dropped, nobody is waiting for the chunk
+            let _ = sender.send(chunk);
+        });
+
+        self.chunk_loaders.push(job);
     }
 
-    // This method does not do the actual updating.
-    // Instead, it will manage the worker thread that does it.
-    // Returns true if a new part of the world was loaded
-    pub fn update_chunk_data(
-        &mut self,
-        new_position: Position,
-        options: PolygonizationOptions,
-    ) -> bool {
-        // Only 1 update can be running at any time
-        if let Some(world_change) = &self.world_change {
-            let builder = &world_change.1;
-            let in_progress = !builder.is_finished();
-            if in_progress {
-                return false;
-            }
+    // Returns whether any loaded chunk was put into the world
+    fn integrate_loaded_chunks(&mut self) -> bool {
+        let mut any_integrated = false;
+
+        while let Ok(chunk) = self.chunk_receiver.try_recv() {
+            let chunk_position = chunk.position;
+            self.chunks_being_loaded.remove(&chunk_position);
+
+            // The world moved on before the chunk was loaded
+            let Some(chunk_index) = self.chunk_index_of(chunk_position) else {
+                continue;
+            };
+
+            self.events.push(AppEvent::ChunkLoaded(chunk_position));
+            self.replace_chunk(chunk_index, chunk);
+            any_integrated = true;
         }
 
-        // There is no update running, or it has finished, itegrate the changes, if any.
-        let world_data_updated = self.integrate_world_change();
-        if world_data_updated {
-            self.dispatch_mesh_builder(options);
+        if any_integrated {
+            minecraft::log_unmapped_blocks();
         }
+        self.chunk_loaders.retain(|loader| !loader.is_finished());
+
+        any_integrated
+    }
+
+    fn chunk_index_of(&self, chunk_position: ChunkPosition) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|chunk| chunk.position == chunk_position)
+    }
+
+    // Moves the loaded window right away and starts the jobs loading the chunks that came into it.
+    // Once new chunks are in the world, their meshes and the instance data are rebuilt
+    pub fn update_chunk_data(&mut self, new_position: Position, options: PolygonizationOptions) {
+        let chunks_loaded = self.integrate_loaded_chunks();
 
-        // Check whether we need to update and dispatch the update task.
         let center_chunk_position = get_minecraft_chunk_position(new_position);
         let recenter = self.center != center_chunk_position;
         if recenter {
-            let chunks = self.get_chunks();
             let direction_of_change = get_difference(&self.center, &center_chunk_position);
+            let chunk_changes = World::offset_chunks(&self.chunks, direction_of_change);
 
-            let handle = thread::spawn(move || {
-                time_it!(
-                    "Offset chunks",
-                    let x = World::offset_chunks(chunks, direction_of_change);
-                );
+            // The changes have to be applied in a specific order
+            for change in chunk_changes {
+                self.apply_chunk_change(change);
+            }
+            let (direction_x, direction_z) = direction_of_change;
+            self.center = self.center.offset(direction_x, direction_z);
+        }
 
-                x
-            });
-            self.world_change = Some(WorldChange(center_chunk_position, handle));
+        if chunks_loaded || recenter {
+            self.dispatch_mesh_builder(options);
+            self.dispatch_instance_data_builder();
         }
+    }
+
+    // Chunks loaded in every direction from the center chunk
+    pub fn load_radius(&self) -> usize {
+        self.chunks.size() / 2
+    }
+
+    // Loads *radius* chunks in every direction from the center chunk instead.
+    // Chunks in both the old and the new window are kept along with their meshes,
+    // the chunks that came into the window are loaded by jobs.
+    pub fn resize(&mut self, radius: usize, options: PolygonizationOptions) {
+        let size = 2 * radius;
+        if size == self.chunks.size() {
+            return;
+        }
+
+        // The rings of the chunks and with them the resolution of their meshes change
+        self.cancel_mesh_builds();
+
+        let offset = offset_from_center(size) as i32;
+        let base_chunk_position = self.center.offset(-offset, -offset);
+        let previous_index = |chunk_position: ChunkPosition| {
+            self.chunks
+                .iter()
+                .position(|chunk| chunk.position == chunk_position)
+        };
+        let sources = (0..size * size)
+            .map(|index| {
+                let (x, z) = (index % size, index / size);
+                let chunk_position = base_chunk_position.offset(x as i32, z as i32);
+                match previous_index(chunk_position) {
+                    Some(previous) => ChunkSource::Reference(previous),
+                    None => ChunkSource::Pending(chunk_position),
+                }
+            })
+            .collect_vec();
 
-        world_data_updated
+        let mut chunks = Vec::with_capacity(sources.len());
+        let mut chunk_meshes = Vec::with_capacity(sources.len());
+        let mut chunk_mesh_lods = Vec::with_capacity(sources.len());
+        let mut chunk_densities = Vec::with_capacity(sources.len());
+        let mut chunk_build_costs = Vec::with_capacity(sources.len());
+        let mut retired_meshes = Vec::with_capacity(sources.len());
+        let mut pending = Vec::new();
+        for source in sources {
+            match source {
+                ChunkSource::Reference(previous) => {
+                    chunks.push(self.chunks[previous].clone());
+                    let mesh = std::mem::replace(&mut self.chunk_meshes[previous], Lazy::new());
+                    chunk_meshes.push(mesh);
+                    chunk_mesh_lods.push(self.chunk_mesh_lods[previous].take());
+                    chunk_densities.push(self.chunk_densities[previous].take());
+                    chunk_build_costs.push(self.chunk_build_costs[previous].take());
+                    retired_meshes.push(self.retired_meshes[previous].take());
+                }
+                ChunkSource::Pending(chunk_position) => {
+                    chunks.push(Arc::new(Chunk::new(chunk_position)));
+                    chunk_meshes.push(Lazy::new());
+                    chunk_mesh_lods.push(None);
+                    chunk_densities.push(None);
+                    chunk_build_costs.push(None);
+                    retired_meshes.push(None);
+                    pending.push(chunk_position);
+                }
+            }
+        }
+
+        self.chunks = WorldChunks { chunks, size };
+        self.chunk_meshes = chunk_meshes;
+        self.chunk_mesh_lods = chunk_mesh_lods;
+        self.chunk_densities = chunk_densities;
+        self.chunk_build_costs = chunk_build_costs;
+        self.retired_meshes = retired_meshes;
+        for chunk_position in pending {
+            self.dispatch_chunk_load(chunk_position);
+        }
+        println!("[INFO] Loaded window resized to {size}x{size} chunks");
+
+        self.dispatch_mesh_builder(options);
+        self.dispatch_instance_data_builder();
+    }
+
+    // Number of chunks that are still being loaded from the save file
+    pub fn chunks_being_loaded(&self) -> usize {
+        self.chunks_being_loaded.len()
     }
 
     // Returns whether any meshes were updated.
@@ -274,10 +739,20 @@ impl World {
     }
 
     fn integrate_built_meshes(&mut self) {
+        let materials = material_registry::snapshot();
         let mut recv_result = self.mesh_receiver.try_recv();
         while let Ok(data) = recv_result {
-            let mesh = data.0;
+            let mut mesh = data.0;
             let chunk_position = data.1;
+            let lod = data.2;
+            let cost = data.3;
+            let generation = data.4;
+
+            // Built with options that were changed since, the chunk is already being rebuilt
+            if generation != self.mesh_generation {
+                recv_result = self.mesh_receiver.try_recv();
+                continue;
+            }
 
             self.meshes_being_built.remove(&chunk_position);
 
@@ -294,7 +769,15 @@ impl World {
                     self.chunk_meshes[mesh_index].get().is_none(),
                     "The mesh for {chunk_position:?} was already built!"
                 );
+                // The activation thresholds may have changed while the mesh was built
+                mesh.recolor(&materials);
                 self.chunk_meshes[mesh_index].get_or_create(|| mesh);
+                self.chunk_mesh_lods[mesh_index] = Some(lod);
+                self.chunk_build_costs[mesh_index] = Some(cost);
+                if let Some(retired) = self.retired_meshes[mesh_index].take() {
+                    self.replaced_meshes.push(retired);
+                }
+                self.events.push(AppEvent::MeshBuilt(chunk_position));
             } else {
                 println!(
                     "Received mesh for chunk {:?}, but that chunk is not loaded!",
@@ -350,7 +833,7 @@ impl World {
 
                     println!("The following errors occured when trying to send to the channel:\n {error_message}");
                 }
-                Err(panic_message) => println!("Worker thread panicked! - {panic_message:?}"),
+                Err(panic_message) => println!("Mesh builder job panicked! - {panic_message:?}"),
                 _ => (), /* println!("Successfully joined worker thread.") */
             };
         }
@@ -358,25 +841,28 @@ impl World {
         return any_finished;
     }
 
-    fn offset_chunks(chunks: WorldChunks, offset: (i32, i32)) -> Vec<ChunkChange> {
+    fn offset_chunks(chunks: &WorldChunks, offset: (i32, i32)) -> Vec<ChunkChange> {
         let (direction_x, direction_z) = offset;
         let reverse_x = direction_x < 0;
         let reverse_z = direction_z < 0;
-
-        let index_builder = |reverse: bool| -> [usize; WORLD_SIZE] {
-            array_init(|index| match reverse {
-                true => WORLD_SIZE - 1 - index,
-                false => index,
-            })
+        let size = chunks.size();
+
+        let index_builder = |reverse: bool| -> Vec<usize> {
+            (0..size)
+                .map(|index| match reverse {
+                    true => size - 1 - index,
+                    false => index,
+                })
+                .collect()
         };
 
         let swappable_region_size = |dir: i32| -> usize {
             let loading_in_direction = dir != 0;
 
             if loading_in_direction {
-                WORLD_SIZE - 1
+                size - 1
             } else {
-                WORLD_SIZE
+                size
             }
         };
 
@@ -390,11 +876,11 @@ impl World {
         let swappable_chunks_iterator = x_iter.cartesian_product(z_iter);
 
         let chunks_swaps = swappable_chunks_iterator.map(|(x, z)| {
-            let current_chunk_index = World::chunk_index(x, z);
+            let current_chunk_index = chunks.chunk_index(x, z);
 
             let next_x = (x as i32 + direction_x) as usize;
             let next_z = (z as i32 + direction_z) as usize;
-            let next_chunk_index = World::chunk_index(next_x, next_z);
+            let next_chunk_index = chunks.chunk_index(next_x, next_z);
 
             let swap_chunks = ChunkChange(
                 current_chunk_index,
@@ -409,13 +895,13 @@ impl World {
         let indices_of_chunks_to_load = {
             let edge_coord = |reverse: bool| match reverse {
                 true => 0,
-                false => WORLD_SIZE - 1,
+                false => size - 1,
             };
             let x_edge_coord = edge_coord(reverse_x);
             let z_edge_coord = edge_coord(reverse_z);
 
-            let x_edge_indices = (0..WORLD_SIZE).map(|z| (x_edge_coord, z));
-            let z_edge_indices = (0..WORLD_SIZE).map(|x| (x, z_edge_coord));
+            let x_edge_indices = (0..size).map(|z| (x_edge_coord, z));
+            let z_edge_indices = (0..size).map(|x| (x, z_edge_coord));
 
             if direction_x == 0 {
                 z_edge_indices.collect_vec()
@@ -427,27 +913,151 @@ impl World {
         };
 
         let chunk_loads = indices_of_chunks_to_load.into_iter().map(|(x, z)| {
-            let current_chunk_index = World::chunk_index(x, z);
+            let current_chunk_index = chunks.chunk_index(x, z);
 
             let original_position = &chunks[current_chunk_index].position;
             let position_to_load = original_position.offset(direction_x, direction_z);
 
-            let mut chunk = minecraft::get_chunk(position_to_load);
-            chunk.build_surface();
-
-            let chunk_load = ChunkChange(current_chunk_index, ChunkSource::Direct(chunk));
-
-            chunk_load
+            ChunkChange(current_chunk_index, ChunkSource::Pending(position_to_load))
         });
 
         chunks_swaps.chain(chunk_loads).collect_vec()
     }
 
-    fn chunk_index(x: usize, z: usize) -> usize {
-        z * config::WORLD_SIZE + x
+    // Chunks of the loaded window that may overlap *area*.
+    // The window is a grid keyed by the global chunk coordinates, offset by those of its first
+    // chunk, so only the chunks under *area* are looked up instead of testing all of them.
+    // A kernel is at most a chunk wide and touches at most 4 chunks.
+    pub fn chunks_overlapping(
+        chunks: &WorldChunks,
+        area: Rectangle,
+    ) -> impl Iterator<Item = &Arc<Chunk>> + '_ {
+        let (base_x, base_z) = chunks[0].position.get_global_position_in_chunks();
+        let window_range = |start: Coord, end: Coord, base: i32| {
+            let chunk_size = minecraft::BLOCKS_IN_CHUNK as Coord;
+            let first = (start / chunk_size).floor() as i32 - base;
+            let last = (end / chunk_size).floor() as i32 - base;
+
+            (first.max(0)..=last.min(chunks.size() as i32 - 1)).map(|index| index as usize)
+        };
+
+        let x_range = window_range(area.left(), area.right(), base_x);
+        let z_range = window_range(area.bottom(), area.top(), base_z);
+
+        z_range
+            .cartesian_product(x_range)
+            .map(|(z, x)| &chunks[chunks.chunk_index(x, z)])
     }
 
     pub fn get_block(&self, position: Position) -> BlockType {
+        get_block_in(&self.chunks, position)
+    }
+
+    // Replaces blocks of the loaded chunks, blocks outside of them or above and below the world are ignored.
+    // The meshes around the changed blocks are rebuilt, returns how many blocks changed.
+    pub fn set_blocks(
+        &mut self,
+        blocks: &[(Position, BlockType)],
+        options: PolygonizationOptions,
+    ) -> usize {
+        let mut changed = 0;
+        let mut edited = HashSet::new();
+        let mut outdated = HashSet::new();
+        for (position, block) in blocks {
+            let chunk_position = get_minecraft_chunk_position(*position);
+            let index = self
+                .chunks
+                .iter()
+                .position(|chunk| chunk.position == chunk_position);
+            let Some(index) = index else {
+                continue;
+            };
+
+            let (block_x, block_z) = Chunk::get_block_coords(position.x, position.z);
+            let block_y = position.y.floor() as isize;
+            if block_y < MIN_BLOCK_Y || block_y >= MAX_BLOCK_Y {
+                continue;
+            }
+            // Chunks shared with a mesh builder are copied before the change
+            Arc::make_mut(&mut self.chunks[index]).push_block(block_x, block_z, block_y, *block);
+            self.events.push(AppEvent::BlockEdited(*position, *block));
+            changed += 1;
+            edited.insert(index);
+
+            // The density kernel reaches into the neighboring chunks
+            outdated.extend(World::neighbourhood(self.chunks.size(), index));
+        }
+
+        // Once per chunk, a brush edits thousands of blocks at a time
+        for index in edited {
+            Arc::make_mut(&mut self.chunks[index]).rebuild_after_edit();
+        }
+
+        if changed > 0 {
+            // Builds already running read the blocks from before the change
+            let building = outdated
+                .iter()
+                .map(|index| self.chunks[*index].position)
+                .any(|position| {
+                    self.meshes_being_built.contains(&position)
+                        || self.densities_being_baked.contains(&position)
+                });
+            if building {
+                self.cancel_mesh_builds();
+            }
+
+            // The old meshes stay on screen until the rebuilt ones arrive
+            for index in outdated {
+                self.retire_mesh(index);
+                self.chunk_mesh_lods[index] = None;
+                self.chunk_densities[index] = None;
+                self.chunk_build_costs[index] = None;
+            }
+            self.dispatch_mesh_builder(options);
+            self.dispatch_instance_data_builder();
+        }
+
+        changed
+    }
+
+    // Content hash of the chunk containing *position*, None if it isn't loaded
+    pub fn get_chunk_hash(&self, position: Position) -> Option<u64> {
+        let chunk_position = get_minecraft_chunk_position(position);
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.position == chunk_position)
+            .map(|chunk| chunk.content_hash())
+    }
+
+    // Height of the topmost block of the column at *position*,
+    // None outside of the loaded chunks and for columns without any blocks
+    pub fn get_surface_height(&self, position: PlanarPosition) -> Option<isize> {
+        let chunk_position = get_minecraft_chunk_position(Point3::new(position.x, 0.0, position.y));
+        let chunk = self
+            .chunks
+            .iter()
+            .find(|chunk| chunk.position == chunk_position)?;
+
+        let (block_x, block_z) = Chunk::get_block_coords(position.x, position.y);
+        chunk.get_surface_height(block_x, block_z)
+    }
+
+    pub fn get_loaded_chunk_positions(&self) -> Vec<ChunkPosition> {
+        self.chunks.iter().map(|chunk| chunk.position).collect()
+    }
+
+    // Block counts of the chunk at *chunk_position*, None if it isn't loaded
+    pub fn count_chunk_materials(
+        &self,
+        chunk_position: ChunkPosition,
+    ) -> Option<[usize; BLOCK_TYPES]> {
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.position == chunk_position)
+            .map(|chunk| chunk.count_materials())
+    }
+
+    pub fn get_light(&self, position: Position) -> LightLevel {
         let chunk_position = get_minecraft_chunk_position(position);
         let chunk = self
             .chunks
@@ -455,59 +1065,414 @@ impl World {
             .find(|chunk| chunk.position == chunk_position);
 
         let Some(chunk) = chunk else {
-            return BlockType::Air;
+            return LightLevel::default();
         };
 
         let (block_x, block_z) = Chunk::get_block_coords(position.x, position.z);
-        chunk.get_block(block_x, position.y.floor() as isize, block_z)
+        chunk.get_light(block_x, position.y.floor() as isize, block_z)
     }
 
-    // TODO: this can be const and return fixed sized array that depends on WORLD_SIZe
-    fn inner_chunk_indices() -> Vec<usize> {
+    // Whether the chunk and all the chunks around it are loaded, the mesh of a chunk depends on them
+    fn neighbourhood_loaded(&self, chunk_index: usize) -> bool {
+        World::neighbourhood(self.chunks.size(), chunk_index).all(|index| {
+            let position = self.chunks[index].position;
+            !self.chunks_being_loaded.contains(&position)
+        })
+    }
+
+    // Indices of the chunk and the loaded chunks around it in a window *size* chunks wide
+    fn neighbourhood(size: usize, chunk_index: usize) -> impl Iterator<Item = usize> {
+        let (x, z) = (chunk_index % size, chunk_index / size);
+        let neighbourhood =
+            |coordinate: usize| coordinate.saturating_sub(1)..(coordinate + 2).min(size);
+
+        neighbourhood(x)
+            .cartesian_product(neighbourhood(z))
+            .map(move |(x, z)| z * size + x)
+    }
+
+    // Hash of the blocks of the chunk and its neighbours, everything the kernels around
+    // the chunk read. Keys the meshes in the mesh cache.
+    fn neighbourhood_hash(&self, chunk_index: usize) -> u64 {
+        World::neighbourhood(self.chunks.size(), chunk_index).fold(
+            FNV_OFFSET_BASIS,
+            |hash, index| {
+                let content_hash = self.chunks[index].content_hash();
+                mesh_cache::fnv_hash(hash, &content_hash.to_le_bytes())
+            },
+        )
+    }
+
+    fn inner_chunk_indices(&self) -> Vec<usize> {
         // To evaluate the sdf at a point, we need data in a radius around that point.
         // For the chunks that are on the edges of the (loaded) world we are missing data,
         // resulting in artifacts when stitching the chunk meshes together.
         //
         // For now the simple solution is just to polygonize only the chunks that have all
-        // neighboring chunks loaded.
-        let chunk_indices = (1..WORLD_SIZE - 1)
-            .cartesian_product(1..WORLD_SIZE - 1)
-            .map(|(x, z)| World::chunk_index(x, z))
+        // neighboring chunks loaded, within the mesh radius of the settings.
+        let size = self.chunks.size();
+        let chunk_indices = (1..size - 1)
+            .cartesian_product(1..size - 1)
+            .filter(|(x, z)| World::is_meshed(size, *x, *z))
+            .map(|(x, z)| self.chunks.chunk_index(x, z))
             .collect::<Vec<usize>>();
 
         chunk_indices
     }
 
+    // Whether the chunk at *x*, *z* of a grid *size* chunks wide gets polygonized
+    fn is_meshed(size: usize, x: usize, z: usize) -> bool {
+        let radius = settings::current().mesh_radius.unwrap_or(usize::MAX);
+        let is_inner = |coordinate: usize| {
+            (1..size - 1).contains(&coordinate)
+                && coordinate.abs_diff(offset_from_center(size)) <= radius
+        };
+
+        is_inner(x) && is_inner(z)
+    }
+
+    // Number of chunks with a built mesh and the number of chunks that get polygonized
+    pub fn mesh_build_progress(&self) -> (usize, usize) {
+        let chunk_indices = self.inner_chunk_indices();
+        let built = chunk_indices
+            .iter()
+            .filter(|index| self.chunk_meshes[**index].get().is_some())
+            .count();
+
+        (built, chunk_indices.len())
+    }
+
     pub fn get_chunks(&self) -> WorldChunks {
-        clone_world(&self.chunks)
+        self.chunks.clone()
+    }
+
+    // Smooth mesh of the chunk at *chunk_position*, None until it is built
+    pub fn get_chunk_mesh(&self, chunk_position: ChunkPosition) -> Option<&Mesh> {
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.position == chunk_position)?;
+
+        self.chunk_meshes[index].get()
     }
 
     pub fn get_smooth_mesh(&self) -> Mesh {
-        let chunk_meshes = World::inner_chunk_indices()
+        let chunk_meshes = self.inner_chunk_indices().into_iter().filter_map(|index| {
+            let chunk_mesh = &self.chunk_meshes[index];
+            chunk_mesh.get()
+        });
+
+        Mesh::copy_merge(chunk_meshes)
+    }
+
+    // Positions of the chunks that get polygonized
+    pub fn polygonized_chunk_positions(&self) -> Vec<ChunkPosition> {
+        self.inner_chunk_indices()
+            .into_iter()
+            .map(|index| self.chunks[index].position)
+            .collect()
+    }
+
+    // Smooth mesh of the chunk at *position*, None until it is built
+    pub fn smooth_chunk_mesh(&self, position: ChunkPosition) -> Option<&Mesh> {
+        self.inner_chunk_indices()
+            .into_iter()
+            .find(|index| self.chunks[*index].position == position)
+            .and_then(|index| self.chunk_meshes[index].get())
+    }
+
+    // Copies of the smooth meshes of all built chunks
+    pub fn get_smooth_chunk_meshes(&self) -> Vec<Mesh> {
+        self.inner_chunk_indices()
+            .into_iter()
+            .filter_map(|index| self.chunk_meshes[index].get())
+            .map(|mesh| Mesh::copy_merge(std::iter::once(mesh)))
+            .collect()
+    }
+
+    // Write the smooth mesh of every built chunk into a file of the format
+    pub fn export_smooth_mesh(&self, path: &str, format: MeshFormat) -> io::Result<()> {
+        export::save_mesh(&self.get_smooth_mesh(), path, format)
+    }
+
+    // Write the walkable part of the smooth mesh into an OBJ file, returns the triangle count
+    pub fn export_navmesh(&self, path: &str) -> io::Result<usize> {
+        let navmesh = NavMesh::extract(
+            &self.get_smooth_mesh(),
+            config::NAVMESH_MAX_SLOPE_DEGREES,
+            config::NAVMESH_CELL_SIZE,
+        );
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        navmesh.write_obj(&mut writer)?;
+        writer.flush()?;
+
+        Ok(navmesh.triangles.len())
+    }
+
+    // Write the mesh and the splat map of every built chunk into *folder*,
+    // returns the number of chunks written
+    pub fn export_splat_maps(&self, folder: &str) -> ImageResult<usize> {
+        let folder = Path::new(folder);
+        fs::create_dir_all(folder)?;
+
+        let mut written = 0;
+        for index in self.inner_chunk_indices() {
+            let Some(mesh) = self.chunk_meshes[index].get() else {
+                continue;
+            };
+
+            let splat_map = SplatMap::bake(
+                mesh,
+                config::SPLAT_MAP_CHART_TEXELS,
+                config::SPLAT_MAP_PAGE_WIDTH,
+            );
+            let (chunk_x, chunk_z) = self.chunks[index].position.get_global_position_in_chunks();
+            let name = format!("chunk_{}_{}", chunk_x, chunk_z);
+
+            let mut writer = BufWriter::new(File::create(folder.join(format!("{name}.obj")))?);
+            splat_map.write_obj(&mut writer)?;
+            writer.flush()?;
+            splat_map.image.save(folder.join(format!("{name}.png")))?;
+
+            written += 1;
+        }
+
+        Ok(written)
+    }
+
+    // Write the open edges of every built chunk mesh into an OBJ file as polylines
+    pub fn export_mesh_boundaries(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        for index in self.inner_chunk_indices() {
+            if let Some(mesh) = self.chunk_meshes[index].get() {
+                writeln!(writer, "o chunk_{}", index)?;
+                mesh.write_boundary_obj(&mut writer)?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    // Signs of all loaded chunks closer than *max_distance* to *position*
+    pub fn get_signs_near(&self, position: Position, max_distance: Real) -> Vec<Sign> {
+        self.chunks
+            .iter()
+            .flat_map(|chunk| chunk.get_signs())
+            .filter(|sign| sign.position.distance(position) < max_distance)
+            .cloned()
+            .collect()
+    }
+
+    // Borders of the chunks that get polygonized as a list of line segments
+    pub fn get_chunk_grid_lines(&self, plane_height: Option<Real>) -> Vec<Position> {
+        self.inner_chunk_indices()
+            .into_iter()
+            .flat_map(|index| chunk_grid_lines(&self.chunks[index], plane_height))
+            .collect()
+    }
+
+    // Walls between the neighbouring loaded chunks where one has data in the save
+    // and the other was never generated
+    pub fn get_generated_boundary(&self) -> Vec<[Position; 4]> {
+        let generated = self
+            .chunks
+            .iter()
+            .map(|chunk| minecraft::is_chunk_generated(chunk.position))
+            .collect_vec();
+
+        let size = self.chunks.size();
+        let mut walls = Vec::new();
+        for (x, z) in (0..size).cartesian_product(0..size) {
+            let index = self.chunks.chunk_index(x, z);
+            let neighbours = [(x + 1, z), (x, z + 1)]
+                .into_iter()
+                .filter(|(x, z)| *x < size && *z < size)
+                .map(|(x, z)| self.chunks.chunk_index(x, z));
+
+            for neighbour in neighbours {
+                match (generated[index], generated[neighbour]) {
+                    (true, false) => walls.push(generated_boundary_wall(
+                        &self.chunks[index],
+                        &self.chunks[neighbour],
+                    )),
+                    (false, true) => walls.push(generated_boundary_wall(
+                        &self.chunks[neighbour],
+                        &self.chunks[index],
+                    )),
+                    _ => {}
+                }
+            }
+        }
+
+        walls
+    }
+
+    // Number of loaded chunks that have data in the save
+    pub fn count_generated_chunks(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|chunk| minecraft::is_chunk_generated(chunk.position))
+            .count()
+    }
+
+    // Height metrics of the chunks that get polygonized
+    pub fn get_chunk_height_metrics(&self) -> Vec<ChunkHeightMetrics> {
+        self.inner_chunk_indices()
+            .into_iter()
+            .map(|index| ChunkHeightMetrics::measure(&self.chunks[index]))
+            .collect()
+    }
+
+    // Build cost of every chunk with a built mesh
+    pub fn get_chunk_build_costs(&self) -> Vec<(ChunkHeightMetrics, BuildCost)> {
+        self.inner_chunk_indices()
             .into_iter()
             .filter_map(|index| {
-                let chunk_mesh = &self.chunk_meshes[index];
-                chunk_mesh.get()
-            });
+                let cost = self.chunk_build_costs[index]?;
+                Some((ChunkHeightMetrics::measure(&self.chunks[index]), cost))
+            })
+            .collect()
+    }
 
-        Mesh::copy_merge(chunk_meshes)
+    // Build cost of the chunk containing *position*, None until its mesh is built
+    pub fn get_chunk_build_cost(&self, position: Position) -> Option<BuildCost> {
+        let chunk_position = get_minecraft_chunk_position(position);
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.position == chunk_position)?;
+
+        self.chunk_build_costs[index]
+    }
+
+    // Baked densities of the polygonized chunks, see dispatch_density_baker
+    pub fn get_chunk_densities(&self) -> Vec<Arc<DensityVolume>> {
+        self.inner_chunk_indices()
+            .into_iter()
+            .filter_map(|index| self.chunk_densities[index].clone())
+            .collect()
+    }
+
+    // Distance of the chunk from the center chunk in chunks, in a window *size* chunks wide
+    fn chunk_ring(size: usize, index: usize) -> usize {
+        let x = index % size;
+        let z = index / size;
+
+        x.abs_diff(offset_from_center(size))
+            .max(z.abs_diff(offset_from_center(size)))
+    }
+
+    fn chunk_lod(size: usize, index: usize, options: PolygonizationOptions) -> ChunkLod {
+        let cell_size = options.cell_size_for_ring(World::chunk_ring(size, index));
+
+        let x = index % size;
+        let z = index / size;
+        let is_polygonized = |x, z| World::is_meshed(size, x, z);
+        let neighbor_cell_size = |side: ChunkSide| {
+            let (neighbor_x, neighbor_z) = match side {
+                ChunkSide::NegativeX => (x.wrapping_sub(1), z),
+                ChunkSide::PositiveX => (x + 1, z),
+                ChunkSide::NegativeZ => (x, z.wrapping_sub(1)),
+                ChunkSide::PositiveZ => (x, z + 1),
+            };
+
+            if is_polygonized(neighbor_x, neighbor_z) {
+                let neighbor_index = neighbor_z * size + neighbor_x;
+                options.cell_size_for_ring(World::chunk_ring(size, neighbor_index))
+            } else {
+                cell_size
+            }
+        };
+
+        ChunkLod {
+            cell_size,
+            neighbor_cell_sizes: [
+                neighbor_cell_size(ChunkSide::NegativeX),
+                neighbor_cell_size(ChunkSide::PositiveX),
+                neighbor_cell_size(ChunkSide::NegativeZ),
+                neighbor_cell_size(ChunkSide::PositiveZ),
+            ],
+            window_edges: [
+                !is_polygonized(x.wrapping_sub(1), z),
+                !is_polygonized(x + 1, z),
+                !is_polygonized(x, z.wrapping_sub(1)),
+                !is_polygonized(x, z + 1),
+            ],
+        }
+    }
+
+    // Meshes are moved around the grid when the world is recentered,
+    // so their resolution may no longer match their new ring.
+    // Meshes moved out of the mesh radius are dropped, they aren't shown anymore.
+    fn invalidate_stale_meshes(&mut self, options: PolygonizationOptions) {
+        let meshed = self.inner_chunk_indices();
+        for index in (0..self.chunks.len()).filter(|index| !meshed.contains(index)) {
+            if self.chunk_meshes[index].get().is_some() || self.retired_meshes[index].is_some() {
+                self.chunk_meshes[index] = Lazy::new();
+                self.retired_meshes[index] = None;
+                self.chunk_mesh_lods[index] = None;
+                self.chunk_densities[index] = None;
+                self.chunk_build_costs[index] = None;
+            }
+        }
+
+        for index in meshed {
+            let has_mesh = self.chunk_meshes[index].get().is_some();
+            let expected_lod = World::chunk_lod(self.chunks.size(), index, options);
+
+            if has_mesh && self.chunk_mesh_lods[index] != Some(expected_lod) {
+                self.retire_mesh(index);
+                self.chunk_mesh_lods[index] = None;
+                self.chunk_build_costs[index] = None;
+            }
+        }
     }
 
     pub fn dispatch_mesh_builder(&mut self, options: PolygonizationOptions) {
+        let options = PolygonizationOptions {
+            materials: material_registry::snapshot(),
+            settings: settings::current(),
+            ..options
+        };
+        self.invalidate_stale_meshes(options);
+        // The meshes built with the density cache bake the densities of their chunks,
+        // the baker only takes the chunks left over
+        self.spawn_mesh_builder(options);
+        self.dispatch_density_baker(options);
+    }
+
+    fn spawn_mesh_builder(&mut self, options: PolygonizationOptions) {
         let chunks = self.get_chunks();
 
-        let chunks_without_mesh = World::inner_chunk_indices()
+        let chunks_without_mesh = self
+            .inner_chunk_indices()
             .into_iter()
             .filter(|index| {
                 let chunk_position = self.chunks[*index].position;
                 let chunk_mesh = &self.chunk_meshes[*index];
 
-                chunk_mesh.get().is_none() && !self.meshes_being_built.contains(&chunk_position)
+                chunk_mesh.get().is_none()
+                    && !self.meshes_being_built.contains(&chunk_position)
+                    && self.neighbourhood_loaded(*index)
+            })
+            .map(|index| {
+                let lod = World::chunk_lod(self.chunks.size(), index, options);
+                let cache_key = (
+                    mesh_cache::build_fingerprint(options, lod),
+                    self.neighbourhood_hash(index),
+                );
+                // A density baked on the grid of the mesh is sampled instead of baking another
+                let baked_density = self.chunk_densities[index]
+                    .clone()
+                    .filter(|density| options.density_cache && density.cell_size == lod.cell_size);
+                let senders = (self.mesh_sender.clone(), self.density_sender.clone());
+                (index, lod, cache_key, baked_density, senders)
             })
-            .map(|index| (index, self.mesh_sender.clone()))
             .collect_vec();
 
-        // Avoid spawning the worker thread when not needed
+        // Avoid spawning the job when not needed
         if chunks_without_mesh.is_empty() {
             return;
         }
@@ -520,20 +1485,51 @@ impl World {
 
         let positions_to_build = chunks_without_mesh
             .iter()
-            .map(|(index, _)| self.chunks[*index].position);
+            .map(|(index, _, _, _, _)| self.chunks[*index].position);
         self.meshes_being_built.extend(positions_to_build);
 
-        let work_handle = thread::spawn(move || {
+        let positions_to_bake = chunks_without_mesh
+            .iter()
+            .filter(|(_, _, _, baked_density, _)| options.density_cache && baked_density.is_none())
+            .map(|(index, _, _, _, _)| self.chunks[*index].position);
+        self.densities_being_baked.extend(positions_to_bake);
+
+        let generation = self.mesh_generation;
+        let mesh_cache = MeshCache::open();
+        let job = jobs::spawn("mesh build", Priority::Normal, 0, move |context| {
             let n = chunks_without_mesh.len();
 
             time_it!("Building meshes of smooth surfaces",
                 let send_errors = chunks_without_mesh
                     //.into_iter() // serial implementation
                     .into_par_iter() // parallel implementation
-                    .filter_map(|(index, tx)| {
-                        let chunk_mesh = polygonize_chunk(&chunks, index, options);
+                    .filter_map(|(index, lod, cache_key, baked_density, (tx, density_tx))| {
+                        if context.is_cancelled() {
+                            return None;
+                        }
+
+                        let (chunk_mesh, cost, density) = build_or_load_mesh(
+                            &chunks,
+                            index,
+                            options,
+                            lod,
+                            cache_key,
+                            baked_density.as_deref(),
+                            mesh_cache.as_ref(),
+                        );
                         let chunk_position = chunks[index].position;
-                        let payload = BoundMesh(chunk_mesh, chunk_position);
+
+                        if options.density_cache && baked_density.is_none() {
+                            // A mesh read from the cache didn't sample any density
+                            let density = density.unwrap_or_else(|| {
+                                DensityVolume::bake_chunk(&chunks, index, options)
+                            });
+                            // The world was dropped, nobody is waiting for the density
+                            let baked = BakedDensity(density, chunk_position, generation);
+                            let _ = density_tx.send(baked);
+                        }
+
+                        let payload = BoundMesh(chunk_mesh, chunk_position, lod, cost, generation);
 
                         if let Err(send_error) = tx.send(payload) {
                             Some(send_error)
@@ -548,12 +1544,228 @@ impl World {
             send_errors
         });
 
-        self.mesh_builders.push(work_handle);
+        self.mesh_builders.push(job);
+    }
+
+    // Bakes the density of the polygonized chunks that don't have one yet. The bake is much
+    // cheaper than the meshes and runs ahead of them, so the preview shows up first.
+    fn dispatch_density_baker(&mut self, options: PolygonizationOptions) {
+        let chunks_to_bake = self
+            .inner_chunk_indices()
+            .into_iter()
+            .filter(|index| {
+                let chunk_position = self.chunks[*index].position;
+
+                self.chunk_densities[*index].is_none()
+                    && !self.densities_being_baked.contains(&chunk_position)
+                    && self.neighbourhood_loaded(*index)
+            })
+            .map(|index| (index, self.density_sender.clone()))
+            .collect_vec();
+        if chunks_to_bake.is_empty() {
+            return;
+        }
+
+        let positions_to_bake = chunks_to_bake
+            .iter()
+            .map(|(index, _)| self.chunks[*index].position);
+        self.densities_being_baked.extend(positions_to_bake);
+
+        let chunks = self.get_chunks();
+        let generation = self.mesh_generation;
+        let job = jobs::spawn("density bake", Priority::High, 0, move |context| {
+            chunks_to_bake.into_par_iter().for_each(|(index, tx)| {
+                if context.is_cancelled() {
+                    return;
+                }
+
+                let density = DensityVolume::bake_chunk(&chunks, index, options);
+                let chunk_position = chunks[index].position;
+
+                // The world was dropped, nobody is waiting for the density
+                let _ = tx.send(BakedDensity(density, chunk_position, generation));
+            });
+        });
+
+        self.density_bakers.push(job);
+    }
+
+    // Puts the baked densities into the world, returns whether any chunk got a new density
+    pub fn update_densities(&mut self) -> bool {
+        let mut any_integrated = false;
+
+        while let Ok(BakedDensity(density, chunk_position, generation)) =
+            self.density_receiver.try_recv()
+        {
+            // Baked from blocks or with options that were changed since
+            if generation != self.mesh_generation {
+                continue;
+            }
+
+            self.densities_being_baked.remove(&chunk_position);
+            if let Some(index) = self.chunk_index_of(chunk_position) {
+                self.chunk_densities[index] = Some(Arc::new(density));
+                any_integrated = true;
+            }
+        }
+
+        self.density_bakers.retain(|baker| !baker.is_finished());
+
+        any_integrated
+    }
+
+    // Applies changes of the material registry to the loaded chunks and rebuilds all meshes
+    pub fn refresh_materials(&mut self, options: PolygonizationOptions) {
+        for chunk in self.chunks.iter_mut() {
+            Arc::make_mut(chunk).refresh_rigid_blocks();
+        }
+
+        self.rebuild_all_meshes(options);
+        self.dispatch_instance_data_builder();
     }
 
+    // Applies changed activation thresholds to the built meshes without polygonizing them again,
+    // the vertices keep the material samples the weights are computed from
+    pub fn recolor_meshes(&mut self) {
+        let materials = material_registry::snapshot();
+        for index in 0..self.chunks.len() {
+            let mesh = std::mem::replace(&mut self.chunk_meshes[index], Lazy::new()).into_inner();
+            if let Some(mut mesh) = mesh {
+                mesh.recolor(&materials);
+                self.chunk_meshes[index].get_or_create(|| mesh);
+            }
+
+            if let Some(retired) = self.retired_meshes[index].as_mut() {
+                retired.recolor(&materials);
+            }
+        }
+    }
+
+    // A chunk rebuilt again before its new mesh arrived keeps the mesh retired first,
+    // that one is still on screen
+    fn retire_mesh(&mut self, index: usize) {
+        let mesh = std::mem::replace(&mut self.chunk_meshes[index], Lazy::new()).into_inner();
+        if self.retired_meshes[index].is_none() {
+            self.retired_meshes[index] = mesh;
+        }
+    }
+
+    // Meshes replaced by their rebuilt version since the last call, see infrastructure/crossfade.rs
+    pub fn take_replaced_meshes(&mut self) -> Vec<Mesh> {
+        std::mem::take(&mut self.replaced_meshes)
+    }
+
+    // Meshes still being built are dropped when they arrive,
+    // the next dispatch builds every chunk without a mesh again
+    fn cancel_mesh_builds(&mut self) {
+        self.mesh_generation += 1;
+        for builder in &self.mesh_builders {
+            builder.cancel();
+        }
+        self.meshes_being_built.clear();
+
+        // The densities are baked from the same blocks and options
+        for baker in &self.density_bakers {
+            baker.cancel();
+        }
+        self.densities_being_baked.clear();
+    }
+
+    // Rebuilds the mesh of a single chunk with the current options,
+    // returns false when the chunk isn't polygonized
+    pub fn rebuild_chunk_mesh(
+        &mut self,
+        chunk_position: ChunkPosition,
+        options: PolygonizationOptions,
+    ) -> bool {
+        let index = self
+            .inner_chunk_indices()
+            .into_iter()
+            .find(|index| self.chunks[*index].position == chunk_position);
+        let Some(index) = index else {
+            return false;
+        };
+
+        // A build already running for the chunk takes the place of the rebuild
+        if !self.meshes_being_built.contains(&chunk_position) {
+            self.retire_mesh(index);
+            self.chunk_mesh_lods[index] = None;
+            self.chunk_densities[index] = None;
+            self.chunk_build_costs[index] = None;
+            self.dispatch_mesh_builder(options);
+        }
+
+        true
+    }
+
+    // Reads the chunk from the save file again, returns false when the chunk isn't loaded.
+    // The meshes around it keep showing until they are rebuilt from the reloaded blocks.
+    pub fn reload_chunk(&mut self, chunk_position: ChunkPosition) -> bool {
+        let Some(index) = self.chunk_index_of(chunk_position) else {
+            return false;
+        };
+
+        // Builds still running would bring back meshes of the old blocks
+        self.cancel_mesh_builds();
+        for neighbor in World::neighbourhood(self.chunks.size(), index) {
+            self.retire_mesh(neighbor);
+            self.chunk_mesh_lods[neighbor] = None;
+            self.chunk_densities[neighbor] = None;
+            self.chunk_build_costs[neighbor] = None;
+        }
+
+        minecraft::forget_region(chunk_position);
+        self.dispatch_chunk_load(chunk_position);
+
+        true
+    }
+
+    // Stops the mesh builds and waits for the builder jobs until the deadline of *shutdown*.
+    // Queued chunk loads are cancelled, the running ones only read the save and are left to finish.
+    pub fn shut_down(&mut self, shutdown: &Shutdown) {
+        self.cancel_jobs();
+
+        let builders = std::mem::take(&mut self.mesh_builders);
+        let finished = shutdown.wait("the mesh builders", || {
+            builders.iter().all(JobHandle::is_finished)
+        });
+        if finished {
+            for builder in builders {
+                let name = builder.name();
+                if let Err(panic_message) = builder.join() {
+                    println!("The {name} job panicked! - {panic_message:?}");
+                }
+            }
+        }
+
+        let bakers = std::mem::take(&mut self.density_bakers);
+        shutdown.wait("the density bakers", || {
+            bakers.iter().all(JobHandle::is_finished)
+        });
+
+        if let Some(builder) = self.instance_data_builder.take() {
+            shutdown.wait("the instance data builder", || builder.is_finished());
+        }
+    }
+
+    // Cancels the mesh builds, the density bakes and the queued chunk loads without waiting for them,
+    // so a world that is about to be replaced doesn't hold on to the job workers
+    pub fn cancel_jobs(&mut self) {
+        self.cancel_mesh_builds();
+        for loader in &self.chunk_loaders {
+            loader.cancel();
+        }
+    }
+
+    // Builds still running with the previous options are cancelled
     pub fn rebuild_all_meshes(&mut self, options: PolygonizationOptions) {
-        for i in 0..CHUNKS_IN_WORLD {
-            self.chunk_meshes[i] = Lazy::new();
+        self.cancel_mesh_builds();
+
+        for i in 0..self.chunks.len() {
+            self.retire_mesh(i);
+            self.chunk_mesh_lods[i] = None;
+            self.chunk_densities[i] = None;
+            self.chunk_build_costs[i] = None;
         }
 
         self.dispatch_mesh_builder(options);
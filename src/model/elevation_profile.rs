@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use cgmath::MetricSpace;
+
+use super::discrete::World;
+use super::{Coord, PlanarPosition};
+
+// Distance between two samples of the profile, one sample per block
+const SAMPLE_SPACING: Coord = 1.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ProfileSample {
+    // Distance from the start of the line
+    pub distance: Coord,
+    pub position: PlanarPosition,
+    // None where the chunk isn't loaded or the column has no blocks
+    pub height: Option<isize>,
+}
+
+// Surface height along a straight line in the xz plane
+pub struct ElevationProfile {
+    pub start: PlanarPosition,
+    pub end: PlanarPosition,
+    pub samples: Vec<ProfileSample>,
+}
+
+impl ElevationProfile {
+    pub fn sample(world: &World, start: PlanarPosition, end: PlanarPosition) -> Self {
+        let length = start.distance(end);
+        let sample_count = (length / SAMPLE_SPACING).ceil() as usize + 1;
+
+        let samples = (0..sample_count)
+            .map(|i| {
+                let t = match sample_count {
+                    1 => 0.0,
+                    _ => i as Coord / (sample_count - 1) as Coord,
+                };
+                let position = start + (end - start) * t;
+
+                ProfileSample {
+                    distance: length * t,
+                    position,
+                    height: world.get_surface_height(position),
+                }
+            })
+            .collect();
+
+        ElevationProfile {
+            start,
+            end,
+            samples,
+        }
+    }
+
+    pub fn length(&self) -> Coord {
+        self.start.distance(self.end)
+    }
+
+    // Lowest and highest sampled height, None if no sample hit the terrain
+    pub fn height_range(&self) -> Option<(isize, isize)> {
+        let heights = self.samples.iter().filter_map(|sample| sample.height);
+        let min = heights.clone().min()?;
+        let max = heights.max()?;
+
+        Some((min, max))
+    }
+
+    // One line per sample, samples without terrain have an empty height
+    pub fn export_csv(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writeln!(writer, "distance,x,z,height")?;
+        for sample in &self.samples {
+            let height = sample
+                .height
+                .map(|height| height.to_string())
+                .unwrap_or_default();
+            writeln!(
+                writer,
+                "{:.2},{:.2},{:.2},{}",
+                sample.distance, sample.position.x, sample.position.y, height
+            )?;
+        }
+
+        writer.flush()
+    }
+}
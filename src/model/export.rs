@@ -0,0 +1,186 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use serde_json::json;
+
+use super::polygonize::Mesh;
+
+// File formats the smooth mesh can be exported to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshFormat {
+    // Positions, normals and triangles
+    Obj,
+    // Binary glTF 2.0 with the material weights as custom vertex attributes
+    Glb,
+}
+
+impl MeshFormat {
+    // GLB when the path ends with .glb, OBJ otherwise
+    pub fn from_path(path: &str) -> Self {
+        match path.to_lowercase().ends_with(".glb") {
+            true => MeshFormat::Glb,
+            false => MeshFormat::Obj,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MeshFormat::Obj => "obj",
+            MeshFormat::Glb => "glb",
+        }
+    }
+}
+
+pub fn write_mesh(mesh: &Mesh, format: MeshFormat, writer: &mut impl Write) -> io::Result<()> {
+    match format {
+        MeshFormat::Obj => mesh.write_obj(writer),
+        MeshFormat::Glb => write_glb(mesh, writer),
+    }
+}
+
+pub fn save_mesh(mesh: &Mesh, path: &str, format: MeshFormat) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_mesh(mesh, format, &mut writer)?;
+
+    writer.flush()
+}
+
+// Constants of the glTF 2.0 specification
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const MODE_TRIANGLES: u32 = 4;
+
+// The mesh as a single glTF primitive with all of its data in the binary chunk.
+//
+// Each of the 16 material weights of a vertex is split into four VEC4 attributes named
+// _MATERIAL_WEIGHTS_0 to _MATERIAL_WEIGHTS_3, in the order of the material indices.
+// Blender imports them as color attributes of the mesh.
+pub fn write_glb(mesh: &Mesh, writer: &mut impl Write) -> io::Result<()> {
+    if mesh.indices.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "glTF can't hold an empty mesh",
+        ));
+    }
+
+    // Every attribute gets a view of its own, the views follow each other in the buffer
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut views = Vec::new();
+    let mut add_view = |data: Vec<u8>, target: u32| {
+        let offset = buffer.len();
+        buffer.extend(data);
+        views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": buffer.len() - offset,
+            "target": target,
+        }));
+        views.len() - 1
+    };
+
+    let vertices = &mesh.vertices;
+    let positions = float_bytes(vertices.iter().flat_map(|vertex| &vertex.position));
+    let position_view = add_view(positions, TARGET_ARRAY_BUFFER);
+    let normals = float_bytes(vertices.iter().flat_map(|vertex| &vertex.normal));
+    let normal_view = add_view(normals, TARGET_ARRAY_BUFFER);
+    let weight_views = [0, 1, 2, 3].map(|part| {
+        let weights = vertices
+            .iter()
+            .flat_map(|vertex| &vertex.vertex_material_weights[part]);
+        add_view(float_bytes(weights), TARGET_ARRAY_BUFFER)
+    });
+    let indices = mesh.indices.iter().flat_map(|index| index.to_le_bytes());
+    let index_view = add_view(indices.collect(), TARGET_ELEMENT_ARRAY_BUFFER);
+
+    // The position accessor has to state the bounds of the mesh
+    let bounds = ([f32::MAX; 3], [f32::MIN; 3]);
+    let (min, max) = vertices.iter().fold(bounds, |(min, max), vertex| {
+        let position = vertex.position;
+        (
+            [0, 1, 2].map(|axis| min[axis].min(position[axis])),
+            [0, 1, 2].map(|axis| max[axis].max(position[axis])),
+        )
+    });
+
+    let vertex_count = vertices.len();
+    let mut accessors = vec![
+        json!({
+            "bufferView": position_view,
+            "componentType": COMPONENT_FLOAT,
+            "count": vertex_count,
+            "type": "VEC3",
+            "min": min,
+            "max": max,
+        }),
+        json!({
+            "bufferView": normal_view,
+            "componentType": COMPONENT_FLOAT,
+            "count": vertex_count,
+            "type": "VEC3",
+        }),
+    ];
+    let mut attributes = json!({ "POSITION": 0, "NORMAL": 1 });
+    for (part, view) in weight_views.into_iter().enumerate() {
+        attributes[format!("_MATERIAL_WEIGHTS_{part}")] = json!(accessors.len());
+        accessors.push(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": vertex_count,
+            "type": "VEC4",
+        }));
+    }
+    accessors.push(json!({
+        "bufferView": index_view,
+        "componentType": COMPONENT_UNSIGNED_INT,
+        "count": mesh.indices.len(),
+        "type": "SCALAR",
+    }));
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "dd-terrain" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "name": "smooth terrain" }],
+        "meshes": [{
+            "primitives": [{
+                "attributes": attributes,
+                "indices": accessors.len() - 1,
+                "mode": MODE_TRIANGLES,
+            }],
+        }],
+        "accessors": accessors,
+        "bufferViews": views,
+        "buffers": [{ "byteLength": buffer.len() }],
+    });
+
+    // Chunks are padded to 4 bytes, the JSON with spaces
+    let mut json_chunk = document.to_string().into_bytes();
+    json_chunk.resize(padded_length(json_chunk.len()), b' ');
+    buffer.resize(padded_length(buffer.len()), 0);
+
+    let total_length = 12 + 8 + json_chunk.len() + 8 + buffer.len();
+    for header in [GLB_MAGIC, GLB_VERSION, total_length as u32] {
+        writer.write_all(&header.to_le_bytes())?;
+    }
+    for (chunk_type, chunk) in [(CHUNK_JSON, &json_chunk), (CHUNK_BIN, &buffer)] {
+        writer.write_all(&(chunk.len() as u32).to_le_bytes())?;
+        writer.write_all(&chunk_type.to_le_bytes())?;
+        writer.write_all(chunk)?;
+    }
+
+    Ok(())
+}
+
+fn float_bytes<'a>(values: impl Iterator<Item = &'a f32>) -> Vec<u8> {
+    values.flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn padded_length(length: usize) -> usize {
+    length + (4 - length % 4) % 4
+}
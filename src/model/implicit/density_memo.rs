@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::infrastructure::texture::MaterialBlend;
+use crate::model::biome::{self, Biome};
+use crate::model::common::{BlockType, MaterialSetup};
+use crate::model::{Coord, Position, Real};
+
+// The grid points of every level of detail lie on the grid of the finest cells,
+// one over the smallest cell size of cell_size_for_level
+const GRID_RESOLUTION: Real = 8.0;
+
+// Locks the lookups are spread over, so the parallel grid sampling rarely waits for another thread
+const SHARDS: usize = 16;
+
+// Grid point in the finest cells and the kernel size
+type MemoKey = ([i64; 3], u64);
+
+// Volume of every material and biome inside a kernel, the blocks below the world count
+// as solid ground. One scan answers the density and the material blend of any material setup,
+// so the passes with different materials can share it.
+#[derive(Clone, Debug, Default)]
+pub struct KernelScan {
+    volumes: Vec<(BlockType, Biome, Real)>,
+}
+
+impl KernelScan {
+    pub fn add(&mut self, material: BlockType, biome: Biome, volume: Real) {
+        let scanned = self
+            .volumes
+            .iter_mut()
+            .find(|(scanned_material, scanned_biome, _)| {
+                *scanned_material == material && *scanned_biome == biome
+            });
+
+        match scanned {
+            Some((_, _, total)) => *total += volume,
+            None => self.volumes.push((material, biome, volume)),
+        }
+    }
+
+    pub fn smoothable_volume(&self, material_setup: &MaterialSetup) -> Real {
+        self.volumes
+            .iter()
+            .filter(|(material, _, _)| material_setup.is_material_smoothable(*material))
+            .map(|(_, _, volume)| *volume)
+            .sum()
+    }
+
+    pub fn material_blend(&self, material_setup: &MaterialSetup) -> MaterialBlend {
+        let mut blend = MaterialBlend::new();
+        let contributing = self
+            .volumes
+            .iter()
+            .filter(|(material, _, _)| material_setup.contributes_color(*material));
+
+        for &(material, biome, volume) in contributing {
+            blend.mix(material, volume);
+            if biome::is_biome_tinted(material) {
+                blend.mix_biome(biome, volume);
+            }
+        }
+
+        blend
+    }
+}
+
+// Kernel scans of a single chunk build, shared by all of its passes.
+//
+// The terrain and the leaves pass sample their densities on the same grid, and with the leaves kernel
+// locked to the default smoothness, with the same kernel. The border stitching samples the grid points
+// of the coarser neighbour again, and the material passes look up the scans of their points as well.
+// Scans are keyed by the grid point and the kernel size, points between the grid points are scanned
+// without being kept.
+pub struct DensityMemo {
+    shards: Vec<Mutex<HashMap<MemoKey, Arc<KernelScan>>>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoStats {
+    pub hits: usize,
+    // Every miss is a scan of the blocks inside a kernel
+    pub misses: usize,
+}
+
+impl MemoStats {
+    // Portion of the lookups answered from the memo
+    pub fn reuse_rate(&self) -> Real {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as Real / lookups as Real,
+        }
+    }
+}
+
+impl DensityMemo {
+    pub fn new() -> Self {
+        DensityMemo {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn get_or_scan(
+        &self,
+        point: Position,
+        kernel_size: Coord,
+        scan: impl FnOnce() -> KernelScan,
+    ) -> Arc<KernelScan> {
+        let Some(key) = grid_key(point, kernel_size) else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Arc::new(scan());
+        };
+
+        let shard = &self.shards[shard_index(&key)];
+        if let Some(scanned) = shard.lock().unwrap().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Arc::clone(scanned);
+        }
+
+        // The lock is not held during the scan, at worst two threads scan the same point
+        // and the first result is kept, both are the same
+        let scanned = Arc::new(scan());
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let mut shard = shard.lock().unwrap();
+
+        Arc::clone(shard.entry(key).or_insert(scanned))
+    }
+
+    pub fn stats(&self) -> MemoStats {
+        MemoStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn grid_key(point: Position, kernel_size: Coord) -> Option<MemoKey> {
+    let scaled = [point.x, point.y, point.z].map(|coord| coord * GRID_RESOLUTION);
+    let on_grid = scaled.iter().all(|coord| coord.fract() == 0.0);
+
+    on_grid.then(|| (scaled.map(|coord| coord as i64), kernel_size.to_bits()))
+}
+
+fn shard_index(key: &MemoKey) -> usize {
+    let ([x, y, z], _) = *key;
+    let hash = x ^ y.wrapping_mul(31) ^ z.wrapping_mul(961);
+
+    hash.rem_euclid(SHARDS as i64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_points_are_scanned_once_per_kernel() {
+        let memo = DensityMemo::new();
+        let scans = AtomicUsize::new(0);
+        let scan = || {
+            scans.fetch_add(1, Ordering::Relaxed);
+            KernelScan::default()
+        };
+
+        let point = Position::new(16.0, 60.125, -3.5);
+        memo.get_or_scan(point, 0.9, scan);
+        memo.get_or_scan(point, 0.9, scan);
+        memo.get_or_scan(point, 1.3, scan);
+
+        assert_eq!(scans.load(Ordering::Relaxed), 2);
+        let stats = memo.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+    }
+
+    #[test]
+    fn points_between_the_grid_points_are_not_kept() {
+        let memo = DensityMemo::new();
+        let point = Position::new(16.0, 60.3, -3.5);
+
+        memo.get_or_scan(point, 0.9, KernelScan::default);
+        memo.get_or_scan(point, 0.9, KernelScan::default);
+
+        let stats = memo.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 2));
+    }
+
+    #[test]
+    fn one_scan_answers_every_material_setup() {
+        let mut scan = KernelScan::default();
+        scan.add(BlockType::Stone, Biome::default(), 0.5);
+        scan.add(BlockType::Leaves, Biome::default(), 0.25);
+        scan.add(BlockType::Stone, Biome::default(), 0.125);
+
+        let terrain = MaterialSetup::exclude([BlockType::Leaves], []);
+        let leaves = MaterialSetup::include([BlockType::Leaves], []);
+        let everything = MaterialSetup::all_smooth([]);
+
+        assert_eq!(scan.smoothable_volume(&terrain), 0.625);
+        assert_eq!(scan.smoothable_volume(&leaves), 0.25);
+        assert_eq!(scan.smoothable_volume(&everything), 0.875);
+    }
+}
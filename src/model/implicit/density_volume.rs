@@ -0,0 +1,121 @@
+use rayon::prelude::*;
+
+use crate::model::{
+    discrete::WorldChunks,
+    polygonize::{PolygonizationOptions, Rectangle3D},
+    Position, Real,
+};
+
+use super::smooth::{chunk_support, terrain_density};
+
+// Distance between the baked density samples of a chunk
+const BAKED_CELL_SIZE: Real = 1.0;
+
+// Density of the terrain sampled on a regular grid.
+// Values are stored with x changing fastest, then y, then z,
+// which is the layout 3D textures expect.
+pub struct DensityVolume {
+    // Position of the first sample
+    pub origin: Position,
+    pub cell_size: Real,
+    // Number of samples along x, y, z
+    pub resolution: [usize; 3],
+    pub values: Vec<f32>,
+}
+
+impl DensityVolume {
+    pub fn sample(
+        chunks: &WorldChunks,
+        support: Rectangle3D,
+        cell_size: Real,
+        options: PolygonizationOptions,
+    ) -> Self {
+        DensityVolume::sample_density(support, cell_size, terrain_density(chunks, options))
+    }
+
+    // Samples any density function, the samples are evaluated in parallel
+    pub fn sample_density(
+        support: Rectangle3D,
+        cell_size: Real,
+        density_func: impl Fn(Position) -> Real + Sync,
+    ) -> Self {
+        let resolution = [
+            (support.width / cell_size) as usize + 1,
+            (support.height / cell_size) as usize + 1,
+            (support.depth / cell_size) as usize + 1,
+        ];
+        let [size_x, size_y, size_z] = resolution;
+
+        let values = (0..size_x * size_y * size_z)
+            .into_par_iter()
+            .map(|index| {
+                let x = index % size_x;
+                let y = (index / size_x) % size_y;
+                let z = index / (size_x * size_y);
+
+                let point = Position::new(
+                    support.position.x + x as Real * cell_size,
+                    support.position.y + y as Real * cell_size,
+                    support.position.z + z as Real * cell_size,
+                );
+
+                density_func(point) as f32
+            })
+            .collect();
+
+        DensityVolume {
+            origin: support.position,
+            cell_size,
+            resolution,
+            values,
+        }
+    }
+
+    // Samples the polygonized part of the chunk, neighboring chunks share the samples on their
+    // common border
+    pub fn bake_chunk(
+        chunks: &WorldChunks,
+        chunk_index: usize,
+        options: PolygonizationOptions,
+    ) -> Self {
+        let support = chunk_support(&chunks[chunk_index], options);
+        DensityVolume::sample(chunks, support, BAKED_CELL_SIZE, options)
+    }
+
+    // Size of the sampled area in world units
+    pub fn extent(&self) -> [f32; 3] {
+        self.resolution
+            .map(|samples| ((samples - 1) as Real * self.cell_size) as f32)
+    }
+
+    // Trilinear interpolation of the samples, None outside of the sampled area
+    pub fn interpolate(&self, point: Position) -> Option<Real> {
+        let local = [
+            (point.x - self.origin.x) / self.cell_size,
+            (point.y - self.origin.y) / self.cell_size,
+            (point.z - self.origin.z) / self.cell_size,
+        ];
+
+        let last = self.resolution.map(|samples| samples.max(2) - 1);
+        let inside = (0..3).all(|axis| (0.0..=last[axis] as Real).contains(&local[axis]));
+        if !inside {
+            return None;
+        }
+
+        let base = [0, 1, 2].map(|axis| (local[axis] as usize).min(last[axis] - 1));
+        let fraction = [0, 1, 2].map(|axis| local[axis] - base[axis] as Real);
+        let [size_x, size_y, size_z] = self.resolution;
+        let value = |x: usize, y: usize, z: usize| {
+            let index = (base[0] + x).min(size_x - 1)
+                + (base[1] + y).min(size_y - 1) * size_x
+                + (base[2] + z).min(size_z - 1) * size_x * size_y;
+            self.values[index] as Real
+        };
+
+        let lerp = |a: Real, b: Real, t: Real| a + (b - a) * t;
+        let along_x = |y, z| lerp(value(0, y, z), value(1, y, z), fraction[0]);
+        let along_y = |z| lerp(along_x(0, z), along_x(1, z), fraction[1]);
+
+        Some(lerp(along_y(0), along_y(1), fraction[2]))
+    }
+}
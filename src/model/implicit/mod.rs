@@ -1,3 +1,5 @@
+pub mod density_memo;
+pub mod density_volume;
 pub mod normal;
 pub mod sdf;
 pub mod smooth;
@@ -1,29 +1,52 @@
 use cgmath::Vector3;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::{
-    config,
     infrastructure::texture::MaterialBlend,
     minecraft,
     model::{
-        common::{BlockType, MaterialSetup, RIGID_MATERIALS},
+        chunk::{Chunk, LightLevel},
+        common::MaterialSetup,
         discrete::{World, WorldChunks},
-        polygonize::{polygonize, Mesh, PolygonizationOptions, Rectangle3D},
+        material_registry::SmoothingPass,
+        polygonize::{
+            add_caps, add_skirts, polygonize, stitch_chunk_borders, ChunkLod, Mesh,
+            PolygonizationOptions, Rectangle3D,
+        },
         rectangle::Rectangle,
         Coord, PlanarPosition, Position, Real,
     },
 };
 
+use super::density_memo::{DensityMemo, KernelScan, MemoStats};
+use super::density_volume::DensityVolume;
 use super::normal;
 use super::sdf;
 
-pub fn get_density(world: &World, point: Position, kernel_size: Coord) -> Real {
+pub fn get_density(world: &World, point: Position, options: PolygonizationOptions) -> Real {
     let chunks = world.get_chunks();
-    evaluate_density_rigid(&chunks, point, kernel_size, &terrain_setup())
+    evaluate_density_rigid(&chunks, point, options, &terrain_setup(options), None)
 }
 
-pub fn get_smooth_normal(world: &World, point: Position, kernel_size: Coord) -> Vector3<Real> {
+// Density function of the terrain pass, for sampling many points at once
+pub fn terrain_density(
+    chunks: &WorldChunks,
+    options: PolygonizationOptions,
+) -> impl Fn(Position) -> Real + Send + Sync + '_ {
+    let setup = terrain_setup(options);
+    move |point| evaluate_density_rigid(chunks, point, options, &setup, None)
+}
+
+pub fn get_smooth_normal(
+    world: &World,
+    point: Position,
+    options: PolygonizationOptions,
+) -> Vector3<Real> {
     let chunks = world.get_chunks();
-    let sdf = |p| evaluate_density_rigid(&chunks, p, kernel_size, &terrain_setup());
+    let setup = terrain_setup(options);
+    let sdf = |p| evaluate_density_rigid(&chunks, p, options, &setup, None);
 
     normal::gradient(sdf, point)
 }
@@ -65,79 +88,266 @@ impl Kernel {
     }
 }
 
-pub fn polygonize_chunk(
-    chunks: &WorldChunks,
-    chunk_index: usize,
-    options: PolygonizationOptions,
-) -> Mesh {
-    let chunk = chunks[chunk_index].clone();
-    let support_xz = chunk.position.get_global_position();
+// The part of the chunk that is polygonized
+pub fn chunk_support(chunk: &Chunk, options: PolygonizationOptions) -> Rectangle3D {
+    let support_xz = chunk.coords().origin();
 
     let support_low_y = options.y_low_limit;
     let support_y_size = options.y_size;
 
-    let support = Rectangle3D {
+    Rectangle3D {
         position: Position::new(support_xz.x, support_low_y, support_xz.y),
         width: minecraft::BLOCKS_IN_CHUNK as Real,
         depth: minecraft::BLOCKS_IN_CHUNK as Real,
         height: support_y_size,
+    }
+}
+
+// Kernel of the leaves with lock_leaves, the default smoothness as the controls store it,
+// so at the default smoothness both passes use the same kernel and share their scans
+const LOCKED_LEAVES_KERNEL_SIZE: f32 = 0.9;
+
+// How much work building the mesh of a chunk took
+#[derive(Clone, Copy, Debug)]
+pub struct BuildCost {
+    pub duration: Duration,
+    // Evaluations of the density kernel, the density cache cuts them down
+    pub density_evaluations: usize,
+    // Kernel scans of the blocks and how many of them the passes shared
+    pub density_memo: MemoStats,
+}
+
+pub fn polygonize_chunk(
+    chunks: &WorldChunks,
+    chunk_index: usize,
+    options: PolygonizationOptions,
+    lod: ChunkLod,
+) -> (Mesh, BuildCost) {
+    let (mesh, cost, _) = polygonize_chunk_with_density(chunks, chunk_index, options, lod, None);
+    (mesh, cost)
+}
+
+// With the density cache on, the terrain pass samples *baked*, the baked density of the chunk,
+// when it lies on the grid of *lod*. Otherwise the pass samples a volume of its own,
+// which is returned so it can take the place of the baked density.
+pub fn polygonize_chunk_with_density(
+    chunks: &WorldChunks,
+    chunk_index: usize,
+    options: PolygonizationOptions,
+    lod: ChunkLod,
+    baked: Option<&DensityVolume>,
+) -> (Mesh, BuildCost, Option<DensityVolume>) {
+    let options = PolygonizationOptions {
+        marching_cubes_cell_size: lod.cell_size,
+        ..options
     };
 
-    let terrain_mesh = {
-        let terrain_setup = terrain_setup();
+    let start = Instant::now();
+    let evaluations = AtomicUsize::new(0);
+    let memo = DensityMemo::new();
+    let measure = || BuildCost {
+        duration: start.elapsed(),
+        density_evaluations: evaluations.load(Ordering::Relaxed),
+        density_memo: memo.stats(),
+    };
+
+    let support = chunk_support(&chunks[chunk_index], options);
+
+    let (terrain_mesh, sampled_density) = {
+        let terrain_setup = terrain_setup(options);
 
-        let density_func =
-            |p| evaluate_density_rigid(&chunks, p, options.kernel_size, &terrain_setup);
+        let exact_density = |p| {
+            evaluations.fetch_add(1, Ordering::Relaxed);
+            evaluate_density_rigid(&chunks, p, options, &terrain_setup, Some(&memo))
+        };
+        let reused = baked.filter(|volume| {
+            options.density_cache && volume.cell_size == options.marching_cubes_cell_size
+        });
+        let sampled = match reused {
+            Some(_) => None,
+            None => density_cache(support, options, &exact_density),
+        };
+        let cache = reused.or(sampled.as_ref());
+        let density_func = |p| cached_density(cache, p, &exact_density);
         let material_func = |p| {
             sample_materials(
                 &chunks,
                 p,
                 material_sample_kernel_size(options.kernel_size),
                 &terrain_setup,
+                Some(&memo),
             )
         };
 
-        polygonize(support, density_func, material_func, options)
+        let mut mesh = polygonize(support, &density_func, &material_func, options);
+        stitch_chunk_borders(
+            &mut mesh,
+            support,
+            lod,
+            &density_func,
+            options.surface_level,
+        );
+
+        if options.skirts {
+            add_skirts(&mut mesh, support, lod.cell_size);
+        }
+        if options.caps {
+            add_caps(
+                &mut mesh,
+                support,
+                lod,
+                &density_func,
+                &material_func,
+                options,
+            );
+        }
+
+        (mesh, sampled)
     };
 
-    if config::MULTIPASS == false {
-        return terrain_mesh;
+    if options.settings.multipass == false {
+        let mut terrain_mesh = terrain_mesh;
+        apply_light(&mut terrain_mesh, chunks);
+
+        return (terrain_mesh, measure(), sampled_density);
     }
 
     let leaves_mesh = {
-        let leaves_setup = MaterialSetup::include([BlockType::Leaves], []);
+        let leaves_setup =
+            MaterialSetup::include(options.materials.materials_in(SmoothingPass::Leaves), []);
 
-        let leaves_kernel_size = if config::LOCK_LEAVES {
-            0.9
+        let leaves_kernel_size = if options.settings.lock_leaves {
+            LOCKED_LEAVES_KERNEL_SIZE as Coord
         } else {
             options.kernel_size
         };
 
-        let density_func =
-            |p| evaluate_density_rigid(&chunks, p, leaves_kernel_size, &leaves_setup);
+        let leaves_options = PolygonizationOptions {
+            kernel_size: leaves_kernel_size,
+            ..options
+        };
+        let exact_density = |p| {
+            evaluations.fetch_add(1, Ordering::Relaxed);
+            evaluate_density_rigid(&chunks, p, leaves_options, &leaves_setup, Some(&memo))
+        };
+        let cache = density_cache(support, options, &exact_density);
+        let density_func = |p| cached_density(cache.as_ref(), p, &exact_density);
         let material_func = |p| {
             sample_materials(
                 &chunks,
                 p,
                 material_sample_kernel_size(leaves_kernel_size),
                 &leaves_setup,
+                Some(&memo),
             )
         };
 
-        polygonize(support, density_func, material_func, options)
+        let mut mesh = polygonize(support, &density_func, &material_func, options);
+        stitch_chunk_borders(
+            &mut mesh,
+            support,
+            lod,
+            &density_func,
+            options.surface_level,
+        );
+
+        if options.skirts {
+            add_skirts(&mut mesh, support, lod.cell_size);
+        }
+        if options.caps {
+            add_caps(
+                &mut mesh,
+                support,
+                lod,
+                &density_func,
+                &material_func,
+                options,
+            );
+        }
+
+        mesh
     };
 
-    Mesh::merge(&mut [terrain_mesh, leaves_mesh])
+    let mut mesh = Mesh::merge(&mut [terrain_mesh, leaves_mesh]);
+    apply_light(&mut mesh, chunks);
+
+    (mesh, measure(), sampled_density)
+}
+
+// Density of the pass sampled once on the grid of the chunk support, the same volume
+// DensityVolume::bake_chunk bakes on that grid. The volume stores f32 samples, so even
+// the grid points read back rounded, and the points in between, where the normals,
+// the stitching and the caps sample, are interpolated instead of evaluating the kernel again.
+fn density_cache(
+    support: Rectangle3D,
+    options: PolygonizationOptions,
+    density_func: &(impl Fn(Position) -> Real + Sync),
+) -> Option<DensityVolume> {
+    if !options.density_cache {
+        return None;
+    }
+
+    Some(DensityVolume::sample_density(
+        support,
+        options.marching_cubes_cell_size,
+        density_func,
+    ))
+}
+
+// Points outside of the cache are evaluated exactly
+fn cached_density(
+    cache: Option<&DensityVolume>,
+    point: Position,
+    density_func: &impl Fn(Position) -> Real,
+) -> Real {
+    cache
+        .and_then(|cache| cache.interpolate(point))
+        .unwrap_or_else(|| density_func(point))
+}
+
+// The light of a vertex is the light stored in the block just outside the surface
+fn apply_light(mesh: &mut Mesh, chunks: &WorldChunks) {
+    for vertex in mesh.vertices.iter_mut() {
+        let [x, y, z] = vertex.position.map(|coord| coord as Coord);
+        let [nx, ny, nz] = vertex.normal.map(|coord| coord as Coord);
+        let outside_position = Position::new(x + 0.5 * nx, y + 0.5 * ny, z + 0.5 * nz);
+
+        vertex.light = sample_light(chunks, outside_position).normalized();
+    }
+}
+
+pub fn sample_light(chunks: &WorldChunks, position: Position) -> LightLevel {
+    let chunk_position = minecraft::get_minecraft_chunk_position(position);
+    let Some(chunk) = chunks.iter().find(|chunk| chunk.position == chunk_position) else {
+        return LightLevel::default();
+    };
+
+    let (block_x, block_z) = Chunk::get_block_coords(position.x, position.z);
+    chunk.get_light(block_x, position.y.floor() as isize, block_z)
 }
 
 const RIGID_BLOCK_SMOOTHNESS: Real = 1.0;
 fn evaluate_density_rigid(
     model: &WorldChunks,
     point: Position,
-    kernel_size: Coord,
+    options: PolygonizationOptions,
     material_setup: &MaterialSetup,
+    memo: Option<&DensityMemo>,
 ) -> Real {
-    let model_distance = -evaluate_density(model, point, kernel_size, material_setup);
+    let kernel_size = options.kernel_size;
+    let approximation = options
+        .octree_density
+        .then(|| octree_approximation_size(kernel_size));
+
+    let density = evaluate_density(
+        model,
+        point,
+        kernel_size,
+        approximation,
+        material_setup,
+        memo,
+    );
+    let model_distance = -options.density_remap.apply(density);
     let rigid_distance = distance_to_rigid_blocks(model, point, kernel_size, material_setup);
 
     match rigid_distance {
@@ -162,11 +372,11 @@ fn distance_to_rigid_blocks(
     let y_low = kernel.y_low();
     let y_high = kernel.y_high();
 
-    let closest_rigid_block = chunks
-        .iter()
+    let closest_rigid_block = World::chunks_overlapping(chunks, kernel_box)
         .filter_map(|chunk| {
             chunk
-                .get_bounding_rectangle()
+                .coords()
+                .bounds()
                 .intersect(kernel_box)
                 .map(|intersection| (chunk, intersection))
         })
@@ -201,27 +411,47 @@ fn evaluate_density(
     chunks: &WorldChunks,
     point: Position,
     kernel_size: Coord,
+    approximation_size: Option<usize>,
     material_setup: &MaterialSetup,
+    memo: Option<&DensityMemo>,
 ) -> Real {
     let kernel = Kernel::new(point, kernel_size);
-    return sample_volume(chunks, kernel, material_setup) / kernel.volume_half() - 1.0;
+    let volume = match approximation_size {
+        Some(size) => sample_volume_approx(chunks, kernel, size, material_setup),
+        None => scan_kernel(chunks, point, kernel_size, memo).smoothable_volume(material_setup),
+    };
+
+    return volume / kernel.volume_half() - 1.0;
 }
 
-fn sample_volume(chunks: &WorldChunks, kernel: Kernel, material_setup: &MaterialSetup) -> Real {
+// The volume is sampled from the chunk octrees, chunks without one are visited block by block
+fn sample_volume_approx(
+    chunks: &WorldChunks,
+    kernel: Kernel,
+    approximation_size: usize,
+    material_setup: &MaterialSetup,
+) -> Real {
     let kernel_box = kernel.get_bounding_rectangle();
     let y_low = kernel.y_low();
     let y_high = kernel.y_high();
 
-    chunks.iter().fold(0.0, |acc, chunk| {
-        let chunk_box = chunk.get_bounding_rectangle();
-        let Some(intersection) = chunk_box.intersect(kernel_box) else {
+    World::chunks_overlapping(chunks, kernel_box).fold(0.0, |acc, chunk| {
+        let coords = chunk.coords();
+        let Some(intersection) = coords.bounds().intersect(kernel_box) else {
                 return acc;
             };
 
-        let offset = chunk.position.get_global_position().map(|coord| -coord);
-        let intersection_local = intersection.offset_origin(offset);
-        let chunk_volume =
-            chunk.get_chunk_intersection_volume(intersection_local, y_low, y_high, material_setup);
+        let intersection_local = coords.rectangle_to_local(intersection);
+        let approximate_volume = chunk.get_chunk_intersection_volume_approx(
+            intersection_local,
+            y_low,
+            y_high,
+            approximation_size,
+            material_setup,
+        );
+        let chunk_volume = approximate_volume.unwrap_or_else(|| {
+            chunk.get_chunk_intersection_volume(intersection_local, y_low, y_high, material_setup)
+        });
 
         acc + chunk_volume
     })
@@ -232,36 +462,53 @@ fn sample_materials(
     point: Position,
     kernel_size: Coord,
     material_setup: &MaterialSetup,
+    memo: Option<&DensityMemo>,
 ) -> MaterialBlend {
-    let kernel = Kernel::new(point, kernel_size);
-    let kernel_box = kernel.get_bounding_rectangle();
-    let y_low = kernel.y_low();
-    let y_high = kernel.y_high();
+    scan_kernel(chunks, point, kernel_size, memo).material_blend(material_setup)
+}
 
-    chunks
-        .iter()
-        .fold(MaterialBlend::new(), |mut blend, chunk| {
-            let chunk_box = chunk.get_bounding_rectangle();
-            let Some(intersection) = chunk_box.intersect(kernel_box) else {
-                    return blend;
-                };
-
-            let offset = chunk.position.get_global_position().map(|coord| -coord);
-            let intersection_local = intersection.offset_origin(offset);
-            let chunk_volume =
-                chunk.get_material_blend(intersection_local, y_low, y_high, material_setup);
-
-            blend.merge(chunk_volume);
-            blend
-        })
+// The blocks inside the kernel, from *memo* when it was scanned before
+fn scan_kernel(
+    chunks: &WorldChunks,
+    point: Position,
+    kernel_size: Coord,
+    memo: Option<&DensityMemo>,
+) -> Arc<KernelScan> {
+    let scan_blocks = || {
+        let kernel = Kernel::new(point, kernel_size);
+        let kernel_box = kernel.get_bounding_rectangle();
+        let y_low = kernel.y_low();
+        let y_high = kernel.y_high();
+
+        let mut scan = KernelScan::default();
+        for chunk in World::chunks_overlapping(chunks, kernel_box) {
+            let coords = chunk.coords();
+            if let Some(intersection) = coords.bounds().intersect(kernel_box) {
+                let intersection_local = coords.rectangle_to_local(intersection);
+                chunk.scan_kernel(intersection_local, y_low, y_high, &mut scan);
+            }
+        }
+
+        scan
+    };
+
+    match memo {
+        Some(memo) => memo.get_or_scan(point, kernel_size, scan_blocks),
+        None => Arc::new(scan_blocks()),
+    }
 }
 
-fn terrain_setup() -> MaterialSetup {
-    if config::MULTIPASS {
-        MaterialSetup::exclude([BlockType::Leaves], RIGID_MATERIALS)
+fn terrain_setup(options: PolygonizationOptions) -> MaterialSetup {
+    let leaves = options.materials.materials_in(SmoothingPass::Leaves);
+    let rigid = options.materials.materials_in(SmoothingPass::Rigid);
+
+    let setup = if options.settings.multipass {
+        MaterialSetup::exclude(leaves, rigid)
     } else {
-        MaterialSetup::all_smooth(RIGID_MATERIALS)
-    }
+        MaterialSetup::all_smooth(rigid)
+    };
+
+    setup.filtering_rigid(options.settings.filter_rigid)
 }
 
 // The smoothing process shrinks the world down a little
@@ -271,3 +518,119 @@ fn terrain_setup() -> MaterialSetup {
 fn material_sample_kernel_size(density_kernel_size: Coord) -> Coord {
     (density_kernel_size - 0.3).max(0.6)
 }
+
+// Octree nodes this large are small enough compared to the kernel
+// that spreading their blocks evenly doesn't change the density much
+fn octree_approximation_size(kernel_size: Coord) -> usize {
+    let size = kernel_size.floor().max(1.0) as usize;
+
+    // Node sizes are powers of 2
+    if size.is_power_of_two() {
+        size
+    } else {
+        size.next_power_of_two() / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::polygonize::test_support::{
+        self, fixture_world, uniform_lod, FIXTURE_CHUNKS,
+    };
+
+    // The terrain pass samples the volume the density baker bakes on its grid,
+    // so a build handed the baked volume doesn't sample it again
+    #[test]
+    fn build_reusing_the_baked_density_matches_sampling_its_own() {
+        let chunks = fixture_world();
+        let options = PolygonizationOptions {
+            marching_cubes_cell_size: 1.0,
+            density_cache: true,
+            ..test_support::fixture_options()
+        };
+        let (x, z) = FIXTURE_CHUNKS[1];
+        let index = chunks.chunk_index(x, z);
+        let lod = uniform_lod(1.0);
+
+        let (sampled_mesh, _, sampled) =
+            polygonize_chunk_with_density(&chunks, index, options, lod, None);
+        let baked = DensityVolume::bake_chunk(&chunks, index, options);
+        assert_eq!(sampled.unwrap().values, baked.values);
+
+        let (reused_mesh, _, resampled) =
+            polygonize_chunk_with_density(&chunks, index, options, lod, Some(&baked));
+        assert!(resampled.is_none());
+        reused_mesh.assert_identical(&sampled_mesh);
+    }
+
+    fn scanned_options() -> PolygonizationOptions {
+        PolygonizationOptions {
+            octree_density: false,
+            density_cache: false,
+            ..test_support::fixture_options()
+        }
+    }
+
+    // The volume the chunks add up block by block, without a scan
+    fn intersection_volume(chunks: &WorldChunks, kernel: Kernel, setup: &MaterialSetup) -> Real {
+        let kernel_box = kernel.get_bounding_rectangle();
+
+        World::chunks_overlapping(chunks, kernel_box)
+            .filter_map(|chunk| {
+                let coords = chunk.coords();
+                let intersection = coords.bounds().intersect(kernel_box)?;
+                let intersection_local = coords.rectangle_to_local(intersection);
+
+                Some(chunk.get_chunk_intersection_volume(
+                    intersection_local,
+                    kernel.y_low(),
+                    kernel.y_high(),
+                    setup,
+                ))
+            })
+            .sum()
+    }
+
+    #[test]
+    fn shared_scans_match_the_volume_of_the_blocks() {
+        let chunks = fixture_world();
+        let options = scanned_options();
+        let setup = terrain_setup(options);
+        let memo = DensityMemo::new();
+
+        let (x, z) = FIXTURE_CHUNKS[0];
+        let origin = chunks[chunks.chunk_index(x, z)].coords().origin();
+        for (dx, y, dz) in [(3.0, 58.0, 5.0), (7.5, 60.5, 2.0), (12.0, 61.25, 9.5)] {
+            let point = Position::new(origin.x + dx, y, origin.y + dz);
+            let kernel = Kernel::new(point, options.kernel_size);
+
+            let scanned = scan_kernel(&chunks, point, options.kernel_size, Some(&memo));
+            let shared = scan_kernel(&chunks, point, options.kernel_size, Some(&memo));
+            let expected = intersection_volume(&chunks, kernel, &setup);
+
+            assert!((scanned.smoothable_volume(&setup) - expected).abs() < 1e-9);
+            assert_eq!(
+                shared.smoothable_volume(&setup),
+                scanned.smoothable_volume(&setup)
+            );
+        }
+        assert_eq!(memo.stats().hits, 3);
+    }
+
+    // With the leaves kernel locked to the default smoothness, the leaves pass samples
+    // the grid points the terrain pass scanned already
+    #[test]
+    fn passes_share_the_kernel_scans_of_the_grid() {
+        let chunks = fixture_world();
+        let options = scanned_options();
+        assert_eq!(options.kernel_size, LOCKED_LEAVES_KERNEL_SIZE as Coord);
+        assert!(options.settings.multipass && options.settings.lock_leaves);
+
+        let (x, z) = FIXTURE_CHUNKS[0];
+        let lod = uniform_lod(options.marching_cubes_cell_size);
+        let (_, cost) = polygonize_chunk(&chunks, chunks.chunk_index(x, z), options, lod);
+
+        assert!(cost.density_memo.hits > 0);
+    }
+}
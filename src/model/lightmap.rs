@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Vector3, Zero};
+use image::{ImageResult, RgbImage};
+use rayon::prelude::*;
+
+use crate::config;
+use crate::infrastructure::jobs::{self, JobHandle, Priority};
+use crate::minecraft::BLOCKS_IN_CHUNK;
+
+use super::implicit::density_volume::DensityVolume;
+use super::polygonize::{Mesh, MeshVertex, PolygonizationOptions, VertexIndex};
+use super::{Position, Real};
+
+type Vec3 = Vector3<f32>;
+type Light = [f32; 3];
+
+// The lights of implicit_fs.glsl, the realtime sun is at +200, +300, +200 from the camera
+const SUN_DIRECTION: [f32; 3] = [200.0, 300.0, 200.0];
+const SUN_COLOR: [f32; 3] = [1.64, 1.27, 0.99];
+const SKY_COLOR: [f32; 3] = [0.16, 0.20, 0.28];
+const INDIRECT_COLOR: [f32; 3] = [0.4, 0.28, 0.20];
+
+// Occlusion rays start this far above the surface so they don't end inside it right away
+const RAY_OFFSET: f32 = 0.3;
+const MARCH_STEP: f32 = 0.5;
+const GOLDEN_ANGLE: f32 = 2.399_963;
+
+// Exported texels hold the light scaled by this, so the sun lit surfaces are not clipped
+const EXPORT_LIGHT_SCALE: f32 = 0.5;
+
+#[derive(Clone, Copy)]
+pub struct LightmapSettings {
+    // Texels along the side of the square every triangle gets in the atlas
+    pub chart_texels: usize,
+    // The triangles of one chunk are laid out in a page of this width
+    pub page_width: usize,
+    pub atlas_width: usize,
+    pub sky_samples: usize,
+    // Terrain further than this from a texel doesn't shadow it
+    pub occlusion_distance: f32,
+    pub surface_level: f32,
+}
+
+impl LightmapSettings {
+    pub fn new(options: PolygonizationOptions) -> Self {
+        LightmapSettings {
+            chart_texels: config::LIGHTMAP_CHART_TEXELS.max(2),
+            page_width: config::LIGHTMAP_PAGE_WIDTH,
+            atlas_width: config::LIGHTMAP_ATLAS_WIDTH,
+            sky_samples: config::LIGHTMAP_SKY_SAMPLES,
+            occlusion_distance: config::LIGHTMAP_OCCLUSION_DISTANCE,
+            surface_level: options.surface_level as f32,
+        }
+    }
+}
+
+// Light reaching the texels of the atlas, linear and in the units of the lighting
+// in implicit_fs.glsl. Row 0 is at v = 0.
+pub struct Lightmap {
+    pub width: usize,
+    pub height: usize,
+    pub texels: Vec<Light>,
+}
+
+impl Lightmap {
+    // Light is scaled by EXPORT_LIGHT_SCALE and gamma encoded, the first row is at the top
+    pub fn to_image(&self) -> RgbImage {
+        let bytes = self
+            .texels
+            .chunks_exact(self.width.max(1))
+            .rev()
+            .flatten()
+            .flat_map(|light| {
+                light.map(|channel| {
+                    let encoded = (channel * EXPORT_LIGHT_SCALE)
+                        .clamp(0.0, 1.0)
+                        .powf(1.0 / 2.2);
+                    (encoded * 255.0).round() as u8
+                })
+            })
+            .collect();
+
+        RgbImage::from_raw(self.width as u32, self.height as u32, bytes).unwrap()
+    }
+}
+
+// Smooth mesh with the sun and sky occlusion of the terrain baked into a texture.
+//
+// Every triangle is unwrapped into its own square chart of the atlas, so the mesh has three
+// vertices per triangle. The charts of one chunk mesh form a page, pages are placed
+// next to each other in rows of the atlas.
+pub struct BakedLighting {
+    pub mesh: Mesh,
+    pub lightmap: Lightmap,
+}
+
+impl BakedLighting {
+    // Writes the mesh with its lightmap coordinates into an OBJ file,
+    // the lightmap is saved next to it as a PNG and referenced from an MTL file
+    pub fn export(&self, path: &str) -> ImageResult<()> {
+        let path = Path::new(path);
+        let material_path = path.with_extension("mtl");
+        let image_path = path.with_extension("png");
+        let file_name = |path: &Path| path.file_name().unwrap().to_string_lossy().into_owned();
+
+        self.lightmap.to_image().save(&image_path)?;
+
+        let mut material = BufWriter::new(File::create(&material_path)?);
+        writeln!(material, "newmtl lightmap")?;
+        writeln!(material, "map_Kd {}", file_name(&image_path))?;
+        material.flush()?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "mtllib {}", file_name(&material_path))?;
+        writeln!(writer, "usemtl lightmap")?;
+        for vertex in &self.mesh.vertices {
+            let [x, y, z] = vertex.position;
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for vertex in &self.mesh.vertices {
+            let [u, v] = vertex.lightmap_uv;
+            writeln!(writer, "vt {} {}", u, v)?;
+        }
+        for vertex in &self.mesh.vertices {
+            let [x, y, z] = vertex.normal;
+            writeln!(writer, "vn {} {} {}", x, y, z)?;
+        }
+
+        // OBJ indices start at 1
+        for triangle in self.mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] + 1);
+            writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+// Lightmap baked by a background job, see BakedLighting
+pub struct LightmapBake {
+    job: JobHandle<BakedLighting>,
+}
+
+impl LightmapBake {
+    pub fn start(
+        chunk_meshes: Vec<Mesh>,
+        densities: Vec<Arc<DensityVolume>>,
+        settings: LightmapSettings,
+    ) -> Self {
+        let triangles = chunk_meshes
+            .iter()
+            .map(|mesh| mesh.indices.len() / 3)
+            .sum::<usize>();
+
+        let texels = triangles * settings.chart_texels * settings.chart_texels;
+        let job = jobs::spawn("lightmap bake", Priority::Low, texels, move |context| {
+            println!("[INFO] Baking lightmaps of {} triangles", triangles);
+            let field = DensityField::new(densities);
+
+            let pages = chunk_meshes
+                .iter()
+                .map(|mesh| Page::unwrap_charts(mesh, settings.chart_texels, settings.page_width))
+                .map(|page| {
+                    let texels = page.bake(|corners, weights| {
+                        if context.is_cancelled() {
+                            return Light::default();
+                        }
+
+                        let position = interpolate(corners, weights, |vertex| vertex.position);
+                        let normal = interpolate(corners, weights, |vertex| vertex.normal);
+
+                        context.advance(1);
+                        light_at(&field, position, normal.normalize(), settings)
+                    });
+                    (page, texels)
+                })
+                .collect();
+
+            pack_pages(pages, settings)
+        });
+
+        LightmapBake { job }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.job.progress()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.job.is_finished()
+    }
+
+    // The texels not baked yet are left dark, the lightmap of a cancelled bake is not usable
+    pub fn cancel(&self) {
+        self.job.cancel();
+    }
+
+    pub fn join(self) -> BakedLighting {
+        self.job.join().expect("The lightmap bake job panicked")
+    }
+}
+
+// Mesh with every triangle unwrapped into its own square chart,
+// the lightmap coordinates are in texels of the page
+pub(super) struct Page {
+    pub(super) mesh: Mesh,
+    pub(super) width: usize,
+    pub(super) height: usize,
+    chart_texels: usize,
+}
+
+impl Page {
+    pub(super) fn unwrap_charts(mesh: &Mesh, chart_texels: usize, page_width: usize) -> Self {
+        let chart_texels = chart_texels.max(2);
+        let columns = (page_width / chart_texels).max(1);
+        let texels = chart_texels as f32;
+
+        // Corners sit on texel centers, bilinear filtering never reads another chart
+        let corner_offsets = [[0.5, 0.5], [texels - 0.5, 0.5], [0.5, texels - 0.5]];
+        let vertices = mesh
+            .indices
+            .chunks_exact(3)
+            .enumerate()
+            .flat_map(|(triangle, corners)| {
+                let chart = [
+                    (triangle % columns) as f32 * texels,
+                    (triangle / columns) as f32 * texels,
+                ];
+                corners
+                    .iter()
+                    .zip(corner_offsets)
+                    .map(move |(&index, [u, v])| MeshVertex {
+                        lightmap_uv: [chart[0] + u, chart[1] + v],
+                        ..mesh.vertices[index as usize]
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        let triangles = mesh.indices.len() / 3;
+        let rows = triangles / columns + usize::from(triangles % columns != 0);
+
+        Page {
+            mesh: Mesh {
+                indices: (0..vertices.len() as VertexIndex).collect(),
+                vertices,
+            },
+            width: columns * chart_texels,
+            height: rows * chart_texels,
+            chart_texels,
+        }
+    }
+
+    // Value of every texel of the page, computed from the corners of the triangle
+    // and the barycentric weights of the texel. Texels outside of all charts get the default.
+    pub(super) fn bake<T: Copy + Default + Send>(
+        &self,
+        texel_value: impl Fn(&[MeshVertex], Vec3) -> T + Sync,
+    ) -> Vec<T> {
+        let texels = self.chart_texels;
+        let columns = self.width / texels;
+
+        let charts = self
+            .mesh
+            .vertices
+            .par_chunks_exact(3)
+            .map(|corners| {
+                (0..texels * texels)
+                    .map(|texel| {
+                        let weights = chart_weights(texel % texels, texel / texels, texels);
+                        texel_value(corners, weights)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut page = vec![T::default(); self.width * self.height];
+        for (triangle, chart) in charts.iter().enumerate() {
+            let chart_x = (triangle % columns) * texels;
+            let chart_y = (triangle / columns) * texels;
+            for (texel, value) in chart.iter().enumerate() {
+                let x = chart_x + texel % texels;
+                let y = chart_y + texel / texels;
+                page[y * self.width + x] = *value;
+            }
+        }
+
+        page
+    }
+}
+
+// Barycentric weights of the texel, texels past the diagonal of the chart are never shown
+// on their own and get the light of the closest point of the triangle
+fn chart_weights(x: usize, y: usize, texels: usize) -> Vec3 {
+    let last = (texels - 1) as f32;
+    let (mut u, mut v) = (x as f32 / last, y as f32 / last);
+    if u + v > 1.0 {
+        let sum = u + v;
+        u /= sum;
+        v /= sum;
+    }
+
+    Vec3::new(1.0 - u - v, u, v)
+}
+
+fn interpolate(
+    corners: &[MeshVertex],
+    weights: Vec3,
+    attribute: impl Fn(&MeshVertex) -> [f32; 3],
+) -> Vec3 {
+    Vec3::from(attribute(&corners[0])) * weights.x
+        + Vec3::from(attribute(&corners[1])) * weights.y
+        + Vec3::from(attribute(&corners[2])) * weights.z
+}
+
+// Pages are placed left to right, a new row of pages starts when the atlas width is reached
+fn pack_pages(pages: Vec<(Page, Vec<Light>)>, settings: LightmapSettings) -> BakedLighting {
+    let mut placements = Vec::with_capacity(pages.len());
+    let (mut x, mut y, mut row_height, mut width) = (0, 0, 0, 0);
+    for (page, _) in &pages {
+        if x > 0 && x + page.width > settings.atlas_width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+
+        placements.push((x, y));
+        x += page.width;
+        width = width.max(x);
+        row_height = row_height.max(page.height);
+    }
+    let height = y + row_height;
+
+    let mut mesh = Mesh::empty();
+    let mut texels = vec![[0.0; 3]; width * height];
+    for ((page, page_texels), (page_x, page_y)) in pages.into_iter().zip(placements) {
+        for (row, page_row) in page_texels.chunks_exact(page.width.max(1)).enumerate() {
+            let start = (page_y + row) * width + page_x;
+            texels[start..start + page.width].copy_from_slice(page_row);
+        }
+
+        let mut page_mesh = page.mesh;
+        for vertex in &mut page_mesh.vertices {
+            let [u, v] = vertex.lightmap_uv;
+            vertex.lightmap_uv = [
+                (page_x as f32 + u) / width.max(1) as f32,
+                (page_y as f32 + v) / height.max(1) as f32,
+            ];
+        }
+        page_mesh.copy_into(&mut mesh);
+    }
+
+    BakedLighting {
+        mesh,
+        lightmap: Lightmap {
+            width,
+            height,
+            texels,
+        },
+    }
+}
+
+// Same lighting as implicit_fs.glsl, except the sun is blocked by the terrain
+// and the sky and the bounced light are reduced by how much of the sky the texel sees
+fn light_at(
+    field: &DensityField,
+    position: Vec3,
+    normal: Vec3,
+    settings: LightmapSettings,
+) -> Light {
+    let sun = Vec3::from(SUN_DIRECTION).normalize();
+    let origin = position + normal * RAY_OFFSET;
+    let reach = settings.occlusion_distance;
+
+    let sun_factor = normal.dot(sun).max(0.0);
+    let sunlight = match sun_factor > 0.0 && field.is_open(origin, sun, reach, settings) {
+        true => Vec3::from(SUN_COLOR) * sun_factor,
+        false => Vec3::zero(),
+    };
+
+    let sky_factor = 0.5 + 0.5 * normal.y;
+    let indirect_direction = Vec3::new(-sun.x, 0.0, -sun.z).normalize();
+    let indirect_factor = normal.dot(indirect_direction).clamp(0.0, 1.0);
+    let ambient = Vec3::from(SKY_COLOR) * sky_factor + Vec3::from(INDIRECT_COLOR) * indirect_factor;
+
+    let open_sky = sky_visibility(field, origin, normal, settings);
+
+    (sunlight + ambient * open_sky).into()
+}
+
+// Fraction of cosine weighted directions around the normal that leave the terrain,
+// the directions follow a golden angle spiral so every texel uses the same set
+fn sky_visibility(
+    field: &DensityField,
+    origin: Vec3,
+    normal: Vec3,
+    settings: LightmapSettings,
+) -> f32 {
+    let samples = settings.sky_samples.max(1);
+    let helper = match normal.x.abs() > 0.9 {
+        true => Vec3::unit_y(),
+        false => Vec3::unit_x(),
+    };
+    let tangent = normal.cross(helper).normalize();
+    let bitangent = normal.cross(tangent);
+
+    let open = (0..samples)
+        .filter(|&sample| {
+            let radius = ((sample as f32 + 0.5) / samples as f32).sqrt();
+            let angle = sample as f32 * GOLDEN_ANGLE;
+            let direction = tangent * (radius * angle.cos())
+                + bitangent * (radius * angle.sin())
+                + normal * (1.0 - radius * radius).max(0.0).sqrt();
+
+            field.is_open(origin, direction, settings.occlusion_distance, settings)
+        })
+        .count();
+
+    open as f32 / samples as f32
+}
+
+// Baked densities of the chunks, looked up by the chunk containing the point
+struct DensityField {
+    volumes: Vec<Arc<DensityVolume>>,
+    by_chunk: HashMap<(i64, i64), usize>,
+}
+
+impl DensityField {
+    fn new(volumes: Vec<Arc<DensityVolume>>) -> Self {
+        let by_chunk = volumes
+            .iter()
+            .enumerate()
+            .map(|(index, volume)| {
+                let chunk = chunk_of(volume.origin.x as f32, volume.origin.z as f32);
+                (chunk, index)
+            })
+            .collect();
+
+        DensityField { volumes, by_chunk }
+    }
+
+    // Trilinear interpolation of the samples, None outside of the baked volumes
+    fn density(&self, point: Vec3) -> Option<f32> {
+        let volume = &self.volumes[*self.by_chunk.get(&chunk_of(point.x, point.z))?];
+        let position = Position::new(point.x as Real, point.y as Real, point.z as Real);
+
+        volume.interpolate(position).map(|density| density as f32)
+    }
+
+    // Whether the ray gets *distance* far without entering the terrain,
+    // space outside of the baked volumes is open
+    fn is_open(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        distance: f32,
+        settings: LightmapSettings,
+    ) -> bool {
+        let steps = (distance / MARCH_STEP) as usize;
+        (1..=steps).all(|step| {
+            let point = origin + direction * (step as f32 * MARCH_STEP);
+            self.density(point)
+                .is_none_or(|density| density >= settings.surface_level)
+        })
+    }
+}
+
+fn chunk_of(x: f32, z: f32) -> (i64, i64) {
+    let size = BLOCKS_IN_CHUNK as f32;
+    ((x / size).floor() as i64, (z / size).floor() as i64)
+}
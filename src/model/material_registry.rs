@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use itertools::Itertools;
+
+use crate::config;
+
+use super::common::{BlockType, ALL_BLOCK_TYPES, BLOCK_TYPES, RIGID_MATERIALS};
+use super::Real;
+
+// How the surface of a material is built
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmoothingPass {
+    // Smoothed together with the rest of the terrain
+    Terrain,
+    // Smoothed on its own, so it doesn't merge with the terrain below it
+    Leaves,
+    // Not smoothed at all, drawn as blocks
+    Rigid,
+}
+
+pub const SMOOTHING_PASSES: [SmoothingPass; 3] = [
+    SmoothingPass::Terrain,
+    SmoothingPass::Leaves,
+    SmoothingPass::Rigid,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaterialProperties {
+    pub pass: SmoothingPass,
+    // Share of the material blend the material needs to show up on the surface,
+    // below it the material gives its share to the dominant material
+    pub activation_threshold: Real,
+}
+
+impl MaterialProperties {
+    pub fn is_rigid(&self) -> bool {
+        self.pass == SmoothingPass::Rigid
+    }
+}
+
+// Materials changed at runtime, the rest keep their default properties
+static OVERRIDES: RwLock<[Option<MaterialProperties>; BLOCK_TYPES]> =
+    RwLock::new([None; BLOCK_TYPES]);
+
+pub fn get(material: BlockType) -> MaterialProperties {
+    let overrides = OVERRIDES.read().unwrap();
+
+    overrides[material as usize].unwrap_or_else(|| default_properties(material))
+}
+
+// Properties of every material at the time of the snapshot.
+// Builds take one when they are dispatched and carry it in PolygonizationOptions::materials,
+// so the threads evaluating the kernels don't read the registry for every vertex and block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaterialRegistry {
+    properties: [MaterialProperties; BLOCK_TYPES],
+}
+
+impl MaterialRegistry {
+    pub fn defaults() -> Self {
+        MaterialRegistry {
+            properties: ALL_BLOCK_TYPES.map(default_properties),
+        }
+    }
+
+    pub fn get(&self, material: BlockType) -> MaterialProperties {
+        self.properties[material as usize]
+    }
+
+    pub fn activation_threshold(&self, material: BlockType) -> Real {
+        self.get(material).activation_threshold
+    }
+
+    pub fn is_rigid(&self, material: BlockType) -> bool {
+        self.get(material).is_rigid()
+    }
+
+    // Materials smoothed in *pass*
+    pub fn materials_in(&self, pass: SmoothingPass) -> Vec<BlockType> {
+        ALL_BLOCK_TYPES
+            .into_iter()
+            .filter(|material| self.get(*material).pass == pass)
+            .collect()
+    }
+}
+
+// Reads the registry once for all materials
+pub fn snapshot() -> MaterialRegistry {
+    let overrides = OVERRIDES.read().unwrap();
+
+    MaterialRegistry {
+        properties: ALL_BLOCK_TYPES.map(|material| {
+            overrides[material as usize].unwrap_or_else(|| default_properties(material))
+        }),
+    }
+}
+
+// Chunks and meshes built before the change keep the old properties,
+// see World::refresh_materials
+pub fn set(material: BlockType, properties: MaterialProperties) {
+    OVERRIDES.write().unwrap()[material as usize] = Some(properties);
+}
+
+pub fn reset() {
+    *OVERRIDES.write().unwrap() = [None; BLOCK_TYPES];
+    NAMESPACE_OVERRIDES.write().unwrap().clear();
+    *MODDED_BLOCK_OVERRIDE.write().unwrap() = None;
+}
+
+// Materials of the modded blocks changed at runtime, by namespace.
+// The rest use config::NAMESPACE_FALLBACKS and config::MODDED_BLOCK_FALLBACK.
+static NAMESPACE_OVERRIDES: RwLock<BTreeMap<String, BlockType>> = RwLock::new(BTreeMap::new());
+static MODDED_BLOCK_OVERRIDE: RwLock<Option<BlockType>> = RwLock::new(None);
+
+// Material of the blocks of *namespace* that don't match any minecraft block
+pub fn namespace_fallback(namespace: &str) -> BlockType {
+    if let Some(material) = NAMESPACE_OVERRIDES.read().unwrap().get(namespace) {
+        return *material;
+    }
+
+    config::NAMESPACE_FALLBACKS
+        .iter()
+        .find(|(fallback_namespace, _)| *fallback_namespace == namespace)
+        .map_or_else(modded_block_fallback, |(_, material)| *material)
+}
+
+// Material of the modded blocks whose namespace has no fallback of its own
+pub fn modded_block_fallback() -> BlockType {
+    MODDED_BLOCK_OVERRIDE
+        .read()
+        .unwrap()
+        .unwrap_or(config::MODDED_BLOCK_FALLBACK)
+}
+
+// Chunks loaded before the change keep the old materials
+pub fn set_namespace_fallback(namespace: &str, material: BlockType) {
+    NAMESPACE_OVERRIDES
+        .write()
+        .unwrap()
+        .insert(namespace.to_owned(), material);
+}
+
+pub fn set_modded_block_fallback(material: BlockType) {
+    *MODDED_BLOCK_OVERRIDE.write().unwrap() = Some(material);
+}
+
+// Namespaces with a fallback material of their own, sorted by name
+pub fn namespace_fallbacks() -> Vec<(String, BlockType)> {
+    let overrides = NAMESPACE_OVERRIDES.read().unwrap();
+    let defaults = config::NAMESPACE_FALLBACKS
+        .iter()
+        .filter(|(namespace, _)| !overrides.contains_key(*namespace))
+        .map(|(namespace, material)| (namespace.to_string(), *material));
+
+    overrides
+        .iter()
+        .map(|(namespace, material)| (namespace.clone(), *material))
+        .chain(defaults)
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .collect()
+}
+
+// Materials smoothed in *pass*
+pub fn materials_in(pass: SmoothingPass) -> Vec<BlockType> {
+    snapshot().materials_in(pass)
+}
+
+pub fn default_properties(material: BlockType) -> MaterialProperties {
+    let pass = match material {
+        material if RIGID_MATERIALS.contains(&material) => SmoothingPass::Rigid,
+        BlockType::Leaves => SmoothingPass::Leaves,
+        _ => SmoothingPass::Terrain,
+    };
+
+    let activation_threshold = match material {
+        BlockType::Dirt => 0.45,
+        BlockType::Leaves => 0.9,
+        _ => 0.0, // always activate
+    };
+
+    MaterialProperties {
+        pass,
+        activation_threshold,
+    }
+}
@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config;
+use crate::settings::Settings;
+
+use super::chunk::chunk::{FNV_OFFSET_BASIS, FNV_PRIME};
+use super::chunk::Chunk;
+use super::material_registry::{MaterialRegistry, SMOOTHING_PASSES};
+use super::polygonize::{ChunkLod, Mesh, PolygonizationOptions};
+
+// Chunk meshes stored on disk between runs, so chunks that didn't change since the last run
+// read their mesh back instead of polygonizing it again.
+//
+// A mesh is stored under the position of its chunk and the fingerprint of everything besides
+// the blocks that shapes it, see build_fingerprint. The kernels reach into the neighbouring chunks,
+// so the hash of the blocks the mesh depends on combines the content hashes of the chunk
+// and its neighbours, see World::neighbourhood_hash. It is stored inside the file,
+// a mesh of blocks that changed since is a miss and gets overwritten.
+// Failing to read or write the cache only costs a rebuild, so errors are not reported.
+#[derive(Clone, Debug)]
+pub struct MeshCache {
+    folder: PathBuf,
+}
+
+impl MeshCache {
+    // None when config::MESH_CACHE_FOLDER disables the cache
+    pub fn open() -> Option<Self> {
+        config::MESH_CACHE_FOLDER.map(|folder| MeshCache {
+            folder: PathBuf::from(folder),
+        })
+    }
+
+    pub fn load(
+        &self,
+        chunk: &Chunk,
+        fingerprint: u64,
+        blocks_hash: u64,
+        materials: &MaterialRegistry,
+    ) -> Option<Mesh> {
+        let bytes = fs::read(self.path(chunk, fingerprint)).ok()?;
+
+        Mesh::from_compressed_bytes(&bytes, blocks_hash, materials).ok()
+    }
+
+    pub fn store(&self, chunk: &Chunk, fingerprint: u64, blocks_hash: u64, mesh: &Mesh) {
+        let origin = chunk.coords().origin();
+        let Ok(bytes) = mesh.to_compressed_bytes(origin, blocks_hash) else {
+            return;
+        };
+
+        if fs::create_dir_all(&self.folder).is_ok() {
+            let _ = fs::write(self.path(chunk, fingerprint), bytes);
+        }
+    }
+
+    fn path(&self, chunk: &Chunk, fingerprint: u64) -> PathBuf {
+        let (x, z) = chunk.position.get_global_position_in_chunks();
+
+        self.folder.join(format!("{x}_{z}_{fingerprint:016x}.mesh"))
+    }
+}
+
+// Hash of the options, the level of detail, the settings that shape the mesh and the smoothing
+// passes of the materials a mesh is built with. The activation thresholds are applied when
+// a mesh is read back, they don't change the stored mesh. Neither do the settings left out,
+// like the camera speed.
+pub fn build_fingerprint(options: PolygonizationOptions, lod: ChunkLod) -> u64 {
+    let Settings {
+        multipass,
+        lock_leaves,
+        filter_rigid,
+        build_octree,
+        ..
+    } = options.settings;
+    let passes = SMOOTHING_PASSES.map(|pass| options.materials.materials_in(pass));
+    let options = PolygonizationOptions {
+        materials: MaterialRegistry::defaults(),
+        settings: Settings::default(),
+        ..options
+    };
+    let description = format!(
+        "{options:?} {lod:?} {multipass} {lock_leaves} {filter_rigid} {build_octree} {passes:?}"
+    );
+
+    fnv_hash(FNV_OFFSET_BASIS, description.as_bytes())
+}
+
+// Continues an FNV-1a hash over the bytes
+pub fn fnv_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::model::polygonize::test_support::{
+        build_chunk, fixture_options, fixture_world, uniform_lod, FIXTURE_CHUNKS,
+    };
+
+    fn fingerprint_with(change: impl FnOnce(&mut Settings)) -> u64 {
+        let mut options = fixture_options();
+        options.settings = Settings::default();
+        change(&mut options.settings);
+
+        build_fingerprint(options, uniform_lod(options.marching_cubes_cell_size))
+    }
+
+    #[test]
+    fn settings_that_shape_the_mesh_change_the_fingerprint() {
+        let unchanged = fingerprint_with(|_| ());
+
+        assert_ne!(fingerprint_with(|s| s.multipass ^= true), unchanged);
+        assert_ne!(fingerprint_with(|s| s.lock_leaves ^= true), unchanged);
+        assert_ne!(fingerprint_with(|s| s.filter_rigid ^= true), unchanged);
+        assert_ne!(fingerprint_with(|s| s.build_octree ^= true), unchanged);
+    }
+
+    #[test]
+    fn other_settings_keep_the_fingerprint() {
+        let unchanged = fingerprint_with(|_| ());
+
+        assert_eq!(fingerprint_with(|s| s.camera_move_speed += 1.0), unchanged);
+        assert_eq!(fingerprint_with(|s| s.scan_workers += 1), unchanged);
+        assert_eq!(fingerprint_with(|s| s.mesh_radius = Some(2)), unchanged);
+    }
+
+    // A mesh read back from the cache stands in for a fresh build of its chunk
+    #[test]
+    fn cached_mesh_matches_a_fresh_build() {
+        let chunks = fixture_world();
+        let options = fixture_options();
+        let (x, z) = FIXTURE_CHUNKS[0];
+        let chunk = &chunks[chunks.chunk_index(x, z)];
+        let fingerprint = build_fingerprint(options, uniform_lod(options.marching_cubes_cell_size));
+        let blocks_hash = chunk.content_hash();
+
+        let cache = MeshCache {
+            folder: env::temp_dir().join(format!("dd-terrain-mesh-cache-{}", process::id())),
+        };
+        let stored = build_chunk(&chunks, (x, z), options);
+        assert!(!stored.vertices.is_empty());
+        cache.store(chunk, fingerprint, blocks_hash, &stored);
+        let cached = cache.load(chunk, fingerprint, blocks_hash, &options.materials);
+        let stale = cache.load(chunk, fingerprint, blocks_hash ^ 1, &options.materials);
+        let _ = fs::remove_dir_all(&cache.folder);
+
+        let fresh = build_chunk(&chunks, (x, z), options);
+        cached.unwrap().assert_within_quantization(&fresh);
+        assert!(stale.is_none());
+    }
+}
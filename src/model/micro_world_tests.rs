@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::config::{MIN_CHUNK_LOAD_RADIUS, WORLD_SIZE};
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::minecraft;
+use crate::model::chunk::{Chunk, ChunkPosition};
+use crate::model::common::BlockType;
+use crate::model::discrete::{self, WorldChunks};
+use crate::model::implicit::smooth::polygonize_chunk;
+use crate::model::polygonize::{ChunkLod, Mesh, PolygonizationOptions};
+use crate::model::Position;
+
+// The chunks of assets/MicroWorld, see generate.py there for their content.
+// They are in the middle of a window loaded from the first chunk of region 0, 0.
+const FLAT: (usize, usize) = (4, 4);
+const WALL: (usize, usize) = (5, 4);
+const TREE: (usize, usize) = (4, 5);
+const HUT: (usize, usize) = (5, 5);
+
+// Blocks in a column of the ground, stone from y 56 covered by grass at y 63
+const GROUND_DEPTH: usize = 8;
+
+fn load_micro_world() -> WorldChunks {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/MicroWorld");
+    minecraft::set_world_folder(folder);
+
+    let base = ChunkPosition::from_global_position_in_chunks(0, 0);
+    discrete::load_window(base, WORLD_SIZE)
+}
+
+fn chunk(chunks: &WorldChunks, (x, z): (usize, usize)) -> &Chunk {
+    &chunks[chunks.chunk_index(x, z)]
+}
+
+// Block at the block coordinates *x*, *y*, *z* of the world
+fn block_at(chunks: &WorldChunks, x: i32, y: i32, z: i32) -> BlockType {
+    let center = Position::new(x as f64 + 0.5, y as f64 + 0.5, z as f64 + 0.5);
+    discrete::get_block_in(chunks, center)
+}
+
+#[test]
+fn blocks_are_where_the_fixture_put_them() {
+    let chunks = load_micro_world();
+
+    // The flat chunk starts at x 64, z 64
+    assert_eq!(block_at(&chunks, 70, 63, 70), BlockType::Grass);
+    assert_eq!(block_at(&chunks, 70, 60, 70), BlockType::Stone);
+    assert_eq!(block_at(&chunks, 70, 64, 70), BlockType::Air);
+    assert_eq!(block_at(&chunks, 70, 50, 70), BlockType::Air);
+
+    // The wall runs along z at x 88
+    assert_eq!(block_at(&chunks, 88, 65, 70), BlockType::Stone);
+    assert_eq!(block_at(&chunks, 89, 65, 70), BlockType::Air);
+
+    // The tree stands at x 72, z 88
+    assert_eq!(block_at(&chunks, 72, 64, 88), BlockType::Wood);
+    assert_eq!(block_at(&chunks, 70, 67, 86), BlockType::Leaves);
+    assert_eq!(block_at(&chunks, 72, 69, 88), BlockType::Leaves);
+
+    // The hut is empty inside and has a door at x 87, z 85
+    assert_eq!(block_at(&chunks, 85, 65, 87), BlockType::Planks);
+    assert_eq!(block_at(&chunks, 87, 64, 85), BlockType::Air);
+    assert_eq!(block_at(&chunks, 87, 66, 85), BlockType::Planks);
+    assert_eq!(block_at(&chunks, 87, 65, 87), BlockType::Air);
+    assert_eq!(block_at(&chunks, 87, 67, 87), BlockType::Planks);
+
+    // Chunks missing in the region file are empty
+    assert_eq!(block_at(&chunks, 8, 63, 8), BlockType::Air);
+}
+
+#[test]
+fn windows_are_loaded_at_any_size() {
+    load_micro_world();
+    let base = ChunkPosition::from_global_position_in_chunks(0, 0);
+
+    // Too small to reach the fixture chunks, blocks outside of a window are air
+    let small = discrete::load_window(base, 2 * MIN_CHUNK_LOAD_RADIUS);
+    assert_eq!(small.len(), small.size() * small.size());
+    assert_eq!(block_at(&small, 70, 63, 70), BlockType::Air);
+
+    let size = WORLD_SIZE + 2;
+    let large = discrete::load_window(base, size);
+    let last = &large[large.chunk_index(size - 1, size - 1)];
+    let expected_last = (size as i32 - 1, size as i32 - 1);
+    assert_eq!(last.position.get_global_position_in_chunks(), expected_last);
+    assert_eq!(block_at(&large, 70, 63, 70), BlockType::Grass);
+    assert_eq!(block_at(&large, 85, 65, 87), BlockType::Planks);
+}
+
+#[test]
+fn surface_of_flat_ground() {
+    let chunks = load_micro_world();
+    let flat = chunk(&chunks, FLAT);
+
+    // Every block of the border columns is kept, inside only the top and the bottom of the ground
+    let border_columns = 4 * 15;
+    let inner_columns = 14 * 14;
+    let expected = border_columns * GROUND_DEPTH + inner_columns * 2;
+
+    assert_eq!(flat.surface_blocks.len(), expected);
+}
+
+#[test]
+fn rigid_blocks_are_detected() {
+    let chunks = load_micro_world();
+    let rigid_materials = |position| {
+        chunk(&chunks, position)
+            .get_rigid_blocks()
+            .map(|(material, _)| material)
+            .collect::<Vec<_>>()
+    };
+
+    assert!(rigid_materials(FLAT).is_empty());
+    // Stone is smoothed, the wall is part of the terrain
+    assert!(rigid_materials(WALL).is_empty());
+
+    // Only the trunk, the leaves have a smoothing pass of their own
+    assert_eq!(rigid_materials(TREE), [BlockType::Wood; 5]);
+
+    let hut = rigid_materials(HUT);
+    assert_eq!(hut.len(), 71);
+    assert!(hut.iter().all(|material| *material == BlockType::Planks));
+}
+
+// Capped on every side, so a single chunk is closed
+fn build_capped(chunks: &WorldChunks, (x, z): (usize, usize)) -> Mesh {
+    let options = PolygonizationOptions {
+        y_low_limit: 52.0,
+        y_size: 20.0,
+        caps: true,
+        ..SmoothMeshOptions::default().into()
+    };
+    let lod = ChunkLod {
+        cell_size: options.marching_cubes_cell_size,
+        neighbor_cell_sizes: [options.marching_cubes_cell_size; 4],
+        window_edges: [true; 4],
+    };
+
+    polygonize_chunk(chunks, chunks.chunk_index(x, z), options, lod).0
+}
+
+// Number of edges used by a single triangle. The caps don't share their vertices with the surface,
+// so the vertices are matched by their position.
+fn open_edges(mesh: &Mesh) -> usize {
+    let mut welded = HashMap::new();
+    let vertex_ids = mesh
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let key = vertex.position.map(|coord| (coord * 1024.0).round() as i64);
+            let next_id = welded.len();
+            *welded.entry(key).or_insert(next_id)
+        })
+        .collect::<Vec<_>>();
+
+    let mut edge_counts: HashMap<(usize, usize), usize> = HashMap::new();
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| vertex_ids[triangle[i] as usize]);
+        for (start, end) in [(a, b), (b, c), (c, a)] {
+            // Edges of triangles collapsed by the welding
+            if start == end {
+                continue;
+            }
+            *edge_counts
+                .entry((start.min(end), start.max(end)))
+                .or_insert(0) += 1;
+        }
+    }
+
+    edge_counts.values().filter(|count| **count == 1).count()
+}
+
+#[test]
+fn meshes_are_closed() {
+    let chunks = load_micro_world();
+
+    for position in [FLAT, WALL, TREE, HUT] {
+        let mesh = build_capped(&chunks, position);
+
+        assert!(!mesh.indices.is_empty(), "chunk {position:?} has no mesh");
+        assert_eq!(open_edges(&mesh), 0, "chunk {position:?} is open");
+    }
+}
+
+#[test]
+fn flat_ground_is_meshed_at_its_top() {
+    let chunks = load_micro_world();
+    let mesh = build_capped(&chunks, FLAT);
+
+    // The grass ends at y 64, the smoothing doesn't move a flat surface
+    let top = mesh
+        .vertices
+        .iter()
+        .map(|vertex| vertex.position[1])
+        .fold(f32::MIN, f32::max);
+    assert!((top - 64.0).abs() < 0.5, "top at {top}");
+}
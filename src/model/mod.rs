@@ -1,9 +1,26 @@
+pub mod biome;
+pub mod bvh;
 pub mod chunk;
+pub mod chunk_grid;
 pub mod common;
 pub mod discrete;
+pub mod elevation_profile;
+pub mod export;
 pub mod implicit;
+pub mod lightmap;
+pub mod material_registry;
+pub mod mesh_cache;
+pub mod navmesh;
+pub mod pathfinding;
 pub mod polygonize;
+pub mod raycast;
 pub mod rectangle;
+pub mod splat_map;
+pub mod terrain_metrics;
+pub mod water_flow;
+
+#[cfg(test)]
+mod micro_world_tests;
 
 pub type Real = f64;
 pub type Coord = f64;
@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use cgmath::{InnerSpace, Vector3};
+
+use super::polygonize::Mesh;
+
+type Vec3 = Vector3<f32>;
+type GridCell = (i32, i32, i32);
+
+// Walkable part of the smooth surface, welded and simplified for path finding in other tools.
+//
+// Triangles steeper than the maximum slope are dropped. The remaining vertices are clustered
+// on a grid, all vertices in a cell collapse into their mean, which welds the chunk borders
+// and removes the small triangles of the marching cubes grid. Triangles that collapse or
+// become too steep are dropped afterwards.
+pub struct NavMesh {
+    pub vertices: Vec<Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl NavMesh {
+    pub fn extract(mesh: &Mesh, max_slope_degrees: f32, cell_size: f32) -> Self {
+        let min_up = max_slope_degrees.to_radians().cos();
+        let position = |index: u32| Vec3::from(mesh.vertices[index as usize].position);
+
+        let walkable = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .filter(|&triangle| {
+                let corners = triangle.map(position);
+                let normal = face_normal(corners, vertex_normal(mesh, triangle));
+                normal.is_some_and(|normal| normal.y >= min_up)
+            })
+            .collect::<Vec<_>>();
+
+        // Every vertex of a walkable triangle joins the cluster of its grid cell
+        let mut clusters: HashMap<GridCell, u32> = HashMap::new();
+        let mut sums: Vec<(Vec3, u32)> = Vec::new();
+        let mut cluster_of = |index: u32| {
+            let point = position(index);
+            let cell = (point / cell_size).map(|coordinate| coordinate.floor() as i32);
+            *clusters.entry((cell.x, cell.y, cell.z)).or_insert_with(|| {
+                sums.push((Vec3::new(0.0, 0.0, 0.0), 0));
+                sums.len() as u32 - 1
+            })
+        };
+        let clustered = walkable
+            .iter()
+            .map(|triangle| triangle.map(&mut cluster_of))
+            .collect::<Vec<_>>();
+
+        // Shared vertices are counted once per triangle, which weights them by their valence
+        for (triangle, clustered) in walkable.iter().zip(&clustered) {
+            for (&vertex, &cluster) in triangle.iter().zip(clustered) {
+                let (sum, count) = &mut sums[cluster as usize];
+                *sum += position(vertex);
+                *count += 1;
+            }
+        }
+        let vertices = sums
+            .into_iter()
+            .map(|(sum, count)| sum / count.max(1) as f32)
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        let triangles = walkable
+            .iter()
+            .zip(clustered)
+            .filter(|(_, [a, b, c])| a != b && b != c && a != c)
+            .filter(|(original, clustered)| {
+                let corners = clustered.map(|cluster| vertices[cluster as usize]);
+                let normal = face_normal(corners, vertex_normal(mesh, **original));
+                normal.is_some_and(|normal| normal.y >= min_up)
+            })
+            .map(|(_, clustered)| clustered)
+            .filter(|clustered| {
+                // The same triangle can be produced by both sides of a chunk border
+                let mut key = *clustered;
+                key.sort_unstable();
+                seen.insert(key)
+            })
+            .collect::<Vec<_>>();
+
+        NavMesh {
+            vertices,
+            triangles,
+        }
+        .without_unused_vertices()
+    }
+
+    fn without_unused_vertices(self) -> Self {
+        let mut remap = vec![None; self.vertices.len()];
+        let mut vertices = Vec::new();
+        let triangles = self
+            .triangles
+            .iter()
+            .map(|triangle| {
+                triangle.map(|vertex| {
+                    *remap[vertex as usize].get_or_insert_with(|| {
+                        vertices.push(self.vertices[vertex as usize]);
+                        vertices.len() as u32 - 1
+                    })
+                })
+            })
+            .collect();
+
+        NavMesh {
+            vertices,
+            triangles,
+        }
+    }
+
+    // Plain OBJ with positions and faces only, the input format of Recast and most navmesh tools
+    pub fn write_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "o navmesh")?;
+        for vertex in &self.vertices {
+            writeln!(writer, "v {} {} {}", vertex.x, vertex.y, vertex.z)?;
+        }
+
+        // OBJ indices start at 1
+        for [a, b, c] in &self.triangles {
+            writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Mean of the vertex normals of the triangle, they point out of the terrain
+fn vertex_normal(mesh: &Mesh, triangle: [u32; 3]) -> Vec3 {
+    triangle
+        .iter()
+        .map(|&index| Vec3::from(mesh.vertices[index as usize].normal))
+        .sum()
+}
+
+// Unit normal of the triangle on the same side as *outside*, None for degenerate triangles
+fn face_normal(corners: [Vec3; 3], outside: Vec3) -> Option<Vec3> {
+    let [a, b, c] = corners;
+    let normal = (b - a).cross(c - a);
+    if normal.magnitude2() == 0.0 {
+        return None;
+    }
+
+    match normal.dot(outside) < 0.0 {
+        true => Some(-normal.normalize()),
+        false => Some(normal.normalize()),
+    }
+}
@@ -0,0 +1,292 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+
+use cgmath::{Point2, Vector3};
+
+use crate::config;
+
+use super::discrete::World;
+use super::polygonize::PolygonizationOptions;
+use super::raycast::raycast_surface;
+use super::{PlanarPosition, Position, Real};
+
+// The smooth surface of a column is looked for this far above and below its topmost block
+const DRAPE_PROBE_HEIGHT: Real = 3.0;
+// Lifts the drawn route off the surface so it doesn't flicker through the mesh
+const DRAPE_OFFSET: Real = 0.1;
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Column {
+    pub x: isize,
+    pub z: isize,
+}
+
+impl Column {
+    pub fn containing(position: PlanarPosition) -> Self {
+        Column {
+            x: position.x.floor() as isize,
+            z: position.y.floor() as isize,
+        }
+    }
+
+    pub fn center(&self) -> PlanarPosition {
+        Point2::new(self.x as Real + 0.5, self.z as Real + 0.5)
+    }
+
+    fn planar_distance(&self, other: Column) -> Real {
+        let dx = (self.x - other.x) as Real;
+        let dz = (self.z - other.z) as Real;
+        (dx * dx + dz * dz).sqrt()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PathStep {
+    pub column: Column,
+    // Height of the topmost block of the column
+    pub height: isize,
+}
+
+// Route over the block columns between two points, from the start to the goal
+pub struct WalkablePath {
+    pub steps: Vec<PathStep>,
+    pub length: Real,
+    // Number of columns the search looked at before reaching the goal
+    pub expanded: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum PathError {
+    // The start or the goal is outside of the loaded terrain
+    Unloaded,
+    NoRoute { expanded: usize },
+    SearchLimit,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::Unloaded => write!(f, "The start or the goal is not on loaded terrain"),
+            PathError::NoRoute { expanded } => write!(
+                f,
+                "No walkable route within the loaded chunks, {expanded} columns searched"
+            ),
+            PathError::SearchLimit => write!(
+                f,
+                "Gave up after searching {} columns",
+                config::PATH_MAX_EXPANDED_COLUMNS
+            ),
+        }
+    }
+}
+
+// Entry of the open set, ordered so the binary heap pops the lowest estimate first
+struct OpenColumn {
+    estimate: Real,
+    column: Column,
+}
+
+impl PartialEq for OpenColumn {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for OpenColumn {}
+
+impl PartialOrd for OpenColumn {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenColumn {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+// Surface heights of the columns the search has looked at
+struct Heightmap<'a> {
+    world: &'a World,
+    heights: HashMap<Column, Option<isize>>,
+}
+
+impl<'a> Heightmap<'a> {
+    fn new(world: &'a World) -> Self {
+        Heightmap {
+            world,
+            heights: HashMap::new(),
+        }
+    }
+
+    fn height(&mut self, column: Column) -> Option<isize> {
+        let world = self.world;
+        *self
+            .heights
+            .entry(column)
+            .or_insert_with(|| world.get_surface_height(column.center()))
+    }
+
+    // Length of the step in 3D, None if it is too steep or leaves the loaded terrain
+    fn step_cost(&mut self, from: Column, to: Column, max_slope: Real) -> Option<Real> {
+        let rise = (self.height(to)? - self.height(from)?) as Real;
+        let run = from.planar_distance(to);
+
+        match rise.abs() / run <= max_slope {
+            true => Some((run * run + rise * rise).sqrt()),
+            false => None,
+        }
+    }
+}
+
+// A* search over the heightmap of the loaded chunks.
+//
+// A step to one of the 8 neighbouring columns is walkable if the height difference
+// divided by the horizontal distance is at most *max_slope*. Diagonal steps also need both
+// of the columns they pass between to be walkable, so the route doesn't cut corners of cliffs.
+// The cost of a step is its length in 3D, the planar distance to the goal is the heuristic.
+pub fn find_path(
+    world: &World,
+    start: PlanarPosition,
+    goal: PlanarPosition,
+    max_slope: Real,
+) -> Result<WalkablePath, PathError> {
+    let start = Column::containing(start);
+    let goal = Column::containing(goal);
+
+    let mut heightmap = Heightmap::new(world);
+    if heightmap.height(start).is_none() || heightmap.height(goal).is_none() {
+        return Err(PathError::Unloaded);
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Column, Column> = HashMap::new();
+    let mut cost: HashMap<Column, Real> = HashMap::new();
+    let mut expanded = 0;
+
+    cost.insert(start, 0.0);
+    open.push(OpenColumn {
+        estimate: start.planar_distance(goal),
+        column: start,
+    });
+
+    while let Some(OpenColumn { estimate, column }) = open.pop() {
+        let column_cost = cost[&column];
+        // Stale entry, the column was reached more cheaply since it was pushed
+        if estimate > column_cost + column.planar_distance(goal) {
+            continue;
+        }
+
+        if column == goal {
+            let steps = reconstruct(&came_from, goal)
+                .into_iter()
+                .map(|column| PathStep {
+                    column,
+                    height: heightmap.height(column).unwrap(),
+                })
+                .collect();
+
+            return Ok(WalkablePath {
+                steps,
+                length: column_cost,
+                expanded,
+            });
+        }
+
+        expanded += 1;
+        if expanded > config::PATH_MAX_EXPANDED_COLUMNS {
+            return Err(PathError::SearchLimit);
+        }
+
+        for (dx, dz) in NEIGHBOURS {
+            let neighbour = Column {
+                x: column.x + dx,
+                z: column.z + dz,
+            };
+            let Some(step_cost) = heightmap.step_cost(column, neighbour, max_slope) else {
+                continue;
+            };
+
+            let diagonal = dx != 0 && dz != 0;
+            if diagonal {
+                let side_x = Column {
+                    x: column.x + dx,
+                    z: column.z,
+                };
+                let side_z = Column {
+                    x: column.x,
+                    z: column.z + dz,
+                };
+                let blocked = heightmap.step_cost(column, side_x, max_slope).is_none()
+                    || heightmap.step_cost(column, side_z, max_slope).is_none();
+                if blocked {
+                    continue;
+                }
+            }
+
+            let neighbour_cost = column_cost + step_cost;
+            let improved = cost
+                .get(&neighbour)
+                .is_none_or(|&known| neighbour_cost < known);
+            if improved {
+                cost.insert(neighbour, neighbour_cost);
+                came_from.insert(neighbour, column);
+                open.push(OpenColumn {
+                    estimate: neighbour_cost + neighbour.planar_distance(goal),
+                    column: neighbour,
+                });
+            }
+        }
+    }
+
+    Err(PathError::NoRoute { expanded })
+}
+
+fn reconstruct(came_from: &HashMap<Column, Column>, goal: Column) -> Vec<Column> {
+    let mut columns = vec![goal];
+    let mut current = goal;
+    while let Some(&previous) = came_from.get(&current) {
+        columns.push(previous);
+        current = previous;
+    }
+
+    columns.reverse();
+    columns
+}
+
+impl WalkablePath {
+    // Points of the route on the smooth surface, one above the center of every column.
+    // Columns where the smooth surface isn't found use the top of their block instead.
+    pub fn drape(&self, world: &World, options: PolygonizationOptions) -> Vec<Position> {
+        let chunks = world.get_chunks();
+        let down = Vector3::new(0.0, -1.0, 0.0);
+
+        self.steps
+            .iter()
+            .map(|step| {
+                let center = step.column.center();
+                let top = step.height as Real + 1.0;
+                let origin = Position::new(center.x, top + DRAPE_PROBE_HEIGHT, center.y);
+
+                let surface =
+                    raycast_surface(&chunks, origin, down, 2.0 * DRAPE_PROBE_HEIGHT, options)
+                        .map_or(top, |hit| hit.position.y);
+
+                Position::new(center.x, surface + DRAPE_OFFSET, center.y)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use super::{Mesh, VertexIndex};
+
+// A chain of boundary edges of a mesh.
+// The vertices are in the order the edges are traversed by their owning triangles.
+pub struct BoundaryLoop {
+    pub vertices: Vec<VertexIndex>,
+
+    // Whether the last vertex connects back to the first one.
+    // Chains are open only when the mesh is not manifold around the boundary.
+    pub closed: bool,
+}
+
+impl BoundaryLoop {
+    // Directed edges of the loop, (start, end) pairs
+    pub fn edges(&self) -> impl Iterator<Item = (VertexIndex, VertexIndex)> + '_ {
+        let edge_count = if self.closed {
+            self.vertices.len()
+        } else {
+            self.vertices.len().saturating_sub(1)
+        };
+
+        (0..edge_count).map(|i| {
+            let start = self.vertices[i];
+            let end = self.vertices[(i + 1) % self.vertices.len()];
+            (start, end)
+        })
+    }
+
+    // Vertex positions along the loop, closed loops repeat the first position at the end
+    pub fn polyline(&self, mesh: &Mesh) -> Vec<[f32; 3]> {
+        let mut points = self
+            .vertices
+            .iter()
+            .map(|index| mesh.vertices[*index as usize].position)
+            .collect::<Vec<_>>();
+
+        if self.closed && !points.is_empty() {
+            points.push(points[0]);
+        }
+
+        points
+    }
+}
+
+impl Mesh {
+    // Edges used by exactly one triangle, chained into loops.
+    //
+    // Edges keep the direction they have in their triangle,
+    // so the loops wind the same way as the surface does.
+    pub fn boundary_edges(&self) -> Vec<BoundaryLoop> {
+        let mut edge_counts: HashMap<(VertexIndex, VertexIndex), usize> = HashMap::new();
+        for triangle in self.indices.chunks_exact(3) {
+            for i in 0..3 {
+                let key = undirected(triangle[i], triangle[(i + 1) % 3]);
+                *edge_counts.entry(key).or_insert(0) += 1;
+            }
+        }
+
+        // start vertex -> end vertices of the open edges leaving it
+        let mut outgoing: HashMap<VertexIndex, Vec<VertexIndex>> = HashMap::new();
+        // Keep the order of the index buffer so the result is deterministic
+        let mut starts = Vec::new();
+        for triangle in self.indices.chunks_exact(3) {
+            for i in 0..3 {
+                let (start, end) = (triangle[i], triangle[(i + 1) % 3]);
+                if edge_counts[&undirected(start, end)] != 1 {
+                    continue;
+                }
+
+                outgoing.entry(start).or_default().push(end);
+                starts.push(start);
+            }
+        }
+
+        let mut loops = Vec::new();
+        for first in starts {
+            let Some(mut current) = take_outgoing(&mut outgoing, first) else {
+                continue;
+            };
+
+            let mut vertices = vec![first];
+            let closed = loop {
+                if current == first {
+                    break true;
+                }
+
+                vertices.push(current);
+                match take_outgoing(&mut outgoing, current) {
+                    Some(next) => current = next,
+                    None => break false,
+                }
+            };
+
+            loops.push(BoundaryLoop { vertices, closed });
+        }
+
+        loops
+    }
+
+    // Writes the boundary loops as OBJ polylines, useful for inspecting chunk stitching
+    // in an external viewer.
+    //
+    // Lines use relative indices, so boundaries of multiple meshes can be written
+    // into the same file.
+    pub fn write_boundary_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+        for boundary in self.boundary_edges() {
+            let polyline = boundary.polyline(self);
+            for [x, y, z] in polyline.iter() {
+                writeln!(writer, "v {} {} {}", x, y, z)?;
+            }
+
+            let line_indices = (1..=polyline.len())
+                .rev()
+                .map(|i| format!("-{}", i))
+                .collect::<Vec<_>>();
+            writeln!(writer, "l {}", line_indices.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn undirected(start: VertexIndex, end: VertexIndex) -> (VertexIndex, VertexIndex) {
+    (start.min(end), start.max(end))
+}
+
+fn take_outgoing(
+    outgoing: &mut HashMap<VertexIndex, Vec<VertexIndex>>,
+    start: VertexIndex,
+) -> Option<VertexIndex> {
+    outgoing.get_mut(&start).and_then(|ends| ends.pop())
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::infrastructure::texture::MaterialBlend;
+use crate::model::material_registry::MaterialRegistry;
+use crate::model::{Position, Real};
+
+use super::marching_cubes::{get_intersection, GridPoint};
+use super::stitching::CHUNK_SIDES;
+use super::{
+    ChunkLod, ChunkSide, Mesh, MeshVertex, PolygonizationOptions, Rectangle3D, VertexIndex,
+};
+
+// Closes the mesh with flat faces where it is cut open by the support.
+//
+// The bottom and the top of the support are always capped, the vertical sides only where the
+// chunk is at the edge of the loaded window, elsewhere the neighboring chunk continues the mesh.
+// A cap covers the part of the face inside the surface, bounded by the marching squares contour
+// the mesh ends on, so the capped mesh is closed. The cap vertices are not shared with the mesh,
+// they have the normal of the face so the caps are shaded flat.
+pub fn add_caps(
+    mesh: &mut Mesh,
+    support: Rectangle3D,
+    lod: ChunkLod,
+    density_func: &impl Fn(Position) -> Real,
+    material_func: &impl Fn(Position) -> MaterialBlend,
+    options: PolygonizationOptions,
+) {
+    let mut faces = vec![
+        CapFace::horizontal(support, lod.cell_size, false),
+        CapFace::horizontal(support, lod.cell_size, true),
+    ];
+    for side in CHUNK_SIDES {
+        if lod.is_window_edge(side) {
+            faces.push(CapFace::side(support, lod.cell_size, side));
+        }
+    }
+
+    for face in faces {
+        let mut cap = Cap::sample(face, density_func, options.surface_level);
+        cap.fill();
+
+        let index_offset = mesh.vertices.len() as VertexIndex;
+        mesh.indices
+            .extend(cap.indices.iter().map(|index| index_offset + index));
+        mesh.vertices.extend(cap.positions.iter().map(|position| {
+            let blend = material_func(*position);
+            cap_vertex(*position, face.normal, blend, &options.materials)
+        }));
+    }
+}
+
+// A face of the support spanned by the positive axes *u* and *v*, facing along *normal*
+#[derive(Clone, Copy)]
+struct CapFace {
+    origin: Position,
+    u: Vector3<Real>,
+    v: Vector3<Real>,
+    // Number of grid points along u and v
+    points_u: usize,
+    points_v: usize,
+    cell_size: Real,
+    normal: Vector3<Real>,
+}
+
+impl CapFace {
+    // The bottom or the top of the support
+    fn horizontal(support: Rectangle3D, cell_size: Real, top: bool) -> Self {
+        let (offset, normal) = match top {
+            true => (grid_extent(support.height, cell_size), Vector3::unit_y()),
+            false => (0.0, -Vector3::unit_y()),
+        };
+
+        CapFace {
+            origin: support.position + Vector3::unit_y() * offset,
+            u: Vector3::unit_x(),
+            v: Vector3::unit_z(),
+            points_u: grid_points(support.width, cell_size),
+            points_v: grid_points(support.depth, cell_size),
+            cell_size,
+            normal,
+        }
+    }
+
+    fn side(support: Rectangle3D, cell_size: Real, side: ChunkSide) -> Self {
+        let (u, size_u, normal, offset) = match side {
+            ChunkSide::NegativeX => (Vector3::unit_z(), support.depth, -Vector3::unit_x(), 0.0),
+            ChunkSide::PositiveX => (
+                Vector3::unit_z(),
+                support.depth,
+                Vector3::unit_x(),
+                grid_extent(support.width, cell_size),
+            ),
+            ChunkSide::NegativeZ => (Vector3::unit_x(), support.width, -Vector3::unit_z(), 0.0),
+            ChunkSide::PositiveZ => (
+                Vector3::unit_x(),
+                support.width,
+                Vector3::unit_z(),
+                grid_extent(support.depth, cell_size),
+            ),
+        };
+
+        CapFace {
+            origin: support.position + normal.map(Real::abs) * offset,
+            u,
+            v: Vector3::unit_y(),
+            points_u: grid_points(size_u, cell_size),
+            points_v: grid_points(support.height, cell_size),
+            cell_size,
+            normal,
+        }
+    }
+
+    fn point(&self, u: usize, v: usize) -> Position {
+        self.origin + (self.u * u as Real + self.v * v as Real) * self.cell_size
+    }
+}
+
+// Same point counts as the marching cubes grid
+fn grid_points(size: Real, cell_size: Real) -> usize {
+    (size / cell_size) as usize + 1
+}
+
+// Distance of the last grid point from the first one
+fn grid_extent(size: Real, cell_size: Real) -> Real {
+    (grid_points(size, cell_size) - 1) as Real * cell_size
+}
+
+// Point on a cap face, a grid point or the intersection on the grid edge from the point along u
+// or along v
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum CapPoint {
+    Corner(usize, usize),
+    EdgeU(usize, usize),
+    EdgeV(usize, usize),
+}
+
+// Marching squares of a face, filling the squares inside the surface instead of tracing them
+struct Cap {
+    face: CapFace,
+    densities: Vec<Real>,
+    surface_level: Real,
+
+    vertices: HashMap<CapPoint, VertexIndex>,
+    positions: Vec<Position>,
+    indices: Vec<VertexIndex>,
+}
+
+impl Cap {
+    fn sample(
+        face: CapFace,
+        density_func: &impl Fn(Position) -> Real,
+        surface_level: Real,
+    ) -> Self {
+        let densities = (0..face.points_v)
+            .flat_map(|v| (0..face.points_u).map(move |u| (u, v)))
+            .map(|(u, v)| density_func(face.point(u, v)))
+            .collect();
+
+        Cap {
+            face,
+            densities,
+            surface_level,
+            vertices: HashMap::new(),
+            positions: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn fill(&mut self) {
+        for v in 0..self.face.points_v.saturating_sub(1) {
+            for u in 0..self.face.points_u.saturating_sub(1) {
+                self.fill_square(u, v);
+            }
+        }
+    }
+
+    fn fill_square(&mut self, u: usize, v: usize) {
+        // Corners counter clockwise in (u, v) and the edges leaving them in that order
+        let corners = [(u, v), (u + 1, v), (u + 1, v + 1), (u, v + 1)];
+        let edges = [
+            CapPoint::EdgeU(u, v),
+            CapPoint::EdgeV(u + 1, v),
+            CapPoint::EdgeU(u, v + 1),
+            CapPoint::EdgeV(u, v),
+        ];
+        let inside = corners.map(|(u, v)| self.is_inside(u, v));
+
+        // The contour pairs the intersections in order like BorderContour, so the two inside
+        // corners of this ambiguous case are separated and the other one is joined
+        if inside == [false, true, false, true] {
+            self.add_polygon(&[edges[0], CapPoint::Corner(u + 1, v), edges[1]]);
+            self.add_polygon(&[edges[2], CapPoint::Corner(u, v + 1), edges[3]]);
+            return;
+        }
+
+        let mut polygon = Vec::with_capacity(6);
+        for i in 0..corners.len() {
+            if inside[i] {
+                let (corner_u, corner_v) = corners[i];
+                polygon.push(CapPoint::Corner(corner_u, corner_v));
+            }
+            if inside[i] != inside[(i + 1) % 4] {
+                polygon.push(edges[i]);
+            }
+        }
+
+        self.add_polygon(&polygon);
+    }
+
+    // Triangle fan of a convex polygon given counter clockwise in (u, v)
+    fn add_polygon(&mut self, polygon: &[CapPoint]) {
+        if polygon.len() < 3 {
+            return;
+        }
+
+        // Counter clockwise in (u, v) faces along u x v, flip the caps facing the other way
+        let flip = self.face.u.cross(self.face.v).dot(self.face.normal) < 0.0;
+
+        let vertices: Vec<VertexIndex> = polygon.iter().map(|point| self.vertex(*point)).collect();
+        for i in 1..vertices.len() - 1 {
+            let triangle = match flip {
+                true => [vertices[0], vertices[i + 1], vertices[i]],
+                false => [vertices[0], vertices[i], vertices[i + 1]],
+            };
+            self.indices.extend(triangle);
+        }
+    }
+
+    fn vertex(&mut self, point: CapPoint) -> VertexIndex {
+        if let Some(vertex) = self.vertices.get(&point) {
+            return *vertex;
+        }
+
+        // Edges are interpolated from their lower end, like the edges of the grid
+        let position = match point {
+            CapPoint::Corner(u, v) => self.face.point(u, v),
+            CapPoint::EdgeU(u, v) => self.intersection((u, v), (u + 1, v)),
+            CapPoint::EdgeV(u, v) => self.intersection((u, v), (u, v + 1)),
+        };
+
+        self.positions.push(position);
+        let vertex = (self.positions.len() - 1) as VertexIndex;
+        self.vertices.insert(point, vertex);
+
+        vertex
+    }
+
+    fn grid_point(&self, (u, v): (usize, usize)) -> GridPoint {
+        GridPoint {
+            position: self.face.point(u, v),
+            density: self.density(u, v),
+            case: None,
+        }
+    }
+
+    fn intersection(&self, start: (usize, usize), end: (usize, usize)) -> Position {
+        let (start, end) = (self.grid_point(start), self.grid_point(end));
+        get_intersection(start, end, self.surface_level).unwrap()
+    }
+
+    fn density(&self, u: usize, v: usize) -> Real {
+        self.densities[v * self.face.points_u + u]
+    }
+
+    fn is_inside(&self, u: usize, v: usize) -> bool {
+        self.density(u, v) < self.surface_level
+    }
+}
+
+fn cap_vertex(
+    position: Position,
+    normal: Vector3<Real>,
+    blend: MaterialBlend,
+    materials: &MaterialRegistry,
+) -> MeshVertex {
+    let biome_tint = blend.biome_tint();
+    let material_sample = blend.into_material_sample();
+
+    MeshVertex {
+        position: [position.x as f32, position.y as f32, position.z as f32],
+        normal: [normal.x as f32, normal.y as f32, normal.z as f32],
+        biome_tint,
+        vertex_material_weights: MaterialBlend::activate_materials(&material_sample, materials),
+        material_sample,
+        light: [0.0, 1.0],
+        lightmap_uv: [0.0, 0.0],
+        ambient_occlusion: 1.0,
+    }
+}
@@ -0,0 +1,80 @@
+use cgmath::Vector3;
+
+use crate::model::{Position, Real};
+
+use super::marching_cubes::{get_intersection, GridPoint};
+use super::mc_tables::{self, CORNER_OFFSETS, CUBE_CORNERS, EDGE_CORNERS};
+use super::PolygonizationOptions;
+
+// A single cell of the marching cubes grid taken apart, shown by the cell inspector.
+//
+// The cell is polygonized on its own with the same lookup tables and interpolation as the
+// chunk grids, so its triangles match the mesh of a full detail chunk.
+pub struct CellInspection {
+    // In the order of mc_tables::CORNER_OFFSETS
+    pub corners: [Position; CUBE_CORNERS],
+    pub densities: [Real; CUBE_CORNERS],
+    pub surface_level: Real,
+    pub case: usize,
+    // Triangles of the surface within the cell, in the winding order of the meshes
+    pub triangles: Vec<[Position; 3]>,
+}
+
+impl CellInspection {
+    // Corner 0 of the cell containing *point* in the full detail grid of the chunks.
+    // The grid points lie on multiples of the cell size in x and z,
+    // in y they are counted from the lower limit of the chunk support
+    pub fn cell_origin(point: Position, options: PolygonizationOptions) -> Position {
+        let cell_size = options.marching_cubes_cell_size;
+        let snap = |coordinate: Real, base: Real| {
+            base + ((coordinate - base) / cell_size).floor() * cell_size
+        };
+
+        Position::new(
+            snap(point.x, 0.0),
+            snap(point.y, options.y_low_limit),
+            snap(point.z, 0.0),
+        )
+    }
+
+    // The cell with its corner 0 at *origin*
+    pub fn new(
+        origin: Position,
+        density_func: impl Fn(Position) -> Real,
+        options: PolygonizationOptions,
+    ) -> Self {
+        let cell_size = options.marching_cubes_cell_size;
+        let corners = CORNER_OFFSETS
+            .map(|[x, y, z]| origin + Vector3::new(x as Real, y as Real, z as Real) * cell_size);
+        let densities = corners.map(density_func);
+
+        let surface_level = options.surface_level;
+        let case = mc_tables::cell_case(|corner| densities[corner] < surface_level);
+
+        let grid_point = |corner: usize| GridPoint {
+            position: corners[corner],
+            density: densities[corner],
+            case: Some(case),
+        };
+        let triangles = mc_tables::triangles(case)
+            .map(|triangle| {
+                triangle.map(|edge| {
+                    let [start, end] = EDGE_CORNERS[edge];
+                    get_intersection(grid_point(start), grid_point(end), surface_level).unwrap()
+                })
+            })
+            .collect();
+
+        CellInspection {
+            corners,
+            densities,
+            surface_level,
+            case,
+            triangles,
+        }
+    }
+
+    pub fn is_inside(&self, corner: usize) -> bool {
+        self.densities[corner] < self.surface_level
+    }
+}
@@ -0,0 +1,338 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::infrastructure::texture::MaterialBlend;
+use crate::minecraft::{BLOCKS_IN_CHUNK, MAX_BLOCK_Y, MIN_BLOCK_Y};
+use crate::model::material_registry::MaterialRegistry;
+use crate::model::PlanarPosition;
+
+use super::{Mesh, MeshVertex, VertexIndex};
+
+// Bumped whenever the layout below changes, older data is rejected instead of misread
+const FORMAT_VERSION: u32 = 6;
+
+// Room around the chunk for vertices the stitching, skirts and caps place past its blocks
+const HORIZONTAL_MARGIN: f32 = 1.0;
+const VERTICAL_MARGIN: f32 = 16.0;
+
+const POSITION_STEPS: f32 = u16::MAX as f32;
+const NORMAL_STEPS: f32 = u8::MAX as f32;
+const WEIGHT_STEPS: f32 = u8::MAX as f32;
+
+// Compact binary form of a chunk mesh, meant for storing meshes on disk and sending
+// them over the network.
+//
+// Positions are quantized to 16 bits per axis relative to the origin of the chunk, over the
+// column of the chunk from the bottom of the world to the build limit. The error stays below
+// a hundredth of a block vertically and far below that horizontally.
+// Normals are octahedral encoded into two bytes,
+// material samples, light, biome tints and ambient occlusion into one byte each.
+// The material weights are computed from the samples with the activation thresholds passed to decode.
+// The serialized form is additionally compressed with LZ4.
+//
+// The content hash of the chunk the mesh was built from is stored alongside,
+// a stored mesh is stale when it no longer matches the hash of the loaded chunk.
+#[derive(Serialize, Deserialize)]
+pub struct CompressedMesh {
+    version: u32,
+    chunk_hash: u64,
+    chunk_origin: [f32; 2],
+    positions: Vec<[u16; 3]>,
+    normals: Vec<[u8; 2]>,
+    material_samples: Vec<[[u8; 4]; 4]>,
+    light: Vec<[u8; 2]>,
+    biome_tints: Vec<[u8; 3]>,
+    ambient_occlusion: Vec<u8>,
+    indices: Vec<VertexIndex>,
+}
+
+impl CompressedMesh {
+    pub fn encode(mesh: &Mesh, chunk_origin: PlanarPosition, chunk_hash: u64) -> Self {
+        let chunk_origin = [chunk_origin.x as f32, chunk_origin.y as f32];
+        let (origin, extent) = quantization_box(chunk_origin);
+
+        let quantize_position = |position: [f32; 3]| {
+            [0, 1, 2].map(|axis| {
+                let relative = (position[axis] - origin[axis]) / extent[axis];
+                (relative.clamp(0.0, 1.0) * POSITION_STEPS).round() as u16
+            })
+        };
+
+        CompressedMesh {
+            version: FORMAT_VERSION,
+            chunk_hash,
+            chunk_origin,
+            positions: mesh
+                .vertices
+                .iter()
+                .map(|vertex| quantize_position(vertex.position))
+                .collect(),
+            normals: mesh
+                .vertices
+                .iter()
+                .map(|vertex| encode_normal(vertex.normal))
+                .collect(),
+            material_samples: mesh
+                .vertices
+                .iter()
+                .map(|vertex| vertex.material_sample.map(|row| row.map(quantize_unit)))
+                .collect(),
+            light: mesh
+                .vertices
+                .iter()
+                .map(|vertex| vertex.light.map(quantize_unit))
+                .collect(),
+            biome_tints: mesh
+                .vertices
+                .iter()
+                .map(|vertex| vertex.biome_tint.map(quantize_unit))
+                .collect(),
+            ambient_occlusion: mesh
+                .vertices
+                .iter()
+                .map(|vertex| quantize_unit(vertex.ambient_occlusion))
+                .collect(),
+            indices: mesh.indices.clone(),
+        }
+    }
+
+    pub fn chunk_hash(&self) -> u64 {
+        self.chunk_hash
+    }
+
+    pub fn decode(&self, materials: &MaterialRegistry) -> Mesh {
+        let (origin, extent) = quantization_box(self.chunk_origin);
+        let vertices = (0..self.positions.len())
+            .map(|i| {
+                let position = [0, 1, 2].map(|axis| {
+                    let relative = self.positions[i][axis] as f32 / POSITION_STEPS;
+                    origin[axis] + relative * extent[axis]
+                });
+
+                let material_sample = self.material_samples[i]
+                    .map(|row| row.map(|weight| weight as f32 / WEIGHT_STEPS));
+
+                MeshVertex {
+                    position,
+                    normal: decode_normal(self.normals[i]),
+                    vertex_material_weights: MaterialBlend::activate_materials(
+                        &material_sample,
+                        materials,
+                    ),
+                    material_sample,
+                    light: self.light[i].map(|light| light as f32 / WEIGHT_STEPS),
+                    lightmap_uv: [0.0, 0.0],
+                    biome_tint: self.biome_tints[i].map(|tint| tint as f32 / WEIGHT_STEPS),
+                    ambient_occlusion: self.ambient_occlusion[i] as f32 / WEIGHT_STEPS,
+                }
+            })
+            .collect();
+
+        Mesh {
+            vertices,
+            indices: self.indices.clone(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let serialized = bincode::serialize(self).map_err(invalid_data)?;
+        Ok(lz4_flex::compress_prepend_size(&serialized))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let serialized = lz4_flex::decompress_size_prepended(bytes).map_err(invalid_data)?;
+        let mesh: CompressedMesh = bincode::deserialize(&serialized).map_err(invalid_data)?;
+
+        if mesh.version != FORMAT_VERSION {
+            let message = format!(
+                "mesh format version {} is not supported, expected {}",
+                mesh.version, FORMAT_VERSION
+            );
+            return Err(invalid_data(message));
+        }
+
+        let vertex_count = mesh.positions.len();
+        let attributes_match = mesh.normals.len() == vertex_count
+            && mesh.material_samples.len() == vertex_count
+            && mesh.light.len() == vertex_count
+            && mesh.biome_tints.len() == vertex_count
+            && mesh.ambient_occlusion.len() == vertex_count;
+        let indices_valid = mesh
+            .indices
+            .iter()
+            .all(|index| (*index as usize) < vertex_count);
+        if !attributes_match || !indices_valid {
+            return Err(invalid_data("mesh data is inconsistent"));
+        }
+
+        Ok(mesh)
+    }
+}
+
+impl Mesh {
+    pub fn to_compressed_bytes(
+        &self,
+        chunk_origin: PlanarPosition,
+        chunk_hash: u64,
+    ) -> io::Result<Vec<u8>> {
+        CompressedMesh::encode(self, chunk_origin, chunk_hash).to_bytes()
+    }
+
+    // Fails when the stored mesh was built from blocks other than the ones hashed to *chunk_hash*
+    pub fn from_compressed_bytes(
+        bytes: &[u8],
+        chunk_hash: u64,
+        materials: &MaterialRegistry,
+    ) -> io::Result<Mesh> {
+        let mesh = CompressedMesh::from_bytes(bytes)?;
+        if mesh.chunk_hash != chunk_hash {
+            return Err(invalid_data("the mesh was built from different chunk data"));
+        }
+
+        Ok(mesh.decode(materials))
+    }
+}
+
+// Corner and size of the box the positions are quantized in, the column of the chunk
+// with some margin around it
+fn quantization_box(chunk_origin: [f32; 2]) -> ([f32; 3], [f32; 3]) {
+    let origin = [
+        chunk_origin[0] - HORIZONTAL_MARGIN,
+        MIN_BLOCK_Y as f32 - VERTICAL_MARGIN,
+        chunk_origin[1] - HORIZONTAL_MARGIN,
+    ];
+    let width = BLOCKS_IN_CHUNK as f32 + 2.0 * HORIZONTAL_MARGIN;
+    let height = (MAX_BLOCK_Y - MIN_BLOCK_Y) as f32 + 2.0 * VERTICAL_MARGIN;
+
+    (origin, [width, height, width])
+}
+
+// Values in [0, 1] such as the material samples, light and biome tints
+fn quantize_unit(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * WEIGHT_STEPS).round() as u8
+}
+
+// Projects the unit sphere onto an octahedron and unfolds it into a square,
+// the lower half of the octahedron is folded over the diagonals of the upper one
+fn encode_normal(normal: [f32; 3]) -> [u8; 2] {
+    let [x, y, z] = normal;
+    let length = x.abs() + y.abs() + z.abs();
+    if length == 0.0 {
+        return [quantize_signed(0.0), quantize_signed(0.0)];
+    }
+
+    let (u, v) = (x / length, z / length);
+    let (u, v) = match y < 0.0 {
+        true => ((1.0 - v.abs()) * u.signum(), (1.0 - u.abs()) * v.signum()),
+        false => (u, v),
+    };
+
+    [quantize_signed(u), quantize_signed(v)]
+}
+
+fn decode_normal(encoded: [u8; 2]) -> [f32; 3] {
+    let u = encoded[0] as f32 / NORMAL_STEPS * 2.0 - 1.0;
+    let v = encoded[1] as f32 / NORMAL_STEPS * 2.0 - 1.0;
+
+    let y = 1.0 - u.abs() - v.abs();
+    let (x, z) = match y < 0.0 {
+        true => ((1.0 - v.abs()) * u.signum(), (1.0 - u.abs()) * v.signum()),
+        false => (u, v),
+    };
+
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
+}
+
+fn quantize_signed(value: f32) -> u8 {
+    ((value.clamp(-1.0, 1.0) + 1.0) / 2.0 * NORMAL_STEPS).round() as u8
+}
+
+fn invalid_data(error: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chunk x -2, z 5
+    const CHUNK_ORIGIN: PlanarPosition = PlanarPosition::new(-32.0, 80.0);
+
+    fn vertex(position: [f32; 3], normal: [f32; 3]) -> MeshVertex {
+        let material_sample = [[0.75, 0.25, 0.0, 0.0], [0.0; 4], [0.0; 4], [0.0; 4]];
+
+        MeshVertex {
+            position,
+            normal,
+            vertex_material_weights: MaterialBlend::activate_materials(
+                &material_sample,
+                &MaterialRegistry::defaults(),
+            ),
+            material_sample,
+            light: [0.5, 1.0],
+            lightmap_uv: [0.0, 0.0],
+            biome_tint: [0.2, 0.6, 0.1],
+            ambient_occlusion: 0.8,
+        }
+    }
+
+    fn triangle() -> Mesh {
+        Mesh {
+            vertices: vec![
+                vertex([-32.0, -64.0, 80.0], [0.0, 1.0, 0.0]),
+                vertex([-16.25, 63.5, 95.75], [0.6, -0.8, 0.0]),
+                vertex([-20.125, 319.0, 81.5], [0.0, 0.0, -1.0]),
+            ],
+            indices: vec![0, 1, 2],
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_the_mesh_within_the_quantization_error() {
+        let mesh = triangle();
+
+        let bytes = mesh.to_compressed_bytes(CHUNK_ORIGIN, 42).unwrap();
+        let decoded =
+            Mesh::from_compressed_bytes(&bytes, 42, &MaterialRegistry::defaults()).unwrap();
+
+        assert_eq!(decoded.indices, mesh.indices);
+        for (decoded, original) in decoded.vertices.iter().zip(&mesh.vertices) {
+            for axis in 0..3 {
+                let position_error = (decoded.position[axis] - original.position[axis]).abs();
+                assert!(position_error < 0.01, "{:?}", decoded.position);
+
+                let normal_error = (decoded.normal[axis] - original.normal[axis]).abs();
+                assert!(normal_error < 0.02, "{:?}", decoded.normal);
+            }
+            assert!((decoded.ambient_occlusion - original.ambient_occlusion).abs() < 0.01);
+            assert!((decoded.light[0] - original.light[0]).abs() < 0.01);
+        }
+    }
+
+    // The quantization box is fixed by the chunk, a mesh covering a small part of it
+    // gets no more precision than one covering the whole column
+    #[test]
+    fn positions_are_quantized_relative_to_the_chunk_origin() {
+        let mesh = triangle();
+        let encoded = CompressedMesh::encode(&mesh, CHUNK_ORIGIN, 0);
+
+        assert_eq!(encoded.chunk_origin, [-32.0, 80.0]);
+        let (origin, extent) = quantization_box(encoded.chunk_origin);
+        assert_eq!(origin, [-33.0, -80.0, 79.0]);
+        assert_eq!(extent, [18.0, 416.0, 18.0]);
+
+        let first = encoded.positions[0];
+        let expected = [1.0 / 18.0, 16.0 / 416.0, 1.0 / 18.0]
+            .map(|relative| (relative * POSITION_STEPS).round() as u16);
+        assert_eq!(first, expected);
+    }
+
+    #[test]
+    fn mesh_of_other_blocks_is_rejected() {
+        let bytes = triangle().to_compressed_bytes(CHUNK_ORIGIN, 42).unwrap();
+
+        assert!(Mesh::from_compressed_bytes(&bytes, 43, &MaterialRegistry::defaults()).is_err());
+    }
+}
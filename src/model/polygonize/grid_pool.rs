@@ -0,0 +1,80 @@
+use std::cell::RefCell;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::marching_cubes::GridPoint;
+
+// Grid point buffers kept between chunk builds.
+//
+// The chunks are built on the rayon worker threads, each of them keeps the buffer
+// of the last grid it dropped. The next grid built on the same thread reuses it
+// instead of allocating the whole grid again, so repeated rebuilds allocate only
+// when a grid outgrows every grid built on that thread before.
+thread_local! {
+    static SCRATCH: RefCell<Vec<GridPoint>> = RefCell::new(Vec::new());
+}
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static REUSES: AtomicUsize = AtomicUsize::new(0);
+static POOLED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GridPoolStats {
+    // Grids that needed a new or bigger buffer
+    pub allocations: usize,
+    pub reuses: usize,
+    // Memory held by the idle buffers of all threads
+    pub pooled_bytes: usize,
+}
+
+impl GridPoolStats {
+    // Portion of the grids built without allocating
+    pub fn reuse_rate(&self) -> f64 {
+        match self.allocations + self.reuses {
+            0 => 0.0,
+            grids => self.reuses as f64 / grids as f64,
+        }
+    }
+}
+
+pub fn grid_pool_stats() -> GridPoolStats {
+    GridPoolStats {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        reuses: REUSES.load(Ordering::Relaxed),
+        pooled_bytes: POOLED_BYTES.load(Ordering::Relaxed),
+    }
+}
+
+fn buffer_bytes(buffer: &Vec<GridPoint>) -> usize {
+    buffer.capacity() * mem::size_of::<GridPoint>()
+}
+
+// Empty buffer with room for at least *points* grid points
+pub(super) fn take(points: usize) -> Vec<GridPoint> {
+    let mut buffer = SCRATCH.with(|scratch| scratch.take());
+    POOLED_BYTES.fetch_sub(buffer_bytes(&buffer), Ordering::Relaxed);
+
+    buffer.clear();
+    if buffer.capacity() >= points {
+        REUSES.fetch_add(1, Ordering::Relaxed);
+    } else {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        buffer.reserve_exact(points);
+    }
+
+    buffer
+}
+
+// Keeps the bigger of the returned buffer and the one already pooled on this thread,
+// a thread building nested grids would otherwise pool only the last one
+pub(super) fn give_back(buffer: Vec<GridPoint>) {
+    // The pool of a thread that is shutting down is already gone
+    let _ = SCRATCH.try_with(|scratch| {
+        let mut scratch = scratch.borrow_mut();
+        if buffer.capacity() > scratch.capacity() {
+            POOLED_BYTES.fetch_add(buffer_bytes(&buffer), Ordering::Relaxed);
+            POOLED_BYTES.fetch_sub(buffer_bytes(&scratch), Ordering::Relaxed);
+            *scratch = buffer;
+        }
+    });
+}
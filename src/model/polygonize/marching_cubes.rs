@@ -1,20 +1,20 @@
 use cgmath::{InnerSpace, Point3, Vector3, Zero};
-use glium::implement_vertex;
 use itertools::Itertools;
+use rayon::prelude::*;
+use std::io::{self, Write};
 
 use crate::{
-    infrastructure::texture::MaterialBlend,
-    model::{Position, Real},
+    infrastructure::texture::{MaterialBlend, MaterialSample},
+    model::{material_registry::MaterialRegistry, Position, Real},
 };
 
 use crate::model::implicit::normal;
 
+use super::grid_pool;
+use super::mc_tables::{self, CUBE_EDGES};
+use super::occlusion;
 use super::PolygonizationOptions;
 
-// Needs to be slightly larger than 0, even though we want to display the isosurface at 0.
-// Otherwise we get weird aliasing when rendering implicit blocks
-const SURFACE_LEVEL: Real = 0.0001;
-
 pub struct Mesh {
     // Vertices of the mesh
     pub vertices: Vec<MeshVertex>,
@@ -42,6 +42,14 @@ impl Mesh {
         other.vertices.extend_from_slice(self.vertices.as_slice());
     }
 
+    // Computes the material weights from the samples again, after the activation thresholds changed
+    pub fn recolor(&mut self, materials: &MaterialRegistry) {
+        for vertex in self.vertices.iter_mut() {
+            vertex.vertex_material_weights =
+                MaterialBlend::activate_materials(&vertex.material_sample, materials);
+        }
+    }
+
     pub fn copy_merge<'a>(meshes: impl Iterator<Item = &'a Mesh>) -> Self {
         let mut merged_mesh = Mesh::empty();
         meshes.for_each(|mesh| mesh.copy_into(&mut merged_mesh));
@@ -49,6 +57,25 @@ impl Mesh {
         merged_mesh
     }
 
+    // Writes the vertices, normals and triangles as a single OBJ object
+    pub fn write_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+        for vertex in &self.vertices {
+            let [x, y, z] = vertex.position;
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for vertex in &self.vertices {
+            let [x, y, z] = vertex.normal;
+            writeln!(writer, "vn {} {} {}", x, y, z)?;
+        }
+
+        // OBJ indices start at 1
+        for (a, b, c) in self.indices.iter().map(|index| index + 1).tuples() {
+            writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+        }
+
+        Ok(())
+    }
+
     pub fn merge(meshes: &mut [Mesh]) -> Self {
         let mut merged_mesh = Mesh::empty();
 
@@ -65,6 +92,217 @@ impl Mesh {
     }
 }
 
+#[cfg(test)]
+impl Mesh {
+    // Fails at the first vertex or index that differs from *expected* to the last bit
+    pub fn assert_identical(&self, expected: &Mesh) {
+        assert_eq!(self.vertices.len(), expected.vertices.len(), "vertex count");
+        assert_eq!(self.indices.len(), expected.indices.len(), "index count");
+
+        let vertex_bits = |vertex: &MeshVertex| {
+            let floats = [vertex.position, vertex.normal, vertex.biome_tint]
+                .into_iter()
+                .flatten()
+                .chain(vertex.vertex_material_weights.into_iter().flatten())
+                .chain(vertex.material_sample.into_iter().flatten())
+                .chain(vertex.light)
+                .chain(vertex.lightmap_uv)
+                .chain([vertex.ambient_occlusion]);
+
+            floats.map(f32::to_bits).collect::<Vec<_>>()
+        };
+        for (index, (vertex, expected_vertex)) in
+            self.vertices.iter().zip(&expected.vertices).enumerate()
+        {
+            assert_eq!(
+                vertex_bits(vertex),
+                vertex_bits(expected_vertex),
+                "vertex {index} at {:?}",
+                expected_vertex.position
+            );
+        }
+
+        let first_difference = self
+            .indices
+            .iter()
+            .zip(&expected.indices)
+            .position(|(index, expected_index)| index != expected_index);
+        assert_eq!(first_difference, None, "first differing index");
+    }
+
+    // Fails at the first vertex that differs from *expected* by more than a compressed mesh
+    // quantizes it. The material weights and lightmap coordinates are left out,
+    // a compressed mesh recomputes them when it is read back.
+    pub fn assert_within_quantization(&self, expected: &Mesh) {
+        assert_eq!(self.vertices.len(), expected.vertices.len(), "vertex count");
+        assert_eq!(self.indices, expected.indices, "indices");
+
+        let close = |values: &[f32], expected: &[f32], error: f32| {
+            values
+                .iter()
+                .zip(expected)
+                .all(|(value, expected)| (value - expected).abs() <= error)
+        };
+        let unit_values = |vertex: &MeshVertex| {
+            let samples = vertex.material_sample.into_iter().flatten();
+            let rest = vertex.light.into_iter().chain(vertex.biome_tint);
+
+            samples
+                .chain(rest)
+                .chain([vertex.ambient_occlusion])
+                .collect::<Vec<_>>()
+        };
+        for (index, (vertex, expected_vertex)) in
+            self.vertices.iter().zip(&expected.vertices).enumerate()
+        {
+            let within = close(&vertex.position, &expected_vertex.position, 0.01)
+                && close(&vertex.normal, &expected_vertex.normal, 0.02)
+                && close(
+                    &unit_values(vertex),
+                    &unit_values(expected_vertex),
+                    1.0 / 255.0,
+                );
+            assert!(within, "vertex {index} at {:?}", expected_vertex.position);
+        }
+    }
+}
+
+// Fixtures and helpers shared by the tests that build meshes
+#[cfg(test)]
+pub(crate) mod test_support {
+    use std::sync::Arc;
+
+    use cgmath::InnerSpace;
+
+    use crate::config::WORLD_SIZE;
+    use crate::imgui_wrapper::SmoothMeshOptions;
+    use crate::infrastructure::texture::MaterialBlend;
+    use crate::minecraft;
+    use crate::model::chunk::{Chunk, ChunkPosition};
+    use crate::model::common::BlockType;
+    use crate::model::discrete::WorldChunks;
+    use crate::model::implicit::smooth::polygonize_chunk;
+    use crate::model::polygonize::{polygonize, ChunkLod, Rectangle3D};
+    use crate::model::{Coord, Position, Real};
+
+    use super::{Mesh, PolygonizationOptions};
+
+    // The default options on a slice of the world around the fixture surfaces
+    pub fn fixture_options() -> PolygonizationOptions {
+        PolygonizationOptions {
+            y_low_limit: 52.0,
+            y_size: 16.0,
+            octree_density: true,
+            ..SmoothMeshOptions::default().into()
+        }
+    }
+
+    // Rolling hills with a cave below them, the chunks of the fixture are 16 blocks wide
+    fn fixture_density(point: Position) -> Real {
+        let hills = 64.0 + 3.0 * (point.x * 0.3).sin() * (point.z * 0.2).cos();
+        let cave = 4.0 - (point - Position::new(20.0, 58.0, 12.0)).magnitude();
+
+        (point.y - hills).max(cave)
+    }
+
+    fn fixture_materials(point: Position) -> MaterialBlend {
+        let grass = (point.y - 60.0).clamp(0.0, 4.0) / 4.0;
+
+        let mut blend = MaterialBlend::new();
+        blend.mix(BlockType::Grass, grass);
+        blend.mix(BlockType::Stone, 1.0 - grass);
+        blend
+    }
+
+    // Four chunks of the fixture density around the cave
+    pub fn fixture_supports() -> Vec<Rectangle3D> {
+        [(0.0, 0.0), (16.0, 0.0), (0.0, 16.0), (16.0, 16.0)]
+            .map(|(x, z)| Rectangle3D {
+                position: Position::new(x, 56.0, z),
+                width: 16.0,
+                height: 16.0,
+                depth: 16.0,
+            })
+            .to_vec()
+    }
+
+    // Polygonizes the fixture density in *support*
+    pub fn build_support(support: Rectangle3D, options: PolygonizationOptions) -> Mesh {
+        polygonize(support, fixture_density, fixture_materials, options)
+    }
+
+    // Chunks in the middle of the fixture world, away from the edges of the window
+    pub const FIXTURE_CHUNKS: [(usize, usize); 4] = [(4, 4), (5, 4), (4, 5), (5, 5)];
+
+    // Rolling hills of stone covered with grass, with a tree of rigid wood and smoothed leaves
+    // in every few columns, so both passes and the rigid blocks take part in the density
+    fn fixture_chunk(position: ChunkPosition) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        let origin = chunk.coords().origin();
+
+        for x in 0..minecraft::BLOCKS_IN_CHUNK {
+            for z in 0..minecraft::BLOCKS_IN_CHUNK {
+                let (global_x, global_z) = (origin.x + x as Coord, origin.y + z as Coord);
+                let hills = 3.0 * (global_x * 0.3).sin() * (global_z * 0.2).cos();
+                let height = 60 + hills.round() as isize;
+
+                for y in 48..height {
+                    chunk.push_block(x, z, y, BlockType::Stone);
+                }
+                chunk.push_block(x, z, height, BlockType::Grass);
+
+                if x % 6 == 3 && z % 6 == 3 {
+                    for y in height + 1..height + 4 {
+                        chunk.push_block(x, z, y, BlockType::Wood);
+                    }
+                    chunk.push_block(x, z, height + 4, BlockType::Leaves);
+                }
+            }
+        }
+
+        chunk.build_surface();
+        chunk.build_octree();
+        chunk
+    }
+
+    pub fn fixture_world() -> WorldChunks {
+        let base = ChunkPosition::from_global_position_in_chunks(0, 0);
+
+        WorldChunks::from_fn(WORLD_SIZE, |x, z| {
+            Arc::new(fixture_chunk(base.offset(x as i32, z as i32)))
+        })
+    }
+
+    // Every chunk and its neighbours on the same grid
+    pub fn uniform_lod(cell_size: Real) -> ChunkLod {
+        ChunkLod {
+            cell_size,
+            neighbor_cell_sizes: [cell_size; 4],
+            window_edges: [false; 4],
+        }
+    }
+
+    // Polygonizes the chunk at *x*, *z* of the window the way the world builds it
+    pub fn build_chunk(
+        chunks: &WorldChunks,
+        (x, z): (usize, usize),
+        options: PolygonizationOptions,
+    ) -> Mesh {
+        let lod = uniform_lod(options.marching_cubes_cell_size);
+
+        polygonize_chunk(chunks, chunks.chunk_index(x, z), options, lod).0
+    }
+
+    // Runs *work* on a rayon pool of its own, one thread builds the way a serial build would
+    pub fn with_threads<T: Send>(threads: usize, work: impl FnOnce() -> T + Send) -> T {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(work)
+    }
+}
+
 // For each cell evaluate this many edge intersections,
 // the rest are evaluated as part of the <n> intersections evaluated for some other cell.
 //
@@ -79,37 +317,96 @@ const EDGE_INDICES: [u16; INTERSECTION_STRIDE] = [
     8, /*up edge*/
 ];
 
-// Grid has n points
-// this vector is 3*n long
-// for point i, this vector contains information about the back,right and up instersection
-// at index 3*i, 3*i + 1, 3*i + 2 respectively
+// Grid has n points, each has 3 edges
+// for point i, edge 3*i, 3*i + 1, 3*i + 2 is the back, right and up edge respectively
 type Intersection = Option<Position>;
-type IntersectionContainer = Vec<Intersection>;
+
+// Most edges are not intersected, so only the intersected ones store a position.
+// A set bit in *intersected* marks an intersected edge, the positions of those edges
+// are packed in *positions* in the order of the edges
+struct IntersectionContainer {
+    intersected: Vec<u64>,
+    positions: Vec<Position>,
+    edges: usize,
+}
+
+impl IntersectionContainer {
+    fn with_edges(edges: usize) -> Self {
+        IntersectionContainer {
+            intersected: Vec::with_capacity(edges / 64 + 1),
+            positions: Vec::new(),
+            edges: 0,
+        }
+    }
+
+    // Edges have to be pushed in order
+    fn push(&mut self, intersection: Intersection) {
+        let (word, bit) = (self.edges / 64, self.edges % 64);
+        if bit == 0 {
+            self.intersected.push(0);
+        }
+
+        if let Some(position) = intersection {
+            self.intersected[word] |= 1 << bit;
+            self.positions.push(position);
+        }
+        self.edges += 1;
+    }
+}
 
 // This is the type used in the index buffer, it must have fixed size, so no usize
-type VertexIndex = u32;
+pub type VertexIndex = u32;
 
-// Has the same length as IntersectionContainer
-// each element maps Intersection with the matching index to the vertex index in the resulting
-// vertex buffer
-// Option variants should match for each index
+// Maps the edges of IntersectionContainer to the vertex index in the resulting vertex buffer,
+// None for edges without an intersection
+//
+// The vertices are the packed positions, so the vertex of an edge is the number of intersected
+// edges before it. The counts before every bitset word are precomputed, the rest is counted
+// within the word.
 //
 // The mapping is decoupled from the Intersection to allow parallelization
-type IntersectionVertexMap = Vec<Option<VertexIndex>>;
+struct IntersectionVertexMap<'a> {
+    intersected: &'a [u64],
+    vertices_before: Vec<VertexIndex>,
+}
+
+impl IntersectionVertexMap<'_> {
+    fn get(&self, edge_index: usize) -> Option<VertexIndex> {
+        let (word, bit) = (edge_index / 64, edge_index % 64);
+        let bits = self.intersected[word];
+        if bits & (1 << bit) == 0 {
+            return None;
+        }
+
+        let below = bits & ((1 << bit) - 1);
+        Some(self.vertices_before[word] + below.count_ones())
+    }
+}
 
 pub fn polygonize(
     support: Rectangle3D,
-    sdf: impl Fn(Position) -> Real,
+    sdf: impl Fn(Position) -> Real + Sync,
     material_func: impl Fn(Position) -> MaterialBlend,
     options: PolygonizationOptions,
 ) -> Mesh {
-    let grid = Grid::new(support, &sdf, options.marching_cubes_cell_size);
+    let grid = Grid::new(
+        support,
+        &sdf,
+        options.marching_cubes_cell_size,
+        options.surface_level,
+    );
     let intersections = find_intersections(&grid);
 
     let vertex_mapping = build_vertex_mapping(&intersections);
     let indices = assemble_triangles(&grid, &vertex_mapping);
 
-    let vertices = build_mesh_vertices(&intersections, &indices, &sdf, &material_func);
+    let vertices = build_mesh_vertices(
+        &intersections.positions,
+        &indices,
+        &sdf,
+        &material_func,
+        options,
+    );
 
     Mesh { vertices, indices }
 }
@@ -117,20 +414,30 @@ pub fn polygonize(
 // Return a collection of mesh vertices
 // The vertices are in the same order they came in
 //
-// The vertices collection only contains actual vertices so its shorter than the intersections
-// collection which contains also None values.
-// For this reason a mapping of Intersection -> MeshVertex is required, see build_vertex_mapping
-fn build_mesh_vertices(
-    intersections: &IntersectionContainer,
+// For marching cubes the vertices are the packed intersection positions, the triangles refer
+// to them through the mapping of edges -> MeshVertex, see build_vertex_mapping
+pub(super) fn build_mesh_vertices(
+    vertex_positions: &Vec<Position>,
     indices: &Vec<VertexIndex>,
     density_func: &impl Fn(Position) -> Real,
     material_func: &impl Fn(Position) -> MaterialBlend,
+    options: PolygonizationOptions,
 ) -> Vec<MeshVertex> {
     let build_vertex = |vertex_position, vertex_normal: Vector3<Real>| {
         //let normal = normal::gradient(density_func, vertex_position);
         let normal = vertex_normal.normalize();
         let blend = material_func(vertex_position);
-        let weights = blend.into_material_weights();
+        let biome_tint = blend.biome_tint();
+        let material_sample = blend.into_material_sample();
+        let ambient_occlusion = match options.ambient_occlusion {
+            true => occlusion::vertex_occlusion(
+                vertex_position,
+                normal,
+                density_func,
+                options.surface_level,
+            ) as f32,
+            false => 1.0,
+        };
 
         MeshVertex {
             position: [
@@ -139,12 +446,19 @@ fn build_mesh_vertices(
                 vertex_position.z as f32,
             ],
             normal: [normal.x as f32, normal.y as f32, normal.z as f32],
-            vertex_material_weights: weights,
+            vertex_material_weights: MaterialBlend::activate_materials(
+                &material_sample,
+                &options.materials,
+            ),
+            material_sample,
+            light: [0.0, 1.0],
+            lightmap_uv: [0.0, 0.0],
+            biome_tint,
+            ambient_occlusion,
         }
     };
 
-    let vertex_positions: Vec<Position> = intersections.iter().filter_map(|x| *x).collect();
-    let vertex_normals = build_normals(&vertex_positions, indices, density_func);
+    let vertex_normals = build_normals(vertex_positions, indices, density_func);
     //let vertex_normals = build_triangle_normals(&vertex_positions, &indices);
 
     let vertices = vertex_positions
@@ -244,57 +558,78 @@ fn build_triangle_normals(
 }
 
 // Returnd a mapping of grid edges "intersections" to actual mesh vetices
-// For intersection at index i the map at index i contains the index of the vertex
+// For intersection at index i the map returns the index of the vertex
 // or none, if there is no intersection
 fn build_vertex_mapping(intersections: &IntersectionContainer) -> IntersectionVertexMap {
-    let mut mapping = IntersectionVertexMap::with_capacity(intersections.len());
+    let mut vertices_before = Vec::with_capacity(intersections.intersected.len());
 
     let mut vertex_index: VertexIndex = 0;
-    for intersection in intersections {
-        let mapping_value = if intersection.is_some() {
-            let index = vertex_index;
-            vertex_index += 1;
-
-            Some(index)
-        } else {
-            None
-        };
-
-        mapping.push(mapping_value);
+    for bits in &intersections.intersected {
+        vertices_before.push(vertex_index);
+        vertex_index += bits.count_ones();
     }
 
-    mapping
+    IntersectionVertexMap {
+        intersected: &intersections.intersected,
+        vertices_before,
+    }
 }
 
 // For each cell in the grid evaluate edges specified in EDGE_INDICES
 // and find the intersections points on them, if any
+//
+// The layers of the grid along z are evaluated in parallel, each of them lists its intersected
+// edges. The layers are then pushed into the container in order, so the edges keep the order
+// the vertex mapping expects.
 fn find_intersections(grid: &Grid) -> IntersectionContainer {
-    let mut intersections = IntersectionContainer::new();
-
-    // Loop over all points in the grid, for each point evaluate neighboring edges
-    for z in 0..grid.depth {
-        for y in 0..grid.height {
-            for x in 0..grid.width {
-                let base_cell_position = GridPosition { x, y, z };
-                let cell_case = get_cell_case(grid, base_cell_position);
-                let intersected_edges = EDGES_LOOKUP[cell_case];
-
-                let base_cell = grid.get_cell(base_cell_position).unwrap();
-                for edge_index in EDGE_INDICES.iter() {
-                    let edge_index = *edge_index;
-                    let is_edge_intersected = (intersected_edges & (1 << edge_index)) != 0;
-                    if !is_edge_intersected {
-                        intersections.push(None);
-                        continue;
-                    }
+    let layer_edges = grid.width * grid.height * INTERSECTION_STRIDE;
+    let mut intersections = IntersectionContainer::with_edges(layer_edges * grid.depth);
 
-                    if let Some(edge_cell) = get_edge_end(grid, base_cell_position, edge_index) {
-                        let intersection = get_intersection(base_cell, edge_cell);
-                        intersections.push(intersection);
-                    } else {
-                        // Do not evaluate intersections for edges outside the grid
-                        intersections.push(None);
-                    }
+    let layers: Vec<Vec<(usize, Position)>> = (0..grid.depth)
+        .into_par_iter()
+        .map(|z| find_layer_intersections(grid, z))
+        .collect();
+
+    for layer in layers {
+        let mut layer = layer.into_iter().peekable();
+        for edge in 0..layer_edges {
+            let intersection = layer.next_if(|(intersected_edge, _)| *intersected_edge == edge);
+            intersections.push(intersection.map(|(_, position)| position));
+        }
+    }
+
+    intersections
+}
+
+// Intersected edges of the grid points with the given z coordinate,
+// as the index of the edge within the layer and the intersection position
+fn find_layer_intersections(grid: &Grid, z: usize) -> Vec<(usize, Position)> {
+    let mut intersections = Vec::new();
+
+    // Loop over all points in the layer, for each point evaluate neighboring edges
+    for y in 0..grid.height {
+        for x in 0..grid.width {
+            let base_cell_position = GridPosition { x, y, z };
+            let cell_case = get_cell_case(grid, base_cell_position);
+            let intersected_edges = mc_tables::intersected_edges(cell_case);
+
+            let base_cell = grid.get_cell(base_cell_position).unwrap();
+            let first_edge = (x + y * grid.width) * INTERSECTION_STRIDE;
+            for (offset, edge_index) in EDGE_INDICES.iter().enumerate() {
+                let edge_index = *edge_index;
+                let is_edge_intersected = (intersected_edges & (1 << edge_index)) != 0;
+                if !is_edge_intersected {
+                    continue;
+                }
+
+                // Do not evaluate intersections for edges outside the grid
+                let Some(edge_cell) = get_edge_end(grid, base_cell_position, edge_index) else {
+                    continue;
+                };
+                if let Some(intersection) =
+                    get_intersection(base_cell, edge_cell, grid.surface_level)
+                {
+                    intersections.push((first_edge + offset, intersection));
                 }
             }
         }
@@ -314,14 +649,8 @@ fn assemble_triangles(grid: &Grid, vertex_mapping: &IntersectionVertexMap) -> Ve
                     get_edge_intersections_for_cell(grid, grid_position, vertex_mapping);
 
                 let case = get_cell_case(grid, grid_position);
-                let lookup_base = case * TRIANGLES * TRIANGLE_VERTICES;
-                let mut edge_index = TRIANGLES_LOOKUP[lookup_base];
-                let mut i = 0;
-                while edge_index != EDGE_INVALID_INDEX && (i / TRIANGLE_VERTICES) < TRIANGLES {
-                    indices.push(edge_vertex_map[edge_index as usize].unwrap());
-
-                    i += 1;
-                    edge_index = TRIANGLES_LOOKUP[lookup_base + i];
+                for triangle in mc_tables::triangles(case) {
+                    indices.extend(triangle.map(|edge| edge_vertex_map[edge].unwrap()));
                 }
             }
         }
@@ -336,7 +665,6 @@ enum CellEdge {
     Up,
 }
 
-const CUBE_EDGES: usize = 12;
 fn get_edge_intersections_for_cell(
     grid: &Grid,
     cell_position: GridPosition,
@@ -357,7 +685,7 @@ fn get_edge_intersections_for_cell(
         };
 
         let edge_index = cube_vertex_index + edge_offset;
-        vertex_mapping[edge_index]
+        vertex_mapping.get(edge_index)
     };
 
     // named after cube vertices
@@ -402,7 +730,11 @@ fn get_edge_end(grid: &Grid, edge_start: GridPosition, edge_index: u16) -> Optio
     grid.get_cell(end_position)
 }
 
-fn get_intersection(edge_start: GridPoint, edge_end: GridPoint) -> Intersection {
+pub(super) fn get_intersection(
+    edge_start: GridPoint,
+    edge_end: GridPoint,
+    surface_level: Real,
+) -> Intersection {
     let start_density = edge_start.density;
     let end_density = edge_end.density;
 
@@ -410,18 +742,13 @@ fn get_intersection(edge_start: GridPoint, edge_end: GridPoint) -> Intersection
         .position
         .zip(edge_end.position, |start_coord, end_coord| {
             start_coord
-                + (SURFACE_LEVEL - start_density) * (end_coord - start_coord)
+                + (surface_level - start_density) * (end_coord - start_coord)
                     / (end_density - start_density)
         });
 
     Some(intersection)
 }
 
-const CUBE_VERTICES: u16 = 8;
-// Assumes vertex index zero is left front bottom
-const ADD_X: [u16; 4] = [2, 3, 6, 7];
-const ADD_Y: [u16; 4] = [4, 5, 6, 7];
-const ADD_Z: [u16; 4] = [1, 2, 5, 6];
 fn get_cell_case(grid: &Grid, cell_index: GridPosition) -> usize {
     let mut base_cell = grid.get_cell(cell_index).unwrap();
 
@@ -429,31 +756,20 @@ fn get_cell_case(grid: &Grid, cell_index: GridPosition) -> usize {
         return lookup_index;
     }
 
-    let mut lookup_index: u32 = 255;
-    for i in 0..CUBE_VERTICES {
-        let grid_position = add(
-            cell_index,
-            if ADD_X.contains(&i) { 1 } else { 0 },
-            if ADD_Y.contains(&i) { 1 } else { 0 },
-            if ADD_Z.contains(&i) { 1 } else { 0 },
-        );
+    let case = mc_tables::cell_case(|corner| {
+        let [x, y, z] = mc_tables::CORNER_OFFSETS[corner];
 
         // If we go outside the grid, there is no edge..
-        if let Some(cell) = grid.get_cell(grid_position) {
-            if cell.density < SURFACE_LEVEL {
-                // WTFFF???
-                lookup_index &= !(1 << i);
-            }
-        }
-    }
+        grid.get_cell(add(cell_index, x, y, z))
+            .is_some_and(|cell| cell.density < grid.surface_level)
+    });
 
-    let case = lookup_index as usize;
     base_cell.case = Some(case);
     return case;
 }
 
 #[derive(Clone, Copy)]
-struct GridPoint {
+pub(super) struct GridPoint {
     pub position: Position,
     pub density: Real,
 
@@ -470,18 +786,22 @@ fn add(gp: GridPosition, x: usize, y: usize, z: usize) -> GridPosition {
     }
 }
 
-struct Grid {
+pub(super) struct Grid {
     data: Vec<GridPoint>,
     pub width: usize,
     pub height: usize,
     pub depth: usize,
+
+    // Density value at which the isosurface is extracted
+    pub surface_level: Real,
 }
 
 impl Grid {
     pub fn new(
         support: Rectangle3D,
-        density_function: impl Fn(Position) -> Real,
+        density_function: impl Fn(Position) -> Real + Sync,
         cell_size: Real,
+        surface_level: Real,
     ) -> Self {
         let depth_cells = (support.depth / cell_size) as usize + 1;
         let height_cells = (support.height / cell_size) as usize + 1;
@@ -489,15 +809,16 @@ impl Grid {
 
         // Create the grid 1 cell bigger in all dimensions
         // this way we have information about all points within the grid
-        let grid_point_offsets: Vec<(usize, usize, usize)> = (0..depth_cells)
-            .cartesian_product(0..height_cells)
-            .cartesian_product(0..width_cells)
-            .map(|((z, y), x)| (x, y, z))
-            .collect();
-
-        let grid_data: Vec<GridPoint> = grid_point_offsets
-            .into_iter()
-            .map(|(x, y, z)| {
+        //
+        // The densities are sampled in parallel, the points stay in the order of their index
+        let mut grid_data = grid_pool::take(depth_cells * height_cells * width_cells);
+        let points = (0..depth_cells * height_cells * width_cells)
+            .into_par_iter()
+            .map(|index| {
+                let x = index % width_cells;
+                let y = (index / width_cells) % height_cells;
+                let z = index / (width_cells * height_cells);
+
                 let point_position = Position::new(
                     support.position.x + (x as Real) * cell_size,
                     support.position.y + (y as Real) * cell_size,
@@ -510,14 +831,15 @@ impl Grid {
                     density: point_density,
                     case: None,
                 }
-            })
-            .collect();
+            });
+        grid_data.par_extend(points);
 
         Grid {
             data: grid_data,
             width: width_cells,
             height: height_cells,
             depth: depth_cells,
+            surface_level,
         }
     }
 
@@ -540,6 +862,12 @@ impl Grid {
     }
 }
 
+impl Drop for Grid {
+    fn drop(&mut self) {
+        grid_pool::give_back(std::mem::take(&mut self.data));
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Rectangle3D {
     pub position: Position,
@@ -548,190 +876,27 @@ pub struct Rectangle3D {
     pub depth: Real,
 }
 
+// Vertices are uploaded split into streams, see infrastructure/mesh_buffers.rs
 #[derive(Copy, Clone)]
 pub struct MeshVertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
     pub vertex_material_weights: [[f32; 4]; 4],
+    // The weights before the activation thresholds were applied, kept on the cpu for recoloring
+    pub material_sample: MaterialSample,
+
+    // Normalized block and sky light, filled in after polygonization
+    pub light: [f32; 2],
+
+    // Position in the baked lightmap, only set on meshes unwrapped by model::lightmap
+    pub lightmap_uv: [f32; 2],
+
+    // Multiplies the textures of grass and leaves, see model/biome.rs
+    pub biome_tint: [f32; 3],
+
+    // Share of the sky around the vertex not blocked by the terrain, 1 unless
+    // PolygonizationOptions::ambient_occlusion is on
+    pub ambient_occlusion: f32,
     //pub blend_coefficients: [f32; 4],
     //pub blend_indices: [u8; 4],
 }
-implement_vertex!(
-    MeshVertex,
-    position,
-    normal,
-    vertex_material_weights,
-    //blend_coefficients,
-    //blend_indices
-);
-
-const CASES: usize = 256;
-const EDGES_LOOKUP: [u16; CASES] = [
-    0x000, 0x109, 0x203, 0x30a, 0x406, 0x50f, 0x605, 0x70c, 0x80c, 0x905, 0xa0f, 0xb06, 0xc0a,
-    0xd03, 0xe09, 0xf00, 0x190, 0x099, 0x393, 0x29a, 0x596, 0x49f, 0x795, 0x69c, 0x99c, 0x895,
-    0xb9f, 0xa96, 0xd9a, 0xc93, 0xf99, 0xe90, 0x230, 0x339, 0x033, 0x13a, 0x636, 0x73f, 0x435,
-    0x53c, 0xa3c, 0xb35, 0x83f, 0x936, 0xe3a, 0xf33, 0xc39, 0xd30, 0x3a0, 0x2a9, 0x1a3, 0x0aa,
-    0x7a6, 0x6af, 0x5a5, 0x4ac, 0xbac, 0xaa5, 0x9af, 0x8a6, 0xfaa, 0xea3, 0xda9, 0xca0, 0x460,
-    0x569, 0x663, 0x76a, 0x066, 0x16f, 0x265, 0x36c, 0xc6c, 0xd65, 0xe6f, 0xf66, 0x86a, 0x963,
-    0xa69, 0xb60, 0x5f0, 0x4f9, 0x7f3, 0x6fa, 0x1f6, 0x0ff, 0x3f5, 0x2fc, 0xdfc, 0xcf5, 0xfff,
-    0xef6, 0x9fa, 0x8f3, 0xbf9, 0xaf0, 0x650, 0x759, 0x453, 0x55a, 0x256, 0x35f, 0x055, 0x15c,
-    0xe5c, 0xf55, 0xc5f, 0xd56, 0xa5a, 0xb53, 0x859, 0x950, 0x7c0, 0x6c9, 0x5c3, 0x4ca, 0x3c6,
-    0x2cf, 0x1c5, 0x0cc, 0xfcc, 0xec5, 0xdcf, 0xcc6, 0xbca, 0xac3, 0x9c9, 0x8c0, 0x8c0, 0x9c9,
-    0xac3, 0xbca, 0xcc6, 0xdcf, 0xec5, 0xfcc, 0x0cc, 0x1c5, 0x2cf, 0x3c6, 0x4ca, 0x5c3, 0x6c9,
-    0x7c0, 0x950, 0x859, 0xb53, 0xa5a, 0xd56, 0xc5f, 0xf55, 0xe5c, 0x15c, 0x055, 0x35f, 0x256,
-    0x55a, 0x453, 0x759, 0x650, 0xaf0, 0xbf9, 0x8f3, 0x9fa, 0xef6, 0xfff, 0xcf5, 0xdfc, 0x2fc,
-    0x3f5, 0x0ff, 0x1f6, 0x6fa, 0x7f3, 0x4f9, 0x5f0, 0xb60, 0xa69, 0x963, 0x86a, 0xf66, 0xe6f,
-    0xd65, 0xc6c, 0x36c, 0x265, 0x16f, 0x066, 0x76a, 0x663, 0x569, 0x460, 0xca0, 0xda9, 0xea3,
-    0xfaa, 0x8a6, 0x9af, 0xaa5, 0xbac, 0x4ac, 0x5a5, 0x6af, 0x7a6, 0x0aa, 0x1a3, 0x2a9, 0x3a0,
-    0xd30, 0xc39, 0xf33, 0xe3a, 0x936, 0x83f, 0xb35, 0xa3c, 0x53c, 0x435, 0x73f, 0x636, 0x13a,
-    0x033, 0x339, 0x230, 0xe90, 0xf99, 0xc93, 0xd9a, 0xa96, 0xb9f, 0x895, 0x99c, 0x69c, 0x795,
-    0x49f, 0x596, 0x29a, 0x393, 0x099, 0x190, 0xf00, 0xe09, 0xd03, 0xc0a, 0xb06, 0xa0f, 0x905,
-    0x80c, 0x70c, 0x605, 0x50f, 0x406, 0x30a, 0x203, 0x109, 0x000,
-];
-
-const EDGE_INVALID_INDEX: i16 = -1;
-const TRIANGLE_VERTICES: usize = 3;
-const TRIANGLES: usize = 5;
-const TRIANGLES_LOOKUP: [i16; CASES * TRIANGLES * 3] = [
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 8, 3, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, 0, 1, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 8, 3, 9, 8,
-    1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 2, 10, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, 0, 8, 3, 1, 2, 10, -1, -1, -1, -1, -1, -1, -1, -1, -1, 9, 2, 10, 0, 2, 9, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, 2, 8, 3, 2, 10, 8, 10, 9, 8, -1, -1, -1, -1, -1, -1, 3, 11, 2, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 11, 2, 8, 11, 0, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1,
-    9, 0, 2, 3, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 11, 2, 1, 9, 11, 9, 8, 11, -1, -1, -1,
-    -1, -1, -1, 3, 10, 1, 11, 10, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 10, 1, 0, 8, 10, 8, 11,
-    10, -1, -1, -1, -1, -1, -1, 3, 9, 0, 3, 11, 9, 11, 10, 9, -1, -1, -1, -1, -1, -1, 9, 8, 10, 10,
-    8, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, 4, 7, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, 4, 3, 0, 7, 3, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 1, 9, 8, 4, 7, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, 4, 1, 9, 4, 7, 1, 7, 3, 1, -1, -1, -1, -1, -1, -1, 1, 2, 10, 8, 4, 7, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, 3, 4, 7, 3, 0, 4, 1, 2, 10, -1, -1, -1, -1, -1, -1, 9, 2, 10, 9, 0,
-    2, 8, 4, 7, -1, -1, -1, -1, -1, -1, 2, 10, 9, 2, 9, 7, 2, 7, 3, 7, 9, 4, -1, -1, -1, 8, 4, 7,
-    3, 11, 2, -1, -1, -1, -1, -1, -1, -1, -1, -1, 11, 4, 7, 11, 2, 4, 2, 0, 4, -1, -1, -1, -1, -1,
-    -1, 9, 0, 1, 8, 4, 7, 2, 3, 11, -1, -1, -1, -1, -1, -1, 4, 7, 11, 9, 4, 11, 9, 11, 2, 9, 2, 1,
-    -1, -1, -1, 3, 10, 1, 3, 11, 10, 7, 8, 4, -1, -1, -1, -1, -1, -1, 1, 11, 10, 1, 4, 11, 1, 0, 4,
-    7, 11, 4, -1, -1, -1, 4, 7, 8, 9, 0, 11, 9, 11, 10, 11, 0, 3, -1, -1, -1, 4, 7, 11, 4, 11, 9,
-    9, 11, 10, -1, -1, -1, -1, -1, -1, 9, 5, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 9,
-    5, 4, 0, 8, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 5, 4, 1, 5, 0, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, 8, 5, 4, 8, 3, 5, 3, 1, 5, -1, -1, -1, -1, -1, -1, 1, 2, 10, 9, 5, 4, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, 3, 0, 8, 1, 2, 10, 4, 9, 5, -1, -1, -1, -1, -1, -1, 5, 2, 10, 5, 4, 2,
-    4, 0, 2, -1, -1, -1, -1, -1, -1, 2, 10, 5, 3, 2, 5, 3, 5, 4, 3, 4, 8, -1, -1, -1, 9, 5, 4, 2,
-    3, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 11, 2, 0, 8, 11, 4, 9, 5, -1, -1, -1, -1, -1, -1,
-    0, 5, 4, 0, 1, 5, 2, 3, 11, -1, -1, -1, -1, -1, -1, 2, 1, 5, 2, 5, 8, 2, 8, 11, 4, 8, 5, -1,
-    -1, -1, 10, 3, 11, 10, 1, 3, 9, 5, 4, -1, -1, -1, -1, -1, -1, 4, 9, 5, 0, 8, 1, 8, 10, 1, 8,
-    11, 10, -1, -1, -1, 5, 4, 0, 5, 0, 11, 5, 11, 10, 11, 0, 3, -1, -1, -1, 5, 4, 8, 5, 8, 10, 10,
-    8, 11, -1, -1, -1, -1, -1, -1, 9, 7, 8, 5, 7, 9, -1, -1, -1, -1, -1, -1, -1, -1, -1, 9, 3, 0,
-    9, 5, 3, 5, 7, 3, -1, -1, -1, -1, -1, -1, 0, 7, 8, 0, 1, 7, 1, 5, 7, -1, -1, -1, -1, -1, -1, 1,
-    5, 3, 3, 5, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, 9, 7, 8, 9, 5, 7, 10, 1, 2, -1, -1, -1, -1,
-    -1, -1, 10, 1, 2, 9, 5, 0, 5, 3, 0, 5, 7, 3, -1, -1, -1, 8, 0, 2, 8, 2, 5, 8, 5, 7, 10, 5, 2,
-    -1, -1, -1, 2, 10, 5, 2, 5, 3, 3, 5, 7, -1, -1, -1, -1, -1, -1, 7, 9, 5, 7, 8, 9, 3, 11, 2, -1,
-    -1, -1, -1, -1, -1, 9, 5, 7, 9, 7, 2, 9, 2, 0, 2, 7, 11, -1, -1, -1, 2, 3, 11, 0, 1, 8, 1, 7,
-    8, 1, 5, 7, -1, -1, -1, 11, 2, 1, 11, 1, 7, 7, 1, 5, -1, -1, -1, -1, -1, -1, 9, 5, 8, 8, 5, 7,
-    10, 1, 3, 10, 3, 11, -1, -1, -1, 5, 7, 0, 5, 0, 9, 7, 11, 0, 1, 0, 10, 11, 10, 0, 11, 10, 0,
-    11, 0, 3, 10, 5, 0, 8, 0, 7, 5, 7, 0, 11, 10, 5, 7, 11, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    10, 6, 5, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 8, 3, 5, 10, 6, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, 9, 0, 1, 5, 10, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 8, 3, 1, 9, 8, 5,
-    10, 6, -1, -1, -1, -1, -1, -1, 1, 6, 5, 2, 6, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 6, 5,
-    1, 2, 6, 3, 0, 8, -1, -1, -1, -1, -1, -1, 9, 6, 5, 9, 0, 6, 0, 2, 6, -1, -1, -1, -1, -1, -1, 5,
-    9, 8, 5, 8, 2, 5, 2, 6, 3, 2, 8, -1, -1, -1, 2, 3, 11, 10, 6, 5, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, 11, 0, 8, 11, 2, 0, 10, 6, 5, -1, -1, -1, -1, -1, -1, 0, 1, 9, 2, 3, 11, 5, 10, 6, -1,
-    -1, -1, -1, -1, -1, 5, 10, 6, 1, 9, 2, 9, 11, 2, 9, 8, 11, -1, -1, -1, 6, 3, 11, 6, 5, 3, 5, 1,
-    3, -1, -1, -1, -1, -1, -1, 0, 8, 11, 0, 11, 5, 0, 5, 1, 5, 11, 6, -1, -1, -1, 3, 11, 6, 0, 3,
-    6, 0, 6, 5, 0, 5, 9, -1, -1, -1, 6, 5, 9, 6, 9, 11, 11, 9, 8, -1, -1, -1, -1, -1, -1, 5, 10, 6,
-    4, 7, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, 4, 3, 0, 4, 7, 3, 6, 5, 10, -1, -1, -1, -1, -1,
-    -1, 1, 9, 0, 5, 10, 6, 8, 4, 7, -1, -1, -1, -1, -1, -1, 10, 6, 5, 1, 9, 7, 1, 7, 3, 7, 9, 4,
-    -1, -1, -1, 6, 1, 2, 6, 5, 1, 4, 7, 8, -1, -1, -1, -1, -1, -1, 1, 2, 5, 5, 2, 6, 3, 0, 4, 3, 4,
-    7, -1, -1, -1, 8, 4, 7, 9, 0, 5, 0, 6, 5, 0, 2, 6, -1, -1, -1, 7, 3, 9, 7, 9, 4, 3, 2, 9, 5, 9,
-    6, 2, 6, 9, 3, 11, 2, 7, 8, 4, 10, 6, 5, -1, -1, -1, -1, -1, -1, 5, 10, 6, 4, 7, 2, 4, 2, 0, 2,
-    7, 11, -1, -1, -1, 0, 1, 9, 4, 7, 8, 2, 3, 11, 5, 10, 6, -1, -1, -1, 9, 2, 1, 9, 11, 2, 9, 4,
-    11, 7, 11, 4, 5, 10, 6, 8, 4, 7, 3, 11, 5, 3, 5, 1, 5, 11, 6, -1, -1, -1, 5, 1, 11, 5, 11, 6,
-    1, 0, 11, 7, 11, 4, 0, 4, 11, 0, 5, 9, 0, 6, 5, 0, 3, 6, 11, 6, 3, 8, 4, 7, 6, 5, 9, 6, 9, 11,
-    4, 7, 9, 7, 11, 9, -1, -1, -1, 10, 4, 9, 6, 4, 10, -1, -1, -1, -1, -1, -1, -1, -1, -1, 4, 10,
-    6, 4, 9, 10, 0, 8, 3, -1, -1, -1, -1, -1, -1, 10, 0, 1, 10, 6, 0, 6, 4, 0, -1, -1, -1, -1, -1,
-    -1, 8, 3, 1, 8, 1, 6, 8, 6, 4, 6, 1, 10, -1, -1, -1, 1, 4, 9, 1, 2, 4, 2, 6, 4, -1, -1, -1, -1,
-    -1, -1, 3, 0, 8, 1, 2, 9, 2, 4, 9, 2, 6, 4, -1, -1, -1, 0, 2, 4, 4, 2, 6, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, 8, 3, 2, 8, 2, 4, 4, 2, 6, -1, -1, -1, -1, -1, -1, 10, 4, 9, 10, 6, 4, 11, 2,
-    3, -1, -1, -1, -1, -1, -1, 0, 8, 2, 2, 8, 11, 4, 9, 10, 4, 10, 6, -1, -1, -1, 3, 11, 2, 0, 1,
-    6, 0, 6, 4, 6, 1, 10, -1, -1, -1, 6, 4, 1, 6, 1, 10, 4, 8, 1, 2, 1, 11, 8, 11, 1, 9, 6, 4, 9,
-    3, 6, 9, 1, 3, 11, 6, 3, -1, -1, -1, 8, 11, 1, 8, 1, 0, 11, 6, 1, 9, 1, 4, 6, 4, 1, 3, 11, 6,
-    3, 6, 0, 0, 6, 4, -1, -1, -1, -1, -1, -1, 6, 4, 8, 11, 6, 8, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, 7, 10, 6, 7, 8, 10, 8, 9, 10, -1, -1, -1, -1, -1, -1, 0, 7, 3, 0, 10, 7, 0, 9, 10, 6, 7,
-    10, -1, -1, -1, 10, 6, 7, 1, 10, 7, 1, 7, 8, 1, 8, 0, -1, -1, -1, 10, 6, 7, 10, 7, 1, 1, 7, 3,
-    -1, -1, -1, -1, -1, -1, 1, 2, 6, 1, 6, 8, 1, 8, 9, 8, 6, 7, -1, -1, -1, 2, 6, 9, 2, 9, 1, 6, 7,
-    9, 0, 9, 3, 7, 3, 9, 7, 8, 0, 7, 0, 6, 6, 0, 2, -1, -1, -1, -1, -1, -1, 7, 3, 2, 6, 7, 2, -1,
-    -1, -1, -1, -1, -1, -1, -1, -1, 2, 3, 11, 10, 6, 8, 10, 8, 9, 8, 6, 7, -1, -1, -1, 2, 0, 7, 2,
-    7, 11, 0, 9, 7, 6, 7, 10, 9, 10, 7, 1, 8, 0, 1, 7, 8, 1, 10, 7, 6, 7, 10, 2, 3, 11, 11, 2, 1,
-    11, 1, 7, 10, 6, 1, 6, 7, 1, -1, -1, -1, 8, 9, 6, 8, 6, 7, 9, 1, 6, 11, 6, 3, 1, 3, 6, 0, 9, 1,
-    11, 6, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, 7, 8, 0, 7, 0, 6, 3, 11, 0, 11, 6, 0, -1, -1, -1,
-    7, 11, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 7, 6, 11, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, 3, 0, 8, 11, 7, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 1, 9, 11, 7, 6,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, 8, 1, 9, 8, 3, 1, 11, 7, 6, -1, -1, -1, -1, -1, -1, 10, 1,
-    2, 6, 11, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 2, 10, 3, 0, 8, 6, 11, 7, -1, -1, -1, -1,
-    -1, -1, 2, 9, 0, 2, 10, 9, 6, 11, 7, -1, -1, -1, -1, -1, -1, 6, 11, 7, 2, 10, 3, 10, 8, 3, 10,
-    9, 8, -1, -1, -1, 7, 2, 3, 6, 2, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, 7, 0, 8, 7, 6, 0, 6, 2,
-    0, -1, -1, -1, -1, -1, -1, 2, 7, 6, 2, 3, 7, 0, 1, 9, -1, -1, -1, -1, -1, -1, 1, 6, 2, 1, 8, 6,
-    1, 9, 8, 8, 7, 6, -1, -1, -1, 10, 7, 6, 10, 1, 7, 1, 3, 7, -1, -1, -1, -1, -1, -1, 10, 7, 6, 1,
-    7, 10, 1, 8, 7, 1, 0, 8, -1, -1, -1, 0, 3, 7, 0, 7, 10, 0, 10, 9, 6, 10, 7, -1, -1, -1, 7, 6,
-    10, 7, 10, 8, 8, 10, 9, -1, -1, -1, -1, -1, -1, 6, 8, 4, 11, 8, 6, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, 3, 6, 11, 3, 0, 6, 0, 4, 6, -1, -1, -1, -1, -1, -1, 8, 6, 11, 8, 4, 6, 9, 0, 1, -1, -1,
-    -1, -1, -1, -1, 9, 4, 6, 9, 6, 3, 9, 3, 1, 11, 3, 6, -1, -1, -1, 6, 8, 4, 6, 11, 8, 2, 10, 1,
-    -1, -1, -1, -1, -1, -1, 1, 2, 10, 3, 0, 11, 0, 6, 11, 0, 4, 6, -1, -1, -1, 4, 11, 8, 4, 6, 11,
-    0, 2, 9, 2, 10, 9, -1, -1, -1, 10, 9, 3, 10, 3, 2, 9, 4, 3, 11, 3, 6, 4, 6, 3, 8, 2, 3, 8, 4,
-    2, 4, 6, 2, -1, -1, -1, -1, -1, -1, 0, 4, 2, 4, 6, 2, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 9,
-    0, 2, 3, 4, 2, 4, 6, 4, 3, 8, -1, -1, -1, 1, 9, 4, 1, 4, 2, 2, 4, 6, -1, -1, -1, -1, -1, -1, 8,
-    1, 3, 8, 6, 1, 8, 4, 6, 6, 10, 1, -1, -1, -1, 10, 1, 0, 10, 0, 6, 6, 0, 4, -1, -1, -1, -1, -1,
-    -1, 4, 6, 3, 4, 3, 8, 6, 10, 3, 0, 3, 9, 10, 9, 3, 10, 9, 4, 6, 10, 4, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, 4, 9, 5, 7, 6, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 8, 3, 4, 9, 5, 11, 7, 6,
-    -1, -1, -1, -1, -1, -1, 5, 0, 1, 5, 4, 0, 7, 6, 11, -1, -1, -1, -1, -1, -1, 11, 7, 6, 8, 3, 4,
-    3, 5, 4, 3, 1, 5, -1, -1, -1, 9, 5, 4, 10, 1, 2, 7, 6, 11, -1, -1, -1, -1, -1, -1, 6, 11, 7, 1,
-    2, 10, 0, 8, 3, 4, 9, 5, -1, -1, -1, 7, 6, 11, 5, 4, 10, 4, 2, 10, 4, 0, 2, -1, -1, -1, 3, 4,
-    8, 3, 5, 4, 3, 2, 5, 10, 5, 2, 11, 7, 6, 7, 2, 3, 7, 6, 2, 5, 4, 9, -1, -1, -1, -1, -1, -1, 9,
-    5, 4, 0, 8, 6, 0, 6, 2, 6, 8, 7, -1, -1, -1, 3, 6, 2, 3, 7, 6, 1, 5, 0, 5, 4, 0, -1, -1, -1, 6,
-    2, 8, 6, 8, 7, 2, 1, 8, 4, 8, 5, 1, 5, 8, 9, 5, 4, 10, 1, 6, 1, 7, 6, 1, 3, 7, -1, -1, -1, 1,
-    6, 10, 1, 7, 6, 1, 0, 7, 8, 7, 0, 9, 5, 4, 4, 0, 10, 4, 10, 5, 0, 3, 10, 6, 10, 7, 3, 7, 10, 7,
-    6, 10, 7, 10, 8, 5, 4, 10, 4, 8, 10, -1, -1, -1, 6, 9, 5, 6, 11, 9, 11, 8, 9, -1, -1, -1, -1,
-    -1, -1, 3, 6, 11, 0, 6, 3, 0, 5, 6, 0, 9, 5, -1, -1, -1, 0, 11, 8, 0, 5, 11, 0, 1, 5, 5, 6, 11,
-    -1, -1, -1, 6, 11, 3, 6, 3, 5, 5, 3, 1, -1, -1, -1, -1, -1, -1, 1, 2, 10, 9, 5, 11, 9, 11, 8,
-    11, 5, 6, -1, -1, -1, 0, 11, 3, 0, 6, 11, 0, 9, 6, 5, 6, 9, 1, 2, 10, 11, 8, 5, 11, 5, 6, 8, 0,
-    5, 10, 5, 2, 0, 2, 5, 6, 11, 3, 6, 3, 5, 2, 10, 3, 10, 5, 3, -1, -1, -1, 5, 8, 9, 5, 2, 8, 5,
-    6, 2, 3, 8, 2, -1, -1, -1, 9, 5, 6, 9, 6, 0, 0, 6, 2, -1, -1, -1, -1, -1, -1, 1, 5, 8, 1, 8, 0,
-    5, 6, 8, 3, 8, 2, 6, 2, 8, 1, 5, 6, 2, 1, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 3, 6, 1, 6,
-    10, 3, 8, 6, 5, 6, 9, 8, 9, 6, 10, 1, 0, 10, 0, 6, 9, 5, 0, 5, 6, 0, -1, -1, -1, 0, 3, 8, 5, 6,
-    10, -1, -1, -1, -1, -1, -1, -1, -1, -1, 10, 5, 6, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, 11, 5, 10, 7, 5, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, 11, 5, 10, 11, 7, 5, 8, 3, 0, -1,
-    -1, -1, -1, -1, -1, 5, 11, 7, 5, 10, 11, 1, 9, 0, -1, -1, -1, -1, -1, -1, 10, 7, 5, 10, 11, 7,
-    9, 8, 1, 8, 3, 1, -1, -1, -1, 11, 1, 2, 11, 7, 1, 7, 5, 1, -1, -1, -1, -1, -1, -1, 0, 8, 3, 1,
-    2, 7, 1, 7, 5, 7, 2, 11, -1, -1, -1, 9, 7, 5, 9, 2, 7, 9, 0, 2, 2, 11, 7, -1, -1, -1, 7, 5, 2,
-    7, 2, 11, 5, 9, 2, 3, 2, 8, 9, 8, 2, 2, 5, 10, 2, 3, 5, 3, 7, 5, -1, -1, -1, -1, -1, -1, 8, 2,
-    0, 8, 5, 2, 8, 7, 5, 10, 2, 5, -1, -1, -1, 9, 0, 1, 5, 10, 3, 5, 3, 7, 3, 10, 2, -1, -1, -1, 9,
-    8, 2, 9, 2, 1, 8, 7, 2, 10, 2, 5, 7, 5, 2, 1, 3, 5, 3, 7, 5, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, 0, 8, 7, 0, 7, 1, 1, 7, 5, -1, -1, -1, -1, -1, -1, 9, 0, 3, 9, 3, 5, 5, 3, 7, -1, -1, -1,
-    -1, -1, -1, 9, 8, 7, 5, 9, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, 5, 8, 4, 5, 10, 8, 10, 11, 8,
-    -1, -1, -1, -1, -1, -1, 5, 0, 4, 5, 11, 0, 5, 10, 11, 11, 3, 0, -1, -1, -1, 0, 1, 9, 8, 4, 10,
-    8, 10, 11, 10, 4, 5, -1, -1, -1, 10, 11, 4, 10, 4, 5, 11, 3, 4, 9, 4, 1, 3, 1, 4, 2, 5, 1, 2,
-    8, 5, 2, 11, 8, 4, 5, 8, -1, -1, -1, 0, 4, 11, 0, 11, 3, 4, 5, 11, 2, 11, 1, 5, 1, 11, 0, 2, 5,
-    0, 5, 9, 2, 11, 5, 4, 5, 8, 11, 8, 5, 9, 4, 5, 2, 11, 3, -1, -1, -1, -1, -1, -1, -1, -1, -1, 2,
-    5, 10, 3, 5, 2, 3, 4, 5, 3, 8, 4, -1, -1, -1, 5, 10, 2, 5, 2, 4, 4, 2, 0, -1, -1, -1, -1, -1,
-    -1, 3, 10, 2, 3, 5, 10, 3, 8, 5, 4, 5, 8, 0, 1, 9, 5, 10, 2, 5, 2, 4, 1, 9, 2, 9, 4, 2, -1, -1,
-    -1, 8, 4, 5, 8, 5, 3, 3, 5, 1, -1, -1, -1, -1, -1, -1, 0, 4, 5, 1, 0, 5, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, 8, 4, 5, 8, 5, 3, 9, 0, 5, 0, 3, 5, -1, -1, -1, 9, 4, 5, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, 4, 11, 7, 4, 9, 11, 9, 10, 11, -1, -1, -1, -1, -1, -1, 0, 8, 3, 4,
-    9, 7, 9, 11, 7, 9, 10, 11, -1, -1, -1, 1, 10, 11, 1, 11, 4, 1, 4, 0, 7, 4, 11, -1, -1, -1, 3,
-    1, 4, 3, 4, 8, 1, 10, 4, 7, 4, 11, 10, 11, 4, 4, 11, 7, 9, 11, 4, 9, 2, 11, 9, 1, 2, -1, -1,
-    -1, 9, 7, 4, 9, 11, 7, 9, 1, 11, 2, 11, 1, 0, 8, 3, 11, 7, 4, 11, 4, 2, 2, 4, 0, -1, -1, -1,
-    -1, -1, -1, 11, 7, 4, 11, 4, 2, 8, 3, 4, 3, 2, 4, -1, -1, -1, 2, 9, 10, 2, 7, 9, 2, 3, 7, 7, 4,
-    9, -1, -1, -1, 9, 10, 7, 9, 7, 4, 10, 2, 7, 8, 7, 0, 2, 0, 7, 3, 7, 10, 3, 10, 2, 7, 4, 10, 1,
-    10, 0, 4, 0, 10, 1, 10, 2, 8, 7, 4, -1, -1, -1, -1, -1, -1, -1, -1, -1, 4, 9, 1, 4, 1, 7, 7, 1,
-    3, -1, -1, -1, -1, -1, -1, 4, 9, 1, 4, 1, 7, 0, 8, 1, 8, 7, 1, -1, -1, -1, 4, 0, 3, 7, 4, 3,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, 4, 8, 7, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 9,
-    10, 8, 10, 11, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, 3, 0, 9, 3, 9, 11, 11, 9, 10, -1, -1, -1,
-    -1, -1, -1, 0, 1, 10, 0, 10, 8, 8, 10, 11, -1, -1, -1, -1, -1, -1, 3, 1, 10, 11, 3, 10, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, 1, 2, 11, 1, 11, 9, 9, 11, 8, -1, -1, -1, -1, -1, -1, 3, 0, 9, 3,
-    9, 11, 1, 2, 9, 2, 11, 9, -1, -1, -1, 0, 2, 11, 8, 0, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    3, 2, 11, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 2, 3, 8, 2, 8, 10, 10, 8, 9, -1, -1,
-    -1, -1, -1, -1, 9, 10, 2, 0, 9, 2, -1, -1, -1, -1, -1, -1, -1, -1, -1, 2, 3, 8, 2, 8, 10, 0, 1,
-    8, 1, 10, 8, -1, -1, -1, 1, 10, 2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1, 3, 8, 9,
-    1, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, 0, 9, 1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, 0, 3, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1,
-];
@@ -1,31 +1,80 @@
 use crate::imgui_wrapper::SmoothMeshOptions;
 use crate::infrastructure::texture::MaterialBlend;
+use crate::minecraft::{MAX_BLOCK_Y, MIN_BLOCK_Y};
+use crate::settings::{self, Settings};
 
+pub use self::capping::add_caps;
+pub use self::cell_inspection::CellInspection;
+pub use self::compressed::CompressedMesh;
+pub use self::grid_pool::{grid_pool_stats, GridPoolStats};
+#[cfg(test)]
+pub(crate) use self::marching_cubes::test_support;
 pub use self::marching_cubes::Mesh;
 pub use self::marching_cubes::MeshVertex;
 pub use self::marching_cubes::Rectangle3D;
+pub use self::marching_cubes::VertexIndex;
+pub use self::skirt::add_skirts;
+pub use self::stitching::{stitch_chunk_borders, ChunkLod, ChunkSide};
 
+use super::material_registry::{self, MaterialRegistry};
 use super::Coord;
 use super::{Position, Real};
 
+mod boundary;
+mod capping;
+mod cell_inspection;
+mod compressed;
+mod grid_pool;
 mod marching_cubes;
+pub mod mc_tables;
+mod occlusion;
+mod skirt;
+mod stitching;
+mod surface_nets;
 
-//pub enum PolygonizationMethod {
-//    MarchingCubes,
-//}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolygonizationMethod {
+    MarchingCubes,
+    // One vertex per cell, fewer and better shaped triangles than marching cubes
+    SurfaceNets,
+}
+
+pub const ALL_POLYGONIZATION_METHODS: [PolygonizationMethod; 2] = [
+    PolygonizationMethod::MarchingCubes,
+    PolygonizationMethod::SurfaceNets,
+];
 
+impl PolygonizationMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PolygonizationMethod::MarchingCubes => "Marching cubes",
+            PolygonizationMethod::SurfaceNets => "Surface nets",
+        }
+    }
+}
+
+// Both methods end the mesh on the marching squares contour of the support faces,
+// so chunks of either method can be stitched with stitch_chunk_borders
 pub fn polygonize(
     support: Rectangle3D,
     density_func: impl Fn(Position) -> Real + Send + Sync,
     material_func: impl Fn(Position) -> MaterialBlend,
     options: PolygonizationOptions,
-    //method: PolygonizationMethod,
 ) -> Mesh {
-    self::marching_cubes::polygonize(support, density_func, material_func, options)
+    match options.method {
+        PolygonizationMethod::MarchingCubes => {
+            self::marching_cubes::polygonize(support, density_func, material_func, options)
+        }
+        PolygonizationMethod::SurfaceNets => {
+            self::surface_nets::polygonize(support, density_func, material_func, options)
+        }
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct PolygonizationOptions {
+    pub method: PolygonizationMethod,
+
     // Radius of the cube used as the convolution kernel used for density evaluation
     // NOTE: if this is larger than 1.0, 1 block thick walls will disappear
     pub kernel_size: Coord,
@@ -36,21 +85,219 @@ pub struct PolygonizationOptions {
     pub marching_cubes_cell_size: Real,
     pub y_low_limit: Coord,
     pub y_size: Coord,
+
+    // Density value at which the isosurface is extracted.
+    // Positive values deflate the terrain, negative values inflate it.
+    pub surface_level: Real,
+    pub density_remap: DensityRemap,
+
+    // Use coarser cells for chunks further away from the center of the loaded world
+    pub distance_lod: bool,
+
+    // Extrude the chunk mesh borders downwards to hide gaps between neighboring chunks
+    pub skirts: bool,
+
+    // Close the mesh at the bottom and the top of the Y range and at the edge of the loaded
+    // window, so the exported mesh is watertight
+    pub caps: bool,
+
+    // Approximate the density of large kernels from aggregated octree nodes
+    pub octree_density: bool,
+
+    // Sample the density once per chunk and interpolate it between the grid points
+    pub density_cache: bool,
+
+    // Darken vertices in creases and cavities by sampling the density around them,
+    // see occlusion.rs
+    pub ambient_occlusion: bool,
+
+    // Snapshot of the material registry, taken again when the mesh builds are dispatched
+    pub materials: MaterialRegistry,
+
+    // Snapshot of the settings, taken with the materials so a settings file reloaded
+    // during a build doesn't change the rest of it
+    pub settings: Settings,
+}
+
+// Number of rings of chunks around the center that are always built at full detail
+const FULL_DETAIL_RINGS: usize = 2;
+
+// Coarsest cells of the distant rings. The cell sizes of the rings are powers of two
+// that divide the 16 blocks of a chunk, so the grids of neighbouring rings line up at the chunk borders.
+// With coarser cells a chunk would be two cells wide and lose most of its terrain.
+const MAX_LOD_CELL_SIZE: Real = 4.0;
+
+// Heights of the blocks of the world
+pub const WORLD_BOTTOM: Coord = MIN_BLOCK_Y as Coord;
+pub const WORLD_TOP: Coord = MAX_BLOCK_Y as Coord;
+
+// Finest and coarsest cells of the mesh detail levels and the smallest kernel of the smoothness levels
+const MIN_CELL_SIZE: Real = 0.125;
+const MAX_CELL_SIZE: Real = 1.0;
+const MIN_KERNEL_SIZE: Coord = 0.5;
+
+// Densities lie in [-1, 1], at either end there is no surface left to extract
+const MAX_SURFACE_LEVEL: Real = 0.99;
+
+// The bias and gain curves divide by the parameter and by one minus it
+const DENSITY_REMAP_LIMITS: (Real, Real) = (0.01, 0.99);
+
+// An option that PolygonizationOptions::validate had to repair
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptionCorrection {
+    pub option: &'static str,
+    pub requested: Real,
+    pub corrected: Real,
+}
+
+impl PolygonizationOptions {
+    // Ring 0 is the center chunk, ring 1 are the 8 chunks surrounding it and so on.
+    // Each ring beyond the full detail rings doubles the cell size.
+    pub fn cell_size_for_ring(&self, ring: usize) -> Real {
+        if !self.distance_lod || ring <= FULL_DETAIL_RINGS {
+            return self.marching_cubes_cell_size;
+        }
+
+        let lod_level = (ring - FULL_DETAIL_RINGS) as i32;
+        let cell_size = self.marching_cubes_cell_size * (2.0 as Real).powi(lod_level);
+
+        cell_size.min(MAX_LOD_CELL_SIZE.max(self.marching_cubes_cell_size))
+    }
+
+    // Clamps the options into ranges the mesh builder can handle and returns what was changed.
+    // The Y range is kept within the world and at least one cell thick,
+    // a support without any cells would produce an empty or degenerate grid.
+    pub fn validate(&mut self) -> Vec<OptionCorrection> {
+        let mut corrections = Vec::new();
+        let mut repair = |option, value: &mut Real, min: Real, max: Real| {
+            let repaired = match value.is_nan() {
+                true => min,
+                false => value.clamp(min, max),
+            };
+            if repaired != *value {
+                corrections.push(OptionCorrection {
+                    option,
+                    requested: *value,
+                    corrected: repaired,
+                });
+                *value = repaired;
+            }
+        };
+
+        repair(
+            "cell size",
+            &mut self.marching_cubes_cell_size,
+            MIN_CELL_SIZE,
+            MAX_CELL_SIZE,
+        );
+        repair(
+            "kernel size",
+            &mut self.kernel_size,
+            MIN_KERNEL_SIZE,
+            Coord::MAX,
+        );
+
+        let min_y_size = self.marching_cubes_cell_size;
+        repair(
+            "y low limit",
+            &mut self.y_low_limit,
+            WORLD_BOTTOM,
+            WORLD_TOP - min_y_size,
+        );
+        repair(
+            "y size",
+            &mut self.y_size,
+            min_y_size,
+            WORLD_TOP - self.y_low_limit,
+        );
+
+        repair(
+            "surface level",
+            &mut self.surface_level,
+            -MAX_SURFACE_LEVEL,
+            MAX_SURFACE_LEVEL,
+        );
+        let (remap_min, remap_max) = DENSITY_REMAP_LIMITS;
+        repair(
+            "density bias",
+            &mut self.density_remap.bias,
+            remap_min,
+            remap_max,
+        );
+        repair(
+            "density gain",
+            &mut self.density_remap.gain,
+            remap_min,
+            remap_max,
+        );
+
+        corrections
+    }
 }
 
 impl From<SmoothMeshOptions> for PolygonizationOptions {
     fn from(value: SmoothMeshOptions) -> Self {
         Self {
-            kernel_size: kernel_size(value.smoothness_level),
-            marching_cubes_cell_size: cell_size(value.mesh_resolution_level),
+            method: value.polygonization_method,
+            kernel_size: value.kernel_size as Coord,
+            marching_cubes_cell_size: value.cell_size as Real,
             y_low_limit: value.y_low_limit as Coord,
             y_size: value.y_size as Coord,
+            surface_level: value.surface_level as Real + SURFACE_LEVEL_OFFSET,
+            density_remap: DensityRemap {
+                bias: value.density_bias as Real,
+                gain: value.density_gain as Real,
+            },
+            distance_lod: value.distance_lod,
+            skirts: value.skirts,
+            caps: value.caps,
+            octree_density: value.octree_density,
+            density_cache: value.density_cache,
+            ambient_occlusion: value.ambient_occlusion,
+            materials: material_registry::snapshot(),
+            settings: settings::current(),
         }
     }
 }
 
+// The surface level needs to be slightly larger than the requested value,
+// even though we want to display the isosurface at 0.
+// Otherwise we get weird aliasing when rendering implicit blocks
+const SURFACE_LEVEL_OFFSET: Real = 0.0001;
+
+// Remaps densities from [-1, 1] using Schlick's bias and gain curves.
+// Bias pushes densities towards one end of the range,
+// gain controls how quickly the density changes around the middle of the range.
+// A value of 0.5 leaves the density unchanged for both parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DensityRemap {
+    pub bias: Real,
+    pub gain: Real,
+}
+
+impl DensityRemap {
+    pub fn apply(&self, density: Real) -> Real {
+        let t = ((density + 1.0) * 0.5).clamp(0.0, 1.0);
+        let remapped = gain(bias(t, self.bias), self.gain);
+
+        2.0 * remapped - 1.0
+    }
+}
+
+fn bias(t: Real, b: Real) -> Real {
+    t / ((1.0 / b - 2.0) * (1.0 - t) + 1.0)
+}
+
+fn gain(t: Real, g: Real) -> Real {
+    if t < 0.5 {
+        bias(2.0 * t, g) * 0.5
+    } else {
+        1.0 - bias(2.0 - 2.0 * t, g) * 0.5
+    }
+}
+
 const SMOOTHNESS_STEP: Coord = 0.5;
-fn kernel_size(smoothness: u8) -> Coord {
+pub fn kernel_size_for_level(smoothness: u8) -> Coord {
     match smoothness {
         0 | 1 => 0.5,
         2 => 0.9,
@@ -58,7 +305,7 @@ fn kernel_size(smoothness: u8) -> Coord {
     }
 }
 
-fn cell_size(mesh_resolution_level: u8) -> Real {
+pub fn cell_size_for_level(mesh_resolution_level: u8) -> Real {
     let cells_per_vertex = match mesh_resolution_level {
         0 | 1 => 1,
         2 => 2,
@@ -68,3 +315,190 @@ fn cell_size(mesh_resolution_level: u8) -> Real {
 
     1.0 / (cells_per_vertex as Real)
 }
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use rayon::prelude::*;
+
+    use crate::model::discrete::WorldChunks;
+
+    use super::test_support::{
+        self, build_chunk, build_support, fixture_supports, fixture_world, with_threads,
+        FIXTURE_CHUNKS,
+    };
+    use super::*;
+
+    fn default_options() -> PolygonizationOptions {
+        SmoothMeshOptions::default().into()
+    }
+
+    // The fixture density polygonized in a support, or a chunk of the fixture world
+    // built with all of its passes
+    #[derive(Clone, Copy)]
+    enum FixtureBuild {
+        Support(Rectangle3D),
+        Chunk((usize, usize)),
+    }
+
+    impl FixtureBuild {
+        fn all() -> Vec<FixtureBuild> {
+            let supports = fixture_supports().into_iter().map(FixtureBuild::Support);
+            let chunks = FIXTURE_CHUNKS.into_iter().map(FixtureBuild::Chunk);
+
+            supports.chain(chunks).collect()
+        }
+
+        fn build(self, chunks: &WorldChunks, options: PolygonizationOptions) -> Mesh {
+            match self {
+                FixtureBuild::Support(support) => build_support(support, options),
+                FixtureBuild::Chunk(chunk) => build_chunk(chunks, chunk, options),
+            }
+        }
+    }
+
+    // Every polygonization method, with and without the density cache
+    fn fixture_option_variants() -> Vec<PolygonizationOptions> {
+        ALL_POLYGONIZATION_METHODS
+            .into_iter()
+            .cartesian_product([false, true])
+            .map(|(method, density_cache)| PolygonizationOptions {
+                method,
+                density_cache,
+                ..test_support::fixture_options()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parallel_builds_match_serial() {
+        let chunks = fixture_world();
+
+        for options in fixture_option_variants() {
+            for fixture in FixtureBuild::all() {
+                let serial = with_threads(1, || fixture.build(&chunks, options));
+                let parallel = with_threads(8, || fixture.build(&chunks, options));
+
+                parallel.assert_identical(&serial);
+            }
+        }
+    }
+
+    #[test]
+    fn batched_builds_match_builds_alone() {
+        let chunks = fixture_world();
+
+        for options in fixture_option_variants() {
+            let alone: Vec<Mesh> = FixtureBuild::all()
+                .into_iter()
+                .map(|fixture| with_threads(1, || fixture.build(&chunks, options)))
+                .collect();
+            let batched: Vec<Mesh> = with_threads(8, || {
+                FixtureBuild::all()
+                    .into_par_iter()
+                    .map(|fixture| fixture.build(&chunks, options))
+                    .collect()
+            });
+
+            for (mesh, expected) in batched.iter().zip(&alone) {
+                mesh.assert_identical(expected);
+            }
+        }
+    }
+
+    #[test]
+    fn reused_grid_buffers_do_not_change_the_mesh() {
+        let options = PolygonizationOptions {
+            method: PolygonizationMethod::MarchingCubes,
+            ..test_support::fixture_options()
+        };
+        let support = fixture_supports()[0];
+
+        with_threads(1, || {
+            let fresh = build_support(support, options);
+
+            // The pooled buffer is left with the points of a bigger grid
+            let bigger = Rectangle3D {
+                height: 32.0,
+                ..fixture_supports()[3]
+            };
+            build_support(bigger, options);
+            let reused = build_support(support, options);
+
+            reused.assert_identical(&fresh);
+        });
+    }
+
+    #[test]
+    fn distant_rings_coarsen_up_to_four_blocks() {
+        let options = PolygonizationOptions {
+            distance_lod: true,
+            marching_cubes_cell_size: 0.5,
+            ..default_options()
+        };
+
+        let cell_sizes = (0..=7).map(|ring| options.cell_size_for_ring(ring));
+        assert_eq!(
+            cell_sizes.collect::<Vec<_>>(),
+            [0.5, 0.5, 0.5, 1.0, 2.0, 4.0, 4.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn default_options_are_valid() {
+        let mut options = default_options();
+        let corrections = options.validate();
+
+        assert!(corrections.is_empty(), "{corrections:?}");
+        assert_eq!(options, default_options());
+    }
+
+    #[test]
+    fn y_range_is_kept_within_the_world() {
+        let mut options = PolygonizationOptions {
+            y_low_limit: 383.0,
+            y_size: 200.0,
+            ..default_options()
+        };
+        let corrections = options.validate();
+
+        assert_eq!(
+            options.y_low_limit,
+            WORLD_TOP - options.marching_cubes_cell_size
+        );
+        assert_eq!(options.y_low_limit + options.y_size, WORLD_TOP);
+        assert_eq!(corrections.len(), 2);
+    }
+
+    #[test]
+    fn y_range_is_at_least_one_cell() {
+        let mut options = PolygonizationOptions {
+            y_low_limit: -100.0,
+            y_size: 0.0,
+            ..default_options()
+        };
+        options.validate();
+
+        assert_eq!(options.y_low_limit, WORLD_BOTTOM);
+        assert_eq!(options.y_size, options.marching_cubes_cell_size);
+    }
+
+    #[test]
+    fn invalid_values_are_repaired() {
+        let mut options = default_options();
+        options.marching_cubes_cell_size = Real::NAN;
+        options.surface_level = 1.0;
+        options.density_remap.bias = 0.0;
+        let corrections = options.validate();
+
+        assert_eq!(options.marching_cubes_cell_size, MIN_CELL_SIZE);
+        assert_eq!(options.surface_level, MAX_SURFACE_LEVEL);
+        assert_eq!(options.density_remap.bias, DENSITY_REMAP_LIMITS.0);
+
+        let repaired = corrections.iter().map(|correction| correction.option);
+        assert_eq!(
+            repaired.collect::<Vec<_>>(),
+            ["cell size", "surface level", "density bias"]
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::cmp::Ordering;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::model::{Position, Real};
+
+// Directions sampled in the hemisphere above a vertex, spread along a golden angle spiral.
+// Directions closer to the surface than the minimal elevation are left out,
+// on flat ground they would end up right at the surface and occlude it at random.
+const DIRECTIONS: usize = 12;
+const MIN_ELEVATION: Real = 0.25;
+const GOLDEN_ANGLE: Real = 2.399_963_229_728_653;
+
+// Distances in blocks along every direction, the near samples find creases and the far ones cavities
+const DISTANCES: [Real; 2] = [1.0, 2.5];
+
+// The samples start a bit above the vertex so they don't land on the surface it lies on
+const NORMAL_OFFSET: Real = 0.25;
+
+// Share of the hemisphere around the *normal* not blocked by the terrain, 1 in the open.
+// Nearer samples weigh more, a wall right next to the vertex blocks more light than a distant one.
+pub fn vertex_occlusion(
+    position: Position,
+    normal: Vector3<Real>,
+    density_func: &impl Fn(Position) -> Real,
+    surface_level: Real,
+) -> Real {
+    // The fallback triangle normal of a degenerate triangle has no direction, NaN included
+    if normal.magnitude2().partial_cmp(&0.0) != Some(Ordering::Greater) {
+        return 1.0;
+    }
+
+    let helper = match normal.y.abs() < 0.9 {
+        true => Vector3::unit_y(),
+        false => Vector3::unit_x(),
+    };
+    let tangent = normal.cross(helper).normalize();
+    let bitangent = normal.cross(tangent);
+    let origin = position + normal * NORMAL_OFFSET;
+
+    let mut open = 0.0;
+    let mut total = 0.0;
+    for i in 0..DIRECTIONS {
+        let elevation = 1.0 - (1.0 - MIN_ELEVATION) * (i as Real + 0.5) / DIRECTIONS as Real;
+        let radius = (1.0 - elevation * elevation).sqrt();
+        let angle = i as Real * GOLDEN_ANGLE;
+        let direction = normal * elevation
+            + tangent * (radius * angle.cos())
+            + bitangent * (radius * angle.sin());
+
+        for distance in DISTANCES {
+            let weight = 1.0 / distance;
+            total += weight;
+
+            let inside = density_func(origin + direction * distance) < surface_level;
+            if !inside {
+                open += weight;
+            }
+        }
+    }
+
+    open / total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Solid below y 0
+    fn ground(position: Position) -> Real {
+        position.y
+    }
+
+    // Ground with a trench 2 blocks wide and 4 deep along z
+    fn trench(position: Position) -> Real {
+        match position.x.abs() < 1.0 {
+            true => position.y + 4.0,
+            false => position.y,
+        }
+    }
+
+    #[test]
+    fn open_ground_is_not_occluded() {
+        let occlusion = vertex_occlusion(
+            Position::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+            &ground,
+            0.0,
+        );
+
+        assert_eq!(occlusion, 1.0);
+    }
+
+    #[test]
+    fn bottom_of_a_trench_is_occluded() {
+        let bottom = Position::new(0.0, -4.0, 0.0);
+        let occlusion = vertex_occlusion(bottom, Vector3::unit_y(), &trench, 0.0);
+
+        assert!(occlusion < 0.9, "occlusion {occlusion}");
+        assert!(occlusion > 0.0, "occlusion {occlusion}");
+    }
+
+    #[test]
+    fn missing_normal_is_not_occluded() {
+        let occlusion = vertex_occlusion(
+            Position::new(0.0, -4.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            &trench,
+            0.0,
+        );
+
+        assert_eq!(occlusion, 1.0);
+    }
+
+    #[test]
+    fn nan_normal_is_not_occluded() {
+        let occlusion = vertex_occlusion(
+            Position::new(0.0, -4.0, 0.0),
+            Vector3::new(Real::NAN, 0.0, 0.0),
+            &trench,
+            0.0,
+        );
+
+        assert_eq!(occlusion, 1.0);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::model::Real;
+
+use super::{Mesh, MeshVertex, Rectangle3D, VertexIndex};
+
+// Vertices closer than this to a chunk border are considered to lie on it
+const BORDER_EPSILON: f32 = 0.001;
+
+// How far below the boundary the skirt reaches, in marching cubes cells
+const SKIRT_DEPTH_CELLS: Real = 2.0;
+
+// Extrudes every open edge lying on a side of the chunk downwards.
+//
+// Neighboring chunks are polygonized independently, so their border vertices don't always
+// match exactly and pinhole gaps appear. The skirt is hidden behind the neighboring mesh
+// whenever there is no gap and covers the gap otherwise.
+pub fn add_skirts(mesh: &mut Mesh, support: Rectangle3D, cell_size: Real) {
+    let depth = (SKIRT_DEPTH_CELLS * cell_size) as f32;
+
+    let skirt_edges = mesh
+        .boundary_edges()
+        .iter()
+        .flat_map(|boundary| boundary.edges().collect::<Vec<_>>())
+        .filter(|(start, end)| {
+            let start = mesh.vertices[*start as usize].position;
+            let end = mesh.vertices[*end as usize].position;
+
+            on_same_side(support, start, end)
+        })
+        .collect::<Vec<_>>();
+
+    for (start, end) in skirt_edges {
+        let start_vertex = mesh.vertices[start as usize];
+        let end_vertex = mesh.vertices[end as usize];
+
+        let lowered = |vertex: MeshVertex| {
+            let [x, y, z] = vertex.position;
+            MeshVertex {
+                position: [x, y - depth, z],
+                ..vertex
+            }
+        };
+
+        let low_start = mesh.vertices.len() as VertexIndex;
+        mesh.vertices.push(lowered(start_vertex));
+        let low_end = low_start + 1;
+        mesh.vertices.push(lowered(end_vertex));
+
+        // The owning triangle goes start -> end, the skirt must traverse the edge in
+        // the opposite direction to keep the winding consistent with the surface
+        mesh.indices.extend_from_slice(&[end, start, low_start]);
+        mesh.indices.extend_from_slice(&[end, low_start, low_end]);
+    }
+}
+
+// Only the vertical sides of the support are shared with neighboring chunks,
+// the top and bottom of the support are left open.
+fn on_same_side(support: Rectangle3D, start: [f32; 3], end: [f32; 3]) -> bool {
+    let min_x = support.position.x as f32;
+    let max_x = (support.position.x + support.width) as f32;
+    let min_z = support.position.z as f32;
+    let max_z = (support.position.z + support.depth) as f32;
+
+    let both_at = |axis: usize, value: f32| {
+        (start[axis] - value).abs() < BORDER_EPSILON && (end[axis] - value).abs() < BORDER_EPSILON
+    };
+
+    both_at(0, min_x) || both_at(0, max_x) || both_at(2, min_z) || both_at(2, max_z)
+}
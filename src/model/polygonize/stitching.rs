@@ -0,0 +1,280 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::model::{Position, Real};
+
+use super::{Mesh, Rectangle3D};
+
+// Vertices closer than this to a chunk border are considered to lie on it
+const BORDER_EPSILON: Real = 0.001;
+
+// Sides of a chunk that are shared with its neighbors
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChunkSide {
+    NegativeX = 0,
+    PositiveX = 1,
+    NegativeZ = 2,
+    PositiveZ = 3,
+}
+
+pub const CHUNK_SIDES: [ChunkSide; 4] = [
+    ChunkSide::NegativeX,
+    ChunkSide::PositiveX,
+    ChunkSide::NegativeZ,
+    ChunkSide::PositiveZ,
+];
+
+// Resolution a chunk mesh was built with, along with the resolution of its neighbors.
+// Neighbors that are not polygonized are expected to have the same cell size as the chunk.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ChunkLod {
+    pub cell_size: Real,
+    pub neighbor_cell_sizes: [Real; 4],
+    // Sides facing a chunk that is not polygonized, at the edge of the loaded window
+    pub window_edges: [bool; 4],
+}
+
+impl ChunkLod {
+    pub fn neighbor_cell_size(&self, side: ChunkSide) -> Real {
+        self.neighbor_cell_sizes[side as usize]
+    }
+
+    pub fn is_window_edge(&self, side: ChunkSide) -> bool {
+        self.window_edges[side as usize]
+    }
+}
+
+// Makes the mesh watertight against coarser neighbors.
+//
+// Both meshes sample the same density function on the shared border,
+// but the coarser mesh only sees every n-th sample, so its border contour is made of
+// longer straight segments. Every vertex of this mesh lying on such border is snapped onto
+// the coarse contour. The fine border then subdivides the coarse one and no cracks appear.
+pub fn stitch_chunk_borders(
+    mesh: &mut Mesh,
+    support: Rectangle3D,
+    lod: ChunkLod,
+    density_func: &impl Fn(Position) -> Real,
+    surface_level: Real,
+) {
+    for side in CHUNK_SIDES {
+        let neighbor_cell_size = lod.neighbor_cell_size(side);
+        let neighbor_is_coarser = neighbor_cell_size > lod.cell_size + BORDER_EPSILON;
+        if !neighbor_is_coarser {
+            continue;
+        }
+
+        let border = BorderFace::new(support, side);
+        let contour =
+            BorderContour::sample(&border, neighbor_cell_size, density_func, surface_level);
+
+        for vertex in mesh.vertices.iter_mut() {
+            let position = Position::new(
+                vertex.position[0] as Real,
+                vertex.position[1] as Real,
+                vertex.position[2] as Real,
+            );
+
+            if !border.contains(position) {
+                continue;
+            }
+
+            let Some(snapped) = contour.snap(border.to_planar(position)) else {
+                continue;
+            };
+
+            let snapped_position = border.to_world(snapped);
+            vertex.position = [
+                snapped_position.x as f32,
+                snapped_position.y as f32,
+                snapped_position.z as f32,
+            ];
+        }
+    }
+}
+
+// A vertical face of the chunk support, parametrized by (u, v)
+// where u runs along the horizontal axis of the face and v along the y axis.
+struct BorderFace {
+    side: ChunkSide,
+    // Value of the constant coordinate of the face
+    offset: Real,
+    origin_u: Real,
+    origin_v: Real,
+    size_u: Real,
+    size_v: Real,
+}
+
+impl BorderFace {
+    fn new(support: Rectangle3D, side: ChunkSide) -> Self {
+        let (offset, origin_u, size_u) = match side {
+            ChunkSide::NegativeX => (support.position.x, support.position.z, support.depth),
+            ChunkSide::PositiveX => (
+                support.position.x + support.width,
+                support.position.z,
+                support.depth,
+            ),
+            ChunkSide::NegativeZ => (support.position.z, support.position.x, support.width),
+            ChunkSide::PositiveZ => (
+                support.position.z + support.depth,
+                support.position.x,
+                support.width,
+            ),
+        };
+
+        BorderFace {
+            side,
+            offset,
+            origin_u,
+            origin_v: support.position.y,
+            size_u,
+            size_v: support.height,
+        }
+    }
+
+    fn contains(&self, position: Position) -> bool {
+        let coord = match self.side {
+            ChunkSide::NegativeX | ChunkSide::PositiveX => position.x,
+            ChunkSide::NegativeZ | ChunkSide::PositiveZ => position.z,
+        };
+
+        (coord - self.offset).abs() < BORDER_EPSILON
+    }
+
+    fn to_planar(&self, position: Position) -> Vector2<Real> {
+        match self.side {
+            ChunkSide::NegativeX | ChunkSide::PositiveX => Vector2::new(position.z, position.y),
+            ChunkSide::NegativeZ | ChunkSide::PositiveZ => Vector2::new(position.x, position.y),
+        }
+    }
+
+    fn to_world(&self, planar: Vector2<Real>) -> Position {
+        match self.side {
+            ChunkSide::NegativeX | ChunkSide::PositiveX => {
+                Position::new(self.offset, planar.y, planar.x)
+            }
+            ChunkSide::NegativeZ | ChunkSide::PositiveZ => {
+                Position::new(planar.x, planar.y, self.offset)
+            }
+        }
+    }
+}
+
+// Marching squares contour of the density function on a border face
+// sampled with the cell size of the coarse neighbor.
+struct BorderContour {
+    origin: Vector2<Real>,
+    cell_size: Real,
+    cells_u: usize,
+    cells_v: usize,
+
+    // Contour segments of every coarse cell, indexed by [v * cells_u + u]
+    segments: Vec<Vec<(Vector2<Real>, Vector2<Real>)>>,
+}
+
+impl BorderContour {
+    fn sample(
+        border: &BorderFace,
+        cell_size: Real,
+        density_func: &impl Fn(Position) -> Real,
+        surface_level: Real,
+    ) -> Self {
+        // Same sample counts as the marching cubes grid uses
+        let points_u = (border.size_u / cell_size) as usize + 1;
+        let points_v = (border.size_v / cell_size) as usize + 1;
+        let origin = Vector2::new(border.origin_u, border.origin_v);
+
+        let point_at = |u: usize, v: usize| {
+            origin + Vector2::new(u as Real * cell_size, v as Real * cell_size)
+        };
+
+        let densities: Vec<Real> = (0..points_v)
+            .flat_map(|v| (0..points_u).map(move |u| (u, v)))
+            .map(|(u, v)| density_func(border.to_world(point_at(u, v))))
+            .collect();
+        let density_at = |u: usize, v: usize| densities[v * points_u + u];
+
+        let cells_u = points_u.saturating_sub(1);
+        let cells_v = points_v.saturating_sub(1);
+
+        let mut segments = Vec::with_capacity(cells_u * cells_v);
+        for v in 0..cells_v {
+            for u in 0..cells_u {
+                // Corners in counter clockwise order
+                let corners = [(u, v), (u + 1, v), (u + 1, v + 1), (u, v + 1)];
+
+                let intersections: Vec<Vector2<Real>> = (0..corners.len())
+                    .filter_map(|i| {
+                        let (start_u, start_v) = corners[i];
+                        let (end_u, end_v) = corners[(i + 1) % corners.len()];
+
+                        let start_density = density_at(start_u, start_v);
+                        let end_density = density_at(end_u, end_v);
+
+                        let start_inside = start_density < surface_level;
+                        let end_inside = end_density < surface_level;
+                        if start_inside == end_inside {
+                            return None;
+                        }
+
+                        let t = (surface_level - start_density) / (end_density - start_density);
+                        let start = point_at(start_u, start_v);
+                        let end = point_at(end_u, end_v);
+
+                        Some(start + (end - start) * t)
+                    })
+                    .collect();
+
+                // Ambiguous cells have 4 intersections, pair them up in order
+                let cell_segments = intersections
+                    .chunks_exact(2)
+                    .map(|pair| (pair[0], pair[1]))
+                    .collect();
+
+                segments.push(cell_segments);
+            }
+        }
+
+        BorderContour {
+            origin,
+            cell_size,
+            cells_u,
+            cells_v,
+            segments,
+        }
+    }
+
+    // Returns the closest point on the contour within the coarse cell containing the point
+    fn snap(&self, point: Vector2<Real>) -> Option<Vector2<Real>> {
+        if self.cells_u == 0 || self.cells_v == 0 {
+            return None;
+        }
+
+        let local = (point - self.origin) / self.cell_size;
+        let cell_u = (local.x.max(0.0) as usize).min(self.cells_u - 1);
+        let cell_v = (local.y.max(0.0) as usize).min(self.cells_v - 1);
+
+        self.segments[cell_v * self.cells_u + cell_u]
+            .iter()
+            .map(|(start, end)| closest_point_on_segment(point, *start, *end))
+            .min_by(|a, b| {
+                let distance_a = (*a - point).magnitude2();
+                let distance_b = (*b - point).magnitude2();
+                distance_a.total_cmp(&distance_b)
+            })
+    }
+}
+
+fn closest_point_on_segment(
+    point: Vector2<Real>,
+    start: Vector2<Real>,
+    end: Vector2<Real>,
+) -> Vector2<Real> {
+    let segment = end - start;
+    let length2 = segment.magnitude2();
+    if length2 < BORDER_EPSILON * BORDER_EPSILON {
+        return start;
+    }
+
+    let t = ((point - start).dot(segment) / length2).clamp(0.0, 1.0);
+    start + segment * t
+}
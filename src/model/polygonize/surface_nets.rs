@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3, Zero};
+use itertools::iproduct;
+
+use crate::{
+    infrastructure::texture::MaterialBlend,
+    model::{Position, Real},
+};
+
+use super::marching_cubes::{build_mesh_vertices, get_intersection, Grid, GridPoint};
+use super::mc_tables::{self, CORNER_OFFSETS, EDGE_CORNERS};
+use super::{Mesh, PolygonizationOptions, Rectangle3D, VertexIndex};
+
+// Grid points are addressed by [x, y, z], cells by the grid point of their corner 0
+type GridCoords = [usize; 3];
+
+// Cells sharing a grid edge in order around it, as offsets along the two other axes.
+// An offset of 0 is the cell before the edge, 1 the cell after it
+const CELLS_AROUND_EDGE: [(usize, usize); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+
+// Naive surface nets.
+//
+// Every cell the surface passes through gets a single vertex at the mean of the intersections
+// on its edges and every intersected edge of the grid becomes the quad connecting the vertices
+// of the four cells around it.
+//
+// Quads of the edges on the faces of the support would need cells outside of it, there the mesh
+// is closed against the face with triangles fanning from the cell vertices to the intersections
+// on the face. The mesh then ends on the marching squares contour of each face, which is what
+// the neighboring chunk ends on and what stitch_chunk_borders snaps onto for coarser neighbors.
+pub fn polygonize(
+    support: Rectangle3D,
+    sdf: impl Fn(Position) -> Real + Sync,
+    material_func: impl Fn(Position) -> MaterialBlend,
+    options: PolygonizationOptions,
+) -> Mesh {
+    let grid = Grid::new(
+        support,
+        &sdf,
+        options.marching_cubes_cell_size,
+        options.surface_level,
+    );
+
+    let mut net = SurfaceNet::new(&grid);
+    net.connect_edges();
+    net.close_faces();
+
+    let SurfaceNet {
+        positions, indices, ..
+    } = net;
+    let vertices = build_mesh_vertices(&positions, &indices, &sdf, &material_func, options);
+
+    Mesh { vertices, indices }
+}
+
+struct SurfaceNet<'a> {
+    grid: &'a Grid,
+    points: GridCoords,
+    cells: GridCoords,
+
+    // Vertex of every cell the surface passes through, indexed like the grid points
+    cell_vertices: Vec<Option<VertexIndex>>,
+    // Vertices on the intersected edges lying on the faces of the support,
+    // keyed by the start of the edge and its axis
+    edge_vertices: HashMap<(GridCoords, usize), VertexIndex>,
+
+    positions: Vec<Position>,
+    indices: Vec<VertexIndex>,
+}
+
+impl<'a> SurfaceNet<'a> {
+    fn new(grid: &'a Grid) -> Self {
+        let points = [grid.width, grid.height, grid.depth];
+        let cells = points.map(|count| count.saturating_sub(1));
+
+        let mut net = SurfaceNet {
+            grid,
+            points,
+            cells,
+            cell_vertices: vec![None; grid.width * grid.height * grid.depth],
+            edge_vertices: HashMap::new(),
+            positions: Vec::new(),
+            indices: Vec::new(),
+        };
+
+        for cell in grid_coords(cells) {
+            if let Some(position) = net.cell_vertex_position(cell) {
+                let vertex = net.add_vertex(position);
+                net.cell_vertices[net.grid_index(cell)] = Some(vertex);
+            }
+        }
+
+        net
+    }
+
+    // Connects the vertices of the cells around every intersected edge of the grid
+    fn connect_edges(&mut self) {
+        for axis in 0..3 {
+            let [u, v] = face_axes(axis);
+            for start in grid_coords(self.points) {
+                if start[axis] + 1 >= self.points[axis] {
+                    continue;
+                }
+
+                let end = step(start, axis);
+                let Some(outward) = self.outward(start, end) else {
+                    continue;
+                };
+
+                // Cells outside the grid are left out
+                let cells: Vec<VertexIndex> = CELLS_AROUND_EDGE
+                    .iter()
+                    .filter_map(|&(offset_u, offset_v)| {
+                        let mut cell = start;
+                        cell[u] = (start[u] + offset_u).checked_sub(1)?;
+                        cell[v] = (start[v] + offset_v).checked_sub(1)?;
+                        if cell[u] >= self.cells[u] || cell[v] >= self.cells[v] {
+                            return None;
+                        }
+
+                        self.cell_vertices[self.grid_index(cell)]
+                    })
+                    .collect();
+
+                match cells.as_slice() {
+                    &[a, b, c, d] => {
+                        self.add_triangle([a, b, c], outward);
+                        self.add_triangle([a, c, d], outward);
+                    }
+                    // The edge lies on a face of the support, the half of the quad inside it
+                    // ends on the edge
+                    &[a, b] => {
+                        let edge_vertex = self.edge_vertex(start, axis);
+                        self.add_triangle([a, b, edge_vertex], outward);
+                    }
+                    // A single cell on an edge of the support is closed by the faces alone
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // Closes the mesh against the six faces of the grid.
+    // Each face is cut into squares by the grid, the marching squares segments of every square
+    // are connected with the vertex of the cell behind the square.
+    fn close_faces(&mut self) {
+        if self.cells.contains(&0) {
+            return;
+        }
+
+        for axis in 0..3 {
+            let [u, v] = face_axes(axis);
+            for layer in [0, self.points[axis] - 1] {
+                let cell_layer = layer.min(self.cells[axis] - 1);
+
+                for (square_v, square_u) in iproduct!(0..self.cells[v], 0..self.cells[u]) {
+                    let mut origin = [0; 3];
+                    origin[axis] = layer;
+                    origin[u] = square_u;
+                    origin[v] = square_v;
+
+                    let mut cell = origin;
+                    cell[axis] = cell_layer;
+                    if let Some(center) = self.cell_vertices[self.grid_index(cell)] {
+                        self.close_square(origin, u, v, center);
+                    }
+                }
+            }
+        }
+    }
+
+    fn close_square(&mut self, origin: GridCoords, u: usize, v: usize, center: VertexIndex) {
+        // Corners counter clockwise in (u, v), with the edges starting at them,
+        // as the start of the edge in the grid and its axis
+        let corners = [
+            origin,
+            step(origin, u),
+            step(step(origin, u), v),
+            step(origin, v),
+        ];
+        let edges = [
+            (corners[0], u),
+            (corners[1], v),
+            (corners[3], u),
+            (corners[0], v),
+        ];
+
+        let intersected: Vec<usize> = (0..corners.len())
+            .filter(|&i| self.is_inside(corners[i]) != self.is_inside(corners[(i + 1) % 4]))
+            .collect();
+
+        // Ambiguous squares have 4 intersections, pair them up in order like BorderContour
+        for pair in intersected.chunks_exact(2) {
+            let [first, second] = [pair[0], pair[1]].map(|i| {
+                let (start, axis) = edges[i];
+                self.edge_vertex(start, axis)
+            });
+
+            // The corners between the two edges are on the same side of the segment
+            let cut_corner = corners[(pair[0] + 1) % 4];
+            let segment_middle = self.position(first).midpoint(self.position(second));
+            let towards_corner = self.point(cut_corner).position - segment_middle;
+            let outward = match self.is_inside(cut_corner) {
+                true => -towards_corner,
+                false => towards_corner,
+            };
+
+            self.add_triangle([center, first, second], outward);
+        }
+    }
+
+    // Mean of the intersections on the edges of the cell, None when the surface misses it
+    fn cell_vertex_position(&self, cell: GridCoords) -> Option<Position> {
+        let corners = CORNER_OFFSETS.map(|offset| add(cell, offset));
+        let case = mc_tables::cell_case(|corner| self.is_inside(corners[corner]));
+        let intersected_edges = mc_tables::intersected_edges(case);
+        if intersected_edges == 0 {
+            return None;
+        }
+
+        let sum = EDGE_CORNERS
+            .iter()
+            .enumerate()
+            .filter(|(edge, _)| intersected_edges & (1 << *edge) != 0)
+            .map(|(_, [start, end])| self.intersection(corners[*start], corners[*end]))
+            .fold(Vector3::zero(), |sum, intersection| {
+                sum + intersection.to_vec()
+            });
+
+        Some(Position::from_vec(
+            sum / intersected_edges.count_ones() as Real,
+        ))
+    }
+
+    fn edge_vertex(&mut self, start: GridCoords, axis: usize) -> VertexIndex {
+        if let Some(vertex) = self.edge_vertices.get(&(start, axis)) {
+            return *vertex;
+        }
+
+        let position = self.intersection(start, step(start, axis));
+        let vertex = self.add_vertex(position);
+        self.edge_vertices.insert((start, axis), vertex);
+
+        vertex
+    }
+
+    // Direction out of the surface along an intersected edge, None when it isn't intersected
+    fn outward(&self, start: GridCoords, end: GridCoords) -> Option<Vector3<Real>> {
+        let along = self.point(end).position - self.point(start).position;
+        match (self.is_inside(start), self.is_inside(end)) {
+            (true, false) => Some(along),
+            (false, true) => Some(-along),
+            _ => None,
+        }
+    }
+
+    // Adds the triangle facing along *outward*, meshes are wound counter clockwise
+    fn add_triangle(&mut self, [a, b, c]: [VertexIndex; 3], outward: Vector3<Real>) {
+        let u = self.position(b) - self.position(a);
+        let v = self.position(c) - self.position(a);
+
+        let triangle = match u.cross(v).dot(outward) < 0.0 {
+            true => [a, c, b],
+            false => [a, b, c],
+        };
+        self.indices.extend(triangle);
+    }
+
+    fn add_vertex(&mut self, position: Position) -> VertexIndex {
+        self.positions.push(position);
+
+        (self.positions.len() - 1) as VertexIndex
+    }
+
+    fn position(&self, vertex: VertexIndex) -> Position {
+        self.positions[vertex as usize]
+    }
+
+    fn point(&self, [x, y, z]: GridCoords) -> GridPoint {
+        self.grid.get_cell(Point3::new(x, y, z)).unwrap()
+    }
+
+    fn grid_index(&self, [x, y, z]: GridCoords) -> usize {
+        self.grid.get_index_for(Point3::new(x, y, z))
+    }
+
+    fn is_inside(&self, point: GridCoords) -> bool {
+        self.point(point).density < self.grid.surface_level
+    }
+
+    fn intersection(&self, start: GridCoords, end: GridCoords) -> Position {
+        get_intersection(self.point(start), self.point(end), self.grid.surface_level).unwrap()
+    }
+}
+
+// The two axes spanning the faces perpendicular to *axis*.
+// The vertical faces match the (u, v) parametrization of the border faces in stitching
+fn face_axes(axis: usize) -> [usize; 2] {
+    match axis {
+        0 => [2, 1],
+        1 => [0, 2],
+        _ => [0, 1],
+    }
+}
+
+fn grid_coords([width, height, depth]: GridCoords) -> impl Iterator<Item = GridCoords> {
+    iproduct!(0..depth, 0..height, 0..width).map(|(z, y, x)| [x, y, z])
+}
+
+fn step(mut coords: GridCoords, axis: usize) -> GridCoords {
+    coords[axis] += 1;
+    coords
+}
+
+fn add(coords: GridCoords, offset: GridCoords) -> GridCoords {
+    [0, 1, 2].map(|axis| coords[axis] + offset[axis])
+}
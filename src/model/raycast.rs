@@ -0,0 +1,316 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::get_minecraft_chunk_position;
+
+use super::chunk::ChunkPosition;
+use super::common::{is_visible_block, BlockType};
+use super::discrete::{get_block_in, World, WorldChunks};
+use super::implicit::normal;
+use super::implicit::smooth::terrain_density;
+use super::polygonize::PolygonizationOptions;
+use super::{Position, Real};
+
+// Distance the smooth raycast advances between density samples,
+// thinner features than this may be skipped
+const SMOOTH_MARCH_STEP: Real = 0.5;
+const SMOOTH_REFINE_STEPS: usize = 8;
+
+// How far below the smooth surface the block under the hit is looked up
+const SURFACE_PROBE_DEPTH: Real = 0.1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub position: Position,
+    pub normal: Vector3<Real>,
+    pub block: BlockType,
+    // Minimum corner of the block, the one to change when editing what was hit
+    pub block_position: Point3<i32>,
+    pub chunk: ChunkPosition,
+}
+
+// What a ray hits in both representations of the world. The block and the smooth surface
+// differ where the smoothing rounded off edges or filled gaps between blocks.
+#[derive(Clone, Copy, Debug)]
+pub struct Pick {
+    pub block: Option<Hit>,
+    pub surface: Option<Hit>,
+}
+
+impl World {
+    pub fn raycast(
+        &self,
+        origin: Position,
+        direction: Vector3<Real>,
+        max_distance: Real,
+    ) -> Option<Hit> {
+        raycast_blocks(&self.get_chunks(), origin, direction, max_distance)
+    }
+
+    pub fn raycast_smooth(
+        &self,
+        origin: Position,
+        direction: Vector3<Real>,
+        max_distance: Real,
+        options: PolygonizationOptions,
+    ) -> Option<Hit> {
+        raycast_surface(&self.get_chunks(), origin, direction, max_distance, options)
+    }
+
+    pub fn pick(
+        &self,
+        origin: Position,
+        direction: Vector3<Real>,
+        max_distance: Real,
+        options: PolygonizationOptions,
+    ) -> Pick {
+        let chunks = self.get_chunks();
+        Pick {
+            block: raycast_blocks(&chunks, origin, direction, max_distance),
+            surface: raycast_surface(&chunks, origin, direction, max_distance, options),
+        }
+    }
+}
+
+// Walks the block grid cell by cell along the ray (Amanatides & Woo)
+// and returns the first visible block hit within `max_distance`.
+pub fn raycast_blocks(
+    chunks: &WorldChunks,
+    origin: Position,
+    direction: Vector3<Real>,
+    max_distance: Real,
+) -> Option<Hit> {
+    if direction.magnitude2() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+
+    let mut cell = origin.map(Real::floor);
+    let step = direction.map(Real::signum);
+
+    // Distance along the ray to the next cell boundary on each axis
+    // and the distance between two boundaries on the same axis
+    let boundary_distance = |coord: Real, cell: Real, direction: Real| {
+        if direction > 0.0 {
+            (cell + 1.0 - coord) / direction
+        } else if direction < 0.0 {
+            (cell - coord) / direction
+        } else {
+            Real::INFINITY
+        }
+    };
+    let mut t_max = Vector3::new(
+        boundary_distance(origin.x, cell.x, direction.x),
+        boundary_distance(origin.y, cell.y, direction.y),
+        boundary_distance(origin.z, cell.z, direction.z),
+    );
+    let t_delta = direction.map(|d| match d {
+        d if d == 0.0 => Real::INFINITY,
+        d => 1.0 / d.abs(),
+    });
+
+    // A ray starting inside a block hits it immediately
+    let mut distance = 0.0;
+    let mut normal = -direction;
+
+    while distance <= max_distance {
+        let center = cell + Vector3::new(0.5, 0.5, 0.5);
+        let block = get_block_in(chunks, center);
+        if is_visible_block(block) {
+            return Some(Hit {
+                position: origin + direction * distance,
+                normal,
+                block,
+                block_position: cell.map(|coordinate| coordinate as i32),
+                chunk: get_minecraft_chunk_position(center),
+            });
+        }
+
+        if t_max.x < t_max.y && t_max.x < t_max.z {
+            cell.x += step.x;
+            distance = t_max.x;
+            t_max.x += t_delta.x;
+            normal = Vector3::new(-step.x, 0.0, 0.0);
+        } else if t_max.y < t_max.z {
+            cell.y += step.y;
+            distance = t_max.y;
+            t_max.y += t_delta.y;
+            normal = Vector3::new(0.0, -step.y, 0.0);
+        } else {
+            cell.z += step.z;
+            distance = t_max.z;
+            t_max.z += t_delta.z;
+            normal = Vector3::new(0.0, 0.0, -step.z);
+        }
+    }
+
+    None
+}
+
+// Finds where the ray crosses the smooth surface by marching the density field
+// and refining the crossing by bisection.
+// Much slower than `raycast_blocks`, every sample evaluates the density kernel.
+pub fn raycast_surface(
+    chunks: &WorldChunks,
+    origin: Position,
+    direction: Vector3<Real>,
+    max_distance: Real,
+    options: PolygonizationOptions,
+) -> Option<Hit> {
+    if direction.magnitude2() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+    let density = terrain_density(chunks, options);
+    let is_inside = |distance: Real| density(origin + direction * distance) < options.surface_level;
+
+    let mut outside = 0.0;
+    let mut inside = None;
+    let mut distance = 0.0;
+    while distance <= max_distance {
+        if is_inside(distance) {
+            inside = Some(distance);
+            break;
+        }
+
+        outside = distance;
+        distance += SMOOTH_MARCH_STEP;
+    }
+
+    let mut inside = inside?;
+    for _ in 0..SMOOTH_REFINE_STEPS {
+        let middle = (outside + inside) / 2.0;
+        match is_inside(middle) {
+            true => inside = middle,
+            false => outside = middle,
+        }
+    }
+
+    let position = origin + direction * inside;
+    let normal = normal::gradient(&density, position);
+    let normal = match normal.magnitude2() {
+        length if length > 0.0 => normal.normalize(),
+        _ => -direction,
+    };
+
+    let probe = position - normal * SURFACE_PROBE_DEPTH;
+    Some(Hit {
+        position,
+        normal,
+        block: get_block_in(chunks, probe),
+        block_position: probe.map(|coordinate| coordinate.floor() as i32),
+        chunk: get_minecraft_chunk_position(probe),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::imgui_wrapper::SmoothMeshOptions;
+    use crate::minecraft::BLOCKS_IN_CHUNK;
+    use crate::model::chunk::Chunk;
+
+    const GROUND_HEIGHT: isize = 64;
+
+    // Stone up to GROUND_HEIGHT in a window of 3x3 chunks from the world origin
+    fn flat_world(mut edit: impl FnMut(&mut Chunk)) -> WorldChunks {
+        let base = ChunkPosition::from_global_position_in_chunks(0, 0);
+
+        WorldChunks::from_fn(3, |x, z| {
+            let mut chunk = Chunk::new(base.offset(x as i32, z as i32));
+            for x in 0..BLOCKS_IN_CHUNK {
+                for z in 0..BLOCKS_IN_CHUNK {
+                    for y in 48..GROUND_HEIGHT {
+                        chunk.push_block(x, z, y, BlockType::Stone);
+                    }
+                }
+            }
+            edit(&mut chunk);
+
+            Arc::new(chunk)
+        })
+    }
+
+    fn assert_close(actual: Position, expected: Position) {
+        let distance = (actual - expected).magnitude();
+        assert!(distance < 1e-9, "{actual:?} is not {expected:?}");
+    }
+
+    #[test]
+    fn axis_aligned_ray_hits_the_top_face() {
+        let chunks = flat_world(|_| ());
+        let origin = Position::new(4.5, 70.5, 4.5);
+
+        let hit = raycast_blocks(&chunks, origin, Vector3::new(0.0, -1.0, 0.0), 20.0).unwrap();
+
+        assert_eq!(hit.block, BlockType::Stone);
+        assert_eq!(hit.block_position, Point3::new(4, 63, 4));
+        assert_eq!(hit.normal, Vector3::new(0.0, 1.0, 0.0));
+        assert_close(hit.position, Position::new(4.5, 64.0, 4.5));
+    }
+
+    #[test]
+    fn diagonal_ray_hits_the_block_it_enters() {
+        let chunks = flat_world(|_| ());
+        let origin = Position::new(2.25, 66.5, 2.75);
+
+        let hit = raycast_blocks(&chunks, origin, Vector3::new(1.0, -1.0, 1.0), 20.0).unwrap();
+
+        // The ray goes down 2.5 blocks and as far along x and z
+        assert_eq!(hit.block_position, Point3::new(4, 63, 5));
+        assert_eq!(hit.normal, Vector3::new(0.0, 1.0, 0.0));
+        assert_close(hit.position, Position::new(4.75, 64.0, 5.25));
+    }
+
+    #[test]
+    fn blocks_beyond_the_max_distance_are_missed() {
+        let chunks = flat_world(|_| ());
+        let origin = Position::new(4.5, 70.5, 4.5);
+        let down = Vector3::new(0.0, -1.0, 0.0);
+
+        assert!(raycast_blocks(&chunks, origin, down, 6.0).is_none());
+        assert!(raycast_blocks(&chunks, origin, down, 7.0).is_some());
+        assert!(raycast_blocks(&chunks, origin, Vector3::new(0.0, 0.0, 0.0), 20.0).is_none());
+    }
+
+    #[test]
+    fn ray_crosses_into_the_next_chunk() {
+        let pillar_chunk = ChunkPosition::from_global_position_in_chunks(1, 0);
+        let chunks = flat_world(|chunk| {
+            if chunk.position == pillar_chunk {
+                chunk.push_block(2, 4, 70, BlockType::Stone);
+            }
+        });
+        let origin = Position::new(10.5, 70.5, 4.5);
+
+        let hit = raycast_blocks(&chunks, origin, Vector3::new(1.0, 0.0, 0.0), 20.0).unwrap();
+
+        assert_eq!(hit.chunk, pillar_chunk);
+        assert_eq!(hit.block_position, Point3::new(18, 70, 4));
+        assert_eq!(hit.normal, Vector3::new(-1.0, 0.0, 0.0));
+        assert_close(hit.position, Position::new(18.0, 70.5, 4.5));
+    }
+
+    #[test]
+    fn smooth_ray_hits_flat_ground_near_the_blocks() {
+        let chunks = flat_world(|_| ());
+        let options: PolygonizationOptions = SmoothMeshOptions::default().into();
+        let origin = Position::new(24.5, 72.0, 24.5);
+
+        let hit =
+            raycast_surface(&chunks, origin, Vector3::new(0.0, -1.0, 0.0), 20.0, options).unwrap();
+
+        assert!(
+            (hit.position.y - GROUND_HEIGHT as Real).abs() < 1.0,
+            "surface at {:?}",
+            hit.position
+        );
+        assert!(hit.normal.y > 0.99, "normal {:?}", hit.normal);
+        assert_eq!(hit.block, BlockType::Stone);
+        assert_eq!(
+            hit.chunk,
+            ChunkPosition::from_global_position_in_chunks(1, 1)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::io::{self, Write};
+
+use image::RgbaImage;
+
+use super::common::{BlockType, BLOCK_TYPES};
+use super::lightmap::Page;
+use super::polygonize::Mesh;
+
+// Materials of a chunk mesh baked into an RGBA texture, for the terrain shaders of other engines.
+//
+// The mesh is unwrapped the same way as for the lightmaps, every triangle gets its own chart.
+// The four materials with the most weight in the chunk go into the red, green, blue
+// and alpha channel, in that order. Weights of the other materials are dropped
+// and the four kept weights of every texel are scaled to sum to one.
+pub struct SplatMap {
+    pub mesh: Mesh,
+    pub materials: Vec<BlockType>,
+    pub image: RgbaImage,
+}
+
+impl SplatMap {
+    pub fn bake(mesh: &Mesh, chart_texels: usize, page_width: usize) -> Self {
+        let materials = dominant_materials(mesh);
+        let page = Page::unwrap_charts(mesh, chart_texels, page_width);
+
+        let texels = page.bake(|corners, weights| {
+            let kept = materials
+                .iter()
+                .map(|&material| {
+                    let weight = |corner: usize| {
+                        material_weight(&corners[corner].vertex_material_weights, material as usize)
+                    };
+                    weight(0) * weights.x + weight(1) * weights.y + weight(2) * weights.z
+                })
+                .collect::<Vec<_>>();
+            let total = kept.iter().sum::<f32>();
+
+            let mut channels = [0; 4];
+            if total > 0.0 {
+                for (channel, weight) in channels.iter_mut().zip(kept) {
+                    *channel = (weight / total * 255.0).round() as u8;
+                }
+            }
+            channels
+        });
+
+        let image =
+            RgbaImage::from_raw(page.width as u32, page.height as u32, texels.concat()).unwrap();
+
+        // Image rows go down, texture coordinates go up
+        let mut mesh = page.mesh;
+        for vertex in &mut mesh.vertices {
+            let [u, v] = vertex.lightmap_uv;
+            vertex.lightmap_uv = [
+                u / page.width.max(1) as f32,
+                1.0 - v / page.height.max(1) as f32,
+            ];
+        }
+
+        SplatMap {
+            mesh,
+            materials,
+            image,
+        }
+    }
+
+    // Writes the mesh with the splat map coordinates,
+    // a comment at the top lists the material of every channel
+    pub fn write_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+        let channels = self
+            .materials
+            .iter()
+            .zip(["r", "g", "b", "a"])
+            .map(|(material, channel)| format!("{channel}={material:?}"))
+            .collect::<Vec<_>>();
+        writeln!(writer, "# splat channels: {}", channels.join(" "))?;
+
+        for vertex in &self.mesh.vertices {
+            let [x, y, z] = vertex.position;
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for vertex in &self.mesh.vertices {
+            let [u, v] = vertex.lightmap_uv;
+            writeln!(writer, "vt {} {}", u, v)?;
+        }
+        for vertex in &self.mesh.vertices {
+            let [x, y, z] = vertex.normal;
+            writeln!(writer, "vn {} {} {}", x, y, z)?;
+        }
+
+        // OBJ indices start at 1
+        for triangle in self.mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] + 1);
+            writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Up to four materials with the largest total weight over the vertices of the mesh
+fn dominant_materials(mesh: &Mesh) -> Vec<BlockType> {
+    let mut totals = [0.0; BLOCK_TYPES];
+    for vertex in &mesh.vertices {
+        for (index, total) in totals.iter_mut().enumerate() {
+            *total += material_weight(&vertex.vertex_material_weights, index);
+        }
+    }
+
+    let mut materials = (0..BLOCK_TYPES)
+        .filter(|&index| totals[index] > 0.0)
+        .filter_map(|index| BlockType::try_from(index).ok())
+        .collect::<Vec<_>>();
+    materials.sort_by(|a, b| totals[*b as usize].total_cmp(&totals[*a as usize]));
+    materials.truncate(4);
+
+    materials
+}
+
+// The weights are laid out like the mat4 in implicit_fs.glsl, material = column * 4 + row
+fn material_weight(weights: &[[f32; 4]; 4], material: usize) -> f32 {
+    match material < 16 {
+        true => weights[material / 4][material % 4],
+        false => 0.0,
+    }
+}
@@ -0,0 +1,67 @@
+use crate::minecraft::BLOCKS_IN_CHUNK;
+
+use super::chunk::Chunk;
+use super::{Coord, PlanarPosition, Real};
+
+// How much the roughness of the surface counts compared to its height range
+const CURVATURE_WEIGHT: Real = 4.0;
+
+// Summary of the surface of a chunk, used to find parts of the world worth looking at
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkHeightMetrics {
+    // Center of the chunk in the xz plane
+    pub center: PlanarPosition,
+    pub min_height: isize,
+    pub max_height: isize,
+    pub mean_height: Real,
+    // Mean absolute discrete laplacian of the height map,
+    // high for cliffs, ravines and peaks, zero for flat or evenly sloped terrain
+    pub curvature: Real,
+}
+
+impl ChunkHeightMetrics {
+    pub fn measure(chunk: &Chunk) -> Self {
+        let heights = chunk.get_height_map();
+        let height_at = |x: usize, z: usize| heights[z * BLOCKS_IN_CHUNK + x] as Real;
+
+        let min_height = *heights.iter().min().unwrap();
+        let max_height = *heights.iter().max().unwrap();
+        let mean_height = heights.iter().sum::<isize>() as Real / heights.len() as Real;
+
+        // The laplacian needs all four neighbors, so the border columns are skipped
+        let inner = 1..BLOCKS_IN_CHUNK - 1;
+        let inner_count = inner.len() * inner.len();
+        let curvature = inner
+            .clone()
+            .flat_map(|z| inner.clone().map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let neighbors = height_at(x - 1, z)
+                    + height_at(x + 1, z)
+                    + height_at(x, z - 1)
+                    + height_at(x, z + 1);
+                (neighbors - 4.0 * height_at(x, z)).abs()
+            })
+            .sum::<Real>()
+            / inner_count as Real;
+
+        let half_chunk = BLOCKS_IN_CHUNK as Coord / 2.0;
+        let origin = chunk.coords().origin();
+
+        ChunkHeightMetrics {
+            center: PlanarPosition::new(origin.x + half_chunk, origin.y + half_chunk),
+            min_height,
+            max_height,
+            mean_height,
+            curvature,
+        }
+    }
+
+    pub fn relief(&self) -> Real {
+        (self.max_height - self.min_height) as Real
+    }
+
+    // Steep and rugged terrain is more interesting than flat plains
+    pub fn interest(&self) -> Real {
+        self.relief() + CURVATURE_WEIGHT * self.curvature
+    }
+}
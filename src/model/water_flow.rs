@@ -0,0 +1,264 @@
+use std::collections::VecDeque;
+
+use crate::minecraft::BLOCKS_IN_CHUNK;
+
+use super::discrete::World;
+use super::pathfinding::Column;
+use super::Real;
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct FlowCell {
+    pub column: Column,
+    // Height of the topmost block of the column
+    pub height: isize,
+    // Number of columns draining through this one, itself included
+    pub accumulation: u32,
+    // Where the water flows next, None for sinks
+    pub downstream: Option<Column>,
+    // Index of the sink the water ends up in
+    pub basin: usize,
+}
+
+// D8 flow over the heightmap of the loaded chunks.
+//
+// Every column drains into the neighbour with the steepest descent. Water on flat areas
+// flows towards the nearest column of the same flat that has a lower neighbour,
+// flats without one drain into a single sink. Accumulation counts the columns upstream
+// of each column, columns with a high accumulation form streams and the columns draining
+// into the same sink form a watershed.
+pub struct FlowMap {
+    // Column of the first cell, the cells are stored row by row along x
+    origin: Column,
+    width: usize,
+    depth: usize,
+
+    // None outside of the loaded chunks
+    heights: Vec<Option<isize>>,
+    downstream: Vec<Option<usize>>,
+    accumulation: Vec<u32>,
+    basins: Vec<usize>,
+    basin_count: usize,
+}
+
+impl FlowMap {
+    pub fn compute(world: &World) -> Self {
+        let origins = world
+            .get_loaded_chunk_positions()
+            .iter()
+            .map(|chunk_position| chunk_position.coords().origin())
+            .collect::<Vec<_>>();
+        let min_x = origins
+            .iter()
+            .map(|origin| origin.x as isize)
+            .min()
+            .unwrap_or(0);
+        let min_z = origins
+            .iter()
+            .map(|origin| origin.y as isize)
+            .min()
+            .unwrap_or(0);
+        let max_x = origins.iter().map(|origin| origin.x as isize).max();
+        let max_z = origins.iter().map(|origin| origin.y as isize).max();
+
+        let chunk_size = BLOCKS_IN_CHUNK as isize;
+        let width = max_x.map_or(0, |max_x| max_x - min_x + chunk_size) as usize;
+        let depth = max_z.map_or(0, |max_z| max_z - min_z + chunk_size) as usize;
+
+        let origin = Column { x: min_x, z: min_z };
+        let heights = (0..width * depth)
+            .map(|index| {
+                let column = Column {
+                    x: origin.x + (index % width) as isize,
+                    z: origin.z + (index / width) as isize,
+                };
+                world.get_surface_height(column.center())
+            })
+            .collect();
+
+        let mut flow = FlowMap {
+            origin,
+            width,
+            depth,
+            heights,
+            downstream: vec![None; width * depth],
+            accumulation: vec![0; width * depth],
+            basins: vec![0; width * depth],
+            basin_count: 0,
+        };
+        flow.compute_directions();
+        flow.compute_accumulation();
+        flow.compute_basins();
+
+        flow
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = FlowCell> + '_ {
+        (0..self.heights.len()).filter_map(|index| {
+            Some(FlowCell {
+                column: self.column(index),
+                height: self.heights[index]?,
+                accumulation: self.accumulation[index],
+                downstream: self.downstream[index].map(|next| self.column(next)),
+                basin: self.basins[index],
+            })
+        })
+    }
+
+    pub fn basin_count(&self) -> usize {
+        self.basin_count
+    }
+
+    pub fn max_accumulation(&self) -> u32 {
+        self.accumulation.iter().copied().max().unwrap_or(0)
+    }
+
+    fn column(&self, index: usize) -> Column {
+        Column {
+            x: self.origin.x + (index % self.width) as isize,
+            z: self.origin.z + (index / self.width) as isize,
+        }
+    }
+
+    // Loaded neighbours of the cell with their height and distance
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = (usize, isize, Real)> + '_ {
+        let x = (index % self.width) as isize;
+        let z = (index / self.width) as isize;
+
+        NEIGHBOURS.iter().filter_map(move |&(dx, dz)| {
+            let (x, z) = (x + dx, z + dz);
+            let outside = x < 0 || z < 0 || x >= self.width as isize || z >= self.depth as isize;
+            if outside {
+                return None;
+            }
+
+            let neighbour = z as usize * self.width + x as usize;
+            let height = self.heights[neighbour]?;
+            let distance = ((dx * dx + dz * dz) as Real).sqrt();
+            Some((neighbour, height, distance))
+        })
+    }
+
+    fn compute_directions(&mut self) {
+        for index in 0..self.heights.len() {
+            let Some(height) = self.heights[index] else {
+                continue;
+            };
+
+            let steepest = self
+                .neighbours(index)
+                .filter(|&(_, neighbour_height, _)| neighbour_height < height)
+                .map(|(neighbour, neighbour_height, distance)| {
+                    (neighbour, (height - neighbour_height) as Real / distance)
+                })
+                .max_by(|(_, a), (_, b)| a.total_cmp(b));
+            self.downstream[index] = steepest.map(|(neighbour, _)| neighbour);
+        }
+
+        // Flats drain towards the columns that already have somewhere to flow
+        let mut resolved = self
+            .downstream
+            .iter()
+            .map(Option::is_some)
+            .collect::<Vec<_>>();
+        let outlets = (0..self.heights.len())
+            .filter(|&index| resolved[index])
+            .collect();
+        self.drain_flats(outlets, &mut resolved);
+
+        // The rest are closed flats and pits, each drains into one of its columns
+        for index in 0..self.heights.len() {
+            if self.heights[index].is_some() && !resolved[index] {
+                resolved[index] = true;
+                self.drain_flats(VecDeque::from([index]), &mut resolved);
+            }
+        }
+    }
+
+    // Breadth first search from *queue* over the columns of the same height
+    fn drain_flats(&mut self, mut queue: VecDeque<usize>, resolved: &mut [bool]) {
+        while let Some(index) = queue.pop_front() {
+            let height = self.heights[index];
+            let flat = self
+                .neighbours(index)
+                .filter(|&(neighbour, neighbour_height, _)| {
+                    Some(neighbour_height) == height && !resolved[neighbour]
+                })
+                .map(|(neighbour, _, _)| neighbour)
+                .collect::<Vec<_>>();
+
+            for neighbour in flat {
+                resolved[neighbour] = true;
+                self.downstream[neighbour] = Some(index);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    // Columns are visited once everything upstream of them has been added
+    fn compute_accumulation(&mut self) {
+        let mut upstream = vec![0; self.heights.len()];
+        for next in self.downstream.iter().flatten() {
+            upstream[*next] += 1;
+        }
+
+        let mut queue = (0..self.heights.len())
+            .filter(|&index| self.heights[index].is_some() && upstream[index] == 0)
+            .collect::<VecDeque<_>>();
+        while let Some(index) = queue.pop_front() {
+            self.accumulation[index] += 1;
+
+            if let Some(next) = self.downstream[index] {
+                self.accumulation[next] += self.accumulation[index];
+                upstream[next] -= 1;
+                if upstream[next] == 0 {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    fn compute_basins(&mut self) {
+        let mut basins: Vec<Option<usize>> = vec![None; self.heights.len()];
+
+        for index in 0..self.heights.len() {
+            if self.heights[index].is_some() && self.downstream[index].is_none() {
+                basins[index] = Some(self.basin_count);
+                self.basin_count += 1;
+            }
+        }
+
+        for index in 0..self.heights.len() {
+            if self.heights[index].is_none() {
+                continue;
+            }
+
+            // Follow the flow until a column with a known basin, then label the whole way
+            let mut path = Vec::new();
+            let mut current = index;
+            let basin = loop {
+                if let Some(basin) = basins[current] {
+                    break basin;
+                }
+                path.push(current);
+                current = self.downstream[current].unwrap();
+            };
+
+            for visited in path {
+                basins[visited] = Some(basin);
+            }
+        }
+
+        self.basins = basins.into_iter().map(|basin| basin.unwrap_or(0)).collect();
+    }
+}
@@ -0,0 +1,373 @@
+use cgmath::{ElementWise, InnerSpace, Matrix4, Rad, SquareMatrix, Vector3, Zero};
+use image::{ImageResult, RgbImage};
+use rayon::prelude::*;
+
+use crate::camera::Camera;
+use crate::config;
+use crate::infrastructure::jobs::{self, JobContext, JobHandle, Priority};
+use crate::infrastructure::texture::BlockTextures;
+use crate::infrastructure::vertical_scale::VerticalScale;
+use crate::model::bvh::{MeshBvh, TriangleHit};
+use crate::model::polygonize::Mesh;
+use crate::model::Real;
+
+type Color = Vector3<f32>;
+
+// The realtime renderer keeps the sun at +200, +300, +200 from the camera
+const SUN_DIRECTION: [f32; 3] = [200.0, 300.0, 200.0];
+const SUN_IRRADIANCE: [f32; 3] = [3.2, 2.5, 1.9];
+const SKY_ZENITH: [f32; 3] = [0.25, 0.42, 0.85];
+const SKY_HORIZON: [f32; 3] = [0.75, 0.82, 0.95];
+
+const MAX_RAY_DISTANCE: f32 = 2048.0;
+// Secondary rays start this far above the surface so they don't hit the triangle they left
+const RAY_OFFSET: f32 = 1e-3;
+
+const TILE_RESOLUTION: usize = config::BLOCK_TEXTURE_RESOLUTION as usize;
+
+// What the offline render sees, copied from the camera when the render starts
+#[derive(Clone, Copy)]
+pub struct RenderView {
+    view_to_world: Matrix4<Real>,
+    fovy: Rad<Real>,
+}
+
+impl RenderView {
+    // The rays are traced through the unscaled mesh, hitting what the camera sees
+    pub fn from_camera(camera: &Camera, vertical_scale: VerticalScale) -> Self {
+        let scene_to_world = vertical_scale.model_matrix().invert().unwrap();
+
+        RenderView {
+            view_to_world: scene_to_world * camera.view_to_world,
+            fovy: camera.fovy(),
+        }
+    }
+
+    // Ray through the point of the image at *x*, *y* from 0.0 to 1.0, y going down
+    fn ray(&self, x: f32, y: f32, aspect_ratio: f32) -> (Color, Color) {
+        let half_height = (self.fovy.0 as f32 / 2.0).tan();
+        let half_width = half_height * aspect_ratio;
+
+        let view_direction = Vector3::new(
+            ((2.0 * x - 1.0) * half_width) as Real,
+            ((1.0 - 2.0 * y) * half_height) as Real,
+            -1.0,
+        );
+        let direction = (self.view_to_world * view_direction.extend(0.0)).truncate();
+        let origin = self.view_to_world.w.truncate();
+
+        (
+            origin.cast().unwrap(),
+            direction.normalize().cast().unwrap(),
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct OfflineRenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    pub max_bounces: u32,
+}
+
+impl OfflineRenderSettings {
+    pub fn from_config() -> Self {
+        OfflineRenderSettings {
+            width: config::OFFLINE_RENDER_WIDTH,
+            height: config::OFFLINE_RENDER_HEIGHT,
+            samples_per_pixel: config::OFFLINE_RENDER_SAMPLES,
+            max_bounces: config::OFFLINE_RENDER_BOUNCES,
+        }
+    }
+}
+
+// Path traced image of the smooth mesh, rendered by a background job.
+//
+// The surface is lambertian with the albedo of the blended block textures, it is lit
+// by the sun and a gradient sky. Each row of the image is traced as one rayon task.
+pub struct OfflineRender {
+    job: JobHandle<ImageResult<()>>,
+}
+
+impl OfflineRender {
+    pub fn start(
+        mesh: Mesh,
+        view: RenderView,
+        settings: OfflineRenderSettings,
+        path: &str,
+    ) -> Self {
+        let path = path.to_owned();
+
+        let rows = settings.height as usize;
+        let job = jobs::spawn("offline render", Priority::Low, rows, move |context| {
+            let textures = MaterialTextures::load()?;
+            let scene = Scene {
+                bvh: MeshBvh::build(&mesh),
+                mesh,
+                textures,
+            };
+            println!(
+                "[INFO] Offline render of {} triangles started",
+                scene.bvh.triangle_count()
+            );
+
+            let image = scene.render(view, settings, context);
+            // A cancelled render is left unfinished, it isn't saved
+            if context.is_cancelled() {
+                return Ok(());
+            }
+            image.save(path)
+        });
+
+        OfflineRender { job }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.job.progress()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.job.is_finished()
+    }
+
+    // The rows not traced yet are skipped, nothing is saved
+    pub fn cancel(&self) {
+        self.job.cancel();
+    }
+
+    pub fn join(self) -> ImageResult<()> {
+        self.job.join().expect("The offline render job panicked")
+    }
+}
+
+struct Scene {
+    mesh: Mesh,
+    bvh: MeshBvh,
+    textures: MaterialTextures,
+}
+
+impl Scene {
+    fn render(
+        &self,
+        view: RenderView,
+        settings: OfflineRenderSettings,
+        context: &JobContext,
+    ) -> RgbImage {
+        let (width, height) = (settings.width, settings.height);
+        let aspect_ratio = width as f32 / height as f32;
+
+        let rows = (0..height)
+            .into_par_iter()
+            .map(|y| {
+                if context.is_cancelled() {
+                    return vec![0; width as usize * 3];
+                }
+
+                let row = (0..width)
+                    .flat_map(|x| {
+                        let mut random = Random::new(y * width + x);
+                        let radiance = (0..settings.samples_per_pixel)
+                            .map(|_| {
+                                let u = (x as f32 + random.next()) / width as f32;
+                                let v = (y as f32 + random.next()) / height as f32;
+                                let (origin, direction) = view.ray(u, v, aspect_ratio);
+
+                                self.trace(origin, direction, settings.max_bounces, &mut random)
+                            })
+                            .sum::<Color>()
+                            / settings.samples_per_pixel.max(1) as f32;
+
+                        tone_map(radiance)
+                    })
+                    .collect::<Vec<u8>>();
+
+                context.advance(1);
+                row
+            })
+            .collect::<Vec<_>>();
+
+        RgbImage::from_raw(width, height, rows.concat()).unwrap()
+    }
+
+    fn trace(
+        &self,
+        mut origin: Color,
+        mut direction: Color,
+        max_bounces: u32,
+        random: &mut Random,
+    ) -> Color {
+        let sun = Color::from(SUN_DIRECTION).normalize();
+        let mut radiance = Color::zero();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+
+        for _ in 0..=max_bounces {
+            let Some(hit) = self.bvh.closest_hit(origin, direction, MAX_RAY_DISTANCE) else {
+                radiance += throughput.mul_element_wise(sky(direction));
+                break;
+            };
+
+            let position = origin + direction * hit.distance;
+            let mut normal = self.normal(&hit);
+            if normal.dot(direction) > 0.0 {
+                normal = -normal;
+            }
+            let albedo = self.albedo(&hit, position, normal);
+            let surface = position + normal * RAY_OFFSET;
+
+            // Direct sunlight, the lambertian brdf is albedo / pi
+            let sun_cosine = normal.dot(sun);
+            if sun_cosine > 0.0 && !self.bvh.any_hit(surface, sun, MAX_RAY_DISTANCE) {
+                let irradiance = Color::from(SUN_IRRADIANCE) * sun_cosine;
+                radiance += throughput
+                    .mul_element_wise(albedo)
+                    .mul_element_wise(irradiance)
+                    / std::f32::consts::PI;
+            }
+
+            // With cosine weighted sampling the brdf and the pdf cancel out up to the albedo
+            throughput = throughput.mul_element_wise(albedo);
+            origin = surface;
+            direction = random.cosine_hemisphere(normal);
+        }
+
+        radiance
+    }
+
+    fn normal(&self, hit: &TriangleHit) -> Color {
+        let corners = &self.mesh.indices[3 * hit.triangle..3 * hit.triangle + 3];
+        let normal = corners
+            .iter()
+            .zip([hit.barycentric.x, hit.barycentric.y, hit.barycentric.z])
+            .map(|(&corner, weight)| {
+                Color::from(self.mesh.vertices[corner as usize].normal) * weight
+            })
+            .sum::<Color>();
+
+        normal.normalize()
+    }
+
+    // Block textures mixed by the material weights, projected along the dominant axis of the normal
+    fn albedo(&self, hit: &TriangleHit, position: Color, normal: Color) -> Color {
+        let corners = &self.mesh.indices[3 * hit.triangle..3 * hit.triangle + 3];
+        let mut weights = [[0.0; 4]; 4];
+        for (&corner, weight) in
+            corners
+                .iter()
+                .zip([hit.barycentric.x, hit.barycentric.y, hit.barycentric.z])
+        {
+            let vertex_weights = self.mesh.vertices[corner as usize].vertex_material_weights;
+            for (col, row) in (0..4).flat_map(|col| (0..4).map(move |row| (col, row))) {
+                weights[col][row] += weight * vertex_weights[col][row];
+            }
+        }
+
+        let (u, v) = match (normal.x.abs(), normal.y.abs(), normal.z.abs()) {
+            (x, y, z) if x >= y && x >= z => (position.y, position.z),
+            (_, y, z) if y >= z => (position.x, position.z),
+            _ => (position.x, position.y),
+        };
+
+        let total = weights.iter().flatten().sum::<f32>();
+        let color = (0..4)
+            .flat_map(|col| (0..4).map(move |row| (col, row)))
+            .filter(|&(col, row)| weights[col][row] > 0.0)
+            .map(|(col, row)| {
+                self.textures.sample(col * 4 + row, u.fract(), v.fract()) * weights[col][row]
+            })
+            .sum::<Color>();
+
+        match total > 0.0 {
+            true => color / total,
+            false => self.textures.sample(0, u.fract(), v.fract()),
+        }
+    }
+}
+
+fn sky(direction: Color) -> Color {
+    let elevation = direction.y.max(0.0);
+    Color::from(SKY_HORIZON) * (1.0 - elevation) + Color::from(SKY_ZENITH) * elevation
+}
+
+// Reinhard tone mapping followed by the srgb transfer function
+fn tone_map(radiance: Color) -> [u8; 3] {
+    [radiance.x, radiance.y, radiance.z].map(|channel| {
+        let mapped = channel / (1.0 + channel);
+        (mapped.powf(1.0 / 2.2) * 255.0).round().clamp(0.0, 255.0) as u8
+    })
+}
+
+// Linear colors of the block textures, one list of texels per material
+struct MaterialTextures {
+    layers: Vec<Vec<Color>>,
+}
+
+impl MaterialTextures {
+    fn load() -> ImageResult<Self> {
+        let layers = BlockTextures::load()?
+            .images()
+            .iter()
+            .map(|image| {
+                image
+                    .pixels()
+                    .map(|pixel| {
+                        let [red, green, blue] = [pixel[0], pixel[1], pixel[2]]
+                            .map(|channel| (channel as f32 / 255.0).powf(2.2));
+                        Color::new(red, green, blue)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(MaterialTextures { layers })
+    }
+
+    // *u* and *v* are from 0.0 to 1.0 within the texture of the material, as in the implicit shader
+    fn sample(&self, material_index: usize, u: f32, v: f32) -> Color {
+        let layer = &self.layers[material_index.min(self.layers.len() - 1)];
+
+        // The images start at the top
+        let texel_x = ((u * TILE_RESOLUTION as f32) as usize).min(TILE_RESOLUTION - 1);
+        let texel_y =
+            TILE_RESOLUTION - 1 - ((v * TILE_RESOLUTION as f32) as usize).min(TILE_RESOLUTION - 1);
+
+        layer[texel_y * TILE_RESOLUTION + texel_x]
+    }
+}
+
+// Xorshift generator, every pixel starts from its own seed so the render is reproducible
+struct Random(u32);
+
+impl Random {
+    fn new(seed: u32) -> Self {
+        // Scramble the seed, neighbouring pixels would start with similar sequences otherwise
+        let mut state = seed.wrapping_mul(747796405).wrapping_add(2891336453);
+        state ^= state >> 16;
+        Random(state.max(1))
+    }
+
+    // Uniform from 0.0 to 1.0
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn cosine_hemisphere(&mut self, normal: Color) -> Color {
+        let radius = self.next().sqrt();
+        let angle = 2.0 * std::f32::consts::PI * self.next();
+        let (x, y) = (radius * angle.cos(), radius * angle.sin());
+        let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+
+        // Any tangent works, pick an axis that isn't parallel to the normal
+        let helper = match normal.x.abs() > 0.9 {
+            true => Color::unit_y(),
+            false => Color::unit_x(),
+        };
+        let tangent = normal.cross(helper).normalize();
+        let bitangent = normal.cross(tangent);
+
+        tangent * x + bitangent * y + normal * z
+    }
+}
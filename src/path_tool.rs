@@ -0,0 +1,91 @@
+use crate::infrastructure::event_bus::{AppEvent, EventSubscriber, FrameContext};
+use crate::model::pathfinding::{find_path, PathError, WalkablePath};
+use crate::model::polygonize::PolygonizationOptions;
+use crate::model::{PlanarPosition, Position, Real};
+
+// Walkable route between two points marked in the 3D view.
+// The first marked point starts a new route, the second one is its goal.
+//
+// The route is searched again when the maximum slope or the terrain changes,
+// and draped on the smooth surface again when the polygonization options change.
+pub struct PathTool {
+    pub start: Option<PlanarPosition>,
+    pub goal: Option<PlanarPosition>,
+    pub path: Option<Result<WalkablePath, PathError>>,
+
+    // Points of the route on the smooth surface, drawn by the path plugin
+    pub polyline: Vec<Position>,
+    // Bumped whenever the polyline changes
+    pub revision: usize,
+
+    searched_slope: Option<Real>,
+    draped_with: Option<PolygonizationOptions>,
+}
+
+impl PathTool {
+    pub fn new() -> Self {
+        PathTool {
+            start: None,
+            goal: None,
+            path: None,
+            polyline: Vec::new(),
+            revision: 0,
+            searched_slope: None,
+            draped_with: None,
+        }
+    }
+
+    pub fn mark(&mut self, point: PlanarPosition) {
+        match (self.start, self.goal) {
+            (Some(_), None) => self.goal = Some(point),
+            _ => {
+                self.clear();
+                self.start = Some(point);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.start = None;
+        self.goal = None;
+        self.path = None;
+        self.searched_slope = None;
+        self.set_polyline(Vec::new());
+    }
+
+    fn set_polyline(&mut self, polyline: Vec<Position>) {
+        self.polyline = polyline;
+        self.revision += 1;
+    }
+}
+
+impl EventSubscriber for PathTool {
+    fn handle_events(&mut self, events: &[AppEvent], context: &FrameContext) {
+        let (world, options) = (context.world, context.options);
+        let max_slope = context.controls.path_max_slope as Real;
+        let (Some(start), Some(goal)) = (self.start, self.goal) else {
+            return;
+        };
+
+        let terrain_changed = events.iter().any(|event| {
+            matches!(
+                event,
+                AppEvent::ChunkLoaded(_) | AppEvent::BlockEdited(_, _)
+            )
+        });
+        if terrain_changed || self.searched_slope != Some(max_slope) {
+            self.path = Some(find_path(world, start, goal, max_slope));
+            self.searched_slope = Some(max_slope);
+            self.draped_with = None;
+        }
+
+        if self.draped_with != Some(options) {
+            let polyline = match &self.path {
+                Some(Ok(path)) => path.drape(world, options),
+                _ => Vec::new(),
+            };
+            self.set_polyline(polyline);
+            self.draped_with = Some(options);
+        }
+    }
+}
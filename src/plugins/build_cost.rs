@@ -0,0 +1,151 @@
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::{uniform, Display, IndexBuffer};
+
+use crate::camera::Camera;
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::infrastructure::color_ramp::ColorRamp;
+use crate::infrastructure::event_bus::AppEvent;
+use crate::infrastructure::render_fragment::RenderFragmentBuilder;
+use crate::infrastructure::render_plugin::{PluginContext, RenderPlugin};
+use crate::infrastructure::vertex::Vertex;
+use crate::infrastructure::vertical_scale::VerticalScale;
+use crate::infrastructure::{RenderState, RenderingMode};
+use crate::minecraft::BLOCKS_IN_CHUNK;
+use crate::model::discrete::World;
+use crate::scene::{NoInstance, RenderPass};
+use crate::to_uniform_matrix;
+
+const HEATMAP_VS: &str = include_str!("../shaders/grid_vs.glsl");
+const HEATMAP_FS: &str = include_str!("../shaders/heatmap_fs.glsl");
+
+const HEATMAP_OPACITY: f32 = 0.6;
+
+// The tiles float this far above the highest block of their chunk
+const TILE_ELEVATION: f32 = 2.0;
+
+type HeatmapScene = RenderPass<'static, NoInstance, Vertex, IndexBuffer<u32>>;
+
+// Colors every polygonized chunk by how long building its mesh took,
+// relative to the most expensive chunk that is loaded
+pub struct BuildCostPlugin {
+    scene: Option<HeatmapScene>,
+    // Ramp the tiles of the scene were colored with
+    color_ramp: ColorRamp,
+}
+
+impl BuildCostPlugin {
+    pub fn new() -> Self {
+        BuildCostPlugin {
+            scene: None,
+            color_ramp: ColorRamp::Heat,
+        }
+    }
+}
+
+impl RenderPlugin for BuildCostPlugin {
+    fn name(&self) -> &'static str {
+        "build cost heatmap"
+    }
+
+    fn update(&mut self, display: &Display, context: &PluginContext) {
+        let costs_changed = context.events.iter().any(|event| {
+            matches!(
+                event,
+                AppEvent::MeshBuilt(_) | AppEvent::ChunkLoaded(_) | AppEvent::OptionsChanged(_)
+            )
+        });
+
+        let ramp_changed = context.controls.color_ramp != self.color_ramp;
+        if costs_changed || ramp_changed {
+            self.color_ramp = context.controls.color_ramp;
+            self.scene = create_heatmap_scene(context.world, display, self.color_ramp);
+        }
+    }
+
+    fn render(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera: &Camera,
+        state: &RenderState,
+        controls: &SmoothMeshOptions,
+    ) {
+        let comparison = matches!(state.settings.render_mode, RenderingMode::Comparison);
+        if !controls.show_build_cost || comparison {
+            return;
+        }
+        let Some(scene) = &self.scene else {
+            return;
+        };
+
+        let uni = uniform! {
+            projection: to_uniform_matrix(&camera.projection),
+            view: to_uniform_matrix(&camera.world_to_view),
+            model: to_uniform_matrix(&VerticalScale::new(controls.vertical_scale).model_matrix()),
+            opacity: HEATMAP_OPACITY,
+        };
+
+        let draw_parameters = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: false,
+                ..Default::default()
+            },
+            blend: glium::Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        scene.execute(target, &uni, Some(draw_parameters));
+    }
+}
+
+// None while no chunk mesh is built yet
+fn create_heatmap_scene(
+    world: &World,
+    display: &Display,
+    color_ramp: ColorRamp,
+) -> Option<HeatmapScene> {
+    let costs = world.get_chunk_build_costs();
+    let most_expensive = costs
+        .iter()
+        .map(|(_, cost)| cost.duration.as_secs_f32())
+        .fold(0.0, f32::max);
+    if costs.is_empty() || most_expensive == 0.0 {
+        return None;
+    }
+
+    let half_size = BLOCKS_IN_CHUNK as f32 / 2.0;
+    let vertices = costs
+        .iter()
+        .flat_map(|(metrics, cost)| {
+            let color = color_ramp.sample(cost.duration.as_secs_f32() / most_expensive);
+            let (x, z) = (metrics.center.x as f32, metrics.center.y as f32);
+            let y = metrics.max_height as f32 + TILE_ELEVATION;
+
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(dx, dz)| Vertex {
+                position: [x + dx * half_size, y, z + dz * half_size],
+                color,
+                normal: [0.0, 1.0, 0.0],
+            })
+        })
+        .collect::<Vec<_>>();
+    let vertex_buffer = glium::VertexBuffer::new(display, &vertices).unwrap();
+
+    let indices = (0..costs.len() as u32)
+        .flat_map(|tile| [0, 1, 2, 0, 2, 3].map(|corner| tile * 4 + corner))
+        .collect::<Vec<_>>();
+    let index_buffer = glium::IndexBuffer::new(
+        display,
+        glium::index::PrimitiveType::TrianglesList,
+        &indices,
+    )
+    .unwrap();
+
+    let fragment = RenderFragmentBuilder::new()
+        .set_geometry(vertex_buffer, index_buffer)
+        .set_vertex_shader(HEATMAP_VS)
+        .set_fragment_shader(HEATMAP_FS)
+        .build(display)
+        .unwrap();
+
+    Some(RenderPass::new(fragment))
+}
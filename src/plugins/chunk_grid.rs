@@ -0,0 +1,118 @@
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::{uniform, Display, IndexBuffer};
+
+use crate::camera::Camera;
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::infrastructure::event_bus::AppEvent;
+use crate::infrastructure::render_fragment::RenderFragmentBuilder;
+use crate::infrastructure::render_plugin::{PluginContext, RenderPlugin};
+use crate::infrastructure::vertex::Vertex;
+use crate::infrastructure::vertical_scale::VerticalScale;
+use crate::infrastructure::{RenderState, RenderingMode};
+use crate::model::discrete::World;
+use crate::model::Real;
+use crate::scene::{NoInstance, RenderPass};
+use crate::to_uniform_matrix;
+
+const GRID_VS: &str = include_str!("../shaders/grid_vs.glsl");
+const GRID_FS: &str = include_str!("../shaders/grid_fs.glsl");
+
+type GridScene = RenderPass<'static, NoInstance, Vertex, IndexBuffer<u32>>;
+
+// Borders of the polygonized chunks, following the terrain or at a fixed height
+pub struct ChunkGridPlugin {
+    scene: Option<GridScene>,
+    plane_height: Option<Real>,
+}
+
+impl ChunkGridPlugin {
+    pub fn new() -> Self {
+        ChunkGridPlugin {
+            scene: None,
+            plane_height: None,
+        }
+    }
+}
+
+impl RenderPlugin for ChunkGridPlugin {
+    fn name(&self) -> &'static str {
+        "chunk grid"
+    }
+
+    fn update(&mut self, display: &Display, context: &PluginContext) {
+        let plane_height = context.controls.grid_plane();
+        let world_changed = context.events.iter().any(|event| {
+            matches!(
+                event,
+                AppEvent::ChunkLoaded(_) | AppEvent::BlockEdited(_, _)
+            )
+        });
+        let outdated = self.scene.is_none() || world_changed || plane_height != self.plane_height;
+
+        if outdated {
+            self.scene = Some(create_grid_scene(context.world, display, plane_height));
+            self.plane_height = plane_height;
+        }
+    }
+
+    fn render(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera: &Camera,
+        state: &RenderState,
+        controls: &SmoothMeshOptions,
+    ) {
+        let comparison = matches!(state.settings.render_mode, RenderingMode::Comparison);
+        if !controls.show_grid || comparison {
+            return;
+        }
+        let Some(scene) = &self.scene else {
+            return;
+        };
+
+        let uni = uniform! {
+            projection: to_uniform_matrix(&camera.projection),
+            view: to_uniform_matrix(&camera.world_to_view),
+            model: to_uniform_matrix(&VerticalScale::new(controls.vertical_scale).model_matrix()),
+        };
+
+        // Lines hidden behind the terrain are not drawn, but they don't occlude anything either
+        let draw_parameters = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            line_width: Some(2.0),
+            ..Default::default()
+        };
+
+        scene.execute(target, &uni, Some(draw_parameters));
+    }
+}
+
+fn create_grid_scene(world: &World, display: &Display, plane_height: Option<Real>) -> GridScene {
+    let vertices = world
+        .get_chunk_grid_lines(plane_height)
+        .into_iter()
+        .map(|point| Vertex {
+            position: [point.x as f32, point.y as f32, point.z as f32],
+            color: [0.9, 0.1, 0.1],
+            normal: [0.0, 1.0, 0.0],
+        })
+        .collect::<Vec<_>>();
+    let vertex_buffer = glium::VertexBuffer::new(display, &vertices).unwrap();
+
+    let indices = (0..vertices.len() as u32).collect::<Vec<_>>();
+    let index_buffer =
+        glium::IndexBuffer::new(display, glium::index::PrimitiveType::LinesList, &indices).unwrap();
+
+    let fragment = RenderFragmentBuilder::new()
+        .set_geometry(vertex_buffer, index_buffer)
+        .set_vertex_shader(GRID_VS)
+        .set_fragment_shader(GRID_FS)
+        .build(display)
+        .unwrap();
+
+    RenderPass::new(fragment)
+}
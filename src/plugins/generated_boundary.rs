@@ -0,0 +1,127 @@
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::{uniform, Display, IndexBuffer};
+
+use crate::camera::Camera;
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::infrastructure::event_bus::AppEvent;
+use crate::infrastructure::render_fragment::RenderFragmentBuilder;
+use crate::infrastructure::render_plugin::{PluginContext, RenderPlugin};
+use crate::infrastructure::vertex::Vertex;
+use crate::infrastructure::vertical_scale::VerticalScale;
+use crate::infrastructure::{RenderState, RenderingMode};
+use crate::model::discrete::World;
+use crate::scene::{NoInstance, RenderPass};
+use crate::to_uniform_matrix;
+
+const WALL_VS: &str = include_str!("../shaders/grid_vs.glsl");
+const WALL_FS: &str = include_str!("../shaders/heatmap_fs.glsl");
+
+const WALL_COLOR: [f32; 3] = [0.55, 0.6, 0.7];
+const WALL_OPACITY: f32 = 0.25;
+
+type WallScene = RenderPass<'static, NoInstance, Vertex, IndexBuffer<u32>>;
+
+// Faint walls where the generated terrain ends,
+// so chunks that were never generated don't look like holes in the terrain
+pub struct GeneratedBoundaryPlugin {
+    scene: Option<WallScene>,
+}
+
+impl GeneratedBoundaryPlugin {
+    pub fn new() -> Self {
+        GeneratedBoundaryPlugin { scene: None }
+    }
+}
+
+impl RenderPlugin for GeneratedBoundaryPlugin {
+    fn name(&self) -> &'static str {
+        "generated boundary"
+    }
+
+    fn update(&mut self, display: &Display, context: &PluginContext) {
+        let world_changed = context.events.iter().any(|event| {
+            matches!(
+                event,
+                AppEvent::ChunkLoaded(_) | AppEvent::BlockEdited(_, _)
+            )
+        });
+
+        if world_changed {
+            self.scene = create_wall_scene(context.world, display);
+        }
+    }
+
+    fn render(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera: &Camera,
+        state: &RenderState,
+        controls: &SmoothMeshOptions,
+    ) {
+        let comparison = matches!(state.settings.render_mode, RenderingMode::Comparison);
+        if !controls.show_generated_boundary || comparison {
+            return;
+        }
+        let Some(scene) = &self.scene else {
+            return;
+        };
+
+        let uni = uniform! {
+            projection: to_uniform_matrix(&camera.projection),
+            view: to_uniform_matrix(&camera.world_to_view),
+            model: to_uniform_matrix(&VerticalScale::new(controls.vertical_scale).model_matrix()),
+            opacity: WALL_OPACITY,
+        };
+
+        // Both sides of the walls are drawn, the terrain hides the parts behind it
+        let draw_parameters = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: false,
+                ..Default::default()
+            },
+            blend: glium::Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        scene.execute(target, &uni, Some(draw_parameters));
+    }
+}
+
+// None when every loaded chunk is generated, or none is
+fn create_wall_scene(world: &World, display: &Display) -> Option<WallScene> {
+    let walls = world.get_generated_boundary();
+    if walls.is_empty() {
+        return None;
+    }
+
+    let vertices = walls
+        .iter()
+        .flatten()
+        .map(|corner| Vertex {
+            position: [corner.x as f32, corner.y as f32, corner.z as f32],
+            color: WALL_COLOR,
+            normal: [0.0, 1.0, 0.0],
+        })
+        .collect::<Vec<_>>();
+    let vertex_buffer = glium::VertexBuffer::new(display, &vertices).unwrap();
+
+    let indices = (0..walls.len() as u32)
+        .flat_map(|wall| [0, 1, 2, 0, 2, 3].map(|corner| wall * 4 + corner))
+        .collect::<Vec<_>>();
+    let index_buffer = glium::IndexBuffer::new(
+        display,
+        glium::index::PrimitiveType::TrianglesList,
+        &indices,
+    )
+    .unwrap();
+
+    let fragment = RenderFragmentBuilder::new()
+        .set_geometry(vertex_buffer, index_buffer)
+        .set_vertex_shader(WALL_VS)
+        .set_fragment_shader(WALL_FS)
+        .build(display)
+        .unwrap();
+
+    Some(RenderPass::new(fragment))
+}
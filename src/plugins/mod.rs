@@ -0,0 +1,7 @@
+// Visualizations drawn through the render plugin interface,
+// see infrastructure::render_plugin
+pub mod build_cost;
+pub mod chunk_grid;
+pub mod generated_boundary;
+pub mod walkable_path;
+pub mod water_flow;
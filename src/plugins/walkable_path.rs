@@ -0,0 +1,121 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::{uniform, Display, IndexBuffer};
+
+use crate::camera::Camera;
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::infrastructure::render_fragment::RenderFragmentBuilder;
+use crate::infrastructure::render_plugin::{PluginContext, RenderPlugin};
+use crate::infrastructure::vertex::Vertex;
+use crate::infrastructure::vertical_scale::VerticalScale;
+use crate::infrastructure::{RenderState, RenderingMode};
+use crate::model::Position;
+use crate::path_tool::PathTool;
+use crate::scene::{NoInstance, RenderPass};
+use crate::to_uniform_matrix;
+
+const PATH_VS: &str = include_str!("../shaders/grid_vs.glsl");
+const PATH_FS: &str = include_str!("../shaders/grid_fs.glsl");
+
+const PATH_COLOR: [f32; 3] = [1.0, 0.85, 0.1];
+
+type PathScene = RenderPass<'static, NoInstance, Vertex, IndexBuffer<u32>>;
+
+// The route found by the path tool, drawn as a polyline on the smooth surface
+pub struct WalkablePathPlugin {
+    tool: Rc<RefCell<PathTool>>,
+    scene: Option<PathScene>,
+    revision: Option<usize>,
+}
+
+impl WalkablePathPlugin {
+    pub fn new(tool: Rc<RefCell<PathTool>>) -> Self {
+        WalkablePathPlugin {
+            tool,
+            scene: None,
+            revision: None,
+        }
+    }
+}
+
+impl RenderPlugin for WalkablePathPlugin {
+    fn name(&self) -> &'static str {
+        "walkable path"
+    }
+
+    fn update(&mut self, display: &Display, _context: &PluginContext) {
+        let tool = self.tool.borrow();
+        if self.revision == Some(tool.revision) {
+            return;
+        }
+
+        self.scene = create_path_scene(display, &tool.polyline);
+        self.revision = Some(tool.revision);
+    }
+
+    fn render(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera: &Camera,
+        state: &RenderState,
+        controls: &SmoothMeshOptions,
+    ) {
+        let comparison = matches!(state.settings.render_mode, RenderingMode::Comparison);
+        if !controls.show_path_finder || comparison {
+            return;
+        }
+        let Some(scene) = &self.scene else {
+            return;
+        };
+
+        let uni = uniform! {
+            projection: to_uniform_matrix(&camera.projection),
+            view: to_uniform_matrix(&camera.world_to_view),
+            model: to_uniform_matrix(&VerticalScale::new(controls.vertical_scale).model_matrix()),
+        };
+
+        let draw_parameters = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            line_width: Some(3.0),
+            ..Default::default()
+        };
+
+        scene.execute(target, &uni, Some(draw_parameters));
+    }
+}
+
+// None when there is no route to draw
+fn create_path_scene(display: &Display, polyline: &[Position]) -> Option<PathScene> {
+    if polyline.len() < 2 {
+        return None;
+    }
+
+    let vertices = polyline
+        .iter()
+        .map(|point| Vertex {
+            position: [point.x as f32, point.y as f32, point.z as f32],
+            color: PATH_COLOR,
+            normal: [0.0, 1.0, 0.0],
+        })
+        .collect::<Vec<_>>();
+    let vertex_buffer = glium::VertexBuffer::new(display, &vertices).unwrap();
+
+    let indices = (0..vertices.len() as u32).collect::<Vec<_>>();
+    let index_buffer =
+        glium::IndexBuffer::new(display, glium::index::PrimitiveType::LineStrip, &indices).unwrap();
+
+    let fragment = RenderFragmentBuilder::new()
+        .set_geometry(vertex_buffer, index_buffer)
+        .set_vertex_shader(PATH_VS)
+        .set_fragment_shader(PATH_FS)
+        .build(display)
+        .unwrap();
+
+    Some(RenderPass::new(fragment))
+}
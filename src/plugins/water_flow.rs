@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use cgmath::Vector3;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::{uniform, Display, IndexBuffer};
+
+use crate::camera::Camera;
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::infrastructure::event_bus::AppEvent;
+use crate::infrastructure::render_fragment::RenderFragmentBuilder;
+use crate::infrastructure::render_plugin::{PluginContext, RenderPlugin};
+use crate::infrastructure::vertex::Vertex;
+use crate::infrastructure::vertical_scale::VerticalScale;
+use crate::infrastructure::{RenderState, RenderingMode};
+use crate::model::discrete::World;
+use crate::model::pathfinding::Column;
+use crate::model::polygonize::PolygonizationOptions;
+use crate::model::raycast::raycast_surface;
+use crate::model::water_flow::FlowMap;
+use crate::model::{Position, Real};
+use crate::scene::{NoInstance, RenderPass};
+use crate::to_uniform_matrix;
+
+const FLOW_VS: &str = include_str!("../shaders/grid_vs.glsl");
+const STREAM_FS: &str = include_str!("../shaders/grid_fs.glsl");
+const WATERSHED_FS: &str = include_str!("../shaders/heatmap_fs.glsl");
+
+const WATERSHED_OPACITY: f32 = 0.35;
+// The watershed tiles float this far above the top of their block
+const TILE_ELEVATION: f32 = 0.3;
+
+// The smooth surface under a stream is looked for this far above and below the top of its block
+const STREAM_PROBE_HEIGHT: Real = 3.0;
+const STREAM_ELEVATION: Real = 0.1;
+
+type FlowScene = RenderPass<'static, NoInstance, Vertex, IndexBuffer<u32>>;
+
+// Streams and watersheds of the D8 flow over the heightmap, see model::water_flow.
+// Streams are draped on the smooth surface, watersheds are tinted tiles above the blocks.
+pub struct WaterFlowPlugin {
+    flow: Option<FlowMap>,
+    outdated: bool,
+    options: Option<PolygonizationOptions>,
+    stream_threshold: u32,
+
+    streams: Option<FlowScene>,
+    watersheds: Option<FlowScene>,
+}
+
+impl WaterFlowPlugin {
+    pub fn new() -> Self {
+        WaterFlowPlugin {
+            flow: None,
+            outdated: true,
+            options: None,
+            stream_threshold: 0,
+            streams: None,
+            watersheds: None,
+        }
+    }
+}
+
+impl RenderPlugin for WaterFlowPlugin {
+    fn name(&self) -> &'static str {
+        "water flow"
+    }
+
+    fn update(&mut self, display: &Display, context: &PluginContext) {
+        let mut options_changed = false;
+        for event in context.events {
+            match event {
+                AppEvent::ChunkLoaded(_) | AppEvent::BlockEdited(_, _) => self.outdated = true,
+                AppEvent::OptionsChanged(options) => {
+                    self.options = Some(*options);
+                    options_changed = true;
+                }
+                _ => {}
+            }
+        }
+
+        // The flow is only computed while it is shown
+        let controls = context.controls;
+        if !controls.show_streams && !controls.show_watersheds {
+            return;
+        }
+
+        // Until the options are applied for the first time they match the controls
+        let options = *self
+            .options
+            .get_or_insert_with(|| PolygonizationOptions::from(*controls));
+
+        let recomputed = self.outdated;
+        if recomputed {
+            self.flow = Some(FlowMap::compute(context.world));
+            self.outdated = false;
+        }
+        let Some(flow) = &self.flow else {
+            return;
+        };
+
+        let threshold_changed = controls.stream_threshold != self.stream_threshold;
+        if recomputed || options_changed || threshold_changed {
+            self.streams = create_stream_scene(
+                context.world,
+                display,
+                flow,
+                controls.stream_threshold,
+                options,
+            );
+            self.stream_threshold = controls.stream_threshold;
+        }
+        if recomputed {
+            self.watersheds = create_watershed_scene(display, flow);
+        }
+    }
+
+    fn render(
+        &self,
+        target: &mut SimpleFrameBuffer,
+        camera: &Camera,
+        state: &RenderState,
+        controls: &SmoothMeshOptions,
+    ) {
+        let comparison = matches!(state.settings.render_mode, RenderingMode::Comparison);
+        if comparison {
+            return;
+        }
+
+        let uni = uniform! {
+            projection: to_uniform_matrix(&camera.projection),
+            view: to_uniform_matrix(&camera.world_to_view),
+            model: to_uniform_matrix(&VerticalScale::new(controls.vertical_scale).model_matrix()),
+            opacity: WATERSHED_OPACITY,
+        };
+
+        if let (true, Some(watersheds)) = (controls.show_watersheds, &self.watersheds) {
+            let draw_parameters = glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::DepthTest::IfLess,
+                    write: false,
+                    ..Default::default()
+                },
+                blend: glium::Blend::alpha_blending(),
+                ..Default::default()
+            };
+            watersheds.execute(target, &uni, Some(draw_parameters));
+        }
+
+        if let (true, Some(streams)) = (controls.show_streams, &self.streams) {
+            let draw_parameters = glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::DepthTest::IfLessOrEqual,
+                    write: false,
+                    ..Default::default()
+                },
+                line_width: Some(3.0),
+                ..Default::default()
+            };
+            streams.execute(target, &uni, Some(draw_parameters));
+        }
+    }
+}
+
+// A line from every stream column to the column it drains into, None if there are no streams
+fn create_stream_scene(
+    world: &World,
+    display: &Display,
+    flow: &FlowMap,
+    threshold: u32,
+    options: PolygonizationOptions,
+) -> Option<FlowScene> {
+    let streams = flow
+        .cells()
+        .filter(|cell| cell.accumulation >= threshold)
+        .collect::<Vec<_>>();
+
+    let chunks = world.get_chunks();
+    let down = Vector3::new(0.0, -1.0, 0.0);
+    let surface = streams
+        .iter()
+        .map(|cell| {
+            let center = cell.column.center();
+            let top = cell.height as Real + 1.0;
+            let origin = Position::new(center.x, top + STREAM_PROBE_HEIGHT, center.y);
+            let height = raycast_surface(&chunks, origin, down, 2.0 * STREAM_PROBE_HEIGHT, options)
+                .map_or(top, |hit| hit.position.y);
+
+            (cell.column, height + STREAM_ELEVATION)
+        })
+        .collect::<HashMap<Column, Real>>();
+
+    // Log scale, so the small streams are still visible next to the large rivers
+    let max_accumulation = (flow.max_accumulation() as f32).ln().max(1.0);
+    let vertices = streams
+        .iter()
+        .filter_map(|cell| {
+            let downstream = cell.downstream?;
+            let strength = (cell.accumulation as f32).ln() / max_accumulation;
+            let color = [0.5 - 0.4 * strength, 0.8 - 0.4 * strength, 1.0];
+
+            Some([cell.column, downstream].map(|column| {
+                let center = column.center();
+                Vertex {
+                    position: [center.x as f32, surface[&column] as f32, center.y as f32],
+                    color,
+                    normal: [0.0, 1.0, 0.0],
+                }
+            }))
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    if vertices.is_empty() {
+        return None;
+    }
+    let vertex_buffer = glium::VertexBuffer::new(display, &vertices).unwrap();
+
+    let indices = (0..vertices.len() as u32).collect::<Vec<_>>();
+    let index_buffer =
+        glium::IndexBuffer::new(display, glium::index::PrimitiveType::LinesList, &indices).unwrap();
+
+    let fragment = RenderFragmentBuilder::new()
+        .set_geometry(vertex_buffer, index_buffer)
+        .set_vertex_shader(FLOW_VS)
+        .set_fragment_shader(STREAM_FS)
+        .build(display)
+        .unwrap();
+
+    Some(RenderPass::new(fragment))
+}
+
+// A tile on top of every column, colored by the watershed it belongs to
+fn create_watershed_scene(display: &Display, flow: &FlowMap) -> Option<FlowScene> {
+    let vertices = flow
+        .cells()
+        .flat_map(|cell| {
+            let color = basin_color(cell.basin);
+            let (x, z) = (cell.column.x as f32, cell.column.z as f32);
+            let y = cell.height as f32 + 1.0 + TILE_ELEVATION;
+
+            [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].map(|(dx, dz)| Vertex {
+                position: [x + dx, y, z + dz],
+                color,
+                normal: [0.0, 1.0, 0.0],
+            })
+        })
+        .collect::<Vec<_>>();
+    if vertices.is_empty() {
+        return None;
+    }
+    let vertex_buffer = glium::VertexBuffer::new(display, &vertices).unwrap();
+
+    let indices = (0..vertices.len() as u32 / 4)
+        .flat_map(|tile| [0, 1, 2, 0, 2, 3].map(|corner| tile * 4 + corner))
+        .collect::<Vec<_>>();
+    let index_buffer = glium::IndexBuffer::new(
+        display,
+        glium::index::PrimitiveType::TrianglesList,
+        &indices,
+    )
+    .unwrap();
+
+    let fragment = RenderFragmentBuilder::new()
+        .set_geometry(vertex_buffer, index_buffer)
+        .set_vertex_shader(FLOW_VS)
+        .set_fragment_shader(WATERSHED_FS)
+        .build(display)
+        .unwrap();
+
+    Some(RenderPass::new(fragment))
+}
+
+// Basins with consecutive indices get hues far apart on the color wheel
+fn basin_color(basin: usize) -> [f32; 3] {
+    const GOLDEN_RATIO_CONJUGATE: f32 = 0.618_034;
+    let hue = (basin as f32 * GOLDEN_RATIO_CONJUGATE).fract() * 6.0;
+
+    let rising = hue.fract();
+    let falling = 1.0 - rising;
+    match hue as usize {
+        0 => [1.0, rising, 0.0],
+        1 => [falling, 1.0, 0.0],
+        2 => [0.0, 1.0, rising],
+        3 => [0.0, falling, 1.0],
+        4 => [rising, 0.0, 1.0],
+        _ => [1.0, 0.0, falling],
+    }
+}
@@ -0,0 +1,35 @@
+use crate::model::discrete::World;
+use crate::model::elevation_profile::ElevationProfile;
+use crate::model::PlanarPosition;
+
+// Elevation profile along a line marked in the 3D view.
+// The first marked point starts a new line, the second one ends it and samples the profile.
+pub struct ProfileTool {
+    pub start: Option<PlanarPosition>,
+    pub profile: Option<ElevationProfile>,
+
+    // Requests from the UI, handled before the next frame
+    pub export_requested: bool,
+}
+
+impl ProfileTool {
+    pub fn new() -> Self {
+        ProfileTool {
+            start: None,
+            profile: None,
+            export_requested: false,
+        }
+    }
+
+    pub fn mark(&mut self, world: &World, point: PlanarPosition) {
+        match self.start.take() {
+            Some(start) => self.profile = Some(ElevationProfile::sample(world, start, point)),
+            None => self.start = Some(point),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.start = None;
+        self.profile = None;
+    }
+}
@@ -1,4 +1,6 @@
-use glium::{index::IndicesSource, uniforms::Uniforms, DrawParameters, Frame, VertexBuffer};
+use std::ops::Range;
+
+use glium::{index::IndicesSource, uniforms::Uniforms, DrawParameters, Surface, VertexBuffer};
 
 use crate::infrastructure::render_fragment::RenderFragment;
 
@@ -17,6 +19,8 @@ where
 {
     pub fragment: RenderFragment<'a, T, I>,
     pub instance_data: Option<VertexBuffer<D>>,
+    // Only these instances are drawn when set, the buffer stays as it is
+    pub instance_ranges: Option<Vec<Range<usize>>>,
 }
 
 impl<'a, D, T, I> RenderPass<'a, D, T, I>
@@ -33,6 +37,7 @@ where
         RenderPass {
             fragment,
             instance_data: Some(instance_data),
+            instance_ranges: None,
         }
     }
 
@@ -40,23 +45,78 @@ where
         self.instance_data = Some(instance_data);
     }
 
-    pub fn execute<U>(
+    pub fn execute<U, S>(
         &'a self,
-        target: &mut Frame,
+        target: &mut S,
         uniforms: &U,
         draw_parameters: Option<DrawParameters>,
     ) where
         U: Uniforms,
+        S: Surface,
     {
-        if let Some(instance_data) = &self.instance_data {
+        match (&self.instance_data, &self.instance_ranges) {
+            (Some(instance_data), None) => {
+                let instances = instance_data.per_instance().unwrap();
+                self.fragment
+                    .render_instanced(target, uniforms, instances, draw_parameters);
+            }
+            (Some(_), Some(ranges)) => {
+                self.execute_ranges(target, uniforms, draw_parameters, ranges)
+            }
+            (None, _) => self.fragment.render(target, uniforms, draw_parameters),
+        }
+    }
+
+    // Draws the instances in *ranges* one range after another, in the given order
+    pub fn execute_ranges<U, S>(
+        &'a self,
+        target: &mut S,
+        uniforms: &U,
+        draw_parameters: Option<DrawParameters>,
+        ranges: &[Range<usize>],
+    ) where
+        U: Uniforms,
+        S: Surface,
+    {
+        let Some(instance_data) = &self.instance_data else {
+            return;
+        };
+
+        for range in ranges {
+            let Some(slice) = instance_data.slice(range.clone()) else {
+                continue;
+            };
+            let instances = slice.per_instance().unwrap();
             self.fragment
-                .render_instanced(target, uniforms, instance_data, draw_parameters);
-        } else {
-            self.fragment.render(target, uniforms, draw_parameters);
+                .render_instanced(target, uniforms, instances, draw_parameters.clone());
         }
     }
 }
 
+// A scene drawn with the uniforms of the world, render passes or the chunk meshes
+pub trait WorldScene {
+    fn draw<U, S>(&self, target: &mut S, uniforms: &U, draw_parameters: DrawParameters)
+    where
+        U: Uniforms,
+        S: Surface;
+}
+
+impl<'a, D, T, I> WorldScene for &'a RenderPass<'a, D, T, I>
+where
+    D: Copy,
+    T: Copy,
+    I: 'a,
+    IndicesSource<'a>: From<&'a I>,
+{
+    fn draw<U, S>(&self, target: &mut S, uniforms: &U, draw_parameters: DrawParameters)
+    where
+        U: Uniforms,
+        S: Surface,
+    {
+        self.execute(target, uniforms, Some(draw_parameters));
+    }
+}
+
 // Dummy type used as D type when no instancing is required
 #[derive(Clone, Copy)]
 pub struct NoInstance {}
@@ -71,6 +131,7 @@ where
         RenderPass {
             fragment,
             instance_data: None,
+            instance_ranges: None,
         }
     }
 }
@@ -0,0 +1,452 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map};
+
+use crate::camera::Camera;
+use crate::config;
+use crate::imgui_wrapper::SmoothMeshOptions;
+use crate::infrastructure::vertical_scale::VerticalScale;
+use crate::model::common::BlockType;
+use crate::model::discrete::{get_block_in, World, WorldChunks};
+use crate::model::export::MeshFormat;
+use crate::model::material_registry;
+use crate::model::polygonize::{PolygonizationMethod, PolygonizationOptions};
+use crate::model::raycast::{raycast_blocks, raycast_surface, Hit};
+use crate::model::{Position, Real};
+use crate::world_dialog::WorldDialog;
+
+// Only the most recent lines of script output are kept
+const CONSOLE_LINES: usize = 200;
+
+// Names accepted by set_option, the same settings as in the controls window
+const SCRIPT_OPTIONS: &[&str] = &[
+    "surface_nets",
+    "smoothness",
+    "mesh_detail",
+    "kernel_size",
+    "cell_size",
+    "y_low",
+    "y_size",
+    "surface_level",
+    "density_bias",
+    "density_gain",
+    "distance_lod",
+    "skirts",
+    "caps",
+    "octree_density",
+    "density_cache",
+    "ambient_occlusion",
+    "vertical_scale",
+    "load_radius",
+];
+
+const EXAMPLE_SCRIPT: &str = r#"// get_block(pos), set_block(pos, block), raycast(origin, dir, max_distance),
+// raycast_smooth(origin, dir, max_distance), camera_position(), camera_direction(),
+// teleport(pos), teleport(pos, dir), set_option(name, value), export_mesh(path), open_world(path)
+let hit = raycast(camera_position(), camera_direction(), 64.0);
+if type_of(hit) == "map" {
+    print(`${hit.block} at ${hit.position}`);
+}
+"#;
+
+// Changes requested by a script.
+// They are applied after the script finishes, so queries made by the script
+// still see the world as it was when the script started.
+pub enum ScriptCommand {
+    SetBlock(Position, BlockType),
+    Teleport(Position, Option<Vector3<Real>>),
+    SetOption(String, Real),
+    ExportMesh(String),
+    OpenWorld(String),
+}
+
+// State of the world the script functions work with while a script runs
+struct ScriptSnapshot {
+    chunks: WorldChunks,
+    camera_position: Position,
+    camera_direction: Vector3<Real>,
+    options: PolygonizationOptions,
+}
+
+#[derive(Default)]
+struct ScriptContext {
+    snapshot: Option<ScriptSnapshot>,
+    commands: Vec<ScriptCommand>,
+    output: Vec<String>,
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// Source and output of the script console window
+pub struct ScriptConsole {
+    pub source: String,
+    pub output: Vec<String>,
+    pub run_requested: bool,
+}
+
+impl ScriptConsole {
+    pub fn new() -> Self {
+        ScriptConsole {
+            source: EXAMPLE_SCRIPT.to_owned(),
+            output: Vec::new(),
+            run_requested: false,
+        }
+    }
+
+    pub fn log(&mut self, line: String) {
+        self.output.push(line);
+
+        let excess = self.output.len().saturating_sub(CONSOLE_LINES);
+        self.output.drain(..excess);
+    }
+}
+
+// Runs rhai scripts against the loaded world
+pub struct ScriptHost {
+    engine: Engine,
+    context: Rc<RefCell<ScriptContext>>,
+}
+
+impl ScriptHost {
+    pub fn new() -> Self {
+        let context = Rc::new(RefCell::new(ScriptContext::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(config::SCRIPT_MAX_OPERATIONS);
+
+        let print_context = context.clone();
+        engine.on_print(move |text| print_context.borrow_mut().output.push(text.to_owned()));
+
+        let ctx = context.clone();
+        engine.register_fn(
+            "get_block",
+            move |position: Array| -> ScriptResult<String> {
+                let position = to_position(&position)?;
+                let block =
+                    with_snapshot(&ctx, |snapshot| get_block_in(&snapshot.chunks, position));
+                Ok(format!("{:?}", block))
+            },
+        );
+
+        let ctx = context.clone();
+        engine.register_fn(
+            "raycast",
+            move |origin: Array,
+                  direction: Array,
+                  max_distance: Dynamic|
+                  -> ScriptResult<Dynamic> {
+                let origin = to_position(&origin)?;
+                let direction = to_vector(&direction)?;
+                let max_distance = to_real(&max_distance)?;
+
+                let hit = with_snapshot(&ctx, |snapshot| {
+                    raycast_blocks(&snapshot.chunks, origin, direction, max_distance)
+                });
+                Ok(hit.map_or(Dynamic::UNIT, hit_to_map))
+            },
+        );
+
+        let ctx = context.clone();
+        engine.register_fn(
+            "raycast_smooth",
+            move |origin: Array,
+                  direction: Array,
+                  max_distance: Dynamic|
+                  -> ScriptResult<Dynamic> {
+                let origin = to_position(&origin)?;
+                let direction = to_vector(&direction)?;
+                let max_distance = to_real(&max_distance)?;
+
+                let hit = with_snapshot(&ctx, |snapshot| {
+                    raycast_surface(
+                        &snapshot.chunks,
+                        origin,
+                        direction,
+                        max_distance,
+                        snapshot.options,
+                    )
+                });
+                Ok(hit.map_or(Dynamic::UNIT, hit_to_map))
+            },
+        );
+
+        let ctx = context.clone();
+        engine.register_fn("camera_position", move || -> Array {
+            with_snapshot(&ctx, |snapshot| to_array(snapshot.camera_position.to_vec()))
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("camera_direction", move || -> Array {
+            with_snapshot(&ctx, |snapshot| to_array(snapshot.camera_direction))
+        });
+
+        let ctx = context.clone();
+        engine.register_fn(
+            "set_block",
+            move |position: Array, block: &str| -> ScriptResult<()> {
+                let position = to_position(&position)?;
+                let Some(block) = block_type_from_name(block) else {
+                    return Err(format!("unknown block '{block}'").into());
+                };
+
+                push_command(&ctx, ScriptCommand::SetBlock(position, block));
+                Ok(())
+            },
+        );
+
+        let ctx = context.clone();
+        engine.register_fn("teleport", move |position: Array| -> ScriptResult<()> {
+            let position = to_position(&position)?;
+            push_command(&ctx, ScriptCommand::Teleport(position, None));
+            Ok(())
+        });
+
+        let ctx = context.clone();
+        engine.register_fn(
+            "teleport",
+            move |position: Array, direction: Array| -> ScriptResult<()> {
+                let position = to_position(&position)?;
+                let direction = to_vector(&direction)?;
+                if direction.magnitude2() == 0.0 {
+                    return Err("the camera direction can not be zero".into());
+                }
+
+                let command = ScriptCommand::Teleport(position, Some(direction.normalize()));
+                push_command(&ctx, command);
+                Ok(())
+            },
+        );
+
+        let ctx = context.clone();
+        engine.register_fn(
+            "set_option",
+            move |name: &str, value: Dynamic| -> ScriptResult<()> {
+                if !SCRIPT_OPTIONS.contains(&name) {
+                    let options = SCRIPT_OPTIONS.join(", ");
+                    return Err(
+                        format!("unknown option '{name}', expected one of {options}").into(),
+                    );
+                }
+
+                let value = match value.as_bool() {
+                    Ok(flag) => flag as u8 as Real,
+                    Err(_) => to_real(&value)?,
+                };
+                push_command(&ctx, ScriptCommand::SetOption(name.to_owned(), value));
+                Ok(())
+            },
+        );
+
+        // Applies to the chunks loaded afterwards, like the material legend
+        engine.register_fn(
+            "set_namespace_fallback",
+            |namespace: &str, block: &str| -> ScriptResult<()> {
+                let Some(block) = block_type_from_name(block) else {
+                    return Err(format!("unknown block '{block}'").into());
+                };
+
+                material_registry::set_namespace_fallback(namespace, block);
+                Ok(())
+            },
+        );
+
+        let ctx = context.clone();
+        engine.register_fn("export_mesh", move |path: &str| {
+            push_command(&ctx, ScriptCommand::ExportMesh(path.to_owned()));
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("open_world", move |path: &str| {
+            push_command(&ctx, ScriptCommand::OpenWorld(path.to_owned()));
+        });
+
+        ScriptHost { engine, context }
+    }
+
+    // Runs *source* and returns the changes it requested.
+    // Printed text and errors go to the console, a failed script requests no changes.
+    // Scripts see the camera in the world, not in the vertically scaled scene.
+    pub fn run(
+        &mut self,
+        source: &str,
+        world: &World,
+        camera: &Camera,
+        vertical_scale: VerticalScale,
+        options: PolygonizationOptions,
+        console: &mut ScriptConsole,
+    ) -> Vec<ScriptCommand> {
+        self.context.borrow_mut().snapshot = Some(ScriptSnapshot {
+            chunks: world.get_chunks(),
+            camera_position: vertical_scale.to_world(camera.get_position()),
+            camera_direction: vertical_scale.direction_to_world(camera.get_direction()),
+            options,
+        });
+
+        let result = self.engine.run(source);
+
+        let context = std::mem::take(&mut *self.context.borrow_mut());
+        for line in context.output {
+            console.log(line);
+        }
+
+        match result {
+            Ok(()) => context.commands,
+            Err(error) => {
+                console.log(format!("[ERROR] {error}"));
+                Vec::new()
+            }
+        }
+    }
+}
+
+pub fn apply_commands(
+    commands: Vec<ScriptCommand>,
+    world: &mut World,
+    camera: &mut Camera,
+    vertical_scale: VerticalScale,
+    controls: &mut SmoothMeshOptions,
+    options: PolygonizationOptions,
+    world_dialog: &mut WorldDialog,
+    console: &mut ScriptConsole,
+) {
+    let mut blocks = Vec::new();
+
+    for command in commands {
+        match command {
+            ScriptCommand::SetBlock(position, block) => blocks.push((position, block)),
+            ScriptCommand::Teleport(position, direction) => {
+                let direction = match direction {
+                    Some(direction) => vertical_scale.direction_to_scene(direction),
+                    None => camera.get_direction(),
+                };
+                camera.look_from(vertical_scale.to_scene(position), direction);
+            }
+            ScriptCommand::SetOption(name, value) => set_option(controls, &name, value),
+            ScriptCommand::ExportMesh(path) => {
+                match world.export_smooth_mesh(&path, MeshFormat::from_path(&path)) {
+                    Ok(()) => console.log(format!("Smooth mesh exported to {path}")),
+                    Err(error) => console.log(format!("[ERROR] Failed to export mesh: {error}")),
+                }
+            }
+            // The world is switched through the world dialog, which asks for the start location
+            ScriptCommand::OpenWorld(path) => world_dialog.chosen_world = Some(path),
+        }
+    }
+
+    if !blocks.is_empty() {
+        let changed = world.set_blocks(&blocks, options);
+        console.log(format!("Changed {changed} of {} blocks", blocks.len()));
+    }
+}
+
+// The options take effect the same way as pressing APPLY in the controls window
+fn set_option(controls: &mut SmoothMeshOptions, name: &str, value: Real) {
+    match name {
+        "surface_nets" => {
+            controls.polygonization_method = match value != 0.0 {
+                true => PolygonizationMethod::SurfaceNets,
+                false => PolygonizationMethod::MarchingCubes,
+            }
+        }
+        "smoothness" => controls.set_smoothness_level(value as u8),
+        "mesh_detail" => controls.set_mesh_detail_level(value as u8),
+        "kernel_size" => controls.kernel_size = value as f32,
+        "cell_size" => controls.cell_size = value as f32,
+        "y_low" => controls.y_low_limit = value as isize,
+        "y_size" => controls.y_size = value as usize,
+        "surface_level" => controls.surface_level = value as f32,
+        "density_bias" => controls.density_bias = value as f32,
+        "density_gain" => controls.density_gain = value as f32,
+        "distance_lod" => controls.distance_lod = value != 0.0,
+        "skirts" => controls.skirts = value != 0.0,
+        "caps" => controls.caps = value != 0.0,
+        "octree_density" => controls.octree_density = value != 0.0,
+        "density_cache" => controls.density_cache = value != 0.0,
+        "ambient_occlusion" => controls.ambient_occlusion = value != 0.0,
+        // Only changes how the world is drawn, the meshes are kept
+        "vertical_scale" => {
+            controls.vertical_scale = (value as f32).clamp(0.5, 5.0);
+            return;
+        }
+        // The loaded window is resized on its own
+        "load_radius" => {
+            let radius = value.max(0.0) as usize;
+            controls.load_radius =
+                radius.clamp(config::MIN_CHUNK_LOAD_RADIUS, config::MAX_CHUNK_LOAD_RADIUS);
+            return;
+        }
+        _ => return,
+    }
+
+    controls.apply = true;
+}
+
+fn with_snapshot<T>(
+    context: &Rc<RefCell<ScriptContext>>,
+    f: impl FnOnce(&ScriptSnapshot) -> T,
+) -> T {
+    let context = context.borrow();
+    let snapshot = context
+        .snapshot
+        .as_ref()
+        .expect("script functions are only called while a script runs");
+
+    f(snapshot)
+}
+
+fn push_command(context: &Rc<RefCell<ScriptContext>>, command: ScriptCommand) {
+    context.borrow_mut().commands.push(command);
+}
+
+// Block names are the names of the block types, case does not matter
+fn block_type_from_name(name: &str) -> Option<BlockType> {
+    (0..=BlockType::Unknown as usize)
+        .filter_map(|value| BlockType::try_from(value).ok())
+        .find(|block| format!("{:?}", block).eq_ignore_ascii_case(name))
+}
+
+fn hit_to_map(hit: Hit) -> Dynamic {
+    let (chunk_x, chunk_z) = hit.chunk.get_global_position_in_chunks();
+
+    let mut map = Map::new();
+    map.insert("position".into(), to_array(hit.position.to_vec()).into());
+    map.insert("normal".into(), to_array(hit.normal).into());
+    map.insert("block".into(), format!("{:?}", hit.block).into());
+    let block_position = hit
+        .block_position
+        .map(|coordinate| Dynamic::from(coordinate as i64));
+    map.insert(
+        "block_position".into(),
+        vec![block_position.x, block_position.y, block_position.z].into(),
+    );
+    map.insert(
+        "chunk".into(),
+        vec![Dynamic::from(chunk_x as i64), Dynamic::from(chunk_z as i64)].into(),
+    );
+
+    map.into()
+}
+
+fn to_real(value: &Dynamic) -> ScriptResult<Real> {
+    value
+        .as_float()
+        .or_else(|_| value.as_int().map(|value| value as Real))
+        .map_err(|type_name| format!("expected a number, got {type_name}").into())
+}
+
+fn to_vector(values: &Array) -> ScriptResult<Vector3<Real>> {
+    let [x, y, z] = values.as_slice() else {
+        return Err(format!("expected 3 coordinates, got {}", values.len()).into());
+    };
+
+    Ok(Vector3::new(to_real(x)?, to_real(y)?, to_real(z)?))
+}
+
+fn to_position(values: &Array) -> ScriptResult<Position> {
+    to_vector(values).map(Point3::from_vec)
+}
+
+fn to_array(vector: Vector3<Real>) -> Array {
+    vec![vector.x.into(), vector.y.into(), vector.z.into()]
+}
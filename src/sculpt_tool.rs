@@ -0,0 +1,55 @@
+use cgmath::MetricSpace;
+use itertools::iproduct;
+
+use crate::config;
+use crate::model::common::{is_visible_block, BlockType};
+use crate::model::discrete::World;
+use crate::model::raycast::Hit;
+use crate::model::{Position, Real};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrushMode {
+    // Fills the air in the brush with the material under the hit point
+    Add,
+    // Replaces every block in the brush with air
+    Carve,
+}
+
+// Blocks changed by a stroke of a spherical brush of *radius* around the smooth surface point of *hit*,
+// a block is in the brush when its center is. The density is sampled from the blocks,
+// so the smooth surface follows the stroke once the meshes of the changed chunks are rebuilt.
+pub fn brush_stroke(
+    world: &World,
+    hit: &Hit,
+    radius: Real,
+    mode: BrushMode,
+) -> Vec<(Position, BlockType)> {
+    let replacement = match mode {
+        BrushMode::Add if is_visible_block(hit.block) => hit.block,
+        BrushMode::Add => config::SCULPT_FALLBACK_BLOCK,
+        BrushMode::Carve => BlockType::Air,
+    };
+
+    let center = hit.position;
+    let reach = radius.ceil() as i32;
+    let [center_x, center_y, center_z] = [center.x, center.y, center.z].map(|c| c.floor() as i32);
+
+    iproduct!(-reach..=reach, -reach..=reach, -reach..=reach)
+        .map(|(x, y, z)| {
+            Position::new(
+                (center_x + x) as Real + 0.5,
+                (center_y + y) as Real + 0.5,
+                (center_z + z) as Real + 0.5,
+            )
+        })
+        .filter(|block_center| block_center.distance(center) <= radius)
+        .filter(|block_center| {
+            let is_air = !is_visible_block(world.get_block(*block_center));
+            match mode {
+                BrushMode::Add => is_air,
+                BrushMode::Carve => !is_air,
+            }
+        })
+        .map(|block_center| (block_center, replacement))
+        .collect()
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use cgmath::Vector3;
+use serde::Deserialize;
+
+use crate::config;
+use crate::model::{Coord, Position, Real};
+
+// How often the settings file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Parameters that can be changed while the app runs, read from config::SETTINGS_FILE.
+//
+// Options missing from the file keep the defaults from config.rs. The file is watched
+// and changes are applied live, see SettingsFile. The size of the loaded world is set
+// from the controls, see SmoothMeshOptions::load_radius.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // Smooth the leaves in a separate pass from the terrain
+    pub multipass: bool,
+    // Smooth the leaves with a fixed kernel instead of the one from the controls
+    pub lock_leaves: bool,
+    // Draw the blocks surrounded by air as rigid blocks instead of smoothing them
+    pub filter_rigid: bool,
+    // Build the density octree of newly loaded chunks
+    pub build_octree: bool,
+    // Caps the chunks meshed around the chunk of the camera, otherwise all inner chunks
+    // of the loaded window are meshed
+    pub mesh_radius: Option<usize>,
+    // Threads of the world scan and the map export, applies to the next one started
+    pub scan_workers: usize,
+    pub camera_move_speed: Real,
+    // Radians per second the camera turns while the mouse moves
+    pub sensitivity: Real,
+    pub spawn_point: [Coord; 3],
+    pub spawn_dir: [Coord; 3],
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            multipass: config::MULTIPASS,
+            lock_leaves: config::LOCK_LEAVES,
+            filter_rigid: config::FILTER_RIGID,
+            build_octree: config::BUILD_OCTREE,
+            mesh_radius: config::MESH_RADIUS,
+            scan_workers: config::SCAN_WORKERS,
+            camera_move_speed: config::CAMERA_MOVE_SPEED,
+            sensitivity: config::SENSITIVITY,
+            spawn_point: config::SPAWN_POINT.into(),
+            spawn_dir: config::SPAWN_DIR.into(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn spawn_point(&self) -> Position {
+        Position::from(self.spawn_point)
+    }
+
+    pub fn spawn_dir(&self) -> Vector3<Coord> {
+        Vector3::from(self.spawn_dir)
+    }
+
+    // The meshes have to be rebuilt for these to show
+    pub fn changes_meshes(&self, other: &Settings) -> bool {
+        self.multipass != other.multipass
+            || self.lock_leaves != other.lock_leaves
+            || self.filter_rigid != other.filter_rigid
+            || self.mesh_radius != other.mesh_radius
+    }
+}
+
+// Settings in use, the defaults until a settings file is loaded
+static CURRENT: Mutex<Option<Settings>> = Mutex::new(None);
+
+pub fn current() -> Settings {
+    CURRENT.lock().unwrap().unwrap_or_default()
+}
+
+// Doesn't wait for the settings, None while they are locked, see crash_report
+pub fn try_current() -> Option<Settings> {
+    CURRENT
+        .try_lock()
+        .ok()
+        .map(|current| current.unwrap_or_default())
+}
+
+fn set_current(settings: Settings) {
+    *CURRENT.lock().unwrap() = Some(settings);
+}
+
+// Watches the settings file by its modification time
+pub struct SettingsFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    checked_at: Instant,
+}
+
+impl SettingsFile {
+    pub fn new(path: &str) -> Self {
+        SettingsFile {
+            path: PathBuf::from(path),
+            modified: None,
+            checked_at: Instant::now() - POLL_INTERVAL,
+        }
+    }
+
+    // Loads the file when it changed since the last poll and makes it the current settings.
+    // Returns the previous and the new settings, or the reason the file was rejected.
+    // A rejected file leaves the current settings as they are.
+    pub fn poll(&mut self) -> Option<Result<(Settings, Settings), String>> {
+        if self.checked_at.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.checked_at = Instant::now();
+
+        // Without the file the defaults are used
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified());
+        let modified = modified.ok();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        let loaded = match modified {
+            Some(_) => self.load(),
+            None => Ok(Settings::default()),
+        };
+        Some(loaded.map(|settings| {
+            let previous = current();
+            set_current(settings);
+            (previous, settings)
+        }))
+    }
+
+    fn load(&self) -> Result<Settings, String> {
+        let text = fs::read_to_string(&self.path)
+            .map_err(|error| format!("Failed to read {} - {error}", self.path.display()))?;
+
+        toml::from_str(&text)
+            .map_err(|error| format!("Invalid settings in {} - {error}", self.path.display()))
+    }
+}
@@ -0,0 +1,43 @@
+#version 400
+
+in vec2 texture_coordinates;
+
+uniform sampler2D discrete_image;
+uniform sampler2D implicit_image;
+
+// Horizontal position of the split between the two scenes, in <0, 1>
+uniform float swipe;
+uniform bool show_difference;
+
+out vec4 fragment_color;
+
+vec3 swipe_line_color = vec3(1.0, 0.8, 0.0);
+vec3 difference_color = vec3(1.0, 0.0, 0.0);
+
+const float DIFFERENCE_GAIN = 4.0;
+const float SWIPE_LINE_PIXELS = 1.5;
+
+void main() {
+    vec3 discrete = texture(discrete_image, texture_coordinates).rgb;
+    vec3 implicit = texture(implicit_image, texture_coordinates).rgb;
+
+    if (show_difference) {
+        // Darkened discrete scene for context, changed pixels are highlighted on top
+        vec3 difference = abs(discrete - implicit);
+        float amount = clamp(DIFFERENCE_GAIN * max(max(difference.r, difference.g), difference.b), 0.0, 1.0);
+        float luminance = dot(discrete, vec3(0.2126, 0.7152, 0.0722));
+
+        fragment_color = vec4(mix(vec3(0.3 * luminance), difference_color, amount), 1.0);
+        return;
+    }
+
+    vec3 color = texture_coordinates.x < swipe ? discrete : implicit;
+
+    float line_width = SWIPE_LINE_PIXELS * fwidth(texture_coordinates.x);
+    if (abs(texture_coordinates.x - swipe) < line_width) {
+        color = swipe_line_color;
+    }
+
+    // The output is gamma corrected automatically - GL_FRAMEBUFFER_SRGB
+    fragment_color = vec4(color, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 400
+
+in vec2 position;
+
+out vec2 texture_coordinates;
+
+void main() {
+    texture_coordinates = 0.5 * position + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
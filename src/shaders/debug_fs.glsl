@@ -0,0 +1,18 @@
+#version 400
+
+in vec3 v_normal;
+in vec4 v_color;
+
+out vec4 fragment_color;
+
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.4, 1.0, 0.3));
+
+void main() {
+    // Lines have no normal and are drawn unshaded
+    float shade = 1.0;
+    if (length(v_normal) > 0.0) {
+        shade = 0.5 + 0.5 * max(dot(normalize(v_normal), LIGHT_DIRECTION), 0.0);
+    }
+
+    fragment_color = vec4(v_color.rgb * shade, v_color.a);
+}
@@ -0,0 +1,21 @@
+#version 400
+
+in vec3 position;
+in vec3 normal;
+
+// Per instance
+in mat4 transform;
+in vec4 tint;
+
+uniform mat4 projection;
+uniform mat4 view;
+uniform mat4 model;
+
+out vec3 v_normal;
+out vec4 v_color;
+
+void main() {
+    v_normal = mat3(transpose(inverse(model))) * mat3(transform) * normal;
+    v_color = tint;
+    gl_Position = projection * view * model * transform * vec4(position, 1.0);
+}
@@ -1,13 +1,40 @@
 #version 400
 
-uniform sampler2D block_pallette;
+// One layer per block type, see infrastructure/texture/block_textures.rs
+uniform sampler2DArray block_textures;
 uniform vec3 sun_position;
+uniform uint light_visualization;
+// See infrastructure/color_ramp.rs
+uniform sampler2D color_ramp;
+
+// Opaque when not set, see infrastructure/translucency.rs
+uniform uint translucency;
+uniform float opacity;
+uniform vec3 camera_position;
+uniform float surface_fog_density;
+uniform float cave_fog_density;
+// Cascaded shadow maps of the sun, see infrastructure/shadow_map.rs
+uniform bool shadows;
+uniform sampler2DShadow shadow_map_0;
+uniform sampler2DShadow shadow_map_1;
+uniform sampler2DShadow shadow_map_2;
+uniform mat4 shadow_matrix_0;
+uniform mat4 shadow_matrix_1;
+uniform mat4 shadow_matrix_2;
+// Distance from the camera each cascade reaches
+uniform vec3 cascade_ends;
 
 in vec2 texture_uv;
+flat in uint v_texture_layer;
 in vec3 v_normal;
 in vec3 fragment_position;
+in vec2 v_light;
+// Already white for blocks that are not tinted, see model/biome.rs
+in vec3 v_biome_tint;
 
-out vec4 color;
+layout(location = 0) out vec4 color;
+// Only bound while accumulating weighted blended transparency
+layout(location = 1) out vec4 oit_weight;
 
 float ambience_strength = 0.1;
 vec3 ambience_color = vec3(0.3, 0.3, 0.4);
@@ -19,18 +46,76 @@ vec3 indirect_color = vec3(0.4, 0.28, 0.20);
 const float SKY_COLOR_STRENGTH = 0.1;
 
 const vec2 TILE_RESOLUTION = vec2(16, 16);
-const vec2 PALLETTE_RESOLUTION = 4 * TILE_RESOLUTION;
 vec2 nearest_pixel_filter(vec2 uv) {
-    vec2 pixel = uv * PALLETTE_RESOLUTION;
+    vec2 pixel = uv * TILE_RESOLUTION;
     pixel = floor(pixel) + 0.5;
 
-    return pixel / PALLETTE_RESOLUTION;
+    return pixel / TILE_RESOLUTION;
+}
+
+// Share of the sun reaching the fragment, filtered over the 3x3 texels around it
+float sample_shadow(sampler2DShadow shadow_map, mat4 shadow_matrix) {
+    vec4 light_position = shadow_matrix * vec4(fragment_position, 1.0);
+    vec3 coords = light_position.xyz / light_position.w * 0.5 + 0.5;
+    if (any(lessThan(coords, vec3(0.0))) || any(greaterThan(coords, vec3(1.0)))) {
+        return 1.0;
+    }
+
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += textureOffset(shadow_map, coords, ivec2(x, y));
+        }
+    }
+    return lit / 9.0;
+}
+
+float sun_visibility() {
+    if (!shadows) {
+        return 1.0;
+    }
+
+    float distance = length(fragment_position - camera_position);
+    if (distance < cascade_ends.x) {
+        return sample_shadow(shadow_map_0, shadow_matrix_0);
+    }
+    if (distance < cascade_ends.y) {
+        return sample_shadow(shadow_map_1, shadow_matrix_1);
+    }
+    if (distance < cascade_ends.z) {
+        return sample_shadow(shadow_map_2, shadow_matrix_2);
+    }
+    return 1.0;
+}
+
+#include "light_visualization.glsl"
+
+// Exponential fog over the distance to the camera. Where neither the sky nor block light reach
+// the cave fog takes over and fades to darkness, the surface fog fades to the background.
+const vec3 SURFACE_FOG_COLOR = vec3(1.0, 1.0, 1.0);
+const vec3 CAVE_FOG_COLOR = vec3(0.0, 0.0, 0.0);
+vec3 apply_fog(vec3 color, float block_light, float sky_visibility) {
+    float distance = length(fragment_position - camera_position);
+    float underground = (1.0 - sky_visibility) * (1.0 - block_light);
+
+    float surface_fog = 1.0 - exp(-surface_fog_density * distance);
+    float cave_fog = 1.0 - exp(-cave_fog_density * distance);
+
+    color = mix(color, SURFACE_FOG_COLOR, surface_fog * (1.0 - underground));
+    return mix(color, CAVE_FOG_COLOR, cave_fog * underground);
+}
+
+const uint TRANSLUCENCY_SORTED = 1u;
+const uint TRANSLUCENCY_WEIGHTED = 2u;
+// Weight of a translucent surface, closer surfaces dominate the ones behind them
+float oit_weight_at(float distance) {
+    return opacity * clamp(10.0 / (1e-5 + pow(distance / 20.0, 3.0)), 1e-2, 3e3);
 }
 
 void main() {
     vec3 sunlight_dir = normalize(sun_position - fragment_position);
     float sun_factor = clamp(dot(v_normal, sunlight_dir), 0.0, 1.0);
-    vec3 sunlight = sun_factor * sun_color;
+    vec3 sunlight = sun_factor * sun_color * sun_visibility();
 
     vec3 skylight_dir = vec3(0.0, 1.0, 0.0); // Light coming directly from above
     float sky_factor = 0.5 + 0.5 * v_normal.y;
@@ -48,8 +133,25 @@ void main() {
 //
 //    vec4 ambience = vec4(ambience_strength * ambience_color, 1.0);
     vec2 tex_coords = nearest_pixel_filter(texture_uv);
-    vec3 texture_color = texture(block_pallette, tex_coords).rgb;
+    vec3 texture_color = texture(block_textures, vec3(tex_coords, v_texture_layer)).rgb;
+    texture_color *= v_biome_tint;
 
     vec3 frag_color = lighting * texture_color;
-    color = vec4(frag_color, 1.0);
+    frag_color = apply_fog(frag_color, v_light.x, v_light.y);
+    frag_color = visualize_light(frag_color, v_light);
+
+    oit_weight = vec4(0.0);
+    switch (translucency) {
+        case TRANSLUCENCY_SORTED:
+            color = vec4(frag_color, opacity);
+            break;
+        case TRANSLUCENCY_WEIGHTED: {
+            float weight = oit_weight_at(length(fragment_position - camera_position));
+            color = vec4(frag_color * weight, opacity);
+            oit_weight = vec4(weight);
+            break;
+        }
+        default:
+            color = vec4(frag_color, 1.0);
+    }
 }
@@ -6,7 +6,9 @@ in vec2 texture_coordinates;
 
 // instance data
 in vec3 offset;
-in vec2 pallette_offset;
+in uint texture_layer;
+in vec2 light;
+in vec3 biome_tint;
 // in vec3 instance_color;
 // in uint height;
 
@@ -17,12 +19,18 @@ uniform mat4 model;
 // out vec3 frag_color;
 out vec3 v_normal;
 out vec2 texture_uv;
+flat out uint v_texture_layer;
 out vec3 fragment_position;
+out vec2 v_light;
+out vec3 v_biome_tint;
 
 void main() {  
     // frag_color = instance_color;
-    v_normal = normal;
-    texture_uv = pallette_offset + texture_coordinates;
+    v_normal = normalize(mat3(transpose(inverse(model))) * normal);
+    v_light = light;
+    v_biome_tint = biome_tint;
+    texture_uv = texture_coordinates;
+    v_texture_layer = texture_layer;
 
     // We start with a block that has 0,0,0 in its center
     // We want 0,0,0 to be one of its corners so we offset x and z by cube_size / 2
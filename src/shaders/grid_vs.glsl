@@ -0,0 +1,15 @@
+#version 400
+
+in vec3 position;
+in vec3 color;
+
+uniform mat4 projection;
+uniform mat4 view;
+uniform mat4 model;
+
+out vec3 v_color;
+
+void main() {
+    v_color = color;
+    gl_Position = projection * view * model * vec4(position, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 400
+
+in vec3 v_color;
+
+uniform float opacity;
+
+out vec4 fragment_color;
+
+void main() {
+    fragment_color = vec4(v_color, opacity);
+}
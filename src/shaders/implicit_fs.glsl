@@ -3,9 +3,34 @@
 in vec3 v_normal;
 in vec3 fragment_position;
 in mat4 blend_weights;
+in vec2 v_light;
+in vec2 v_lightmap_uv;
+in vec3 v_biome_tint;
+in float v_ambient_occlusion;
 
-uniform sampler2D block_pallette;
+// One layer per block type, see infrastructure/texture/block_textures.rs
+uniform sampler2DArray block_textures;
 uniform vec3 sun_position;
+uniform uint light_visualization;
+// See infrastructure/color_ramp.rs
+uniform sampler2D color_ramp;
+uniform bool material_dithering;
+uniform uint dither_seed;
+uniform bool baked_lighting;
+uniform sampler2D lightmap;
+uniform vec3 camera_position;
+uniform float surface_fog_density;
+uniform float cave_fog_density;
+// Cascaded shadow maps of the sun, see infrastructure/shadow_map.rs
+uniform bool shadows;
+uniform sampler2DShadow shadow_map_0;
+uniform sampler2DShadow shadow_map_1;
+uniform sampler2DShadow shadow_map_2;
+uniform mat4 shadow_matrix_0;
+uniform mat4 shadow_matrix_1;
+uniform mat4 shadow_matrix_2;
+// Distance from the camera each cascade reaches
+uniform vec3 cascade_ends;
 
 out vec4 fragment_color;
 
@@ -20,84 +45,45 @@ vec3 sky_color = vec3(0.16, 0.20, 0.28);
 
 vec3 indirect_color = vec3(0.4, 0.28, 0.20);
 
-const int PALLETTE_SIZE = 4;
-const int MAX_PALLETTE_OFFSET = PALLETTE_SIZE - 1;
-vec2 get_pallette_offset(uint material_index) {
-    switch (material_index) {
-        case 1: // Dirt
-            return vec2(0, 2);
-        case 2: // Grass
-            return vec2(0, 3);
-        case 3: // Stone
-            return vec2(1, 3);
-        case 4: // Wood
-            return vec2(1, 2);
-        case 5: // Leaves
-            return vec2(2, 2);
-        case 6: // Sand
-            return vec2(2, 3);
-        case 7: // Ore
-            return vec2(3, 3);
-        case 8: // Water 
-            return vec2(3, 2);
-        case 9: // Lava 
-            return vec2(0, 1);
-        case 10: // Planks 
-            return vec2(1, 1);
-        case 11: // DarkStone 
-            return vec2(2, 1);
-        case 12: // RedSand 
-            return vec2(3, 1);
-        case 13: // Cobblestone
-            return vec2(0, 0);
-        case 14: // Glass
-            return vec2(1, 0);
-        case 15: // Cactus
-            return vec2(2, 0);
-        default: // The last pallette element contains some invalid texture
-            return vec2(MAX_PALLETTE_OFFSET, 0);
-    }
-}
-
 const vec2 TILE_RESOLUTION = vec2(16, 16);
-const vec2 PALLETTE_RESOLUTION = PALLETTE_SIZE * TILE_RESOLUTION;
 vec2 nearest_pixel_filter(vec2 uv) {
-    vec2 pixel = uv * PALLETTE_RESOLUTION;
+    vec2 pixel = uv * TILE_RESOLUTION;
     pixel = floor(pixel) + 0.5;
 
-    return pixel / PALLETTE_RESOLUTION;
+    return pixel / TILE_RESOLUTION;
 }
 
-const float PALLETTE_TILE_SIZE = 1.0 / PALLETTE_SIZE;
-// Texture coords u, v are indexes into a single tile in the pallette
-// and are from range 0.0 to 1.0
-vec4 sample_pallette(uint material_index, float u, float v) {
-    vec2 pallette_offset = get_pallette_offset(material_index);
-
-    vec2 tile_base = PALLETTE_TILE_SIZE * pallette_offset;
-    vec2 tile_coords = PALLETTE_TILE_SIZE * vec2(u, v);
+// Texture coords u, v are from range 0.0 to 1.0 within the texture of the material,
+// the layer of the texture array is the material index
+vec4 sample_block_texture(uint material_index, float u, float v) {
+    vec2 tex_coords = nearest_pixel_filter(vec2(u, v));
 
-    vec2 tex_coords = tile_base + tile_coords;
-
-    tex_coords = nearest_pixel_filter(tex_coords);
+    return texture(block_textures, vec3(tex_coords, material_index));
+}
 
-    vec4 tex_color = texture(block_pallette, tex_coords);
+// Grass and leaves take the color of the biome, see model/biome.rs
+const uint GRASS_INDEX = 2u;
+const uint LEAVES_INDEX = 5u;
+vec3 apply_biome_tint(uint material_index, vec3 color) {
+    if (material_index == GRASS_INDEX || material_index == LEAVES_INDEX) {
+        return color * v_biome_tint;
+    }
 
-    return tex_color;
+    return color;
 }
 
 const float EPSILON = 0.0001;
-vec4 sample_blended_texture(float u, float v) {
+vec4 sample_blended_texture(mat4 weights, float u, float v) {
     vec3 color = vec3(0.0, 0.0, 0.0);
 
     for (int col = 0; col < 4; col++) {
         for (int row = 0; row < 4; row++) {
             uint material_index = col * 4 + row;
-            float weight = blend_weights[col][row];
+            float weight = weights[col][row];
 
             if (weight > EPSILON) {
-                vec3 texture_color = sample_pallette(material_index, u, v).rgb;
-                color += weight * texture_color;
+                vec3 texture_color = sample_block_texture(material_index, u, v).rgb;
+                color += weight * apply_biome_tint(material_index, texture_color);
             }
         }
     }
@@ -105,6 +91,55 @@ vec4 sample_blended_texture(float u, float v) {
     return vec4(color, 1.0);
 }
 
+// Integer hash with good avalanche, see "Hash Functions for GPU Rendering" (Jarzynski, Olano)
+uint pcg_hash(uint value) {
+    uint state = value * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Random number from 0.0 to 1.0 for the texel of the terrain containing the point,
+// the same for a given seed no matter where the camera is
+float texel_noise(vec3 world_position) {
+    uvec3 texel = uvec3(ivec3(floor(world_position * TILE_RESOLUTION.x)));
+    uint hash = pcg_hash(texel.x ^ pcg_hash(texel.y ^ pcg_hash(texel.z ^ dither_seed)));
+
+    return float(hash) / 4294967295.0;
+}
+
+// Higher values favor the dominant material more, which narrows the dithered band
+const float DITHER_CONTRAST = 2.0;
+// Instead of mixing the textures every texel shows a single material,
+// picked at random with a probability given by the sharpened blend weights
+mat4 dither_weights(vec3 world_position, vec3 normal) {
+    // Half a texel below the surface, so texels of axis aligned faces don't flicker between two cells
+    vec3 texel_position = world_position - normal * (0.5 / TILE_RESOLUTION.x);
+
+    float total = 0.0;
+    for (int col = 0; col < 4; col++) {
+        for (int row = 0; row < 4; row++) {
+            total += pow(blend_weights[col][row], DITHER_CONTRAST);
+        }
+    }
+
+    float threshold = texel_noise(texel_position) * total;
+    float cumulative = 0.0;
+    mat4 weights = mat4(0.0);
+    for (int col = 0; col < 4; col++) {
+        for (int row = 0; row < 4; row++) {
+            float weight = pow(blend_weights[col][row], DITHER_CONTRAST);
+            cumulative += weight;
+
+            if (weight > EPSILON && cumulative >= threshold) {
+                weights[col][row] = 1.0;
+                return weights;
+            }
+        }
+    }
+
+    return blend_weights;
+}
+
 // With increasing value of AXIS_BLEND_SMOOTHNESS
 // the length of the normal vector gets smaller as well as the sum of the coefficients
 // This makes surfaces that are at an angle look dark regardless of lighting 
@@ -146,6 +181,11 @@ vec4 assemble_color(vec3 world_position, vec3 normal) {
     float y = fract(world_position.y);
     float z = fract(world_position.z);
 
+    mat4 weights = blend_weights;
+    if (material_dithering) {
+        weights = dither_weights(world_position, normal);
+    }
+
     vec3 projection_coefficients = get_projection_coefficients(normal);
     float alpha = projection_coefficients.x;
     float beta = projection_coefficients.y;
@@ -156,9 +196,9 @@ vec4 assemble_color(vec3 world_position, vec3 normal) {
     // 
     // alpha, beta, gamma correspond to the x,y,z coordinates of the normal respectively
     // each coefficient controls the plane perpendicular to its axis 
-    vec4 texture_color = gamma * sample_blended_texture(x, y)
-        + beta * sample_blended_texture(x, z)
-        + alpha * sample_blended_texture(y, z);
+    vec4 texture_color = gamma * sample_blended_texture(weights, x, y)
+        + beta * sample_blended_texture(weights, x, z)
+        + alpha * sample_blended_texture(weights, y, z);
 
     // Visualize the darkening caused by the arches coefficients with p > 1
     //    float xx = 1.0 - (alpha + beta + gamma);
@@ -169,10 +209,62 @@ vec4 assemble_color(vec3 world_position, vec3 normal) {
     return vec4(texture_color.rgb, 1.0);
 }
 
+// Share of the sun reaching the fragment, filtered over the 3x3 texels around it
+float sample_shadow(sampler2DShadow shadow_map, mat4 shadow_matrix) {
+    vec4 light_position = shadow_matrix * vec4(fragment_position, 1.0);
+    vec3 coords = light_position.xyz / light_position.w * 0.5 + 0.5;
+    if (any(lessThan(coords, vec3(0.0))) || any(greaterThan(coords, vec3(1.0)))) {
+        return 1.0;
+    }
+
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += textureOffset(shadow_map, coords, ivec2(x, y));
+        }
+    }
+    return lit / 9.0;
+}
+
+float sun_visibility() {
+    if (!shadows) {
+        return 1.0;
+    }
+
+    float distance = length(fragment_position - camera_position);
+    if (distance < cascade_ends.x) {
+        return sample_shadow(shadow_map_0, shadow_matrix_0);
+    }
+    if (distance < cascade_ends.y) {
+        return sample_shadow(shadow_map_1, shadow_matrix_1);
+    }
+    if (distance < cascade_ends.z) {
+        return sample_shadow(shadow_map_2, shadow_matrix_2);
+    }
+    return 1.0;
+}
+
+#include "light_visualization.glsl"
+
+// Exponential fog over the distance to the camera. Where neither the sky nor block light reach
+// the cave fog takes over and fades to darkness, the surface fog fades to the background.
+const vec3 SURFACE_FOG_COLOR = vec3(1.0, 1.0, 1.0);
+const vec3 CAVE_FOG_COLOR = vec3(0.0, 0.0, 0.0);
+vec3 apply_fog(vec3 color, float block_light, float sky_visibility) {
+    float distance = length(fragment_position - camera_position);
+    float underground = (1.0 - sky_visibility) * (1.0 - block_light);
+
+    float surface_fog = 1.0 - exp(-surface_fog_density * distance);
+    float cave_fog = 1.0 - exp(-cave_fog_density * distance);
+
+    color = mix(color, SURFACE_FOG_COLOR, surface_fog * (1.0 - underground));
+    return mix(color, CAVE_FOG_COLOR, cave_fog * underground);
+}
+
 void main() {
     vec3 sunlight_dir = normalize(sun_position - fragment_position);
     float sun_factor = clamp(dot(v_normal, sunlight_dir), 0.0, 1.0);
-    vec3 sunlight = sun_factor * sun_color;
+    vec3 sunlight = sun_factor * sun_color * sun_visibility();
 
     vec3 skylight_dir = vec3(0.0, 1.0, 0.0); // Light coming directly from above
     float sky_factor = 0.5 + 0.5 * v_normal.y;
@@ -182,7 +274,21 @@ void main() {
     float indirect_coefficient = clamp(dot(v_normal, indirect_light_dir), 0.0, 1.0);
     vec3 indirect = indirect_coefficient * indirect_color;
 
-    vec3 lighting = sunlight + skylight + indirect;
+    // Creases and cavities see less of the sky and of the light bounced around them,
+    // the direct sunlight is left to the shadows
+    vec3 lighting = sunlight + v_ambient_occlusion * (skylight + indirect);
+    float sky_visibility = v_light.y;
+    if (baked_lighting) {
+        // Same rig with the shadows and sky occlusion of the terrain, see model/lightmap.rs
+        vec3 baked = texture(lightmap, v_lightmap_uv).rgb;
+
+        // The share of the rig that reaches the surface tells how enclosed it is
+        vec3 luminance = vec3(0.2126, 0.7152, 0.0722);
+        float occlusion = dot(baked, luminance) / max(dot(lighting, luminance), EPSILON);
+        sky_visibility = min(sky_visibility, clamp(occlusion, 0.0, 1.0));
+
+        lighting = baked;
+    }
 
     vec4 texture_color = assemble_color(fragment_position, v_normal);
     vec3 diffuse_color = texture_color.rgb;
@@ -191,6 +297,8 @@ void main() {
     // diffuse_color = vec3(0.5, 0.5, 0.5);
 
     vec3 color = lighting * diffuse_color;
+    color = apply_fog(color, v_light.x, sky_visibility);
+    color = visualize_light(color, v_light);
 
     // Debug normals
     // color = v_normal;
@@ -3,17 +3,24 @@
 in vec3 position;
 in vec3 normal;
 in mat4 vertex_material_weights;
+in vec2 light;
+in vec2 lightmap_uv;
+in vec3 biome_tint;
+in float ambient_occlusion;
 //in vec4 blend_coefficients;
 //in vec4 blend_indices;
 
 uniform mat4 projection;
 uniform mat4 view;
 uniform mat4 model;
-uniform sampler2D block_pallette;
 
 out vec3 v_normal;
 out vec3 fragment_position;
 out mat4 blend_weights;
+out vec2 v_light;
+out vec2 v_lightmap_uv;
+out vec3 v_biome_tint;
+out float v_ambient_occlusion;
 //out vec4 blend_weights;
 //out vec4 blend_materials;
 //out vec4 fragment_color;
@@ -34,10 +41,15 @@ vec3 get_material_color(uint material_index) {
 }
 
 void main() {  
-    v_normal = normalize(normal);
+    // The model matrix may scale y, normals are transformed by its inverse transpose
+    v_normal = normalize(mat3(transpose(inverse(model))) * normal);
     fragment_position = vec3(model * vec4(position, 1.0));
     //fragment_color = assemble_color(fragment_position, v_normal);
     blend_weights = vertex_material_weights;
+    v_light = light;
+    v_lightmap_uv = lightmap_uv;
+    v_biome_tint = biome_tint;
+    v_ambient_occlusion = ambient_occlusion;
 
     gl_Position = projection * view * model * vec4(position, 1.);
 }
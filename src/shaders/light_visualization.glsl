@@ -0,0 +1,24 @@
+// Shared by the world shaders, expanded in place of its #include, see
+// infrastructure/render_fragment.rs. Expects the light_visualization and color_ramp uniforms.
+const uint LIGHT_OFF = 0u;
+const uint LIGHT_TINT = 1u;
+const uint LIGHT_HEATMAP = 2u;
+const float MOB_SPAWN_LIGHT = 0.5 / 15.0; // only block light 0 allows spawning
+vec3 visualize_light(vec3 color, vec2 light) {
+    float block_light = light.x;
+    float sky_light = light.y;
+
+    switch (light_visualization) {
+        case LIGHT_TINT:
+            return color * (0.1 + 0.9 * max(block_light, sky_light));
+        case LIGHT_HEATMAP:
+            // Dark enough for mobs is marked in black, it stands out with any ramp
+            if (block_light < MOB_SPAWN_LIGHT) {
+                return mix(color, vec3(0.0), 0.6);
+            }
+            vec3 heat = texture(color_ramp, vec2(block_light, 0.5)).rgb;
+            return mix(color, heat, 0.6);
+        default:
+            return color;
+    }
+}
@@ -0,0 +1,23 @@
+#version 400
+
+// Weighted blended transparency accumulated by discrete_fs.glsl
+uniform sampler2D accumulation;
+uniform sampler2D weights;
+
+in vec2 texture_coordinates;
+
+out vec4 color;
+
+void main() {
+    vec4 accumulated = texture(accumulation, texture_coordinates);
+    float weight = texture(weights, texture_coordinates).r;
+
+    // Alpha holds how much of the scene still shows through
+    float transparency = accumulated.a;
+    if (transparency >= 1.0) {
+        discard;
+    }
+
+    vec3 average_color = accumulated.rgb / max(weight, 1e-5);
+    color = vec4(average_color, transparency);
+}
@@ -0,0 +1,144 @@
+#version 400
+
+in vec2 ndc_position;
+
+uniform mat4 projection;
+uniform mat4 inverse_projection;
+uniform mat4 world_to_view;
+uniform mat4 view_to_world;
+uniform vec3 camera_position;
+uniform vec3 sun_position;
+
+uniform sampler3D density;
+uniform vec3 volume_origin;
+uniform vec3 volume_extent;
+uniform vec3 volume_resolution;
+uniform float surface_level;
+
+out vec4 fragment_color;
+
+vec3 sun_color = vec3(1.64, 1.27, 0.99);
+vec3 sky_color = vec3(0.16, 0.20, 0.28);
+vec3 indirect_color = vec3(0.4, 0.28, 0.20);
+vec3 terrain_color = vec3(0.431, 0.573, 0.631);
+
+const float STEP_SIZE = 0.25;
+const int MAX_STEPS = 1024;
+const int REFINEMENT_STEPS = 6;
+const float AO_DISTANCES[3] = float[](0.5, 1.0, 2.0);
+
+// Samples are stored at grid points, the first and last sample lie on the volume boundary
+float sample_density(vec3 world_position) {
+    vec3 local = (world_position - volume_origin) / volume_extent;
+    vec3 uvw = (local * (volume_resolution - 1.0) + 0.5) / volume_resolution;
+
+    return texture(density, uvw).r;
+}
+
+vec3 density_normal(vec3 world_position) {
+    const float h = 0.5;
+    vec3 gradient = vec3(
+        sample_density(world_position + vec3(h, 0, 0)) - sample_density(world_position - vec3(h, 0, 0)),
+        sample_density(world_position + vec3(0, h, 0)) - sample_density(world_position - vec3(0, h, 0)),
+        sample_density(world_position + vec3(0, 0, h)) - sample_density(world_position - vec3(0, 0, h)));
+
+    // Density grows towards the outside of the terrain
+    return normalize(gradient);
+}
+
+// Returns the distances along the ray where it enters and leaves the volume
+vec2 intersect_volume(vec3 origin, vec3 direction) {
+    vec3 inverse_direction = 1.0 / direction;
+    vec3 t_low = (volume_origin - origin) * inverse_direction;
+    vec3 t_high = (volume_origin + volume_extent - origin) * inverse_direction;
+
+    vec3 t_min = min(t_low, t_high);
+    vec3 t_max = max(t_low, t_high);
+
+    float t_enter = max(max(t_min.x, t_min.y), t_min.z);
+    float t_exit = min(min(t_max.x, t_max.y), t_max.z);
+
+    return vec2(max(t_enter, 0.0), t_exit);
+}
+
+// Samples taken along the normal that still end up inside or close to the terrain
+// mean the point is in a crevice, nearer samples weigh more
+float ambient_occlusion(vec3 position, vec3 normal) {
+    const float AO_FALLOFF = 0.15;
+
+    float occlusion = 0.0;
+    float total_weight = 0.0;
+    for (int i = 0; i < 3; i++) {
+        float weight = 1.0 / AO_DISTANCES[i];
+        float offset_density = sample_density(position + AO_DISTANCES[i] * normal);
+        occlusion += weight * (1.0 - smoothstep(surface_level, surface_level + AO_FALLOFF, offset_density));
+        total_weight += weight;
+    }
+
+    return 1.0 - occlusion / total_weight;
+}
+
+vec3 shade(vec3 position, vec3 normal) {
+    vec3 sunlight_dir = normalize(sun_position - position);
+    float sun_factor = clamp(dot(normal, sunlight_dir), 0.0, 1.0);
+    vec3 sunlight = sun_factor * sun_color;
+
+    float sky_factor = 0.5 + 0.5 * normal.y;
+    vec3 skylight = sky_factor * sky_color;
+
+    vec3 indirect_light_dir = normalize(vec3(-sunlight_dir.x, 0.0, -sunlight_dir.z));
+    float indirect_coefficient = clamp(dot(normal, indirect_light_dir), 0.0, 1.0);
+    vec3 indirect = indirect_coefficient * indirect_color;
+
+    float occlusion = ambient_occlusion(position, normal);
+
+    return (sunlight + occlusion * (skylight + indirect)) * terrain_color;
+}
+
+void main() {
+    vec4 view_direction = inverse_projection * vec4(ndc_position, 1.0, 1.0);
+    view_direction /= view_direction.w;
+    vec3 direction = normalize(mat3(view_to_world) * view_direction.xyz);
+
+    vec2 range = intersect_volume(camera_position, direction);
+    if (range.x >= range.y) {
+        discard;
+    }
+
+    // March until the ray gets inside the terrain, then refine the hit by bisection
+    float t_outside = range.x;
+    float t = range.x;
+    bool hit = false;
+    for (int i = 0; i < MAX_STEPS && t < range.y; i++) {
+        if (sample_density(camera_position + t * direction) < surface_level) {
+            hit = true;
+            break;
+        }
+
+        t_outside = t;
+        t += STEP_SIZE;
+    }
+
+    if (!hit) {
+        discard;
+    }
+
+    float t_inside = t;
+    for (int i = 0; i < REFINEMENT_STEPS; i++) {
+        float t_middle = 0.5 * (t_outside + t_inside);
+        if (sample_density(camera_position + t_middle * direction) < surface_level) {
+            t_inside = t_middle;
+        } else {
+            t_outside = t_middle;
+        }
+    }
+
+    vec3 hit_position = camera_position + t_inside * direction;
+    vec3 normal = density_normal(hit_position);
+
+    vec4 clip_position = projection * world_to_view * vec4(hit_position, 1.0);
+    gl_FragDepth = 0.5 * (clip_position.z / clip_position.w) + 0.5;
+
+    // The output is gamma corrected automatically - GL_FRAMEBUFFER_SRGB
+    fragment_color = vec4(shade(hit_position, normal), 1.0);
+}
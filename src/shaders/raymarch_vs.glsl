@@ -0,0 +1,10 @@
+#version 400
+
+in vec2 position;
+
+out vec2 ndc_position;
+
+void main() {
+    ndc_position = position;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
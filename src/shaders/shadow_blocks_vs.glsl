@@ -0,0 +1,15 @@
+#version 400
+
+in vec3 position;
+
+// instance data
+in vec3 offset;
+
+uniform mat4 light_matrix;
+uniform mat4 model;
+
+void main() {
+    // The cube has 0,0,0 in its center, the offset is the corner of the block
+    vec3 real_position = position + offset + vec3(0.5);
+    gl_Position = light_matrix * model * vec4(real_position, 1.);
+}
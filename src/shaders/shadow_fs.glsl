@@ -0,0 +1,5 @@
+#version 400
+
+// Only the depth is written, see infrastructure/shadow_map.rs
+void main() {
+}
@@ -0,0 +1,121 @@
+#version 400
+
+// One layer per block type, see infrastructure/texture/block_textures.rs
+uniform sampler2DArray block_textures;
+uniform vec3 sun_position;
+uniform uint light_visualization;
+// See infrastructure/color_ramp.rs
+uniform sampler2D color_ramp;
+
+// See infrastructure/translucency.rs
+uniform uint translucency;
+uniform float opacity;
+uniform vec3 camera_position;
+uniform float surface_fog_density;
+uniform float cave_fog_density;
+// Seconds since the start, scrolls the ripples
+uniform float time;
+
+in vec2 texture_uv;
+flat in uint v_texture_layer;
+in vec3 v_normal;
+in vec3 fragment_position;
+in vec2 v_light;
+
+layout(location = 0) out vec4 color;
+// Only bound while accumulating weighted blended transparency
+layout(location = 1) out vec4 oit_weight;
+
+vec3 sun_color = vec3(1.64, 1.27, 0.99);
+vec3 sky_color = vec3(0.16, 0.20, 0.28);
+// Reflected at grazing angles, the background of the surface fog
+vec3 horizon_color = vec3(0.75, 0.85, 1.0);
+
+const vec2 TILE_RESOLUTION = vec2(16, 16);
+vec2 nearest_pixel_filter(vec2 uv) {
+    vec2 pixel = uv * TILE_RESOLUTION;
+    pixel = floor(pixel) + 0.5;
+
+    return pixel / TILE_RESOLUTION;
+}
+
+// Two sets of waves crossing each other, in world units
+const float RIPPLE_STRENGTH = 0.08;
+const vec2 RIPPLE_FREQUENCY = vec2(1.3, 2.1);
+const vec2 RIPPLE_SPEED = vec2(0.6, 0.9);
+vec3 perturb_normal(vec3 normal) {
+    vec2 p = fragment_position.xz;
+    float a = RIPPLE_FREQUENCY.x * (p.x + p.y * 0.4) + RIPPLE_SPEED.x * time;
+    float b = RIPPLE_FREQUENCY.y * (p.y - p.x * 0.3) - RIPPLE_SPEED.y * time;
+    vec2 slope = RIPPLE_STRENGTH * vec2(cos(a) - 0.3 * sin(b), sin(a) * 0.3 + cos(b));
+
+    // Only the faces looking up are rippled, the sides of the blocks stay flat
+    float up = max(normal.y, 0.0);
+    return normalize(normal + up * vec3(slope.x, 0.0, slope.y));
+}
+
+// Schlick's approximation with the reflectance of water looked at head on
+const float WATER_REFLECTANCE = 0.02;
+float fresnel(vec3 normal, vec3 view_dir) {
+    float cos_theta = clamp(dot(normal, view_dir), 0.0, 1.0);
+    return WATER_REFLECTANCE + (1.0 - WATER_REFLECTANCE) * pow(1.0 - cos_theta, 5.0);
+}
+
+#include "light_visualization.glsl"
+
+// Same as in discrete_fs.glsl
+const vec3 SURFACE_FOG_COLOR = vec3(1.0, 1.0, 1.0);
+const vec3 CAVE_FOG_COLOR = vec3(0.0, 0.0, 0.0);
+vec3 apply_fog(vec3 color, float block_light, float sky_visibility) {
+    float distance = length(fragment_position - camera_position);
+    float underground = (1.0 - sky_visibility) * (1.0 - block_light);
+
+    float surface_fog = 1.0 - exp(-surface_fog_density * distance);
+    float cave_fog = 1.0 - exp(-cave_fog_density * distance);
+
+    color = mix(color, SURFACE_FOG_COLOR, surface_fog * (1.0 - underground));
+    return mix(color, CAVE_FOG_COLOR, cave_fog * underground);
+}
+
+const uint TRANSLUCENCY_SORTED = 1u;
+const uint TRANSLUCENCY_WEIGHTED = 2u;
+float oit_weight_at(float distance, float alpha) {
+    return alpha * clamp(10.0 / (1e-5 + pow(distance / 20.0, 3.0)), 1e-2, 3e3);
+}
+
+void main() {
+    vec3 normal = perturb_normal(normalize(v_normal));
+    vec3 view_dir = normalize(camera_position - fragment_position);
+    vec3 sunlight_dir = normalize(sun_position - fragment_position);
+
+    float sun_factor = clamp(dot(normal, sunlight_dir), 0.0, 1.0);
+    float sky_factor = 0.5 + 0.5 * normal.y;
+    vec3 lighting = sun_factor * sun_color + sky_factor * sky_color;
+
+    vec2 tex_coords = nearest_pixel_filter(texture_uv);
+    vec3 water_color = lighting * texture(block_textures, vec3(tex_coords, v_texture_layer)).rgb;
+
+    // The sky is reflected only where the sky reaches
+    float reflectance = fresnel(normal, view_dir) * v_light.y;
+    vec3 half_dir = normalize(sunlight_dir + view_dir);
+    vec3 specular = sun_color * pow(max(dot(normal, half_dir), 0.0), 64.0);
+    vec3 frag_color = mix(water_color, horizon_color, reflectance) + specular * v_light.y;
+
+    frag_color = apply_fog(frag_color, v_light.x, v_light.y);
+    frag_color = visualize_light(frag_color, v_light);
+
+    // Water looked at from the side reflects more and lets less of the bottom through
+    float alpha = mix(opacity, 1.0, reflectance);
+
+    oit_weight = vec4(0.0);
+    switch (translucency) {
+        case TRANSLUCENCY_WEIGHTED: {
+            float weight = oit_weight_at(length(fragment_position - camera_position), alpha);
+            color = vec4(frag_color * weight, alpha);
+            oit_weight = vec4(weight);
+            break;
+        }
+        default:
+            color = vec4(frag_color, alpha);
+    }
+}
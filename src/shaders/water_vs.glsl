@@ -0,0 +1,32 @@
+#version 400
+
+in vec3 position;
+in vec3 normal;
+in vec2 texture_coordinates;
+
+// instance data, the rigid water blocks
+in vec3 offset;
+in uint texture_layer;
+in vec2 light;
+
+uniform mat4 projection;
+uniform mat4 view;
+uniform mat4 model;
+
+out vec3 v_normal;
+out vec2 texture_uv;
+flat out uint v_texture_layer;
+out vec3 fragment_position;
+out vec2 v_light;
+
+void main() {
+    v_normal = normalize(mat3(transpose(inverse(model))) * normal);
+    v_light = light;
+    texture_uv = texture_coordinates;
+    v_texture_layer = texture_layer;
+
+    // The cube has 0,0,0 in its center, the offset is the corner of the block
+    vec3 real_position = position + offset + vec3(0.5);
+    fragment_position = vec3(model * vec4(real_position, 1.));
+    gl_Position = projection * view * model * vec4(real_position, 1.);
+}
@@ -0,0 +1,32 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often the work still running is checked while waiting for it
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// Deadline shared by all the work waited for when the application closes.
+// Work still running past it is left behind and ends with the process.
+pub struct Shutdown {
+    deadline: Instant,
+}
+
+impl Shutdown {
+    pub fn new(timeout: Duration) -> Self {
+        Shutdown {
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    // Waits until *is_finished* returns true, returns false when the deadline passed first
+    pub fn wait(&self, name: &str, is_finished: impl Fn() -> bool) -> bool {
+        while !is_finished() {
+            if Instant::now() >= self.deadline {
+                println!("[INFO] Not waiting for {name} any longer");
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        true
+    }
+}
@@ -0,0 +1,81 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::camera_path::CameraPath;
+use crate::config;
+use crate::model::discrete::World;
+use crate::model::{Position, Real};
+
+// How much the camera looks down at the terrain while touring
+const TOUR_PITCH: Real = -0.35;
+
+// Camera flying automatically over the most interesting chunks of the loaded world
+pub struct Tour {
+    path: CameraPath,
+    distance: Real,
+}
+
+impl Tour {
+    // Picks the chunks with the most relief and curvature and visits them,
+    // always flying to the closest unvisited one
+    pub fn plan(world: &World, start: Position) -> Option<Self> {
+        let mut metrics = world.get_chunk_height_metrics();
+        metrics.sort_by(|a, b| b.interest().total_cmp(&a.interest()));
+        metrics.truncate(config::TOUR_WAYPOINTS);
+
+        let mut unvisited = metrics
+            .iter()
+            .map(|chunk| {
+                let y = chunk.max_height as Real + config::TOUR_ALTITUDE;
+                Position::new(chunk.center.x, y, chunk.center.y)
+            })
+            .collect::<Vec<_>>();
+
+        let mut waypoints = vec![start];
+        while !unvisited.is_empty() {
+            let current = *waypoints.last().unwrap();
+            let distance_to = |point: &Position| (*point - current).magnitude2();
+
+            let closest = unvisited
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| distance_to(a).total_cmp(&distance_to(b)))
+                .map(|(index, _)| index)
+                .unwrap();
+            waypoints.push(unvisited.swap_remove(closest));
+        }
+
+        let visited_chunks = waypoints.len() - 1;
+        let path = CameraPath::new(waypoints)?;
+        println!(
+            "[INFO] Touring {} chunks, {:.0} blocks",
+            visited_chunks,
+            path.length()
+        );
+
+        Some(Tour {
+            path,
+            distance: 0.0,
+        })
+    }
+
+    // Moves along the path, returns the new camera position and look direction
+    // or None once the end of the path is reached
+    pub fn advance(&mut self, delta_time: Real) -> Option<(Position, Vector3<Real>)> {
+        if self.distance >= self.path.length() {
+            return None;
+        }
+
+        self.distance += config::TOUR_SPEED * delta_time;
+        let (position, tangent) = self.path.sample(self.distance);
+
+        // Look where the camera is heading, but keep the horizon level
+        let heading = Vector3::new(tangent.x, 0.0, tangent.z);
+        let heading = match heading.magnitude2() > 0.0 {
+            true => heading.normalize(),
+            false => Vector3::unit_x(),
+        };
+        let direction = (heading + Vector3::new(0.0, TOUR_PITCH, 0.0)).normalize();
+
+        Some((position, direction))
+    }
+}
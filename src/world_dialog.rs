@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::Path;
+
+use crate::config;
+use crate::minecraft::level::StartLocation;
+use crate::model::Position;
+
+// State of the dialog shown before a world is loaded, and when switching to another one.
+//
+// First a world is picked from the recently opened ones or browsed to,
+// then the user picks where the camera starts if the save has a level.dat.
+pub struct WorldDialog {
+    // Most recently opened first
+    pub recent_worlds: Vec<String>,
+
+    pub start_locations: Vec<StartLocation>,
+
+    // Why the last picked world could not be opened
+    pub error: Option<String>,
+
+    // Requests from the UI, handled before the next frame
+    pub chosen_world: Option<String>,
+    pub chosen_start: Option<Position>,
+    pub browse_folder_requested: bool,
+    pub browse_backup_requested: bool,
+    // Go back to the loaded world without switching
+    pub cancel_requested: bool,
+}
+
+impl WorldDialog {
+    pub fn new() -> Self {
+        WorldDialog {
+            recent_worlds: load_recent_worlds(),
+            start_locations: Vec::new(),
+            error: None,
+            chosen_world: None,
+            chosen_start: None,
+            browse_folder_requested: false,
+            browse_backup_requested: false,
+            cancel_requested: false,
+        }
+    }
+
+    // Moves *world_path* to the top of the recent worlds and saves the list
+    pub fn remember_world(&mut self, world_path: &str) {
+        let world_path = fs::canonicalize(world_path)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| world_path.to_owned());
+
+        self.recent_worlds.retain(|path| *path != world_path);
+        self.recent_worlds.insert(0, world_path);
+        self.recent_worlds.truncate(config::RECENT_WORLDS_COUNT);
+
+        let contents = self.recent_worlds.join("\n");
+        if let Err(error) = fs::write(config::RECENT_WORLDS_FILE, contents) {
+            eprintln!(
+                "[ERROR] Failed to save the recent worlds to {} - {}",
+                config::RECENT_WORLDS_FILE,
+                error
+            );
+        }
+    }
+}
+
+// One path per line, the file doesn't exist until a world is opened for the first time
+fn load_recent_worlds() -> Vec<String> {
+    let Ok(contents) = fs::read_to_string(config::RECENT_WORLDS_FILE) else {
+        return Vec::new();
+    };
+
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .filter(|path| Path::new(path).exists())
+        .map(str::to_owned)
+        .collect()
+}
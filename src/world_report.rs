@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use crate::block_composition::MaterialCounts;
+use crate::config;
+use crate::minecraft::{self, BLOCKS_IN_CHUNK};
+use crate::model::chunk::Chunk;
+use crate::model::common::{ALL_BLOCK_TYPES, BLOCK_TYPES};
+use crate::model::polygonize::PolygonizationOptions;
+use crate::world_scanner::{log_progress, ScanOutput, ScanSettings, WorldScanner};
+
+type BlockPosition = (i32, i32, i32);
+
+// Summary of every generated chunk of the world, not just the loaded ones
+#[derive(Clone)]
+pub struct WorldReport {
+    pub regions: usize,
+    pub chunks: usize,
+    // Lowest and highest surface block of any column, None for a world without blocks
+    pub lowest_surface: Option<isize>,
+    pub highest_surface: Option<isize>,
+    pub material_counts: MaterialCounts,
+    // Connected groups of rigid blocks, a group crossing a chunk border is counted in both chunks
+    pub rigid_structures: usize,
+    pub cell_size: f64,
+    pub estimated_triangles: usize,
+}
+
+impl WorldReport {
+    fn empty(cell_size: f64) -> Self {
+        WorldReport {
+            regions: 0,
+            chunks: 0,
+            lowest_surface: None,
+            highest_surface: None,
+            material_counts: [0; BLOCK_TYPES],
+            rigid_structures: 0,
+            cell_size,
+            estimated_triangles: 0,
+        }
+    }
+
+    fn merge(mut self, other: WorldReport) -> Self {
+        self.regions += other.regions;
+        self.chunks += other.chunks;
+        self.lowest_surface = min_option(self.lowest_surface, other.lowest_surface);
+        self.highest_surface = self.highest_surface.max(other.highest_surface);
+        for (total, count) in self.material_counts.iter_mut().zip(other.material_counts) {
+            *total += count;
+        }
+        self.rigid_structures += other.rigid_structures;
+        self.estimated_triangles += other.estimated_triangles;
+
+        self
+    }
+
+    fn add_chunk(&mut self, chunk: &Chunk, options: &PolygonizationOptions) {
+        self.chunks += 1;
+
+        for (total, count) in self.material_counts.iter_mut().zip(chunk.count_materials()) {
+            *total += count;
+        }
+
+        for z in 0..BLOCKS_IN_CHUNK {
+            for x in 0..BLOCKS_IN_CHUNK {
+                let surface = chunk.get_surface_height(x, z);
+                self.lowest_surface = min_option(self.lowest_surface, surface);
+                self.highest_surface = self.highest_surface.max(surface);
+            }
+        }
+
+        self.rigid_structures += count_rigid_structures(chunk);
+        self.estimated_triangles += estimate_triangles(chunk, options);
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let materials = ALL_BLOCK_TYPES
+            .into_iter()
+            .filter(|material| self.material_counts[*material as usize] > 0)
+            .map(|material| {
+                let name = format!("{material:?}");
+                (name, json!(self.material_counts[material as usize]))
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        json!({
+            "regions": self.regions,
+            "chunks": self.chunks,
+            "lowest_surface": self.lowest_surface,
+            "highest_surface": self.highest_surface,
+            "block_composition": materials,
+            "rigid_structures": self.rigid_structures,
+            "smooth_mesh": {
+                "cell_size": self.cell_size,
+                "estimated_triangles": self.estimated_triangles,
+            },
+        })
+    }
+}
+
+// Reads every region of the world in the background, the regions are scanned in parallel
+// and a cancelled scan resumes from its checkpoint when started again with the same options
+pub fn start_world_scan(
+    options: PolygonizationOptions,
+    workers: usize,
+) -> WorldScanner<WorldReport> {
+    let regions = minecraft::region_positions();
+    println!("[INFO] World scan of {} regions started", regions.len());
+
+    let settings = ScanSettings {
+        workers,
+        checkpoint: Some(PathBuf::from(config::WORLD_SCAN_CHECKPOINT)),
+        signature: format!("{} {options:?}", minecraft::world_folder().display()),
+        on_progress: Some(log_progress("regions")),
+    };
+
+    let cell_size = options.marching_cubes_cell_size;
+    let visit = move |&(region_x, region_z): &(i32, i32)| {
+        let mut report = WorldReport::empty(cell_size);
+        report.regions = 1;
+        minecraft::visit_region_chunks(region_x, region_z, |chunk| {
+            report.add_chunk(&chunk, &options)
+        });
+
+        Ok(report)
+    };
+    let finish = move |reports: Vec<((i32, i32), WorldReport)>| {
+        let total = reports
+            .into_iter()
+            .fold(WorldReport::empty(cell_size), |total, (_, report)| {
+                total.merge(report)
+            });
+        Ok(total)
+    };
+
+    WorldScanner::start(regions, settings, visit, finish)
+}
+
+impl ScanOutput for WorldReport {
+    fn to_json(&self) -> serde_json::Value {
+        WorldReport::to_json(self)
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        let count = |value: &serde_json::Value| value.as_u64().map(|count| count as usize);
+        let surface = |value: &serde_json::Value| match value {
+            serde_json::Value::Null => Some(None),
+            _ => value.as_i64().map(|height| Some(height as isize)),
+        };
+
+        let mut material_counts = [0; BLOCK_TYPES];
+        for (name, material_count) in value["block_composition"].as_object()? {
+            let material = ALL_BLOCK_TYPES
+                .into_iter()
+                .find(|material| format!("{material:?}") == *name)?;
+            material_counts[material as usize] = count(material_count)?;
+        }
+
+        Some(WorldReport {
+            regions: count(&value["regions"])?,
+            chunks: count(&value["chunks"])?,
+            lowest_surface: surface(&value["lowest_surface"])?,
+            highest_surface: surface(&value["highest_surface"])?,
+            material_counts,
+            rigid_structures: count(&value["rigid_structures"])?,
+            cell_size: value["smooth_mesh"]["cell_size"].as_f64()?,
+            estimated_triangles: count(&value["smooth_mesh"]["estimated_triangles"])?,
+        })
+    }
+}
+
+fn min_option(a: Option<isize>, b: Option<isize>) -> Option<isize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        _ => a.or(b),
+    }
+}
+
+// Groups of rigid blocks touching by a face
+fn count_rigid_structures(chunk: &Chunk) -> usize {
+    let mut unvisited = chunk
+        .get_rigid_block_data()
+        .iter()
+        .map(|block| {
+            let [x, y, z] = block.offset.map(|coordinate| coordinate.floor() as i32);
+            (x, y, z)
+        })
+        .collect::<HashSet<BlockPosition>>();
+
+    let mut structures = 0;
+    while let Some(&start) = unvisited.iter().next() {
+        unvisited.remove(&start);
+        structures += 1;
+
+        let mut stack = vec![start];
+        while let Some((x, y, z)) = stack.pop() {
+            let neighbours = [
+                (x - 1, y, z),
+                (x + 1, y, z),
+                (x, y - 1, z),
+                (x, y + 1, z),
+                (x, y, z - 1),
+                (x, y, z + 1),
+            ];
+            for neighbour in neighbours {
+                if unvisited.remove(&neighbour) {
+                    stack.push(neighbour);
+                }
+            }
+        }
+    }
+
+    structures
+}
+
+// The smooth surface roughly follows the exposed faces of the columns,
+// marching cubes makes about two triangles per cell of that area
+fn estimate_triangles(chunk: &Chunk, options: &PolygonizationOptions) -> usize {
+    let y_low = options.y_low_limit.floor() as isize;
+    let y_high = (options.y_low_limit + options.y_size).ceil() as isize;
+    let height = |x: usize, z: usize| {
+        chunk
+            .get_surface_height(x, z)
+            .map_or(y_low, |surface| (surface + 1).clamp(y_low, y_high))
+    };
+
+    let mut area = 0;
+    for z in 0..BLOCKS_IN_CHUNK {
+        for x in 0..BLOCKS_IN_CHUNK {
+            let column = height(x, z);
+            if column > y_low && column < y_high {
+                area += 1;
+            }
+            if x + 1 < BLOCKS_IN_CHUNK {
+                area += (column - height(x + 1, z)).unsigned_abs();
+            }
+            if z + 1 < BLOCKS_IN_CHUNK {
+                area += (column - height(x, z + 1)).unsigned_abs();
+            }
+        }
+    }
+
+    let cells_per_block =
+        1.0 / (options.marching_cubes_cell_size * options.marching_cubes_cell_size);
+    (2.0 * area as f64 * cells_per_block).round() as usize
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rayon::prelude::*;
+use serde_json::{json, Value};
+
+use crate::infrastructure::jobs::{self, JobContext, JobHandle, Priority};
+
+// How often paused workers look whether they may continue
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// A piece of the world visited by one worker, such as a region or a map tile
+pub trait ScanUnit: Send + Sync + 'static {
+    // Identifies the unit in the checkpoint
+    fn key(&self) -> String;
+}
+
+impl ScanUnit for () {
+    fn key(&self) -> String {
+        String::from("()")
+    }
+}
+
+impl ScanUnit for (i32, i32) {
+    fn key(&self) -> String {
+        format!("{}.{}", self.0, self.1)
+    }
+}
+
+// What a visit produced, kept in the checkpoint so a resumed scan doesn't visit the unit again
+pub trait ScanOutput: Send + Sized + 'static {
+    fn to_json(&self) -> Value;
+    fn from_json(value: &Value) -> Option<Self>;
+}
+
+impl ScanOutput for () {
+    fn to_json(&self) -> Value {
+        Value::Null
+    }
+
+    fn from_json(_value: &Value) -> Option<Self> {
+        Some(())
+    }
+}
+
+impl ScanOutput for Vec<(i32, i32)> {
+    fn to_json(&self) -> Value {
+        json!(self)
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        serde_json::from_value(value.clone()).ok()
+    }
+}
+
+// Called by the workers with the number of units done and of all units
+pub type ProgressCallback = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+pub struct ScanSettings {
+    // Threads visiting units at the same time, as many as there are cores for 0
+    pub workers: usize,
+    // File of the units visited so far, a scan without one starts over every time
+    pub checkpoint: Option<PathBuf>,
+    // Everything the outputs depend on, a checkpoint written with another signature is discarded
+    pub signature: String,
+    pub on_progress: Option<ProgressCallback>,
+}
+
+pub enum ScanEnd<R> {
+    Finished(R),
+    // The checkpoint keeps the units visited before the scan was cancelled
+    Cancelled { done: usize, total: usize },
+    Failed(String),
+}
+
+struct ScanControl {
+    done: AtomicUsize,
+    paused: AtomicBool,
+}
+
+// Visits units of the world in parallel on a pool of its own, in a background job.
+//
+// Every visited unit is appended to the checkpoint as a line of JSON with its key and output.
+// A scan started with the checkpoint of an unfinished one takes the outputs from it
+// and only visits the rest. Once all units are visited, *finish* combines the outputs
+// in the order of the units and the checkpoint is removed.
+//
+// Pausing and cancelling are seen by the workers before they take the next unit,
+// the units being visited are finished first.
+pub struct WorldScanner<R> {
+    control: Arc<ScanControl>,
+    total: usize,
+    job: JobHandle<ScanEnd<R>>,
+}
+
+impl<R: Send + 'static> WorldScanner<R> {
+    pub fn start<U: ScanUnit, T: ScanOutput>(
+        units: Vec<U>,
+        settings: ScanSettings,
+        visit: impl Fn(&U) -> Result<T, String> + Send + Sync + 'static,
+        finish: impl FnOnce(Vec<(U, T)>) -> Result<R, String> + Send + 'static,
+    ) -> Self {
+        let total = units.len();
+        let control = Arc::new(ScanControl {
+            done: AtomicUsize::new(0),
+            paused: AtomicBool::new(false),
+        });
+        let shared = control.clone();
+
+        let job = jobs::spawn("world scan", Priority::Low, 0, move |context| {
+            let outputs = match scan(units, &settings, &shared, context, visit) {
+                Ok(Some(outputs)) => outputs,
+                Ok(None) => {
+                    let done = shared.done.load(Ordering::Relaxed);
+                    return ScanEnd::Cancelled { done, total };
+                }
+                Err(error) => return ScanEnd::Failed(error),
+            };
+
+            match finish(outputs) {
+                Ok(result) => {
+                    if let Some(path) = &settings.checkpoint {
+                        let _ = fs::remove_file(path);
+                    }
+                    ScanEnd::Finished(result)
+                }
+                Err(error) => ScanEnd::Failed(error),
+            }
+        });
+
+        WorldScanner {
+            control,
+            total,
+            job,
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.control.done.load(Ordering::Relaxed) as f32 / self.total.max(1) as f32
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.control.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.job.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.job.is_finished()
+    }
+
+    pub fn join(self) -> ScanEnd<R> {
+        self.job.join().expect("The scan job panicked")
+    }
+}
+
+// Prints the progress every tenth of the units, *units* names them in the message
+pub fn log_progress(units: &'static str) -> ProgressCallback {
+    Box::new(move |done, total| {
+        if (done * 10 / total.max(1)) != ((done - 1) * 10 / total.max(1)) {
+            println!("[INFO] {done} of {total} {units} scanned");
+        }
+    })
+}
+
+// Outputs of all units in their order, None once the scan is cancelled
+fn scan<U: ScanUnit, T: ScanOutput>(
+    units: Vec<U>,
+    settings: &ScanSettings,
+    control: &ScanControl,
+    context: &JobContext,
+    visit: impl Fn(&U) -> Result<T, String> + Sync,
+) -> Result<Option<Vec<(U, T)>>, String> {
+    let total = units.len();
+    let mut checkpoint = None;
+    let mut resumed = HashMap::new();
+    if let Some(path) = &settings.checkpoint {
+        let (file, outputs) = open_checkpoint(path, &settings.signature)
+            .map_err(|error| format!("Failed to open the checkpoint - {error}"))?;
+        checkpoint = Some(Mutex::new(file));
+        resumed = outputs;
+    }
+
+    let resumed: Vec<Option<T>> = units
+        .iter()
+        .map(|unit| resumed.get(&unit.key()).and_then(T::from_json))
+        .collect();
+    let resumed_count = resumed.iter().flatten().count();
+    control.done.store(resumed_count, Ordering::Relaxed);
+    if resumed_count > 0 {
+        println!("[INFO] Resuming a scan, {resumed_count} of {total} units were already done");
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(settings.workers)
+        .build()
+        .map_err(|error| format!("Failed to start the scan workers - {error}"))?;
+
+    let outputs = pool.install(|| {
+        units
+            .into_par_iter()
+            .zip(resumed)
+            .map(|(unit, resumed)| {
+                if let Some(output) = resumed {
+                    return Ok(Some((unit, output)));
+                }
+
+                while control.paused.load(Ordering::Relaxed) && !context.is_cancelled() {
+                    thread::sleep(PAUSE_POLL_INTERVAL);
+                }
+                if context.is_cancelled() {
+                    return Ok(None);
+                }
+
+                let output = visit(&unit)?;
+                if let Some(file) = &checkpoint {
+                    let line = json!({ "key": unit.key(), "output": output.to_json() });
+                    writeln!(file.lock().unwrap(), "{line}")
+                        .map_err(|error| format!("Failed to write the checkpoint - {error}"))?;
+                }
+
+                let done = control.done.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Some(on_progress) = &settings.on_progress {
+                    on_progress(done, total);
+                }
+
+                Ok(Some((unit, output)))
+            })
+            .collect::<Result<Vec<_>, String>>()
+    })?;
+
+    Ok(outputs.into_iter().collect())
+}
+
+// Opens the checkpoint for appending together with the outputs already in it.
+// The first line holds the signature, the checkpoint is started over when it differs.
+fn open_checkpoint(
+    path: &Path,
+    signature: &str,
+) -> std::io::Result<(File, HashMap<String, Value>)> {
+    let mut outputs = HashMap::new();
+    let mut matches = false;
+
+    if let Ok(file) = File::open(path) {
+        let mut lines = BufReader::new(file).lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let header: Value = serde_json::from_str(&header).unwrap_or_default();
+        matches = header["signature"] == signature;
+
+        // Lines that don't parse, like one cut off when the application was closed, are skipped
+        // and their units are visited again
+        if matches {
+            for line in lines {
+                let Ok(entry) = serde_json::from_str::<Value>(&line?) else {
+                    continue;
+                };
+                if let (Some(key), Some(output)) = (entry["key"].as_str(), entry.get("output")) {
+                    outputs.insert(key.to_owned(), output.clone());
+                }
+            }
+        }
+    }
+
+    if matches {
+        let file = OpenOptions::new().append(true).open(path)?;
+        return Ok((file, outputs));
+    }
+
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", json!({ "signature": signature }))?;
+    Ok((file, outputs))
+}
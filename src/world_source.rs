@@ -0,0 +1,180 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+// Where the region files of the loaded world come from.
+//
+// Archives are unpacked into a temporary folder on startup, only the region files
+// of the overworld and the files with start locations are extracted.
+pub enum WorldSource {
+    Folder(PathBuf),
+    Zip(PathBuf),
+    Tar { path: PathBuf, gzipped: bool },
+}
+
+impl WorldSource {
+    pub fn from_path(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if file_name.ends_with(".zip") {
+            WorldSource::Zip(path)
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            WorldSource::Tar {
+                path,
+                gzipped: true,
+            }
+        } else if file_name.ends_with(".tar") {
+            WorldSource::Tar {
+                path,
+                gzipped: false,
+            }
+        } else {
+            WorldSource::Folder(path)
+        }
+    }
+
+    // Returns the folder containing the region files
+    pub fn open(&self) -> io::Result<PathBuf> {
+        match self {
+            WorldSource::Folder(path) => Ok(region_folder(path)),
+            WorldSource::Zip(path) => {
+                let destination = unpack_destination(path)?;
+                unpack_zip(path, &destination)?;
+                find_region_folder(&destination)
+            }
+            WorldSource::Tar { path, gzipped } => {
+                let destination = unpack_destination(path)?;
+                unpack_tar(path, *gzipped, &destination)?;
+                find_region_folder(&destination)
+            }
+        }
+    }
+}
+
+pub fn has_region_files(folder: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return false;
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .any(|entry| is_overworld_region_file(&entry.path()))
+}
+
+// A save folder keeps its region files in the region subfolder,
+// but the path may also point directly at a folder of region files
+fn region_folder(path: &Path) -> PathBuf {
+    let region = path.join("region");
+    match region.is_dir() {
+        true => region,
+        false => path.to_path_buf(),
+    }
+}
+
+// Region files of the nether and the end live in DIM-1/region and DIM1/region
+fn is_overworld_region_file(path: &Path) -> bool {
+    let is_region = path.extension().is_some_and(|extension| extension == "mca");
+    let in_dimension = path
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with("DIM"));
+
+    is_region && !in_dimension
+}
+
+// Besides the region files, level.dat and the player files are needed for the start locations
+fn is_extracted_file(path: &Path) -> bool {
+    let is_level = path.file_name().is_some_and(|name| name == "level.dat");
+    let is_player = path
+        .parent()
+        .and_then(Path::file_name)
+        .is_some_and(|folder| folder == "playerdata");
+
+    is_overworld_region_file(path) || is_level || is_player
+}
+
+fn unpack_destination(archive: &Path) -> io::Result<PathBuf> {
+    let archive_name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let destination = std::env::temp_dir().join(format!("dd-terrain-{archive_name}"));
+    fs::create_dir_all(&destination)?;
+
+    println!(
+        "[INFO] Unpacking {} into {}",
+        archive.display(),
+        destination.display()
+    );
+
+    Ok(destination)
+}
+
+fn unpack_zip(archive: &Path, destination: &Path) -> io::Result<()> {
+    let mut zip = zip::ZipArchive::new(BufReader::new(File::open(archive)?))?;
+
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        // Entries trying to escape the destination are skipped
+        let Some(relative_path) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        if !is_extracted_file(&relative_path) {
+            continue;
+        }
+
+        let target = destination.join(relative_path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(target)?)?;
+    }
+
+    Ok(())
+}
+
+fn unpack_tar(archive: &Path, gzipped: bool, destination: &Path) -> io::Result<()> {
+    let file = BufReader::new(File::open(archive)?);
+    let reader: Box<dyn Read> = match gzipped {
+        true => Box::new(GzDecoder::new(file)),
+        false => Box::new(file),
+    };
+
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !is_extracted_file(&entry.path()?) {
+            continue;
+        }
+
+        // unpack_in refuses paths that would end up outside the destination
+        entry.unpack_in(destination)?;
+    }
+
+    Ok(())
+}
+
+fn find_region_folder(root: &Path) -> io::Result<PathBuf> {
+    let mut folders = vec![root.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in fs::read_dir(&folder)? {
+            let path = entry?.path();
+            let relative_path = path.strip_prefix(root).unwrap_or(&path);
+            if path.is_dir() {
+                folders.push(path.clone());
+            } else if is_overworld_region_file(relative_path) {
+                return Ok(folder);
+            }
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "the archive does not contain any region files",
+    ))
+}